@@ -4,8 +4,12 @@ use clap::{crate_authors, crate_description, crate_name, App};
 use failure::ResultExt;
 use log::info;
 
+use edgelet_core::RuntimeSettings;
+
 #[cfg(feature = "runtime-docker")]
 use edgelet_docker::Settings;
+#[cfg(feature = "runtime-process")]
+use edgelet_process::Settings;
 
 use crate::error::{Error, ErrorKind, InitializeErrorReason};
 use crate::logging;
@@ -27,7 +31,14 @@ pub fn init() -> Result<Settings, Error> {
     info!("Starting Azure IoT Edge Module Runtime");
     info!("Version - {}", edgelet_core::version_with_source_version());
 
-    let settings = edgelet_docker::Settings::new()
-        .context(ErrorKind::Initialize(InitializeErrorReason::LoadSettings))?;
+    let settings =
+        Settings::new().context(ErrorKind::Initialize(InitializeErrorReason::LoadSettings))?;
+    logging::set_log_level(settings.log_level());
+    Ok(settings)
+}
+
+/// Loads the settings again, for a reload of the running daemon.
+pub fn load_settings() -> Result<Settings, Error> {
+    let settings = Settings::new().context(ErrorKind::ReloadSettings)?;
     Ok(settings)
 }
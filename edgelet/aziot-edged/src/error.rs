@@ -27,6 +27,9 @@ pub enum ErrorKind {
     #[fail(display = "The timer that checks the edge runtime status encountered an error.")]
     EdgeRuntimeStatusCheckerTimer,
 
+    #[fail(display = "The image garbage collection timer encountered an error.")]
+    ImageGarbageCollectorTimer,
+
     #[fail(display = "The daemon could not start up successfully: {}", _0)]
     Initialize(InitializeErrorReason),
 
@@ -39,6 +42,12 @@ pub enum ErrorKind {
     #[fail(display = "A module runtime error occurred.")]
     ModuleRuntime,
 
+    #[fail(display = "The daemon did not respond in time")]
+    Unresponsive,
+
+    #[fail(display = "The settings could not be reloaded")]
+    ReloadSettings,
+
     #[fail(display = "The reprovisioning operation failed")]
     ReprovisionFailure,
 
@@ -48,6 +57,9 @@ pub enum ErrorKind {
     #[fail(display = "The watchdog encountered an error")]
     Watchdog,
 
+    #[fail(display = "The timer that sends systemd watchdog keep-alives encountered an error.")]
+    WatchdogKeepAliveTimer,
+
     #[fail(display = "The workload service encountered an error")]
     WorkloadService,
 }
@@ -136,6 +148,7 @@ pub enum InitializeErrorReason {
     LoadSettings,
     ManagementService,
     ModuleRuntime,
+    ModuleSecrets,
     RemoveExistingModules,
     SaveProvisioning,
     StopExistingModules,
@@ -182,6 +195,8 @@ impl fmt::Display for InitializeErrorReason {
                 write!(f, "Could not initialize module runtime")
             }
 
+            InitializeErrorReason::ModuleSecrets => write!(f, "Could not seed module secrets"),
+
             InitializeErrorReason::RemoveExistingModules => {
                 write!(f, "Could not remove existing modules")
             }
@@ -0,0 +1,426 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+use std::time::Instant;
+
+use failure::Fail;
+use futures::future::{self, Either};
+use futures::{stream, Future, Stream};
+use log::{debug, info, warn, Level};
+use tokio::timer::Interval;
+
+use edgelet_core::{
+    DiskInfo, ImageGarbageCollection, ImageInfo, Module, ModuleImage, ModuleRegistry, ModuleRuntime,
+};
+use edgelet_utils::log_failure;
+
+use crate::error::{Error, ErrorKind};
+
+/// Periodically removes module images that no module uses any more.
+///
+/// Only images that a module has used while the collector was running are module images; other
+/// images on the host are never removed. An image is also only removed once it has been unused
+/// for a whole recurrence, so images that were just pulled or imported for a deployment that
+/// hasn't created its modules yet are kept.
+pub struct ImageGarbageCollector<M> {
+    runtime: M,
+    settings: ImageGarbageCollection,
+    pinned_images: Vec<String>,
+}
+
+impl<M> ImageGarbageCollector<M>
+where
+    M: 'static + ModuleRuntime + Clone,
+    <M::Module as Module>::Config: ModuleImage,
+{
+    /// `pinned_images` are never removed, even if no module currently uses them.
+    pub fn new(runtime: M, settings: ImageGarbageCollection, pinned_images: Vec<String>) -> Self {
+        ImageGarbageCollector {
+            runtime,
+            settings,
+            pinned_images,
+        }
+    }
+
+    // The first collection happens one recurrence after startup, so that images pulled
+    // by a deployment in progress are not removed before their modules are created.
+    pub fn run(self) -> impl Future<Item = (), Error = Error> {
+        let ImageGarbageCollector {
+            runtime,
+            settings,
+            pinned_images,
+        } = self;
+
+        if !settings.enabled() {
+            info!("Image garbage collection is disabled");
+            return Either::A(future::empty());
+        }
+
+        let recurrence = settings.cleanup_recurrence();
+        info!(
+            "Starting image garbage collection with {} second frequency...",
+            recurrence.as_secs()
+        );
+
+        let collections = Interval::new(Instant::now() + recurrence, recurrence)
+            .map_err(|err| Error::from(err.context(ErrorKind::ImageGarbageCollectorTimer)))
+            .fold(CollectorState::default(), move |state, _| {
+                let previous = state.clone();
+                collect_garbage(runtime.clone(), &settings, &pinned_images, state).or_else(|err| {
+                    warn!("Error in image garbage collection:");
+                    log_failure(Level::Warn, &err);
+                    Ok(previous)
+                })
+            })
+            .map(|_| ());
+        Either::B(collections)
+    }
+}
+
+/// What the collector has learned about the host's images in earlier collections.
+#[derive(Clone, Debug, Default)]
+struct CollectorState {
+    /// IDs of the images that existed at the previous collection.
+    previous_images: BTreeSet<String>,
+    /// Repositories of the images that modules have used.
+    module_repositories: BTreeSet<String>,
+    /// IDs of the images that modules have used.
+    module_images: BTreeSet<String>,
+}
+
+impl CollectorState {
+    /// Records which images modules use now and which images exist.
+    fn update(&mut self, images: &[ImageInfo], referenced: &BTreeSet<String>) {
+        for image in images {
+            if referenced
+                .iter()
+                .any(|reference| image.is_referenced_by(reference))
+            {
+                self.module_repositories
+                    .extend(image.repositories().map(ToOwned::to_owned));
+                self.module_images.insert(image.id().to_owned());
+            }
+        }
+    }
+
+    /// Whether `image` is a module image that already existed at the previous collection.
+    fn is_collectable(&self, image: &ImageInfo) -> bool {
+        self.previous_images.contains(image.id())
+            && (self.module_images.contains(image.id())
+                || image
+                    .repositories()
+                    .any(|repository| self.module_repositories.contains(repository)))
+    }
+}
+
+fn collect_garbage<M>(
+    runtime: M,
+    settings: &ImageGarbageCollection,
+    pinned_images: &[String],
+    mut state: CollectorState,
+) -> impl Future<Item = CollectorState, Error = Error>
+where
+    M: 'static + ModuleRuntime + Clone,
+    <M::Module as Module>::Config: ModuleImage,
+{
+    let keep_versions = settings.keep_versions();
+    let mut referenced: BTreeSet<String> = pinned_images.iter().cloned().collect();
+
+    let over_high_water_mark = match settings.disk_high_water_mark() {
+        None => Either::A(future::ok(true)),
+        Some(high_water_mark) => {
+            let image_store_path = settings.image_store_path().to_path_buf();
+            Either::B(
+                runtime
+                    .system_resources()
+                    .map_err(|err| Error::from(err.context(ErrorKind::ModuleRuntime)))
+                    .map(move |resources| {
+                        match image_store_usage(resources.disks(), &image_store_path) {
+                            Some(usage) if usage < u64::from(high_water_mark) => {
+                                debug!(
+                                    "Image store disk is {}% full, below the high-water mark of {}%",
+                                    usage, high_water_mark
+                                );
+                                false
+                            }
+                            Some(_) => true,
+                            None => {
+                                warn!(
+                                    "Could not determine the usage of the disk holding {}, collecting unused images anyway",
+                                    image_store_path.display()
+                                );
+                                true
+                            }
+                        }
+                    }),
+            )
+        }
+    };
+
+    over_high_water_mark.and_then(move |over_high_water_mark| {
+        if !over_high_water_mark {
+            return Either::A(future::ok(state));
+        }
+
+        info!("Looking for unused module images...");
+        let modules = runtime
+            .list()
+            .map_err(|err| Error::from(err.context(ErrorKind::ModuleRuntime)));
+        let images = runtime
+            .registry()
+            .list_images()
+            .map_err(|err| Error::from(err.context(ErrorKind::ModuleRuntime)));
+
+        let collection = modules.join(images).and_then(move |(modules, images)| {
+            for module in &modules {
+                let config = module.config();
+                referenced.insert(config.image().to_owned());
+                if let Some(image_id) = config.image_id() {
+                    referenced.insert(image_id.to_owned());
+                }
+            }
+
+            state.update(&images, &referenced);
+            let unused: Vec<ImageInfo> = images_to_remove(&images, &referenced, keep_versions)
+                .into_iter()
+                .filter(|image| state.is_collectable(image))
+                .cloned()
+                .collect();
+            if unused.is_empty() {
+                info!("No unused module images to remove");
+            }
+            state.previous_images = images.iter().map(|image| image.id().to_owned()).collect();
+
+            stream::iter_ok(unused)
+                .for_each(move |image| remove_image(&runtime, image))
+                .map(move |()| state)
+        });
+        Either::B(collection)
+    })
+}
+
+// Removing an image by ID fails if it is tagged in several repositories, so remove each of
+// its tags instead. The engine deletes the image together with its last tag.
+fn remove_image<M>(runtime: &M, image: ImageInfo) -> impl Future<Item = (), Error = Error>
+where
+    M: 'static + ModuleRuntime + Clone,
+{
+    let mut references: Vec<String> = image
+        .repo_tags()
+        .iter()
+        .filter(|tag| *tag != "<none>:<none>")
+        .cloned()
+        .collect();
+    if references.is_empty() {
+        references.push(image.id().to_owned());
+    }
+
+    let runtime = runtime.clone();
+    stream::iter_ok(references)
+        .for_each(move |reference| {
+            runtime
+                .registry()
+                .remove(&reference)
+                .map_err(|err| Error::from(err.context(ErrorKind::ModuleRuntime)))
+        })
+        .then(move |result| {
+            match result {
+                Ok(()) => info!(
+                    "Image garbage collection removed unused image {} ({}, {} bytes)",
+                    image.id(),
+                    image.repo_tags().join(", "),
+                    image.size(),
+                ),
+                // The registry already logged why; keep going with the other images.
+                Err(_) => warn!(
+                    "Image garbage collection could not remove image {}",
+                    image.id()
+                ),
+            }
+            Ok(())
+        })
+}
+
+/// Images that no module references and that are not among the `keep_versions` most
+/// recently created images of any of their repositories.
+fn images_to_remove<'a>(
+    images: &'a [ImageInfo],
+    referenced: &BTreeSet<String>,
+    keep_versions: usize,
+) -> Vec<&'a ImageInfo> {
+    let mut keep: BTreeSet<&str> = images
+        .iter()
+        .filter(|image| {
+            referenced
+                .iter()
+                .any(|reference| image.is_referenced_by(reference))
+        })
+        .map(ImageInfo::id)
+        .collect();
+
+    let mut versions_by_repository: BTreeMap<&str, Vec<&ImageInfo>> = BTreeMap::new();
+    for image in images {
+        for repository in image.repositories() {
+            versions_by_repository
+                .entry(repository)
+                .or_default()
+                .push(image);
+        }
+    }
+    for versions in versions_by_repository.values_mut() {
+        versions.sort_by_key(|image| Reverse(image.created()));
+        keep.extend(versions.iter().take(keep_versions).map(|image| image.id()));
+    }
+
+    images
+        .iter()
+        .filter(|image| !keep.contains(image.id()))
+        .collect()
+}
+
+/// Usage in percent of the disk with the longest mount point containing `image_store_path`.
+fn image_store_usage(disks: &[DiskInfo], image_store_path: &Path) -> Option<u64> {
+    disks
+        .iter()
+        .filter(|disk| image_store_path.starts_with(disk.mount_point()))
+        .max_by_key(|disk| disk.mount_point().len())
+        .and_then(DiskInfo::used_percent)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+    use std::path::Path;
+
+    use edgelet_core::{DiskInfo, ImageInfo};
+
+    use super::{image_store_usage, images_to_remove, CollectorState};
+
+    fn image(id: &str, tags: &[&str], created: i64) -> ImageInfo {
+        ImageInfo::new(
+            id.to_owned(),
+            tags.iter().map(|tag| (*tag).to_owned()).collect(),
+            vec![],
+            created,
+            1000,
+        )
+    }
+
+    fn disk(mount_point: &str, available_space: u64, total_space: u64) -> DiskInfo {
+        DiskInfo::new(
+            "disk".to_owned(),
+            available_space,
+            total_space,
+            "ext4".to_owned(),
+            "SSD".to_owned(),
+            mount_point.to_owned(),
+        )
+    }
+
+    fn removed_ids(images: &[ImageInfo], referenced: &[&str], keep_versions: usize) -> Vec<String> {
+        let referenced: BTreeSet<String> =
+            referenced.iter().map(|image| (*image).to_owned()).collect();
+        images_to_remove(images, &referenced, keep_versions)
+            .into_iter()
+            .map(|image| image.id().to_owned())
+            .collect()
+    }
+
+    #[test]
+    fn keeps_most_recent_versions_per_repository() {
+        let images = vec![
+            image("sha256:1", &["contoso.azurecr.io/sensor:1.0"], 1),
+            image("sha256:2", &["contoso.azurecr.io/sensor:1.1"], 2),
+            image("sha256:3", &["contoso.azurecr.io/sensor:1.2"], 3),
+            image("sha256:4", &["localhost:5000/filter:1.0"], 1),
+        ];
+
+        assert_eq!(vec!["sha256:1"], removed_ids(&images, &[], 2));
+        assert_eq!(vec!["sha256:1", "sha256:2"], removed_ids(&images, &[], 1));
+        assert_eq!(
+            vec!["sha256:1", "sha256:2", "sha256:3", "sha256:4"],
+            removed_ids(&images, &[], 0)
+        );
+    }
+
+    #[test]
+    fn keeps_referenced_images() {
+        let images = vec![
+            image("sha256:1", &["contoso.azurecr.io/sensor:1.0"], 1),
+            image("sha256:2", &["contoso.azurecr.io/sensor:1.1"], 2),
+            image("sha256:3", &["contoso.azurecr.io/sensor:1.2"], 3),
+        ];
+
+        assert_eq!(
+            vec!["sha256:2"],
+            removed_ids(&images, &["contoso.azurecr.io/sensor:1.0"], 1)
+        );
+        assert_eq!(
+            vec!["sha256:1", "sha256:3"],
+            removed_ids(&images, &["sha256:2"], 0)
+        );
+    }
+
+    #[test]
+    fn removes_untagged_images() {
+        let images = vec![
+            image("sha256:1", &[], 1),
+            image("sha256:2", &["<none>:<none>"], 2),
+            image("sha256:3", &["contoso.azurecr.io/sensor:1.0"], 3),
+        ];
+
+        assert_eq!(vec!["sha256:1", "sha256:2"], removed_ids(&images, &[], 2));
+    }
+
+    #[test]
+    fn collects_only_module_images_seen_in_previous_collection() {
+        let images = vec![
+            image("sha256:1", &["contoso.azurecr.io/sensor:1.0"], 1),
+            image("sha256:2", &["contoso.azurecr.io/sensor:1.1"], 2),
+            image("sha256:3", &["ubuntu:18.04"], 3),
+            image("sha256:4", &[], 4),
+        ];
+        let referenced: BTreeSet<String> = vec![
+            "contoso.azurecr.io/sensor:1.1".to_owned(),
+            "sha256:4".to_owned(),
+        ]
+        .into_iter()
+        .collect();
+
+        let mut state = CollectorState::default();
+        state.update(&images, &referenced);
+        assert!(!state.is_collectable(&images[0]));
+
+        state.previous_images = images.iter().map(|image| image.id().to_owned()).collect();
+        let pulled = image("sha256:5", &["contoso.azurecr.io/sensor:1.2"], 5);
+        let collectable: Vec<&str> = images
+            .iter()
+            .chain(Some(&pulled))
+            .filter(|image| state.is_collectable(image))
+            .map(ImageInfo::id)
+            .collect();
+        assert_eq!(vec!["sha256:1", "sha256:2", "sha256:4"], collectable);
+    }
+
+    #[test]
+    fn image_store_usage_uses_closest_mount_point() {
+        let disks = vec![
+            disk("/", 90, 100),
+            disk("/var/lib/docker", 25, 100),
+            disk("/var/lib/dockerfoo", 100, 100),
+        ];
+
+        assert_eq!(
+            Some(75),
+            image_store_usage(&disks, Path::new("/var/lib/docker"))
+        );
+        assert_eq!(Some(10), image_store_usage(&disks, Path::new("/data")));
+        assert_eq!(None, image_store_usage(&[], Path::new("/var/lib/docker")));
+        assert_eq!(
+            None,
+            image_store_usage(&[disk("/", 0, 0)], Path::new("/var/lib/docker"))
+        );
+    }
+}
@@ -15,9 +15,17 @@
     clippy::use_self,
 )]
 
+#[cfg(all(feature = "runtime-docker", feature = "runtime-process"))]
+compile_error!(
+    "Only one module runtime can be enabled. Build with --no-default-features to select runtime-process."
+);
+
 pub mod app;
 mod error;
+pub mod image_gc;
 pub mod logging;
+pub mod notify;
+pub mod reload;
 pub mod signal;
 pub mod watchdog;
 pub mod workload;
@@ -37,18 +45,18 @@ use edgelet_core::{
     settings::AutoReprovisioningMode,
 };
 use edgelet_core::{
-    Authenticator, MakeModuleRuntime, Module, ModuleRuntime, ModuleRuntimeErrorReason, ModuleSpec,
-    RuntimeSettings, WorkloadConfig,
+    Authenticator, MakeModuleRuntime, Module, ModuleImage, ModuleRuntime, ModuleRuntimeErrorReason,
+    ModuleSpec, RuntimeSettings, WorkloadConfig,
 };
 use edgelet_http::logging::LoggingService;
-use edgelet_http::{HyperExt, API_VERSION};
+use edgelet_http::{HyperExt, MutualTls, API_VERSION};
 use edgelet_http_mgmt::ManagementService;
-use edgelet_http_workload::WorkloadService;
+use edgelet_http_workload::{SecretStore, WorkloadService};
 use edgelet_utils::log_failure;
 pub use error::{Error, ErrorKind, InitializeErrorReason};
 use failure::{Context, Fail, ResultExt};
 use futures::future::Either;
-use futures::sync::oneshot::{self, Receiver};
+use futures::sync::oneshot::{self, Receiver, Sender};
 use futures::{future, Future, Stream};
 use hyper::server::conn::Http;
 use hyper::{Body, Request};
@@ -57,7 +65,10 @@ use serde::de::DeserializeOwned;
 use serde::Serialize;
 use sha2::{Digest, Sha256};
 
-use crate::watchdog::Watchdog;
+use crate::image_gc::ImageGarbageCollector;
+use crate::notify::KeepAlive;
+use crate::reload::Reload;
+use crate::watchdog::{Watchdog, WatchdogHandle};
 use crate::workload::WorkloadData;
 
 const EDGE_RUNTIME_MODULEID: &str = "$edgeAgent";
@@ -117,11 +128,16 @@ const EDGE_SETTINGS_SUBDIR: &str = "cache";
 /// This is the name of the settings backup file
 const EDGE_PROVISIONING_STATE_FILENAME: &str = "provisioning_state";
 
+/// This is the name of the file that module secrets are stored in
+const MODULE_SECRETS_FILENAME: &str = "module_secrets.json";
+
 // 2 hours
 const AZIOT_EDGE_ID_CERT_MAX_DURATION_SECS: i64 = 2 * 3600;
 // 90 days
 const AZIOT_EDGE_SERVER_CERT_MAX_DURATION_SECS: i64 = 90 * 24 * 3600;
 
+/// The time that all modules get together to stop before they're killed. This has to leave
+/// room within the `TimeoutStopSec` of the aziot-edged systemd service, which is 40 seconds.
 const STOP_TIME: Duration = Duration::from_secs(30);
 
 /// This is the interval at which to poll Identity Service for device information.
@@ -145,6 +161,7 @@ where
     M: MakeModuleRuntime,
 {
     settings: M::Settings,
+    load_settings: Option<fn() -> Result<M::Settings, Error>>,
 }
 
 impl<M> Main<M>
@@ -152,14 +169,24 @@ where
     M: MakeModuleRuntime + Send + 'static,
     M::ModuleRuntime: 'static + Authenticator<Request = Request<Body>> + Clone + Send + Sync,
     <<M::ModuleRuntime as ModuleRuntime>::Module as Module>::Config:
-        Clone + DeserializeOwned + Serialize + edgelet_core::module::NestedEdgeBodge,
+        Clone + DeserializeOwned + Serialize + edgelet_core::module::NestedEdgeBodge + ModuleImage,
     M::Settings: 'static + Clone + Serialize,
     <M::ModuleRuntime as ModuleRuntime>::Logs: Into<Body>,
+    <M::ModuleRuntime as ModuleRuntime>::Archive: Into<Body>,
     <M::ModuleRuntime as Authenticator>::Error: Fail + Sync,
     for<'r> &'r <M::ModuleRuntime as ModuleRuntime>::Error: Into<ModuleRuntimeErrorReason>,
 {
     pub fn new(settings: M::Settings) -> Self {
-        Main { settings }
+        Main {
+            settings,
+            load_settings: None,
+        }
+    }
+
+    /// Reloads the settings with `load_settings` on SIGHUP.
+    pub fn with_reload(mut self, load_settings: fn() -> Result<M::Settings, Error>) -> Self {
+        self.load_settings = Some(load_settings);
+        self
     }
 
     // Allowing cognitive complexity errors for now. TODO: Refactor method later.
@@ -169,7 +196,10 @@ where
         F: Future<Item = (), Error = ()> + Send + 'static,
         G: Fn() -> F,
     {
-        let Main { mut settings } = self;
+        let Main {
+            settings,
+            load_settings,
+        } = self;
 
         let mut tokio_runtime = tokio::runtime::Runtime::new()
             .context(ErrorKind::Initialize(InitializeErrorReason::Tokio))?;
@@ -185,9 +215,24 @@ where
 
         let runtime = init_runtime::<M>(settings.clone(), &mut tokio_runtime)?;
 
+        // The settings as they were last loaded, which a reload replaces.
+        let loaded_settings = Arc::new(Mutex::new(settings));
+
+        // Whether systemd was told that the daemon is ready before the APIs started, because
+        // provisioning failed.
+        let mut notified_ready = false;
+
         // This "do-while" loop runs until a StartApiReturnStatus::Shutdown
         // is received. If the TLS cert needs a restart, we will loop again.
         loop {
+            let mut settings = loaded_settings
+                .lock()
+                .expect("settings lock poisoned")
+                .clone();
+
+            // Until start_api sends keep-alives of its own.
+            let busy = notify::Busy::start();
+
             info!("Obtaining edge device provisioning data...");
 
             let url = settings.endpoints().aziot_identityd_url().clone();
@@ -282,18 +327,22 @@ where
                             .to_string(),
                         AZIOT_EDGE_ID_CERT_MAX_DURATION_SECS,
                         AZIOT_EDGE_SERVER_CERT_MAX_DURATION_SECS,
+                        settings.server_cert_policies().clone(),
                     );
 
+                    drop(busy);
                     let (code, should_reprovision) = start_api::<_, _, M>(
                         &settings,
                         &provisioning_result.gateway_host_name,
                         &runtime,
                         cfg.clone(),
                         make_shutdown_signal(),
+                        load_settings.map(|load_settings| (loaded_settings.clone(), load_settings)),
                         &mut tokio_runtime,
                     )?;
 
                     if should_reprovision {
+                        let _busy = notify::Busy::start();
                         tokio_runtime.block_on(reprovision_device(&client))?;
                     }
 
@@ -304,6 +353,13 @@ where
                 Err(err) => {
                     log_failure(Level::Warn, &err);
 
+                    // The device may take a long time to be provisioned, or never be. Don't
+                    // keep `systemctl start` and `iotedge config apply` waiting for it.
+                    if !notified_ready {
+                        notify::ready();
+                        notified_ready = true;
+                    }
+
                     std::thread::sleep(IS_GET_DEVICE_INFO_RETRY_INTERVAL_SECS);
 
                     log::warn!("Retrying getting edge device provisioning information.");
@@ -443,6 +499,7 @@ fn start_api<F, W, M>(
     runtime: &M::ModuleRuntime,
     workload_config: W,
     shutdown_signal: F,
+    reload: Option<(Arc<Mutex<M::Settings>>, fn() -> Result<M::Settings, Error>)>,
     tokio_runtime: &mut tokio::runtime::Runtime,
 ) -> Result<(StartApiReturnStatus, bool), Error>
 where
@@ -451,9 +508,10 @@ where
     M::ModuleRuntime: Authenticator<Request = Request<Body>> + Send + Sync + Clone + 'static,
     M: MakeModuleRuntime + 'static,
     <<M::ModuleRuntime as ModuleRuntime>::Module as Module>::Config:
-        Clone + DeserializeOwned + Serialize,
-    M::Settings: 'static,
+        Clone + DeserializeOwned + Serialize + ModuleImage + edgelet_core::module::NestedEdgeBodge,
+    M::Settings: 'static + Clone + Serialize,
     <M::ModuleRuntime as ModuleRuntime>::Logs: Into<Body>,
+    <M::ModuleRuntime as ModuleRuntime>::Archive: Into<Body>,
     <M::ModuleRuntime as Authenticator>::Error: Fail + Sync,
     for<'r> &'r <M::ModuleRuntime as ModuleRuntime>::Error: Into<ModuleRuntimeErrorReason>,
 {
@@ -461,15 +519,47 @@ where
     let device_id = workload_config.device_id().to_string();
 
     let (mgmt_tx, mgmt_rx) = oneshot::channel();
+    let (mgmt_bound_tx, mgmt_bound_rx) = oneshot::channel();
     let (mgmt_stop_and_reprovision_tx, mgmt_stop_and_reprovision_rx) = mpsc::unbounded();
     let (work_tx, work_rx) = oneshot::channel();
+    let (work_bound_tx, work_bound_rx) = oneshot::channel();
+    let (keep_alive_tx, keep_alive_rx) = oneshot::channel();
 
-    let mgmt = start_management::<M>(settings, runtime, mgmt_rx, mgmt_stop_and_reprovision_tx);
+    let mgmt = start_management::<M>(
+        settings,
+        runtime,
+        mgmt_rx,
+        mgmt_bound_tx,
+        mgmt_stop_and_reprovision_tx,
+    );
 
-    let workload = start_workload::<_, M>(settings, runtime, work_rx, workload_config);
+    let workload =
+        start_workload::<_, M>(settings, runtime, work_rx, work_bound_tx, workload_config);
+
+    // Tell systemd that the daemon is ready once both APIs are listening, and only then start
+    // sending keep-alives to its watchdog. The keep-alives stop along with the APIs.
+    let keep_alive = KeepAlive::new(runtime.clone(), settings.connect());
+    let keep_alive = mgmt_bound_rx
+        .join(work_bound_rx)
+        .then(move |bound| match bound {
+            Ok(((), ())) => {
+                notify::ready();
+                Either::A(
+                    keep_alive
+                        .run()
+                        .select2(keep_alive_rx)
+                        .then(|res| match res {
+                            Ok(_) | Err(Either::B(_)) => Ok(()),
+                            Err(Either::A((err, _))) => Err(err),
+                        }),
+                )
+            }
+            // One of the APIs failed to start, which fails start_api.
+            Err(_) => Either::B(future::ok(())),
+        });
 
     let (runt_tx, runt_rx) = oneshot::channel();
-    let edge_rt = start_runtime::<M>(
+    let (edge_rt, watchdog) = start_runtime::<M>(
         runtime.clone(),
         &iot_hub_name,
         parent_hostname,
@@ -478,6 +568,29 @@ where
         runt_rx,
     )?;
 
+    // Settings are reloaded on SIGHUP for as long as the edge runtime runs.
+    let (reload_tx, reload_rx) = oneshot::channel();
+    let reload = match reload {
+        Some((loaded_settings, load_settings)) => Either::A(
+            Reload::<M>::new(
+                loaded_settings,
+                load_settings,
+                runtime.clone(),
+                watchdog,
+                &iot_hub_name,
+                parent_hostname,
+                &device_id,
+            )
+            .run_until(signal::reload())
+            .select2(reload_rx)
+            .then(|res| match res {
+                Ok(_) | Err(Either::B(_)) => Ok(()),
+                Err(Either::A((err, _))) => Err(err),
+            }),
+        ),
+        None => Either::B(future::ok(())),
+    };
+
     // This mpsc sender/receiver is used for getting notifications from the mgmt service
     // indicating that the daemon should shut down and attempt to reprovision the device.
     let mgmt_stop_and_reprovision_signaled =
@@ -523,6 +636,8 @@ where
     let edge_rt_with_cleanup = edge_rt_with_mgmt_signal.then(move |res| {
         mgmt_tx.send(()).unwrap_or(());
         work_tx.send(()).unwrap_or(());
+        keep_alive_tx.send(()).unwrap_or(());
+        reload_tx.send(()).unwrap_or(());
 
         // A -> EdgeRt + Mgmt Stop and Reprovision Signal Future
         // B -> Restart Signal Future
@@ -536,15 +651,16 @@ where
 
     let shutdown = shutdown_signal.map(move |_| {
         debug!("shutdown signaled");
+        notify::stopping();
         // Signal the watchdog to shutdown
         runt_tx.send(()).unwrap_or(());
     });
     tokio_runtime.spawn(shutdown);
 
     let services = mgmt
-        .join3(workload, edge_rt_with_cleanup)
+        .join5(workload, edge_rt_with_cleanup, keep_alive, reload)
         .then(|result| match result {
-            Ok(((), (), (code, should_reprovision))) => Ok((code, should_reprovision)),
+            Ok(((), (), (code, should_reprovision), (), ())) => Ok((code, should_reprovision)),
             Err(err) => Err(err),
         });
     let (restart_code, should_reprovision) = tokio_runtime.block_on(services)?;
@@ -576,14 +692,77 @@ fn start_runtime<M>(
     device_id: &str,
     settings: &M::Settings,
     shutdown: Receiver<()>,
-) -> Result<impl Future<Item = (), Error = Error>, Error>
+) -> Result<
+    (
+        impl Future<Item = (), Error = Error>,
+        WatchdogHandle<<M::ModuleRuntime as ModuleRuntime>::Config>,
+    ),
+    Error,
+>
 where
     M: MakeModuleRuntime,
     M::ModuleRuntime: Clone + 'static,
     <<M::ModuleRuntime as ModuleRuntime>::Module as Module>::Config:
-        Clone + DeserializeOwned + Serialize,
+        Clone + DeserializeOwned + Serialize + ModuleImage,
     <M::ModuleRuntime as ModuleRuntime>::Logs: Into<Body>,
+    <M::ModuleRuntime as ModuleRuntime>::Archive: Into<Body>,
     for<'r> &'r <M::ModuleRuntime as ModuleRuntime>::Error: Into<ModuleRuntimeErrorReason>,
+{
+    let spec = agent_spec::<M>(settings, hostname, parent_hostname, device_id)?;
+
+    // The agent image is kept even while no edgeAgent container exists, so that the
+    // watchdog can recreate it without pulling.
+    let image_gc = ImageGarbageCollector::new(
+        runtime.clone(),
+        settings.image_garbage_collection().clone(),
+        vec![spec.config().image().to_owned()],
+    );
+
+    // Modules with a startup order or dependencies are started here, in that order, while
+    // the watchdog starts edgeAgent. Failing to start them isn't fatal, since edgeAgent
+    // starts them too.
+    info!("Starting modules...");
+    let start_modules = runtime.start_all().then(|result| {
+        match result {
+            Ok(()) => info!("Finished starting modules."),
+            Err(err) => {
+                let err = Error::from(err.context(ErrorKind::ModuleRuntime));
+                log_failure(Level::Warn, &err);
+            }
+        }
+        Ok(())
+    });
+
+    let watchdog = Watchdog::new(
+        runtime,
+        spec,
+        settings.watchdog().max_retries(),
+        settings.endpoints().aziot_identityd_url(),
+    );
+    let watchdog_handle = watchdog.handle();
+
+    // The image garbage collector never completes on its own, so this resolves when the
+    // watchdog does, and stops collecting images once the edge runtime is shut down.
+    let runtime_future = watchdog
+        .run_until(EDGE_RUNTIME_MODULEID, shutdown.map_err(|_| ()))
+        .map_err(Error::from)
+        .select(image_gc.run().join(start_modules).map(|((), ())| ()))
+        .map(|((), _)| ())
+        .map_err(|(err, _)| err);
+
+    Ok((runtime_future, watchdog_handle))
+}
+
+// The EdgeAgent's spec, completed with the environment variables it needs.
+fn agent_spec<M>(
+    settings: &M::Settings,
+    hostname: &str,
+    parent_hostname: &str,
+    device_id: &str,
+) -> Result<ModuleSpec<<M::ModuleRuntime as ModuleRuntime>::Config>, Error>
+where
+    M: MakeModuleRuntime,
+    <<M::ModuleRuntime as ModuleRuntime>::Module as Module>::Config: Clone,
 {
     let spec = settings.agent().clone();
     let env = build_env(spec.env(), hostname, parent_hostname, device_id, settings);
@@ -595,17 +774,7 @@ where
         spec.image_pull_policy(),
     )
     .context(ErrorKind::Initialize(InitializeErrorReason::EdgeRuntime))?;
-
-    let watchdog = Watchdog::new(
-        runtime,
-        settings.watchdog().max_retries(),
-        settings.endpoints().aziot_identityd_url(),
-    );
-    let runtime_future = watchdog
-        .run_until(spec, EDGE_RUNTIME_MODULEID, shutdown.map_err(|_| ()))
-        .map_err(Error::from);
-
-    Ok(runtime_future)
+    Ok(spec)
 }
 
 // Add the environment variables needed by the EdgeAgent.
@@ -636,7 +805,7 @@ where
     env.insert(DEVICEID_KEY.to_string(), device_id.to_string());
     env.insert(MODULEID_KEY.to_string(), EDGE_RUNTIME_MODULEID.to_string());
 
-    #[cfg(feature = "runtime-docker")]
+    #[cfg(any(feature = "runtime-docker", feature = "runtime-process"))]
     let (workload_uri, management_uri) = (
         settings.connect().workload_uri().to_string(),
         settings.connect().management_uri().to_string(),
@@ -660,6 +829,7 @@ fn start_management<M>(
     settings: &M::Settings,
     runtime: &M::ModuleRuntime,
     shutdown: Receiver<()>,
+    bound: Sender<()>,
     initiate_shutdown_and_reprovision: mpsc::UnboundedSender<()>,
 ) -> impl Future<Item = (), Error = Error>
 where
@@ -669,11 +839,23 @@ where
     for<'r> &'r <M::ModuleRuntime as ModuleRuntime>::Error: Into<ModuleRuntimeErrorReason>,
     <<M::ModuleRuntime as ModuleRuntime>::Module as Module>::Config: DeserializeOwned + Serialize,
     <M::ModuleRuntime as ModuleRuntime>::Logs: Into<Body>,
+    <M::ModuleRuntime as ModuleRuntime>::Archive: Into<Body>,
 {
     info!("Starting management API...");
 
     let label = "mgmt".to_string();
     let url = settings.listen().management_uri().clone();
+    let tls = settings
+        .listen()
+        .management_tls()
+        .map(|management_tls| {
+            MutualTls::from_settings(management_tls, settings.listen().min_tls_version())
+                .map(|tls| (management_tls.uri().clone(), tls))
+                .context(ErrorKind::Initialize(
+                    InitializeErrorReason::ManagementService,
+                ))
+        })
+        .transpose();
 
     let identity_uri = settings.endpoints().aziot_identityd_url().clone();
     let identity_client = Arc::new(Mutex::new(identity_client::IdentityClient::new(
@@ -687,6 +869,28 @@ where
                 InitializeErrorReason::ManagementService,
             ))?;
             let service = LoggingService::new(label, service);
+            let shutdown = shutdown.map_err(|_| ()).shared();
+
+            // Remote clients are served by their own listener, which stops along with the local one.
+            let run_tls = match tls? {
+                Some((tls_url, tls)) => {
+                    let run = Http::new()
+                        .bind_tls_url(tls_url.clone(), tls, service.clone())
+                        .map_err(|err| {
+                            err.context(ErrorKind::Initialize(
+                                InitializeErrorReason::ManagementService,
+                            ))
+                        })?
+                        .run_until(shutdown.clone().map(|_| ()).map_err(|_| ()))
+                        .map_err(|err| Error::from(err.context(ErrorKind::ManagementService)));
+                    info!(
+                        "Listening on {} with 1 thread for remote management API.",
+                        tls_url
+                    );
+                    Either::A(run)
+                }
+                None => Either::B(future::ok(())),
+            };
 
             let run = Http::new()
                 .bind_url(url.clone(), service)
@@ -695,10 +899,11 @@ where
                         InitializeErrorReason::ManagementService,
                     ))
                 })?
-                .run_until(shutdown.map_err(|_| ()))
+                .run_until(shutdown.map(|_| ()).map_err(|_| ()))
                 .map_err(|err| Error::from(err.context(ErrorKind::ManagementService)));
             info!("Listening on {} with 1 thread for management API.", url);
-            Ok(run)
+            bound.send(()).unwrap_or(());
+            Ok(run.join(run_tls).map(|((), ())| ()))
         })
         .flatten()
 }
@@ -707,6 +912,7 @@ fn start_workload<W, M>(
     settings: &M::Settings,
     runtime: &M::ModuleRuntime,
     shutdown: Receiver<()>,
+    bound: Sender<()>,
     config: W,
 ) -> impl Future<Item = (), Error = Error>
 where
@@ -719,6 +925,7 @@ where
     <<M::ModuleRuntime as ModuleRuntime>::Module as Module>::Config:
         Clone + DeserializeOwned + Serialize,
     <M::ModuleRuntime as ModuleRuntime>::Logs: Into<Body>,
+    <M::ModuleRuntime as ModuleRuntime>::Archive: Into<Body>,
 {
     info!("Starting workload API...");
 
@@ -744,26 +951,46 @@ where
         &identityd_url,
     )));
 
-    WorkloadService::new(runtime, identity_client, cert_client, key_client, config)
-        .then(move |service| -> Result<_, Error> {
-            let service = service.context(ErrorKind::Initialize(
-                InitializeErrorReason::WorkloadService,
-            ))?;
-            let service = LoggingService::new(label, service);
+    let secret_store = SecretStore::new(
+        settings.homedir().join(MODULE_SECRETS_FILENAME),
+        key_client.clone(),
+    );
+    if let Err(err) = secret_store.seed(settings.module_secrets()) {
+        return Either::A(future::err(Error::from(
+            err.context(ErrorKind::Initialize(InitializeErrorReason::ModuleSecrets)),
+        )));
+    }
 
-            let run = Http::new()
-                .bind_url(url.clone(), service)
-                .map_err(|err| {
-                    err.context(ErrorKind::Initialize(
-                        InitializeErrorReason::WorkloadService,
-                    ))
-                })?
-                .run_until(shutdown.map_err(|_| ()))
-                .map_err(|err| Error::from(err.context(ErrorKind::WorkloadService)));
-            info!("Listening on {} with 1 thread for workload API.", url);
-            Ok(run)
-        })
-        .flatten()
+    let workload = WorkloadService::new(
+        runtime,
+        identity_client,
+        cert_client,
+        key_client,
+        secret_store,
+        config,
+    )
+    .then(move |service| -> Result<_, Error> {
+        let service = service.context(ErrorKind::Initialize(
+            InitializeErrorReason::WorkloadService,
+        ))?;
+        let service = LoggingService::new(label, service);
+
+        let run = Http::new()
+            .bind_url(url.clone(), service)
+            .map_err(|err| {
+                err.context(ErrorKind::Initialize(
+                    InitializeErrorReason::WorkloadService,
+                ))
+            })?
+            .run_until(shutdown.map_err(|_| ()))
+            .map_err(|err| Error::from(err.context(ErrorKind::WorkloadService)));
+        info!("Listening on {} with 1 thread for workload API.", url);
+        bound.send(()).unwrap_or(());
+        Ok(run)
+    })
+    .flatten();
+
+    Either::B(workload)
 }
 
 #[cfg(test)]
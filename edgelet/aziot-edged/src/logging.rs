@@ -2,16 +2,53 @@
 
 use std::env;
 use std::io::Write;
+use std::sync::RwLock;
 
 use edgelet_utils::log_failure;
-use log::{Level, LevelFilter};
+use lazy_static::lazy_static;
+use log::{Level, LevelFilter, Log, Metadata, Record};
 
 use crate::error::Error;
 
 const ENV_LOG: &str = "IOTEDGE_LOG";
 
+lazy_static! {
+    static ref LOGGER: Logger = Logger(RwLock::new(build(None)));
+}
+
+/// The daemon's logger, which is rebuilt when the log level changes.
+struct Logger(RwLock<env_logger::Logger>);
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        self.0
+            .read()
+            .expect("logger lock poisoned")
+            .enabled(metadata)
+    }
+
+    fn log(&self, record: &Record<'_>) {
+        self.0.read().expect("logger lock poisoned").log(record);
+    }
+
+    fn flush(&self) {}
+}
+
 pub fn init() {
-    env_logger::Builder::new()
+    log::set_logger(&*LOGGER).expect("logger should only be initialized once");
+    log::set_max_level(LOGGER.0.read().expect("logger lock poisoned").filter());
+}
+
+/// Replaces the log level of the `log_level` setting. `IOTEDGE_LOG` still applies on top of it.
+pub fn set_log_level(log_level: Option<&str>) {
+    let logger = build(log_level);
+    log::set_max_level(logger.filter());
+    *LOGGER.0.write().expect("logger lock poisoned") = logger;
+}
+
+fn build(log_level: Option<&str>) -> env_logger::Logger {
+    let mut builder = env_logger::Builder::new();
+    builder
         .format(|fmt, record| {
             let level = match record.level() {
                 Level::Trace => "TRCE",
@@ -43,9 +80,14 @@ pub fn init() {
                 )
             }
         })
-        .filter_level(LevelFilter::Info)
-        .parse(&env::var(ENV_LOG).unwrap_or_default())
-        .init();
+        .filter_level(LevelFilter::Info);
+
+    // Later directives take precedence over earlier ones for the same modules.
+    if let Some(log_level) = log_level {
+        builder.parse(log_level);
+    }
+    builder.parse(&env::var(ENV_LOG).unwrap_or_default());
+    builder.build()
 }
 
 fn syslog_level(level: Level) -> i8 {
@@ -0,0 +1,250 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+//! Reports the daemon's state to systemd when it runs as a `Type=notify` service: readiness
+//! once the management and workload APIs are listening, and watchdog keep-alives while the
+//! module runtime and both APIs respond. While the daemon provisions the device or stops
+//! modules before starting the APIs, it keeps systemd waiting instead.
+
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use failure::{Fail, ResultExt};
+use futures::future::{self, Either};
+use futures::{Future, Stream};
+use hyper::header::USER_AGENT;
+use hyper::{Body, Client, Request};
+use log::{debug, info, warn, Level};
+use tokio::timer::{Interval, Timeout};
+use url::Url;
+
+use edgelet_core::settings::Connect;
+use edgelet_core::{ModuleRuntime, UrlExt};
+use edgelet_http::UrlConnector;
+use edgelet_utils::log_failure;
+
+use crate::error::{Error, ErrorKind};
+
+const PROBE_USER_AGENT: &str = "aziot-edged-watchdog";
+
+/// How often `Busy` extends the start timeout when the watchdog isn't enabled.
+const BUSY_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Tells systemd that the daemon finished starting up.
+pub fn ready() {
+    match systemd::notify_ready() {
+        Ok(true) => info!("Notified systemd that the daemon is ready."),
+        Ok(false) => (),
+        Err(err) => {
+            warn!("Could not notify systemd that the daemon is ready.");
+            log_failure(Level::Warn, &err);
+        }
+    }
+}
+
+/// Tells systemd that the daemon is shutting down.
+pub fn stopping() {
+    match systemd::notify_stopping() {
+        Ok(_) => (),
+        Err(err) => {
+            warn!("Could not notify systemd that the daemon is stopping.");
+            log_failure(Level::Warn, &err);
+        }
+    }
+}
+
+/// Keeps systemd waiting while the daemon is busy outside of the APIs, for as long as it's
+/// alive: provisioning, which retries until the identity service has provisioned the device,
+/// and stopping modules before a restart.
+///
+/// It extends the start timeout and sends watchdog keep-alives from a thread of its own, since
+/// the main thread is blocked. The keep-alives only show that the daemon is still running, not
+/// that it's responsive, which `KeepAlive` takes over once the APIs are listening.
+pub struct Busy {
+    stop_tx: mpsc::Sender<()>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Busy {
+    pub fn start() -> Self {
+        let interval = match systemd::watchdog_enabled() {
+            Ok(Some(timeout)) => timeout / 2,
+            Ok(None) => BUSY_INTERVAL,
+            Err(err) => {
+                warn!("Could not read the systemd watchdog's settings.");
+                log_failure(Level::Warn, &err);
+                BUSY_INTERVAL
+            }
+        };
+
+        let (stop_tx, stop_rx) = mpsc::channel();
+        let thread = thread::spawn(move || loop {
+            let notified = systemd::notify_extend_timeout(interval * 3)
+                .and_then(|_| systemd::notify_watchdog());
+            if let Err(err) = notified {
+                warn!("Could not tell systemd that the daemon is busy.");
+                log_failure(Level::Warn, &err);
+            }
+
+            match stop_rx.recv_timeout(interval) {
+                Err(RecvTimeoutError::Timeout) => (),
+                Ok(()) | Err(RecvTimeoutError::Disconnected) => break,
+            }
+        });
+
+        Busy {
+            stop_tx,
+            thread: Some(thread),
+        }
+    }
+}
+
+impl Drop for Busy {
+    fn drop(&mut self) {
+        self.stop_tx.send(()).unwrap_or(());
+        if let Some(thread) = self.thread.take() {
+            thread.join().unwrap_or(());
+        }
+    }
+}
+
+/// Sends keep-alives to systemd's watchdog, so that it restarts the daemon when the module
+/// runtime or the management and workload APIs stop responding.
+pub struct KeepAlive<M> {
+    runtime: M,
+    management_uri: Url,
+    workload_uri: Url,
+}
+
+impl<M> KeepAlive<M>
+where
+    M: 'static + ModuleRuntime + Clone,
+{
+    pub fn new(runtime: M, connect: &Connect) -> Self {
+        KeepAlive {
+            runtime,
+            management_uri: connect.management_uri().clone(),
+            workload_uri: connect.workload_uri().clone(),
+        }
+    }
+
+    // Keep-alives are sent at half of the watchdog's timeout, as systemd recommends. Each check
+    // gets a quarter of it, so that a check that hangs is reported before the watchdog fires.
+    pub fn run(self) -> impl Future<Item = (), Error = Error> {
+        let KeepAlive {
+            runtime,
+            management_uri,
+            workload_uri,
+        } = self;
+
+        let timeout = match systemd::watchdog_enabled() {
+            Ok(Some(timeout)) => timeout,
+            Ok(None) => {
+                debug!("The systemd watchdog is not enabled.");
+                return Either::A(future::empty());
+            }
+            Err(err) => {
+                warn!("Could not read the systemd watchdog's settings.");
+                log_failure(Level::Warn, &err);
+                return Either::A(future::empty());
+            }
+        };
+        let frequency = timeout / 2;
+        let check_timeout = timeout / 4;
+        info!(
+            "Sending systemd watchdog keep-alives every {} milliseconds...",
+            frequency.as_millis()
+        );
+
+        let keep_alives = Interval::new(Instant::now(), frequency)
+            .map_err(|err| Error::from(err.context(ErrorKind::WatchdogKeepAliveTimer)))
+            .for_each(move |_| {
+                check(&runtime, &management_uri, &workload_uri, check_timeout).then(|result| {
+                    match result {
+                        Ok(()) => {
+                            if let Err(err) = systemd::notify_watchdog() {
+                                warn!("Could not send a keep-alive to the systemd watchdog.");
+                                log_failure(Level::Warn, &err);
+                            }
+                        }
+                        Err(err) => {
+                            warn!("Skipping the systemd watchdog keep-alive because the daemon is not responsive:");
+                            log_failure(Level::Warn, &err);
+                        }
+                    }
+                    Ok(())
+                })
+            });
+        Either::B(keep_alives)
+    }
+}
+
+fn check<M>(
+    runtime: &M,
+    management_uri: &Url,
+    workload_uri: &Url,
+    timeout: Duration,
+) -> impl Future<Item = (), Error = Error>
+where
+    M: ModuleRuntime,
+{
+    let list = runtime
+        .list()
+        .map(|_| ())
+        .map_err(|err| Error::from(err.context(ErrorKind::ModuleRuntime)));
+    let management = probe(management_uri, ErrorKind::ManagementService);
+    let workload = probe(workload_uri, ErrorKind::WorkloadService);
+
+    with_timeout(list, timeout, ErrorKind::ModuleRuntime)
+        .join3(
+            with_timeout(management, timeout, ErrorKind::ManagementService),
+            with_timeout(workload, timeout, ErrorKind::WorkloadService),
+        )
+        .map(|((), (), ())| ())
+}
+
+/// Sends a request to an API. It is responsive if it sends back any response at all.
+fn probe(url: &Url, kind: ErrorKind) -> impl Future<Item = (), Error = Error> {
+    match probe_request(url, &kind) {
+        Ok((connector, request)) => {
+            let client: Client<_, Body> = Client::builder().build(connector);
+            Either::A(
+                client
+                    .request(request)
+                    .map(|_| ())
+                    .map_err(move |err| Error::from(err.context(kind))),
+            )
+        }
+        Err(err) => Either::B(future::err(err)),
+    }
+}
+
+fn probe_request(url: &Url, kind: &ErrorKind) -> Result<(UrlConnector, Request<Body>), Error> {
+    let connector = UrlConnector::new(url).with_context(|_| kind.clone())?;
+    let base_path = url.to_base_path().with_context(|_| kind.clone())?;
+    let uri = UrlConnector::build_hyper_uri(url.scheme(), &base_path.to_string_lossy(), "/")
+        .with_context(|_| kind.clone())?;
+    let request = Request::get(uri)
+        .header(USER_AGENT, PROBE_USER_AGENT)
+        .body(Body::empty())
+        .with_context(|_| kind.clone())?;
+    Ok((connector, request))
+}
+
+fn with_timeout<F>(
+    future: F,
+    timeout: Duration,
+    kind: ErrorKind,
+) -> impl Future<Item = F::Item, Error = Error>
+where
+    F: Future<Error = Error>,
+{
+    Timeout::new(future, timeout).map_err(move |err| {
+        if err.is_elapsed() {
+            Error::from(ErrorKind::Unresponsive.context(kind))
+        } else {
+            err.into_inner()
+                .unwrap_or_else(|| Error::from(ErrorKind::WatchdogKeepAliveTimer))
+        }
+    })
+}
@@ -0,0 +1,328 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+//! Reloads the daemon's settings when it receives SIGHUP.
+//!
+//! Changes to the edge runtime module's spec, the watchdog, the log level and content trust
+//! are applied without restarting any other module. Changes to any other section only take effect when
+//! the daemon is restarted, so they are reported instead, on every reload until then.
+
+use std::collections::BTreeSet;
+use std::sync::{Arc, Mutex};
+
+use failure::{Fail, ResultExt};
+use futures::future::{self, Either};
+use futures::{Future, Stream};
+use log::{info, warn, Level};
+use serde::Serialize;
+use serde_json::Value;
+
+use edgelet_core::module::NestedEdgeBodge;
+use edgelet_core::{
+    MakeModuleRuntime, Module, ModuleImage, ModuleRuntime, ModuleRuntimeErrorReason,
+    RuntimeSettings,
+};
+use edgelet_utils::log_failure;
+
+use crate::error::{Error, ErrorKind};
+use crate::logging;
+use crate::watchdog::{self, WatchdogHandle};
+
+/// Sections that are applied to the running daemon.
+const LIVE_SECTIONS: &[&str] = &["agent", "log_level", "watchdog"];
+
+/// Sections whose settings are compared one by one, since each of them configures a different
+/// part of the module runtime.
+const RUNTIME_SECTIONS: &[&str] = &["moby_runtime", "process_runtime"];
+
+/// Settings of the runtime sections that the module runtime applies while it's running.
+const LIVE_RUNTIME_SETTINGS: &[&str] = &[CONTENT_TRUST];
+
+const CONTENT_TRUST: &str = "moby_runtime.content_trust";
+
+/// The sections of the settings that differ between two versions of them, named by their keys
+/// in the config file.
+#[derive(Debug, Default, PartialEq)]
+pub struct Changes {
+    live: BTreeSet<String>,
+    restart: BTreeSet<String>,
+}
+
+impl Changes {
+    pub fn new<S>(old: &S, new: &S) -> Result<Self, Error>
+    where
+        S: Serialize,
+    {
+        let old = serde_json::to_value(old).context(ErrorKind::ReloadSettings)?;
+        let new = serde_json::to_value(new).context(ErrorKind::ReloadSettings)?;
+
+        let mut changes = Changes::default();
+        for key in changed_keys(&old, &new) {
+            if LIVE_SECTIONS.contains(&key.as_str()) {
+                changes.live.insert(key);
+            } else if RUNTIME_SECTIONS.contains(&key.as_str()) {
+                for runtime_key in changed_keys(&old[&key], &new[&key]) {
+                    let runtime_key = format!("{}.{}", key, runtime_key);
+                    if LIVE_RUNTIME_SETTINGS.contains(&runtime_key.as_str()) {
+                        changes.live.insert(runtime_key);
+                    } else {
+                        changes.restart.insert(runtime_key);
+                    }
+                }
+            } else {
+                changes.restart.insert(key);
+            }
+        }
+        Ok(changes)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.live.is_empty() && self.restart.is_empty()
+    }
+
+    /// Sections that can be applied to the running daemon.
+    pub fn live(&self) -> &BTreeSet<String> {
+        &self.live
+    }
+
+    /// Sections that only take effect when the daemon is restarted.
+    pub fn restart(&self) -> &BTreeSet<String> {
+        &self.restart
+    }
+
+    fn contains(&self, section: &str) -> bool {
+        self.live.contains(section)
+    }
+}
+
+/// Keys of two JSON objects whose values differ, including keys that only one of them has.
+fn changed_keys(old: &Value, new: &Value) -> BTreeSet<String> {
+    match (old, new) {
+        (Value::Object(old), Value::Object(new)) => old
+            .keys()
+            .chain(new.keys())
+            .filter(|key| old.get(*key) != new.get(*key))
+            .cloned()
+            .collect(),
+        _ => BTreeSet::new(),
+    }
+}
+
+/// Applies changes to the settings from the time the edge runtime module was started.
+pub struct Reload<M>
+where
+    M: MakeModuleRuntime,
+{
+    settings: Arc<Mutex<M::Settings>>,
+    load_settings: fn() -> Result<M::Settings, Error>,
+    runtime: M::ModuleRuntime,
+    watchdog: WatchdogHandle<<M::ModuleRuntime as ModuleRuntime>::Config>,
+    hostname: String,
+    parent_hostname: String,
+    device_id: String,
+}
+
+impl<M> Reload<M>
+where
+    M: MakeModuleRuntime + 'static,
+    M::ModuleRuntime: Clone + 'static,
+    M::Settings: Clone + Serialize,
+    <<M::ModuleRuntime as ModuleRuntime>::Module as Module>::Config:
+        Clone + ModuleImage + NestedEdgeBodge,
+    for<'r> &'r <M::ModuleRuntime as ModuleRuntime>::Error: Into<ModuleRuntimeErrorReason>,
+{
+    /// `settings` are the settings as they were loaded, before the edge runtime module's
+    /// spec was completed. They are replaced by the new settings once all of their changes
+    /// have been applied.
+    pub fn new(
+        settings: Arc<Mutex<M::Settings>>,
+        load_settings: fn() -> Result<M::Settings, Error>,
+        runtime: M::ModuleRuntime,
+        watchdog: WatchdogHandle<<M::ModuleRuntime as ModuleRuntime>::Config>,
+        hostname: &str,
+        parent_hostname: &str,
+        device_id: &str,
+    ) -> Self {
+        Reload {
+            settings,
+            load_settings,
+            runtime,
+            watchdog,
+            hostname: hostname.to_string(),
+            parent_hostname: parent_hostname.to_string(),
+            device_id: device_id.to_string(),
+        }
+    }
+
+    /// Reloads the settings every time `signals` yields. Failing to reload them isn't fatal,
+    /// and the current settings are kept.
+    pub fn run_until<S>(self, signals: S) -> impl Future<Item = (), Error = Error>
+    where
+        S: Stream<Item = (), Error = ()>,
+    {
+        signals
+            .map_err(|()| Error::from(ErrorKind::ReloadSettings))
+            .for_each(move |()| {
+                self.reload().or_else(|err| {
+                    warn!("Could not reload settings, the current settings are kept:");
+                    log_failure(Level::Warn, &err);
+                    Ok(())
+                })
+            })
+    }
+
+    fn reload(&self) -> impl Future<Item = (), Error = Error> {
+        let new = match (self.load_settings)() {
+            Ok(new) => new,
+            Err(err) => return Either::A(future::err(err)),
+        };
+        let changes = {
+            let settings = self.settings.lock().expect("settings lock poisoned");
+            match Changes::new(&*settings, &new) {
+                Ok(changes) => changes,
+                Err(err) => return Either::A(future::err(err)),
+            }
+        };
+        if changes.is_empty() {
+            info!("Settings have not changed.");
+            return Either::A(future::ok(()));
+        }
+
+        if changes.contains("log_level") {
+            logging::set_log_level(new.log_level());
+            info!("Applied the new log level.");
+        }
+
+        if changes.contains("watchdog") {
+            self.watchdog.set_max_retries(new.watchdog().max_retries());
+            info!("Applied the new watchdog settings.");
+        }
+
+        // The module runtime keeps its current content trust settings when it can't apply the
+        // new ones.
+        let reload_runtime = if changes.contains(CONTENT_TRUST) {
+            Either::A(M::reload(&self.runtime, &new).then(|result| match result {
+                Ok(()) => {
+                    info!("Applied the new content trust settings.");
+                    Ok(())
+                }
+                Err(err) => Err(Error::from(err.context(ErrorKind::ModuleRuntime))),
+            }))
+        } else {
+            Either::B(future::ok(()))
+        };
+
+        if !changes.restart().is_empty() {
+            let sections: Vec<_> = changes.restart().iter().map(String::as_str).collect();
+            warn!(
+                "Changes to {} only take effect when aziot-edged is restarted.",
+                sections.join(", ")
+            );
+            // The edge runtime module's spec is completed from other sections, which have to
+            // be applied first.
+            if changes.contains("agent") {
+                warn!("Changes to agent are applied when aziot-edged is restarted.");
+            }
+            return Either::B(Either::A(reload_runtime));
+        }
+
+        let recreate_agent = if changes.contains("agent") {
+            let mut resolved = new.clone();
+            resolved
+                .agent_mut()
+                .parent_hostname_resolve(&self.parent_hostname);
+            let spec = match crate::agent_spec::<M>(
+                &resolved,
+                &self.hostname,
+                &self.parent_hostname,
+                &self.device_id,
+            ) {
+                Ok(spec) => spec,
+                Err(err) => return Either::A(future::err(err)),
+            };
+            let name = spec.name().to_string();
+            self.watchdog.set_spec(spec);
+
+            info!(
+                "Removing edge runtime module {} so that the watchdog creates it with the new settings...",
+                name
+            );
+            Either::A(watchdog::remove_runtime(&self.runtime, &name))
+        } else {
+            Either::B(future::ok(()))
+        };
+
+        let settings = self.settings.clone();
+        Either::B(Either::B(reload_runtime.and_then(|()| recreate_agent).map(
+            move |()| {
+                *settings.lock().expect("settings lock poisoned") = new;
+                info!("Finished reloading settings.");
+            },
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::Changes;
+
+    fn changes(old: &serde_json::Value, new: &serde_json::Value) -> (Vec<String>, Vec<String>) {
+        let changes = Changes::new(old, new).unwrap();
+        (
+            changes.live().iter().cloned().collect(),
+            changes.restart().iter().cloned().collect(),
+        )
+    }
+
+    #[test]
+    fn unchanged_settings_have_no_changes() {
+        let settings = json!({
+            "hostname": "zoo",
+            "agent": { "name": "edgeAgent" },
+            "moby_runtime": { "uri": "unix:///var/run/docker.sock" },
+        });
+
+        assert!(Changes::new(&settings, &settings).unwrap().is_empty());
+    }
+
+    #[test]
+    fn live_sections_are_separate_from_others() {
+        let old = json!({
+            "hostname": "zoo",
+            "agent": { "name": "edgeAgent", "env": {} },
+            "watchdog": { "max_retries": "infinite" },
+        });
+        let new = json!({
+            "hostname": "zoo2",
+            "agent": { "name": "edgeAgent", "env": { "RuntimeLogLevel": "debug" } },
+            "watchdog": { "max_retries": 3 },
+            "log_level": "debug",
+        });
+
+        let (live, restart) = changes(&old, &new);
+        assert_eq!(vec!["agent", "log_level", "watchdog"], live);
+        assert_eq!(vec!["hostname"], restart);
+    }
+
+    #[test]
+    fn runtime_sections_are_compared_by_key() {
+        let old = json!({
+            "moby_runtime": {
+                "uri": "unix:///var/run/docker.sock",
+                "network": "azure-iot-edge",
+            },
+        });
+        let new = json!({
+            "moby_runtime": {
+                "uri": "unix:///var/run/docker.sock",
+                "network": "edge",
+                "content_trust": { "ca_certs": { "contoso": "contoso-ca" } },
+            },
+        });
+
+        let (live, restart) = changes(&old, &new);
+        assert_eq!(vec!["moby_runtime.content_trust"], live);
+        assert_eq!(vec!["moby_runtime.network"], restart);
+    }
+}
@@ -1,16 +1,36 @@
 // Copyright (c) Microsoft. All rights reserved.
 
+#[cfg(feature = "runtime-docker")]
+use edgelet_docker::{ContainerEngine, DockerModuleRuntime};
+#[cfg(feature = "runtime-docker")]
+use edgelet_podman::PodmanModuleRuntime;
+#[cfg(feature = "runtime-process")]
+use edgelet_process::ProcessModuleRuntime;
+
 use crate::app;
 use crate::error::Error;
 use crate::signal;
 
 #[cfg(feature = "runtime-docker")]
-type ModuleRuntime = edgelet_docker::DockerModuleRuntime;
-
 pub fn run() -> Result<(), Error> {
     let settings = app::init()?;
-    let main = super::Main::<ModuleRuntime>::new(settings);
 
-    main.run_until(signal::shutdown)?;
+    match settings.moby_runtime().engine() {
+        ContainerEngine::Docker => super::Main::<DockerModuleRuntime>::new(settings)
+            .with_reload(app::load_settings)
+            .run_until(signal::shutdown)?,
+        ContainerEngine::Podman => super::Main::<PodmanModuleRuntime>::new(settings)
+            .with_reload(app::load_settings)
+            .run_until(signal::shutdown)?,
+    }
+    Ok(())
+}
+
+#[cfg(feature = "runtime-process")]
+pub fn run() -> Result<(), Error> {
+    let settings = app::init()?;
+    super::Main::<ProcessModuleRuntime>::new(settings)
+        .with_reload(app::load_settings)
+        .run_until(signal::shutdown)?;
     Ok(())
 }
@@ -1,6 +1,7 @@
 // Copyright (c) Microsoft. All rights reserved.
 
 use std::cmp::Ordering;
+use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
 
 use failure::{Fail, ResultExt};
@@ -30,9 +31,12 @@ const MODULE_GENERATIONID: &str = "IOTEDGE_MODULEGENERATIONID";
 /// This is the frequency with which the watchdog checks for the status of the edge runtime module.
 const WATCHDOG_FREQUENCY_SECS: u64 = 60;
 
-pub struct Watchdog<M> {
+pub struct Watchdog<M>
+where
+    M: ModuleRuntime,
+{
     runtime: M,
-    max_retries: RetryLimit,
+    handle: WatchdogHandle<<M::Module as Module>::Config>,
     identityd_url: url::Url,
 }
 
@@ -42,20 +46,31 @@ where
     for<'r> &'r <M as ModuleRuntime>::Error: Into<ModuleRuntimeErrorReason>,
     <M::Module as Module>::Config: Clone,
 {
-    pub fn new(runtime: M, max_retries: RetryLimit, identityd_url: &url::Url) -> Self {
+    pub fn new(
+        runtime: M,
+        spec: ModuleSpec<<M::Module as Module>::Config>,
+        max_retries: RetryLimit,
+        identityd_url: &url::Url,
+    ) -> Self {
         Watchdog {
             runtime,
-            max_retries,
+            handle: WatchdogHandle {
+                spec: Arc::new(Mutex::new(spec)),
+                max_retries: Arc::new(Mutex::new(max_retries)),
+            },
             identityd_url: identityd_url.clone(),
         }
     }
 
+    pub fn handle(&self) -> WatchdogHandle<<M::Module as Module>::Config> {
+        self.handle.clone()
+    }
+
     // Start the edge runtime module (EdgeAgent). This also updates the identity of the module (module_id)
     // to make sure it is configured for the right authentication type (sas token)
     // spec.name = edgeAgent / module_id = $edgeAgent
     pub fn run_until<F>(
         self,
-        spec: ModuleSpec<<M::Module as Module>::Config>,
         module_id: &str,
         shutdown_signal: F,
     ) -> impl Future<Item = (), Error = Error>
@@ -64,12 +79,11 @@ where
     {
         let runtime = self.runtime;
         let runtime_copy = runtime.clone();
-        let name = spec.name().to_string();
+        let name = self.handle.spec().name().to_string();
         let module_id = module_id.to_string();
-        let max_retries = self.max_retries;
         let identityd_url = self.identityd_url;
 
-        let watchdog = start_watchdog(runtime, spec, module_id, max_retries, identityd_url);
+        let watchdog = start_watchdog(runtime, self.handle, module_id, identityd_url);
 
         // Swallow any errors from shutdown_signal
         let shutdown_signal = shutdown_signal.then(|_| Ok(()));
@@ -87,6 +101,36 @@ where
     }
 }
 
+/// Changes the edge runtime module's spec and the watchdog's retry limit while the watchdog
+/// runs. Changes apply from the watchdog's next check, and a changed spec is only used when
+/// the module is created again.
+#[derive(Clone)]
+pub struct WatchdogHandle<C> {
+    spec: Arc<Mutex<ModuleSpec<C>>>,
+    max_retries: Arc<Mutex<RetryLimit>>,
+}
+
+impl<C> WatchdogHandle<C>
+where
+    C: Clone,
+{
+    pub fn spec(&self) -> ModuleSpec<C> {
+        self.spec.lock().expect("watchdog lock poisoned").clone()
+    }
+
+    pub fn set_spec(&self, spec: ModuleSpec<C>) {
+        *self.spec.lock().expect("watchdog lock poisoned") = spec;
+    }
+
+    pub fn max_retries(&self) -> RetryLimit {
+        *self.max_retries.lock().expect("watchdog lock poisoned")
+    }
+
+    pub fn set_max_retries(&self, max_retries: RetryLimit) {
+        *self.max_retries.lock().expect("watchdog lock poisoned") = max_retries;
+    }
+}
+
 // Stop EdgeAgent
 fn stop_runtime<M>(runtime: &M, name: &str) -> impl Future<Item = (), Error = Error>
 where
@@ -105,12 +149,32 @@ where
         })
 }
 
+// Stop and remove EdgeAgent, so that the watchdog creates it again from its current spec
+pub fn remove_runtime<M>(runtime: &M, name: &str) -> impl Future<Item = (), Error = Error>
+where
+    M: 'static + ModuleRuntime + Clone,
+    for<'r> &'r <M as ModuleRuntime>::Error: Into<ModuleRuntimeErrorReason>,
+    <M::Module as Module>::Config: Clone,
+{
+    let runtime_copy = runtime.clone();
+    let name_copy = name.to_string();
+    stop_runtime(runtime, name).and_then(move |()| {
+        runtime_copy
+            .remove(&name_copy)
+            .or_else(|err| match (&err).into() {
+                ModuleRuntimeErrorReason::NotFound => Ok(()),
+                ModuleRuntimeErrorReason::Other => {
+                    Err(Error::from(err.context(ErrorKind::ModuleRuntime)))
+                }
+            })
+    })
+}
+
 // Start watchdog on a timer for 1 minute
 pub fn start_watchdog<M>(
     runtime: M,
-    spec: ModuleSpec<<M::Module as Module>::Config>,
+    handle: WatchdogHandle<<M::Module as Module>::Config>,
     module_id: String,
-    max_retries: RetryLimit,
     identityd_url: url::Url,
 ) -> impl Future<Item = (), Error = Error>
 where
@@ -122,6 +186,7 @@ where
         WATCHDOG_FREQUENCY_SECS
     );
 
+    let max_retries = handle.clone();
     Interval::new(Instant::now(), Duration::from_secs(WATCHDOG_FREQUENCY_SECS))
         .map_err(|err| Error::from(err.context(ErrorKind::EdgeRuntimeStatusCheckerTimer)))
         .and_then(move |_| {
@@ -129,7 +194,7 @@ where
 
             check_runtime(
                 runtime.clone(),
-                spec.clone(),
+                handle.spec(),
                 module_id.clone(),
                 identityd_url.clone(),
             )
@@ -144,7 +209,7 @@ where
             result.map_or_else(
                 || Ok(0),
                 |e| {
-                    if max_retries.compare(exec_count) == Ordering::Greater {
+                    if max_retries.max_retries().compare(exec_count) == Ordering::Greater {
                         Ok(exec_count + 1)
                     } else {
                         Err(e)
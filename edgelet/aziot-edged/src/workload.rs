@@ -1,6 +1,7 @@
 // Copyright (c) Microsoft. All rights reserved.
 
-use edgelet_core::{CertificateType, WorkloadConfig};
+use edgelet_core::{CertificateType, ServerCertPolicy, WorkloadConfig};
+use std::collections::BTreeMap;
 use std::sync::Arc;
 
 #[derive(Debug, Clone)]
@@ -13,6 +14,7 @@ struct WorkloadConfigData {
     manifest_trust_bundle_cert: String,
     id_cert_max_duration: i64,
     srv_cert_max_duration: i64,
+    server_cert_policies: BTreeMap<String, ServerCertPolicy>,
 }
 
 impl WorkloadConfigData {
@@ -25,6 +27,7 @@ impl WorkloadConfigData {
         manifest_trust_bundle_cert: String,
         id_cert_max_duration: i64,
         srv_cert_max_duration: i64,
+        server_cert_policies: BTreeMap<String, ServerCertPolicy>,
     ) -> Self {
         WorkloadConfigData {
             iot_hub_name,
@@ -35,6 +38,7 @@ impl WorkloadConfigData {
             manifest_trust_bundle_cert,
             id_cert_max_duration,
             srv_cert_max_duration,
+            server_cert_policies,
         }
     }
 
@@ -69,6 +73,10 @@ impl WorkloadConfigData {
     pub fn server_cert_max(&self) -> i64 {
         self.srv_cert_max_duration
     }
+
+    pub fn server_cert_policy(&self, module_id: &str) -> Option<&ServerCertPolicy> {
+        self.server_cert_policies.get(module_id)
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -86,6 +94,7 @@ impl WorkloadData {
         manifest_trust_bundle_cert: String,
         id_cert_max_duration: i64,
         srv_cert_max_duration: i64,
+        server_cert_policies: BTreeMap<String, ServerCertPolicy>,
     ) -> Self {
         let w = WorkloadConfigData::new(
             iot_hub_name,
@@ -96,6 +105,7 @@ impl WorkloadData {
             manifest_trust_bundle_cert,
             id_cert_max_duration,
             srv_cert_max_duration,
+            server_cert_policies,
         );
         WorkloadData { data: Arc::new(w) }
     }
@@ -133,4 +143,8 @@ impl WorkloadConfig for WorkloadData {
             _ => 0,
         }
     }
+
+    fn server_cert_policy(&self, module_id: &str) -> Option<&ServerCertPolicy> {
+        self.data.server_cert_policy(module_id)
+    }
 }
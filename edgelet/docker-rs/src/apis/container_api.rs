@@ -34,7 +34,7 @@ pub trait ContainerApi: Send + Sync {
         &self,
         id: &str,
         path: &str,
-    ) -> Box<dyn Future<Item = (), Error = Error<serde_json::Value>>>;
+    ) -> Box<dyn Future<Item = hyper::Body, Error = Error<serde_json::Value>> + Send>;
     fn container_archive_info(
         &self,
         id: &str,
@@ -184,9 +184,9 @@ pub trait ContainerApi: Send + Sync {
         &self,
         id: &str,
         path: &str,
-        input_stream: &str,
-        no_overwrite_dir_non_dir: &str,
-    ) -> Box<dyn Future<Item = (), Error = Error<serde_json::Value>>>;
+        input_stream: hyper::Body,
+        no_overwrite_dir_non_dir: bool,
+    ) -> Box<dyn Future<Item = (), Error = Error<serde_json::Value>> + Send>;
 }
 
 impl<C> ContainerApi for ContainerApiClient<C>
@@ -199,7 +199,7 @@ where
         &self,
         id: &str,
         path: &str,
-    ) -> Box<dyn Future<Item = (), Error = Error<serde_json::Value>>> {
+    ) -> Box<dyn Future<Item = hyper::Body, Error = Error<serde_json::Value>> + Send> {
         let configuration: &configuration::Configuration<C> = self.configuration.borrow();
 
         let method = hyper::Method::GET;
@@ -231,18 +231,16 @@ where
                 .map_err(|e| Error::from(e))
                 .and_then(|resp| {
                     let (http::response::Parts { status, .. }, body) = resp.into_parts();
-                    body.concat2()
-                        .and_then(move |body| Ok((status, body)))
-                        .map_err(|e| Error::from(e))
-                })
-                .and_then(|(status, body)| {
                     if status.is_success() {
-                        Ok(body)
+                        futures::future::Either::A(futures::future::ok(body))
                     } else {
-                        Err(Error::from((status, &*body)))
+                        futures::future::Either::B(
+                            body.concat2()
+                                .map_err(|e| Error::from(e))
+                                .and_then(move |body| Err(Error::from((status, &*body)))),
+                        )
                     }
-                })
-                .and_then(|_| futures::future::ok(())),
+                }),
         )
     }
 
@@ -1519,9 +1517,9 @@ where
         &self,
         id: &str,
         path: &str,
-        input_stream: &str,
-        no_overwrite_dir_non_dir: &str,
-    ) -> Box<dyn Future<Item = (), Error = Error<serde_json::Value>>> {
+        input_stream: hyper::Body,
+        no_overwrite_dir_non_dir: bool,
+    ) -> Box<dyn Future<Item = (), Error = Error<serde_json::Value>> + Send> {
         let configuration: &configuration::Configuration<C> = self.configuration.borrow();
 
         let method = hyper::Method::PUT;
@@ -1540,21 +1538,17 @@ where
         // if let Err(e) = uri {
         //     return Box::new(futures::future::err(e));
         // }
-        let serialized = serde_json::to_string(&input_stream).unwrap();
-        let serialized_len = serialized.len();
-
         let mut req = hyper::Request::builder();
         req.method(method).uri(uri.unwrap());
         if let Some(ref user_agent) = configuration.user_agent {
             req.header(http::header::USER_AGENT, &**user_agent);
         }
         let mut req = req
-            .body(hyper::Body::from(serialized))
+            .body(input_stream)
             .expect("could not build hyper::Request");
-        req.headers_mut()
-            .typed_insert(&typed_headers::ContentType(mime::APPLICATION_JSON));
-        req.headers_mut()
-            .typed_insert(&typed_headers::ContentLength(serialized_len as u64));
+        req.headers_mut().typed_insert(&typed_headers::ContentType(
+            "application/x-tar".parse().unwrap(),
+        ));
 
         // send request
         Box::new(
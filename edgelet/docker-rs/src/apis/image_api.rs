@@ -89,7 +89,7 @@ pub trait ImageApi: Send + Sync {
         name: &str,
         force: bool,
         noprune: bool,
-    ) -> Box<dyn Future<Item = Vec<ImageDeleteResponseItem>, Error = Error<serde_json::Value>>>;
+    ) -> Box<dyn Future<Item = Vec<ImageDeleteResponseItem>, Error = Error<serde_json::Value>> + Send>;
     fn image_get(
         &self,
         name: &str,
@@ -107,18 +107,22 @@ pub trait ImageApi: Send + Sync {
     fn image_inspect(
         &self,
         name: &str,
-    ) -> Box<dyn Future<Item = crate::models::Image, Error = Error<serde_json::Value>>>;
+    ) -> Box<dyn Future<Item = crate::models::Image, Error = Error<serde_json::Value>> + Send>;
     fn image_list(
         &self,
         all: bool,
         filters: &str,
         digests: bool,
-    ) -> Box<dyn Future<Item = Vec<crate::models::ImageSummary>, Error = Error<serde_json::Value>>>;
+    ) -> Box<
+        dyn Future<Item = Vec<crate::models::ImageSummary>, Error = Error<serde_json::Value>>
+            + Send,
+    >;
     fn image_load(
         &self,
-        images_tarball: Vec<u8>,
+        images_tarball: hyper::Body,
+        tarball_len: u64,
         quiet: bool,
-    ) -> Box<dyn Future<Item = (), Error = Error<serde_json::Value>>>;
+    ) -> Box<dyn Future<Item = (), Error = Error<serde_json::Value>> + Send>;
     fn image_prune(
         &self,
         filters: &str,
@@ -142,7 +146,7 @@ pub trait ImageApi: Send + Sync {
         name: &str,
         repo: &str,
         tag: &str,
-    ) -> Box<dyn Future<Item = (), Error = Error<serde_json::Value>>>;
+    ) -> Box<dyn Future<Item = (), Error = Error<serde_json::Value>> + Send>;
 }
 
 impl<C> ImageApi for ImageApiClient<C>
@@ -469,7 +473,7 @@ where
         name: &str,
         force: bool,
         noprune: bool,
-    ) -> Box<dyn Future<Item = Vec<ImageDeleteResponseItem>, Error = Error<serde_json::Value>>>
+    ) -> Box<dyn Future<Item = Vec<ImageDeleteResponseItem>, Error = Error<serde_json::Value>> + Send>
     {
         let configuration: &configuration::Configuration<C> = self.configuration.borrow();
 
@@ -681,7 +685,7 @@ where
     fn image_inspect(
         &self,
         name: &str,
-    ) -> Box<dyn Future<Item = crate::models::Image, Error = Error<serde_json::Value>>> {
+    ) -> Box<dyn Future<Item = crate::models::Image, Error = Error<serde_json::Value>> + Send> {
         let configuration: &configuration::Configuration<C> = self.configuration.borrow();
 
         let method = hyper::Method::GET;
@@ -733,8 +737,10 @@ where
         all: bool,
         filters: &str,
         digests: bool,
-    ) -> Box<dyn Future<Item = Vec<crate::models::ImageSummary>, Error = Error<serde_json::Value>>>
-    {
+    ) -> Box<
+        dyn Future<Item = Vec<crate::models::ImageSummary>, Error = Error<serde_json::Value>>
+            + Send,
+    > {
         let configuration: &configuration::Configuration<C> = self.configuration.borrow();
 
         let method = hyper::Method::GET;
@@ -789,9 +795,10 @@ where
 
     fn image_load(
         &self,
-        images_tarball: Vec<u8>,
+        images_tarball: hyper::Body,
+        tarball_len: u64,
         quiet: bool,
-    ) -> Box<dyn Future<Item = (), Error = Error<serde_json::Value>>> {
+    ) -> Box<dyn Future<Item = (), Error = Error<serde_json::Value>> + Send> {
         let configuration: &configuration::Configuration<C> = self.configuration.borrow();
 
         let method = hyper::Method::POST;
@@ -806,21 +813,19 @@ where
         // if let Err(e) = uri {
         //     return Box::new(futures::future::err(e));
         // }
-        let serialized = serde_json::to_string(&images_tarball).unwrap();
-        let serialized_len = serialized.len();
-
+        // DEVNOTE: The generated code sent the tarball as a JSON array of bytes. The engine
+        // expects the raw tarball, which is streamed since it can be hundreds of MB.
         let mut req = hyper::Request::builder();
         req.method(method).uri(uri.unwrap());
         if let Some(ref user_agent) = configuration.user_agent {
             req.header(http::header::USER_AGENT, &**user_agent);
         }
+        req.header(http::header::CONTENT_TYPE, "application/x-tar");
         let mut req = req
-            .body(hyper::Body::from(serialized))
+            .body(images_tarball)
             .expect("could not build hyper::Request");
         req.headers_mut()
-            .typed_insert(&typed_headers::ContentType(mime::APPLICATION_JSON));
-        req.headers_mut()
-            .typed_insert(&typed_headers::ContentLength(serialized_len as u64));
+            .typed_insert(&typed_headers::ContentLength(tarball_len));
 
         // send request
         Box::new(
@@ -1018,7 +1023,7 @@ where
         name: &str,
         repo: &str,
         tag: &str,
-    ) -> Box<dyn Future<Item = (), Error = Error<serde_json::Value>>> {
+    ) -> Box<dyn Future<Item = (), Error = Error<serde_json::Value>> + Send> {
         let configuration: &configuration::Configuration<C> = self.configuration.borrow();
 
         let method = hyper::Method::POST;
@@ -59,6 +59,8 @@ impl<T> From<serde_json::Error> for Error<T> {
 
 mod container_api;
 pub use self::container_api::{ContainerApi, ContainerApiClient};
+mod exec_api;
+pub use self::exec_api::{ExecApi, ExecApiClient};
 mod image_api;
 pub use self::image_api::{ImageApi, ImageApiClient};
 mod network_api;
@@ -48,7 +48,10 @@ pub trait SystemApi: Send + Sync {
     fn system_ping(&self) -> Box<dyn Future<Item = String, Error = Error<serde_json::Value>>>;
     fn system_version(
         &self,
-    ) -> Box<dyn Future<Item = crate::models::InlineResponse20011, Error = Error<serde_json::Value>>>;
+    ) -> Box<
+        dyn Future<Item = crate::models::InlineResponse20011, Error = Error<serde_json::Value>>
+            + Send,
+    >;
 }
 
 impl<C> SystemApi for SystemApiClient<C>
@@ -326,8 +329,10 @@ where
 
     fn system_version(
         &self,
-    ) -> Box<dyn Future<Item = crate::models::InlineResponse20011, Error = Error<serde_json::Value>>>
-    {
+    ) -> Box<
+        dyn Future<Item = crate::models::InlineResponse20011, Error = Error<serde_json::Value>>
+            + Send,
+    > {
         let configuration: &configuration::Configuration<C> = self.configuration.borrow();
 
         let method = hyper::Method::GET;
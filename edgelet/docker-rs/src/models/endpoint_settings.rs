@@ -55,9 +55,9 @@ pub struct EndpointSettings {
     // /// Gateway address for this network.
     // #[serde(rename = "Gateway", skip_serializing_if = "Option::is_none")]
     // gateway: Option<String>,
-    // /// IPv4 address.
-    // #[serde(rename = "IPAddress", skip_serializing_if = "Option::is_none")]
-    // ip_address: Option<String>,
+    /// IPv4 address.
+    #[serde(rename = "IPAddress", skip_serializing_if = "Option::is_none")]
+    ip_address: Option<String>,
     // /// Mask length of the IPv4 address.
     // #[serde(rename = "IPPrefixLen", skip_serializing_if = "Option::is_none")]
     // ip_prefix_len: Option<i32>,
@@ -93,7 +93,7 @@ impl EndpointSettings {
             network_id: None,
             // endpoint_id: None,
             // gateway: None,
-            // ip_address: None,
+            ip_address: None,
             // ip_prefix_len: None,
             // i_pv6_gateway: None,
             // global_i_pv6_address: None,
@@ -206,22 +206,22 @@ impl EndpointSettings {
     //     self.gateway = None;
     // }
 
-    // pub fn set_ip_address(&mut self, ip_address: String) {
-    //     self.ip_address = Some(ip_address);
-    // }
+    pub fn set_ip_address(&mut self, ip_address: String) {
+        self.ip_address = Some(ip_address);
+    }
 
-    // pub fn with_ip_address(mut self, ip_address: String) -> Self {
-    //     self.ip_address = Some(ip_address);
-    //     self
-    // }
+    pub fn with_ip_address(mut self, ip_address: String) -> Self {
+        self.ip_address = Some(ip_address);
+        self
+    }
 
-    // pub fn ip_address(&self) -> Option<&str> {
-    //     self.ip_address.as_ref().map(AsRef::as_ref)
-    // }
+    pub fn ip_address(&self) -> Option<&str> {
+        self.ip_address.as_ref().map(AsRef::as_ref)
+    }
 
-    // pub fn reset_ip_address(&mut self) {
-    //     self.ip_address = None;
-    // }
+    pub fn reset_ip_address(&mut self) {
+        self.ip_address = None;
+    }
 
     // pub fn set_ip_prefix_len(&mut self, ip_prefix_len: i32) {
     //     self.ip_prefix_len = Some(ip_prefix_len);
@@ -40,9 +40,9 @@ use serde_json::Value;
 
 #[derive(Debug, serde_derive::Serialize, serde_derive::Deserialize, Clone)]
 pub struct HostConfig {
-    // /// An integer value representing this container's relative CPU weight versus other containers.
-    // #[serde(rename = "CpuShares", skip_serializing_if = "Option::is_none")]
-    // cpu_shares: Option<i32>,
+    /// An integer value representing this container's relative CPU weight versus other containers.
+    #[serde(rename = "CpuShares", skip_serializing_if = "Option::is_none")]
+    cpu_shares: Option<i32>,
     /// Memory limit in bytes.
     #[serde(rename = "Memory", skip_serializing_if = "Option::is_none")]
     memory: Option<i64>,
@@ -76,12 +76,12 @@ pub struct HostConfig {
     //     skip_serializing_if = "Option::is_none"
     // )]
     // blkio_device_write_i_ops: Option<Vec<crate::models::ThrottleDevice>>,
-    // /// The length of a CPU period in microseconds.
-    // #[serde(rename = "CpuPeriod", skip_serializing_if = "Option::is_none")]
-    // cpu_period: Option<i64>,
-    // /// Microseconds of CPU time that the container can get in a CPU period.
-    // #[serde(rename = "CpuQuota", skip_serializing_if = "Option::is_none")]
-    // cpu_quota: Option<i64>,
+    /// The length of a CPU period in microseconds.
+    #[serde(rename = "CpuPeriod", skip_serializing_if = "Option::is_none")]
+    cpu_period: Option<i64>,
+    /// Microseconds of CPU time that the container can get in a CPU period.
+    #[serde(rename = "CpuQuota", skip_serializing_if = "Option::is_none")]
+    cpu_quota: Option<i64>,
     // /// The length of a CPU real-time period in microseconds. Set to 0 to allocate no time allocated to real-time tasks.
     // #[serde(rename = "CpuRealtimePeriod", skip_serializing_if = "Option::is_none")]
     // cpu_realtime_period: Option<i64>,
@@ -109,21 +109,21 @@ pub struct HostConfig {
     // /// Memory soft limit in bytes.
     // #[serde(rename = "MemoryReservation", skip_serializing_if = "Option::is_none")]
     // memory_reservation: Option<i64>,
-    // /// Total memory limit (memory + swap). Set as `-1` to enable unlimited swap.
-    // #[serde(rename = "MemorySwap", skip_serializing_if = "Option::is_none")]
-    // memory_swap: Option<i64>,
+    /// Total memory limit (memory + swap). Set as `-1` to enable unlimited swap.
+    #[serde(rename = "MemorySwap", skip_serializing_if = "Option::is_none")]
+    memory_swap: Option<i64>,
     // /// Tune a container's memory swappiness behavior. Accepts an integer between 0 and 100.
     // #[serde(rename = "MemorySwappiness", skip_serializing_if = "Option::is_none")]
     // memory_swappiness: Option<i64>,
-    // /// CPU quota in units of 10<sup>-9</sup> CPUs.
-    // #[serde(rename = "NanoCPUs", skip_serializing_if = "Option::is_none")]
-    // nano_cp_us: Option<i64>,
+    /// CPU quota in units of 10<sup>-9</sup> CPUs.
+    #[serde(rename = "NanoCPUs", skip_serializing_if = "Option::is_none")]
+    nano_cp_us: Option<i64>,
     // /// Disable OOM Killer for the container.
     // #[serde(rename = "OomKillDisable", skip_serializing_if = "Option::is_none")]
     // oom_kill_disable: Option<bool>,
-    // /// Tune a container's pids limit. Set -1 for unlimited.
-    // #[serde(rename = "PidsLimit", skip_serializing_if = "Option::is_none")]
-    // pids_limit: Option<i64>,
+    /// Tune a container's pids limit. Set -1 for unlimited.
+    #[serde(rename = "PidsLimit", skip_serializing_if = "Option::is_none")]
+    pids_limit: Option<i64>,
     // /// A list of resource limits to set in the container. For example: `{\"Name\": \"nofile\", \"Soft\": 1024, \"Hard\": 2048}`\"
     // #[serde(rename = "Ulimits", skip_serializing_if = "Option::is_none")]
     // ulimits: Option<Vec<crate::models::ResourcesUlimits>>,
@@ -147,9 +147,9 @@ pub struct HostConfig {
     // container_id_file: Option<String>,
     // #[serde(rename = "LogConfig", skip_serializing_if = "Option::is_none")]
     // log_config: Option<crate::models::HostConfigLogConfig>,
-    // /// Network mode to use for this container. Supported standard values are: `bridge`, `host`, `none`, and `container:<name|id>`. Any other value is taken as a custom network's name to which this container should connect to.
-    // #[serde(rename = "NetworkMode", skip_serializing_if = "Option::is_none")]
-    // network_mode: Option<String>,
+    /// Network mode to use for this container. Supported standard values are: `bridge`, `host`, `none`, and `container:<name|id>`. Any other value is taken as a custom network's name to which this container should connect to.
+    #[serde(rename = "NetworkMode", skip_serializing_if = "Option::is_none")]
+    network_mode: Option<String>,
     /// A map of exposed container ports and the host port they should map to.
     #[serde(rename = "PortBindings", skip_serializing_if = "Option::is_none")]
     port_bindings:
@@ -216,9 +216,9 @@ pub struct HostConfig {
     // /// A list of string values to customize labels for MLS systems, such as SELinux.
     // #[serde(rename = "SecurityOpt", skip_serializing_if = "Option::is_none")]
     // security_opt: Option<Vec<String>>,
-    // /// Storage driver options for this container, in the form `{\"size\": \"120G\"}`.
-    // #[serde(rename = "StorageOpt", skip_serializing_if = "Option::is_none")]
-    // storage_opt: Option<::std::collections::BTreeMap<String, String>>,
+    /// Storage driver options for this container, in the form `{\"size\": \"120G\"}`.
+    #[serde(rename = "StorageOpt", skip_serializing_if = "Option::is_none")]
+    storage_opt: Option<::std::collections::BTreeMap<String, String>>,
     // /// A map of container directories which should be replaced by tmpfs mounts, and their corresponding mount options. For example: `{ \"/run\": \"rw,noexec,nosuid,size=65536k\" }`.
     // #[serde(rename = "Tmpfs", skip_serializing_if = "Option::is_none")]
     // tmpfs: Option<::std::collections::BTreeMap<String, String>>,
@@ -251,7 +251,7 @@ impl HostConfig {
     /// Container configuration that depends on the host we are running on
     pub fn new() -> Self {
         HostConfig {
-            // cpu_shares: None,
+            cpu_shares: None,
             memory: None,
             // cgroup_parent: None,
             // blkio_weight: None,
@@ -260,8 +260,8 @@ impl HostConfig {
             // blkio_device_write_bps: None,
             // blkio_device_read_i_ops: None,
             // blkio_device_write_i_ops: None,
-            // cpu_period: None,
-            // cpu_quota: None,
+            cpu_period: None,
+            cpu_quota: None,
             // cpu_realtime_period: None,
             // cpu_realtime_runtime: None,
             // cpuset_cpus: None,
@@ -271,11 +271,11 @@ impl HostConfig {
             // disk_quota: None,
             // kernel_memory: None,
             // memory_reservation: None,
-            // memory_swap: None,
+            memory_swap: None,
             // memory_swappiness: None,
-            // nano_cp_us: None,
+            nano_cp_us: None,
             // oom_kill_disable: None,
-            // pids_limit: None,
+            pids_limit: None,
             // ulimits: None,
             // cpu_count: None,
             // cpu_percent: None,
@@ -284,7 +284,7 @@ impl HostConfig {
             binds: None,
             // container_id_file: None,
             // log_config: None,
-            // network_mode: None,
+            network_mode: None,
             port_bindings: None,
             // restart_policy: None,
             // auto_remove: None,
@@ -307,7 +307,7 @@ impl HostConfig {
             // publish_all_ports: None,
             // readonly_rootfs: None,
             // security_opt: None,
-            // storage_opt: None,
+            storage_opt: None,
             // tmpfs: None,
             // uts_mode: None,
             // userns_mode: None,
@@ -320,22 +320,22 @@ impl HostConfig {
         }
     }
 
-    // pub fn set_cpu_shares(&mut self, cpu_shares: i32) {
-    //     self.cpu_shares = Some(cpu_shares);
-    // }
+    pub fn set_cpu_shares(&mut self, cpu_shares: i32) {
+        self.cpu_shares = Some(cpu_shares);
+    }
 
-    // pub fn with_cpu_shares(mut self, cpu_shares: i32) -> Self {
-    //     self.cpu_shares = Some(cpu_shares);
-    //     self
-    // }
+    pub fn with_cpu_shares(mut self, cpu_shares: i32) -> Self {
+        self.cpu_shares = Some(cpu_shares);
+        self
+    }
 
-    // pub fn cpu_shares(&self) -> Option<i32> {
-    //     self.cpu_shares
-    // }
+    pub fn cpu_shares(&self) -> Option<i32> {
+        self.cpu_shares
+    }
 
-    // pub fn reset_cpu_shares(&mut self) {
-    //     self.cpu_shares = None;
-    // }
+    pub fn reset_cpu_shares(&mut self) {
+        self.cpu_shares = None;
+    }
 
     pub fn set_memory(&mut self, memory: i64) {
         self.memory = Some(memory);
@@ -503,39 +503,39 @@ impl HostConfig {
     //     self.blkio_device_write_i_ops = None;
     // }
 
-    // pub fn set_cpu_period(&mut self, cpu_period: i64) {
-    //     self.cpu_period = Some(cpu_period);
-    // }
+    pub fn set_cpu_period(&mut self, cpu_period: i64) {
+        self.cpu_period = Some(cpu_period);
+    }
 
-    // pub fn with_cpu_period(mut self, cpu_period: i64) -> Self {
-    //     self.cpu_period = Some(cpu_period);
-    //     self
-    // }
+    pub fn with_cpu_period(mut self, cpu_period: i64) -> Self {
+        self.cpu_period = Some(cpu_period);
+        self
+    }
 
-    // pub fn cpu_period(&self) -> Option<i64> {
-    //     self.cpu_period
-    // }
+    pub fn cpu_period(&self) -> Option<i64> {
+        self.cpu_period
+    }
 
-    // pub fn reset_cpu_period(&mut self) {
-    //     self.cpu_period = None;
-    // }
+    pub fn reset_cpu_period(&mut self) {
+        self.cpu_period = None;
+    }
 
-    // pub fn set_cpu_quota(&mut self, cpu_quota: i64) {
-    //     self.cpu_quota = Some(cpu_quota);
-    // }
+    pub fn set_cpu_quota(&mut self, cpu_quota: i64) {
+        self.cpu_quota = Some(cpu_quota);
+    }
 
-    // pub fn with_cpu_quota(mut self, cpu_quota: i64) -> Self {
-    //     self.cpu_quota = Some(cpu_quota);
-    //     self
-    // }
+    pub fn with_cpu_quota(mut self, cpu_quota: i64) -> Self {
+        self.cpu_quota = Some(cpu_quota);
+        self
+    }
 
-    // pub fn cpu_quota(&self) -> Option<i64> {
-    //     self.cpu_quota
-    // }
+    pub fn cpu_quota(&self) -> Option<i64> {
+        self.cpu_quota
+    }
 
-    // pub fn reset_cpu_quota(&mut self) {
-    //     self.cpu_quota = None;
-    // }
+    pub fn reset_cpu_quota(&mut self) {
+        self.cpu_quota = None;
+    }
 
     // pub fn set_cpu_realtime_period(&mut self, cpu_realtime_period: i64) {
     //     self.cpu_realtime_period = Some(cpu_realtime_period);
@@ -690,22 +690,22 @@ impl HostConfig {
     //     self.memory_reservation = None;
     // }
 
-    // pub fn set_memory_swap(&mut self, memory_swap: i64) {
-    //     self.memory_swap = Some(memory_swap);
-    // }
+    pub fn set_memory_swap(&mut self, memory_swap: i64) {
+        self.memory_swap = Some(memory_swap);
+    }
 
-    // pub fn with_memory_swap(mut self, memory_swap: i64) -> Self {
-    //     self.memory_swap = Some(memory_swap);
-    //     self
-    // }
+    pub fn with_memory_swap(mut self, memory_swap: i64) -> Self {
+        self.memory_swap = Some(memory_swap);
+        self
+    }
 
-    // pub fn memory_swap(&self) -> Option<i64> {
-    //     self.memory_swap
-    // }
+    pub fn memory_swap(&self) -> Option<i64> {
+        self.memory_swap
+    }
 
-    // pub fn reset_memory_swap(&mut self) {
-    //     self.memory_swap = None;
-    // }
+    pub fn reset_memory_swap(&mut self) {
+        self.memory_swap = None;
+    }
 
     // pub fn set_memory_swappiness(&mut self, memory_swappiness: i64) {
     //     self.memory_swappiness = Some(memory_swappiness);
@@ -724,22 +724,22 @@ impl HostConfig {
     //     self.memory_swappiness = None;
     // }
 
-    // pub fn set_nano_cp_us(&mut self, nano_cp_us: i64) {
-    //     self.nano_cp_us = Some(nano_cp_us);
-    // }
+    pub fn set_nano_cp_us(&mut self, nano_cp_us: i64) {
+        self.nano_cp_us = Some(nano_cp_us);
+    }
 
-    // pub fn with_nano_cp_us(mut self, nano_cp_us: i64) -> Self {
-    //     self.nano_cp_us = Some(nano_cp_us);
-    //     self
-    // }
+    pub fn with_nano_cp_us(mut self, nano_cp_us: i64) -> Self {
+        self.nano_cp_us = Some(nano_cp_us);
+        self
+    }
 
-    // pub fn nano_cp_us(&self) -> Option<i64> {
-    //     self.nano_cp_us
-    // }
+    pub fn nano_cp_us(&self) -> Option<i64> {
+        self.nano_cp_us
+    }
 
-    // pub fn reset_nano_cp_us(&mut self) {
-    //     self.nano_cp_us = None;
-    // }
+    pub fn reset_nano_cp_us(&mut self) {
+        self.nano_cp_us = None;
+    }
 
     // pub fn set_oom_kill_disable(&mut self, oom_kill_disable: bool) {
     //     self.oom_kill_disable = Some(oom_kill_disable);
@@ -758,22 +758,22 @@ impl HostConfig {
     //     self.oom_kill_disable = None;
     // }
 
-    // pub fn set_pids_limit(&mut self, pids_limit: i64) {
-    //     self.pids_limit = Some(pids_limit);
-    // }
+    pub fn set_pids_limit(&mut self, pids_limit: i64) {
+        self.pids_limit = Some(pids_limit);
+    }
 
-    // pub fn with_pids_limit(mut self, pids_limit: i64) -> Self {
-    //     self.pids_limit = Some(pids_limit);
-    //     self
-    // }
+    pub fn with_pids_limit(mut self, pids_limit: i64) -> Self {
+        self.pids_limit = Some(pids_limit);
+        self
+    }
 
-    // pub fn pids_limit(&self) -> Option<i64> {
-    //     self.pids_limit
-    // }
+    pub fn pids_limit(&self) -> Option<i64> {
+        self.pids_limit
+    }
 
-    // pub fn reset_pids_limit(&mut self) {
-    //     self.pids_limit = None;
-    // }
+    pub fn reset_pids_limit(&mut self) {
+        self.pids_limit = None;
+    }
 
     // pub fn set_ulimits(&mut self, ulimits: Vec<crate::models::ResourcesUlimits>) {
     //     self.ulimits = Some(ulimits);
@@ -911,22 +911,22 @@ impl HostConfig {
     //     self.log_config = None;
     // }
 
-    // pub fn set_network_mode(&mut self, network_mode: String) {
-    //     self.network_mode = Some(network_mode);
-    // }
+    pub fn set_network_mode(&mut self, network_mode: String) {
+        self.network_mode = Some(network_mode);
+    }
 
-    // pub fn with_network_mode(mut self, network_mode: String) -> Self {
-    //     self.network_mode = Some(network_mode);
-    //     self
-    // }
+    pub fn with_network_mode(mut self, network_mode: String) -> Self {
+        self.network_mode = Some(network_mode);
+        self
+    }
 
-    // pub fn network_mode(&self) -> Option<&str> {
-    //     self.network_mode.as_ref().map(AsRef::as_ref)
-    // }
+    pub fn network_mode(&self) -> Option<&str> {
+        self.network_mode.as_ref().map(AsRef::as_ref)
+    }
 
-    // pub fn reset_network_mode(&mut self) {
-    //     self.network_mode = None;
-    // }
+    pub fn reset_network_mode(&mut self) {
+        self.network_mode = None;
+    }
 
     pub fn set_port_bindings(
         &mut self,
@@ -1317,25 +1317,25 @@ impl HostConfig {
     //     self.security_opt = None;
     // }
 
-    // pub fn set_storage_opt(&mut self, storage_opt: ::std::collections::BTreeMap<String, String>) {
-    //     self.storage_opt = Some(storage_opt);
-    // }
+    pub fn set_storage_opt(&mut self, storage_opt: ::std::collections::BTreeMap<String, String>) {
+        self.storage_opt = Some(storage_opt);
+    }
 
-    // pub fn with_storage_opt(
-    //     mut self,
-    //     storage_opt: ::std::collections::BTreeMap<String, String>,
-    // ) -> Self {
-    //     self.storage_opt = Some(storage_opt);
-    //     self
-    // }
+    pub fn with_storage_opt(
+        mut self,
+        storage_opt: ::std::collections::BTreeMap<String, String>,
+    ) -> Self {
+        self.storage_opt = Some(storage_opt);
+        self
+    }
 
-    // pub fn storage_opt(&self) -> Option<&::std::collections::BTreeMap<String, String>> {
-    //     self.storage_opt.as_ref()
-    // }
+    pub fn storage_opt(&self) -> Option<&::std::collections::BTreeMap<String, String>> {
+        self.storage_opt.as_ref()
+    }
 
-    // pub fn reset_storage_opt(&mut self) {
-    //     self.storage_opt = None;
-    // }
+    pub fn reset_storage_opt(&mut self) {
+        self.storage_opt = None;
+    }
 
     // pub fn set_tmpfs(&mut self, tmpfs: ::std::collections::BTreeMap<String, String>) {
     //     self.tmpfs = Some(tmpfs);
@@ -11,25 +11,31 @@
 #[allow(unused_imports)]
 use serde_json::Value;
 
+// DEVNOTE: The image sizes and creation time are int64 in the Engine API, and the daemon
+// returns null rather than an empty list or map for RepoTags, RepoDigests and Labels of some images.
+
 #[derive(Debug, serde_derive::Serialize, serde_derive::Deserialize)]
 pub struct ImageSummary {
     #[serde(rename = "Id")]
     id: String,
     #[serde(rename = "ParentId")]
     parent_id: String,
-    #[serde(rename = "RepoTags")]
+    #[serde(rename = "RepoTags", deserialize_with = "deserialize_null_as_default")]
     repo_tags: Vec<String>,
-    #[serde(rename = "RepoDigests")]
+    #[serde(
+        rename = "RepoDigests",
+        deserialize_with = "deserialize_null_as_default"
+    )]
     repo_digests: Vec<String>,
     #[serde(rename = "Created")]
-    created: i32,
+    created: i64,
     #[serde(rename = "Size")]
-    size: i32,
+    size: i64,
     #[serde(rename = "SharedSize")]
-    shared_size: i32,
+    shared_size: i64,
     #[serde(rename = "VirtualSize")]
-    virtual_size: i32,
-    #[serde(rename = "Labels")]
+    virtual_size: i64,
+    #[serde(rename = "Labels", deserialize_with = "deserialize_null_as_default")]
     labels: ::std::collections::HashMap<String, String>,
     #[serde(rename = "Containers")]
     containers: i32,
@@ -41,10 +47,10 @@ impl ImageSummary {
         parent_id: String,
         repo_tags: Vec<String>,
         repo_digests: Vec<String>,
-        created: i32,
-        size: i32,
-        shared_size: i32,
-        virtual_size: i32,
+        created: i64,
+        size: i64,
+        shared_size: i64,
+        virtual_size: i64,
         labels: ::std::collections::HashMap<String, String>,
         containers: i32,
     ) -> Self {
@@ -114,55 +120,55 @@ impl ImageSummary {
         &self.repo_digests
     }
 
-    pub fn set_created(&mut self, created: i32) {
+    pub fn set_created(&mut self, created: i64) {
         self.created = created;
     }
 
-    pub fn with_created(mut self, created: i32) -> Self {
+    pub fn with_created(mut self, created: i64) -> Self {
         self.created = created;
         self
     }
 
-    pub fn created(&self) -> &i32 {
+    pub fn created(&self) -> &i64 {
         &self.created
     }
 
-    pub fn set_size(&mut self, size: i32) {
+    pub fn set_size(&mut self, size: i64) {
         self.size = size;
     }
 
-    pub fn with_size(mut self, size: i32) -> Self {
+    pub fn with_size(mut self, size: i64) -> Self {
         self.size = size;
         self
     }
 
-    pub fn size(&self) -> &i32 {
+    pub fn size(&self) -> &i64 {
         &self.size
     }
 
-    pub fn set_shared_size(&mut self, shared_size: i32) {
+    pub fn set_shared_size(&mut self, shared_size: i64) {
         self.shared_size = shared_size;
     }
 
-    pub fn with_shared_size(mut self, shared_size: i32) -> Self {
+    pub fn with_shared_size(mut self, shared_size: i64) -> Self {
         self.shared_size = shared_size;
         self
     }
 
-    pub fn shared_size(&self) -> &i32 {
+    pub fn shared_size(&self) -> &i64 {
         &self.shared_size
     }
 
-    pub fn set_virtual_size(&mut self, virtual_size: i32) {
+    pub fn set_virtual_size(&mut self, virtual_size: i64) {
         self.virtual_size = virtual_size;
     }
 
-    pub fn with_virtual_size(mut self, virtual_size: i32) -> Self {
+    pub fn with_virtual_size(mut self, virtual_size: i64) -> Self {
         self.virtual_size = virtual_size;
         self
     }
 
-    pub fn virtual_size(&self) -> &i32 {
+    pub fn virtual_size(&self) -> &i64 {
         &self.virtual_size
     }
 
@@ -192,3 +198,12 @@ impl ImageSummary {
         &self.containers
     }
 }
+
+fn deserialize_null_as_default<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Default + serde::Deserialize<'de>,
+{
+    let value: Option<T> = serde::Deserialize::deserialize(deserializer)?;
+    Ok(value.unwrap_or_default())
+}
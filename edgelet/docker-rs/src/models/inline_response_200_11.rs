@@ -13,6 +13,8 @@ use serde_json::Value;
 
 #[derive(Debug, serde_derive::Serialize, serde_derive::Deserialize)]
 pub struct InlineResponse20011 {
+    #[serde(rename = "Components", skip_serializing_if = "Option::is_none")]
+    components: Option<Vec<crate::models::InlineResponse20011Components>>,
     #[serde(rename = "Version", skip_serializing_if = "Option::is_none")]
     version: Option<String>,
     #[serde(rename = "ApiVersion", skip_serializing_if = "Option::is_none")]
@@ -38,6 +40,7 @@ pub struct InlineResponse20011 {
 impl InlineResponse20011 {
     pub fn new() -> Self {
         InlineResponse20011 {
+            components: None,
             version: None,
             api_version: None,
             min_api_version: None,
@@ -51,6 +54,29 @@ impl InlineResponse20011 {
         }
     }
 
+    pub fn set_components(
+        &mut self,
+        components: Vec<crate::models::InlineResponse20011Components>,
+    ) {
+        self.components = Some(components);
+    }
+
+    pub fn with_components(
+        mut self,
+        components: Vec<crate::models::InlineResponse20011Components>,
+    ) -> Self {
+        self.components = Some(components);
+        self
+    }
+
+    pub fn components(&self) -> Option<&[crate::models::InlineResponse20011Components]> {
+        self.components.as_ref().map(AsRef::as_ref)
+    }
+
+    pub fn reset_components(&mut self) {
+        self.components = None;
+    }
+
     pub fn set_version(&mut self, version: String) {
         self.version = Some(version);
     }
@@ -74,6 +74,8 @@ mod generic_resources_inner_named_resource_spec;
 pub use self::generic_resources_inner_named_resource_spec::GenericResourcesInnerNamedResourceSpec;
 mod graph_driver_data;
 pub use self::graph_driver_data::GraphDriverData;
+mod health;
+pub use self::health::Health;
 mod health_config;
 pub use self::health_config::HealthConfig;
 mod host_config_log_config;
@@ -102,6 +104,8 @@ mod inline_response_200_10;
 pub use self::inline_response_200_10::InlineResponse20010;
 mod inline_response_200_11;
 pub use self::inline_response_200_11::InlineResponse20011;
+mod inline_response_200_11_components;
+pub use self::inline_response_200_11_components::InlineResponse20011Components;
 mod inline_response_200_12;
 pub use self::inline_response_200_12::InlineResponse20012;
 mod inline_response_200_12_actor;
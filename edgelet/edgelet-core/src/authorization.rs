@@ -46,6 +46,42 @@ impl fmt::Display for AuthId {
     }
 }
 
+/// The role of a client of the management API that authenticated with a certificate, instead of
+/// as a module on the device. Each role allows everything that the roles before it allow.
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Eq,
+    Ord,
+    PartialEq,
+    PartialOrd,
+    serde_derive::Deserialize,
+    serde_derive::Serialize,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    /// Can list and inspect modules, and read their logs.
+    ReadOnly,
+
+    /// Can also start, stop and restart modules, copy files from them, and get support bundles.
+    Operator,
+
+    /// Can also create, update and delete modules and identities, deploy to the device, run
+    /// commands in modules, copy files to them, and reprovision the device.
+    Admin,
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Role::ReadOnly => write!(f, "read_only"),
+            Role::Operator => write!(f, "operator"),
+            Role::Admin => write!(f, "admin"),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum Policy {
     Anonymous,
@@ -86,7 +122,7 @@ impl Policy {
 
 #[cfg(test)]
 mod tests {
-    use crate::{AuthId, Policy};
+    use crate::{AuthId, Policy, Role};
 
     #[test]
     fn should_authorize_anonymous() {
@@ -129,4 +165,10 @@ mod tests {
         let policy = Policy::Module("abc");
         assert!(!policy.authorize(None, AuthId::Value("xyz".into())));
     }
+
+    #[test]
+    fn roles_are_ordered_by_privilege() {
+        assert!(Role::ReadOnly < Role::Operator);
+        assert!(Role::Operator < Role::Admin);
+    }
 }
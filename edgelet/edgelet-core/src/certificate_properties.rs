@@ -25,6 +25,7 @@ pub struct CertificateProperties {
     issuer: CertificateIssuer,
     dns_san_entries: Option<Vec<String>>,
     ip_entries: Option<Vec<String>>,
+    validity_in_secs: Option<i64>,
 }
 
 impl CertificateProperties {
@@ -36,6 +37,7 @@ impl CertificateProperties {
             issuer: CertificateIssuer::DefaultCa,
             dns_san_entries: None,
             ip_entries: None,
+            validity_in_secs: None,
         }
     }
 
@@ -92,6 +94,15 @@ impl CertificateProperties {
         self.ip_entries = Some(entries);
         self
     }
+
+    pub fn validity_in_secs(&self) -> Option<i64> {
+        self.validity_in_secs
+    }
+
+    pub fn with_validity_in_secs(mut self, validity_in_secs: i64) -> Self {
+        self.validity_in_secs = Some(validity_in_secs);
+        self
+    }
 }
 
 #[cfg(test)]
@@ -111,6 +122,7 @@ mod tests {
         assert_eq!("alias", c.alias());
         assert_eq!(&CertificateIssuer::DefaultCa, c.issuer());
         assert_eq!(true, c.dns_san_entries().is_none());
+        assert_eq!(None, c.validity_in_secs());
     }
 
     #[test]
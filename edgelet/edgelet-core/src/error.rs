@@ -63,6 +63,18 @@ pub enum ErrorKind {
     #[fail(display = "Invalid or unsupported certificate issuer.")]
     InvalidIssuer,
 
+    #[fail(display = "Invalid log format {:?}", _0)]
+    InvalidLogFormat(String),
+
+    #[fail(display = "Invalid log regex {:?}", _0)]
+    InvalidLogRegex(String),
+
+    #[fail(display = "Invalid log severity {:?}", _0)]
+    InvalidLogSeverity(String),
+
+    #[fail(display = "Invalid log stream {:?}", _0)]
+    InvalidLogStream(String),
+
     #[fail(display = "Invalid log tail {:?}", _0)]
     InvalidLogTail(String),
 
@@ -84,6 +96,9 @@ pub enum ErrorKind {
     #[fail(display = "An error occured when generating a random number.")]
     MakeRandom,
 
+    #[fail(display = "Module dependencies form a cycle: {}", _0)]
+    ModuleDependencyCycle(String),
+
     #[fail(display = "A module runtime error occurred.")]
     ModuleRuntime,
 
@@ -27,11 +27,12 @@ pub mod module;
 mod network;
 mod parse_since;
 pub mod settings;
+mod startup;
 mod virtualization;
 pub mod workload;
 
 pub use authentication::Authenticator;
-pub use authorization::{AuthId, ModuleId, Policy};
+pub use authorization::{AuthId, ModuleId, Policy, Role};
 pub use certificate_properties::{CertificateIssuer, CertificateProperties, CertificateType};
 pub use crypto::{
     Certificate, CreateCertificate, GetDeviceIdentityCertificate, GetIssuerAlias, KeyBytes,
@@ -39,18 +40,24 @@ pub use crypto::{
 };
 pub use error::{Error, ErrorKind};
 pub use identity::{AuthType, Identity, IdentityManager, IdentityOperation, IdentitySpec};
-pub use logs::{Chunked, LogChunk, LogDecode};
+pub use logs::{
+    decode_log_lines, filter_logs, Chunked, LogChunk, LogDecode, LogFilter, LogFormat, LogLine,
+    LogSeverity, LogStream, MergedLogs,
+};
 pub use module::{
-    DiskInfo, ImagePullPolicy, LogOptions, LogTail, MakeModuleRuntime, Module, ModuleOperation,
-    ModuleRegistry, ModuleRuntime, ModuleRuntimeErrorReason, ModuleRuntimeState, ModuleSpec,
-    ModuleStatus, ModuleTop, ProvisioningInfo, RegistryOperation, RuntimeOperation, SystemInfo,
+    DiskInfo, ExecOptions, ImageInfo, ImagePullPolicy, LogOptions, LogTail, MakeModuleRuntime,
+    Module, ModuleDependency, ModuleImage, ModuleOperation, ModuleRegistry, ModuleRuntime,
+    ModuleRuntimeErrorReason, ModuleRuntimeState, ModuleSpec, ModuleStatus, ModuleTop,
+    ProvisioningInfo, ReadinessCondition, RegistryOperation, RuntimeOperation, SystemInfo,
     SystemResources,
 };
 pub use network::{Ipam, IpamConfig, MobyNetwork, Network};
 pub use parse_since::parse_since;
 pub use settings::{
-    Connect, Endpoints, Listen, Protocol, RetryLimit, RuntimeSettings, Settings, WatchdogSettings,
+    Connect, Endpoints, ImageGarbageCollection, Listen, ManagementTls, Protocol, RetryLimit,
+    RuntimeSettings, ServerCertPolicy, Settings, WatchdogSettings,
 };
+pub use startup::{shutdown_stages, startup_sequence, stop_in_stages, ModuleStartup};
 pub use virtualization::is_virtualized_env;
 pub use workload::WorkloadConfig;
 
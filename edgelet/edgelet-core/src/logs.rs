@@ -1,15 +1,24 @@
 // Copyright (c) Microsoft. All rights reserved.
 
 use std::cmp;
+use std::fmt;
 use std::io;
+use std::str::FromStr;
 
 use bytes::{Buf, BufMut, Bytes, BytesMut, IntoBuf};
+use chrono::prelude::*;
+use failure::ResultExt;
 use futures::prelude::*;
 use futures::try_ready;
+use regex::Regex;
+use serde_derive::Serialize;
 use tokio::codec::length_delimited;
 use tokio::codec::FramedRead;
 use tokio::io::AsyncRead;
 
+use crate::error::{Error, ErrorKind, Result};
+use crate::module::LogOptions;
+
 /// Logs parser
 /// Logs are emitted with a simple header to specify stdout or stderr
 ///
@@ -34,6 +43,38 @@ pub enum LogChunk {
     Unknown(Bytes),
 }
 
+impl LogChunk {
+    pub fn payload(&self) -> &Bytes {
+        match self {
+            LogChunk::Stdin(b)
+            | LogChunk::Stdout(b)
+            | LogChunk::Stderr(b)
+            | LogChunk::Unknown(b) => b,
+        }
+    }
+
+    fn stream_type(&self) -> u8 {
+        match self {
+            LogChunk::Stdin(_) => 0,
+            LogChunk::Stdout(_) => 1,
+            LogChunk::Stderr(_) => 2,
+            LogChunk::Unknown(_) => 3,
+        }
+    }
+
+    /// Encodes this chunk back into a frame with the header understood by [`LogDecode`]
+    pub fn encode(&self) -> Bytes {
+        let payload = self.payload();
+        let mut buf = BytesMut::with_capacity(8 + payload.len());
+        buf.put_u8(self.stream_type());
+        buf.put_slice(&[0, 0, 0]);
+        #[allow(clippy::cast_possible_truncation)]
+        buf.put_u32_be(payload.len() as u32);
+        buf.put_slice(payload);
+        buf.freeze()
+    }
+}
+
 pub struct LogDecode<T: AsyncRead> {
     inner: FramedRead<T, length_delimited::LengthDelimitedCodec>,
 }
@@ -80,6 +121,449 @@ impl<T: AsyncRead> Stream for LogDecode<T> {
     }
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogStream {
+    Stdin,
+    Stdout,
+    Stderr,
+    Unknown,
+}
+
+impl<'a> From<&'a LogChunk> for LogStream {
+    fn from(chunk: &'a LogChunk) -> Self {
+        match chunk {
+            LogChunk::Stdin(_) => LogStream::Stdin,
+            LogChunk::Stdout(_) => LogStream::Stdout,
+            LogChunk::Stderr(_) => LogStream::Stderr,
+            LogChunk::Unknown(_) => LogStream::Unknown,
+        }
+    }
+}
+
+impl FromStr for LogStream {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "stdout" => Ok(LogStream::Stdout),
+            "stderr" => Ok(LogStream::Stderr),
+            _ => Err(Error::from(ErrorKind::InvalidLogStream(s.to_string()))),
+        }
+    }
+}
+
+impl fmt::Display for LogStream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            LogStream::Stdin => "stdin",
+            LogStream::Stdout => "stdout",
+            LogStream::Stderr => "stderr",
+            LogStream::Unknown => "unknown",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// Severity of a log line, ordered from most to least severe as in syslog (RFC 5424).
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogSeverity {
+    Emergency,
+    Alert,
+    Critical,
+    Error,
+    Warning,
+    Notice,
+    Info,
+    Debug,
+}
+
+impl LogSeverity {
+    fn from_code(code: u64) -> Option<Self> {
+        match code {
+            0 => Some(LogSeverity::Emergency),
+            1 => Some(LogSeverity::Alert),
+            2 => Some(LogSeverity::Critical),
+            3 => Some(LogSeverity::Error),
+            4 => Some(LogSeverity::Warning),
+            5 => Some(LogSeverity::Notice),
+            6 => Some(LogSeverity::Info),
+            7 => Some(LogSeverity::Debug),
+            _ => None,
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "emerg" | "emergency" | "panic" => Some(LogSeverity::Emergency),
+            "alert" => Some(LogSeverity::Alert),
+            "crit" | "critical" | "fatal" | "ftl" => Some(LogSeverity::Critical),
+            "err" | "error" | "eror" | "fail" => Some(LogSeverity::Error),
+            "warn" | "warning" | "wrn" => Some(LogSeverity::Warning),
+            "notice" => Some(LogSeverity::Notice),
+            "info" | "information" | "informational" | "inf" => Some(LogSeverity::Info),
+            "debug" | "dbg" | "verbose" | "vrb" | "trace" | "trce" => Some(LogSeverity::Debug),
+            _ => None,
+        }
+    }
+
+    /// Parses the severity of a log line.
+    ///
+    /// Two formats are recognized: a syslog priority prefix such as `<6>`, where the
+    /// severity is the priority modulo 8, and JSON objects with a `level`, `severity`,
+    /// `lvl` or `@l` property holding either a level name or a syslog severity number.
+    pub fn parse(line: &str) -> Option<Self> {
+        let line = line.trim_start();
+
+        if line.starts_with('<') {
+            let end = line.find('>')?;
+            let priority = line[1..end].parse::<u64>().ok()?;
+            return if priority <= 191 {
+                Self::from_code(priority % 8)
+            } else {
+                None
+            };
+        }
+
+        if line.starts_with('{') {
+            let value: serde_json::Value = serde_json::from_str(line.trim_end()).ok()?;
+            return ["level", "severity", "lvl", "@l"]
+                .iter()
+                .filter_map(|key| value.get(key))
+                .find_map(|level| match level {
+                    serde_json::Value::String(name) => Self::from_name(name),
+                    serde_json::Value::Number(code) => code.as_u64().and_then(Self::from_code),
+                    _ => None,
+                });
+        }
+
+        None
+    }
+}
+
+impl FromStr for LogSeverity {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        s.parse::<u64>()
+            .ok()
+            .map_or_else(|| Self::from_name(s), Self::from_code)
+            .ok_or_else(|| Error::from(ErrorKind::InvalidLogSeverity(s.to_string())))
+    }
+}
+
+impl fmt::Display for LogSeverity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            LogSeverity::Emergency => "emergency",
+            LogSeverity::Alert => "alert",
+            LogSeverity::Critical => "critical",
+            LogSeverity::Error => "error",
+            LogSeverity::Warning => "warning",
+            LogSeverity::Notice => "notice",
+            LogSeverity::Info => "info",
+            LogSeverity::Debug => "debug",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LogFormat {
+    /// Log frames as returned by the container runtime
+    Raw,
+    /// One JSON object per line, see [`LogLine`]
+    Ndjson,
+}
+
+impl Default for LogFormat {
+    fn default() -> Self {
+        LogFormat::Raw
+    }
+}
+
+impl FromStr for LogFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "raw" => Ok(LogFormat::Raw),
+            "ndjson" => Ok(LogFormat::Ndjson),
+            _ => Err(Error::from(ErrorKind::InvalidLogFormat(s.to_string()))),
+        }
+    }
+}
+
+impl fmt::Display for LogFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LogFormat::Raw => write!(f, "raw"),
+            LogFormat::Ndjson => write!(f, "ndjson"),
+        }
+    }
+}
+
+/// Server-side filter applied to the lines of a module's logs
+#[derive(Clone, Debug, Default)]
+pub struct LogFilter {
+    stream: Option<LogStream>,
+    contains: Option<String>,
+    regex: Option<Regex>,
+    severity: Option<LogSeverity>,
+}
+
+impl LogFilter {
+    pub fn new() -> Self {
+        LogFilter::default()
+    }
+
+    pub fn with_stream(mut self, stream: LogStream) -> Self {
+        self.stream = Some(stream);
+        self
+    }
+
+    pub fn with_contains(mut self, contains: String) -> Self {
+        self.contains = Some(contains);
+        self
+    }
+
+    pub fn with_regex(mut self, regex: &str) -> Result<Self> {
+        let regex =
+            Regex::new(regex).with_context(|_| ErrorKind::InvalidLogRegex(regex.to_string()))?;
+        self.regex = Some(regex);
+        Ok(self)
+    }
+
+    /// Only lines at least as severe as `severity` match.
+    /// Lines whose severity can't be determined never match.
+    pub fn with_severity(mut self, severity: LogSeverity) -> Self {
+        self.severity = Some(severity);
+        self
+    }
+
+    pub fn stream(&self) -> Option<LogStream> {
+        self.stream
+    }
+
+    pub fn contains(&self) -> Option<&str> {
+        self.contains.as_deref()
+    }
+
+    pub fn regex(&self) -> Option<&str> {
+        self.regex.as_ref().map(Regex::as_str)
+    }
+
+    pub fn severity(&self) -> Option<LogSeverity> {
+        self.severity
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.stream.is_none()
+            && self.contains.is_none()
+            && self.regex.is_none()
+            && self.severity.is_none()
+    }
+
+    pub fn matches(&self, line: &LogLine) -> bool {
+        self.stream.map_or(true, |stream| stream == line.stream)
+            && self
+                .contains
+                .as_ref()
+                .map_or(true, |contains| line.message.contains(contains.as_str()))
+            && self
+                .regex
+                .as_ref()
+                .map_or(true, |regex| regex.is_match(&line.message))
+            && self.severity.map_or(true, |severity| {
+                line.severity.map_or(false, |s| s <= severity)
+            })
+    }
+}
+
+/// A single decoded log line of a module
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct LogLine {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    timestamp: Option<DateTime<Utc>>,
+    module: String,
+    stream: LogStream,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    severity: Option<LogSeverity>,
+    message: String,
+}
+
+impl LogLine {
+    /// Builds a line from a decoded chunk. If `timestamps` is set, the chunk is expected to
+    /// start with the RFC 3339 timestamp and space that the container runtime adds.
+    pub fn new(module: &str, chunk: &LogChunk, timestamps: bool) -> Self {
+        let text = String::from_utf8_lossy(chunk.payload());
+        let (timestamp, message) = if timestamps {
+            split_timestamp(&text)
+        } else {
+            (None, text.as_ref())
+        };
+        let message = message.trim_end_matches(|c| c == '\n' || c == '\r');
+
+        LogLine {
+            timestamp,
+            module: module.to_string(),
+            stream: LogStream::from(chunk),
+            severity: LogSeverity::parse(message),
+            message: message.to_string(),
+        }
+    }
+
+    pub fn timestamp(&self) -> Option<DateTime<Utc>> {
+        self.timestamp
+    }
+
+    pub fn module(&self) -> &str {
+        &self.module
+    }
+
+    pub fn stream(&self) -> LogStream {
+        self.stream
+    }
+
+    pub fn severity(&self) -> Option<LogSeverity> {
+        self.severity
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    pub fn to_ndjson(&self) -> Bytes {
+        let mut line = serde_json::to_vec(self).expect("serializing a log line cannot fail");
+        line.push(b'\n');
+        Bytes::from(line)
+    }
+}
+
+fn split_timestamp(text: &str) -> (Option<DateTime<Utc>>, &str) {
+    text.find(' ')
+        .and_then(|index| {
+            DateTime::parse_from_rfc3339(&text[..index])
+                .ok()
+                .map(|timestamp| (Some(timestamp.with_timezone(&Utc)), &text[index + 1..]))
+        })
+        .unwrap_or((None, text))
+}
+
+/// Decodes a module's log stream, drops the lines that don't match `options.filter()`
+/// and re-encodes the rest in `options.format()`.
+pub fn filter_logs<S, C>(
+    module: String,
+    logs: S,
+    options: &LogOptions,
+) -> impl Stream<Item = Bytes, Error = io::Error>
+where
+    C: AsRef<[u8]>,
+    S: Stream<Item = C, Error = io::Error>,
+{
+    let filter = options.filter().clone();
+    let format = options.format();
+    let timestamps = options.timestamps();
+
+    LogDecode::new(Chunked::new(logs)).filter_map(move |chunk| {
+        let line = LogLine::new(&module, &chunk, timestamps);
+        if filter.matches(&line) {
+            Some(match format {
+                LogFormat::Raw => chunk.encode(),
+                LogFormat::Ndjson => line.to_ndjson(),
+            })
+        } else {
+            None
+        }
+    })
+}
+
+/// Decodes a module's log stream into [`LogLine`]s.
+pub fn decode_log_lines<S, C>(
+    module: String,
+    logs: S,
+    timestamps: bool,
+) -> impl Stream<Item = LogLine, Error = io::Error>
+where
+    C: AsRef<[u8]>,
+    S: Stream<Item = C, Error = io::Error>,
+{
+    LogDecode::new(Chunked::new(logs)).map(move |chunk| LogLine::new(&module, &chunk, timestamps))
+}
+
+/// Merges the log lines of several modules into a single stream ordered by timestamp.
+///
+/// A line is only emitted once every stream has either ended or has a line ready,
+/// so that the output is fully ordered. When following logs, waiting for idle modules
+/// would block the output indefinitely, so lines that are ready are emitted in
+/// timestamp order without waiting for the remaining streams.
+pub struct MergedLogs<S> {
+    streams: Vec<MergedLogsEntry<S>>,
+    follow: bool,
+}
+
+struct MergedLogsEntry<S> {
+    stream: S,
+    next: Option<LogLine>,
+    done: bool,
+}
+
+impl<S> MergedLogs<S>
+where
+    S: Stream<Item = LogLine>,
+{
+    pub fn new(streams: Vec<S>, follow: bool) -> Self {
+        let streams = streams
+            .into_iter()
+            .map(|stream| MergedLogsEntry {
+                stream,
+                next: None,
+                done: false,
+            })
+            .collect();
+        MergedLogs { streams, follow }
+    }
+}
+
+impl<S> Stream for MergedLogs<S>
+where
+    S: Stream<Item = LogLine>,
+{
+    type Item = LogLine;
+    type Error = S::Error;
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        let mut pending = false;
+
+        for entry in &mut self.streams {
+            if entry.next.is_none() && !entry.done {
+                match entry.stream.poll()? {
+                    Async::Ready(Some(line)) => entry.next = Some(line),
+                    Async::Ready(None) => entry.done = true,
+                    Async::NotReady => pending = true,
+                }
+            }
+        }
+
+        if pending && !self.follow {
+            return Ok(Async::NotReady);
+        }
+
+        let earliest = self
+            .streams
+            .iter_mut()
+            .filter(|entry| entry.next.is_some())
+            .min_by_key(|entry| entry.next.as_ref().and_then(LogLine::timestamp));
+
+        match earliest {
+            Some(entry) => Ok(Async::Ready(entry.next.take())),
+            None if pending => Ok(Async::NotReady),
+            None => Ok(Async::Ready(None)),
+        }
+    }
+}
+
 pub struct Chunked<S, C>
 where
     C: AsRef<[u8]>,
@@ -174,7 +658,10 @@ where
 
 #[cfg(test)]
 mod tests {
-    use super::{io, Bytes, Chunked, Future, LogChunk, LogDecode, Stream};
+    use super::{
+        decode_log_lines, filter_logs, io, Bytes, Chunked, Future, LogChunk, LogDecode, LogFilter,
+        LogFormat, LogLine, LogOptions, LogSeverity, LogStream, MergedLogs, Stream,
+    };
 
     use std::io::Read;
 
@@ -222,4 +709,164 @@ mod tests {
         }
         assert_eq!(b"Roses are red violets are blue", read_buffer);
     }
+
+    #[test]
+    fn encode_round_trips() {
+        let chunks = vec![
+            LogChunk::Stdout(Bytes::from("Roses are red")),
+            LogChunk::Stderr(Bytes::from("violets are blue")),
+        ];
+        let encoded: Vec<Bytes> = chunks.iter().map(LogChunk::encode).collect();
+
+        let stream = iter_ok::<Vec<Bytes>, io::Error>(encoded);
+        let decoded = LogDecode::new(Chunked::new(stream))
+            .collect()
+            .wait()
+            .unwrap();
+
+        assert_eq!(chunks, decoded);
+    }
+
+    #[test]
+    fn parse_severity() {
+        assert_eq!(
+            Some(LogSeverity::Info),
+            LogSeverity::parse("<6> 2021-01-01 hello")
+        );
+        assert_eq!(Some(LogSeverity::Error), LogSeverity::parse("<11>hello"));
+        assert_eq!(
+            Some(LogSeverity::Warning),
+            LogSeverity::parse(r#"{"level":"Warning","msg":"hello"}"#)
+        );
+        assert_eq!(
+            Some(LogSeverity::Critical),
+            LogSeverity::parse(r#"{"@l":"Fatal","@m":"hello"}"#)
+        );
+        assert_eq!(
+            Some(LogSeverity::Debug),
+            LogSeverity::parse(r#"{"severity":7}"#)
+        );
+        assert_eq!(None, LogSeverity::parse("<abc> hello"));
+        assert_eq!(None, LogSeverity::parse("<200> hello"));
+        assert_eq!(None, LogSeverity::parse("hello"));
+        assert_eq!(None, LogSeverity::parse("{ not json"));
+    }
+
+    #[test]
+    fn log_line_splits_timestamp() {
+        let chunk = LogChunk::Stderr(Bytes::from("2021-03-04T05:06:07.123456789Z <3> oops\n"));
+        let line = LogLine::new("mod1", &chunk, true);
+
+        assert_eq!(
+            "2021-03-04T05:06:07.123456789+00:00",
+            line.timestamp().unwrap().to_rfc3339()
+        );
+        assert_eq!("mod1", line.module());
+        assert_eq!(LogStream::Stderr, line.stream());
+        assert_eq!(Some(LogSeverity::Error), line.severity());
+        assert_eq!("<3> oops", line.message());
+    }
+
+    #[test]
+    fn filter_matches() {
+        let line = LogLine::new(
+            "mod1",
+            &LogChunk::Stdout(Bytes::from("<4> disk low")),
+            false,
+        );
+
+        assert!(LogFilter::new().matches(&line));
+        assert!(LogFilter::new()
+            .with_stream(LogStream::Stdout)
+            .matches(&line));
+        assert!(!LogFilter::new()
+            .with_stream(LogStream::Stderr)
+            .matches(&line));
+        assert!(LogFilter::new()
+            .with_contains("disk".to_string())
+            .matches(&line));
+        assert!(!LogFilter::new()
+            .with_contains("memory".to_string())
+            .matches(&line));
+        assert!(LogFilter::new()
+            .with_regex("d[aeiou]sk")
+            .unwrap()
+            .matches(&line));
+        assert!(LogFilter::new()
+            .with_severity(LogSeverity::Warning)
+            .matches(&line));
+        assert!(!LogFilter::new()
+            .with_severity(LogSeverity::Error)
+            .matches(&line));
+
+        let plain = LogLine::new("mod1", &LogChunk::Stdout(Bytes::from("disk low")), false);
+        assert!(!LogFilter::new()
+            .with_severity(LogSeverity::Debug)
+            .matches(&plain));
+    }
+
+    #[test]
+    fn merged_logs_ordered_by_timestamp() {
+        let module_logs = |module: &str, lines: Vec<&'static str>| {
+            let chunks: Vec<Bytes> = lines
+                .into_iter()
+                .map(|line| LogChunk::Stdout(Bytes::from(line)).encode())
+                .collect();
+            decode_log_lines(
+                module.to_string(),
+                iter_ok::<Vec<Bytes>, io::Error>(chunks),
+                true,
+            )
+        };
+        let streams = vec![
+            module_logs(
+                "mod1",
+                vec!["2021-03-04T05:06:01Z one\n", "2021-03-04T05:06:04Z four\n"],
+            ),
+            module_logs(
+                "mod2",
+                vec!["2021-03-04T05:06:02Z two\n", "2021-03-04T05:06:03Z three\n"],
+            ),
+            module_logs("mod3", vec![]),
+        ];
+
+        let merged: Vec<(String, String)> = MergedLogs::new(streams, false)
+            .map(|line| (line.module().to_string(), line.message().to_string()))
+            .collect()
+            .wait()
+            .unwrap();
+
+        assert_eq!(
+            vec![
+                ("mod1".to_string(), "one".to_string()),
+                ("mod2".to_string(), "two".to_string()),
+                ("mod2".to_string(), "three".to_string()),
+                ("mod1".to_string(), "four".to_string()),
+            ],
+            merged
+        );
+    }
+
+    #[test]
+    fn filter_logs_ndjson() {
+        let chunks = vec![
+            LogChunk::Stdout(Bytes::from("2021-03-04T05:06:07Z <6> started\n")).encode(),
+            LogChunk::Stderr(Bytes::from("2021-03-04T05:06:08Z <3> failed\n")).encode(),
+        ];
+        let options = LogOptions::new()
+            .with_timestamps(true)
+            .with_format(LogFormat::Ndjson)
+            .with_filter(LogFilter::new().with_stream(LogStream::Stderr));
+
+        let stream = iter_ok::<Vec<Bytes>, io::Error>(chunks);
+        let output = filter_logs("mod1".to_string(), stream, &options)
+            .concat2()
+            .wait()
+            .unwrap();
+
+        assert_eq!(
+            &b"{\"timestamp\":\"2021-03-04T05:06:08Z\",\"module\":\"mod1\",\"stream\":\"stderr\",\"severity\":\"error\",\"message\":\"<3> failed\"}\n"[..],
+            &output[..]
+        );
+    }
 }
@@ -3,19 +3,23 @@
 use std::collections::BTreeMap;
 use std::default::Default;
 use std::fmt;
+use std::io;
 use std::result::Result as StdResult;
 use std::str::FromStr;
 use std::string::ToString;
 use std::time::Duration;
 
+use bytes::Bytes;
 use chrono::prelude::*;
 use failure::{Fail, ResultExt};
 use futures::{Future, Stream};
 use serde_derive::Serialize;
+use tokio::io::{AsyncRead, AsyncWrite};
 
 use edgelet_utils::ensure_not_empty_with_context;
 
 use crate::error::{Error, ErrorKind, Result};
+use crate::logs::{LogFilter, LogFormat};
 use crate::settings::RuntimeSettings;
 
 #[derive(Clone, Copy, Debug, serde_derive::Deserialize, PartialEq, serde_derive::Serialize)]
@@ -148,6 +152,12 @@ pub struct ModuleSpec<T> {
     pub config: T,
     #[serde(default)]
     pub env: BTreeMap<String, String>,
+    #[serde(default)]
+    #[serde(rename = "startupOrder", skip_serializing_if = "Option::is_none")]
+    pub startup_order: Option<u32>,
+    #[serde(default)]
+    #[serde(rename = "dependsOn", skip_serializing_if = "Vec::is_empty")]
+    pub depends_on: Vec<ModuleDependency>,
 }
 
 impl<T> Clone for ModuleSpec<T>
@@ -161,6 +171,8 @@ where
             config: self.config.clone(),
             env: self.env.clone(),
             image_pull_policy: self.image_pull_policy,
+            startup_order: self.startup_order,
+            depends_on: self.depends_on.clone(),
         }
     }
 }
@@ -209,6 +221,8 @@ impl<T> ModuleSpec<T> {
             config,
             env,
             image_pull_policy,
+            startup_order: None,
+            depends_on: Vec::new(),
         })
     }
 
@@ -268,6 +282,70 @@ impl<T> ModuleSpec<T> {
         self.image_pull_policy = image_pull_policy;
         self
     }
+
+    pub fn startup_order(&self) -> Option<u32> {
+        self.startup_order
+    }
+
+    pub fn with_startup_order(mut self, startup_order: Option<u32>) -> Self {
+        self.startup_order = startup_order;
+        self
+    }
+
+    pub fn depends_on(&self) -> &[ModuleDependency] {
+        &self.depends_on
+    }
+
+    pub fn with_depends_on(mut self, depends_on: Vec<ModuleDependency>) -> Self {
+        self.depends_on = depends_on;
+        self
+    }
+}
+
+/// A module that has to be ready before the module that depends on it is started.
+#[derive(Clone, Debug, serde_derive::Deserialize, PartialEq, serde_derive::Serialize)]
+pub struct ModuleDependency {
+    name: String,
+    #[serde(flatten)]
+    condition: ReadinessCondition,
+}
+
+impl ModuleDependency {
+    pub fn new(name: String, condition: ReadinessCondition) -> Self {
+        ModuleDependency { name, condition }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn condition(&self) -> ReadinessCondition {
+        self.condition
+    }
+}
+
+/// When a module that other modules depend on counts as ready.
+#[derive(Clone, Copy, Debug, serde_derive::Deserialize, PartialEq, serde_derive::Serialize)]
+#[serde(tag = "condition", rename_all = "camelCase")]
+pub enum ReadinessCondition {
+    /// The module is running.
+    Started,
+    /// The module's container healthcheck reports it as healthy.
+    Healthy,
+    /// The module accepts TCP connections on the port.
+    TcpPort { port: u16 },
+}
+
+impl fmt::Display for ReadinessCondition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReadinessCondition::Started => write!(f, "started"),
+            ReadinessCondition::Healthy => write!(f, "healthy"),
+            ReadinessCondition::TcpPort { port } => {
+                write!(f, "accepting connections on port {}", port)
+            }
+        }
+    }
 }
 
 #[derive(Clone, Copy, Debug, PartialEq)]
@@ -307,12 +385,15 @@ impl ToString for LogTail {
     }
 }
 
-#[derive(Debug, Default)]
+#[derive(Clone, Debug, Default)]
 pub struct LogOptions {
     follow: bool,
     tail: LogTail,
     since: i32,
     until: Option<i32>,
+    timestamps: bool,
+    filter: LogFilter,
+    format: LogFormat,
 }
 
 impl LogOptions {
@@ -322,6 +403,9 @@ impl LogOptions {
             tail: LogTail::All,
             since: 0,
             until: None,
+            timestamps: false,
+            filter: LogFilter::default(),
+            format: LogFormat::Raw,
         }
     }
 
@@ -345,6 +429,21 @@ impl LogOptions {
         self
     }
 
+    pub fn with_timestamps(mut self, timestamps: bool) -> Self {
+        self.timestamps = timestamps;
+        self
+    }
+
+    pub fn with_filter(mut self, filter: LogFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    pub fn with_format(mut self, format: LogFormat) -> Self {
+        self.format = format;
+        self
+    }
+
     pub fn follow(&self) -> bool {
         self.follow
     }
@@ -360,6 +459,60 @@ impl LogOptions {
     pub fn until(&self) -> Option<i32> {
         self.until
     }
+
+    pub fn timestamps(&self) -> bool {
+        self.timestamps
+    }
+
+    pub fn filter(&self) -> &LogFilter {
+        &self.filter
+    }
+
+    pub fn format(&self) -> LogFormat {
+        self.format
+    }
+}
+
+/// A command to run in a module's container.
+#[derive(Clone, Debug, Default)]
+pub struct ExecOptions {
+    cmd: Vec<String>,
+    tty: bool,
+    stdin: bool,
+}
+
+impl ExecOptions {
+    pub fn new(cmd: Vec<String>) -> Self {
+        ExecOptions {
+            cmd,
+            tty: false,
+            stdin: false,
+        }
+    }
+
+    pub fn with_tty(mut self, tty: bool) -> Self {
+        self.tty = tty;
+        self
+    }
+
+    pub fn with_stdin(mut self, stdin: bool) -> Self {
+        self.stdin = stdin;
+        self
+    }
+
+    pub fn cmd(&self) -> &[String] {
+        &self.cmd
+    }
+
+    /// With a TTY, the command's output is a single raw stream. Without one, stdout and
+    /// stderr are multiplexed in frames with an 8-byte header.
+    pub fn tty(&self) -> bool {
+        self.tty
+    }
+
+    pub fn stdin(&self) -> bool {
+        self.stdin
+    }
 }
 
 pub trait Module {
@@ -376,11 +529,90 @@ pub trait Module {
 pub trait ModuleRegistry {
     type Error: Fail;
     type PullFuture: Future<Item = (), Error = Self::Error> + Send;
-    type RemoveFuture: Future<Item = (), Error = Self::Error>;
+    type RemoveFuture: Future<Item = (), Error = Self::Error> + Send;
+    type ListImagesFuture: Future<Item = Vec<ImageInfo>, Error = Self::Error> + Send;
     type Config;
 
     fn pull(&self, config: &Self::Config) -> Self::PullFuture;
     fn remove(&self, name: &str) -> Self::RemoveFuture;
+    fn list_images(&self) -> Self::ListImagesFuture;
+}
+
+/// Gives access to the image that a module config refers to.
+pub trait ModuleImage {
+    /// The image name the module was created from, e.g. `mcr.microsoft.com/azureiotedge-agent:1.2`
+    fn image(&self) -> &str;
+    /// The ID of the image the module's container runs, if known.
+    fn image_id(&self) -> Option<&str>;
+}
+
+/// An image stored by the module registry.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ImageInfo {
+    id: String,
+    repo_tags: Vec<String>,
+    repo_digests: Vec<String>,
+    /// Creation time in seconds since the Unix epoch
+    created: i64,
+    size: i64,
+}
+
+impl ImageInfo {
+    pub fn new(
+        id: String,
+        repo_tags: Vec<String>,
+        repo_digests: Vec<String>,
+        created: i64,
+        size: i64,
+    ) -> Self {
+        ImageInfo {
+            id,
+            repo_tags,
+            repo_digests,
+            created,
+            size,
+        }
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn repo_tags(&self) -> &[String] {
+        &self.repo_tags
+    }
+
+    pub fn repo_digests(&self) -> &[String] {
+        &self.repo_digests
+    }
+
+    pub fn created(&self) -> i64 {
+        self.created
+    }
+
+    pub fn size(&self) -> i64 {
+        self.size
+    }
+
+    /// The repositories this image is tagged in, e.g. `mcr.microsoft.com/azureiotedge-agent`
+    /// for `mcr.microsoft.com/azureiotedge-agent:1.2`. Untagged images have no repositories.
+    pub fn repositories(&self) -> impl Iterator<Item = &str> {
+        self.repo_tags.iter().filter_map(|tag| {
+            // The tag separator is the last ':' that is not part of a registry host:port.
+            let name_start = tag.rfind('/').map_or(0, |i| i + 1);
+            tag[name_start..]
+                .rfind(':')
+                .map(|i| &tag[..name_start + i])
+                .filter(|repository| *repository != "<none>")
+        })
+    }
+
+    /// Whether `reference` (an image name, a name with digest or an image ID) refers to this image.
+    pub fn is_referenced_by(&self, reference: &str) -> bool {
+        self.id == reference
+            || self.repo_tags.iter().any(|tag| tag == reference)
+            || self.repo_digests.iter().any(|digest| digest == reference)
+    }
 }
 
 #[derive(Debug, Default, Serialize)]
@@ -422,6 +654,10 @@ pub struct SystemResources {
 }
 
 impl SystemResources {
+    pub fn disks(&self) -> &[DiskInfo] {
+        &self.disks
+    }
+
     pub fn new(
         host_uptime: u64,
         process_uptime: u64,
@@ -450,6 +686,7 @@ pub struct DiskInfo {
     total_space: u64,
     file_system: String,
     file_type: String,
+    mount_point: String,
 }
 
 impl DiskInfo {
@@ -459,6 +696,7 @@ impl DiskInfo {
         total_space: u64,
         file_system: String,
         file_type: String,
+        mount_point: String,
     ) -> Self {
         DiskInfo {
             name,
@@ -466,6 +704,33 @@ impl DiskInfo {
             total_space,
             file_system,
             file_type,
+            mount_point,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn available_space(&self) -> u64 {
+        self.available_space
+    }
+
+    pub fn total_space(&self) -> u64 {
+        self.total_space
+    }
+
+    pub fn mount_point(&self) -> &str {
+        &self.mount_point
+    }
+
+    /// Percentage of the disk that is in use, or `None` if the disk reports no capacity.
+    pub fn used_percent(&self) -> Option<u64> {
+        if self.total_space == 0 {
+            None
+        } else {
+            let used = self.total_space.saturating_sub(self.available_space);
+            Some(used.saturating_mul(100) / self.total_space)
         }
     }
 }
@@ -503,8 +768,13 @@ pub trait MakeModuleRuntime {
     type ModuleRuntime: ModuleRuntime<Config = Self::Config>;
     type Error: Fail;
     type Future: Future<Item = Self::ModuleRuntime, Error = Self::Error> + Send;
+    type ReloadFuture: Future<Item = (), Error = Self::Error> + Send;
 
     fn make_runtime(settings: Self::Settings) -> Self::Future;
+
+    /// Applies the settings that a runtime can change while it's running, like content trust
+    /// for container engines.
+    fn reload(runtime: &Self::ModuleRuntime, settings: &Self::Settings) -> Self::ReloadFuture;
 }
 
 pub trait ModuleRuntime: Sized {
@@ -515,6 +785,7 @@ pub trait ModuleRuntime: Sized {
     type ModuleRegistry: ModuleRegistry<Config = Self::Config, Error = Self::Error>;
     type Chunk: AsRef<[u8]>;
     type Logs: Stream<Item = Self::Chunk, Error = Self::Error> + Send;
+    type Archive: Stream<Item = Self::Chunk, Error = Self::Error> + Send;
 
     type CreateFuture: Future<Item = (), Error = Self::Error> + Send;
     type GetFuture: Future<Item = (Self::Module, ModuleRuntimeState), Error = Self::Error> + Send;
@@ -529,7 +800,14 @@ pub trait ModuleRuntime: Sized {
     type SystemInfoFuture: Future<Item = SystemInfo, Error = Self::Error> + Send;
     type SystemResourcesFuture: Future<Item = SystemResources, Error = Self::Error> + Send;
     type RemoveAllFuture: Future<Item = (), Error = Self::Error> + Send;
+    type StartAllFuture: Future<Item = (), Error = Self::Error> + Send;
     type StopAllFuture: Future<Item = (), Error = Self::Error> + Send;
+    type ExecIo: AsyncRead + AsyncWrite + Send + 'static;
+    type ExecExitCodeFuture: Future<Item = Option<i32>, Error = Self::Error> + Send + 'static;
+    type ExecFuture: Future<Item = (Self::ExecIo, Self::ExecExitCodeFuture), Error = Self::Error>
+        + Send;
+    type GetArchiveFuture: Future<Item = Self::Archive, Error = Self::Error> + Send;
+    type PutArchiveFuture: Future<Item = (), Error = Self::Error> + Send;
 
     fn create(&self, module: ModuleSpec<Self::Config>) -> Self::CreateFuture;
     fn get(&self, id: &str) -> Self::GetFuture;
@@ -544,7 +822,17 @@ pub trait ModuleRuntime: Sized {
     fn logs(&self, id: &str, options: &LogOptions) -> Self::LogsFuture;
     fn registry(&self) -> &Self::ModuleRegistry;
     fn remove_all(&self) -> Self::RemoveAllFuture;
+    fn start_all(&self) -> Self::StartAllFuture;
     fn stop_all(&self, wait_before_kill: Option<Duration>) -> Self::StopAllFuture;
+    /// Runs a command in a module. The command's stdin, stdout and stderr are read from and
+    /// written to the returned IO, and its exit code is known once its output has ended.
+    fn exec(&self, id: &str, options: &ExecOptions) -> Self::ExecFuture;
+    /// Returns a tar archive of a file or directory in a module.
+    fn get_archive(&self, id: &str, path: &str) -> Self::GetArchiveFuture;
+    /// Extracts a tar archive into a directory in a module.
+    fn put_archive<S>(&self, id: &str, path: &str, archive: S) -> Self::PutArchiveFuture
+    where
+        S: Stream<Item = Bytes, Error = io::Error> + Send + 'static;
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -570,6 +858,8 @@ impl fmt::Display for ModuleOperation {
 // Useful for error contexts
 #[derive(Clone, Debug)]
 pub enum RegistryOperation {
+    ImportImage(String),
+    ListImages,
     PullImage(String),
     RemoveImage(String),
 }
@@ -577,6 +867,8 @@ pub enum RegistryOperation {
 impl fmt::Display for RegistryOperation {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
+            RegistryOperation::ImportImage(name) => write!(f, "Could not import image {}", name),
+            RegistryOperation::ListImages => write!(f, "Could not list images"),
             RegistryOperation::PullImage(name) => write!(f, "Could not pull image {}", name),
             RegistryOperation::RemoveImage(name) => write!(f, "Could not remove image {}", name),
         }
@@ -587,14 +879,18 @@ impl fmt::Display for RegistryOperation {
 #[derive(Clone, Debug, PartialEq)]
 pub enum RuntimeOperation {
     CreateModule(String),
+    ExecModule(String),
     GetModule(String),
+    GetModuleArchive(String),
     GetModuleLogs(String),
     GetSupportBundle,
     Init,
     ListModules,
+    PutModuleArchive(String),
     RemoveModule(String),
     RestartModule(String),
     StartModule(String),
+    StartModules,
     StopModule(String),
     SystemInfo,
     SystemResources,
@@ -605,16 +901,26 @@ impl fmt::Display for RuntimeOperation {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             RuntimeOperation::CreateModule(name) => write!(f, "Could not create module {}", name),
+            RuntimeOperation::ExecModule(name) => {
+                write!(f, "Could not run command in module {}", name)
+            }
             RuntimeOperation::GetModule(name) => write!(f, "Could not get module {}", name),
+            RuntimeOperation::GetModuleArchive(name) => {
+                write!(f, "Could not copy files from module {}", name)
+            }
             RuntimeOperation::GetModuleLogs(name) => {
                 write!(f, "Could not get logs for module {}", name)
             }
             RuntimeOperation::GetSupportBundle => write!(f, "Could not get support bundle"),
             RuntimeOperation::Init => write!(f, "Could not initialize module runtime"),
             RuntimeOperation::ListModules => write!(f, "Could not list modules"),
+            RuntimeOperation::PutModuleArchive(name) => {
+                write!(f, "Could not copy files to module {}", name)
+            }
             RuntimeOperation::RemoveModule(name) => write!(f, "Could not remove module {}", name),
             RuntimeOperation::RestartModule(name) => write!(f, "Could not restart module {}", name),
             RuntimeOperation::StartModule(name) => write!(f, "Could not start module {}", name),
+            RuntimeOperation::StartModules => write!(f, "Could not start modules"),
             RuntimeOperation::StopModule(name) => write!(f, "Could not stop module {}", name),
             RuntimeOperation::SystemInfo => write!(f, "Could not query system info"),
             RuntimeOperation::SystemResources => write!(f, "Could not query system resources"),
@@ -653,7 +959,9 @@ impl FromStr for ImagePullPolicy {
 
 #[cfg(test)]
 mod tests {
-    use super::{BTreeMap, Default, ImagePullPolicy, ModuleSpec};
+    use super::{
+        BTreeMap, Default, ImagePullPolicy, ModuleDependency, ModuleSpec, ReadinessCondition,
+    };
 
     use std::str::FromStr;
     use std::string::ToString;
@@ -769,4 +1077,31 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn module_spec_depends_on_deser() {
+        let spec: ModuleSpec<i32> = serde_json::from_value(serde_json::json!({
+            "name": "consumer",
+            "type": "docker",
+            "config": 10,
+            "startupOrder": 2,
+            "dependsOn": [
+                { "name": "db", "condition": "healthy" },
+                { "name": "broker", "condition": "tcpPort", "port": 1883 },
+            ],
+        }))
+        .unwrap();
+
+        assert_eq!(Some(2), spec.startup_order());
+        assert_eq!(
+            &[
+                ModuleDependency::new("db".to_string(), ReadinessCondition::Healthy),
+                ModuleDependency::new(
+                    "broker".to_string(),
+                    ReadinessCondition::TcpPort { port: 1883 }
+                ),
+            ],
+            spec.depends_on()
+        );
+    }
 }
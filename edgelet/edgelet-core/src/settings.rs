@@ -1,14 +1,18 @@
 // Copyright (c) Microsoft. All rights reserved.
 
 use std::cmp::Ordering;
+use std::collections::BTreeMap;
 use std::convert::TryInto;
 use std::fmt::Display;
+use std::net::IpAddr;
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
+use std::time::Duration;
 
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use url::Url;
 
+use crate::authorization::Role;
 use crate::module::ModuleSpec;
 
 #[derive(Clone, Debug, serde_derive::Deserialize, serde_derive::Serialize)]
@@ -61,6 +65,8 @@ pub struct Listen {
     pub management_uri: Url,
     #[serde(default = "Protocol::default")]
     pub min_tls_version: Protocol,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub management_tls: Option<ManagementTls>,
 }
 
 impl Listen {
@@ -75,6 +81,10 @@ impl Listen {
     pub fn min_tls_version(&self) -> Protocol {
         self.min_tls_version
     }
+
+    pub fn management_tls(&self) -> Option<&ManagementTls> {
+        self.management_tls.as_ref()
+    }
 }
 
 impl Default for Listen {
@@ -102,10 +112,46 @@ impl Default for Listen {
                 .parse()
                 .expect("hard-coded url::Url must parse successfully"),
             min_tls_version: Default::default(),
+            management_tls: None,
         }
     }
 }
 
+/// An additional listener for the management API that remote clients connect to over TLS.
+/// Clients authenticate with a certificate issued by `client_ca`, and the common name of its
+/// subject selects the client's role from `roles`.
+#[derive(Clone, Debug, serde_derive::Deserialize, serde_derive::Serialize)]
+pub struct ManagementTls {
+    pub uri: Url,
+    pub cert: PathBuf,
+    pub key: PathBuf,
+    pub client_ca: PathBuf,
+    #[serde(default)]
+    pub roles: BTreeMap<String, Role>,
+}
+
+impl ManagementTls {
+    pub fn uri(&self) -> &Url {
+        &self.uri
+    }
+
+    pub fn cert(&self) -> &Path {
+        &self.cert
+    }
+
+    pub fn key(&self) -> &Path {
+        &self.key
+    }
+
+    pub fn client_ca(&self) -> &Path {
+        &self.client_ca
+    }
+
+    pub fn roles(&self) -> &BTreeMap<String, Role> {
+        &self.roles
+    }
+}
+
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Protocol {
     Tls10,
@@ -300,6 +346,199 @@ impl WatchdogSettings {
     }
 }
 
+/// Settings for the periodic removal of module images that are no longer used.
+#[derive(Clone, Debug, PartialEq, serde_derive::Deserialize, serde_derive::Serialize)]
+#[serde(default)]
+pub struct ImageGarbageCollection {
+    /// Whether unused images are removed. Defaults to false.
+    pub enabled: bool,
+
+    /// How often unused images are looked for, e.g. "1d" or "12h".
+    #[serde(with = "humantime_duration")]
+    pub cleanup_recurrence: Duration,
+
+    /// Number of most recent images to keep per repository, even if no module uses them.
+    pub keep_versions: usize,
+
+    /// If set, images are only removed while the disk holding the image store is
+    /// at least this percent full.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub disk_high_water_mark: Option<u8>,
+
+    /// A path on the disk that holds the container engine's image store.
+    pub image_store_path: PathBuf,
+}
+
+impl Default for ImageGarbageCollection {
+    fn default() -> Self {
+        ImageGarbageCollection {
+            enabled: false,
+            cleanup_recurrence: Duration::from_secs(24 * 60 * 60),
+            keep_versions: 2,
+            disk_high_water_mark: None,
+            image_store_path: PathBuf::from("/var/lib/docker"),
+        }
+    }
+}
+
+impl ImageGarbageCollection {
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn cleanup_recurrence(&self) -> Duration {
+        self.cleanup_recurrence
+    }
+
+    pub fn keep_versions(&self) -> usize {
+        self.keep_versions
+    }
+
+    pub fn disk_high_water_mark(&self) -> Option<u8> {
+        self.disk_high_water_mark
+    }
+
+    pub fn image_store_path(&self) -> &Path {
+        &self.image_store_path
+    }
+}
+
+/// Limits on the server certificates that a module may request from the workload API,
+/// beyond the certificate for its common name and module ID.
+#[derive(Clone, Debug, Default, PartialEq, serde_derive::Deserialize, serde_derive::Serialize)]
+pub struct ServerCertPolicy {
+    /// DNS names that may be requested as SANs. A name starting with `*.` matches any
+    /// name with exactly one more label, e.g. `*.contoso.local` matches `api.contoso.local`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allowed_dns_names: Vec<String>,
+
+    /// IP addresses that may be requested as SANs, or networks in CIDR notation.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allowed_ip_addresses: Vec<String>,
+
+    /// The longest validity that may be requested, e.g. "30d". Without it, the module's
+    /// certificates get the validity that certd issues certificates with.
+    #[serde(
+        default,
+        with = "humantime_duration::option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub max_validity: Option<Duration>,
+}
+
+impl ServerCertPolicy {
+    pub fn allowed_dns_names(&self) -> &[String] {
+        &self.allowed_dns_names
+    }
+
+    pub fn allowed_ip_addresses(&self) -> &[String] {
+        &self.allowed_ip_addresses
+    }
+
+    pub fn max_validity(&self) -> Option<Duration> {
+        self.max_validity
+    }
+
+    /// A requested wildcard name is only allowed if it is listed itself.
+    pub fn allows_dns_name(&self, name: &str) -> bool {
+        let name = name.trim_end_matches('.').to_ascii_lowercase();
+        self.allowed_dns_names.iter().any(|pattern| {
+            let pattern = pattern.trim_end_matches('.').to_ascii_lowercase();
+            if name == pattern {
+                return true;
+            }
+
+            match (pattern.strip_prefix("*."), name.find('.')) {
+                (Some(suffix), Some(index)) => {
+                    index > 0 && !name[..index].contains('*') && name[index + 1..] == *suffix
+                }
+                _ => false,
+            }
+        })
+    }
+
+    pub fn allows_ip_address(&self, address: IpAddr) -> bool {
+        self.allowed_ip_addresses.iter().any(|allowed| {
+            let (network, prefix_len) = match allowed.find('/') {
+                Some(index) => match allowed[index + 1..].parse::<u32>() {
+                    Ok(prefix_len) => (&allowed[..index], prefix_len),
+                    Err(_) => return false,
+                },
+                None => (allowed.as_str(), 128),
+            };
+            match (network.parse::<IpAddr>(), address) {
+                (Ok(IpAddr::V4(network)), IpAddr::V4(address)) => {
+                    let prefix_len = prefix_len.min(32);
+                    let mask = u32::MAX.checked_shl(32 - prefix_len).unwrap_or(0);
+                    u32::from(network) & mask == u32::from(address) & mask
+                }
+                (Ok(IpAddr::V6(network)), IpAddr::V6(address)) => {
+                    let prefix_len = prefix_len.min(128);
+                    let mask = u128::MAX.checked_shl(128 - prefix_len).unwrap_or(0);
+                    u128::from(network) & mask == u128::from(address) & mask
+                }
+                _ => false,
+            }
+        })
+    }
+}
+
+mod humantime_duration {
+    use std::time::Duration;
+
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub(super) fn serialize<S>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&humantime::format_duration(*duration).to_string())
+    }
+
+    pub(super) fn deserialize<'de, D>(deserializer: D) -> Result<Duration, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        let duration = humantime::parse_duration(&s).map_err(serde::de::Error::custom)?;
+        if duration == Duration::from_secs(0) {
+            return Err(serde::de::Error::custom(
+                "duration must be greater than zero",
+            ));
+        }
+        Ok(duration)
+    }
+
+    pub(super) mod option {
+        use std::time::Duration;
+
+        use serde::{Deserializer, Serializer};
+
+        #[allow(clippy::trivially_copy_pass_by_ref)]
+        pub(in super::super) fn serialize<S>(
+            duration: &Option<Duration>,
+            serializer: S,
+        ) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+        {
+            match duration {
+                Some(duration) => super::serialize(duration, serializer),
+                None => serializer.serialize_none(),
+            }
+        }
+
+        pub(in super::super) fn deserialize<'de, D>(
+            deserializer: D,
+        ) -> Result<Option<Duration>, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            super::deserialize(deserializer).map(Some)
+        }
+    }
+}
+
 pub trait RuntimeSettings {
     type Config;
 
@@ -310,12 +549,16 @@ pub trait RuntimeSettings {
     fn listen(&self) -> &Listen;
     fn homedir(&self) -> &Path;
     fn watchdog(&self) -> &WatchdogSettings;
+    fn image_garbage_collection(&self) -> &ImageGarbageCollection;
+    fn server_cert_policies(&self) -> &BTreeMap<String, ServerCertPolicy>;
+    fn module_secrets(&self) -> &BTreeMap<String, BTreeMap<String, String>>;
     fn endpoints(&self) -> &Endpoints;
     fn edge_ca_cert(&self) -> Option<&str>;
     fn edge_ca_key(&self) -> Option<&str>;
     fn trust_bundle_cert(&self) -> Option<&str>;
     fn manifest_trust_bundle_cert(&self) -> Option<&str>;
     fn auto_reprovisioning_mode(&self) -> &AutoReprovisioningMode;
+    fn log_level(&self) -> Option<&str>;
 }
 
 #[derive(Clone, Debug, serde_derive::Deserialize, serde_derive::Serialize)]
@@ -358,6 +601,24 @@ pub struct Settings<T> {
     #[serde(default)]
     pub watchdog: WatchdogSettings,
 
+    #[serde(default)]
+    pub image_garbage_collection: ImageGarbageCollection,
+
+    /// The daemon's log level, in the same format as the `IOTEDGE_LOG` environment variable,
+    /// which is applied on top of it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub log_level: Option<String>,
+
+    /// Map of module IDs to the server certificates that they may request.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub server_cert_policies: BTreeMap<String, ServerCertPolicy>,
+
+    /// Map of module IDs to the secrets that their secret store starts with. A secret is
+    /// stored again only when its configured value changes, so changes that a module
+    /// makes to it are kept until then.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub module_secrets: BTreeMap<String, BTreeMap<String, String>>,
+
     /// Map of service names to endpoint URIs.
     ///
     /// Only configurable in debug builds for the sake of tests.
@@ -400,6 +661,18 @@ where
         &self.watchdog
     }
 
+    fn image_garbage_collection(&self) -> &ImageGarbageCollection {
+        &self.image_garbage_collection
+    }
+
+    fn server_cert_policies(&self) -> &BTreeMap<String, ServerCertPolicy> {
+        &self.server_cert_policies
+    }
+
+    fn module_secrets(&self) -> &BTreeMap<String, BTreeMap<String, String>> {
+        &self.module_secrets
+    }
+
     fn endpoints(&self) -> &Endpoints {
         &self.endpoints
     }
@@ -423,6 +696,10 @@ where
     fn auto_reprovisioning_mode(&self) -> &AutoReprovisioningMode {
         &self.auto_reprovisioning_mode
     }
+
+    fn log_level(&self) -> Option<&str> {
+        self.log_level.as_deref()
+    }
 }
 
 #[derive(Clone, Debug, PartialEq, serde_derive::Deserialize, serde_derive::Serialize)]
@@ -461,7 +738,7 @@ impl Endpoints {
 mod tests {
     use test_case::test_case;
 
-    use super::{FromStr, Protocol};
+    use super::{FromStr, Protocol, ServerCertPolicy};
 
     #[test_case("tls", Protocol::Tls10; "when tls provided")]
     #[test_case("tls1", Protocol::Tls10; "when tls1 with dot provided")]
@@ -495,4 +772,46 @@ mod tests {
             Err(format!("Unsupported TLS protocol version: {}", value))
         )
     }
+
+    #[test]
+    fn server_cert_policy_matches_dns_names() {
+        let policy = ServerCertPolicy {
+            allowed_dns_names: vec![
+                "api.contoso.local".to_owned(),
+                "*.edge.contoso.local".to_owned(),
+            ],
+            ..Default::default()
+        };
+
+        assert!(policy.allows_dns_name("api.contoso.local"));
+        assert!(policy.allows_dns_name("API.Contoso.Local."));
+        assert!(policy.allows_dns_name("sensor.edge.contoso.local"));
+        assert!(!policy.allows_dns_name("edge.contoso.local"));
+        assert!(!policy.allows_dns_name("a.sensor.edge.contoso.local"));
+        assert!(!policy.allows_dns_name("www.contoso.local"));
+        assert!(policy.allows_dns_name("*.edge.contoso.local"));
+        assert!(!policy.allows_dns_name("*.contoso.local"));
+        assert!(!ServerCertPolicy::default().allows_dns_name("api.contoso.local"));
+    }
+
+    #[test]
+    fn server_cert_policy_matches_ip_addresses() {
+        let policy = ServerCertPolicy {
+            allowed_ip_addresses: vec![
+                "10.0.0.5".to_owned(),
+                "192.168.1.0/24".to_owned(),
+                "fd00::/64".to_owned(),
+                "172.16.0.0/not-a-prefix".to_owned(),
+            ],
+            ..Default::default()
+        };
+
+        assert!(policy.allows_ip_address("10.0.0.5".parse().unwrap()));
+        assert!(!policy.allows_ip_address("10.0.0.6".parse().unwrap()));
+        assert!(policy.allows_ip_address("192.168.1.200".parse().unwrap()));
+        assert!(!policy.allows_ip_address("192.168.2.1".parse().unwrap()));
+        assert!(policy.allows_ip_address("fd00::1234".parse().unwrap()));
+        assert!(!policy.allows_ip_address("fd00:0:0:1::1".parse().unwrap()));
+        assert!(!policy.allows_ip_address("172.16.0.1".parse().unwrap()));
+    }
 }
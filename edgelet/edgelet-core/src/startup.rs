@@ -0,0 +1,287 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::time::{Duration, Instant};
+
+use futures::{future, stream, Future, IntoFuture, Stream};
+
+use crate::error::{Error, ErrorKind};
+use crate::module::ModuleDependency;
+
+/// The startup order and dependencies of a module.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ModuleStartup {
+    name: String,
+    order: Option<u32>,
+    depends_on: Vec<ModuleDependency>,
+}
+
+impl ModuleStartup {
+    pub fn new(name: String, order: Option<u32>, depends_on: Vec<ModuleDependency>) -> Self {
+        ModuleStartup {
+            name,
+            order,
+            depends_on,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn order(&self) -> Option<u32> {
+        self.order
+    }
+
+    pub fn depends_on(&self) -> &[ModuleDependency] {
+        &self.depends_on
+    }
+
+    fn key(&self) -> (u32, String) {
+        (self.order.unwrap_or(u32::max_value()), self.name.clone())
+    }
+}
+
+/// Sorts modules in the order they should be started in.
+///
+/// A module comes after the modules it depends on. Otherwise modules are
+/// sorted by their startup order, with modules that don't have one last, and
+/// then by name. Dependencies on modules that aren't in `modules` are ignored.
+/// Modules should be stopped in the reverse order.
+pub fn startup_sequence(modules: Vec<ModuleStartup>) -> Result<Vec<ModuleStartup>, Error> {
+    let mut pending: BTreeMap<(u32, String), ModuleStartup> =
+        modules.into_iter().map(|m| (m.key(), m)).collect();
+    let names: BTreeSet<String> = pending.keys().map(|(_, name)| name.clone()).collect();
+    let mut started: BTreeSet<String> = BTreeSet::new();
+    let mut sequence = Vec::with_capacity(pending.len());
+
+    while !pending.is_empty() {
+        // `pending` is ordered by key, so the first module whose dependencies
+        // have all been started is the next one to start.
+        let next = pending
+            .iter()
+            .find(|(_, module)| {
+                module.depends_on.iter().all(|dependency| {
+                    !names.contains(dependency.name()) || started.contains(dependency.name())
+                })
+            })
+            .map(|(key, _)| key.clone());
+
+        if let Some(key) = next {
+            let module = pending.remove(&key).expect("key was just found in pending");
+            started.insert(module.name.clone());
+            sequence.push(module);
+        } else {
+            let cycle = pending
+                .keys()
+                .map(|(_, name)| name.as_str())
+                .collect::<Vec<_>>()
+                .join(", ");
+            return Err(Error::from(ErrorKind::ModuleDependencyCycle(cycle)));
+        }
+    }
+
+    Ok(sequence)
+}
+
+/// Groups the modules of a startup sequence into the stages they should be stopped in.
+///
+/// Stages come in the reverse of the startup sequence. The modules of a stage share a
+/// startup order and don't depend on each other, so they can be stopped at the same time.
+pub fn shutdown_stages(sequence: Vec<ModuleStartup>) -> Vec<Vec<ModuleStartup>> {
+    let mut stages: Vec<Vec<ModuleStartup>> = vec![];
+
+    for module in sequence.into_iter().rev() {
+        let joins_stage = stages.last().map_or(false, |stage| {
+            stage.iter().all(|other| {
+                other.order == module.order
+                    && other
+                        .depends_on
+                        .iter()
+                        .all(|dependency| dependency.name() != module.name)
+            })
+        });
+
+        match stages.last_mut() {
+            Some(stage) if joins_stage => stage.push(module),
+            _ => stages.push(vec![module]),
+        }
+    }
+
+    stages
+}
+
+/// Stops the modules of each stage at the same time, one stage after the other.
+///
+/// `wait_before_kill` is the time that all stages get together, so that stopping every module
+/// takes about that long at most. Each stage gets an even share of the time that the stages
+/// before it left, and `stop` is called with that share for each of its modules.
+pub fn stop_in_stages<F, T>(
+    stages: Vec<Vec<String>>,
+    wait_before_kill: Option<Duration>,
+    stop: F,
+) -> impl Future<Item = (), Error = T::Error>
+where
+    F: Fn(String, Option<Duration>) -> T,
+    T: IntoFuture<Item = ()>,
+{
+    let deadline = wait_before_kill.map(|wait_before_kill| Instant::now() + wait_before_kill);
+    let stage_count = stages.len();
+    stream::iter_ok(stages.into_iter().enumerate()).for_each(move |(i, stage)| {
+        #[allow(clippy::cast_possible_truncation)]
+        let wait_before_kill = deadline.map(|deadline| {
+            deadline.saturating_duration_since(Instant::now()) / (stage_count - i) as u32
+        });
+        future::join_all(stage.into_iter().map(|name| stop(name, wait_before_kill))).map(|_| ())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+    use std::time::Duration;
+
+    use futures::Future;
+
+    use super::{shutdown_stages, startup_sequence, stop_in_stages, ModuleStartup};
+    use crate::error::ErrorKind;
+    use crate::module::{ModuleDependency, ReadinessCondition};
+
+    fn module(name: &str, order: Option<u32>, depends_on: &[&str]) -> ModuleStartup {
+        ModuleStartup::new(
+            name.to_string(),
+            order,
+            depends_on
+                .iter()
+                .map(|d| ModuleDependency::new((*d).to_string(), ReadinessCondition::Started))
+                .collect(),
+        )
+    }
+
+    fn names(sequence: &[ModuleStartup]) -> Vec<&str> {
+        sequence.iter().map(ModuleStartup::name).collect()
+    }
+
+    #[test]
+    fn sorts_by_order_then_name() {
+        let sequence = startup_sequence(vec![
+            module("c", None, &[]),
+            module("b", Some(1), &[]),
+            module("a", None, &[]),
+            module("d", Some(0), &[]),
+            module("e", Some(1), &[]),
+        ])
+        .unwrap();
+
+        assert_eq!(vec!["d", "b", "e", "a", "c"], names(&sequence));
+    }
+
+    #[test]
+    fn dependencies_start_first() {
+        let sequence = startup_sequence(vec![
+            module("broker", Some(5), &["db"]),
+            module("consumer", Some(0), &["broker"]),
+            module("db", None, &[]),
+            module("other", Some(1), &[]),
+        ])
+        .unwrap();
+
+        assert_eq!(vec!["other", "db", "broker", "consumer"], names(&sequence));
+    }
+
+    #[test]
+    fn missing_dependencies_are_ignored() {
+        let sequence = startup_sequence(vec![
+            module("a", Some(1), &["missing"]),
+            module("b", Some(0), &[]),
+        ])
+        .unwrap();
+
+        assert_eq!(vec!["b", "a"], names(&sequence));
+    }
+
+    #[test]
+    fn modules_with_the_same_order_stop_together() {
+        let sequence = startup_sequence(vec![
+            module("a", None, &[]),
+            module("b", Some(1), &[]),
+            module("c", Some(1), &[]),
+            module("d", Some(0), &[]),
+            module("e", None, &[]),
+        ])
+        .unwrap();
+
+        let stages: Vec<Vec<&str>> = shutdown_stages(sequence)
+            .iter()
+            .map(|stage| names(stage))
+            .collect();
+        assert_eq!(vec![vec!["e", "a"], vec!["c", "b"], vec!["d"]], stages);
+    }
+
+    #[test]
+    fn dependents_stop_before_their_dependencies() {
+        let sequence = startup_sequence(vec![
+            module("broker", Some(1), &["db"]),
+            module("consumer", Some(1), &["broker"]),
+            module("db", Some(1), &[]),
+            module("other", Some(1), &[]),
+        ])
+        .unwrap();
+
+        let stages: Vec<Vec<&str>> = shutdown_stages(sequence)
+            .iter()
+            .map(|stage| names(stage))
+            .collect();
+        assert_eq!(
+            vec![vec!["other", "consumer"], vec!["broker"], vec!["db"]],
+            stages
+        );
+    }
+
+    #[test]
+    fn stages_share_the_time_to_stop() {
+        let stopped = Mutex::new(vec![]);
+        stop_in_stages(
+            vec![
+                vec!["a".to_string(), "b".to_string()],
+                vec!["c".to_string()],
+            ],
+            Some(Duration::from_secs(20)),
+            |name, wait_before_kill| -> Result<(), ()> {
+                stopped
+                    .lock()
+                    .unwrap()
+                    .push((name, wait_before_kill.unwrap()));
+                Ok(())
+            },
+        )
+        .wait()
+        .unwrap();
+
+        let stopped = stopped.into_inner().unwrap();
+        let names: Vec<&str> = stopped.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(vec!["a", "b", "c"], names);
+
+        // The first stage gets half of the time, and the last one all that's left.
+        assert!(stopped[0].1 <= Duration::from_secs(10));
+        assert!(stopped[0].1 > Duration::from_secs(9));
+        assert!(stopped[2].1 <= Duration::from_secs(20));
+        assert!(stopped[2].1 > Duration::from_secs(19));
+    }
+
+    #[test]
+    fn cycle_fails() {
+        let err = startup_sequence(vec![
+            module("a", None, &["c"]),
+            module("b", None, &["a"]),
+            module("c", None, &["b"]),
+            module("d", None, &[]),
+        ])
+        .unwrap_err();
+
+        match err.kind() {
+            ErrorKind::ModuleDependencyCycle(cycle) => assert_eq!("a, b, c", cycle),
+            kind => panic!("unexpected error kind {:?}", kind),
+        }
+    }
+}
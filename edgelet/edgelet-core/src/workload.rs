@@ -1,6 +1,7 @@
 // Copyright (c) Microsoft. All rights reserved.
 
 use crate::certificate_properties::CertificateType;
+use crate::settings::ServerCertPolicy;
 
 /// Trait to obtain configuration data needed by any implementation of the workload interface
 /// for module identity and certificate management.
@@ -12,4 +13,5 @@ pub trait WorkloadConfig {
     fn trust_bundle_cert(&self) -> &str;
     fn manifest_trust_bundle_cert(&self) -> &str;
     fn get_cert_max_duration(&self, cert_type: CertificateType) -> i64;
+    fn server_cert_policy(&self, module_id: &str) -> Option<&ServerCertPolicy>;
 }
@@ -3,7 +3,7 @@
 use failure::ResultExt;
 
 use docker::models::{AuthConfig, ContainerCreateBody};
-use edgelet_core::module::NestedEdgeBodge;
+use edgelet_core::module::{ModuleImage, NestedEdgeBodge};
 use edgelet_utils::{ensure_not_empty_with_context, serde_clone};
 
 use crate::error::{ErrorKind, Result};
@@ -93,6 +93,16 @@ impl DockerConfig {
 
 pub const UPSTREAM_PARENT_KEYWORD: &str = "$upstream";
 
+impl ModuleImage for DockerConfig {
+    fn image(&self) -> &str {
+        DockerConfig::image(self)
+    }
+
+    fn image_id(&self) -> Option<&str> {
+        DockerConfig::image_id(self)
+    }
+}
+
 impl NestedEdgeBodge for DockerConfig {
     fn parent_hostname_resolve(&mut self, parent_hostname: &str) {
         if let Some(rest) = self.image.strip_prefix(UPSTREAM_PARENT_KEYWORD) {
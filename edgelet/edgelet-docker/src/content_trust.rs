@@ -0,0 +1,1205 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+//! In-process verification of signed module images.
+//!
+//! Images are expected to be signed the way `cosign` signs them: the signature of an image
+//! manifest is stored in the same repository under the tag `sha256-<digest>.sig`, as an OCI
+//! manifest whose layers are "simple signing" payloads naming the signed manifest digest.
+//! Each layer carries its signature and signing certificate as annotations. A signature is
+//! trusted if its certificate chains to the root CA configured for the registry and is for
+//! code signing.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+use failure::{Fail, ResultExt};
+use foreign_types_shared::ForeignTypeRef;
+use futures::future::{self, Either};
+use futures::{Future, Stream};
+use hyper::client::HttpConnector;
+use hyper::header::{ACCEPT, AUTHORIZATION, WWW_AUTHENTICATE};
+use hyper::{Body, Client, Request, StatusCode};
+use hyper_tls::HttpsConnector;
+use log::{debug, info};
+use openssl::hash::MessageDigest;
+use openssl::sign::Verifier;
+use openssl::stack::Stack;
+use openssl::x509::store::{X509Store, X509StoreBuilder};
+use openssl::x509::{X509Ref, X509StoreContext, X509};
+use serde_derive::Deserialize;
+
+use crate::error::{Error, ErrorKind, Result};
+use crate::image_sources::{split_registry, split_tag};
+
+const SIGNATURE_ANNOTATION: &str = "dev.cosignproject.cosign/signature";
+const CERTIFICATE_ANNOTATION: &str = "dev.sigstore.cosign/certificate";
+const CHAIN_ANNOTATION: &str = "dev.sigstore.cosign/chain";
+
+const MANIFEST_MEDIA_TYPES: &str = "application/vnd.oci.image.manifest.v1+json, \
+     application/vnd.oci.image.index.v1+json, \
+     application/vnd.docker.distribution.manifest.v2+json, \
+     application/vnd.docker.distribution.manifest.list.v2+json";
+
+const DOCKER_HUB_REGISTRY: &str = "docker.io";
+const DOCKER_HUB_API_HOST: &str = "registry-1.docker.io";
+const DOCKER_HUB_INDEX_HOST: &str = "index.docker.io";
+
+// From openssl/x509v3.h
+const EXFLAG_XKUSAGE: u32 = 0x4;
+const KU_DIGITAL_SIGNATURE: u32 = 0x0080;
+const XKU_CODE_SIGN: u32 = 0x8;
+
+extern "C" {
+    fn X509_get_extension_flags(x: *mut openssl_sys::X509) -> u32;
+    fn X509_get_key_usage(x: *mut openssl_sys::X509) -> u32;
+    fn X509_get_extended_key_usage(x: *mut openssl_sys::X509) -> u32;
+}
+
+/// Why content trust refused an image.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ContentTrustRefusal {
+    /// The registry has no signatures for the image manifest.
+    NotSigned,
+
+    /// None of the signing certificates chain to the root CA of the registry.
+    UntrustedSigner(String),
+
+    /// A signature doesn't match its payload.
+    InvalidSignature,
+
+    /// The signed payload is for a different image.
+    PayloadMismatch(String),
+
+    /// The manifest differs from the one the module asked for.
+    DigestMismatch { expected: String, actual: String },
+}
+
+impl fmt::Display for ContentTrustRefusal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ContentTrustRefusal::NotSigned => write!(f, "the image is not signed"),
+            ContentTrustRefusal::UntrustedSigner(reason) => write!(
+                f,
+                "the image is not signed by a trusted signer ({})",
+                reason
+            ),
+            ContentTrustRefusal::InvalidSignature => write!(f, "the image signature is invalid"),
+            ContentTrustRefusal::PayloadMismatch(reason) => {
+                write!(f, "the signature is for a different image ({})", reason)
+            }
+            ContentTrustRefusal::DigestMismatch { expected, actual } => write!(
+                f,
+                "expected manifest digest {} but the registry has {}",
+                expected, actual
+            ),
+        }
+    }
+}
+
+/// An image on a registry that content trust is enabled for.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct TrustedImage {
+    image: String,
+    name: String,
+    registry: String,
+    repository: String,
+    reference: String,
+}
+
+impl TrustedImage {
+    /// The image to pull or create once `digest` has been verified.
+    pub(crate) fn with_digest(&self, digest: &str) -> String {
+        format!("{}@{}", self.name, digest)
+    }
+
+    fn signed_reference(&self) -> String {
+        format!("{}/{}", self.registry, self.repository)
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct ContentTrustVerifier {
+    client: Client<HttpsConnector<HttpConnector>, Body>,
+    scheme: &'static str,
+    registries: Arc<BTreeMap<String, Arc<X509Store>>>,
+    cache: Arc<Mutex<Cache>>,
+}
+
+#[derive(Default)]
+struct Cache {
+    /// Digest that was last verified for each image.
+    digests: BTreeMap<String, String>,
+
+    /// Manifests whose signatures have been verified, as `<registry>/<repository>@<digest>`.
+    verified: BTreeSet<String>,
+}
+
+impl ContentTrustVerifier {
+    /// `ca_certs` maps registry host names to the PEM of the root CA that signs their images.
+    pub(crate) fn new(ca_certs: &BTreeMap<String, Vec<u8>>) -> Result<Self> {
+        Self::with_scheme("https", ca_certs)
+    }
+
+    fn with_scheme(scheme: &'static str, ca_certs: &BTreeMap<String, Vec<u8>>) -> Result<Self> {
+        let mut registries = BTreeMap::new();
+        for (hostname, ca_cert) in ca_certs {
+            let store = build_store(ca_cert)
+                .with_context(|_| ErrorKind::ContentTrustRootCa(hostname.clone()))?;
+            registries.insert(hostname.clone(), Arc::new(store));
+        }
+
+        let connector = HttpsConnector::new(1).context(ErrorKind::ContentTrustClient)?;
+
+        Ok(ContentTrustVerifier {
+            client: Client::builder().build(connector),
+            scheme,
+            registries: Arc::new(registries),
+            cache: Arc::new(Mutex::new(Cache::default())),
+        })
+    }
+
+    /// Returns `None` if content trust isn't enabled for the image's registry.
+    pub(crate) fn image(&self, image: &str) -> Option<TrustedImage> {
+        let (name, reference) = match image.find('@') {
+            Some(index) => (&image[..index], &image[index + 1..]),
+            None => split_tag(image),
+        };
+        let (registry, repository) = match split_registry(name) {
+            (Some(registry), repository) => (registry, repository.to_owned()),
+            (None, repository) if repository.contains('/') => {
+                (DOCKER_HUB_REGISTRY, repository.to_owned())
+            }
+            (None, repository) => (DOCKER_HUB_REGISTRY, format!("library/{}", repository)),
+        };
+
+        if !self.registries.contains_key(registry) {
+            return None;
+        }
+
+        Some(TrustedImage {
+            image: image.to_owned(),
+            name: name.to_owned(),
+            registry: registry.to_owned(),
+            repository,
+            reference: reference.to_owned(),
+        })
+    }
+
+    /// The digest that was last verified for the image, if any.
+    pub(crate) fn verified_digest(&self, image: &TrustedImage) -> Option<String> {
+        let cache = self.cache.lock().expect("content trust cache lock failed");
+        cache.digests.get(&image.image).cloned()
+    }
+
+    /// Resolves the image to a manifest digest and verifies that the manifest is signed
+    /// by a certificate issued by the registry's root CA.
+    pub(crate) fn verify(
+        &self,
+        image: &TrustedImage,
+        credentials: Option<(String, String)>,
+    ) -> Box<dyn Future<Item = String, Error = Error> + Send> {
+        let store = match self.registries.get(&image.registry) {
+            Some(store) => store.clone(),
+            None => {
+                return Box::new(future::err(Error::from(
+                    ErrorKind::ContentTrustVerification(image.image.clone()),
+                )))
+            }
+        };
+
+        let host = if image.registry == DOCKER_HUB_REGISTRY {
+            DOCKER_HUB_API_HOST
+        } else {
+            &image.registry
+        };
+        let registry = RegistryClient {
+            client: self.client.clone(),
+            base_url: format!("{}://{}/v2/{}", self.scheme, host, image.repository),
+            scope: format!("repository:{}:pull", image.repository),
+            credentials,
+            token: Arc::new(Mutex::new(None)),
+        };
+
+        let cache = self.cache.clone();
+        let image = image.clone();
+        let verify_image = image.clone();
+
+        let verified = registry
+            .fetch(
+                &format!("manifests/{}", image.reference),
+                MANIFEST_MEDIA_TYPES,
+            )
+            .and_then({
+                let image = image.clone();
+                move |manifest| -> Result<_> {
+                    let manifest = manifest.ok_or_else(|| {
+                        ErrorKind::NotFound(format!("manifest of {} not found", image.image))
+                    })?;
+                    let digest = sha256_digest(&manifest);
+                    if image.reference.starts_with("sha256:") && image.reference != digest {
+                        return Err(refused(
+                            &image,
+                            ContentTrustRefusal::DigestMismatch {
+                                expected: image.reference.clone(),
+                                actual: digest,
+                            },
+                        ));
+                    }
+                    Ok(digest)
+                }
+            })
+            .and_then(move |digest| {
+                let key = format!("{}@{}", image.signed_reference(), digest);
+                if cache
+                    .lock()
+                    .expect("content trust cache lock failed")
+                    .verified
+                    .contains(&key)
+                {
+                    debug!("Signature of {} was verified before", key);
+                    return Either::A(future::ok(digest));
+                }
+
+                Either::B(
+                    verify_signatures(&registry, &store, &image, &digest).map(move |()| {
+                        info!("Verified signature of {}", key);
+                        cache
+                            .lock()
+                            .expect("content trust cache lock failed")
+                            .verified
+                            .insert(key);
+                        digest
+                    }),
+                )
+            })
+            .map_err({
+                let image = verify_image.image.clone();
+                move |err| match err.kind() {
+                    ErrorKind::ContentTrustRefused(..) => err,
+                    _ => Error::from(err.context(ErrorKind::ContentTrustVerification(image))),
+                }
+            });
+
+        let cache = self.cache.clone();
+        let image = verify_image.image.clone();
+        Box::new(verified.map(move |digest| {
+            cache
+                .lock()
+                .expect("content trust cache lock failed")
+                .digests
+                .insert(image, digest.clone());
+            digest
+        }))
+    }
+}
+
+fn build_store(ca_cert: &[u8]) -> std::result::Result<X509Store, openssl::error::ErrorStack> {
+    let mut builder = X509StoreBuilder::new()?;
+    for cert in X509::stack_from_pem(ca_cert)? {
+        builder.add_cert(cert)?;
+    }
+    Ok(builder.build())
+}
+
+fn refused(image: &TrustedImage, refusal: ContentTrustRefusal) -> Error {
+    ErrorKind::ContentTrustRefused(image.image.clone(), refusal).into()
+}
+
+fn sha256_digest(content: &[u8]) -> String {
+    let hash: String = openssl::sha::sha256(content)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    format!("sha256:{}", hash)
+}
+
+#[derive(Deserialize)]
+struct SignatureManifest {
+    #[serde(default)]
+    layers: Vec<SignatureLayer>,
+}
+
+#[derive(Deserialize)]
+struct SignatureLayer {
+    digest: String,
+    #[serde(default)]
+    annotations: BTreeMap<String, String>,
+}
+
+#[derive(Deserialize)]
+struct SignedPayload {
+    critical: SignedPayloadCritical,
+}
+
+#[derive(Deserialize)]
+struct SignedPayloadCritical {
+    identity: SignedIdentity,
+    image: SignedImage,
+}
+
+#[derive(Deserialize)]
+struct SignedIdentity {
+    #[serde(rename = "docker-reference")]
+    docker_reference: String,
+}
+
+#[derive(Deserialize)]
+struct SignedImage {
+    #[serde(rename = "docker-manifest-digest")]
+    docker_manifest_digest: String,
+}
+
+fn verify_signatures(
+    registry: &RegistryClient,
+    store: &Arc<X509Store>,
+    image: &TrustedImage,
+    digest: &str,
+) -> Box<dyn Future<Item = (), Error = Error> + Send> {
+    let registry = registry.clone();
+    let store = store.clone();
+    let image = image.clone();
+    let digest = digest.to_owned();
+    let signature_tag = format!("{}.sig", digest.replacen(':', "-", 1));
+
+    let verified = registry
+        .fetch(
+            &format!("manifests/{}", signature_tag),
+            "application/vnd.oci.image.manifest.v1+json",
+        )
+        .and_then({
+            let image = image.clone();
+            move |manifest| -> Result<_> {
+                let manifest = match manifest {
+                    Some(manifest) => manifest,
+                    None => return Err(refused(&image, ContentTrustRefusal::NotSigned)),
+                };
+                let manifest: SignatureManifest = serde_json::from_slice(&manifest)
+                    .context(ErrorKind::ContentTrustVerification(image.image.clone()))?;
+                Ok(manifest.layers)
+            }
+        })
+        .and_then(move |layers| {
+            futures::stream::iter_ok(layers)
+                .and_then(move |layer| {
+                    registry
+                        .fetch(&format!("blobs/{}", layer.digest), "*/*")
+                        .map(|payload| (layer, payload))
+                })
+                .collect()
+        })
+        .and_then(move |layers| {
+            // Any one trusted signature is enough. Otherwise report why the last one failed.
+            let mut refusal = ContentTrustRefusal::NotSigned;
+            for (layer, payload) in layers {
+                let payload = match payload {
+                    Some(payload) => payload,
+                    None => continue,
+                };
+                match verify_signature(&store, &image, &digest, &layer, &payload) {
+                    Ok(()) => return Ok(()),
+                    Err(err) => {
+                        debug!(
+                            "Signature {} of {} refused: {}",
+                            layer.digest, image.image, err
+                        );
+                        refusal = err;
+                    }
+                }
+            }
+            Err(refused(&image, refusal))
+        });
+
+    Box::new(verified)
+}
+
+fn verify_signature(
+    store: &X509Store,
+    image: &TrustedImage,
+    digest: &str,
+    layer: &SignatureLayer,
+    payload: &[u8],
+) -> std::result::Result<(), ContentTrustRefusal> {
+    let signature = match layer.annotations.get(SIGNATURE_ANNOTATION) {
+        Some(signature) => {
+            base64::decode(signature.trim()).map_err(|_| ContentTrustRefusal::InvalidSignature)?
+        }
+        None => return Err(ContentTrustRefusal::NotSigned),
+    };
+    if sha256_digest(payload) != layer.digest {
+        return Err(ContentTrustRefusal::InvalidSignature);
+    }
+
+    let certificate = layer
+        .annotations
+        .get(CERTIFICATE_ANNOTATION)
+        .ok_or_else(|| ContentTrustRefusal::UntrustedSigner("no signing certificate".to_owned()))?;
+    let certificate = X509::from_pem(certificate.as_bytes())
+        .map_err(|err| ContentTrustRefusal::UntrustedSigner(err.to_string()))?;
+    let mut chain =
+        Stack::new().map_err(|err| ContentTrustRefusal::UntrustedSigner(err.to_string()))?;
+    if let Some(pem) = layer.annotations.get(CHAIN_ANNOTATION) {
+        for cert in X509::stack_from_pem(pem.as_bytes())
+            .map_err(|err| ContentTrustRefusal::UntrustedSigner(err.to_string()))?
+        {
+            chain
+                .push(cert)
+                .map_err(|err| ContentTrustRefusal::UntrustedSigner(err.to_string()))?;
+        }
+    }
+
+    let mut context = X509StoreContext::new()
+        .map_err(|err| ContentTrustRefusal::UntrustedSigner(err.to_string()))?;
+    let chain_error = context
+        .init(store, &certificate, &chain, |context| {
+            Ok(if context.verify_cert()? {
+                None
+            } else {
+                Some(context.error().error_string().to_owned())
+            })
+        })
+        .map_err(|err| ContentTrustRefusal::UntrustedSigner(err.to_string()))?;
+    if let Some(chain_error) = chain_error {
+        return Err(ContentTrustRefusal::UntrustedSigner(chain_error));
+    }
+    check_signing_usage(&certificate)?;
+
+    let public_key = certificate
+        .public_key()
+        .map_err(|_| ContentTrustRefusal::InvalidSignature)?;
+    let valid = Verifier::new(MessageDigest::sha256(), &public_key)
+        .and_then(|mut verifier| {
+            verifier.update(payload)?;
+            verifier.verify(&signature)
+        })
+        .unwrap_or(false);
+    if !valid {
+        return Err(ContentTrustRefusal::InvalidSignature);
+    }
+
+    let payload: SignedPayload = serde_json::from_slice(payload)
+        .map_err(|err| ContentTrustRefusal::PayloadMismatch(err.to_string()))?;
+    if payload.critical.image.docker_manifest_digest != digest {
+        return Err(ContentTrustRefusal::PayloadMismatch(format!(
+            "signed manifest is {}",
+            payload.critical.image.docker_manifest_digest
+        )));
+    }
+    let signed_reference = payload.critical.identity.docker_reference;
+    let signed_reference = match signed_reference.find('/') {
+        Some(index) if &signed_reference[..index] == DOCKER_HUB_INDEX_HOST => {
+            format!("{}{}", DOCKER_HUB_REGISTRY, &signed_reference[index..])
+        }
+        _ => signed_reference,
+    };
+    if signed_reference != image.signed_reference() {
+        return Err(ContentTrustRefusal::PayloadMismatch(format!(
+            "signed image is {}",
+            signed_reference
+        )));
+    }
+
+    Ok(())
+}
+
+/// Refuses signing certificates that aren't for code signing, like TLS server certificates
+/// from the same CA.
+///
+/// The certificate has to have the codeSigning extended key usage. A key usage extension is
+/// optional, but has to allow digitalSignature if the certificate has one.
+fn check_signing_usage(certificate: &X509Ref) -> std::result::Result<(), ContentTrustRefusal> {
+    // The openssl crate can't read these extensions, so the libcrypto functions that it links
+    // to are used. The key usages are all ones when the certificate has no such extension.
+    let certificate = certificate.as_ptr();
+    let (flags, key_usage, extended_key_usage) = unsafe {
+        (
+            X509_get_extension_flags(certificate),
+            X509_get_key_usage(certificate),
+            X509_get_extended_key_usage(certificate),
+        )
+    };
+
+    if flags & EXFLAG_XKUSAGE == 0 || extended_key_usage & XKU_CODE_SIGN == 0 {
+        return Err(ContentTrustRefusal::UntrustedSigner(
+            "the signing certificate is not for code signing".to_owned(),
+        ));
+    }
+    if key_usage & KU_DIGITAL_SIGNATURE == 0 {
+        return Err(ContentTrustRefusal::UntrustedSigner(
+            "the signing certificate is not for digital signatures".to_owned(),
+        ));
+    }
+
+    Ok(())
+}
+
+/// The status, `WWW-Authenticate` challenge and body of a registry response.
+type RegistryResponse = (StatusCode, Option<String>, Vec<u8>);
+
+/// Just enough of the registry API to read manifests and blobs, including the token
+/// authentication that most registries require even for anonymous pulls.
+#[derive(Clone)]
+struct RegistryClient {
+    client: Client<HttpsConnector<HttpConnector>, Body>,
+    base_url: String,
+    scope: String,
+    credentials: Option<(String, String)>,
+    token: Arc<Mutex<Option<String>>>,
+}
+
+impl RegistryClient {
+    /// Returns `None` if the registry doesn't have the manifest or blob.
+    fn fetch(
+        &self,
+        path: &str,
+        accept: &'static str,
+    ) -> Box<dyn Future<Item = Option<Vec<u8>>, Error = Error> + Send> {
+        let url = format!("{}/{}", self.base_url, path);
+        let authorization = self.authorization();
+        let registry = self.clone();
+
+        let response =
+            self.get(&url, accept, authorization)
+                .and_then(move |(status, challenge, body)| {
+                    if status != StatusCode::UNAUTHORIZED {
+                        return Either::A(future::result(response_body(&url, status, body)));
+                    }
+
+                    let challenge = challenge.unwrap_or_default();
+                    Either::B(
+                        registry
+                            .authorize(&challenge)
+                            .and_then(move |authorization| {
+                                registry.get(&url, accept, Some(authorization)).and_then(
+                                    move |(status, _, body)| response_body(&url, status, body),
+                                )
+                            }),
+                    )
+                });
+
+        Box::new(response)
+    }
+
+    fn authorization(&self) -> Option<String> {
+        let token = self.token.lock().expect("registry token lock failed");
+        token.as_ref().map(|token| format!("Bearer {}", token))
+    }
+
+    fn basic_authorization(&self) -> Option<String> {
+        self.credentials.as_ref().map(|(username, password)| {
+            format!(
+                "Basic {}",
+                base64::encode(&format!("{}:{}", username, password))
+            )
+        })
+    }
+
+    fn get(
+        &self,
+        url: &str,
+        accept: &'static str,
+        authorization: Option<String>,
+    ) -> Box<dyn Future<Item = RegistryResponse, Error = Error> + Send> {
+        let mut request = Request::get(url);
+        request.header(ACCEPT, accept);
+        if let Some(authorization) = authorization {
+            request.header(AUTHORIZATION, authorization);
+        }
+
+        let client = self.client.clone();
+        let response = future::result(request.body(Body::empty()))
+            .map_err(|err| Error::from(err.context(ErrorKind::ContentTrustRegistry)))
+            .and_then(move |request| {
+                client
+                    .request(request)
+                    .and_then(|response| {
+                        let status = response.status();
+                        let challenge = response
+                            .headers()
+                            .get(WWW_AUTHENTICATE)
+                            .and_then(|value| value.to_str().ok())
+                            .map(ToOwned::to_owned);
+                        response
+                            .into_body()
+                            .concat2()
+                            .map(move |body| (status, challenge, body.to_vec()))
+                    })
+                    .map_err(|err| Error::from(err.context(ErrorKind::ContentTrustRegistry)))
+            });
+
+        Box::new(response)
+    }
+
+    fn authorize(&self, challenge: &str) -> Box<dyn Future<Item = String, Error = Error> + Send> {
+        let (scheme, params) = parse_challenge(challenge);
+
+        if scheme.eq_ignore_ascii_case("basic") {
+            return Box::new(future::result(self.basic_authorization().ok_or_else(
+                || {
+                    Error::from(
+                        failure::err_msg("the registry requires credentials")
+                            .context(ErrorKind::ContentTrustRegistry),
+                    )
+                },
+            )));
+        }
+
+        let realm = match params.get("realm") {
+            Some(realm) if scheme.eq_ignore_ascii_case("bearer") => realm,
+            _ => {
+                return Box::new(future::err(Error::from(
+                    failure::err_msg(format!("unsupported authentication {:?}", challenge))
+                        .context(ErrorKind::ContentTrustRegistry),
+                )))
+            }
+        };
+
+        let mut query = url::form_urlencoded::Serializer::new(String::new());
+        if let Some(service) = params.get("service") {
+            query.append_pair("service", service);
+        }
+        query.append_pair(
+            "scope",
+            params.get("scope").map_or(&*self.scope, String::as_str),
+        );
+        let url = format!("{}?{}", realm, query.finish());
+
+        let token = self.token.clone();
+        Box::new(
+            self.get(&url, "application/json", self.basic_authorization())
+                .and_then(move |(status, _, body)| -> Result<_> {
+                    #[derive(Deserialize)]
+                    struct TokenResponse {
+                        token: Option<String>,
+                        access_token: Option<String>,
+                    }
+
+                    let body = response_body(&url, status, body)?
+                        .ok_or_else(|| failure::err_msg("the token service was not found"))
+                        .context(ErrorKind::ContentTrustRegistry)?;
+                    let response: TokenResponse =
+                        serde_json::from_slice(&body).context(ErrorKind::ContentTrustRegistry)?;
+                    let new_token = response
+                        .token
+                        .or(response.access_token)
+                        .ok_or_else(|| failure::err_msg("the token service returned no token"))
+                        .context(ErrorKind::ContentTrustRegistry)?;
+
+                    let authorization = format!("Bearer {}", new_token);
+                    *token.lock().expect("registry token lock failed") = Some(new_token);
+                    Ok(authorization)
+                }),
+        )
+    }
+}
+
+fn response_body(url: &str, status: StatusCode, body: Vec<u8>) -> Result<Option<Vec<u8>>> {
+    match status {
+        StatusCode::OK => Ok(Some(body)),
+        StatusCode::NOT_FOUND => Ok(None),
+        status => Err(Error::from(
+            failure::err_msg(format!("GET {} returned {}", url, status))
+                .context(ErrorKind::ContentTrustRegistry),
+        )),
+    }
+}
+
+/// Parses a `WWW-Authenticate` header such as
+/// `Bearer realm="https://auth.example.com/token",service="registry.example.com"`.
+fn parse_challenge(challenge: &str) -> (&str, BTreeMap<String, String>) {
+    let challenge = challenge.trim();
+    let (scheme, mut rest) = match challenge.find(' ') {
+        Some(index) => (&challenge[..index], challenge[index + 1..].trim_start()),
+        None => (challenge, ""),
+    };
+
+    let mut params = BTreeMap::new();
+    while let Some(index) = rest.find('=') {
+        let name = rest[..index].trim().to_ascii_lowercase();
+        rest = &rest[index + 1..];
+
+        let value = if rest.starts_with('"') {
+            let end = rest[1..].find('"').map_or(rest.len(), |end| end + 1);
+            let value = rest[1..end].to_owned();
+            rest = rest.get(end + 1..).unwrap_or("");
+            value
+        } else {
+            let end = rest.find(',').unwrap_or(rest.len());
+            let value = rest[..end].trim().to_owned();
+            rest = &rest[end..];
+            value
+        };
+        params.insert(name, value);
+
+        rest = rest.trim_start_matches(|c: char| c == ',' || c.is_whitespace());
+    }
+
+    (scheme, params)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::sync::{Arc, Mutex};
+
+    use futures::{future, Future};
+    use hyper::header::{AUTHORIZATION, HOST, WWW_AUTHENTICATE};
+    use hyper::{Body, Request, Response, StatusCode};
+    use openssl::asn1::Asn1Time;
+    use openssl::bn::BigNum;
+    use openssl::ec::{EcGroup, EcKey};
+    use openssl::hash::MessageDigest;
+    use openssl::nid::Nid;
+    use openssl::pkey::{PKey, Private};
+    use openssl::sign::Signer;
+    use openssl::x509::extension::{BasicConstraints, ExtendedKeyUsage, KeyUsage};
+    use openssl::x509::{X509Builder, X509NameBuilder, X509};
+    use tokio::runtime::Runtime;
+
+    use edgelet_test_utils::run_tcp_server;
+
+    use super::{
+        parse_challenge, sha256_digest, ContentTrustRefusal, ContentTrustVerifier,
+        CERTIFICATE_ANNOTATION, SIGNATURE_ANNOTATION,
+    };
+    use crate::error::{ErrorKind, Result};
+
+    const TOKEN: &str = "test-token";
+
+    type Contents = Arc<Mutex<BTreeMap<String, Vec<u8>>>>;
+
+    /// A registry stand-in that serves fixed manifests and blobs to clients that have
+    /// fetched a bearer token, like most registries do even for anonymous pulls.
+    struct TestRegistry {
+        runtime: Runtime,
+        host: String,
+        contents: Contents,
+    }
+
+    impl TestRegistry {
+        fn new() -> Self {
+            let contents = Contents::default();
+            let handler = {
+                let contents = contents.clone();
+                move |req: Request<Body>| {
+                    let path = req.uri().path().to_owned();
+                    let authorized = req
+                        .headers()
+                        .get(AUTHORIZATION)
+                        .map_or(false, |value| value == &*format!("Bearer {}", TOKEN));
+
+                    let response = if path == "/token" {
+                        Response::new(Body::from(format!(r#"{{"token":"{}"}}"#, TOKEN)))
+                    } else if !authorized {
+                        let host = req.headers()[HOST].to_str().unwrap().to_owned();
+                        Response::builder()
+                            .status(StatusCode::UNAUTHORIZED)
+                            .header(
+                                WWW_AUTHENTICATE,
+                                format!(
+                                    r#"Bearer realm="http://{}/token",service="test-registry""#,
+                                    host
+                                ),
+                            )
+                            .body(Body::empty())
+                            .unwrap()
+                    } else {
+                        match contents.lock().unwrap().get(&path) {
+                            Some(body) => Response::new(Body::from(body.clone())),
+                            None => Response::builder()
+                                .status(StatusCode::NOT_FOUND)
+                                .body(Body::empty())
+                                .unwrap(),
+                        }
+                    };
+                    future::ok(response)
+                }
+            };
+
+            let (server, port) = run_tcp_server("127.0.0.1", handler);
+            let mut runtime = Runtime::new().unwrap();
+            runtime.spawn(server.map_err(|err| panic!("{}", err)));
+
+            TestRegistry {
+                runtime,
+                host: format!("127.0.0.1:{}", port),
+                contents,
+            }
+        }
+
+        fn image(&self, repository: &str, tag: &str) -> String {
+            format!("{}/{}:{}", self.host, repository, tag)
+        }
+
+        fn push_image(&self, repository: &str, tag: &str) -> String {
+            let manifest = format!(r#"{{"schemaVersion":2,"tag":"{}"}}"#, tag).into_bytes();
+            let digest = sha256_digest(&manifest);
+            let mut contents = self.contents.lock().unwrap();
+            contents.insert(
+                format!("/v2/{}/manifests/{}", repository, tag),
+                manifest.clone(),
+            );
+            contents.insert(format!("/v2/{}/manifests/{}", repository, digest), manifest);
+            digest
+        }
+
+        fn push_signature(&self, repository: &str, digest: &str, signature: &Signature) {
+            let payload_digest = sha256_digest(&signature.payload);
+            let manifest = serde_json::json!({
+                "schemaVersion": 2,
+                "layers": [{
+                    "mediaType": "application/vnd.dev.cosign.simplesigning.v1+json",
+                    "digest": payload_digest,
+                    "size": signature.payload.len(),
+                    "annotations": {
+                        SIGNATURE_ANNOTATION: signature.signature,
+                        CERTIFICATE_ANNOTATION: signature.certificate,
+                    },
+                }],
+            });
+
+            let mut contents = self.contents.lock().unwrap();
+            contents.insert(
+                format!("/v2/{}/blobs/{}", repository, payload_digest),
+                signature.payload.clone(),
+            );
+            contents.insert(
+                format!(
+                    "/v2/{}/manifests/{}.sig",
+                    repository,
+                    digest.replace(':', "-")
+                ),
+                serde_json::to_vec(&manifest).unwrap(),
+            );
+        }
+
+        fn remove_signatures(&self) {
+            let mut contents = self.contents.lock().unwrap();
+            contents.retain(|path, _| !path.ends_with(".sig"));
+        }
+
+        fn verifier(&self, signer: &TestSigner) -> ContentTrustVerifier {
+            let mut ca_certs = BTreeMap::new();
+            ca_certs.insert(self.host.clone(), signer.ca.to_pem().unwrap());
+            ContentTrustVerifier::with_scheme("http", &ca_certs).unwrap()
+        }
+
+        fn verify(&mut self, verifier: &ContentTrustVerifier, image: &str) -> Result<String> {
+            let image = verifier.image(image).unwrap();
+            self.runtime.block_on(verifier.verify(&image, None))
+        }
+    }
+
+    struct TestSigner {
+        ca: X509,
+        cert: X509,
+        key: PKey<Private>,
+    }
+
+    struct Signature {
+        payload: Vec<u8>,
+        signature: String,
+        certificate: String,
+    }
+
+    fn generate_key() -> PKey<Private> {
+        let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+        PKey::from_ec_key(EcKey::generate(&group).unwrap()).unwrap()
+    }
+
+    /// Signers get a code signing certificate, unless `code_signing` is false, when they get a
+    /// TLS server certificate instead.
+    fn generate_cert(
+        common_name: &str,
+        key: &PKey<Private>,
+        issuer: Option<(&X509, &PKey<Private>)>,
+        code_signing: bool,
+    ) -> X509 {
+        let mut name = X509NameBuilder::new().unwrap();
+        name.append_entry_by_nid(Nid::COMMONNAME, common_name)
+            .unwrap();
+        let name = name.build();
+
+        let mut builder = X509Builder::new().unwrap();
+        builder.set_version(2).unwrap();
+        builder
+            .set_serial_number(&BigNum::from_u32(1).unwrap().to_asn1_integer().unwrap())
+            .unwrap();
+        builder.set_subject_name(&name).unwrap();
+        builder
+            .set_issuer_name(issuer.map_or(&name, |(ca, _)| ca.subject_name()))
+            .unwrap();
+        builder.set_pubkey(key).unwrap();
+        builder
+            .set_not_before(&Asn1Time::days_from_now(0).unwrap())
+            .unwrap();
+        builder
+            .set_not_after(&Asn1Time::days_from_now(1).unwrap())
+            .unwrap();
+        if issuer.is_none() {
+            builder
+                .append_extension(BasicConstraints::new().critical().ca().build().unwrap())
+                .unwrap();
+        } else if code_signing {
+            builder
+                .append_extension(
+                    KeyUsage::new()
+                        .critical()
+                        .digital_signature()
+                        .build()
+                        .unwrap(),
+                )
+                .unwrap();
+            builder
+                .append_extension(ExtendedKeyUsage::new().code_signing().build().unwrap())
+                .unwrap();
+        } else {
+            builder
+                .append_extension(ExtendedKeyUsage::new().server_auth().build().unwrap())
+                .unwrap();
+        }
+        builder
+            .sign(issuer.map_or(key, |(_, key)| key), MessageDigest::sha256())
+            .unwrap();
+        builder.build()
+    }
+
+    impl TestSigner {
+        fn new() -> Self {
+            TestSigner::with_usage(true)
+        }
+
+        fn with_usage(code_signing: bool) -> Self {
+            let ca_key = generate_key();
+            let ca = generate_cert("root", &ca_key, None, false);
+            let key = generate_key();
+            let cert = generate_cert("signer", &key, Some((&ca, &ca_key)), code_signing);
+            TestSigner { ca, cert, key }
+        }
+
+        fn sign(&self, reference: &str, digest: &str) -> Signature {
+            let payload = serde_json::json!({
+                "critical": {
+                    "identity": { "docker-reference": reference },
+                    "image": { "docker-manifest-digest": digest },
+                    "type": "cosign container image signature",
+                },
+                "optional": null,
+            });
+            let payload = serde_json::to_vec(&payload).unwrap();
+
+            let mut signer = Signer::new(MessageDigest::sha256(), &self.key).unwrap();
+            signer.update(&payload).unwrap();
+            Signature {
+                signature: base64::encode(&signer.sign_to_vec().unwrap()),
+                certificate: String::from_utf8(self.cert.to_pem().unwrap()).unwrap(),
+                payload,
+            }
+        }
+    }
+
+    fn refusal(result: Result<String>) -> ContentTrustRefusal {
+        match result.unwrap_err().kind() {
+            ErrorKind::ContentTrustRefused(_, refusal) => refusal.clone(),
+            kind => panic!("expected the image to be refused, got {}", kind),
+        }
+    }
+
+    #[test]
+    fn verifies_signed_image() {
+        let mut registry = TestRegistry::new();
+        let signer = TestSigner::new();
+        let digest = registry.push_image("sample", "1.0");
+        let reference = format!("{}/sample", registry.host);
+        registry.push_signature("sample", &digest, &signer.sign(&reference, &digest));
+
+        let verifier = registry.verifier(&signer);
+        let image = registry.image("sample", "1.0");
+        assert_eq!(digest, registry.verify(&verifier, &image).unwrap());
+
+        let trusted_image = verifier.image(&image).unwrap();
+        assert_eq!(
+            Some(digest.clone()),
+            verifier.verified_digest(&trusted_image)
+        );
+        assert_eq!(
+            format!("{}@{}", reference, digest),
+            trusted_image.with_digest(&digest)
+        );
+    }
+
+    #[test]
+    fn verified_digests_are_cached() {
+        let mut registry = TestRegistry::new();
+        let signer = TestSigner::new();
+        let digest = registry.push_image("sample", "1.0");
+        let reference = format!("{}/sample", registry.host);
+        registry.push_signature("sample", &digest, &signer.sign(&reference, &digest));
+
+        let verifier = registry.verifier(&signer);
+        let image = registry.image("sample", "1.0");
+        registry.verify(&verifier, &image).unwrap();
+
+        registry.remove_signatures();
+        assert_eq!(digest, registry.verify(&verifier, &image).unwrap());
+        let by_digest = format!("{}@{}", reference, digest);
+        assert_eq!(digest, registry.verify(&verifier, &by_digest).unwrap());
+    }
+
+    #[test]
+    fn refuses_unsigned_image() {
+        let mut registry = TestRegistry::new();
+        let signer = TestSigner::new();
+        registry.push_image("sample", "1.0");
+
+        let verifier = registry.verifier(&signer);
+        let image = registry.image("sample", "1.0");
+        assert_eq!(
+            ContentTrustRefusal::NotSigned,
+            refusal(registry.verify(&verifier, &image))
+        );
+    }
+
+    #[test]
+    fn refuses_image_signed_by_other_ca() {
+        let mut registry = TestRegistry::new();
+        let signer = TestSigner::new();
+        let digest = registry.push_image("sample", "1.0");
+        let reference = format!("{}/sample", registry.host);
+        registry.push_signature("sample", &digest, &signer.sign(&reference, &digest));
+
+        let verifier = registry.verifier(&TestSigner::new());
+        let image = registry.image("sample", "1.0");
+        match refusal(registry.verify(&verifier, &image)) {
+            ContentTrustRefusal::UntrustedSigner(_) => (),
+            refusal => panic!("unexpected refusal {:?}", refusal),
+        }
+    }
+
+    #[test]
+    fn refuses_image_signed_without_code_signing_certificate() {
+        let mut registry = TestRegistry::new();
+        let signer = TestSigner::with_usage(false);
+        let digest = registry.push_image("sample", "1.0");
+        let reference = format!("{}/sample", registry.host);
+        registry.push_signature("sample", &digest, &signer.sign(&reference, &digest));
+
+        let verifier = registry.verifier(&signer);
+        let image = registry.image("sample", "1.0");
+        assert_eq!(
+            ContentTrustRefusal::UntrustedSigner(
+                "the signing certificate is not for code signing".to_owned()
+            ),
+            refusal(registry.verify(&verifier, &image))
+        );
+    }
+
+    #[test]
+    fn refuses_tampered_signature() {
+        let mut registry = TestRegistry::new();
+        let signer = TestSigner::new();
+        let digest = registry.push_image("sample", "1.0");
+        let reference = format!("{}/sample", registry.host);
+        let mut signature = signer.sign(&reference, &digest);
+        signature.signature = signer.sign(&reference, "sha256:0000").signature;
+        registry.push_signature("sample", &digest, &signature);
+
+        let verifier = registry.verifier(&signer);
+        let image = registry.image("sample", "1.0");
+        assert_eq!(
+            ContentTrustRefusal::InvalidSignature,
+            refusal(registry.verify(&verifier, &image))
+        );
+    }
+
+    #[test]
+    fn refuses_signature_of_other_image() {
+        let mut registry = TestRegistry::new();
+        let signer = TestSigner::new();
+        let digest = registry.push_image("sample", "1.0");
+        let other_digest = registry.push_image("sample", "2.0");
+        let reference = format!("{}/sample", registry.host);
+        registry.push_signature("sample", &digest, &signer.sign(&reference, &other_digest));
+
+        let other_reference = format!("{}/other", registry.host);
+        registry.push_image("other", "1.0");
+        registry.push_signature("other", &digest, &signer.sign(&reference, &digest));
+
+        let verifier = registry.verifier(&signer);
+        for image in &[
+            registry.image("sample", "1.0"),
+            format!("{}:1.0", other_reference),
+        ] {
+            match refusal(registry.verify(&verifier, image)) {
+                ContentTrustRefusal::PayloadMismatch(_) => (),
+                refusal => panic!("unexpected refusal {:?}", refusal),
+            }
+        }
+    }
+
+    #[test]
+    fn refuses_image_with_other_digest() {
+        let mut registry = TestRegistry::new();
+        let signer = TestSigner::new();
+        let digest = registry.push_image("sample", "1.0");
+        let reference = format!("{}/sample", registry.host);
+        registry.push_signature("sample", &digest, &signer.sign(&reference, &digest));
+
+        // The registry serves the 1.0 manifest for this digest.
+        let other_digest = sha256_digest(b"other");
+        let mut contents = registry.contents.lock().unwrap();
+        let manifest = contents[&format!("/v2/sample/manifests/{}", digest)].clone();
+        contents.insert(format!("/v2/sample/manifests/{}", other_digest), manifest);
+        drop(contents);
+
+        let verifier = registry.verifier(&signer);
+        let image = format!("{}@{}", reference, other_digest);
+        assert_eq!(
+            ContentTrustRefusal::DigestMismatch {
+                expected: other_digest,
+                actual: digest,
+            },
+            refusal(registry.verify(&verifier, &image))
+        );
+    }
+
+    #[test]
+    fn only_configured_registries_are_verified() {
+        let mut ca_certs = BTreeMap::new();
+        ca_certs.insert(
+            "docker.io".to_owned(),
+            TestSigner::new().ca.to_pem().unwrap(),
+        );
+        let verifier = ContentTrustVerifier::new(&ca_certs).unwrap();
+
+        let image = verifier.image("alpine:3.12").unwrap();
+        assert_eq!("docker.io", image.registry);
+        assert_eq!("library/alpine", image.repository);
+        assert_eq!("3.12", image.reference);
+        assert_eq!("alpine@sha256:0000", image.with_digest("sha256:0000"));
+
+        let image = verifier.image("azureiotedge/agent@sha256:0000").unwrap();
+        assert_eq!("azureiotedge/agent", image.repository);
+        assert_eq!("sha256:0000", image.reference);
+
+        assert!(verifier
+            .image("mcr.microsoft.com/azureiotedge-agent:1.2")
+            .is_none());
+        assert!(verifier.image("localhost:5000/alpine:3.12").is_none());
+    }
+
+    #[test]
+    fn parses_authentication_challenge() {
+        let (scheme, params) = parse_challenge(
+            r#"Bearer realm="https://auth.docker.io/token",service="registry.docker.io",scope="repository:library/alpine:pull,push""#,
+        );
+        assert_eq!("Bearer", scheme);
+        assert_eq!("https://auth.docker.io/token", params["realm"]);
+        assert_eq!("registry.docker.io", params["service"]);
+        assert_eq!("repository:library/alpine:pull,push", params["scope"]);
+
+        let (scheme, params) = parse_challenge("Basic realm=registry");
+        assert_eq!("Basic", scheme);
+        assert_eq!("registry", params["realm"]);
+    }
+}
@@ -11,6 +11,8 @@ use edgelet_core::{
     ModuleOperation, ModuleRuntimeErrorReason, RegistryOperation, RuntimeOperation,
 };
 
+use crate::content_trust::ContentTrustRefusal;
+
 pub type Result<T> = ::std::result::Result<T, Error>;
 
 #[derive(Debug)]
@@ -43,9 +45,35 @@ pub enum ErrorKind {
     #[fail(display = "Could not clone create options")]
     CloneCreateOptions,
 
+    #[fail(display = "Could not create the content trust registry client")]
+    ContentTrustClient,
+
+    #[fail(display = "Image {} was refused by content trust: {}", _0, _1)]
+    ContentTrustRefused(String, ContentTrustRefusal),
+
+    #[fail(display = "Could not read from the container registry")]
+    ContentTrustRegistry,
+
+    #[fail(
+        display = "Could not load the content trust root CA for registry {:?}",
+        _0
+    )]
+    ContentTrustRootCa(String),
+
+    #[fail(display = "Could not verify the signature of image {}", _0)]
+    ContentTrustVerification(String),
+
     #[fail(display = "Conflict with current operation")]
     Conflict,
 
+    #[fail(display = "The copied files exceed the limit of {} bytes", _0)]
+    CopySizeExceeded(u64),
+
+    #[fail(
+        display = "Compressed archives can't be copied, since their size is only known once they're extracted"
+    )]
+    CopyCompressed,
+
     #[fail(display = "Container runtime error")]
     Docker,
 
@@ -58,9 +86,6 @@ pub enum ErrorKind {
     #[fail(display = "Could not initialize module runtime")]
     Initialization,
 
-    #[fail(display = "Could not initialize Notary configuration: {}", _0)]
-    InitializeNotary(String),
-
     #[fail(display = "Invalid docker image {:?}", _0)]
     InvalidImage(String),
 
@@ -70,21 +95,18 @@ pub enum ErrorKind {
     #[fail(display = "Invalid module type {:?}", _0)]
     InvalidModuleType(String),
 
+    #[fail(display = "Invalid module networks: {}", _0)]
+    InvalidModuleNetworks(String),
+
+    #[fail(display = "Invalid resource limits: {}", _0)]
+    InvalidResourceLimits(String),
+
     #[fail(display = "Invalid socket URI: {:?}", _0)]
     InvalidSocketUri(String),
 
-    #[fail(display = "{}", _0)]
-    LaunchNotary(String),
-
     #[fail(display = "{}", _0)]
     ModuleOperation(ModuleOperation),
 
-    #[fail(display = "{}", _0)]
-    NotaryDigestMismatch(String),
-
-    #[fail(display = "{}", _0)]
-    NotaryRootCAReadError(String),
-
     #[fail(display = "{}", _0)]
     NotFound(String),
 
@@ -96,6 +118,9 @@ pub enum ErrorKind {
 
     #[fail(display = "{}", _0)]
     RuntimeOperation(RuntimeOperation),
+
+    #[fail(display = "Unsupported container engine: {}", _0)]
+    UnsupportedContainerEngine(String),
 }
 
 impl Fail for Error {
@@ -0,0 +1,367 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+//! Sources that module images can be pulled from besides the registry in the image
+//! reference: registry mirrors, and `docker save` tarballs in a local directory.
+
+use std::convert::TryFrom;
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+use log::warn;
+
+const TAR_BLOCK_SIZE: usize = 512;
+const MANIFEST_FILE_NAME: &str = "manifest.json";
+
+/// An image to pull from a registry mirror, and the name to tag it with afterwards so
+/// that modules find it under their original image reference.
+#[derive(Debug, PartialEq)]
+pub(crate) struct MirroredImage {
+    pub(crate) image: String,
+    pub(crate) repository: String,
+    pub(crate) tag: String,
+}
+
+/// Returns `None` for references by digest, since a digest can't be given to an image
+/// as a tag after pulling it from the mirror.
+pub(crate) fn mirror_image(mirror: &str, image: &str) -> Option<MirroredImage> {
+    if image.contains('@') {
+        return None;
+    }
+
+    let (repository, tag) = split_tag(image);
+    let path = match split_registry(repository) {
+        (Some(_), path) => path.to_owned(),
+        (None, path) if path.contains('/') => path.to_owned(),
+        (None, path) => format!("library/{}", path),
+    };
+
+    let mirror = mirror
+        .trim_start_matches("https://")
+        .trim_start_matches("http://")
+        .trim_end_matches('/');
+
+    Some(MirroredImage {
+        image: format!("{}/{}:{}", mirror, path, tag),
+        repository: repository.to_owned(),
+        tag: tag.to_owned(),
+    })
+}
+
+/// A tarball created by `docker save` that contains an image.
+#[derive(Debug, PartialEq)]
+pub(crate) struct ImageTarball {
+    pub(crate) path: PathBuf,
+    /// The ID of the image, if the tarball's manifest names its config.
+    pub(crate) image_id: Option<String>,
+}
+
+/// Finds a tarball created by `docker save` in `dir` whose manifest lists `image`.
+///
+/// Tarballs that can't be read are skipped, so that a single corrupt file doesn't
+/// prevent importing the others. This reads the directory and the tarballs' headers,
+/// so it must not run on the reactor.
+pub(crate) fn find_image_tarball(dir: &Path, image: &str) -> io::Result<Option<ImageTarball>> {
+    let (repository, tag) = split_tag(image);
+    let image = format!("{}:{}", repository, tag);
+
+    let mut tarballs = vec![];
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_file()
+            && path
+                .extension()
+                .map_or(false, |extension| extension == "tar")
+        {
+            tarballs.push(path);
+        }
+    }
+    tarballs.sort();
+
+    for path in tarballs {
+        let manifest = match File::open(&path).and_then(|mut file| read_manifest(&mut file)) {
+            Ok(manifest) => manifest,
+            Err(err) => {
+                warn!("Could not read image tarball {}: {}", path.display(), err);
+                continue;
+            }
+        };
+
+        let entry = manifest.into_iter().find(|entry| {
+            entry
+                .repo_tags
+                .iter()
+                .flatten()
+                .any(|repo_tag| *repo_tag == image)
+        });
+        if let Some(entry) = entry {
+            return Ok(Some(ImageTarball {
+                path,
+                image_id: entry.config.as_deref().and_then(config_image_id),
+            }));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Splits a reference without a digest into its repository and tag.
+pub(crate) fn split_tag(image: &str) -> (&str, &str) {
+    let name_start = image.rfind('/').map_or(0, |index| index + 1);
+    match image[name_start..].rfind(':') {
+        Some(index) => (
+            &image[..name_start + index],
+            &image[name_start + index + 1..],
+        ),
+        None => (image, "latest"),
+    }
+}
+
+/// Splits a repository into its registry, if it names one, and the path on the registry.
+pub fn split_registry(repository: &str) -> (Option<&str>, &str) {
+    // Same rules as the engine: the first component is a registry only if it looks like a
+    // host name. Anything else is a repository on Docker Hub.
+    match repository.find('/') {
+        Some(index)
+            if repository[..index].contains(&['.', ':'][..])
+                || &repository[..index] == "localhost" =>
+        {
+            (Some(&repository[..index]), &repository[index + 1..])
+        }
+        _ => (None, repository),
+    }
+}
+
+#[derive(serde_derive::Deserialize)]
+struct ManifestEntry {
+    #[serde(rename = "Config", default)]
+    config: Option<String>,
+    #[serde(rename = "RepoTags", default)]
+    repo_tags: Option<Vec<String>>,
+}
+
+/// The image ID for the `Config` of a manifest entry, which is the digest of the image
+/// config, e.g. `<digest>.json` or `blobs/sha256/<digest>`.
+fn config_image_id(config: &str) -> Option<String> {
+    let digest = config.rsplit('/').next()?.trim_end_matches(".json");
+    if digest.is_empty() || !digest.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    Some(format!("sha256:{}", digest))
+}
+
+/// Reads the `manifest.json` of a `docker save` tarball, seeking past the image layers
+/// instead of reading them.
+fn read_manifest<R>(tarball: &mut R) -> io::Result<Vec<ManifestEntry>>
+where
+    R: Read + Seek,
+{
+    let mut header = [0_u8; TAR_BLOCK_SIZE];
+    loop {
+        tarball.read_exact(&mut header)?;
+
+        // The archive ends with zero-filled blocks.
+        if header.iter().all(|b| *b == 0) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "tarball does not contain a manifest.json",
+            ));
+        }
+
+        let name = header_str(&header[..100]);
+        let prefix = header_str(&header[345..500]);
+        let size = header_size(&header[124..136])?;
+
+        if prefix.is_empty() && name.trim_start_matches("./") == MANIFEST_FILE_NAME {
+            let size = usize::try_from(size)
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+            let mut manifest = vec![0_u8; size];
+            tarball.read_exact(&mut manifest)?;
+            return serde_json::from_slice(&manifest)
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err));
+        }
+
+        let block_size = TAR_BLOCK_SIZE as u64;
+        let padded_size = i64::try_from((size + block_size - 1) / block_size * block_size)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        tarball.seek(SeekFrom::Current(padded_size))?;
+    }
+}
+
+fn header_str(field: &[u8]) -> &str {
+    let end = field.iter().position(|b| *b == 0).unwrap_or(field.len());
+    std::str::from_utf8(&field[..end]).unwrap_or_default()
+}
+
+fn header_size(field: &[u8]) -> io::Result<u64> {
+    // Sizes that don't fit in the octal field are stored in base-256, flagged by the high bit.
+    if field[0] & 0x80 != 0 {
+        return Ok(field[1..]
+            .iter()
+            .fold(0, |size, b| (size << 8) | u64::from(*b)));
+    }
+
+    let size = header_str(field).trim_matches(|c| c == ' ' || c == '\0');
+    u64::from_str_radix(size, 8).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::io::Cursor;
+
+    use tempdir::TempDir;
+
+    use super::{
+        config_image_id, find_image_tarball, mirror_image, read_manifest, ImageTarball,
+        MirroredImage,
+    };
+
+    fn tar_entry(name: &str, contents: &[u8]) -> Vec<u8> {
+        let mut header = vec![0_u8; 512];
+        header[..name.len()].copy_from_slice(name.as_bytes());
+        let size = format!("{:011o}\0", contents.len());
+        header[124..136].copy_from_slice(size.as_bytes());
+
+        let mut entry = header;
+        entry.extend_from_slice(contents);
+        entry.resize((entry.len() + 511) / 512 * 512, 0);
+        entry
+    }
+
+    fn tarball(repo_tags: &[&str]) -> Vec<u8> {
+        let manifest = serde_json::json!([{
+            "Config": "0123456789abcdef.json",
+            "RepoTags": repo_tags,
+            "Layers": ["layer/layer.tar"],
+        }]);
+
+        let mut tarball = tar_entry("layer/layer.tar", &[1_u8; 1000]);
+        tarball.extend(tar_entry("0123456789abcdef.json", b"{}"));
+        tarball.extend(tar_entry(
+            "manifest.json",
+            &serde_json::to_vec(&manifest).unwrap(),
+        ));
+        tarball.extend(vec![0_u8; 1024]);
+        tarball
+    }
+
+    #[test]
+    fn mirror_image_keeps_repository_path() {
+        assert_eq!(
+            Some(MirroredImage {
+                image: "mirror.local:5000/azureiotedge-agent:1.2".to_owned(),
+                repository: "mcr.microsoft.com/azureiotedge-agent".to_owned(),
+                tag: "1.2".to_owned(),
+            }),
+            mirror_image(
+                "https://mirror.local:5000/",
+                "mcr.microsoft.com/azureiotedge-agent:1.2"
+            )
+        );
+        assert_eq!(
+            Some(MirroredImage {
+                image: "mirror.local/contoso/sensor:latest".to_owned(),
+                repository: "localhost:5000/contoso/sensor".to_owned(),
+                tag: "latest".to_owned(),
+            }),
+            mirror_image("mirror.local", "localhost:5000/contoso/sensor")
+        );
+    }
+
+    #[test]
+    fn mirror_image_uses_docker_hub_path() {
+        assert_eq!(
+            "mirror.local/library/alpine:3.12",
+            mirror_image("mirror.local", "alpine:3.12").unwrap().image
+        );
+        assert_eq!(
+            "mirror.local/contoso/sensor:latest",
+            mirror_image("mirror.local", "contoso/sensor")
+                .unwrap()
+                .image
+        );
+    }
+
+    #[test]
+    fn mirror_image_ignores_digests() {
+        assert_eq!(
+            None,
+            mirror_image(
+                "mirror.local",
+                "mcr.microsoft.com/azureiotedge-agent@sha256:0123456789abcdef"
+            )
+        );
+    }
+
+    #[test]
+    fn read_manifest_finds_manifest() {
+        let manifest = read_manifest(&mut Cursor::new(tarball(&[
+            "contoso.azurecr.io/sensor:1.0",
+            "contoso.azurecr.io/sensor:latest",
+        ])))
+        .unwrap();
+        assert_eq!(1, manifest.len());
+        assert_eq!(
+            Some(vec![
+                "contoso.azurecr.io/sensor:1.0".to_owned(),
+                "contoso.azurecr.io/sensor:latest".to_owned(),
+            ]),
+            manifest[0].repo_tags
+        );
+        assert_eq!(Some("0123456789abcdef.json"), manifest[0].config.as_deref());
+
+        let mut without_manifest = tar_entry("layer/layer.tar", &[1_u8; 1000]);
+        without_manifest.extend(vec![0_u8; 1024]);
+        assert!(read_manifest(&mut Cursor::new(without_manifest)).is_err());
+    }
+
+    #[test]
+    fn config_image_id_uses_config_digest() {
+        assert_eq!(
+            Some("sha256:0123456789abcdef".to_owned()),
+            config_image_id("0123456789abcdef.json")
+        );
+        assert_eq!(
+            Some("sha256:0123456789abcdef".to_owned()),
+            config_image_id("blobs/sha256/0123456789abcdef")
+        );
+        assert_eq!(None, config_image_id("config.json"));
+        assert_eq!(None, config_image_id(""));
+    }
+
+    #[test]
+    fn find_image_tarball_matches_repo_tags() {
+        let tmp_dir = TempDir::new("images").unwrap();
+        fs::write(
+            tmp_dir.path().join("agent.tar"),
+            tarball(&["mcr.microsoft.com/azureiotedge-agent:1.2"]),
+        )
+        .unwrap();
+        fs::write(
+            tmp_dir.path().join("alpine.tar"),
+            tarball(&["alpine:latest"]),
+        )
+        .unwrap();
+        fs::write(tmp_dir.path().join("corrupt.tar"), b"not a tarball").unwrap();
+        fs::write(tmp_dir.path().join("notes.txt"), b"alpine:latest").unwrap();
+
+        assert_eq!(
+            Some(ImageTarball {
+                path: tmp_dir.path().join("agent.tar"),
+                image_id: Some("sha256:0123456789abcdef".to_owned()),
+            }),
+            find_image_tarball(tmp_dir.path(), "mcr.microsoft.com/azureiotedge-agent:1.2").unwrap()
+        );
+        assert_eq!(
+            Some(tmp_dir.path().join("alpine.tar")),
+            find_image_tarball(tmp_dir.path(), "alpine")
+                .unwrap()
+                .map(|tarball| tarball.path)
+        );
+        assert_eq!(
+            None,
+            find_image_tarball(tmp_dir.path(), "mcr.microsoft.com/azureiotedge-hub:1.2").unwrap()
+        );
+    }
+}
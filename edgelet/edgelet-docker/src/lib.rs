@@ -13,14 +13,21 @@
 
 mod client;
 mod config;
+mod content_trust;
 mod error;
+mod image_sources;
 mod module;
-mod notary;
 mod runtime;
 mod settings;
+mod startup;
 
 pub use config::{DockerConfig, UPSTREAM_PARENT_KEYWORD};
+pub use content_trust::ContentTrustRefusal;
 pub use error::{Error, ErrorKind};
+pub use image_sources::split_registry;
 pub use module::{DockerModule, MODULE_TYPE};
 pub use runtime::DockerModuleRuntime;
-pub use settings::{ContentTrust, LoadSettingsError, MobyRuntime, Settings, CONFIG_FILE_DEFAULT};
+pub use settings::{
+    ContainerEngine, ContentTrust, LoadSettingsError, MobyRuntime, ResourceLimits, Settings,
+    CONFIG_FILE_DEFAULT,
+};
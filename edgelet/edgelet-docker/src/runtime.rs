@@ -1,27 +1,34 @@
 // Copyright (c) Microsoft. All rights reserved.
 
 use std::collections::{BTreeMap, HashMap};
+use std::io;
 use std::ops::Deref;
 use std::path::{Path, PathBuf};
 use std::str;
 use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
+use bytes::{Bytes, BytesMut};
 use failure::{Fail, ResultExt};
 use futures::future::Either;
 use futures::prelude::*;
 use futures::{future, stream, Async, Stream};
 use hyper::{Body, Chunk as HyperChunk, Client, Request};
 use lazy_static::lazy_static;
-use log::{debug, info, Level};
+use log::{debug, info, warn, Level};
+use tokio::codec::{BytesCodec, FramedRead};
 use url::Url;
 
 use docker::apis::client::APIClient;
 use docker::apis::configuration::Configuration;
-use docker::models::{ContainerCreateBody, InlineResponse200, Ipam, NetworkConfig};
+use docker::models::{
+    ContainerCreateBody, ExecConfig, ExecStartConfig, HostConfig, InlineResponse200,
+    InlineResponse20011, Ipam, NetworkConfig,
+};
 use edgelet_core::{
-    AuthId, Authenticator, Ipam as CoreIpam, LogOptions, MakeModuleRuntime, MobyNetwork, Module,
-    ModuleId, ModuleRegistry, ModuleRuntime, ModuleRuntimeState, ModuleSpec, ProvisioningInfo,
+    shutdown_stages, startup_sequence, stop_in_stages, AuthId, Authenticator, ExecOptions,
+    ImageInfo, Ipam as CoreIpam, LogOptions, MakeModuleRuntime, MobyNetwork, Module, ModuleId,
+    ModuleRegistry, ModuleRuntime, ModuleRuntimeState, ModuleSpec, ProvisioningInfo,
     RegistryOperation, RuntimeOperation, RuntimeSettings, SystemInfo as CoreSystemInfo,
     SystemResources, UrlExt,
 };
@@ -30,12 +37,14 @@ use edgelet_utils::{ensure_not_empty_with_context, log_failure};
 
 use crate::client::DockerClient;
 use crate::config::DockerConfig;
+use crate::content_trust::{ContentTrustRefusal, ContentTrustVerifier, TrustedImage};
 use crate::error::{Error, ErrorKind, Result};
+use crate::image_sources::{self, ImageTarball, MirroredImage};
 use crate::module::{
     runtime_state, DockerModule, DockerModuleTop, MODULE_TYPE as DOCKER_MODULE_TYPE,
 };
-use crate::notary;
-use crate::settings::{ContentTrust, Settings};
+use crate::settings::{apply_module_networks, ContentTrust, ResourceLimits, Settings};
+use crate::startup;
 
 use edgelet_core::DiskInfo;
 use std::convert::TryInto;
@@ -63,11 +72,27 @@ lazy_static! {
 pub struct DockerModuleRuntime {
     client: DockerClient<UrlConnector>,
     system_resources: Arc<Mutex<System>>,
-    notary_registries: BTreeMap<String, PathBuf>,
-    notary_lock: tokio::sync::lock::Lock<BTreeMap<String, String>>,
+    content_trust: Arc<Mutex<Option<ContentTrustVerifier>>>,
+    resource_limits: Option<ResourceLimits>,
+    module_networks: BTreeMap<String, Vec<String>>,
+    registry_mirrors: Vec<String>,
+    image_import_dir: Option<PathBuf>,
+    max_copy_size: u64,
 }
 
 impl DockerModuleRuntime {
+    /// Queries the version of the container engine, including the components it's made of.
+    pub fn version(&self) -> impl Future<Item = InlineResponse20011, Error = Error> + Send {
+        self.client.system_api().system_version().map_err(|err| {
+            let err = Error::from_docker_error(
+                err,
+                ErrorKind::RuntimeOperation(RuntimeOperation::SystemInfo),
+            );
+            log_failure(Level::Warn, &err);
+            err
+        })
+    }
+
     fn merge_env(cur_env: Option<&[String]>, new_env: &BTreeMap<String, String>) -> Vec<String> {
         // build a new merged map containing string slices for keys and values
         // pointing into String instances in new_env
@@ -89,6 +114,17 @@ impl DockerModuleRuntime {
             .map(|(key, value)| format!("{}={}", key, value))
             .collect()
     }
+
+    fn trusted_image(&self, image: &str) -> Option<(ContentTrustVerifier, TrustedImage)> {
+        let content_trust = self
+            .content_trust
+            .lock()
+            .expect("Could not acquire content trust lock")
+            .clone()?;
+        let trusted_image = content_trust.image(image)?;
+        info!("{} is enabled for content trust", image);
+        Some((content_trust, trusted_image))
+    }
 }
 
 impl std::fmt::Debug for DockerModuleRuntime {
@@ -97,59 +133,32 @@ impl std::fmt::Debug for DockerModuleRuntime {
     }
 }
 
-struct MutexFuture<T>(tokio::sync::lock::Lock<T>);
-
-impl<T> Future for MutexFuture<T> {
-    type Item = tokio::sync::lock::LockGuard<T>;
-    type Error = Error;
-
-    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
-        Ok(self.0.poll_lock())
-    }
-}
-
 impl ModuleRegistry for DockerModuleRuntime {
     type Error = Error;
     type PullFuture = Box<dyn Future<Item = (), Error = Self::Error> + Send>;
-    type RemoveFuture = Box<dyn Future<Item = (), Error = Self::Error>>;
+    type RemoveFuture = Box<dyn Future<Item = (), Error = Self::Error> + Send>;
+    type ListImagesFuture = Box<dyn Future<Item = Vec<ImageInfo>, Error = Self::Error> + Send>;
     type Config = DockerConfig;
 
     fn pull(&self, config: &Self::Config) -> Self::PullFuture {
-        let image_with_tag = config.image().to_string();
-
-        let image_by_notary = if let Some((notary_auth, gun, tag, config_path)) =
-            get_notary_parameters(config, &self.notary_registries, &image_with_tag)
-        {
-            let lock = self.notary_lock.clone();
-            let mutex = MutexFuture(lock);
-            future::Either::A(
-                mutex
-                    .and_then({
-                        let gun = gun.clone();
-                        move |lock| {
-                            notary::notary_lookup(
-                                notary_auth.as_deref(),
-                                &gun,
-                                &tag,
-                                &config_path,
-                                lock,
-                            )
-                        }
-                    })
-                    .map(move |(digest, mut lock)| {
-                        debug!("Digest from notary lookup {}", digest);
-                        let image_with_digest = format!("{}@{}", gun, digest);
-                        lock.insert(image_with_tag, digest);
-                        (image_with_digest, true)
-                    }),
-            )
-        } else {
-            future::Either::B(futures::future::ok((image_with_tag, false)))
-        };
+        let image = config.image().to_string();
+
+        let image_by_digest =
+            if let Some((content_trust, trusted_image)) = self.trusted_image(&image) {
+                future::Either::A(
+                    content_trust
+                        .verify(&trusted_image, registry_credentials(config))
+                        .map(move |digest| (trusted_image.with_digest(&digest), true)),
+                )
+            } else {
+                future::Either::B(futures::future::ok((image, false)))
+            };
 
         let creds = config.auth().cloned();
         let client_copy = self.client.clone();
-        let response = image_by_notary
+        let registry_mirrors = self.registry_mirrors.clone();
+        let image_import_dir = self.image_import_dir.clone();
+        let response = image_by_digest
             .and_then(|(image, is_content_trust_enabled)| {
                 let creds = match creds {
                     Some(a) => {
@@ -165,21 +174,26 @@ impl ModuleRegistry for DockerModuleRuntime {
                 Ok((image, is_content_trust_enabled, creds))
             })
             .and_then(move |(image, is_content_trust_enabled, creds)| {
+                // A verified image has to come from the registry that signed it.
                 if is_content_trust_enabled {
                     info!("Pulling image via digest {}...", image);
-                } else {
-                    info!("Pulling image via tag {}...", image);
+                    return Either::A(pull_from_registry(&client_copy, image, &creds));
                 }
-                client_copy
-                    .image_api()
-                    .image_create(&image, "", "", "", "", &creds, "")
-                    .then(|result| match result {
-                        Ok(()) => Ok(image),
-                        Err(err) => Err(Error::from_docker_error(
-                            err,
-                            ErrorKind::RegistryOperation(RegistryOperation::PullImage(image)),
-                        )),
-                    })
+
+                let pulled = pull_from_image_sources(
+                    &client_copy,
+                    &image,
+                    image_import_dir.as_deref(),
+                    &registry_mirrors,
+                );
+                Either::B(pulled.and_then(move |pulled| {
+                    if pulled {
+                        Either::A(future::ok(image))
+                    } else {
+                        info!("Pulling image via tag {}...", image);
+                        Either::B(pull_from_registry(&client_copy, image, &creds))
+                    }
+                }))
             })
             .then(move |result| match result {
                 Ok(image) => {
@@ -226,6 +240,38 @@ impl ModuleRegistry for DockerModuleRuntime {
                 }),
         )
     }
+
+    fn list_images(&self) -> Self::ListImagesFuture {
+        debug!("Listing images...");
+
+        Box::new(
+            self.client
+                .image_api()
+                .image_list(false, "", true)
+                .then(|result| match result {
+                    Ok(images) => Ok(images
+                        .into_iter()
+                        .map(|image| {
+                            ImageInfo::new(
+                                image.id().to_string(),
+                                image.repo_tags().to_vec(),
+                                image.repo_digests().to_vec(),
+                                *image.created(),
+                                *image.size(),
+                            )
+                        })
+                        .collect()),
+                    Err(err) => {
+                        let err = Error::from_docker_error(
+                            err,
+                            ErrorKind::RegistryOperation(RegistryOperation::ListImages),
+                        );
+                        log_failure(Level::Warn, &err);
+                        Err(err)
+                    }
+                }),
+        )
+    }
 }
 
 fn parse_get_response<'de, D>(resp: &InlineResponse200) -> std::result::Result<String, D::Error>
@@ -245,87 +291,49 @@ impl MakeModuleRuntime for DockerModuleRuntime {
     type ModuleRuntime = Self;
     type Error = Error;
     type Future = Box<dyn Future<Item = Self, Error = Self::Error> + Send>;
+    type ReloadFuture = Box<dyn Future<Item = (), Error = Self::Error> + Send>;
 
     fn make_runtime(settings: Settings) -> Self::Future {
         info!("Initializing module runtime...");
 
         let created = match init_client(settings.moby_runtime().uri()) {
             Ok(client) => {
-                let home_dir: Arc<Path> = settings.homedir().into();
                 let network_id = settings.moby_runtime().network().name().to_string();
-                let notary_registries = BTreeMap::new();
-                let certd_url = settings.endpoints().aziot_certd_url().clone();
-                let cert_client = cert_client::CertificateClient::new(
-                    aziot_cert_common_http::ApiVersion::V2020_09_01,
-                    &certd_url,
-                );
-
-                let notary_registries = if let Some(content_trust_map) = settings
+                let resource_limits = settings.moby_runtime().resource_limits().cloned();
+                let registry_mirrors = settings.moby_runtime().registry_mirrors().to_vec();
+                let image_import_dir = settings
                     .moby_runtime()
-                    .content_trust()
-                    .and_then(ContentTrust::ca_certs)
-                {
-                    debug!("Notary Content Trust is enabled");
-                    future::Either::A(futures::stream::iter_ok(content_trust_map.clone()).fold(
-                        (notary_registries, cert_client),
-                        move |(mut notary_registries, cert_client),
-                              (registry_server_hostname, cert_id)| {
-                            let home_dir = home_dir.clone();
-                            cert_client
-                                .get_cert(&cert_id)
-                                .then(move |cert_output| -> Result<_> {
-                                    match cert_output {
-                                        Ok(cert_buf) => {
-                                            let config_path = notary::notary_init(
-                                                &home_dir,
-                                                &registry_server_hostname,
-                                                &cert_buf,
-                                            )
-                                            .context(ErrorKind::Initialization)?;
-                                            notary_registries.insert(
-                                                registry_server_hostname.clone(),
-                                                config_path,
-                                            );
-                                            Ok((notary_registries, cert_client))
-                                        }
-                                        Err(_e) => Err(ErrorKind::NotaryRootCAReadError(
-                                            "Notary root CA read error".to_owned(),
-                                        )
-                                        .into()),
-                                    }
-                                })
-                        },
-                    ))
-                } else {
-                    debug!("Notary Content Trust is disabled");
-                    future::Either::B(future::ok((notary_registries, cert_client)))
-                };
-                let (enable_i_pv6, ipam) = get_ipv6_settings(settings.moby_runtime().network());
+                    .image_import_dir()
+                    .map(Path::to_path_buf);
+                let max_copy_size = settings.moby_runtime().max_copy_size();
+                let content_trust = content_trust_verifier(&settings);
+                let module_networks = settings.moby_runtime().module_networks().clone();
                 info!("Using runtime network id {}", network_id);
 
-                let filter = format!(r#"{{"name":{{"{}":true}}}}"#, network_id);
+                // The networks that modules join with `module_networks` are created along with
+                // the runtime network, so that they exist before any module is created.
+                let networks: Vec<_> = std::iter::once(settings.moby_runtime().network().clone())
+                    .chain(
+                        settings
+                            .moby_runtime()
+                            .additional_networks()
+                            .iter()
+                            .cloned()
+                            .map(MobyNetwork::Network),
+                    )
+                    .collect();
                 let client_copy = client.clone();
-                let fut = client
-                    .network_api()
-                    .network_list(&filter)
-                    .and_then(move |existing_networks| {
-                        if existing_networks.is_empty() {
-                            let mut network_config =
-                                NetworkConfig::new(network_id).with_enable_i_pv6(enable_i_pv6);
-
-                            if let Some(ipam_config) = ipam {
-                                network_config.set_IPAM(ipam_config);
-                            };
-
-                            let fut = client_copy
-                                .network_api()
-                                .network_create(network_config)
-                                .map(move |_| client_copy);
-                            future::Either::A(fut)
-                        } else {
-                            future::Either::B(future::ok(client_copy))
-                        }
+                let fut = stream::iter_ok(networks)
+                    .for_each(move |network| {
+                        let (enable_i_pv6, ipam) = get_ipv6_settings(&network);
+                        create_network_if_missing(
+                            &client_copy,
+                            network.name().to_string(),
+                            enable_i_pv6,
+                            ipam,
+                        )
                     })
+                    .map(move |()| client)
                     .map_err(|err| {
                         let e = Error::from_docker_error(
                             err,
@@ -334,18 +342,21 @@ impl MakeModuleRuntime for DockerModuleRuntime {
                         log_failure(Level::Warn, &e);
                         e
                     })
-                    .join(notary_registries)
-                    .map(move |(client, (notary_registries, _))| {
+                    .join(content_trust)
+                    .map(move |(client, content_trust)| {
                         // to avoid excessive FD usage, we will not allow sysinfo to keep files open.
                         sysinfo::set_open_files_limit(0);
                         let system_resources = System::new_all();
                         info!("Successfully initialized module runtime");
-                        let notary_lock = tokio::sync::lock::Lock::new(BTreeMap::new());
                         DockerModuleRuntime {
                             client,
                             system_resources: Arc::new(Mutex::new(system_resources)),
-                            notary_registries,
-                            notary_lock,
+                            content_trust: Arc::new(Mutex::new(content_trust)),
+                            resource_limits,
+                            module_networks,
+                            registry_mirrors,
+                            image_import_dir,
+                            max_copy_size,
                         }
                     });
                 future::Either::A(fut)
@@ -357,6 +368,94 @@ impl MakeModuleRuntime for DockerModuleRuntime {
         };
         Box::new(created)
     }
+
+    /// Content trust is applied to the images that are pulled from then on. Images that were
+    /// already pulled aren't verified again.
+    fn reload(runtime: &Self, settings: &Settings) -> Self::ReloadFuture {
+        let runtime_content_trust = runtime.content_trust.clone();
+        Box::new(content_trust_verifier(settings).map(move |content_trust| {
+            *runtime_content_trust
+                .lock()
+                .expect("Could not acquire content trust lock") = content_trust;
+        }))
+    }
+}
+
+/// The verifier of image signatures for the content trust settings, with the root CAs that
+/// certd has for them.
+fn content_trust_verifier(
+    settings: &Settings,
+) -> impl Future<Item = Option<ContentTrustVerifier>, Error = Error> + Send {
+    let ca_certs = match settings
+        .moby_runtime()
+        .content_trust()
+        .and_then(ContentTrust::ca_certs)
+    {
+        Some(ca_certs) => ca_certs.clone(),
+        None => {
+            debug!("Content trust is disabled");
+            return future::Either::B(future::ok(None));
+        }
+    };
+
+    debug!("Content trust is enabled");
+    let cert_client = cert_client::CertificateClient::new(
+        aziot_cert_common_http::ApiVersion::V2020_09_01,
+        settings.endpoints().aziot_certd_url(),
+    );
+    future::Either::A(
+        futures::stream::iter_ok(ca_certs)
+            .and_then(move |(hostname, cert_id)| {
+                cert_client.get_cert(&cert_id).then(|cert| match cert {
+                    Ok(cert) => Ok((hostname, cert)),
+                    Err(err) => Err(Error::from(
+                        err.context(ErrorKind::ContentTrustRootCa(hostname)),
+                    )),
+                })
+            })
+            .collect()
+            .and_then(|ca_certs| {
+                let ca_certs: BTreeMap<_, _> = ca_certs.into_iter().collect();
+                ContentTrustVerifier::new(&ca_certs).map(Some)
+            }),
+    )
+}
+
+fn create_network_if_missing(
+    client: &DockerClient<UrlConnector>,
+    network_id: String,
+    enable_i_pv6: bool,
+    ipam: Option<Ipam>,
+) -> impl Future<Item = (), Error = docker::apis::Error<serde_json::Value>> + Send {
+    // The name filter matches networks whose names contain the network ID, so the names are
+    // compared too.
+    let filter = format!(r#"{{"name":{{"{}":true}}}}"#, network_id);
+    let client = client.clone();
+    client
+        .network_api()
+        .network_list(&filter)
+        .and_then(move |existing_networks| {
+            if existing_networks
+                .iter()
+                .any(|network| network.name() == Some(network_id.as_str()))
+            {
+                return future::Either::B(future::ok(()));
+            }
+
+            info!("Creating network {}", network_id);
+            let mut network_config = NetworkConfig::new(network_id).with_enable_i_pv6(enable_i_pv6);
+
+            if let Some(ipam_config) = ipam {
+                network_config.set_IPAM(ipam_config);
+            }
+
+            future::Either::A(
+                client
+                    .network_api()
+                    .network_create(network_config)
+                    .map(|_| ()),
+            )
+        })
 }
 
 fn get_ipv6_settings(network_configuration: &MobyNetwork) -> (bool, Option<Ipam>) {
@@ -400,6 +499,7 @@ impl ModuleRuntime for DockerModuleRuntime {
     type ModuleRegistry = Self;
     type Chunk = Chunk;
     type Logs = Logs;
+    type Archive = Archive;
 
     type CreateFuture = Box<dyn Future<Item = (), Error = Self::Error> + Send>;
     type GetFuture =
@@ -416,7 +516,15 @@ impl ModuleRuntime for DockerModuleRuntime {
     type SystemResourcesFuture =
         Box<dyn Future<Item = SystemResources, Error = Self::Error> + Send>;
     type RemoveAllFuture = Box<dyn Future<Item = (), Error = Self::Error> + Send>;
+    type StartAllFuture = Box<dyn Future<Item = (), Error = Self::Error> + Send>;
     type StopAllFuture = Box<dyn Future<Item = (), Error = Self::Error> + Send>;
+    type ExecIo = hyper::upgrade::Upgraded;
+    type ExecExitCodeFuture = Box<dyn Future<Item = Option<i32>, Error = Self::Error> + Send>;
+    type ExecFuture = Box<
+        dyn Future<Item = (Self::ExecIo, Self::ExecExitCodeFuture), Error = Self::Error> + Send,
+    >;
+    type GetArchiveFuture = Box<dyn Future<Item = Self::Archive, Error = Self::Error> + Send>;
+    type PutArchiveFuture = Box<dyn Future<Item = (), Error = Self::Error> + Send>;
 
     fn create(&self, module: ModuleSpec<Self::Config>) -> Self::CreateFuture {
         info!("Creating module {}...", module.name());
@@ -428,65 +536,39 @@ impl ModuleRuntime for DockerModuleRuntime {
             ))));
         }
 
-        let image_with_tag = module.config().image().to_string();
+        let image = module.config().image().to_string();
         let digest_from_manifest = module.config().digest().map(&str::to_owned);
 
-        let image_by_notary = if let Some((notary_auth, gun, tag, config_path)) =
-            get_notary_parameters(module.config(), &self.notary_registries, &image_with_tag)
-        {
-            let lock = self.notary_lock.clone();
-            let mutex = MutexFuture(lock);
-            future::Either::A(
-                mutex
-                    .and_then({
-                        let gun = gun.clone();
-                        let image_with_tag = image_with_tag.clone();
-                        move |lock| {
-                            let digest_from_notary = lock.get(&image_with_tag);
-                            #[allow(clippy::option_if_let_else)]
-                            if let Some(digest_from_notary) = digest_from_notary {
-                                future::Either::A(future::ok((digest_from_notary.clone(), lock)))
-                            }
-                            else {
-                                future::Either::B(notary::notary_lookup(
-                                    notary_auth.as_deref(),
-                                    &gun,
-                                    &tag,
-                                    &config_path,
-                                    lock,
-                                ))
-                            }
-                        }
-                    })
-                    .and_then(move |(digest_from_notary, mut lock)| {
-                        lock.insert(image_with_tag, digest_from_notary.clone());
-                        let image_with_digest = format!("{}@{}", gun, digest_from_notary);
-                        if let Some(digest_from_manifest_str) = digest_from_manifest {
-                            if digest_from_manifest_str == digest_from_notary {
-                                info!("Digest from notary and Digest from manifest does match");
-                                debug!("Digest from notary : {} and Digest from manifest : {} does match", digest_from_notary, digest_from_manifest_str);
-                                Ok((image_with_digest, true))
-                            }
-                            else {
-                                info!("Digest from notary and Digest from manifest does not match");
-                                debug!("Digest from notary : {} and Digest from manifest : {} does not match", digest_from_notary, digest_from_manifest_str);
-                                Err(Error::from(ErrorKind::NotaryDigestMismatch(
-                                        "notary digest mismatch with the manifest".to_owned(),
-                                )))
-                            }
-                        }
-                        else {
-                            info!("No Digest from the manifest");
-                            Ok((image_with_digest, true))
-                        }
-                    }),
-            )
-        } else {
-            future::Either::B(futures::future::ok((image_with_tag, false)))
-        };
+        let image_by_digest =
+            if let Some((content_trust, trusted_image)) = self.trusted_image(&image) {
+                // The image was verified when it was pulled, unless it was pulled before the
+                // runtime started.
+                let digest = match content_trust.verified_digest(&trusted_image) {
+                    Some(digest) => future::Either::A(future::ok(digest)),
+                    None => future::Either::B(
+                        content_trust.verify(&trusted_image, registry_credentials(module.config())),
+                    ),
+                };
+                future::Either::A(digest.and_then(move |digest| match digest_from_manifest {
+                    Some(expected) if expected != digest => {
+                        Err(Error::from(ErrorKind::ContentTrustRefused(
+                            image,
+                            ContentTrustRefusal::DigestMismatch {
+                                expected,
+                                actual: digest,
+                            },
+                        )))
+                    }
+                    _ => Ok((trusted_image.with_digest(&digest), true)),
+                }))
+            } else {
+                future::Either::B(futures::future::ok((image, false)))
+            };
 
         let client = self.client.clone();
-        let result = image_by_notary
+        let resource_limits = self.resource_limits.clone();
+        let module_networks = self.module_networks.get(module.name()).cloned();
+        let result = image_by_digest
             .and_then(|(image, is_content_trust_enabled)| {
                 if is_content_trust_enabled {
                     info!("Creating image via digest {}...", image);
@@ -509,19 +591,87 @@ impl ModuleRuntime for DockerModuleRuntime {
                             ORIGINAL_IMAGE_LABEL_KEY.to_string(),
                             module.config().image().to_string(),
                         );
+                        labels.extend(startup::startup_labels(
+                            module.startup_order(),
+                            module.depends_on(),
+                        ));
 
                         debug!("Creating container {} with image {}", module.name(), image);
 
-                        let create_options = create_options
+                        let mut create_options = create_options
                             .with_image(image)
                             .with_env(merged_env)
                             .with_labels(labels);
 
+                        // Device-level limits only fill in what the module's create options leave unset.
+                        if let Some(resource_limits) = &resource_limits {
+                            let host_config = create_options
+                                .host_config()
+                                .cloned()
+                                .unwrap_or_else(HostConfig::new);
+                            create_options.set_host_config(resource_limits.apply_to(host_config));
+                        }
+
                         // Here we don't add the container to the iot edge docker network as the edge-agent is expected to do that.
                         // It contains the logic to add a container to the iot edge network only if a network is not already specified.
+                        // Modules with networks in the settings join those instead, whatever their create options ask for.
+                        let other_networks = if let Some(networks) = &module_networks {
+                            if networks.is_empty() {
+                                warn!(
+                                    "Module {} joins no networks, so it has no network access",
+                                    module.name()
+                                );
+                            } else {
+                                info!(
+                                    "Module {} joins networks [{}]",
+                                    module.name(),
+                                    networks.join(", ")
+                                );
+                            }
+                            let (options, other_networks) =
+                                apply_module_networks(create_options, networks);
+                            create_options = options;
+                            other_networks
+                        } else {
+                            vec![]
+                        };
+
+                        let name = module.name().to_string();
                         client
                             .container_api()
                             .container_create(create_options, module.name())
+                            .and_then(move |_| {
+                                let connect_client = client.clone();
+                                let connect_name = name.clone();
+                                stream::iter_ok(other_networks)
+                                    .for_each(move |(network, endpoint)| {
+                                        connect_client.network_api().network_connect(
+                                            &network,
+                                            docker::models::Container::new()
+                                                .with_container(connect_name.clone())
+                                                .with_endpoint_config(endpoint),
+                                        )
+                                    })
+                                    .or_else(move |err| {
+                                        // A module that isn't on all of its networks isn't
+                                        // left behind, so that creating it can be retried.
+                                        client
+                                            .container_api()
+                                            .container_delete(
+                                                &name, /* remove volumes */ false,
+                                                /* force */ true, /* remove link */ false,
+                                            )
+                                            .then(move |result| {
+                                                if result.is_err() {
+                                                    warn!(
+                                                        "Could not remove container {} after it failed to join its networks",
+                                                        name
+                                                    );
+                                                }
+                                                Err(err)
+                                            })
+                                    })
+                            })
                             .then(|result| match result {
                                 Ok(_) => Ok(module),
                                 Err(err) => Err(Error::from_docker_error(
@@ -875,6 +1025,7 @@ impl ModuleRuntime for DockerModuleRuntime {
                     disk.get_total_space(),
                     String::from_utf8_lossy(disk.get_file_system()).into_owned(),
                     format!("{:?}", disk.get_type()),
+                    disk.get_mount_point().to_string_lossy().into_owned(),
                 )
             })
             .collect();
@@ -985,7 +1136,7 @@ impl ModuleRuntime for DockerModuleRuntime {
                 true,
                 options.since(),
                 options.until(),
-                false,
+                options.timestamps(),
                 tail,
             )
             .then(|result| match result {
@@ -1019,13 +1170,59 @@ impl ModuleRuntime for DockerModuleRuntime {
         }))
     }
 
+    fn start_all(&self) -> Self::StartAllFuture {
+        let self_for_start = self.clone();
+        Box::new(self.list().and_then(move |list| {
+            let sequence = startup_sequence(
+                list.iter()
+                    .map(|m| {
+                        startup::module_startup(m.name(), m.config().create_options().labels())
+                    })
+                    .collect(),
+            )
+            .context(ErrorKind::RuntimeOperation(RuntimeOperation::StartModules))
+            .map_err(Error::from);
+
+            // Modules without a startup order or dependencies are left for edgeAgent to start.
+            sequence.into_future().and_then(move |sequence| {
+                stream::iter_ok(sequence.into_iter().filter(startup::is_ordered)).for_each(
+                    move |module| {
+                        let self_for_start = self_for_start.clone();
+                        let client = self_for_start.client.clone();
+                        let name = module.name().to_string();
+                        let dependencies = module.depends_on().to_vec();
+
+                        stream::iter_ok(dependencies)
+                            .for_each(move |dependency| {
+                                startup::wait_until_ready(client.clone(), dependency)
+                            })
+                            .and_then(move |()| {
+                                <DockerModuleRuntime as ModuleRuntime>::start(
+                                    &self_for_start,
+                                    &name,
+                                )
+                                .or_else(|err| {
+                                    match Fail::find_root_cause(&err).downcast_ref::<ErrorKind>() {
+                                        Some(ErrorKind::NotModified) => Ok(()),
+                                        _ => Err(err),
+                                    }
+                                })
+                            })
+                    },
+                )
+            })
+        }))
+    }
+
+    /// The startup order and dependencies of modules come from their container labels. If they
+    /// can't be put in order, all modules are stopped at the same time.
     fn stop_all(&self, wait_before_kill: Option<Duration>) -> Self::StopAllFuture {
         let self_for_stop = self.clone();
         Box::new(self.list().and_then(move |list| {
-            let n = list.into_iter().map(move |c| {
+            let stop = move |name: String, wait_before_kill: Option<Duration>| {
                 <DockerModuleRuntime as ModuleRuntime>::stop(
                     &self_for_stop,
-                    c.name(),
+                    &name,
                     wait_before_kill,
                 )
                 .or_else(|err| {
@@ -1034,10 +1231,210 @@ impl ModuleRuntime for DockerModuleRuntime {
                         _ => Err(err),
                     }
                 })
-            });
-            future::join_all(n).map(|_| ())
+            };
+
+            let sequence = startup_sequence(
+                list.iter()
+                    .map(|m| {
+                        startup::module_startup(m.name(), m.config().create_options().labels())
+                    })
+                    .collect(),
+            );
+
+            let stages: Vec<Vec<String>> = match sequence {
+                Ok(sequence) => shutdown_stages(sequence)
+                    .into_iter()
+                    .map(|stage| stage.iter().map(|m| m.name().to_string()).collect())
+                    .collect(),
+                Err(err) => {
+                    warn!("Stopping modules in any order. {}", err);
+                    vec![list.iter().map(|m| m.name().to_string()).collect()]
+                }
+            };
+
+            stop_in_stages(stages, wait_before_kill, stop)
         }))
     }
+
+    fn exec(&self, id: &str, options: &ExecOptions) -> Self::ExecFuture {
+        info!("Running {:?} in module {}...", options.cmd(), id);
+        let id = id.to_string();
+
+        if let Err(err) = ensure_not_empty_with_context(&id, || {
+            ErrorKind::RuntimeOperation(RuntimeOperation::ExecModule(id.clone()))
+        }) {
+            return Box::new(future::err(Error::from(err)));
+        }
+
+        let exec_config = ExecConfig::new()
+            .with_cmd(options.cmd().to_vec())
+            .with_tty(options.tty())
+            .with_attach_stdin(options.stdin())
+            .with_attach_stdout(true)
+            .with_attach_stderr(true);
+        let exec_start_config = ExecStartConfig::new()
+            .with_detach(false)
+            .with_tty(options.tty());
+
+        let client = self.client.clone();
+        let module_id = id.clone();
+        let result = self
+            .client
+            .exec_api()
+            .container_exec(&id, exec_config)
+            .and_then(move |exec| {
+                let exec_id = exec.id().clone();
+                client
+                    .exec_api()
+                    .exec_start(&exec_id, exec_start_config)
+                    .map(move |io| {
+                        // The exit code is only looked up once the command's output has ended.
+                        let exit_code: Self::ExecExitCodeFuture = Box::new(
+                            future::lazy(move || client.exec_api().exec_inspect(&exec_id))
+                                .map(|exec| exec.exit_code())
+                                .map_err(|err| {
+                                    Error::from_docker_error(
+                                        err,
+                                        ErrorKind::RuntimeOperation(RuntimeOperation::ExecModule(
+                                            module_id,
+                                        )),
+                                    )
+                                }),
+                        );
+                        (io, exit_code)
+                    })
+            })
+            .then(|result| match result {
+                Ok(exec) => {
+                    info!("Successfully started command in module {}", id);
+                    Ok(exec)
+                }
+                Err(err) => {
+                    let err = Error::from_docker_error(
+                        err,
+                        ErrorKind::RuntimeOperation(RuntimeOperation::ExecModule(id)),
+                    );
+                    log_failure(Level::Warn, &err);
+                    Err(err)
+                }
+            });
+
+        Box::new(result)
+    }
+
+    fn get_archive(&self, id: &str, path: &str) -> Self::GetArchiveFuture {
+        info!("Copying {} from module {}...", path, id);
+        let id = id.to_string();
+
+        if let Err(err) = ensure_not_empty_with_context(&id, || {
+            ErrorKind::RuntimeOperation(RuntimeOperation::GetModuleArchive(id.clone()))
+        }) {
+            return Box::new(future::err(Error::from(err)));
+        }
+
+        let max_copy_size = self.max_copy_size;
+        let result = self
+            .client
+            .container_api()
+            .container_archive(&id, path)
+            .then(move |result| match result {
+                Ok(body) => Ok(Archive::new(id, body, max_copy_size)),
+                Err(err) => {
+                    let err = Error::from_docker_error(
+                        err,
+                        ErrorKind::RuntimeOperation(RuntimeOperation::GetModuleArchive(id)),
+                    );
+                    log_failure(Level::Warn, &err);
+                    Err(err)
+                }
+            });
+
+        Box::new(result)
+    }
+
+    fn put_archive<S>(&self, id: &str, path: &str, archive: S) -> Self::PutArchiveFuture
+    where
+        S: Stream<Item = Bytes, Error = io::Error> + Send + 'static,
+    {
+        info!("Copying to {} in module {}...", path, id);
+        let id = id.to_string();
+
+        if let Err(err) = ensure_not_empty_with_context(&id, || {
+            ErrorKind::RuntimeOperation(RuntimeOperation::PutModuleArchive(id.clone()))
+        }) {
+            return Box::new(future::err(Error::from(err)));
+        }
+
+        let refused = Arc::new(Mutex::new(None));
+        let archive = check_archive(archive, self.max_copy_size, refused.clone());
+
+        let result = self
+            .client
+            .container_api()
+            .put_container_archive(&id, path, Body::wrap_stream(archive), true)
+            .then(move |result| match result {
+                Ok(()) => {
+                    info!("Successfully copied to module {}", id);
+                    Ok(())
+                }
+                Err(err) => {
+                    let context =
+                        ErrorKind::RuntimeOperation(RuntimeOperation::PutModuleArchive(id));
+                    let refused = refused.lock().expect("lock poisoned").take();
+                    let err = match refused {
+                        Some(refused) => Error::from(refused.context(context)),
+                        None => Error::from_docker_error(err, context),
+                    };
+                    log_failure(Level::Warn, &err);
+                    Err(err)
+                }
+            });
+
+        Box::new(result)
+    }
+}
+
+/// The magic bytes that gzip, bzip2 and xz files start with.
+const COMPRESSED_MAGIC: &[&[u8]] = &[b"\x1f\x8b", b"BZh", b"\xfd7zXZ\x00"];
+const MAGIC_LEN: usize = 6;
+
+/// Checks an archive as it's streamed to the container runtime, so a copy that's too large is
+/// only noticed once the limit is reached, which aborts the request. The container runtime
+/// extracts compressed archives, whose size is then only known too late, so they're refused.
+/// Why an archive was refused is put in `refused`.
+fn check_archive<S>(
+    archive: S,
+    max_copy_size: u64,
+    refused: Arc<Mutex<Option<ErrorKind>>>,
+) -> impl Stream<Item = Bytes, Error = io::Error>
+where
+    S: Stream<Item = Bytes, Error = io::Error>,
+{
+    let mut copied = 0;
+    let mut head = Vec::with_capacity(MAGIC_LEN);
+    archive.and_then(move |bytes| {
+        if head.len() < MAGIC_LEN {
+            let missing = (MAGIC_LEN - head.len()).min(bytes.len());
+            head.extend_from_slice(&bytes[..missing]);
+        }
+        copied += bytes.len() as u64;
+
+        let error = if COMPRESSED_MAGIC.iter().any(|magic| head.starts_with(magic)) {
+            Some(ErrorKind::CopyCompressed)
+        } else if copied > max_copy_size {
+            Some(ErrorKind::CopySizeExceeded(max_copy_size))
+        } else {
+            None
+        };
+        match error {
+            Some(error) => {
+                let err = io::Error::new(io::ErrorKind::Other, error.to_string());
+                *refused.lock().expect("lock poisoned") = Some(error);
+                Err(err)
+            }
+            None => Ok(bytes),
+        }
+    })
 }
 
 impl Authenticator for DockerModuleRuntime {
@@ -1098,6 +1495,61 @@ impl From<Logs> for Body {
     }
 }
 
+/// A tar archive of files in a module. It fails once it's larger than the copy size limit.
+#[derive(Debug)]
+pub struct Archive {
+    name: String,
+    body: Body,
+    remaining: u64,
+    max_copy_size: u64,
+}
+
+impl Archive {
+    fn new(name: String, body: Body, max_copy_size: u64) -> Self {
+        Archive {
+            name,
+            body,
+            remaining: max_copy_size,
+            max_copy_size,
+        }
+    }
+}
+
+impl Stream for Archive {
+    type Item = Chunk;
+    type Error = Error;
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        match self.body.poll() {
+            Ok(Async::Ready(Some(chunk))) => {
+                let len = chunk.len() as u64;
+                if len > self.remaining {
+                    return Err(Error::from(
+                        ErrorKind::CopySizeExceeded(self.max_copy_size).context(
+                            ErrorKind::RuntimeOperation(RuntimeOperation::GetModuleArchive(
+                                self.name.clone(),
+                            )),
+                        ),
+                    ));
+                }
+                self.remaining -= len;
+                Ok(Async::Ready(Some(Chunk(chunk))))
+            }
+            Ok(Async::Ready(None)) => Ok(Async::Ready(None)),
+            Ok(Async::NotReady) => Ok(Async::NotReady),
+            Err(err) => Err(Error::from(err.context(ErrorKind::RuntimeOperation(
+                RuntimeOperation::GetModuleArchive(self.name.clone()),
+            )))),
+        }
+    }
+}
+
+impl From<Archive> for Body {
+    fn from(archive: Archive) -> Self {
+        Body::wrap_stream(archive.map(|chunk| chunk.0).map_err(Fail::compat))
+    }
+}
+
 #[derive(Debug, Default)]
 pub struct Chunk(HyperChunk);
 
@@ -1237,50 +1689,239 @@ where
     })
 }
 
-fn get_notary_parameters(
-    config: &DockerConfig,
-    notary_registries: &BTreeMap<String, PathBuf>,
-    image_with_tag: &str,
-) -> Option<(Option<String>, Arc<str>, String, PathBuf)> {
-    // check if the serveraddress exists & check if it exists in notary_registries
-    let registry_auth = config.auth();
-    let (registry_hostname, registry_username, registry_password) = match registry_auth {
-        Some(a) => (a.serveraddress(), a.username(), a.password()),
-        None => (None, None, None),
+fn pull_from_registry(
+    client: &DockerClient<UrlConnector>,
+    image: String,
+    creds: &str,
+) -> impl Future<Item = String, Error = Error> + Send {
+    client
+        .image_api()
+        .image_create(&image, "", "", "", "", creds, "")
+        .then(|result| match result {
+            Ok(()) => Ok(image),
+            Err(err) => Err(Error::from_docker_error(
+                err,
+                ErrorKind::RegistryOperation(RegistryOperation::PullImage(image)),
+            )),
+        })
+}
+
+// Tries the image import directory, then each registry mirror in order. Resolves to whether
+// one of them provided the image. Failures are only logged, so that the next source is tried.
+fn pull_from_image_sources(
+    client: &DockerClient<UrlConnector>,
+    image: &str,
+    image_import_dir: Option<&Path>,
+    registry_mirrors: &[String],
+) -> Box<dyn Future<Item = bool, Error = Error> + Send> {
+    let mut pulled: Box<dyn Future<Item = bool, Error = Error> + Send> = match image_import_dir {
+        Some(image_import_dir) => Box::new(import_image(client, image, image_import_dir)),
+        None => Box::new(future::ok(false)),
     };
-    let hostname = registry_hostname?;
-    let config_path = notary_registries.get(hostname)?;
-    info!("{} is enabled for notary content trust", hostname);
-    let notary_auth = match (registry_username, registry_password) {
-        (None, None) => None,
-        (username, password) => {
-            let notary_auth = format!(
-                "{}:{}",
-                username.unwrap_or_default(),
-                password.unwrap_or_default()
-            );
-            let notary_auth = base64::encode(&notary_auth);
-            Some(notary_auth)
+
+    for mirrored_image in registry_mirrors
+        .iter()
+        .filter_map(|mirror| image_sources::mirror_image(mirror, image))
+    {
+        let client = client.clone();
+        pulled = Box::new(pulled.and_then(move |pulled| {
+            if pulled {
+                Either::A(future::ok(true))
+            } else {
+                Either::B(pull_from_mirror(&client, mirrored_image))
+            }
+        }));
+    }
+
+    pulled
+}
+
+fn import_image(
+    client: &DockerClient<UrlConnector>,
+    image: &str,
+    image_import_dir: &Path,
+) -> impl Future<Item = bool, Error = Error> + Send {
+    let client = client.clone();
+    let image = image.to_owned();
+    find_image_tarball(image_import_dir.to_owned(), image.clone()).and_then(move |tarball| {
+        match tarball {
+            Some(tarball) => Either::A(import_image_tarball(client, image, tarball)),
+            None => Either::B(future::ok(false)),
         }
+    })
+}
+
+// Looking for the tarball reads the import directory and the tarballs' headers, so it runs on
+// its own thread instead of the reactor.
+fn find_image_tarball(
+    image_import_dir: PathBuf,
+    image: String,
+) -> impl Future<Item = Option<ImageTarball>, Error = Error> + Send {
+    let (sender, receiver) = futures::sync::oneshot::channel();
+    std::thread::spawn(move || {
+        let tarball = match image_sources::find_image_tarball(&image_import_dir, &image) {
+            Ok(Some(tarball)) => Some(tarball),
+            Ok(None) => {
+                debug!(
+                    "No tarball for image {} in {}",
+                    image,
+                    image_import_dir.display()
+                );
+                None
+            }
+            Err(err) => {
+                warn!(
+                    "Could not look for image tarballs in {}: {}",
+                    image_import_dir.display(),
+                    err
+                );
+                None
+            }
+        };
+        let _ = sender.send(tarball);
+    });
+    receiver.then(|tarball| Ok(tarball.unwrap_or(None)))
+}
+
+// The tarball is only imported if the engine doesn't already have the image it contains, and
+// it is streamed to the engine rather than read into memory.
+fn import_image_tarball(
+    client: DockerClient<UrlConnector>,
+    image: String,
+    tarball: ImageTarball,
+) -> impl Future<Item = bool, Error = Error> + Send {
+    let ImageTarball { path, image_id } = tarball;
+
+    let is_present = match image_id {
+        Some(image_id) => Either::A(
+            client
+                .image_api()
+                .image_inspect(&image)
+                .then(move |result| Ok(result.map_or(false, |present| *present.id() == image_id))),
+        ),
+        None => Either::B(future::ok(false)),
     };
-    let mut image_with_tag_parts = image_with_tag.split(':');
-    let gun = image_with_tag_parts
-        .next()
-        .expect("split always returns atleast one element")
-        .to_owned();
-    let gun: Arc<str> = gun.into();
-    let tag = image_with_tag_parts.next().unwrap_or("latest").to_owned();
-
-    Some((notary_auth, gun, tag, config_path.to_path_buf()))
+
+    is_present.and_then(move |is_present| {
+        if is_present {
+            debug!(
+                "Image {} from {} is already present, not importing it again",
+                image,
+                path.display()
+            );
+            return Either::A(future::ok(true));
+        }
+
+        info!("Importing image {} from {}...", image, path.display());
+        let import = tokio::fs::File::open(path)
+            .and_then(tokio::fs::File::metadata)
+            .map_err({
+                let image = image.clone();
+                move |err| {
+                    Error::from(err.context(ErrorKind::RegistryOperation(
+                        RegistryOperation::ImportImage(image),
+                    )))
+                }
+            })
+            .and_then({
+                let image = image.clone();
+                move |(file, metadata)| {
+                    let tarball = Body::wrap_stream(
+                        FramedRead::new(file, BytesCodec::new()).map(BytesMut::freeze),
+                    );
+                    client
+                        .image_api()
+                        .image_load(tarball, metadata.len(), true)
+                        .map_err(|err| {
+                            Error::from_docker_error(
+                                err,
+                                ErrorKind::RegistryOperation(RegistryOperation::ImportImage(image)),
+                            )
+                        })
+                }
+            })
+            .then(move |result| match result {
+                Ok(()) => Ok(true),
+                Err(err) => {
+                    log_failure(Level::Warn, &err);
+                    warn!("Could not import image {}, pulling it instead", image);
+                    Ok(false)
+                }
+            });
+        Either::B(import)
+    })
+}
+
+// The image is tagged with its original name after the pull, so that modules find it, and the
+// mirror's name for it is removed again.
+fn pull_from_mirror(
+    client: &DockerClient<UrlConnector>,
+    mirrored_image: MirroredImage,
+) -> impl Future<Item = bool, Error = Error> + Send {
+    let MirroredImage {
+        image,
+        repository,
+        tag,
+    } = mirrored_image;
+    info!(
+        "Pulling image {}:{} from mirror as {}...",
+        repository, tag, image
+    );
+
+    let client = client.clone();
+    client
+        .image_api()
+        .image_create(&image, "", "", "", "", "", "")
+        .and_then({
+            let client = client.clone();
+            let image = image.clone();
+            move |()| client.image_api().image_tag(&image, &repository, &tag)
+        })
+        .map_err({
+            let image = image.clone();
+            move |err| {
+                Error::from_docker_error(
+                    err,
+                    ErrorKind::RegistryOperation(RegistryOperation::PullImage(image)),
+                )
+            }
+        })
+        .and_then(move |()| {
+            client
+                .image_api()
+                .image_delete(&image, false, true)
+                .then(move |result| {
+                    if let Err(err) = result {
+                        debug!("Could not remove mirror tag {}: {:?}", image, err);
+                    }
+                    Ok(true)
+                })
+        })
+        .or_else(|err| {
+            log_failure(Level::Warn, &err);
+            Ok(false)
+        })
+}
+
+fn registry_credentials(config: &DockerConfig) -> Option<(String, String)> {
+    let auth = config.auth()?;
+    match (auth.username(), auth.password()) {
+        (None, None) => None,
+        (username, password) => Some((
+            username.unwrap_or_default().to_owned(),
+            password.unwrap_or_default().to_owned(),
+        )),
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::{
-        authenticate, future, list_with_details, parse_get_response, AuthId, Authenticator,
-        BTreeMap, Body, CoreSystemInfo, Deserializer, DockerModuleRuntime, DockerModuleTop,
-        Duration, Error, ErrorKind, Future, InlineResponse200, LogOptions, MakeModuleRuntime,
-        Module, ModuleId, ModuleRuntime, ModuleRuntimeState, ModuleSpec, Pid, Request, Stream,
+        authenticate, check_archive, future, io, list_with_details, parse_get_response, stream,
+        Arc, Archive, AuthId, Authenticator, BTreeMap, Body, Bytes, CoreSystemInfo, Deserializer,
+        DockerModuleRuntime, DockerModuleTop, Duration, Error, ErrorKind, ExecOptions, Fail,
+        Future, InlineResponse200, LogOptions, MakeModuleRuntime, Module, ModuleId, ModuleRuntime,
+        ModuleRuntimeState, ModuleSpec, Mutex, Pid, Request, RuntimeOperation, Stream,
         SystemResources,
     };
 
@@ -1290,10 +1931,74 @@ mod tests {
     use futures::stream::Empty;
 
     use edgelet_core::{
-        settings::AutoReprovisioningMode, Connect, Endpoints, Listen, ModuleRegistry, ModuleTop,
-        RuntimeSettings, WatchdogSettings,
+        settings::AutoReprovisioningMode, Connect, Endpoints, ImageGarbageCollection, ImageInfo,
+        Listen, ModuleRegistry, ModuleTop, RuntimeSettings, ServerCertPolicy, WatchdogSettings,
     };
 
+    #[test]
+    fn archive_fails_past_max_copy_size() {
+        let body = Body::wrap_stream(futures::stream::iter_ok::<_, io::Error>(vec![
+            "abcd", "efgh",
+        ]));
+        let archive = Archive::new("m1".to_string(), body, 6);
+
+        let err = archive.collect().wait().unwrap_err();
+        match err.kind() {
+            ErrorKind::RuntimeOperation(RuntimeOperation::GetModuleArchive(name)) => {
+                assert_eq!("m1", name)
+            }
+            kind => panic!("unexpected error kind {:?}", kind),
+        }
+        match err
+            .cause()
+            .and_then(|cause| cause.downcast_ref::<ErrorKind>())
+        {
+            Some(ErrorKind::CopySizeExceeded(6)) => (),
+            cause => panic!("unexpected cause {:?}", cause),
+        }
+    }
+
+    #[test]
+    fn gzipped_archives_are_refused() {
+        let gzipped: &[u8] = &[0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00];
+        let refused = Arc::new(Mutex::new(None));
+        let archive = check_archive(
+            stream::iter_ok(vec![Bytes::from(&gzipped[..1]), Bytes::from(&gzipped[1..])]),
+            100,
+            refused.clone(),
+        );
+
+        archive.collect().wait().unwrap_err();
+        match refused.lock().unwrap().take() {
+            Some(ErrorKind::CopyCompressed) => (),
+            kind => panic!("unexpected error kind {:?}", kind),
+        }
+    }
+
+    #[test]
+    fn archives_fail_to_copy_past_max_copy_size() {
+        let refused = Arc::new(Mutex::new(None));
+        let tar = check_archive(
+            stream::iter_ok(vec![Bytes::from("abcd"), Bytes::from("efgh")]),
+            6,
+            refused.clone(),
+        );
+        tar.collect().wait().unwrap_err();
+        match refused.lock().unwrap().take() {
+            Some(ErrorKind::CopySizeExceeded(6)) => (),
+            kind => panic!("unexpected error kind {:?}", kind),
+        }
+
+        let refused = Arc::new(Mutex::new(None));
+        let tar = check_archive(
+            stream::iter_ok(vec![Bytes::from("abcd"), Bytes::from("efgh")]),
+            8,
+            refused.clone(),
+        );
+        assert_eq!(2, tar.collect().wait().unwrap().len());
+        assert!(refused.lock().unwrap().is_none());
+    }
+
     #[test]
     fn merge_env_empty() {
         let cur_env = Some(&[][..]);
@@ -1512,6 +2217,18 @@ mod tests {
             unimplemented!()
         }
 
+        fn image_garbage_collection(&self) -> &ImageGarbageCollection {
+            unimplemented!()
+        }
+
+        fn server_cert_policies(&self) -> &BTreeMap<String, ServerCertPolicy> {
+            unimplemented!()
+        }
+
+        fn module_secrets(&self) -> &BTreeMap<String, BTreeMap<String, String>> {
+            unimplemented!()
+        }
+
         fn endpoints(&self) -> &Endpoints {
             unimplemented!()
         }
@@ -1535,6 +2252,10 @@ mod tests {
         fn auto_reprovisioning_mode(&self) -> &AutoReprovisioningMode {
             unimplemented!()
         }
+
+        fn log_level(&self) -> Option<&str> {
+            unimplemented!()
+        }
     }
 
     #[derive(Clone, Copy, Debug, PartialEq)]
@@ -1589,6 +2310,7 @@ mod tests {
         type Error = Error;
         type PullFuture = FutureResult<(), Self::Error>;
         type RemoveFuture = FutureResult<(), Self::Error>;
+        type ListImagesFuture = FutureResult<Vec<ImageInfo>, Self::Error>;
         type Config = TestConfig;
 
         fn pull(&self, _config: &Self::Config) -> Self::PullFuture {
@@ -1598,6 +2320,10 @@ mod tests {
         fn remove(&self, _name: &str) -> Self::RemoveFuture {
             unimplemented!()
         }
+
+        fn list_images(&self) -> Self::ListImagesFuture {
+            unimplemented!()
+        }
     }
 
     impl DockerModuleTop for TestModule {
@@ -1622,10 +2348,15 @@ mod tests {
         type Settings = TestSettings;
         type Error = Error;
         type Future = FutureResult<Self, Self::Error>;
+        type ReloadFuture = FutureResult<(), Self::Error>;
 
         fn make_runtime(_settings: Self::Settings) -> Self::Future {
             unimplemented!()
         }
+
+        fn reload(_runtime: &Self, _settings: &Self::Settings) -> Self::ReloadFuture {
+            unimplemented!()
+        }
     }
 
     impl ModuleRuntime for TestModuleList {
@@ -1635,6 +2366,7 @@ mod tests {
         type ModuleRegistry = Self;
         type Chunk = String;
         type Logs = Empty<Self::Chunk, Self::Error>;
+        type Archive = Empty<Self::Chunk, Self::Error>;
 
         type CreateFuture = FutureResult<(), Self::Error>;
         type GetFuture = FutureResult<(Self::Module, ModuleRuntimeState), Self::Error>;
@@ -1650,7 +2382,13 @@ mod tests {
         type SystemResourcesFuture =
             Box<dyn Future<Item = SystemResources, Error = Self::Error> + Send>;
         type RemoveAllFuture = FutureResult<(), Self::Error>;
+        type StartAllFuture = FutureResult<(), Self::Error>;
         type StopAllFuture = FutureResult<(), Self::Error>;
+        type ExecIo = std::io::Cursor<Vec<u8>>;
+        type ExecExitCodeFuture = FutureResult<Option<i32>, Self::Error>;
+        type ExecFuture = FutureResult<(Self::ExecIo, Self::ExecExitCodeFuture), Self::Error>;
+        type GetArchiveFuture = FutureResult<Self::Archive, Self::Error>;
+        type PutArchiveFuture = FutureResult<(), Self::Error>;
 
         fn create(&self, _module: ModuleSpec<Self::Config>) -> Self::CreateFuture {
             unimplemented!()
@@ -1704,9 +2442,28 @@ mod tests {
             unimplemented!()
         }
 
+        fn start_all(&self) -> Self::StartAllFuture {
+            unimplemented!()
+        }
+
         fn stop_all(&self, _wait_before_kill: Option<Duration>) -> Self::StopAllFuture {
             unimplemented!()
         }
+
+        fn exec(&self, _id: &str, _options: &ExecOptions) -> Self::ExecFuture {
+            unimplemented!()
+        }
+
+        fn get_archive(&self, _id: &str, _path: &str) -> Self::GetArchiveFuture {
+            unimplemented!()
+        }
+
+        fn put_archive<S>(&self, _id: &str, _path: &str, _archive: S) -> Self::PutArchiveFuture
+        where
+            S: Stream<Item = Bytes, Error = io::Error> + Send + 'static,
+        {
+            unimplemented!()
+        }
     }
 
     impl Authenticator for TestModuleList {
@@ -1,12 +1,15 @@
 // Copyright (c) Microsoft. All rights reserved.
 
 use std::collections::BTreeMap;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
-use docker::models::{ContainerCreateBodyNetworkingConfig, EndpointSettings, HostConfig};
+use docker::models::{
+    ContainerCreateBody, ContainerCreateBodyNetworkingConfig, EndpointSettings, HostConfig,
+};
 use edgelet_core::{
-    settings::AutoReprovisioningMode, Connect, Endpoints, Listen, MobyNetwork, ModuleSpec,
-    RuntimeSettings, Settings as BaseSettings, UrlExt, WatchdogSettings,
+    settings::AutoReprovisioningMode, Connect, Endpoints, ImageGarbageCollection, Listen,
+    MobyNetwork, ModuleSpec, Network, RuntimeSettings, ServerCertPolicy, Settings as BaseSettings,
+    UrlExt, WatchdogSettings,
 };
 use failure::{Context, Fail, ResultExt};
 
@@ -20,12 +23,35 @@ const EDGE_NETWORKID_KEY: &str = "NetworkId";
 
 const UNIX_SCHEME: &str = "unix";
 
+const DEFAULT_MAX_COPY_SIZE: u64 = 100 * 1024 * 1024;
+
 #[derive(Clone, Debug, serde_derive::Deserialize, serde_derive::Serialize)]
 pub struct MobyRuntime {
     pub uri: Url,
+    /// Registries to try, in order, before the registry in a module's image reference.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub registry_mirrors: Vec<String>,
+    /// Directory of `docker save` tarballs that images are imported from before pulling them.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image_import_dir: Option<PathBuf>,
     pub network: MobyNetwork,
+    /// Networks besides `network`, that modules join with `module_networks`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub additional_networks: Vec<Network>,
+    /// The networks that each module joins instead of `network`. Modules that aren't listed join
+    /// `network`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub module_networks: BTreeMap<String, Vec<String>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub content_trust: Option<ContentTrust>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resource_limits: Option<ResourceLimits>,
+    /// Largest number of bytes that can be copied to or from a module at once.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_copy_size: Option<u64>,
+    /// The container engine that serves the Docker API at `uri`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub engine: Option<ContainerEngine>,
 }
 
 impl MobyRuntime {
@@ -37,9 +63,73 @@ impl MobyRuntime {
         &self.network
     }
 
+    pub fn additional_networks(&self) -> &[Network] {
+        &self.additional_networks
+    }
+
+    pub fn module_networks(&self) -> &BTreeMap<String, Vec<String>> {
+        &self.module_networks
+    }
+
     pub fn content_trust(&self) -> Option<&ContentTrust> {
         self.content_trust.as_ref()
     }
+
+    pub fn resource_limits(&self) -> Option<&ResourceLimits> {
+        self.resource_limits.as_ref()
+    }
+
+    pub fn registry_mirrors(&self) -> &[String] {
+        &self.registry_mirrors
+    }
+
+    pub fn image_import_dir(&self) -> Option<&Path> {
+        self.image_import_dir.as_deref()
+    }
+
+    pub fn max_copy_size(&self) -> u64 {
+        self.max_copy_size.unwrap_or(DEFAULT_MAX_COPY_SIZE)
+    }
+
+    pub fn engine(&self) -> ContainerEngine {
+        self.engine.unwrap_or_default()
+    }
+
+    /// Checks that modules only join `network` and `additional_networks`, which are the
+    /// networks that are created for them. A misspelled network would otherwise cut a module
+    /// off from the network.
+    pub fn validate_module_networks(&self) -> Result<(), ErrorKind> {
+        let is_known = |name: &str| {
+            name == self.network.name()
+                || self
+                    .additional_networks
+                    .iter()
+                    .any(|network| network.name() == name)
+        };
+        for (module, networks) in &self.module_networks {
+            if let Some(unknown) = networks.iter().find(|network| !is_known(network)) {
+                return Err(ErrorKind::InvalidModuleNetworks(format!(
+                    "module {} joins {}, which is neither network nor one of additional_networks",
+                    module, unknown
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The container engines that modules can be run with.
+#[derive(Clone, Copy, Debug, PartialEq, serde_derive::Deserialize, serde_derive::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ContainerEngine {
+    Docker,
+    Podman,
+}
+
+impl Default for ContainerEngine {
+    fn default() -> Self {
+        ContainerEngine::Docker
+    }
 }
 
 #[derive(Clone, Debug, serde_derive::Deserialize, serde_derive::Serialize)]
@@ -53,6 +143,207 @@ impl ContentTrust {
     }
 }
 
+/// Device-level default resource limits for modules.
+///
+/// Each limit is applied to a module's `HostConfig` at create time unless the
+/// module's own create options already set that property.
+#[derive(Clone, Debug, Default, PartialEq, serde_derive::Deserialize, serde_derive::Serialize)]
+pub struct ResourceLimits {
+    /// Memory limit in bytes.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memory: Option<i64>,
+    /// Total memory limit (memory + swap) in bytes. `-1` allows unlimited swap.
+    /// Only applied to modules that also get the default `memory`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memory_swap: Option<i64>,
+    /// Relative CPU weight versus other containers.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cpu_shares: Option<i32>,
+    /// Length of a CPU period in microseconds.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cpu_period: Option<i64>,
+    /// Microseconds of CPU time the container can get in a CPU period.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cpu_quota: Option<i64>,
+    /// CPU quota in units of 10^-9 CPUs.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nano_cpus: Option<i64>,
+    /// Maximum number of processes. `-1` means unlimited.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pids_limit: Option<i64>,
+    /// Storage driver options, e.g. `{ size = "1G" }`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub storage_opt: Option<BTreeMap<String, String>>,
+}
+
+impl ResourceLimits {
+    pub fn memory(&self) -> Option<i64> {
+        self.memory
+    }
+
+    pub fn memory_swap(&self) -> Option<i64> {
+        self.memory_swap
+    }
+
+    pub fn cpu_shares(&self) -> Option<i32> {
+        self.cpu_shares
+    }
+
+    pub fn cpu_period(&self) -> Option<i64> {
+        self.cpu_period
+    }
+
+    pub fn cpu_quota(&self) -> Option<i64> {
+        self.cpu_quota
+    }
+
+    pub fn nano_cpus(&self) -> Option<i64> {
+        self.nano_cpus
+    }
+
+    pub fn pids_limit(&self) -> Option<i64> {
+        self.pids_limit
+    }
+
+    pub fn storage_opt(&self) -> Option<&BTreeMap<String, String>> {
+        self.storage_opt.as_ref()
+    }
+
+    /// Checks the limits for values that the engine would refuse when creating a container.
+    pub fn validate(&self) -> Result<(), ErrorKind> {
+        let invalid = |message: &str| Err(ErrorKind::InvalidResourceLimits(message.to_string()));
+
+        if self.memory.map_or(false, |memory| memory <= 0) {
+            return invalid("memory must be positive");
+        }
+        if let Some(memory_swap) = self.memory_swap {
+            if memory_swap != -1 && memory_swap <= 0 {
+                return invalid("memory_swap must be positive or -1");
+            }
+            if memory_swap != -1 && self.memory.map_or(false, |memory| memory_swap < memory) {
+                return invalid("memory_swap must not be less than memory");
+            }
+        }
+        if self.cpu_shares.map_or(false, |cpu_shares| cpu_shares < 0) {
+            return invalid("cpu_shares must not be negative");
+        }
+        if self.cpu_period.map_or(false, |cpu_period| {
+            !(1000..=1_000_000).contains(&cpu_period)
+        }) {
+            return invalid("cpu_period must be between 1000 and 1000000 microseconds");
+        }
+        if self.cpu_quota.map_or(false, |cpu_quota| cpu_quota < 1000) {
+            return invalid("cpu_quota must be at least 1000 microseconds");
+        }
+        if let Some(nano_cpus) = self.nano_cpus {
+            if nano_cpus <= 0 {
+                return invalid("nano_cpus must be positive");
+            }
+            if self.cpu_period.is_some() || self.cpu_quota.is_some() {
+                return invalid("nano_cpus can't be set together with cpu_period or cpu_quota");
+            }
+        }
+        if self.pids_limit.map_or(false, |pids_limit| pids_limit < -1) {
+            return invalid("pids_limit must not be negative, except for -1");
+        }
+
+        Ok(())
+    }
+
+    /// Fills in every limit that `host_config` does not already set.
+    pub fn apply_to(&self, mut host_config: HostConfig) -> HostConfig {
+        // Docker rejects a MemorySwap smaller than Memory, so the default swap limit
+        // only goes with the default memory limit, never with the module's own.
+        if let (None, Some(memory)) = (host_config.memory(), self.memory) {
+            host_config.set_memory(memory);
+            if let (None, Some(memory_swap)) = (host_config.memory_swap(), self.memory_swap) {
+                host_config.set_memory_swap(memory_swap);
+            }
+        }
+        if let (None, Some(cpu_shares)) = (host_config.cpu_shares(), self.cpu_shares) {
+            host_config.set_cpu_shares(cpu_shares);
+        }
+
+        // NanoCPUs and CpuPeriod/CpuQuota are mutually exclusive in Docker,
+        // so a module that sets either form keeps its own CPU limit.
+        let module_sets_cpu_limit = host_config.nano_cp_us().is_some()
+            || host_config.cpu_period().is_some()
+            || host_config.cpu_quota().is_some();
+        if !module_sets_cpu_limit {
+            if let Some(nano_cpus) = self.nano_cpus {
+                host_config.set_nano_cp_us(nano_cpus);
+            } else {
+                if let Some(cpu_period) = self.cpu_period {
+                    host_config.set_cpu_period(cpu_period);
+                }
+                if let Some(cpu_quota) = self.cpu_quota {
+                    host_config.set_cpu_quota(cpu_quota);
+                }
+            }
+        }
+
+        if let (None, Some(pids_limit)) = (host_config.pids_limit(), self.pids_limit) {
+            host_config.set_pids_limit(pids_limit);
+        }
+        if let Some(storage_opt) = &self.storage_opt {
+            let mut merged = storage_opt.clone();
+            if let Some(module_storage_opt) = host_config.storage_opt() {
+                merged.extend(module_storage_opt.clone());
+            }
+            host_config.set_storage_opt(merged);
+        }
+
+        host_config
+    }
+}
+
+/// Makes a module's create options join exactly `networks`, whatever networks they ask for.
+///
+/// Docker only connects a container to one network when creating it, so the create options
+/// join the first network, and the others are returned with their endpoint settings to be
+/// connected once the container exists. A module with no networks isn't connected to any,
+/// not even the default bridge. Endpoint settings that the module's own create options have
+/// for a network, like aliases, are kept.
+pub fn apply_module_networks(
+    create_options: ContainerCreateBody,
+    networks: &[String],
+) -> (ContainerCreateBody, Vec<(String, EndpointSettings)>) {
+    let mut networking_config = create_options
+        .networking_config()
+        .cloned()
+        .unwrap_or_else(ContainerCreateBodyNetworkingConfig::new);
+    let mut endpoints = networking_config
+        .endpoints_config()
+        .cloned()
+        .unwrap_or_else(BTreeMap::new);
+    let mut networks = networks.iter().map(|network| {
+        let endpoint = endpoints
+            .remove(network)
+            .unwrap_or_else(EndpointSettings::new);
+        (network.clone(), endpoint)
+    });
+
+    let mut endpoints_config = BTreeMap::new();
+    let network_mode = if let Some((network, endpoint)) = networks.next() {
+        endpoints_config.insert(network.clone(), endpoint);
+        network
+    } else {
+        "none".to_string()
+    };
+    let others = networks.collect();
+
+    let host_config = create_options
+        .host_config()
+        .cloned()
+        .unwrap_or_else(HostConfig::new)
+        .with_network_mode(network_mode);
+    networking_config.set_endpoints_config(endpoints_config);
+    let create_options = create_options
+        .with_host_config(host_config)
+        .with_networking_config(networking_config);
+    (create_options, others)
+}
+
 /// This struct is the same as the Settings type from the `edgelet_core` crate
 /// except that it also sets up the volume mounting of workload & management
 /// UDS sockets for the edge agent container and injects the docker network
@@ -89,6 +380,11 @@ impl Settings {
 
         init_agent_spec(&mut settings)?;
 
+        if let Some(resource_limits) = settings.moby_runtime().resource_limits() {
+            resource_limits.validate()?;
+        }
+        settings.moby_runtime().validate_module_networks()?;
+
         Ok(settings)
     }
 
@@ -128,6 +424,18 @@ impl RuntimeSettings for Settings {
         self.base.watchdog()
     }
 
+    fn image_garbage_collection(&self) -> &ImageGarbageCollection {
+        self.base.image_garbage_collection()
+    }
+
+    fn server_cert_policies(&self) -> &BTreeMap<String, ServerCertPolicy> {
+        self.base.server_cert_policies()
+    }
+
+    fn module_secrets(&self) -> &BTreeMap<String, BTreeMap<String, String>> {
+        self.base.module_secrets()
+    }
+
     fn endpoints(&self) -> &Endpoints {
         self.base.endpoints()
     }
@@ -151,6 +459,10 @@ impl RuntimeSettings for Settings {
     fn auto_reprovisioning_mode(&self) -> &AutoReprovisioningMode {
         self.base.auto_reprovisioning_mode()
     }
+
+    fn log_level(&self) -> Option<&str> {
+        self.base.log_level()
+    }
 }
 
 fn init_agent_spec(settings: &mut Settings) -> Result<(), LoadSettingsError> {
@@ -308,10 +620,15 @@ impl From<ErrorKind> for LoadSettingsError {
 
 #[cfg(test)]
 mod tests {
-    use super::ContentTrust;
-    use super::{MobyNetwork, MobyRuntime, RuntimeSettings, Settings, Url};
+    use super::{apply_module_networks, ContainerEngine, ContentTrust, ErrorKind};
+    use super::{MobyNetwork, MobyRuntime, ResourceLimits, RuntimeSettings, Settings, Url};
+    use docker::models::{
+        ContainerCreateBody, ContainerCreateBodyNetworkingConfig, EndpointSettings, HostConfig,
+    };
     use edgelet_core::{IpamConfig, DEFAULT_NETWORKID};
     use std::cmp::Ordering;
+    use std::collections::BTreeMap;
+    use std::path::Path;
 
     #[cfg(unix)]
     static GOOD_SETTINGS: &str = "test/linux/sample_settings.toml";
@@ -325,6 +642,16 @@ mod tests {
     static GOOD_SETTINGS_CONTENT_TRUST: &str = "test/linux/sample_settings_content_trust.toml";
     #[cfg(unix)]
     static BAD_SETTINGS_CONTENT_TRUST: &str = "test/linux/bad_settings_content_trust.toml";
+    #[cfg(unix)]
+    static GOOD_SETTINGS_RESOURCE_LIMITS: &str = "test/linux/sample_settings_resource_limits.toml";
+    #[cfg(unix)]
+    static GOOD_SETTINGS_MODULE_NETWORKS: &str = "test/linux/sample_settings_module_networks.toml";
+    #[cfg(unix)]
+    static BAD_SETTINGS_MODULE_NETWORKS: &str = "test/linux/bad_settings_module_networks.toml";
+    #[cfg(unix)]
+    static GOOD_SETTINGS_IMAGE_SOURCES: &str = "test/linux/sample_settings_image_sources.toml";
+    #[cfg(unix)]
+    static GOOD_SETTINGS_PODMAN: &str = "test/linux/sample_settings_podman.toml";
 
     lazy_static::lazy_static! {
         static ref ENV_LOCK: std::sync::Mutex<()> = Default::default();
@@ -335,14 +662,28 @@ mod tests {
         let moby1 = MobyRuntime {
             uri: Url::parse("http://test").unwrap(),
             network: MobyNetwork::Name("".to_string()),
+            additional_networks: Vec::new(),
+            module_networks: BTreeMap::new(),
             content_trust: None,
+            resource_limits: None,
+            registry_mirrors: Vec::new(),
+            image_import_dir: None,
+            max_copy_size: None,
+            engine: None,
         };
         assert_eq!(DEFAULT_NETWORKID, moby1.network().name());
 
         let moby2 = MobyRuntime {
             uri: Url::parse("http://test").unwrap(),
             network: MobyNetwork::Name("some-network".to_string()),
+            additional_networks: Vec::new(),
+            module_networks: BTreeMap::new(),
             content_trust: None,
+            resource_limits: None,
+            registry_mirrors: Vec::new(),
+            image_import_dir: None,
+            max_copy_size: None,
+            engine: None,
         };
         assert_eq!("some-network", moby2.network().name());
     }
@@ -502,4 +843,325 @@ mod tests {
         let settings = Settings::new();
         assert!(settings.is_err());
     }
+
+    #[cfg(unix)]
+    #[test]
+    fn resource_limits_are_read() {
+        let _env_lock = ENV_LOCK.lock().expect("env lock poisoned");
+        std::env::set_var("AZIOT_EDGED_CONFIG", GOOD_SETTINGS_RESOURCE_LIMITS);
+        let settings = Settings::new().unwrap();
+        let limits = settings.moby_runtime().resource_limits().unwrap();
+        assert_eq!(Some(268_435_456), limits.memory());
+        assert_eq!(Some(512), limits.cpu_shares());
+        assert_eq!(Some(500_000_000), limits.nano_cpus());
+        assert_eq!(Some(256), limits.pids_limit());
+        assert_eq!(None, limits.cpu_quota());
+        assert_eq!(
+            Some("1G"),
+            limits
+                .storage_opt()
+                .and_then(|opts| opts.get("size"))
+                .map(AsRef::as_ref)
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn resource_limits_default_to_none() {
+        let _env_lock = ENV_LOCK.lock().expect("env lock poisoned");
+        std::env::set_var("AZIOT_EDGED_CONFIG", GOOD_SETTINGS);
+        let settings = Settings::new().unwrap();
+        assert!(settings.moby_runtime().resource_limits().is_none());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn module_networks_are_read() {
+        let _env_lock = ENV_LOCK.lock().expect("env lock poisoned");
+        std::env::set_var("AZIOT_EDGED_CONFIG", GOOD_SETTINGS_MODULE_NETWORKS);
+        let settings = Settings::new().unwrap();
+        let moby_runtime = settings.moby_runtime();
+
+        let additional_networks: Vec<_> = moby_runtime
+            .additional_networks()
+            .iter()
+            .map(|network| (network.name(), network.ipv6()))
+            .collect();
+        assert_eq!(
+            vec![("third-party", None), ("third-party-v6", Some(true))],
+            additional_networks
+        );
+
+        let module_networks = moby_runtime.module_networks();
+        assert_eq!(
+            Some(&["azure-iot-edge".to_string(), "third-party".to_string()][..]),
+            module_networks.get("edgeHub").map(AsRef::as_ref)
+        );
+        assert_eq!(
+            Some(&["third-party".to_string()][..]),
+            module_networks.get("ThirdPartySensor").map(AsRef::as_ref)
+        );
+        assert_eq!(
+            Some(&[][..]),
+            module_networks.get("OfflineModule").map(AsRef::as_ref)
+        );
+        assert!(module_networks.get("SimulatedSensor").is_none());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn unknown_module_networks_fail() {
+        let _env_lock = ENV_LOCK.lock().expect("env lock poisoned");
+        std::env::set_var("AZIOT_EDGED_CONFIG", BAD_SETTINGS_MODULE_NETWORKS);
+        assert!(Settings::new().is_err());
+
+        std::env::set_var("AZIOT_EDGED_CONFIG", GOOD_SETTINGS_MODULE_NETWORKS);
+        let mut moby_runtime = Settings::new().unwrap().moby_runtime().clone();
+        moby_runtime.validate_module_networks().unwrap();
+        moby_runtime
+            .module_networks
+            .insert("Typo".to_string(), vec!["azure-iot-egde".to_string()]);
+        match moby_runtime.validate_module_networks() {
+            Err(ErrorKind::InvalidModuleNetworks(message)) => {
+                assert!(message.starts_with("module Typo joins azure-iot-egde,"))
+            }
+            result => panic!("unexpected result {:?}", result),
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn module_networks_default_to_none() {
+        let _env_lock = ENV_LOCK.lock().expect("env lock poisoned");
+        std::env::set_var("AZIOT_EDGED_CONFIG", GOOD_SETTINGS);
+        let settings = Settings::new().unwrap();
+        assert!(settings.moby_runtime().additional_networks().is_empty());
+        assert!(settings.moby_runtime().module_networks().is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn image_sources_are_read() {
+        let _env_lock = ENV_LOCK.lock().expect("env lock poisoned");
+        std::env::set_var("AZIOT_EDGED_CONFIG", GOOD_SETTINGS_IMAGE_SOURCES);
+        let settings = Settings::new().unwrap();
+        assert_eq!(
+            &["mirror1.contoso.local:5000", "mirror2.contoso.local"],
+            settings.moby_runtime().registry_mirrors()
+        );
+        assert_eq!(
+            Some(Path::new("/var/lib/aziot/edged/images")),
+            settings.moby_runtime().image_import_dir()
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn image_sources_default_to_none() {
+        let _env_lock = ENV_LOCK.lock().expect("env lock poisoned");
+        std::env::set_var("AZIOT_EDGED_CONFIG", GOOD_SETTINGS);
+        let settings = Settings::new().unwrap();
+        assert!(settings.moby_runtime().registry_mirrors().is_empty());
+        assert!(settings.moby_runtime().image_import_dir().is_none());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn engine_is_read() {
+        let _env_lock = ENV_LOCK.lock().expect("env lock poisoned");
+        std::env::set_var("AZIOT_EDGED_CONFIG", GOOD_SETTINGS_PODMAN);
+        let settings = Settings::new().unwrap();
+        assert_eq!(ContainerEngine::Podman, settings.moby_runtime().engine());
+
+        std::env::set_var("AZIOT_EDGED_CONFIG", GOOD_SETTINGS);
+        let settings = Settings::new().unwrap();
+        assert_eq!(ContainerEngine::Docker, settings.moby_runtime().engine());
+    }
+
+    #[test]
+    fn resource_limits_fill_unset_properties() {
+        let limits = ResourceLimits {
+            memory: Some(1024),
+            cpu_shares: Some(512),
+            pids_limit: Some(64),
+            ..Default::default()
+        };
+
+        let host_config = limits.apply_to(HostConfig::new());
+        assert_eq!(Some(1024), host_config.memory());
+        assert_eq!(Some(512), host_config.cpu_shares());
+        assert_eq!(Some(64), host_config.pids_limit());
+        assert_eq!(None, host_config.memory_swap());
+    }
+
+    #[test]
+    fn resource_limits_do_not_override_create_options() {
+        let limits = ResourceLimits {
+            memory: Some(1024),
+            memory_swap: Some(1536),
+            nano_cpus: Some(500_000_000),
+            storage_opt: Some(
+                vec![
+                    ("size".to_string(), "1G".to_string()),
+                    ("other".to_string(), "a".to_string()),
+                ]
+                .into_iter()
+                .collect(),
+            ),
+            ..Default::default()
+        };
+
+        let host_config = limits.apply_to(
+            HostConfig::new()
+                .with_memory(2048)
+                .with_cpu_quota(50_000)
+                .with_storage_opt(
+                    vec![("size".to_string(), "2G".to_string())]
+                        .into_iter()
+                        .collect(),
+                ),
+        );
+        assert_eq!(Some(2048), host_config.memory());
+        assert_eq!(Some(50_000), host_config.cpu_quota());
+        assert_eq!(None, host_config.nano_cp_us());
+        assert_eq!(None, host_config.memory_swap());
+
+        let storage_opt = host_config.storage_opt().unwrap();
+        assert_eq!(Some("2G"), storage_opt.get("size").map(AsRef::as_ref));
+        assert_eq!(Some("a"), storage_opt.get("other").map(AsRef::as_ref));
+    }
+
+    #[test]
+    fn resource_limits_apply_memory_swap_with_default_memory() {
+        let limits = ResourceLimits {
+            memory: Some(1024),
+            memory_swap: Some(1536),
+            ..Default::default()
+        };
+
+        let host_config = limits.apply_to(HostConfig::new());
+        assert_eq!(Some(1024), host_config.memory());
+        assert_eq!(Some(1536), host_config.memory_swap());
+
+        let host_config = limits.apply_to(HostConfig::new().with_memory_swap(-1));
+        assert_eq!(Some(1024), host_config.memory());
+        assert_eq!(Some(-1), host_config.memory_swap());
+    }
+
+    #[test]
+    fn resource_limits_validate() {
+        let valid = ResourceLimits {
+            memory: Some(1024),
+            memory_swap: Some(-1),
+            cpu_period: Some(100_000),
+            cpu_quota: Some(50_000),
+            pids_limit: Some(-1),
+            ..Default::default()
+        };
+        valid.validate().unwrap();
+
+        let invalid = vec![
+            ResourceLimits {
+                memory: Some(-1),
+                ..Default::default()
+            },
+            ResourceLimits {
+                memory: Some(2048),
+                memory_swap: Some(1024),
+                ..Default::default()
+            },
+            ResourceLimits {
+                cpu_shares: Some(-2),
+                ..Default::default()
+            },
+            ResourceLimits {
+                cpu_period: Some(-100_000),
+                ..Default::default()
+            },
+            ResourceLimits {
+                cpu_quota: Some(-1),
+                ..Default::default()
+            },
+            ResourceLimits {
+                nano_cpus: Some(-500_000_000),
+                ..Default::default()
+            },
+            ResourceLimits {
+                nano_cpus: Some(500_000_000),
+                cpu_quota: Some(50_000),
+                ..Default::default()
+            },
+            ResourceLimits {
+                pids_limit: Some(-2),
+                ..Default::default()
+            },
+        ];
+        for limits in invalid {
+            match limits.validate() {
+                Err(ErrorKind::InvalidResourceLimits(_)) => (),
+                result => panic!("{:?} was not refused: {:?}", limits, result),
+            }
+        }
+    }
+
+    fn endpoint_networks(create_options: &ContainerCreateBody) -> Vec<&str> {
+        create_options
+            .networking_config()
+            .and_then(ContainerCreateBodyNetworkingConfig::endpoints_config)
+            .map_or_else(Vec::new, |endpoints| {
+                endpoints.keys().map(AsRef::as_ref).collect()
+            })
+    }
+
+    #[test]
+    fn module_networks_replace_create_options_networks() {
+        let endpoint: EndpointSettings =
+            serde_json::from_str(r#"{ "Aliases": ["sensor"] }"#).unwrap();
+        let mut endpoints = BTreeMap::new();
+        endpoints.insert("third-party".to_string(), endpoint);
+        endpoints.insert("azure-iot-edge".to_string(), EndpointSettings::new());
+        let create_options = ContainerCreateBody::new()
+            .with_host_config(HostConfig::new().with_network_mode("host".to_string()))
+            .with_networking_config(
+                ContainerCreateBodyNetworkingConfig::new().with_endpoints_config(endpoints),
+            );
+
+        let (create_options, others) = apply_module_networks(
+            create_options,
+            &["third-party".to_string(), "other".to_string()],
+        );
+
+        assert_eq!(
+            Some("third-party"),
+            create_options
+                .host_config()
+                .and_then(HostConfig::network_mode)
+        );
+        assert_eq!(vec!["third-party"], endpoint_networks(&create_options));
+        let endpoint = &create_options
+            .networking_config()
+            .and_then(ContainerCreateBodyNetworkingConfig::endpoints_config)
+            .unwrap()["third-party"];
+        assert_eq!(
+            serde_json::json!({ "Aliases": ["sensor"] }),
+            serde_json::to_value(endpoint).unwrap()
+        );
+
+        let others: Vec<&str> = others.iter().map(|(network, _)| network.as_str()).collect();
+        assert_eq!(vec!["other"], others);
+    }
+
+    #[test]
+    fn module_without_networks_is_not_connected() {
+        let (create_options, others) = apply_module_networks(ContainerCreateBody::new(), &[]);
+
+        assert_eq!(
+            Some("none"),
+            create_options
+                .host_config()
+                .and_then(HostConfig::network_mode)
+        );
+        assert!(endpoint_networks(&create_options).is_empty());
+        assert!(others.is_empty());
+    }
 }
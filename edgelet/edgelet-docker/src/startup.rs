@@ -0,0 +1,304 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use std::collections::BTreeMap;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::time::{Duration, Instant};
+
+use futures::future::{self, Either, Loop};
+use futures::Future;
+use log::{debug, info, warn};
+use tokio::net::TcpStream;
+use tokio::timer::Delay;
+use tokio::util::FutureExt;
+
+use docker::models::InlineResponse200;
+use edgelet_core::{ModuleDependency, ModuleStartup, ReadinessCondition};
+use edgelet_http::UrlConnector;
+
+use crate::client::DockerClient;
+use crate::error::Error;
+
+const STARTUP_ORDER_LABEL_KEY: &str = "net.azure-devices.edge.startup-order";
+const DEPENDS_ON_LABEL_KEY: &str = "net.azure-devices.edge.depends-on";
+
+const READINESS_POLL_INTERVAL: Duration = Duration::from_secs(1);
+const READINESS_TIMEOUT: Duration = Duration::from_secs(120);
+const TCP_CONNECT_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// The labels that record a module's startup order and dependencies on its container,
+/// so that they're known when the runtime starts modules again after a reboot.
+pub(crate) fn startup_labels(
+    startup_order: Option<u32>,
+    depends_on: &[ModuleDependency],
+) -> Vec<(String, String)> {
+    let mut labels = vec![];
+
+    if let Some(startup_order) = startup_order {
+        labels.push((
+            STARTUP_ORDER_LABEL_KEY.to_string(),
+            startup_order.to_string(),
+        ));
+    }
+
+    if !depends_on.is_empty() {
+        if let Ok(depends_on) = serde_json::to_string(depends_on) {
+            labels.push((DEPENDS_ON_LABEL_KEY.to_string(), depends_on));
+        }
+    }
+
+    labels
+}
+
+/// Reads back the startup order and dependencies of a module from its container labels.
+/// Labels that can't be parsed are ignored.
+pub(crate) fn module_startup(
+    name: &str,
+    labels: Option<&BTreeMap<String, String>>,
+) -> ModuleStartup {
+    let order = labels
+        .and_then(|labels| labels.get(STARTUP_ORDER_LABEL_KEY))
+        .and_then(|order| {
+            let parsed = order.parse().ok();
+            if parsed.is_none() {
+                warn!(
+                    "Ignoring invalid startup order {:?} of module {}",
+                    order, name
+                );
+            }
+            parsed
+        });
+
+    let depends_on = labels
+        .and_then(|labels| labels.get(DEPENDS_ON_LABEL_KEY))
+        .and_then(
+            |depends_on| match serde_json::from_str::<Vec<ModuleDependency>>(depends_on) {
+                Ok(depends_on) => Some(depends_on),
+                Err(err) => {
+                    warn!("Ignoring invalid dependencies of module {}: {}", name, err);
+                    None
+                }
+            },
+        )
+        .unwrap_or_default();
+
+    ModuleStartup::new(name.to_string(), order, depends_on)
+}
+
+/// Whether the runtime orders the start of this module, rather than leaving it to edgeAgent.
+pub(crate) fn is_ordered(module: &ModuleStartup) -> bool {
+    module.order().is_some() || !module.depends_on().is_empty()
+}
+
+/// Waits until the module a dependency refers to meets its readiness condition.
+///
+/// Gives up after `READINESS_TIMEOUT`, so that a dependency that never becomes ready
+/// delays the modules that depend on it instead of keeping them stopped.
+pub(crate) fn wait_until_ready(
+    client: DockerClient<UrlConnector>,
+    dependency: ModuleDependency,
+) -> impl Future<Item = (), Error = Error> + Send {
+    let deadline = Instant::now() + READINESS_TIMEOUT;
+
+    future::loop_fn((client, dependency), move |(client, dependency)| {
+        client
+            .container_api()
+            .container_inspect(dependency.name(), false)
+            .then(move |result| {
+                let readiness = match result {
+                    Ok(container) => readiness(&container, dependency.condition()),
+                    Err(err) => {
+                        debug!("Could not inspect module {}: {:?}", dependency.name(), err);
+                        Readiness::NotReady
+                    }
+                };
+
+                let ready = match readiness {
+                    Readiness::Ready => Either::A(future::ok(true)),
+                    Readiness::NotReady => Either::A(future::ok(false)),
+                    Readiness::AcceptsConnections(addr) => Either::B(
+                        TcpStream::connect(&addr)
+                            .timeout(TCP_CONNECT_TIMEOUT)
+                            .then(|result| Ok(result.is_ok())),
+                    ),
+                };
+
+                ready.and_then(move |ready| {
+                    if ready {
+                        info!("Module {} is {}", dependency.name(), dependency.condition());
+                        Either::A(future::ok(Loop::Break(())))
+                    } else if Instant::now() >= deadline {
+                        warn!(
+                            "Timed out waiting for module {} to be {}",
+                            dependency.name(),
+                            dependency.condition()
+                        );
+                        Either::A(future::ok(Loop::Break(())))
+                    } else {
+                        Either::B(
+                            Delay::new(Instant::now() + READINESS_POLL_INTERVAL)
+                                .then(move |_| Ok(Loop::Continue((client, dependency)))),
+                        )
+                    }
+                })
+            })
+    })
+}
+
+#[derive(Debug, PartialEq)]
+enum Readiness {
+    Ready,
+    NotReady,
+    AcceptsConnections(SocketAddr),
+}
+
+fn readiness(container: &InlineResponse200, condition: ReadinessCondition) -> Readiness {
+    let state = container.state();
+    if !state
+        .and_then(|state| state.running())
+        .copied()
+        .unwrap_or(false)
+    {
+        return Readiness::NotReady;
+    }
+
+    match condition {
+        ReadinessCondition::Started => Readiness::Ready,
+
+        // Modules without a healthcheck are as healthy as they get once they're running.
+        ReadinessCondition::Healthy => {
+            match state
+                .and_then(|state| state.health())
+                .and_then(|health| health.status())
+            {
+                None | Some("none") | Some("healthy") => Readiness::Ready,
+                Some(_) => Readiness::NotReady,
+            }
+        }
+
+        // Modules that aren't attached to a network, like those that use the host's network,
+        // listen on the host's addresses.
+        ReadinessCondition::TcpPort { port } => {
+            let ip = container
+                .network_settings()
+                .and_then(|settings| settings.networks())
+                .and_then(|networks| {
+                    networks
+                        .values()
+                        .filter_map(|endpoint| endpoint.ip_address())
+                        .find_map(|ip| ip.parse::<IpAddr>().ok())
+                })
+                .unwrap_or(IpAddr::V4(Ipv4Addr::LOCALHOST));
+            Readiness::AcceptsConnections(SocketAddr::new(ip, port))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::net::SocketAddr;
+
+    use docker::models::InlineResponse200;
+    use edgelet_core::{ModuleDependency, ReadinessCondition};
+    use serde_json::json;
+
+    use super::{module_startup, readiness, startup_labels, Readiness};
+
+    fn inspect(value: serde_json::Value) -> InlineResponse200 {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn startup_labels_round_trip() {
+        let depends_on = vec![
+            ModuleDependency::new("db".to_string(), ReadinessCondition::Healthy),
+            ModuleDependency::new(
+                "broker".to_string(),
+                ReadinessCondition::TcpPort { port: 1883 },
+            ),
+        ];
+        let labels: BTreeMap<_, _> = startup_labels(Some(3), &depends_on).into_iter().collect();
+
+        let startup = module_startup("consumer", Some(&labels));
+        assert_eq!("consumer", startup.name());
+        assert_eq!(Some(3), startup.order());
+        assert_eq!(&depends_on[..], startup.depends_on());
+    }
+
+    #[test]
+    fn invalid_startup_labels_are_ignored() {
+        let labels: BTreeMap<_, _> = vec![
+            (
+                "net.azure-devices.edge.startup-order".to_string(),
+                "-1".to_string(),
+            ),
+            (
+                "net.azure-devices.edge.depends-on".to_string(),
+                "db".to_string(),
+            ),
+        ]
+        .into_iter()
+        .collect();
+
+        let startup = module_startup("consumer", Some(&labels));
+        assert_eq!(None, startup.order());
+        assert!(startup.depends_on().is_empty());
+    }
+
+    #[test]
+    fn stopped_module_is_not_ready() {
+        let container = inspect(json!({ "State": { "Running": false } }));
+        assert_eq!(
+            Readiness::NotReady,
+            readiness(&container, ReadinessCondition::Started)
+        );
+    }
+
+    #[test]
+    fn healthy_depends_on_healthcheck() {
+        let starting = inspect(json!({
+            "State": { "Running": true, "Health": { "Status": "starting" } },
+        }));
+        assert_eq!(
+            Readiness::NotReady,
+            readiness(&starting, ReadinessCondition::Healthy)
+        );
+
+        let healthy = inspect(json!({
+            "State": { "Running": true, "Health": { "Status": "healthy" } },
+        }));
+        assert_eq!(
+            Readiness::Ready,
+            readiness(&healthy, ReadinessCondition::Healthy)
+        );
+
+        let no_healthcheck = inspect(json!({ "State": { "Running": true } }));
+        assert_eq!(
+            Readiness::Ready,
+            readiness(&no_healthcheck, ReadinessCondition::Healthy)
+        );
+    }
+
+    #[test]
+    fn tcp_port_uses_container_address() {
+        let container = inspect(json!({
+            "State": { "Running": true },
+            "NetworkSettings": {
+                "Networks": { "azure-iot-edge": { "IPAddress": "172.18.0.3" } },
+            },
+        }));
+        assert_eq!(
+            Readiness::AcceptsConnections("172.18.0.3:1883".parse::<SocketAddr>().unwrap()),
+            readiness(&container, ReadinessCondition::TcpPort { port: 1883 })
+        );
+
+        let host_network = inspect(json!({
+            "State": { "Running": true },
+            "NetworkSettings": { "Networks": { "host": { "IPAddress": "" } } },
+        }));
+        assert_eq!(
+            Readiness::AcceptsConnections("127.0.0.1:1883".parse::<SocketAddr>().unwrap()),
+            readiness(&host_network, ReadinessCondition::TcpPort { port: 1883 })
+        );
+    }
+}
@@ -1,28 +1,32 @@
 // Copyright (c) Microsoft. All rights reserved.
 
 use std::fmt;
+use std::io;
 use std::str::FromStr;
 use std::sync::Arc;
 use std::time::Duration;
 
+use bytes::Bytes;
 use failure::{Fail, ResultExt};
 use futures::future::{self, FutureResult};
 use futures::prelude::*;
 use futures::stream;
+use hyper::upgrade::Upgraded;
 use hyper::{Body, Chunk as HyperChunk, Client};
 use management::apis::client::APIClient;
 use management::apis::configuration::Configuration;
-use management::models::{Config, ModuleDetails as HttpModuleDetails};
+use management::models::{Config, DeployedModule, ModuleDetails as HttpModuleDetails};
 use url::Url;
 
 use edgelet_core::{
-    LogOptions, Module, ModuleRegistry, ModuleRuntime, ModuleRuntimeState, ModuleSpec, ModuleStatus,
+    ExecOptions, ImageInfo, LogOptions, Module, ModuleRegistry, ModuleRuntime, ModuleRuntimeState,
+    ModuleSpec, ModuleStatus,
 };
 use edgelet_core::{
     ModuleOperation, RuntimeOperation, SystemInfo as CoreSystemInfo, SystemResources, UrlExt,
 };
 use edgelet_docker::{self, DockerConfig};
-use edgelet_http::{UrlConnector, API_VERSION};
+use edgelet_http::{PemCertificate, UrlConnector, API_VERSION};
 
 use crate::error::{Error, ErrorKind};
 
@@ -32,8 +36,20 @@ pub struct ModuleClient {
 
 impl ModuleClient {
     pub fn new(url: &Url) -> Result<Self, Error> {
-        let client = Client::builder()
-            .build(UrlConnector::new(url).context(ErrorKind::InitializeModuleClient)?);
+        ModuleClient::with_tls(url, None, None)
+    }
+
+    /// Connects to a remote management API at an `https` URL, authenticating with `client_cert`.
+    /// The server certificate may also be issued by `trusted_ca`.
+    pub fn with_tls(
+        url: &Url,
+        client_cert: Option<&PemCertificate>,
+        trusted_ca: Option<&[u8]>,
+    ) -> Result<Self, Error> {
+        let client = Client::builder().build(
+            UrlConnector::with_tls(url, client_cert, trusted_ca)
+                .context(ErrorKind::InitializeModuleClient)?,
+        );
 
         let base_path = url
             .to_base_path()
@@ -54,6 +70,83 @@ impl ModuleClient {
         };
         Ok(module_client)
     }
+
+    /// Converges the modules to a deployment manifest, returning what was done to each module.
+    pub fn deploy(
+        &self,
+        deployment: serde_json::Value,
+    ) -> impl Future<Item = Vec<DeployedModule>, Error = Error> + Send {
+        self.client
+            .module_api()
+            .deploy_modules(&API_VERSION.to_string(), deployment)
+            .map(|result| result.modules().to_vec())
+            .map_err(|err| Error::from_mgmt_error(err, ErrorKind::Deployment))
+    }
+
+    /// Runs a command in a module. The returned connection takes the command's stdin as raw
+    /// bytes, and yields its output and exit code as frames that `ExecCodec` decodes.
+    pub fn exec_module(
+        &self,
+        id: &str,
+        options: &ExecOptions,
+    ) -> impl Future<Item = Upgraded, Error = Error> + Send {
+        let id = id.to_string();
+
+        self.client
+            .module_api()
+            .exec_module(
+                &API_VERSION.to_string(),
+                &id,
+                options.cmd(),
+                options.tty(),
+                options.stdin(),
+            )
+            .map_err(|err| {
+                Error::from_mgmt_error(
+                    err,
+                    ErrorKind::RuntimeOperation(RuntimeOperation::ExecModule(id)),
+                )
+            })
+    }
+
+    /// Returns a tar archive of a file or directory in a module.
+    pub fn get_module_archive(
+        &self,
+        id: &str,
+        path: &str,
+    ) -> impl Future<Item = Body, Error = Error> + Send {
+        let id = id.to_string();
+
+        self.client
+            .module_api()
+            .get_module_archive(&API_VERSION.to_string(), &id, path)
+            .map_err(|err| {
+                Error::from_mgmt_error(
+                    err,
+                    ErrorKind::RuntimeOperation(RuntimeOperation::GetModuleArchive(id)),
+                )
+            })
+    }
+
+    /// Extracts a tar archive into a directory in a module.
+    pub fn put_module_archive(
+        &self,
+        id: &str,
+        path: &str,
+        archive: Body,
+    ) -> impl Future<Item = (), Error = Error> + Send {
+        let id = id.to_string();
+
+        self.client
+            .module_api()
+            .put_module_archive(&API_VERSION.to_string(), &id, path, archive)
+            .map_err(|err| {
+                Error::from_mgmt_error(
+                    err,
+                    ErrorKind::RuntimeOperation(RuntimeOperation::PutModuleArchive(id)),
+                )
+            })
+    }
 }
 
 impl Clone for ModuleClient {
@@ -70,6 +163,17 @@ pub struct ModuleDetails(HttpModuleDetails, ModuleConfig);
 #[derive(Clone, Debug)]
 pub struct ModuleConfig(String, Config);
 
+impl ModuleConfig {
+    pub fn type_(&self) -> &str {
+        &self.0
+    }
+
+    /// The module's spec as returned by the management API, with its settings and env.
+    pub fn spec(&self) -> &Config {
+        &self.1
+    }
+}
+
 impl fmt::Display for ModuleConfig {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         if let edgelet_docker::MODULE_TYPE = self.0.as_ref() {
@@ -120,13 +224,21 @@ fn runtime_status(details: &HttpModuleDetails) -> Result<ModuleRuntimeState, Err
         .exit_status()
         .and_then(|e| e.exit_time().parse().ok());
     let start_time = details.status().start_time().and_then(|s| s.parse().ok());
+    let image_id = details
+        .status()
+        .runtime_status()
+        .image_id()
+        .map(ToOwned::to_owned);
+    let pid = details.status().runtime_status().pid();
 
     let state = ModuleRuntimeState::default()
         .with_status(status)
         .with_status_description(description)
         .with_exit_code(exit_code)
         .with_started_at(start_time)
-        .with_finished_at(exit_time);
+        .with_finished_at(exit_time)
+        .with_image_id(image_id)
+        .with_pid(pid);
     Ok(state)
 }
 
@@ -134,6 +246,7 @@ impl ModuleRegistry for ModuleClient {
     type Error = Error;
     type PullFuture = FutureResult<(), Self::Error>;
     type RemoveFuture = FutureResult<(), Self::Error>;
+    type ListImagesFuture = FutureResult<Vec<ImageInfo>, Self::Error>;
     type Config = ModuleConfig;
 
     fn pull(&self, _config: &Self::Config) -> Self::PullFuture {
@@ -143,6 +256,10 @@ impl ModuleRegistry for ModuleClient {
     fn remove(&self, _name: &str) -> Self::RemoveFuture {
         future::ok(())
     }
+
+    fn list_images(&self) -> Self::ListImagesFuture {
+        future::ok(Vec::new())
+    }
 }
 
 impl ModuleRuntime for ModuleClient {
@@ -152,6 +269,7 @@ impl ModuleRuntime for ModuleClient {
     type ModuleRegistry = Self;
     type Chunk = Chunk;
     type Logs = Logs;
+    type Archive = Logs;
 
     type CreateFuture = Box<dyn Future<Item = (), Error = Self::Error> + Send>;
     type GetFuture =
@@ -168,7 +286,15 @@ impl ModuleRuntime for ModuleClient {
     type SystemResourcesFuture =
         Box<dyn Future<Item = SystemResources, Error = Self::Error> + Send>;
     type RemoveAllFuture = Box<dyn Future<Item = (), Error = Self::Error> + Send>;
+    type StartAllFuture = Box<dyn Future<Item = (), Error = Self::Error> + Send>;
     type StopAllFuture = Box<dyn Future<Item = (), Error = Self::Error> + Send>;
+    type ExecIo = Upgraded;
+    type ExecExitCodeFuture = Box<dyn Future<Item = Option<i32>, Error = Self::Error> + Send>;
+    type GetArchiveFuture = Box<dyn Future<Item = Self::Archive, Error = Self::Error> + Send>;
+    type PutArchiveFuture = Box<dyn Future<Item = (), Error = Self::Error> + Send>;
+    type ExecFuture = Box<
+        dyn Future<Item = (Self::ExecIo, Self::ExecExitCodeFuture), Error = Self::Error> + Send,
+    >;
 
     fn create(&self, _module: ModuleSpec<Self::Config>) -> Self::CreateFuture {
         unimplemented!()
@@ -314,6 +440,9 @@ impl ModuleRuntime for ModuleClient {
         let id = id.to_string();
 
         let tail = &options.tail().to_string();
+        let filter = options.filter();
+        let stream = filter.stream().map(|stream| stream.to_string());
+        let severity = filter.severity().map(|severity| severity.to_string());
         let result = self
             .client
             .module_api()
@@ -323,6 +452,12 @@ impl ModuleRuntime for ModuleClient {
                 options.follow(),
                 tail,
                 options.since(),
+                options.timestamps(),
+                &options.format().to_string(),
+                stream.as_deref(),
+                filter.contains(),
+                filter.regex(),
+                severity.as_deref(),
             )
             .then(|logs| match logs {
                 Ok(logs) => Ok(Logs(id, logs)),
@@ -348,6 +483,10 @@ impl ModuleRuntime for ModuleClient {
         }))
     }
 
+    fn start_all(&self) -> Self::StartAllFuture {
+        unimplemented!()
+    }
+
     fn stop_all(&self, _wait_before_kill: Option<Duration>) -> Self::StopAllFuture {
         let self_for_stop = self.clone();
         Box::new(self.list().and_then(move |list| {
@@ -357,6 +496,21 @@ impl ModuleRuntime for ModuleClient {
             future::join_all(n).map(|_| ())
         }))
     }
+
+    fn exec(&self, _id: &str, _options: &ExecOptions) -> Self::ExecFuture {
+        unimplemented!()
+    }
+
+    fn get_archive(&self, _id: &str, _path: &str) -> Self::GetArchiveFuture {
+        unimplemented!()
+    }
+
+    fn put_archive<S>(&self, _id: &str, _path: &str, _archive: S) -> Self::PutArchiveFuture
+    where
+        S: Stream<Item = Bytes, Error = io::Error> + Send + 'static,
+    {
+        unimplemented!()
+    }
 }
 
 pub struct Logs(String, Body);
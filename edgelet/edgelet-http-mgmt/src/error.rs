@@ -26,6 +26,9 @@ pub enum ErrorKind {
     #[fail(display = "Client error")]
     Client(MgmtError<serde_json::Value>),
 
+    #[fail(display = "Could not apply deployment")]
+    Deployment,
+
     #[fail(display = "{}", _0)]
     IdentityOperation(IdentityOperation),
 
@@ -35,6 +38,9 @@ pub enum ErrorKind {
     #[fail(display = "Invalid API version {:?}", _0)]
     InvalidApiVersion(String),
 
+    #[fail(display = "Deployment manifest is invalid: {}", _0)]
+    InvalidDeployment(String),
+
     #[fail(display = "Invalid Identity type")]
     InvalidIdentityType,
 
@@ -135,12 +141,14 @@ impl IntoResponse for Error {
                 match cause {
                     DockerErrorKind::NotFound(_) => StatusCode::NOT_FOUND,
                     DockerErrorKind::Conflict => StatusCode::CONFLICT,
+                    DockerErrorKind::CopySizeExceeded(_) => StatusCode::PAYLOAD_TOO_LARGE,
                     DockerErrorKind::NotModified => StatusCode::NOT_MODIFIED,
                     _ => StatusCode::INTERNAL_SERVER_ERROR,
                 }
             } else {
                 match self.kind() {
                     ErrorKind::InvalidApiVersion(_)
+                    | ErrorKind::InvalidDeployment(_)
                     | ErrorKind::InvalidIdentityType
                     | ErrorKind::MalformedRequestBody
                     | ErrorKind::MalformedRequestParameter(_)
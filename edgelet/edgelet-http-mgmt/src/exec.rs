@@ -0,0 +1,161 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use std::io;
+
+use bytes::{BufMut, Bytes, BytesMut};
+use tokio::codec::{Decoder, Encoder};
+
+const HEADER_LEN: usize = 8;
+
+const STDIN: u8 = 0;
+const STDOUT: u8 = 1;
+const STDERR: u8 = 2;
+const EXIT: u8 = 3;
+
+/// The output of a command run in a module, as sent over an upgraded exec connection.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ExecFrame {
+    Stdout(Bytes),
+    Stderr(Bytes),
+    /// The last frame. The exit code is `None` if the runtime couldn't report it.
+    Exit(Option<i32>),
+}
+
+/// Frames the output of a command the way the Docker engine multiplexes stdout and stderr:
+/// a header with the stream type in the first byte and the big-endian payload length in
+/// the last four, followed by the payload. Exit frames have a type of 3 and carry the exit
+/// code as a big-endian `i32`, or no payload if it's unknown.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ExecCodec;
+
+impl Decoder for ExecCodec {
+    type Item = ExecFrame;
+    type Error = io::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        if src.len() < HEADER_LEN {
+            return Ok(None);
+        }
+
+        let stream = src[0];
+        let len = be_bytes(&src[4..HEADER_LEN]) as usize;
+        if src.len() < HEADER_LEN + len {
+            src.reserve(HEADER_LEN + len - src.len());
+            return Ok(None);
+        }
+
+        src.advance(HEADER_LEN);
+        let payload = src.split_to(len).freeze();
+
+        match stream {
+            // The Docker engine only writes stdin frames for TTYs, which aren't framed,
+            // so treat them as output like it does.
+            STDIN | STDOUT => Ok(Some(ExecFrame::Stdout(payload))),
+            STDERR => Ok(Some(ExecFrame::Stderr(payload))),
+            EXIT => match payload.len() {
+                0 => Ok(Some(ExecFrame::Exit(None))),
+                #[allow(clippy::cast_possible_wrap)]
+                4 => Ok(Some(ExecFrame::Exit(Some(be_bytes(&payload) as i32)))),
+                len => Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("exit frame has a payload of {} bytes", len),
+                )),
+            },
+            stream => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unknown stream type {}", stream),
+            )),
+        }
+    }
+}
+
+impl Encoder for ExecCodec {
+    type Item = ExecFrame;
+    type Error = io::Error;
+
+    fn encode(&mut self, item: Self::Item, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        let (stream, payload) = match item {
+            ExecFrame::Stdout(payload) => (STDOUT, payload),
+            ExecFrame::Stderr(payload) => (STDERR, payload),
+            ExecFrame::Exit(Some(code)) => (EXIT, Bytes::from(&code.to_be_bytes()[..])),
+            ExecFrame::Exit(None) => (EXIT, Bytes::new()),
+        };
+
+        #[allow(clippy::cast_possible_truncation)]
+        let len = payload.len() as u32;
+        if payload.len() != len as usize {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "frame payload is too large",
+            ));
+        }
+
+        dst.reserve(HEADER_LEN + payload.len());
+        dst.put_u8(stream);
+        dst.put_slice(&[0, 0, 0]);
+        dst.put_u32_be(len);
+        dst.put_slice(&payload);
+        Ok(())
+    }
+}
+
+fn be_bytes(bytes: &[u8]) -> u32 {
+    let mut buf = [0; 4];
+    buf.copy_from_slice(bytes);
+    u32::from_be_bytes(buf)
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::{Bytes, BytesMut};
+    use tokio::codec::{Decoder, Encoder};
+
+    use super::{ExecCodec, ExecFrame};
+
+    #[test]
+    fn frames_round_trip() {
+        let frames = vec![
+            ExecFrame::Stdout(Bytes::from("out\n")),
+            ExecFrame::Stderr(Bytes::from("err\n")),
+            ExecFrame::Exit(Some(-1)),
+            ExecFrame::Exit(None),
+        ];
+
+        let mut buf = BytesMut::new();
+        for frame in frames.clone() {
+            ExecCodec.encode(frame, &mut buf).unwrap();
+        }
+
+        let mut decoded = vec![];
+        while let Some(frame) = ExecCodec.decode(&mut buf).unwrap() {
+            decoded.push(frame);
+        }
+        assert_eq!(frames, decoded);
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn decodes_docker_streams() {
+        let mut buf =
+            BytesMut::from(&b"\x01\x00\x00\x00\x00\x00\x00\x02hi\x02\x00\x00\x00\x00\x00"[..]);
+
+        assert_eq!(
+            Some(ExecFrame::Stdout(Bytes::from("hi"))),
+            ExecCodec.decode(&mut buf).unwrap()
+        );
+        // The second frame is incomplete.
+        assert_eq!(None, ExecCodec.decode(&mut buf).unwrap());
+
+        buf.extend_from_slice(b"\x00\x03no\n");
+        assert_eq!(
+            Some(ExecFrame::Stderr(Bytes::from("no\n"))),
+            ExecCodec.decode(&mut buf).unwrap()
+        );
+    }
+
+    #[test]
+    fn unknown_stream_fails() {
+        let mut buf = BytesMut::from(&b"\x07\x00\x00\x00\x00\x00\x00\x00"[..]);
+        assert!(ExecCodec.decode(&mut buf).is_err());
+    }
+}
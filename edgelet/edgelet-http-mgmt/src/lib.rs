@@ -14,10 +14,12 @@ use hyper::{Body, Response};
 
 mod client;
 mod error;
+mod exec;
 mod server;
 
 pub use client::ModuleClient;
 pub use error::{Error, ErrorKind};
+pub use exec::{ExecCodec, ExecFrame};
 pub use server::ListModules;
 pub use server::ManagementService;
 
@@ -0,0 +1,427 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use failure::ResultExt;
+use futures::future::{self, Either};
+use futures::{stream, Future, Stream};
+use hyper::header::{CONTENT_LENGTH, CONTENT_TYPE};
+use hyper::{Body, Request, Response, StatusCode};
+use log::info;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use edgelet_core::{
+    ImagePullPolicy, Module, ModuleRegistry, ModuleRuntime, ModuleRuntimeState,
+    ModuleSpec as CoreModuleSpec, ModuleStatus, RuntimeOperation,
+};
+use edgelet_http::route::{Handler, Parameters};
+use edgelet_http::Error as HttpError;
+use management::models::{DeployedModule, DeploymentResult};
+
+use super::{parse_deployment, plan, CurrentModule, Step};
+use crate::error::{Error, ErrorKind};
+use crate::server::module::spec_to_core;
+use crate::IntoResponse;
+
+type ModuleConfig<M> = <<M as ModuleRuntime>::Module as Module>::Config;
+
+pub struct DeployModules<M> {
+    runtime: M,
+}
+
+impl<M> DeployModules<M> {
+    pub fn new(runtime: M) -> Self {
+        DeployModules { runtime }
+    }
+}
+
+impl<M> Handler<Parameters> for DeployModules<M>
+where
+    M: 'static + ModuleRuntime + Clone + Send + Sync,
+    <M::Module as Module>::Config: DeserializeOwned + Serialize,
+{
+    fn handle(
+        &self,
+        req: Request<Body>,
+        _params: Parameters,
+    ) -> Box<dyn Future<Item = Response<Body>, Error = HttpError> + Send> {
+        let runtime = self.runtime.clone();
+        let list_runtime = self.runtime.clone();
+
+        let response = req
+            .into_body()
+            .concat2()
+            .then(|body| -> Result<_, Error> {
+                let body = body.context(ErrorKind::MalformedRequestBody)?;
+                let manifest: serde_json::Value =
+                    serde_json::from_slice(&body).context(ErrorKind::MalformedRequestBody)?;
+
+                // Convert every module before touching the runtime, so that an invalid
+                // deployment leaves the device as it is.
+                let mut desired = vec![];
+                for module in parse_deployment(&manifest)? {
+                    let context =
+                        ErrorKind::InvalidDeployment(format!("module {}", module.spec().name()));
+                    let spec = spec_to_core::<M>(module.spec(), context)?;
+                    desired.push((module, spec));
+                }
+                Ok(desired)
+            })
+            .and_then(move |desired| {
+                list_runtime.list_with_details().collect().then(|current| {
+                    let current = current
+                        .context(ErrorKind::RuntimeOperation(RuntimeOperation::ListModules))?;
+                    let current: Vec<CurrentModule> = current
+                        .into_iter()
+                        .map(|(module, state)| current_module(&module, &state))
+                        .collect();
+                    Ok(plan(desired, &current))
+                })
+            })
+            .and_then(move |steps| {
+                stream::iter_ok(steps)
+                    .and_then(move |step| apply(&runtime, step))
+                    .collect()
+            })
+            .and_then(|modules| -> Result<_, Error> {
+                let b = serde_json::to_string(&DeploymentResult::new(modules))
+                    .context(ErrorKind::Deployment)?;
+                let response = Response::builder()
+                    .status(StatusCode::OK)
+                    .header(CONTENT_TYPE, "application/json")
+                    .header(CONTENT_LENGTH, b.len().to_string().as_str())
+                    .body(b.into())
+                    .context(ErrorKind::Deployment)?;
+                Ok(response)
+            })
+            .or_else(|e| Ok(e.into_response()));
+
+        Box::new(response)
+    }
+}
+
+fn current_module<T>(module: &T, state: &ModuleRuntimeState) -> CurrentModule
+where
+    T: Module,
+    T::Config: Serialize,
+{
+    let config = serde_json::to_value(module.config()).unwrap_or_default();
+    CurrentModule::new(
+        module.name().to_string(),
+        &config,
+        *state.status() == ModuleStatus::Running,
+    )
+}
+
+fn apply<M>(
+    runtime: &M,
+    step: Step<CoreModuleSpec<ModuleConfig<M>>>,
+) -> Box<dyn Future<Item = DeployedModule, Error = Error> + Send>
+where
+    M: 'static + ModuleRuntime + Clone + Send + Sync,
+{
+    let action = step.action().to_string();
+    let deployed = move |name: String| {
+        info!("Local deployment {} module {}", action, name);
+        DeployedModule::new(name, action)
+    };
+
+    match step {
+        Step::Remove(name) => Box::new(remove(runtime, name).map(deployed)),
+        Step::Create { spec, start } => Box::new(create(runtime, spec, start).map(deployed)),
+        Step::Update { spec, start } => {
+            // Pull the new image while the old module keeps running, so that a failed pull
+            // doesn't leave the module removed.
+            let runtime = runtime.clone();
+            let name = spec.name().to_string();
+            let remove_runtime = runtime.clone();
+            Box::new(
+                pull(&runtime, &spec)
+                    .and_then(move |_| remove(&remove_runtime, name))
+                    .and_then(move |_| create_pulled(&runtime, spec, start))
+                    .map(deployed),
+            )
+        }
+        Step::Start(name) => Box::new(
+            runtime
+                .start(&name)
+                .then(move |result| {
+                    result.with_context(|_| {
+                        ErrorKind::RuntimeOperation(RuntimeOperation::StartModule(name.clone()))
+                    })?;
+                    Ok(name)
+                })
+                .map(deployed),
+        ),
+        Step::Stop(name) => Box::new(
+            runtime
+                .stop(&name, None)
+                .then(move |result| {
+                    result.with_context(|_| {
+                        ErrorKind::RuntimeOperation(RuntimeOperation::StopModule(name.clone()))
+                    })?;
+                    Ok(name)
+                })
+                .map(deployed),
+        ),
+        Step::Unchanged(name) => Box::new(future::ok(deployed(name))),
+    }
+}
+
+fn remove<M>(runtime: &M, name: String) -> impl Future<Item = String, Error = Error> + Send
+where
+    M: ModuleRuntime,
+{
+    runtime.remove(&name).then(move |result| {
+        result.with_context(|_| {
+            ErrorKind::RuntimeOperation(RuntimeOperation::RemoveModule(name.clone()))
+        })?;
+        Ok(name)
+    })
+}
+
+fn pull<M>(
+    runtime: &M,
+    spec: &CoreModuleSpec<ModuleConfig<M>>,
+) -> impl Future<Item = (), Error = Error> + Send
+where
+    M: 'static + ModuleRuntime + Clone + Send + Sync,
+{
+    let name = spec.name().to_string();
+    let pull = match spec.image_pull_policy() {
+        ImagePullPolicy::OnCreate => Either::A(runtime.registry().pull(spec.config())),
+        ImagePullPolicy::Never => Either::B(future::ok(())),
+    };
+    pull.then(move |result| {
+        result
+            .with_context(|_| ErrorKind::RuntimeOperation(RuntimeOperation::CreateModule(name)))?;
+        Ok(())
+    })
+}
+
+fn create<M>(
+    runtime: &M,
+    spec: CoreModuleSpec<ModuleConfig<M>>,
+    start: bool,
+) -> Box<dyn Future<Item = String, Error = Error> + Send>
+where
+    M: 'static + ModuleRuntime + Clone + Send + Sync,
+{
+    let runtime = runtime.clone();
+    Box::new(pull(&runtime, &spec).and_then(move |_| create_pulled(&runtime, spec, start)))
+}
+
+/// Creates a module whose image has already been pulled.
+fn create_pulled<M>(
+    runtime: &M,
+    spec: CoreModuleSpec<ModuleConfig<M>>,
+    start: bool,
+) -> Box<dyn Future<Item = String, Error = Error> + Send>
+where
+    M: 'static + ModuleRuntime + Clone + Send + Sync,
+{
+    let runtime = runtime.clone();
+    let name = spec.name().to_string();
+
+    let create_name = name.clone();
+    Box::new(
+        runtime
+            .create(spec)
+            .map(|_| runtime)
+            .then(move |result| -> Result<_, Error> {
+                let runtime = result.with_context(|_| {
+                    ErrorKind::RuntimeOperation(RuntimeOperation::CreateModule(create_name))
+                })?;
+                Ok(runtime)
+            })
+            .and_then(move |runtime| {
+                if start {
+                    Either::A(runtime.start(&name).then(move |result| {
+                        result.with_context(|_| {
+                            ErrorKind::RuntimeOperation(RuntimeOperation::StartModule(name.clone()))
+                        })?;
+                        Ok(name)
+                    }))
+                } else {
+                    Either::B(future::ok(name))
+                }
+            }),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::prelude::*;
+    use serde_json::json;
+
+    use edgelet_core::MakeModuleRuntime;
+    use edgelet_test_utils::module::{
+        TestConfig, TestModule, TestRegistry, TestRuntime, TestSettings,
+    };
+    use management::models::ErrorResponse;
+
+    use super::{
+        DeployModules, DeploymentResult, Future, Handler, ModuleRuntimeState, ModuleStatus,
+        Parameters, Request, StatusCode, Stream,
+    };
+    use crate::server::module::tests::Error;
+
+    fn runtime() -> TestRuntime<Error, TestSettings> {
+        let state = ModuleRuntimeState::default()
+            .with_status(ModuleStatus::Running)
+            .with_started_at(Some(Utc.ymd(2018, 4, 13).and_hms_milli(14, 20, 0, 1)));
+        let config = TestConfig::new("microsoft/test-image".to_string());
+        let module = TestModule::new("test-module".to_string(), config, Ok(state));
+        TestRuntime::make_runtime(TestSettings::new())
+            .wait()
+            .unwrap()
+            .with_module(Ok(module))
+    }
+
+    #[test]
+    fn success() {
+        let handler = DeployModules::new(runtime());
+        let manifest = json!({
+            "modules": {
+                "test-module": {
+                    "type": "docker",
+                    "settings": { "image": "microsoft/test-image" }
+                },
+                "other-module": {
+                    "type": "docker",
+                    "status": "stopped",
+                    "settings": { "image": "microsoft/other-image" }
+                }
+            }
+        });
+        let request = Request::post("http://localhost/deployment")
+            .body(manifest.to_string().into())
+            .unwrap();
+
+        // act
+        let response = handler.handle(request, Parameters::new()).wait().unwrap();
+
+        // assert
+        assert_eq!(StatusCode::OK, response.status());
+        response
+            .into_body()
+            .concat2()
+            .and_then(|b| {
+                let result: DeploymentResult = serde_json::from_slice(&b).unwrap();
+                let actions: Vec<(&str, &str)> = result
+                    .modules()
+                    .iter()
+                    .map(|module| (module.name().as_str(), module.action().as_str()))
+                    .collect();
+                assert_eq!(
+                    vec![("other-module", "created"), ("test-module", "updated")],
+                    actions
+                );
+                Ok(())
+            })
+            .wait()
+            .unwrap();
+    }
+
+    #[test]
+    fn update_pull_error() {
+        let handler =
+            DeployModules::new(runtime().with_registry(TestRegistry::new(Some(Error::General))));
+        let manifest = json!({
+            "modules": {
+                "test-module": {
+                    "type": "docker",
+                    "settings": { "image": "microsoft/test-image:2.0" }
+                }
+            }
+        });
+        let request = Request::post("http://localhost/deployment")
+            .body(manifest.to_string().into())
+            .unwrap();
+
+        // act
+        let response = handler.handle(request, Parameters::new()).wait().unwrap();
+
+        // assert
+        assert_eq!(StatusCode::INTERNAL_SERVER_ERROR, response.status());
+        response
+            .into_body()
+            .concat2()
+            .and_then(|b| {
+                let error: ErrorResponse = serde_json::from_slice(&b).unwrap();
+                assert_eq!(
+                    "Could not create module test-module\n\tcaused by: General error",
+                    error.message()
+                );
+                Ok(())
+            })
+            .wait()
+            .unwrap();
+    }
+
+    #[test]
+    fn invalid_deployment() {
+        let handler = DeployModules::new(runtime());
+        let manifest = json!({
+            "modules": {
+                "test-module": {
+                    "type": "docker",
+                    "imagePullPolicy": "what",
+                    "settings": { "image": "microsoft/test-image" }
+                }
+            }
+        });
+        let request = Request::post("http://localhost/deployment")
+            .body(manifest.to_string().into())
+            .unwrap();
+
+        // act
+        let response = handler.handle(request, Parameters::new()).wait().unwrap();
+
+        // assert
+        assert_eq!(StatusCode::BAD_REQUEST, response.status());
+        response
+            .into_body()
+            .concat2()
+            .and_then(|b| {
+                let error: ErrorResponse = serde_json::from_slice(&b).unwrap();
+                assert_eq!(
+                    "Deployment manifest is invalid: module test-module\n\tcaused by: Invalid image pull policy configuration \"what\"",
+                    error.message()
+                );
+                Ok(())
+            })
+            .wait()
+            .unwrap();
+    }
+
+    #[test]
+    fn runtime_error() {
+        let runtime = TestRuntime::make_runtime(TestSettings::new())
+            .wait()
+            .unwrap()
+            .with_module(Err(Error::General));
+        let handler = DeployModules::new(runtime);
+        let request = Request::post("http://localhost/deployment")
+            .body(json!({ "modules": {} }).to_string().into())
+            .unwrap();
+
+        // act
+        let response = handler.handle(request, Parameters::new()).wait().unwrap();
+
+        // assert
+        assert_eq!(StatusCode::INTERNAL_SERVER_ERROR, response.status());
+        response
+            .into_body()
+            .concat2()
+            .and_then(|b| {
+                let error: ErrorResponse = serde_json::from_slice(&b).unwrap();
+                assert_eq!(
+                    "Could not list modules\n\tcaused by: General error",
+                    error.message()
+                );
+                Ok(())
+            })
+            .wait()
+            .unwrap();
+    }
+}
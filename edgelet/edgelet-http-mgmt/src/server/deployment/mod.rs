@@ -0,0 +1,663 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+//! Local deployments: the daemon converges its modules to an edgeAgent-style deployment
+//! manifest itself, for devices that are set up without a connection to IoT Hub.
+//!
+//! Each module that is created from a manifest is labelled with a hash of its desired
+//! configuration, so that later deployments only re-create the modules that changed.
+
+use std::collections::BTreeMap;
+
+use openssl::sha::sha256;
+use serde_derive::Deserialize;
+use serde_json::{json, Map, Value};
+
+use edgelet_docker::split_registry;
+use management::models::{Config, EnvVar, ModuleDependency, ModuleSpec};
+
+use super::AGENT_NAME;
+use crate::error::{Error, ErrorKind};
+
+mod deploy;
+
+pub use self::deploy::DeployModules;
+
+const DEPLOYMENT_HASH_LABEL: &str = "net.azure-devices.edge.deployment";
+const MODULE_TYPE: &str = "docker";
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AgentDesiredProperties {
+    #[serde(default)]
+    runtime: Option<RuntimeProperties>,
+    #[serde(default)]
+    system_modules: BTreeMap<String, ModuleProperties>,
+    #[serde(default)]
+    modules: BTreeMap<String, ModuleProperties>,
+}
+
+#[derive(Deserialize)]
+struct RuntimeProperties {
+    #[serde(default)]
+    settings: RuntimeSettings,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RuntimeSettings {
+    #[serde(default)]
+    registry_credentials: BTreeMap<String, RegistryCredential>,
+}
+
+#[derive(Deserialize)]
+struct RegistryCredential {
+    address: String,
+    username: String,
+    password: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ModuleProperties {
+    #[serde(rename = "type", default = "default_module_type")]
+    type_: String,
+    #[serde(default)]
+    status: Option<String>,
+    #[serde(default)]
+    restart_policy: Option<String>,
+    #[serde(default)]
+    startup_order: Option<u32>,
+    #[serde(default)]
+    depends_on: Vec<ModuleDependency>,
+    #[serde(default)]
+    image_pull_policy: Option<String>,
+    #[serde(default)]
+    env: BTreeMap<String, EnvValue>,
+    #[serde(default)]
+    settings: Map<String, Value>,
+}
+
+#[derive(Deserialize)]
+struct EnvValue {
+    value: Value,
+}
+
+fn default_module_type() -> String {
+    MODULE_TYPE.to_string()
+}
+
+/// A module of a deployment manifest, converted to a module spec of the management API.
+#[derive(Debug)]
+pub(crate) struct DesiredModule {
+    spec: ModuleSpec,
+    hash: String,
+    running: bool,
+    startup_order: u32,
+}
+
+impl DesiredModule {
+    pub(crate) fn spec(&self) -> &ModuleSpec {
+        &self.spec
+    }
+}
+
+/// A module that currently exists in the runtime.
+#[derive(Debug)]
+pub(crate) struct CurrentModule {
+    name: String,
+    hash: Option<String>,
+    running: bool,
+}
+
+impl CurrentModule {
+    pub(crate) fn new(name: String, config: &Value, running: bool) -> Self {
+        let hash = config
+            .pointer(&format!("/createOptions/Labels/{}", DEPLOYMENT_HASH_LABEL))
+            .and_then(Value::as_str)
+            .map(ToOwned::to_owned);
+        CurrentModule {
+            name,
+            hash,
+            running,
+        }
+    }
+}
+
+/// A step towards the deployment, carrying the runtime's spec for modules that are
+/// (re-)created.
+#[derive(Debug, PartialEq)]
+pub(crate) enum Step<T> {
+    Remove(String),
+    Create { spec: T, start: bool },
+    Update { spec: T, start: bool },
+    Start(String),
+    Stop(String),
+    Unchanged(String),
+}
+
+impl<T> Step<T> {
+    pub(crate) fn action(&self) -> &'static str {
+        match self {
+            Step::Remove(_) => "removed",
+            Step::Create { .. } => "created",
+            Step::Update { .. } => "updated",
+            Step::Start(_) => "started",
+            Step::Stop(_) => "stopped",
+            Step::Unchanged(_) => "unchanged",
+        }
+    }
+}
+
+/// Reads the modules of a deployment manifest, in the order they should be started in.
+///
+/// The manifest is either a full deployment with `modulesContent`, or just the desired
+/// properties of the edgeAgent twin. edgeAgent itself is skipped, since the daemon
+/// manages it from its own configuration.
+pub(crate) fn parse_deployment(manifest: &Value) -> Result<Vec<DesiredModule>, Error> {
+    let desired = manifest
+        .pointer("/modulesContent/$edgeAgent/properties.desired")
+        .unwrap_or(manifest);
+    let desired: AgentDesiredProperties =
+        serde_json::from_value(desired.clone()).map_err(|err| invalid(err.to_string()))?;
+
+    let credentials = desired
+        .runtime
+        .map(|runtime| runtime.settings.registry_credentials)
+        .unwrap_or_default();
+
+    let mut modules = vec![];
+    for (name, module) in desired.system_modules.into_iter().chain(desired.modules) {
+        if name == *AGENT_NAME {
+            continue;
+        }
+        modules.push(desired_module(name, module, &credentials)?);
+    }
+
+    modules.sort_by(|module1, module2| {
+        (module1.startup_order, module1.spec.name())
+            .cmp(&(module2.startup_order, module2.spec.name()))
+    });
+    Ok(modules)
+}
+
+fn desired_module(
+    name: String,
+    module: ModuleProperties,
+    credentials: &BTreeMap<String, RegistryCredential>,
+) -> Result<DesiredModule, Error> {
+    if module.type_ != MODULE_TYPE {
+        return Err(invalid(format!(
+            "module {} has unsupported type {:?}",
+            name, module.type_
+        )));
+    }
+
+    let running = match module.status.as_deref() {
+        None | Some("running") => true,
+        Some("stopped") => false,
+        Some(status) => {
+            return Err(invalid(format!(
+                "module {} has invalid status {:?}",
+                name, status
+            )))
+        }
+    };
+
+    let restart_policy = match module.restart_policy.as_deref() {
+        None | Some("always") | Some("on-unhealthy") => "always",
+        Some("on-failure") => "on-failure",
+        Some("never") => "no",
+        Some(policy) => {
+            return Err(invalid(format!(
+                "module {} has invalid restart policy {:?}",
+                name, policy
+            )))
+        }
+    };
+
+    let image = module
+        .settings
+        .get("image")
+        .and_then(Value::as_str)
+        .ok_or_else(|| invalid(format!("module {} has no image", name)))?
+        .to_string();
+
+    let mut create_options = create_options(&name, &module.settings)?;
+    let host_config = create_options
+        .entry("HostConfig")
+        .or_insert_with(|| json!({}))
+        .as_object_mut()
+        .ok_or_else(|| invalid(format!("module {} has invalid HostConfig", name)))?;
+    host_config
+        .entry("RestartPolicy")
+        .or_insert_with(|| json!({ "Name": restart_policy }));
+
+    let env: BTreeMap<String, String> = module
+        .env
+        .into_iter()
+        .map(|(key, env)| {
+            let value = match env.value {
+                Value::String(value) => value,
+                value => value.to_string(),
+            };
+            (key, value)
+        })
+        .collect();
+
+    // The startup order and dependencies are kept on the module's container, to start it
+    // in order after a reboot. Modules that are meant to stay stopped don't get them.
+    let (startup_order, depends_on) = if running {
+        (module.startup_order, module.depends_on)
+    } else {
+        (None, Vec::new())
+    };
+
+    // Registry credentials and labels are left out, since changing them doesn't need the
+    // module to be re-created.
+    let mut desired = json!({
+        "type": module.type_,
+        "image": image,
+        "createOptions": create_options,
+        "env": env,
+        "imagePullPolicy": module.image_pull_policy,
+    });
+    if let Some(startup_order) = startup_order {
+        desired["startupOrder"] = json!(startup_order);
+    }
+    if !depends_on.is_empty() {
+        desired["dependsOn"] = json!(depends_on);
+    }
+    let hash = hex(&sha256(desired.to_string().as_bytes()));
+
+    let labels = create_options
+        .entry("Labels")
+        .or_insert_with(|| json!({}))
+        .as_object_mut()
+        .ok_or_else(|| invalid(format!("module {} has invalid Labels", name)))?;
+    labels.insert(DEPLOYMENT_HASH_LABEL.to_string(), json!(hash));
+
+    let mut settings = json!({
+        "image": image,
+        "createOptions": create_options,
+    });
+    if let Some(credential) = registry_credential(&image, credentials) {
+        settings["auth"] = json!({
+            "username": credential.username,
+            "password": credential.password,
+            "serveraddress": credential.address,
+        });
+    }
+
+    let env = env
+        .into_iter()
+        .map(|(key, value)| EnvVar::new(key, value))
+        .collect();
+    let mut spec = ModuleSpec::new(
+        name,
+        MODULE_TYPE.to_string(),
+        Config::new(settings).with_env(env),
+    );
+    if let Some(image_pull_policy) = module.image_pull_policy {
+        spec.set_image_pull_policy(image_pull_policy);
+    }
+    if let Some(startup_order) = startup_order {
+        spec.set_startup_order(startup_order.into());
+    }
+    if !depends_on.is_empty() {
+        spec.set_depends_on(depends_on);
+    }
+
+    Ok(DesiredModule {
+        spec,
+        hash,
+        running,
+        startup_order: module.startup_order.unwrap_or(u32::MAX),
+    })
+}
+
+/// Reads the create options of a module, which edgeAgent manifests carry as a JSON string
+/// that may be split across `createOptions01`, `createOptions02`, ... when it's too long
+/// for a single twin property.
+fn create_options(name: &str, settings: &Map<String, Value>) -> Result<Map<String, Value>, Error> {
+    let create_options = match settings.get("createOptions") {
+        None => return Ok(Map::new()),
+        Some(Value::Object(create_options)) => return Ok(create_options.clone()),
+        Some(Value::String(create_options)) => {
+            let mut create_options = create_options.clone();
+            for index in 1..100 {
+                match settings
+                    .get(&format!("createOptions{:02}", index))
+                    .and_then(Value::as_str)
+                {
+                    Some(part) => create_options.push_str(part),
+                    None => break,
+                }
+            }
+            create_options
+        }
+        Some(_) => {
+            return Err(invalid(format!(
+                "module {} has invalid createOptions",
+                name
+            )))
+        }
+    };
+
+    serde_json::from_str(&create_options).map_err(|err| {
+        invalid(format!(
+            "module {} has invalid createOptions: {}",
+            name, err
+        ))
+    })
+}
+
+fn registry_credential<'a>(
+    image: &str,
+    credentials: &'a BTreeMap<String, RegistryCredential>,
+) -> Option<&'a RegistryCredential> {
+    let registry = split_registry(image).0.unwrap_or("docker.io");
+
+    credentials.values().find(|credential| {
+        credential
+            .address
+            .trim_start_matches("https://")
+            .trim_start_matches("http://")
+            .trim_end_matches('/')
+            == registry
+    })
+}
+
+/// Plans the steps that converge the current modules to the desired ones: modules that
+/// aren't in the deployment are removed first, then the deployed modules are created or
+/// updated in startup order.
+pub(crate) fn plan<T>(desired: Vec<(DesiredModule, T)>, current: &[CurrentModule]) -> Vec<Step<T>> {
+    let mut removed: Vec<&String> = current
+        .iter()
+        .map(|current| &current.name)
+        .filter(|name| {
+            **name != *AGENT_NAME
+                && desired
+                    .iter()
+                    .all(|(desired, _)| desired.spec.name() != *name)
+        })
+        .collect();
+    removed.sort();
+    let mut steps: Vec<Step<T>> = removed
+        .into_iter()
+        .map(|name| Step::Remove(name.clone()))
+        .collect();
+
+    for (desired, spec) in desired {
+        let name = desired.spec.name().clone();
+        let step = match current.iter().find(|current| current.name == name) {
+            None => Step::Create {
+                spec,
+                start: desired.running,
+            },
+            Some(current) if current.hash.as_ref() != Some(&desired.hash) => Step::Update {
+                spec,
+                start: desired.running,
+            },
+            Some(current) if desired.running && !current.running => Step::Start(name),
+            Some(current) if !desired.running && current.running => Step::Stop(name),
+            Some(_) => Step::Unchanged(name),
+        };
+        steps.push(step);
+    }
+
+    steps
+}
+
+fn invalid(message: String) -> Error {
+    Error::from(ErrorKind::InvalidDeployment(message))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{parse_deployment, plan, CurrentModule, Step, DEPLOYMENT_HASH_LABEL};
+
+    fn manifest() -> serde_json::Value {
+        json!({
+            "modulesContent": {
+                "$edgeAgent": {
+                    "properties.desired": {
+                        "schemaVersion": "1.1",
+                        "runtime": {
+                            "type": "docker",
+                            "settings": {
+                                "registryCredentials": {
+                                    "contoso": {
+                                        "address": "contoso.azurecr.io",
+                                        "username": "contoso",
+                                        "password": "secret"
+                                    }
+                                }
+                            }
+                        },
+                        "systemModules": {
+                            "edgeAgent": {
+                                "type": "docker",
+                                "settings": { "image": "mcr.microsoft.com/azureiotedge-agent:1.2" }
+                            },
+                            "edgeHub": {
+                                "type": "docker",
+                                "status": "running",
+                                "restartPolicy": "always",
+                                "startupOrder": 0,
+                                "settings": {
+                                    "image": "mcr.microsoft.com/azureiotedge-hub:1.2",
+                                    "createOptions": "{\"HostConfig\":{\"PortBindings\":"
+                                    ,"createOptions01": "{\"443/tcp\":[{\"HostPort\":\"443\"}]}}}"
+                                }
+                            }
+                        },
+                        "modules": {
+                            "sensor": {
+                                "type": "docker",
+                                "status": "stopped",
+                                "restartPolicy": "never",
+                                "startupOrder": 1,
+                                "env": {
+                                    "INTERVAL": { "value": 5 },
+                                    "UNIT": { "value": "celsius" }
+                                },
+                                "settings": {
+                                    "image": "contoso.azurecr.io/sensor:1.0",
+                                    "createOptions": {
+                                        "HostConfig": { "RestartPolicy": { "Name": "unless-stopped" } }
+                                    }
+                                }
+                            },
+                            "filter": {
+                                "type": "docker",
+                                "imagePullPolicy": "never",
+                                "dependsOn": [
+                                    { "name": "edgeHub", "condition": "tcpPort", "port": 443 }
+                                ],
+                                "settings": { "image": "contoso/filter" }
+                            }
+                        }
+                    }
+                }
+            }
+        })
+    }
+
+    #[test]
+    fn parse_deployment_reads_modules_in_startup_order() {
+        let modules = parse_deployment(&manifest()).unwrap();
+        let names: Vec<&str> = modules
+            .iter()
+            .map(|module| module.spec().name().as_str())
+            .collect();
+        assert_eq!(vec!["edgeHub", "sensor", "filter"], names);
+
+        let hub = &modules[0];
+        assert!(hub.running);
+        assert_eq!(
+            json!({
+                "PortBindings": { "443/tcp": [{ "HostPort": "443" }] },
+                "RestartPolicy": { "Name": "always" }
+            }),
+            hub.spec().config().settings()["createOptions"]["HostConfig"]
+        );
+        assert_eq!(
+            json!(hub.hash),
+            hub.spec().config().settings()["createOptions"]["Labels"][DEPLOYMENT_HASH_LABEL]
+        );
+        assert!(hub.spec().config().settings().get("auth").is_none());
+
+        let sensor = &modules[1];
+        assert!(!sensor.running);
+        assert_eq!(
+            json!({ "Name": "unless-stopped" }),
+            sensor.spec().config().settings()["createOptions"]["HostConfig"]["RestartPolicy"]
+        );
+        assert_eq!(
+            json!({
+                "username": "contoso",
+                "password": "secret",
+                "serveraddress": "contoso.azurecr.io"
+            }),
+            sensor.spec().config().settings()["auth"]
+        );
+        let env: Vec<(&str, &str)> = sensor
+            .spec()
+            .config()
+            .env()
+            .unwrap()
+            .iter()
+            .map(|var| (var.key().as_str(), var.value().as_str()))
+            .collect();
+        assert_eq!(vec![("INTERVAL", "5"), ("UNIT", "celsius")], env);
+
+        let filter = &modules[2];
+        assert_eq!(Some("never"), filter.spec().image_pull_policy());
+        let depends_on = filter.spec().depends_on().unwrap();
+        assert_eq!("edgeHub", depends_on[0].name());
+        assert_eq!("tcpPort", depends_on[0].condition());
+        assert_eq!(Some(443), depends_on[0].port());
+
+        // Modules that stay stopped aren't started in order after a reboot.
+        assert_eq!(Some(0), hub.spec().startup_order());
+        assert_eq!(None, sensor.spec().startup_order());
+    }
+
+    #[test]
+    fn parse_deployment_accepts_agent_desired_properties() {
+        let manifest = manifest();
+        let desired = &manifest["modulesContent"]["$edgeAgent"]["properties.desired"];
+
+        let from_manifest = parse_deployment(&manifest).unwrap();
+        let from_desired = parse_deployment(desired).unwrap();
+        let hashes = |modules: &[super::DesiredModule]| -> Vec<String> {
+            modules.iter().map(|module| module.hash.clone()).collect()
+        };
+        assert_eq!(hashes(&from_manifest), hashes(&from_desired));
+    }
+
+    #[test]
+    fn parse_deployment_hash_ignores_credentials() {
+        let mut manifest = manifest();
+        let before = parse_deployment(&manifest).unwrap();
+
+        manifest["modulesContent"]["$edgeAgent"]["properties.desired"]["runtime"]["settings"]
+            ["registryCredentials"]["contoso"]["password"] = json!("rotated");
+        manifest["modulesContent"]["$edgeAgent"]["properties.desired"]["modules"]["filter"]
+            ["env"] = json!({ "LEVEL": { "value": "debug" } });
+        let after = parse_deployment(&manifest).unwrap();
+
+        assert_eq!(before[0].hash, after[0].hash);
+        assert_eq!(before[1].hash, after[1].hash);
+        assert_ne!(before[2].hash, after[2].hash);
+    }
+
+    #[test]
+    fn parse_deployment_rejects_invalid_modules() {
+        for module in &[
+            json!({ "type": "docker", "settings": {} }),
+            json!({ "type": "process", "settings": { "image": "alpine" } }),
+            json!({ "status": "paused", "settings": { "image": "alpine" } }),
+            json!({ "restartPolicy": "sometimes", "settings": { "image": "alpine" } }),
+            json!({ "settings": { "image": "alpine", "createOptions": "{" } }),
+            json!({ "settings": { "image": "alpine", "createOptions": { "HostConfig": 1 } } }),
+        ] {
+            let manifest = json!({ "modules": { "module": module } });
+            let err = parse_deployment(&manifest).unwrap_err();
+            assert!(
+                err.to_string()
+                    .starts_with("Deployment manifest is invalid: module module"),
+                "{}",
+                err
+            );
+        }
+    }
+
+    #[test]
+    fn plan_converges_modules() {
+        let desired = parse_deployment(&manifest()).unwrap();
+        let hub_config =
+            json!({ "createOptions": desired[0].spec().config().settings()["createOptions"] });
+        let sensor_config =
+            json!({ "createOptions": desired[1].spec().config().settings()["createOptions"] });
+
+        let current = vec![
+            CurrentModule::new("old".to_string(), &json!({}), true),
+            CurrentModule::new("edgeAgent".to_string(), &json!({}), true),
+            CurrentModule::new("filter".to_string(), &json!({}), true),
+            CurrentModule::new("sensor".to_string(), &sensor_config, true),
+            CurrentModule::new("edgeHub".to_string(), &hub_config, true),
+        ];
+        let desired = desired
+            .into_iter()
+            .map(|module| {
+                let name = module.spec().name().clone();
+                (module, name)
+            })
+            .collect();
+
+        assert_eq!(
+            vec![
+                Step::Remove("old".to_string()),
+                Step::Unchanged("edgeHub".to_string()),
+                Step::Stop("sensor".to_string()),
+                Step::Update {
+                    spec: "filter".to_string(),
+                    start: true
+                },
+            ],
+            plan(desired, &current)
+        );
+    }
+
+    #[test]
+    fn plan_creates_missing_modules() {
+        let desired = parse_deployment(&manifest())
+            .unwrap()
+            .into_iter()
+            .map(|module| {
+                let name = module.spec().name().clone();
+                (module, name)
+            })
+            .collect();
+
+        let steps = plan(desired, &[]);
+        assert_eq!(
+            vec!["created", "created", "created"],
+            steps.iter().map(Step::action).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            Step::Create {
+                spec: "sensor".to_string(),
+                start: false
+            },
+            steps[1]
+        );
+    }
+}
@@ -11,7 +11,7 @@ use lazy_static::lazy_static;
 use serde::de::DeserializeOwned;
 use serde::Serialize;
 
-use edgelet_core::{Authenticator, Module, ModuleRuntime, ModuleRuntimeErrorReason, Policy};
+use edgelet_core::{Authenticator, Module, ModuleRuntime, ModuleRuntimeErrorReason, Policy, Role};
 use edgelet_http::authentication::Authentication;
 use edgelet_http::authorization::Authorization;
 use edgelet_http::route::{Builder, RegexRecognizer, Router, RouterService};
@@ -19,11 +19,13 @@ use edgelet_http::router;
 use edgelet_http::Version;
 use identity_client::client::IdentityClient;
 
+mod deployment;
 mod device_actions;
 mod identity;
 mod module;
 mod system_info;
 
+use self::deployment::DeployModules;
 use self::device_actions::ReprovisionDevice;
 use self::identity::{CreateIdentity, DeleteIdentity, ListIdentities, UpdateIdentity};
 pub use self::module::*;
@@ -50,30 +52,35 @@ impl ManagementService {
         for<'r> &'r <M as ModuleRuntime>::Error: Into<ModuleRuntimeErrorReason>,
         <M::Module as Module>::Config: DeserializeOwned + Serialize,
         M::Logs: Into<Body>,
+        M::Archive: Into<Body>,
         <M::AuthenticateFuture as Future>::Error: Fail,
     {
         let router = router!(
-            get     Version2018_06_28 runtime Policy::Anonymous             => "/modules"                           => ListModules::new(runtime.clone()),
-            post    Version2018_06_28 runtime Policy::Module(&*AGENT_NAME)  => "/modules"                           => CreateModule::new(runtime.clone()),
-            get     Version2018_06_28 runtime Policy::Anonymous             => "/modules/(?P<name>[^/]+)"           => GetModule,
-            put     Version2018_06_28 runtime Policy::Module(&*AGENT_NAME)  => "/modules/(?P<name>[^/]+)"           => UpdateModule::new(runtime.clone()),
-            post    Version2019_01_30 runtime Policy::Module(&*AGENT_NAME)  => "/modules/(?P<name>[^/]+)/prepareupdate"   => PrepareUpdateModule::new(runtime.clone()),
-            delete  Version2018_06_28 runtime Policy::Module(&*AGENT_NAME)  => "/modules/(?P<name>[^/]+)"           => DeleteModule::new(runtime.clone()),
-            post    Version2018_06_28 runtime Policy::Anonymous             => "/modules/(?P<name>[^/]+)/start"     => StartModule::new(runtime.clone()),
-            post    Version2018_06_28 runtime Policy::Anonymous             => "/modules/(?P<name>[^/]+)/stop"      => StopModule::new(runtime.clone()),
-            post    Version2018_06_28 runtime Policy::Anonymous             => "/modules/(?P<name>[^/]+)/restart"   => RestartModule::new(runtime.clone()),
-            get     Version2018_06_28 runtime Policy::Anonymous             => "/modules/(?P<name>[^/]+)/logs"      => ModuleLogs::new(runtime.clone()),
-
-            get     Version2018_06_28 runtime Policy::Module(&*AGENT_NAME)  => "/identities"                        => ListIdentities::new(identity_client.clone()),
-            post    Version2018_06_28 runtime Policy::Module(&*AGENT_NAME)  => "/identities"                        => CreateIdentity::new(identity_client.clone()),
-            put     Version2018_06_28 runtime Policy::Module(&*AGENT_NAME)  => "/identities/(?P<name>[^/]+)"        => UpdateIdentity::new(identity_client.clone()),
-            delete  Version2018_06_28 runtime Policy::Module(&*AGENT_NAME)  => "/identities/(?P<name>[^/]+)"        => DeleteIdentity::new(identity_client),
-
-            get     Version2018_06_28 runtime Policy::Anonymous             => "/systeminfo"                        => GetSystemInfo::new(runtime.clone()),
-            get     Version2019_11_05 runtime Policy::Anonymous             => "/systeminfo/resources"              => GetSystemResources::new(runtime.clone()),
-            get     Version2020_07_07 runtime Policy::Anonymous             => "/systeminfo/supportbundle"          => GetSupportBundle::new(runtime.clone()),
-
-            post    Version2019_10_22 runtime Policy::Module(&*AGENT_NAME)  => "/device/reprovision"                => ReprovisionDevice::new(initiate_shutdown_and_reprovision),
+            get     Version2018_06_28 runtime Policy::Anonymous, Role::ReadOnly            => "/modules"                               => ListModules::new(runtime.clone()),
+            post    Version2018_06_28 runtime Policy::Module(&*AGENT_NAME), Role::Admin    => "/modules"                               => CreateModule::new(runtime.clone()),
+            get     Version2018_06_28 runtime Policy::Anonymous, Role::ReadOnly            => "/modules/(?P<name>[^/]+)"               => GetModule,
+            put     Version2018_06_28 runtime Policy::Module(&*AGENT_NAME), Role::Admin    => "/modules/(?P<name>[^/]+)"               => UpdateModule::new(runtime.clone()),
+            post    Version2019_01_30 runtime Policy::Module(&*AGENT_NAME), Role::Admin    => "/modules/(?P<name>[^/]+)/prepareupdate" => PrepareUpdateModule::new(runtime.clone()),
+            delete  Version2018_06_28 runtime Policy::Module(&*AGENT_NAME), Role::Admin    => "/modules/(?P<name>[^/]+)"               => DeleteModule::new(runtime.clone()),
+            post    Version2018_06_28 runtime Policy::Anonymous, Role::Operator            => "/modules/(?P<name>[^/]+)/start"         => StartModule::new(runtime.clone()),
+            post    Version2018_06_28 runtime Policy::Anonymous, Role::Operator            => "/modules/(?P<name>[^/]+)/stop"          => StopModule::new(runtime.clone()),
+            post    Version2018_06_28 runtime Policy::Anonymous, Role::Operator            => "/modules/(?P<name>[^/]+)/restart"       => RestartModule::new(runtime.clone()),
+            get     Version2018_06_28 runtime Policy::Anonymous, Role::ReadOnly            => "/modules/(?P<name>[^/]+)/logs"          => ModuleLogs::new(runtime.clone()),
+            post    Version2020_07_07 runtime Policy::Anonymous, Role::Admin               => "/modules/(?P<name>[^/]+)/exec"          => ExecModule::new(runtime.clone()),
+            get     Version2020_07_07 runtime Policy::Anonymous, Role::Operator            => "/modules/(?P<name>[^/]+)/archive"       => GetModuleArchive::new(runtime.clone()),
+            put     Version2020_07_07 runtime Policy::Anonymous, Role::Admin               => "/modules/(?P<name>[^/]+)/archive"       => PutModuleArchive::new(runtime.clone()),
+            post    Version2020_07_07 runtime Policy::Module(&*AGENT_NAME), Role::Admin    => "/deployment"                            => DeployModules::new(runtime.clone()),
+
+            get     Version2018_06_28 runtime Policy::Module(&*AGENT_NAME), Role::Admin    => "/identities"                            => ListIdentities::new(identity_client.clone()),
+            post    Version2018_06_28 runtime Policy::Module(&*AGENT_NAME), Role::Admin    => "/identities"                            => CreateIdentity::new(identity_client.clone()),
+            put     Version2018_06_28 runtime Policy::Module(&*AGENT_NAME), Role::Admin    => "/identities/(?P<name>[^/]+)"            => UpdateIdentity::new(identity_client.clone()),
+            delete  Version2018_06_28 runtime Policy::Module(&*AGENT_NAME), Role::Admin    => "/identities/(?P<name>[^/]+)"            => DeleteIdentity::new(identity_client),
+
+            get     Version2018_06_28 runtime Policy::Anonymous, Role::ReadOnly            => "/systeminfo"                            => GetSystemInfo::new(runtime.clone()),
+            get     Version2019_11_05 runtime Policy::Anonymous, Role::ReadOnly            => "/systeminfo/resources"                  => GetSystemResources::new(runtime.clone()),
+            get     Version2020_07_07 runtime Policy::Anonymous, Role::Operator            => "/systeminfo/supportbundle"              => GetSupportBundle::new(runtime.clone()),
+
+            post    Version2019_10_22 runtime Policy::Module(&*AGENT_NAME), Role::Admin    => "/device/reprovision"                    => ReprovisionDevice::new(initiate_shutdown_and_reprovision),
         );
 
         router.new_service().then(|inner| {
@@ -106,3 +113,79 @@ impl NewService for ManagementService {
         future::ok(self.clone())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use chrono::prelude::*;
+    use futures::sync::mpsc;
+    use futures::Future;
+    use hyper::service::Service;
+    use hyper::{Request, StatusCode};
+    use serde_json::json;
+
+    use edgelet_core::{
+        AuthId, MakeModuleRuntime, ModuleRuntimeErrorReason, ModuleRuntimeState, ModuleStatus,
+    };
+    use edgelet_test_utils::module::{TestConfig, TestModule, TestRuntime, TestSettings};
+    use identity_client::client::IdentityClient;
+
+    use super::ManagementService;
+    use crate::server::module::tests::Error;
+
+    impl<'a> From<&'a Error> for ModuleRuntimeErrorReason {
+        fn from(_: &'a Error) -> Self {
+            ModuleRuntimeErrorReason::Other
+        }
+    }
+
+    fn service(auth_id: AuthId) -> ManagementService {
+        let state = ModuleRuntimeState::default()
+            .with_status(ModuleStatus::Running)
+            .with_started_at(Some(Utc.ymd(2018, 4, 13).and_hms_milli(14, 20, 0, 1)));
+        let config = TestConfig::new("microsoft/test-image".to_string());
+        let module = TestModule::new("test-module".to_string(), config, Ok(state));
+        let runtime = TestRuntime::<Error, _>::make_runtime(TestSettings::new())
+            .wait()
+            .unwrap()
+            .with_module(Ok(module))
+            .with_auth_id(auth_id);
+
+        let identity_client = Arc::new(Mutex::new(IdentityClient::new(
+            aziot_identity_common_http::ApiVersion::V2020_09_01,
+            &"unix:///var/run/aziot/identityd.sock".parse().unwrap(),
+        )));
+        let (initiate_shutdown_and_reprovision, _) = mpsc::unbounded();
+
+        ManagementService::new(&runtime, identity_client, initiate_shutdown_and_reprovision)
+            .wait()
+            .unwrap()
+    }
+
+    fn deploy(auth_id: AuthId) -> StatusCode {
+        let manifest = json!({
+            "modules": {
+                "test-module": {
+                    "type": "docker",
+                    "settings": { "image": "microsoft/test-image" }
+                }
+            }
+        });
+        let request = Request::post("http://localhost/deployment?api-version=2020-07-07")
+            .body(manifest.to_string().into())
+            .unwrap();
+
+        service(auth_id).call(request).wait().unwrap().status()
+    }
+
+    #[test]
+    fn only_agent_can_deploy() {
+        assert_eq!(StatusCode::NOT_FOUND, deploy(AuthId::None));
+        assert_eq!(
+            StatusCode::NOT_FOUND,
+            deploy(AuthId::Value("test-module".into()))
+        );
+        assert_eq!(StatusCode::OK, deploy(AuthId::Value("edgeAgent".into())));
+    }
+}
@@ -0,0 +1,260 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use std::io;
+
+use failure::ResultExt;
+use futures::{future, Future, IntoFuture, Stream};
+use hyper::header::CONTENT_TYPE;
+use hyper::{Body, Chunk, Request, Response, StatusCode};
+use url::form_urlencoded;
+
+use edgelet_core::{ModuleRuntime, RuntimeOperation};
+use edgelet_http::route::{Handler, Parameters};
+use edgelet_http::Error as HttpError;
+
+use crate::error::{Error, ErrorKind};
+use crate::IntoResponse;
+
+const ARCHIVE_CONTENT_TYPE: &str = "application/x-tar";
+
+pub struct GetModuleArchive<M> {
+    runtime: M,
+}
+
+impl<M> GetModuleArchive<M> {
+    pub fn new(runtime: M) -> Self {
+        GetModuleArchive { runtime }
+    }
+}
+
+impl<M> Handler<Parameters> for GetModuleArchive<M>
+where
+    M: 'static + ModuleRuntime + Send,
+    M::Archive: Into<Body>,
+{
+    fn handle(
+        &self,
+        req: Request<Body>,
+        params: Parameters,
+    ) -> Box<dyn Future<Item = Response<Body>, Error = HttpError> + Send> {
+        let response = parse_params(&req, &params)
+            .map(|(name, path)| {
+                self.runtime
+                    .get_archive(&name, &path)
+                    .then(move |archive| -> Result<_, Error> {
+                        let archive = archive.with_context(|_| {
+                            ErrorKind::RuntimeOperation(RuntimeOperation::GetModuleArchive(
+                                name.clone(),
+                            ))
+                        })?;
+                        let response = Response::builder()
+                            .status(StatusCode::OK)
+                            .header(CONTENT_TYPE, ARCHIVE_CONTENT_TYPE)
+                            .body(archive.into())
+                            .context(ErrorKind::RuntimeOperation(
+                                RuntimeOperation::GetModuleArchive(name),
+                            ))?;
+                        Ok(response)
+                    })
+            })
+            .into_future()
+            .flatten()
+            .or_else(|e| future::ok(e.into_response()));
+
+        Box::new(response)
+    }
+}
+
+pub struct PutModuleArchive<M> {
+    runtime: M,
+}
+
+impl<M> PutModuleArchive<M> {
+    pub fn new(runtime: M) -> Self {
+        PutModuleArchive { runtime }
+    }
+}
+
+impl<M> Handler<Parameters> for PutModuleArchive<M>
+where
+    M: 'static + ModuleRuntime + Send,
+{
+    fn handle(
+        &self,
+        req: Request<Body>,
+        params: Parameters,
+    ) -> Box<dyn Future<Item = Response<Body>, Error = HttpError> + Send> {
+        let response = parse_params(&req, &params)
+            .map(|(name, path)| {
+                let archive = req
+                    .into_body()
+                    .map(Chunk::into_bytes)
+                    .map_err(|err| io::Error::new(io::ErrorKind::Other, err));
+
+                self.runtime.put_archive(&name, &path, archive).then(
+                    move |result| -> Result<_, Error> {
+                        result.with_context(|_| {
+                            ErrorKind::RuntimeOperation(RuntimeOperation::PutModuleArchive(
+                                name.clone(),
+                            ))
+                        })?;
+                        let response = Response::builder()
+                            .status(StatusCode::NO_CONTENT)
+                            .body(Body::default())
+                            .context(ErrorKind::RuntimeOperation(
+                                RuntimeOperation::PutModuleArchive(name),
+                            ))?;
+                        Ok(response)
+                    },
+                )
+            })
+            .into_future()
+            .flatten()
+            .or_else(|e| future::ok(e.into_response()));
+
+        Box::new(response)
+    }
+}
+
+fn parse_params(req: &Request<Body>, params: &Parameters) -> Result<(String, String), Error> {
+    let name = params
+        .name("name")
+        .ok_or_else(|| Error::from(ErrorKind::MissingRequiredParameter("name")))?
+        .to_string();
+    let path = req
+        .uri()
+        .query()
+        .and_then(|query| {
+            form_urlencoded::parse(query.as_bytes())
+                .find(|&(ref key, _)| key == "path")
+                .map(|(_, val)| val.into_owned())
+        })
+        .filter(|path| !path.is_empty())
+        .ok_or_else(|| Error::from(ErrorKind::MissingRequiredParameter("path")))?;
+    Ok((name, path))
+}
+
+#[cfg(test)]
+mod tests {
+    use edgelet_core::{MakeModuleRuntime, ModuleRuntimeState};
+    use edgelet_http::route::Parameters;
+    use edgelet_test_utils::module::{TestConfig, TestModule, TestRuntime, TestSettings};
+    use management::models::ErrorResponse;
+
+    use super::{
+        Body, Future, GetModuleArchive, Handler, PutModuleArchive, Request, StatusCode, Stream,
+        CONTENT_TYPE,
+    };
+    use crate::server::module::tests::Error;
+
+    fn runtime(
+        module: Result<TestModule<Error, TestConfig>, Error>,
+    ) -> TestRuntime<Error, TestSettings> {
+        TestRuntime::make_runtime(TestSettings::new())
+            .wait()
+            .unwrap()
+            .with_module(module)
+    }
+
+    fn test_module() -> TestModule<Error, TestConfig> {
+        let config = TestConfig::new("microsoft/test-image".to_string());
+        TestModule::new(
+            "test-module".to_string(),
+            config,
+            Ok(ModuleRuntimeState::default()),
+        )
+    }
+
+    fn parameters() -> Parameters {
+        Parameters::with_captures(vec![(Some("name".to_string()), "test".to_string())])
+    }
+
+    fn assert_error(response: hyper::Response<Body>, status: StatusCode, message: &str) {
+        assert_eq!(status, response.status());
+        response
+            .into_body()
+            .concat2()
+            .and_then(|b| {
+                let error: ErrorResponse = serde_json::from_slice(&b).unwrap();
+                assert_eq!(message, error.message());
+                Ok(())
+            })
+            .wait()
+            .unwrap();
+    }
+
+    #[test]
+    fn get_success() {
+        let handler = GetModuleArchive::new(runtime(Ok(test_module())));
+        let request = Request::get("http://localhost/modules/test/archive?path=%2Fapp")
+            .body(Body::default())
+            .unwrap();
+
+        let response = handler.handle(request, parameters()).wait().unwrap();
+
+        assert_eq!(StatusCode::OK, response.status());
+        assert_eq!("application/x-tar", response.headers()[CONTENT_TYPE]);
+        let body = response.into_body().concat2().wait().unwrap();
+        assert_eq!(&b"archive"[..], &body[..]);
+    }
+
+    #[test]
+    fn get_missing_path() {
+        let handler = GetModuleArchive::new(runtime(Ok(test_module())));
+        let request = Request::get("http://localhost/modules/test/archive")
+            .body(Body::default())
+            .unwrap();
+
+        let response = handler.handle(request, parameters()).wait().unwrap();
+
+        assert_error(
+            response,
+            StatusCode::BAD_REQUEST,
+            "The request is missing required parameter `path`",
+        );
+    }
+
+    #[test]
+    fn get_runtime_error() {
+        let handler = GetModuleArchive::new(runtime(Err(Error::General)));
+        let request = Request::get("http://localhost/modules/test/archive?path=%2Fapp")
+            .body(Body::default())
+            .unwrap();
+
+        let response = handler.handle(request, parameters()).wait().unwrap();
+
+        assert_error(
+            response,
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Could not copy files from module test\n\tcaused by: General error",
+        );
+    }
+
+    #[test]
+    fn put_success() {
+        let handler = PutModuleArchive::new(runtime(Ok(test_module())));
+        let request = Request::put("http://localhost/modules/test/archive?path=%2Fapp")
+            .body(Body::from("archive"))
+            .unwrap();
+
+        let response = handler.handle(request, parameters()).wait().unwrap();
+
+        assert_eq!(StatusCode::NO_CONTENT, response.status());
+    }
+
+    #[test]
+    fn put_runtime_error() {
+        let handler = PutModuleArchive::new(runtime(Err(Error::General)));
+        let request = Request::put("http://localhost/modules/test/archive?path=%2Fapp")
+            .body(Body::from("archive"))
+            .unwrap();
+
+        let response = handler.handle(request, parameters()).wait().unwrap();
+
+        assert_error(
+            response,
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Could not copy files to module test\n\tcaused by: General error",
+        );
+    }
+}
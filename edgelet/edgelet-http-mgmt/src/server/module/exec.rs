@@ -0,0 +1,290 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use std::io;
+
+use failure::{Fail, ResultExt};
+use futures::{future, Future, IntoFuture, Stream};
+use hyper::header::{CONNECTION, UPGRADE};
+use hyper::{Body, Request, Response, StatusCode};
+use log::{debug, warn};
+use tokio::codec::{BytesCodec, FramedRead, FramedWrite};
+use tokio::io::AsyncRead;
+use url::form_urlencoded;
+
+use edgelet_core::{ExecOptions, ModuleRuntime, RuntimeOperation};
+use edgelet_http::route::{Handler, Parameters};
+use edgelet_http::Error as HttpError;
+
+use crate::error::{Error, ErrorKind};
+use crate::exec::{ExecCodec, ExecFrame};
+use crate::IntoResponse;
+
+/// The protocol the client switches the connection to. After the switch, the client sends
+/// the command's stdin as raw bytes and receives its output as `ExecFrame`s.
+const EXEC_PROTOCOL: &str = "iotedge-exec";
+
+pub struct ExecModule<M> {
+    runtime: M,
+}
+
+impl<M> ExecModule<M> {
+    pub fn new(runtime: M) -> Self {
+        ExecModule { runtime }
+    }
+}
+
+impl<M> Handler<Parameters> for ExecModule<M>
+where
+    M: 'static + ModuleRuntime + Send,
+{
+    fn handle(
+        &self,
+        req: Request<Body>,
+        params: Parameters,
+    ) -> Box<dyn Future<Item = Response<Body>, Error = HttpError> + Send> {
+        let response = params
+            .name("name")
+            .ok_or_else(|| Error::from(ErrorKind::MissingRequiredParameter("name")))
+            .and_then(|name| {
+                let name = name.to_string();
+                let options = parse_options(req.uri().query().unwrap_or_default())?;
+                Ok((name, options))
+            })
+            .map(|(name, options)| {
+                // The command is started before the connection is upgraded, so that
+                // failures to start it are reported as regular responses.
+                self.runtime
+                    .exec(&name, &options)
+                    .then(move |result| match result {
+                        Ok((io, exit_code)) => {
+                            let tty = options.tty();
+                            let upgrade = req
+                                .into_body()
+                                .on_upgrade()
+                                .map_err(|err| debug!("Could not upgrade exec connection: {}", err))
+                                .and_then(move |upgraded| pipe(upgraded, io, exit_code, tty));
+                            tokio::spawn(upgrade);
+
+                            Ok(Response::builder()
+                                .status(StatusCode::SWITCHING_PROTOCOLS)
+                                .header(CONNECTION, "upgrade")
+                                .header(UPGRADE, EXEC_PROTOCOL)
+                                .body(Body::empty())
+                                .context(ErrorKind::RuntimeOperation(
+                                    RuntimeOperation::ExecModule(name),
+                                ))?)
+                        }
+                        Err(err) => Err(Error::from(err.context(ErrorKind::RuntimeOperation(
+                            RuntimeOperation::ExecModule(name),
+                        )))),
+                    })
+            })
+            .into_future()
+            .flatten()
+            .or_else(|e| Ok(e.into_response()));
+
+        Box::new(response)
+    }
+}
+
+/// Copies the client's input to the command, and the command's output followed by its exit
+/// code to the client, until the command's output ends.
+fn pipe<C, E, X>(
+    client: C,
+    exec: E,
+    exit_code: X,
+    tty: bool,
+) -> impl Future<Item = (), Error = ()> + Send
+where
+    C: AsyncRead + tokio::io::AsyncWrite + Send + 'static,
+    E: AsyncRead + tokio::io::AsyncWrite + Send + 'static,
+    X: Future<Item = Option<i32>> + Send + 'static,
+    X::Error: Fail,
+{
+    let (client_read, client_write) = client.split();
+    let (exec_read, exec_write) = exec.split();
+
+    let input = tokio::io::copy(client_read, exec_write)
+        .and_then(|(_, _, exec_write)| tokio::io::shutdown(exec_write))
+        .then(|result| {
+            if let Err(err) = result {
+                debug!("Could not copy exec input: {}", err);
+            }
+            Ok(())
+        });
+    tokio::spawn(input);
+
+    // With a TTY, stdout and stderr are a single stream that isn't framed.
+    let output: Box<dyn Stream<Item = ExecFrame, Error = io::Error> + Send> = if tty {
+        Box::new(
+            FramedRead::new(exec_read, BytesCodec::new())
+                .map(|bytes| ExecFrame::Stdout(bytes.freeze())),
+        )
+    } else {
+        Box::new(FramedRead::new(exec_read, ExecCodec))
+    };
+
+    let exit_code = exit_code.then(|result| {
+        let exit_code = result.unwrap_or_else(|err| {
+            warn!("Could not get the exit code of the command: {}", err);
+            None
+        });
+        Ok::<_, io::Error>(ExecFrame::Exit(exit_code))
+    });
+
+    output
+        .chain(exit_code.into_stream())
+        .forward(FramedWrite::new(client_write, ExecCodec))
+        .then(|result| {
+            if let Err(err) = result {
+                debug!("Could not copy exec output: {}", err);
+            }
+            future::ok(())
+        })
+}
+
+fn parse_options(query: &str) -> Result<ExecOptions, Error> {
+    let parse: Vec<_> = form_urlencoded::parse(query.as_bytes()).collect();
+    let cmd: Vec<String> = parse
+        .iter()
+        .filter(|&(ref key, _)| key == "cmd")
+        .map(|(_, val)| val.to_string())
+        .collect();
+    if cmd.is_empty() {
+        return Err(Error::from(ErrorKind::MissingRequiredParameter("cmd")));
+    }
+    let tty = parse
+        .iter()
+        .find(|&(ref key, _)| key == "tty")
+        .map_or_else(|| Ok(false), |(_, val)| val.parse::<bool>())
+        .context(ErrorKind::MalformedRequestParameter("tty"))?;
+    let stdin = parse
+        .iter()
+        .find(|&(ref key, _)| key == "stdin")
+        .map_or_else(|| Ok(false), |(_, val)| val.parse::<bool>())
+        .context(ErrorKind::MalformedRequestParameter("stdin"))?;
+
+    Ok(ExecOptions::new(cmd).with_tty(tty).with_stdin(stdin))
+}
+
+#[cfg(test)]
+mod tests {
+    use edgelet_core::{MakeModuleRuntime, ModuleRuntimeState};
+    use edgelet_http::route::Parameters;
+    use edgelet_test_utils::module::{TestConfig, TestModule, TestRuntime, TestSettings};
+    use futures::Stream;
+    use management::models::ErrorResponse;
+    use tokio::runtime::current_thread::Runtime;
+
+    use super::{parse_options, Body, ExecModule, Future, Handler, Request, StatusCode, UPGRADE};
+    use crate::server::module::tests::Error;
+
+    fn handler(
+        module: Result<TestModule<Error, TestConfig>, Error>,
+    ) -> ExecModule<TestRuntime<Error, TestSettings>> {
+        let runtime = TestRuntime::make_runtime(TestSettings::new())
+            .wait()
+            .unwrap()
+            .with_module(module);
+        ExecModule::new(runtime)
+    }
+
+    fn test_module() -> TestModule<Error, TestConfig> {
+        let config = TestConfig::new("microsoft/test-image".to_string());
+        TestModule::new(
+            "test-module".to_string(),
+            config,
+            Ok(ModuleRuntimeState::default()),
+        )
+    }
+
+    #[test]
+    fn exec_options() {
+        let options = parse_options("cmd=sh&cmd=-c&cmd=echo%20hi&tty=true").unwrap();
+        assert_eq!(&["sh", "-c", "echo hi"], options.cmd());
+        assert!(options.tty());
+        assert!(!options.stdin());
+    }
+
+    #[test]
+    fn exec_options_malformed() {
+        let err = parse_options("cmd=sh&stdin=yes").unwrap_err();
+        assert_eq!(
+            "The request parameter `stdin` is malformed",
+            err.to_string()
+        );
+    }
+
+    #[test]
+    fn success_switches_protocols() {
+        let handler = handler(Ok(test_module()));
+        let parameters =
+            Parameters::with_captures(vec![(Some("name".to_string()), "test".to_string())]);
+        let request = Request::post("http://localhost/modules/test/exec?cmd=ls")
+            .body(Body::default())
+            .unwrap();
+
+        // The handler spawns the task that pipes the upgraded connection.
+        let response = Runtime::new()
+            .unwrap()
+            .block_on(handler.handle(request, parameters))
+            .unwrap();
+
+        assert_eq!(StatusCode::SWITCHING_PROTOCOLS, response.status());
+        assert_eq!("iotedge-exec", response.headers()[UPGRADE]);
+    }
+
+    #[test]
+    fn missing_cmd() {
+        let handler = handler(Ok(test_module()));
+        let parameters =
+            Parameters::with_captures(vec![(Some("name".to_string()), "test".to_string())]);
+        let request = Request::post("http://localhost/modules/test/exec")
+            .body(Body::default())
+            .unwrap();
+
+        let response = handler.handle(request, parameters).wait().unwrap();
+
+        assert_eq!(StatusCode::BAD_REQUEST, response.status());
+        response
+            .into_body()
+            .concat2()
+            .and_then(|b| {
+                let error: ErrorResponse = serde_json::from_slice(&b).unwrap();
+                assert_eq!(
+                    "The request is missing required parameter `cmd`",
+                    error.message()
+                );
+                Ok(())
+            })
+            .wait()
+            .unwrap();
+    }
+
+    #[test]
+    fn runtime_error() {
+        let handler = handler(Err(Error::General));
+        let parameters =
+            Parameters::with_captures(vec![(Some("name".to_string()), "test".to_string())]);
+        let request = Request::post("http://localhost/modules/test/exec?cmd=ls")
+            .body(Body::default())
+            .unwrap();
+
+        let response = handler.handle(request, parameters).wait().unwrap();
+
+        assert_eq!(StatusCode::INTERNAL_SERVER_ERROR, response.status());
+        response
+            .into_body()
+            .concat2()
+            .and_then(|b| {
+                let error: ErrorResponse = serde_json::from_slice(&b).unwrap();
+                assert_eq!(
+                    "Could not run command in module test\n\tcaused by: General error",
+                    error.message()
+                );
+                Ok(())
+            })
+            .wait()
+            .unwrap();
+    }
+}
@@ -76,6 +76,12 @@ where
     if let Some(description) = state.status_description() {
         runtime_status.set_description(description.to_string());
     }
+    if let Some(image_id) = state.image_id() {
+        runtime_status.set_image_id(image_id.to_string());
+    }
+    if let Some(pid) = state.pid() {
+        runtime_status.set_pid(pid);
+    }
     let mut status = Status::new(runtime_status);
     if let Some(started_at) = state.started_at() {
         status.set_start_time(started_at.to_rfc3339());
@@ -116,7 +122,8 @@ mod tests {
             .with_status_description(Some("description".to_string()))
             .with_started_at(Some(Utc.ymd(2018, 4, 13).and_hms_milli(14, 20, 0, 1)))
             .with_finished_at(Some(Utc.ymd(2018, 4, 13).and_hms_milli(15, 20, 0, 1)))
-            .with_image_id(Some("image-id".to_string()));
+            .with_image_id(Some("image-id".to_string()))
+            .with_pid(Some(1234));
         let config = TestConfig::new("microsoft/test-image".to_string());
         let module: TestModule<Error, _> =
             TestModule::new("test-module".to_string(), config, Ok(state));
@@ -163,6 +170,11 @@ mod tests {
                     "description",
                     module.status().runtime_status().description().unwrap()
                 );
+                assert_eq!(
+                    Some("image-id"),
+                    module.status().runtime_status().image_id()
+                );
+                assert_eq!(Some(1234), module.status().runtime_status().pid());
                 Ok(())
             })
             .wait()
@@ -1,11 +1,17 @@
 // Copyright (c) Microsoft. All rights reserved.
 
+use std::borrow::Cow;
+use std::io;
+
 use failure::ResultExt;
-use futures::{future, Future, IntoFuture};
+use futures::{future, Future, IntoFuture, Stream};
 use hyper::{Body, Request, Response, StatusCode};
 use url::form_urlencoded;
 
-use edgelet_core::{parse_since, LogOptions, LogTail, ModuleRuntime, RuntimeOperation};
+use edgelet_core::{
+    filter_logs, parse_since, LogFilter, LogFormat, LogOptions, LogSeverity, LogStream, LogTail,
+    ModuleRuntime, RuntimeOperation,
+};
 use edgelet_http::route::{Handler, Parameters};
 use edgelet_http::Error as HttpError;
 
@@ -46,18 +52,22 @@ where
                 Ok((name, options))
             })
             .map(move |(name, options)| {
-                runtime.logs(&name, &options).then(|s| -> Result<_, Error> {
-                    let s = s.with_context(|_| {
-                        ErrorKind::RuntimeOperation(RuntimeOperation::GetModuleLogs(name.clone()))
-                    })?;
-                    let response = Response::builder()
-                        .status(StatusCode::OK)
-                        .body(s.into())
-                        .context(ErrorKind::RuntimeOperation(
-                            RuntimeOperation::GetModuleLogs(name),
-                        ))?;
-                    Ok(response)
-                })
+                runtime
+                    .logs(&name, &options)
+                    .then(move |s| -> Result<_, Error> {
+                        let s = s.with_context(|_| {
+                            ErrorKind::RuntimeOperation(RuntimeOperation::GetModuleLogs(
+                                name.clone(),
+                            ))
+                        })?;
+                        let response = Response::builder()
+                            .status(StatusCode::OK)
+                            .body(filter_body(&name, s.into(), &options))
+                            .context(ErrorKind::RuntimeOperation(
+                                RuntimeOperation::GetModuleLogs(name),
+                            ))?;
+                        Ok(response)
+                    })
             })
             .into_future()
             .flatten()
@@ -67,6 +77,15 @@ where
     }
 }
 
+fn filter_body(name: &str, body: Body, options: &LogOptions) -> Body {
+    if options.filter().is_empty() && options.format() == LogFormat::Raw {
+        body
+    } else {
+        let logs = body.map_err(|err| io::Error::new(io::ErrorKind::Other, err));
+        Body::wrap_stream(filter_logs(name.to_string(), logs, options))
+    }
+}
+
 fn parse_options(query: &str) -> Result<LogOptions, Error> {
     let parse: Vec<_> = form_urlencoded::parse(query.as_bytes()).collect();
     let tail = parse
@@ -84,10 +103,27 @@ fn parse_options(query: &str) -> Result<LogOptions, Error> {
         .find(|&(ref key, _)| key == "since")
         .map_or_else(|| Ok(0), |(_, val)| parse_since(val))
         .context(ErrorKind::MalformedRequestParameter("since"))?;
+    let format = parse
+        .iter()
+        .find(|&(ref key, _)| key == "format")
+        .map_or_else(
+            || Ok(LogFormat::default()),
+            |(_, val)| val.parse::<LogFormat>(),
+        )
+        .context(ErrorKind::MalformedRequestParameter("format"))?;
+    let timestamps = parse
+        .iter()
+        .find(|&(ref key, _)| key == "timestamps")
+        .map_or_else(|| Ok(false), |(_, val)| val.parse::<bool>())
+        .context(ErrorKind::MalformedRequestParameter("timestamps"))?;
     let mut options = LogOptions::new()
         .with_follow(follow)
         .with_tail(tail)
-        .with_since(since);
+        .with_since(since)
+        .with_format(format)
+        // NDJSON lines always carry the timestamp reported by the runtime
+        .with_timestamps(timestamps || format == LogFormat::Ndjson)
+        .with_filter(parse_filter(&parse)?);
 
     if let Some(until) = parse
         .iter()
@@ -107,6 +143,30 @@ fn parse_options(query: &str) -> Result<LogOptions, Error> {
     Ok(options)
 }
 
+fn parse_filter(parse: &[(Cow<'_, str>, Cow<'_, str>)]) -> Result<LogFilter, Error> {
+    let mut filter = LogFilter::new();
+
+    for (key, val) in parse {
+        filter = match key.as_ref() {
+            "stream" => filter.with_stream(
+                val.parse::<LogStream>()
+                    .context(ErrorKind::MalformedRequestParameter("stream"))?,
+            ),
+            "contains" => filter.with_contains(val.to_string()),
+            "regex" => filter
+                .with_regex(val)
+                .context(ErrorKind::MalformedRequestParameter("regex"))?,
+            "severity" => filter.with_severity(
+                val.parse::<LogSeverity>()
+                    .context(ErrorKind::MalformedRequestParameter("severity"))?,
+            ),
+            _ => filter,
+        };
+    }
+
+    Ok(filter)
+}
+
 #[cfg(test)]
 mod tests {
     use chrono::prelude::*;
@@ -116,9 +176,11 @@ mod tests {
     use management::models::ErrorResponse;
 
     use super::{
-        parse_options, Body, Future, Handler, LogTail, ModuleLogs, Parameters, Request, StatusCode,
+        parse_options, Body, Future, Handler, LogFormat, LogTail, ModuleLogs, Parameters, Request,
+        StatusCode,
     };
     use crate::server::module::tests::Error;
+    use edgelet_core::{LogSeverity, LogStream};
 
     #[test]
     fn correct_logoptions() {
@@ -171,6 +233,49 @@ mod tests {
         );
     }
 
+    #[test]
+    fn logoption_filter() {
+        let query = "stream=stderr&contains=foo&regex=ba%5Br%5D&severity=warning&format=ndjson";
+        let options = parse_options(&query).unwrap();
+        assert_eq!(Some(LogStream::Stderr), options.filter().stream());
+        assert_eq!(Some("foo"), options.filter().contains());
+        assert_eq!(Some("ba[r]"), options.filter().regex());
+        assert_eq!(Some(LogSeverity::Warning), options.filter().severity());
+        assert_eq!(LogFormat::Ndjson, options.format());
+        assert_eq!(true, options.timestamps());
+    }
+
+    #[test]
+    fn logoption_filter_defaults() {
+        let query = "";
+        let options = parse_options(&query).unwrap();
+        assert!(options.filter().is_empty());
+        assert_eq!(LogFormat::Raw, options.format());
+        assert_eq!(false, options.timestamps());
+    }
+
+    #[test]
+    fn logoption_regex_error() {
+        let query = "regex=%5Bfoo";
+        let options = parse_options(&query);
+        assert!(options.is_err());
+        assert_eq!(
+            "The request parameter `regex` is malformed",
+            options.err().unwrap().to_string()
+        );
+    }
+
+    #[test]
+    fn logoption_severity_error() {
+        let query = "severity=loud";
+        let options = parse_options(&query);
+        assert!(options.is_err());
+        assert_eq!(
+            "The request parameter `severity` is malformed",
+            options.err().unwrap().to_string()
+        );
+    }
+
     #[test]
     fn test_success() {
         let state = ModuleRuntimeState::default()
@@ -214,6 +319,53 @@ mod tests {
             .unwrap();
     }
 
+    #[test]
+    fn test_filtered() {
+        let config = TestConfig::new("microsoft/test-image".to_string());
+        let module: TestModule<Error, _> = TestModule::new_with_logs(
+            "test-module".to_string(),
+            config,
+            Ok(ModuleRuntimeState::default()),
+            vec![
+                &[
+                    0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x03, b'A', b'B', b'C',
+                ],
+                &[
+                    0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x03, b'D', b'E', b'F',
+                ],
+            ],
+        );
+        let runtime = TestRuntime::make_runtime(TestSettings::new())
+            .wait()
+            .unwrap()
+            .with_module(Ok(module));
+        let handler = ModuleLogs::new(runtime);
+        let request =
+            Request::get("http://localhost/modules/mod1/logs?api-version=2018-06-28&stream=stderr")
+                .body(Body::default())
+                .unwrap();
+        let parameters =
+            Parameters::with_captures(vec![(Some("name".to_string()), "mod1".to_string())]);
+
+        // act
+        let response = handler.handle(request, parameters).wait().unwrap();
+
+        // assert
+        assert_eq!(StatusCode::OK, response.status());
+        response
+            .into_body()
+            .concat2()
+            .and_then(|b| {
+                assert_eq!(
+                    &[0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x03, b'D', b'E', b'F'],
+                    &b[..]
+                );
+                Ok(())
+            })
+            .wait()
+            .unwrap();
+    }
+
     #[test]
     fn runtime_error() {
         let runtime = TestRuntime::make_runtime(TestSettings::new())
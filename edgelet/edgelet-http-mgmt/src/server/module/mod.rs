@@ -1,6 +1,7 @@
 // Copyright (c) Microsoft. All rights reserved.
 
 use std::collections::BTreeMap;
+use std::convert::TryInto;
 
 use failure::Fail;
 use serde::de::DeserializeOwned;
@@ -13,8 +14,10 @@ use management::models::{Config, EnvVar, ModuleDetails, ModuleSpec, RuntimeStatu
 
 use crate::error::{Error, ErrorKind};
 
+mod archive;
 mod create;
 mod delete;
+mod exec;
 mod get;
 mod list;
 mod logs;
@@ -24,8 +27,10 @@ mod start;
 mod stop;
 mod update;
 
+pub use self::archive::{GetModuleArchive, PutModuleArchive};
 pub use self::create::CreateModule;
 pub use self::delete::DeleteModule;
+pub use self::exec::ExecModule;
 pub use self::get::GetModule;
 pub use self::list::ListModules;
 pub use self::logs::ModuleLogs;
@@ -35,7 +40,7 @@ pub use self::start::StartModule;
 pub use self::stop::StopModule;
 pub use self::update::UpdateModule;
 
-fn spec_to_core<M>(
+pub(super) fn spec_to_core<M>(
     spec: &ModuleSpec,
     context: ErrorKind,
 ) -> Result<CoreModuleSpec<<M::Module as Module>::Config>, Error>
@@ -64,8 +69,25 @@ where
         Err(err) => return Err(Error::from(err.context(context))),
     };
 
+    let startup_order: Result<Option<u32>, _> =
+        spec.startup_order().map(TryInto::try_into).transpose();
+    let startup_order = match startup_order {
+        Ok(startup_order) => startup_order,
+        Err(err) => return Err(Error::from(err.context(context))),
+    };
+
+    // The dependencies of the management API have the same shape as those of the core spec.
+    let depends_on = match spec.depends_on().map_or(Ok(Vec::new()), |depends_on| {
+        serde_json::to_value(depends_on).and_then(serde_json::from_value)
+    }) {
+        Ok(depends_on) => depends_on,
+        Err(err) => return Err(Error::from(err.context(context))),
+    };
+
     let module_spec = match CoreModuleSpec::new(name, type_, config, env, image_pull_policy) {
-        Ok(module_spec) => module_spec,
+        Ok(module_spec) => module_spec
+            .with_startup_order(startup_order)
+            .with_depends_on(depends_on),
         Err(err) => return Err(Error::from(err.context(context))),
     };
 
@@ -12,7 +12,7 @@ use url::form_urlencoded;
 use edgelet_core::{parse_since, LogOptions, Module, ModuleRuntime, RuntimeOperation};
 use edgelet_http::route::{Handler, Parameters};
 use edgelet_http::Error as HttpError;
-use support_bundle::{make_bundle, OutputLocation};
+use support_bundle::{make_bundle, Component, OutputLocation, Redactor};
 
 use crate::error::{Error, ErrorKind};
 use crate::IntoResponse;
@@ -108,6 +108,8 @@ where
         edge_runtime_only,
         false,
         iothub_hostname,
+        Component::ALL.iter().copied().collect(),
+        Redactor::default(),
         runtime,
     )
     .map_err(|_| Error::from(ErrorKind::SupportBundle));
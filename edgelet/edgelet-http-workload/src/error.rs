@@ -61,6 +61,15 @@ pub enum ErrorKind {
     #[fail(display = "Module not found")]
     ModuleNotFound(String),
 
+    #[fail(display = "Secret {} not found", _0)]
+    SecretNotFound(String),
+
+    #[fail(display = "Could not access the module secret store")]
+    SecretStore,
+
+    #[fail(display = "The server certificate request is not allowed: {}", _0)]
+    ServerCertNotAllowed(String),
+
     #[fail(display = "Could not start workload service")]
     StartService,
 }
@@ -111,7 +120,8 @@ impl IntoResponse for Error {
         }
 
         let status_code = match *self.kind() {
-            ErrorKind::ModuleNotFound(_) => StatusCode::NOT_FOUND,
+            ErrorKind::ModuleNotFound(_) | ErrorKind::SecretNotFound(_) => StatusCode::NOT_FOUND,
+            ErrorKind::ServerCertNotAllowed(_) => StatusCode::FORBIDDEN,
             ErrorKind::MalformedRequestBody
             | ErrorKind::MalformedRequestParameter(_)
             | ErrorKind::MissingRequiredParameter(_) => StatusCode::BAD_REQUEST,
@@ -148,6 +158,7 @@ impl IntoResponse for Error {
 pub enum CertOperation {
     CreateIdentityCert,
     GetServerCert,
+    WatchCerts,
 }
 
 impl fmt::Display for CertOperation {
@@ -155,6 +166,7 @@ impl fmt::Display for CertOperation {
         match self {
             CertOperation::CreateIdentityCert => write!(f, "Could not create identity cert"),
             CertOperation::GetServerCert => write!(f, "Could not get server cert"),
+            CertOperation::WatchCerts => write!(f, "Could not watch certificates"),
         }
     }
 }
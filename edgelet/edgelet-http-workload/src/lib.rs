@@ -4,6 +4,7 @@
 #![deny(clippy::all, clippy::pedantic)]
 #![allow(
     clippy::bind_instead_of_map,
+    clippy::missing_errors_doc,
     clippy::module_name_repetitions,
     clippy::must_use_candidate,
     clippy::too_many_arguments,
@@ -15,8 +16,10 @@
 use hyper::{Body, Response};
 
 mod error;
+mod secret_store;
 mod server;
 
+pub use crate::secret_store::SecretStore;
 pub use crate::server::WorkloadService;
 
 pub trait IntoResponse {
@@ -0,0 +1,503 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+//! Key/value secrets that modules keep through the workload API.
+//!
+//! Values are encrypted with the master encryption key and bound to their module and key,
+//! so that the stored file can't be used to read or move them around. Unlike the workload
+//! `encrypt` and `decrypt` APIs, secrets aren't tied to a module's generation, so they
+//! survive the module being re-created.
+//!
+//! Secrets seeded from the configuration are remembered by a hash of their configured value,
+//! so that a seed is only stored again when its configured value changes. A module that
+//! deletes or changes a seeded secret keeps that change across restarts.
+
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use aziot_key_common::{EncryptMechanism, KeyHandle};
+use failure::{Fail, ResultExt};
+use log::{info, warn};
+use serde_derive::{Deserialize, Serialize};
+
+use crate::error::{Error, ErrorKind, Result};
+
+const MASTER_ENCRYPTION_KEY_ID: &str = "iotedge_master_encryption_id";
+const IV_SIZE: usize = 12;
+const MAX_KEY_LENGTH: usize = 256;
+pub(crate) const MAX_VALUE_SIZE: usize = 64 * 1024;
+
+#[derive(Clone)]
+pub struct SecretStore {
+    path: PathBuf,
+    cipher: Arc<dyn SecretCipher>,
+    lock: Arc<Mutex<()>>,
+}
+
+/// Authenticated encryption of secret values.
+pub(crate) trait SecretCipher: Send + Sync {
+    fn encrypt(&self, iv: &[u8], aad: &[u8], plaintext: &[u8]) -> Result<Vec<u8>>;
+
+    fn decrypt(&self, iv: &[u8], aad: &[u8], ciphertext: &[u8]) -> Result<Vec<u8>>;
+}
+
+/// Encrypts with the master encryption key in keyd.
+struct KeyServiceCipher(Arc<aziot_key_client::Client>);
+
+#[derive(Default, Deserialize, Serialize)]
+struct Contents {
+    #[serde(default)]
+    secrets: Secrets,
+
+    /// Hashes of the configured values that have been seeded, by module and key.
+    #[serde(default)]
+    seeded: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+type Secrets = BTreeMap<String, BTreeMap<String, EncryptedSecret>>;
+
+#[derive(Deserialize, Serialize)]
+struct EncryptedSecret {
+    iv: String,
+    ciphertext: String,
+}
+
+impl SecretStore {
+    pub fn new(path: PathBuf, key_client: Arc<aziot_key_client::Client>) -> Self {
+        SecretStore::with_cipher(path, Arc::new(KeyServiceCipher(key_client)))
+    }
+
+    pub(crate) fn with_cipher(path: PathBuf, cipher: Arc<dyn SecretCipher>) -> Self {
+        SecretStore {
+            path,
+            cipher,
+            lock: Arc::new(Mutex::new(())),
+        }
+    }
+
+    pub fn get(&self, module_id: &str, key: &str) -> Result<Option<Vec<u8>>> {
+        check_key(key)?;
+
+        let _lock = self.lock.lock().expect("secret store lock failed");
+        let contents = self.read()?;
+        let secret = match contents
+            .secrets
+            .get(module_id)
+            .and_then(|secrets| secrets.get(key))
+        {
+            Some(secret) => secret,
+            None => return Ok(None),
+        };
+
+        let iv = base64::decode(&secret.iv).context(ErrorKind::SecretStore)?;
+        let ciphertext = base64::decode(&secret.ciphertext).context(ErrorKind::SecretStore)?;
+        let value = self
+            .cipher
+            .decrypt(&iv, &aad(module_id, key), &ciphertext)?;
+        Ok(Some(value))
+    }
+
+    pub fn set(&self, module_id: &str, key: &str, value: &[u8]) -> Result<()> {
+        check_key(key)?;
+        if value.len() > MAX_VALUE_SIZE {
+            return Err(ErrorKind::MalformedRequestBody.into());
+        }
+
+        let _lock = self.lock.lock().expect("secret store lock failed");
+        let mut contents = self.read()?;
+        let secret = self.encrypt(module_id, key, value)?;
+        contents
+            .secrets
+            .entry(module_id.to_string())
+            .or_default()
+            .insert(key.to_string(), secret);
+        self.write(&contents)
+    }
+
+    /// Returns whether the secret existed.
+    pub fn delete(&self, module_id: &str, key: &str) -> Result<bool> {
+        check_key(key)?;
+
+        let _lock = self.lock.lock().expect("secret store lock failed");
+        let mut contents = self.read()?;
+        let module_secrets = match contents.secrets.get_mut(module_id) {
+            Some(module_secrets) => module_secrets,
+            None => return Ok(false),
+        };
+        if module_secrets.remove(key).is_none() {
+            return Ok(false);
+        }
+        if module_secrets.is_empty() {
+            contents.secrets.remove(module_id);
+        }
+        self.write(&contents)?;
+        Ok(true)
+    }
+
+    /// Stores the configured secrets that haven't been seeded with their current value yet.
+    ///
+    /// A secret whose configured value is unchanged since it was last seeded is left alone,
+    /// so that values a module has changed or deleted since are kept.
+    pub fn seed(&self, seeds: &BTreeMap<String, BTreeMap<String, String>>) -> Result<()> {
+        let _lock = self.lock.lock().expect("secret store lock failed");
+        let mut contents = self.read()?;
+
+        let mut seeded = BTreeMap::<String, BTreeMap<String, String>>::new();
+        let mut count = 0;
+        for (module_id, module_seeds) in seeds {
+            for (key, value) in module_seeds {
+                if check_key(key).is_err() {
+                    warn!(
+                        "Ignoring secret {} of module {}: invalid key",
+                        key, module_id
+                    );
+                    continue;
+                }
+
+                let hash = seed_hash(module_id, key, value);
+                let unchanged = contents
+                    .seeded
+                    .get(module_id)
+                    .and_then(|hashes| hashes.get(key))
+                    .map_or(false, |seeded_hash| *seeded_hash == hash);
+                if !unchanged {
+                    let secret = self.encrypt(module_id, key, value.as_bytes())?;
+                    contents
+                        .secrets
+                        .entry(module_id.clone())
+                        .or_default()
+                        .insert(key.clone(), secret);
+                    count += 1;
+                }
+                seeded
+                    .entry(module_id.clone())
+                    .or_default()
+                    .insert(key.clone(), hash);
+            }
+        }
+
+        // Forget seeds that were removed from the configuration, so that configuring them
+        // again stores them again.
+        if count > 0 || seeded != contents.seeded {
+            if count > 0 {
+                info!("Seeded {} module secret(s) from the configuration", count);
+            }
+            contents.seeded = seeded;
+            self.write(&contents)?;
+        }
+        Ok(())
+    }
+
+    fn encrypt(&self, module_id: &str, key: &str, value: &[u8]) -> Result<EncryptedSecret> {
+        let mut iv = vec![0_u8; IV_SIZE];
+        openssl::rand::rand_bytes(&mut iv).context(ErrorKind::SecretStore)?;
+        let ciphertext = self.cipher.encrypt(&iv, &aad(module_id, key), value)?;
+        Ok(EncryptedSecret {
+            iv: base64::encode(&iv),
+            ciphertext: base64::encode(&ciphertext),
+        })
+    }
+
+    fn read(&self) -> Result<Contents> {
+        match fs::read(&self.path) {
+            Ok(contents) => Ok(serde_json::from_slice(&contents).context(ErrorKind::SecretStore)?),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Contents::default()),
+            Err(err) => Err(Error::from(err.context(ErrorKind::SecretStore))),
+        }
+    }
+
+    fn write(&self, contents: &Contents) -> Result<()> {
+        // Write to a temporary file first so that a crash can't leave a truncated store.
+        let temp_path = self.path.with_extension("tmp");
+        let contents = serde_json::to_vec(contents).context(ErrorKind::SecretStore)?;
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(0o600)
+            .open(&temp_path)
+            .context(ErrorKind::SecretStore)?;
+        file.write_all(&contents).context(ErrorKind::SecretStore)?;
+        file.sync_all().context(ErrorKind::SecretStore)?;
+        fs::rename(&temp_path, &self.path).context(ErrorKind::SecretStore)?;
+        Ok(())
+    }
+}
+
+impl KeyServiceCipher {
+    fn master_encryption_key(&self) -> Result<KeyHandle> {
+        self.0
+            .create_key_if_not_exists(
+                MASTER_ENCRYPTION_KEY_ID,
+                aziot_key_common::CreateKeyValue::Generate,
+                &[aziot_key_common::KeyUsage::Encrypt],
+            )
+            .map_err(|_| Error::from(ErrorKind::LoadMasterEncKey))
+    }
+}
+
+impl SecretCipher for KeyServiceCipher {
+    fn encrypt(&self, iv: &[u8], aad: &[u8], plaintext: &[u8]) -> Result<Vec<u8>> {
+        self.0
+            .encrypt(
+                &self.master_encryption_key()?,
+                EncryptMechanism::Aead {
+                    iv: iv.to_vec(),
+                    aad: aad.to_vec(),
+                },
+                plaintext,
+            )
+            .map_err(|err| Error::from(err.context(ErrorKind::SecretStore)))
+    }
+
+    fn decrypt(&self, iv: &[u8], aad: &[u8], ciphertext: &[u8]) -> Result<Vec<u8>> {
+        self.0
+            .decrypt(
+                &self.master_encryption_key()?,
+                EncryptMechanism::Aead {
+                    iv: iv.to_vec(),
+                    aad: aad.to_vec(),
+                },
+                ciphertext,
+            )
+            .map_err(|err| Error::from(err.context(ErrorKind::SecretStore)))
+    }
+}
+
+fn check_key(key: &str) -> Result<()> {
+    let valid = !key.is_empty()
+        && key.len() <= MAX_KEY_LENGTH
+        && !key.starts_with('.')
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-' || c == '_');
+    if valid {
+        Ok(())
+    } else {
+        Err(ErrorKind::MalformedRequestParameter("key").into())
+    }
+}
+
+fn aad(module_id: &str, key: &str) -> Vec<u8> {
+    format!("module-secret/{}/{}", module_id, key).into_bytes()
+}
+
+fn seed_hash(module_id: &str, key: &str, value: &str) -> String {
+    let mut hasher = openssl::sha::Sha256::new();
+    hasher.update(&aad(module_id, key));
+    hasher.update(&[0]);
+    hasher.update(value.as_bytes());
+    base64::encode(&hasher.finish())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::collections::BTreeMap;
+    use std::path::Path;
+    use std::sync::Arc;
+
+    use failure::ResultExt;
+    use openssl::symm::{decrypt_aead, encrypt_aead, Cipher};
+
+    use super::{SecretCipher, SecretStore};
+    use crate::error::{ErrorKind, Result};
+
+    const TAG_SIZE: usize = 16;
+
+    /// AES-GCM with a fixed key in place of keyd's master encryption key.
+    struct TestCipher;
+
+    impl SecretCipher for TestCipher {
+        fn encrypt(&self, iv: &[u8], aad: &[u8], plaintext: &[u8]) -> Result<Vec<u8>> {
+            let mut tag = [0_u8; TAG_SIZE];
+            let mut ciphertext = encrypt_aead(
+                Cipher::aes_256_gcm(),
+                &[1; 32],
+                Some(iv),
+                aad,
+                plaintext,
+                &mut tag,
+            )
+            .context(ErrorKind::SecretStore)?;
+            ciphertext.extend_from_slice(&tag);
+            Ok(ciphertext)
+        }
+
+        fn decrypt(&self, iv: &[u8], aad: &[u8], ciphertext: &[u8]) -> Result<Vec<u8>> {
+            let (ciphertext, tag) = ciphertext.split_at(ciphertext.len() - TAG_SIZE);
+            let plaintext = decrypt_aead(
+                Cipher::aes_256_gcm(),
+                &[1; 32],
+                Some(iv),
+                aad,
+                ciphertext,
+                tag,
+            )
+            .context(ErrorKind::SecretStore)?;
+            Ok(plaintext)
+        }
+    }
+
+    pub(crate) fn test_secret_store(dir: &Path) -> SecretStore {
+        SecretStore::with_cipher(dir.join("secrets.json"), Arc::new(TestCipher))
+    }
+
+    fn seeds(
+        module_id: &str,
+        key: &str,
+        value: &str,
+    ) -> BTreeMap<String, BTreeMap<String, String>> {
+        let mut module_seeds = BTreeMap::new();
+        module_seeds.insert(key.to_string(), value.to_string());
+        let mut seeds = BTreeMap::new();
+        seeds.insert(module_id.to_string(), module_seeds);
+        seeds
+    }
+
+    #[test]
+    fn set_get_delete() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = test_secret_store(dir.path());
+
+        assert_eq!(None, store.get("m1", "k1").unwrap());
+
+        store.set("m1", "k1", b"v1").unwrap();
+        assert_eq!(Some(b"v1".to_vec()), store.get("m1", "k1").unwrap());
+
+        store.set("m1", "k1", b"v2").unwrap();
+        assert_eq!(Some(b"v2".to_vec()), store.get("m1", "k1").unwrap());
+
+        assert!(store.delete("m1", "k1").unwrap());
+        assert_eq!(None, store.get("m1", "k1").unwrap());
+        assert!(!store.delete("m1", "k1").unwrap());
+    }
+
+    #[test]
+    fn values_are_stored_encrypted() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = test_secret_store(dir.path());
+
+        store.set("m1", "k1", b"plaintext-value").unwrap();
+
+        let contents = std::fs::read_to_string(dir.path().join("secrets.json")).unwrap();
+        assert!(!contents.contains("plaintext-value"));
+        assert!(!contents.contains(&base64::encode(b"plaintext-value")));
+    }
+
+    #[test]
+    fn secrets_are_scoped_to_their_module() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = test_secret_store(dir.path());
+
+        store.set("m1", "k1", b"v1").unwrap();
+        assert_eq!(None, store.get("m2", "k1").unwrap());
+        assert!(!store.delete("m2", "k1").unwrap());
+        assert_eq!(Some(b"v1".to_vec()), store.get("m1", "k1").unwrap());
+    }
+
+    #[test]
+    fn secrets_are_bound_to_their_module_and_key() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("secrets.json");
+        let store = test_secret_store(dir.path());
+
+        store.set("m1", "k1", b"v1").unwrap();
+        let contents = std::fs::read_to_string(&path).unwrap();
+
+        // Move the stored secret to another key, then to another module.
+        std::fs::write(&path, contents.replace("\"k1\"", "\"k2\"")).unwrap();
+        assert!(store.get("m1", "k2").is_err());
+
+        std::fs::write(&path, contents.replace("\"m1\"", "\"m2\"")).unwrap();
+        assert!(store.get("m2", "k1").is_err());
+    }
+
+    #[test]
+    fn invalid_keys_are_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = test_secret_store(dir.path());
+
+        let long_key = "k".repeat(257);
+        for key in &["", ".hidden", "a/b", "a b", long_key.as_str()] {
+            assert!(store.set("m1", key, b"v1").is_err(), "{:?}", key);
+            assert!(store.get("m1", key).is_err(), "{:?}", key);
+            assert!(store.delete("m1", key).is_err(), "{:?}", key);
+        }
+    }
+
+    #[test]
+    fn oversized_values_are_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = test_secret_store(dir.path());
+
+        assert!(store.set("m1", "k1", &vec![0_u8; 64 * 1024 + 1]).is_err());
+        store.set("m1", "k1", &vec![0_u8; 64 * 1024]).unwrap();
+    }
+
+    #[test]
+    fn seed_stores_configured_secrets() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = test_secret_store(dir.path());
+
+        store.seed(&seeds("m1", "k1", "v1")).unwrap();
+        assert_eq!(Some(b"v1".to_vec()), store.get("m1", "k1").unwrap());
+    }
+
+    #[test]
+    fn seed_keeps_values_changed_by_the_module() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = test_secret_store(dir.path());
+
+        store.seed(&seeds("m1", "k1", "v1")).unwrap();
+        store.set("m1", "k1", b"changed").unwrap();
+        store.seed(&seeds("m1", "k1", "v1")).unwrap();
+        assert_eq!(Some(b"changed".to_vec()), store.get("m1", "k1").unwrap());
+    }
+
+    #[test]
+    fn seed_does_not_restore_deleted_secrets() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = test_secret_store(dir.path());
+
+        store.seed(&seeds("m1", "k1", "v1")).unwrap();
+        assert!(store.delete("m1", "k1").unwrap());
+        store.seed(&seeds("m1", "k1", "v1")).unwrap();
+        assert_eq!(None, store.get("m1", "k1").unwrap());
+    }
+
+    #[test]
+    fn seed_stores_rotated_values() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = test_secret_store(dir.path());
+
+        store.seed(&seeds("m1", "k1", "v1")).unwrap();
+        store.set("m1", "k1", b"changed").unwrap();
+        store.seed(&seeds("m1", "k1", "v2")).unwrap();
+        assert_eq!(Some(b"v2".to_vec()), store.get("m1", "k1").unwrap());
+    }
+
+    #[test]
+    fn seed_stores_values_configured_again() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = test_secret_store(dir.path());
+
+        store.seed(&seeds("m1", "k1", "v1")).unwrap();
+        assert!(store.delete("m1", "k1").unwrap());
+        store.seed(&BTreeMap::new()).unwrap();
+        store.seed(&seeds("m1", "k1", "v1")).unwrap();
+        assert_eq!(Some(b"v1".to_vec()), store.get("m1", "k1").unwrap());
+    }
+
+    #[test]
+    fn seed_ignores_invalid_keys() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = test_secret_store(dir.path());
+
+        store.seed(&seeds("m1", "a/b", "v1")).unwrap();
+        store.seed(&seeds("m1", "k1", "v1")).unwrap();
+        assert_eq!(Some(b"v1".to_vec()), store.get("m1", "k1").unwrap());
+    }
+}
@@ -0,0 +1,443 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use std::cmp;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use failure::{Fail, ResultExt};
+use futures::future::{self, Either, Loop};
+use futures::{Future, IntoFuture};
+use hyper::header::{CONTENT_LENGTH, CONTENT_TYPE};
+use hyper::{Body, Request, Response, StatusCode};
+use serde_derive::{Deserialize, Serialize};
+use tokio::timer::Delay;
+use url::form_urlencoded;
+
+use cert_client::client::CertificateClient;
+use edgelet_core::WorkloadConfig;
+use edgelet_http::route::{Handler, Parameters};
+use edgelet_http::Error as HttpError;
+use workload::models::{CertificateEvent, CertificateEventsResponse};
+
+use super::parse_openssl_time;
+use crate::error::{CertOperation, Error, ErrorKind, Result};
+use crate::IntoResponse;
+
+const DEFAULT_TIMEOUT_SECS: u64 = 300;
+const MAX_TIMEOUT_SECS: u64 = 3600;
+
+/// How often certd is asked for the current certificates while a request is waiting.
+const POLL_INTERVAL: Duration = Duration::from_secs(10);
+
+/// A certificate is reported as nearing expiry once less than this fraction of its
+/// lifetime remains, i.e. a cert valid for 90 days is reported 18 days before it expires.
+const EXPIRY_THRESHOLD_DIVISOR: i32 = 5;
+
+/// Waits until a module's certificates or the trust bundles change, so that modules
+/// don't have to poll `/trust-bundle` and re-request certs on their own schedule.
+///
+/// The response carries an opaque token describing what the module has been told about.
+/// Passing it back as `since` waits for anything that happened after that; omitting it
+/// returns the current state immediately.
+pub struct CertificateEventsHandler<W: WorkloadConfig> {
+    cert_client: Arc<Mutex<CertificateClient>>,
+    config: W,
+}
+
+impl<W: WorkloadConfig> CertificateEventsHandler<W> {
+    pub fn new(cert_client: Arc<Mutex<CertificateClient>>, config: W) -> Self {
+        CertificateEventsHandler {
+            cert_client,
+            config,
+        }
+    }
+}
+
+impl<W> Handler<Parameters> for CertificateEventsHandler<W>
+where
+    W: WorkloadConfig + Clone + Send + Sync + 'static,
+{
+    fn handle(
+        &self,
+        req: Request<Body>,
+        params: Parameters,
+    ) -> Box<dyn Future<Item = Response<Body>, Error = HttpError> + Send> {
+        let cert_client = self.cert_client.clone();
+        let config = &self.config;
+
+        let response = params
+            .name("name")
+            .ok_or_else(|| Error::from(ErrorKind::MissingRequiredParameter("name")))
+            .and_then(|module_id| {
+                let gen_id = params
+                    .name("genid")
+                    .ok_or_else(|| Error::from(ErrorKind::MissingRequiredParameter("genid")))?;
+                let aliases = CertAliases {
+                    trust_bundle: config.trust_bundle_cert().to_string(),
+                    manifest_trust_bundle: config.manifest_trust_bundle_cert().to_string(),
+                    identity: format!("aziot-edged/module/{}:identity", module_id),
+                    server: format!("aziot-edged/module/{}:{}:server", module_id, gen_id),
+                };
+                let (since, timeout) = parse_options(req.uri().query().unwrap_or(""))?;
+                Ok((aliases, since, timeout))
+            })
+            .into_future()
+            .and_then(move |(aliases, since, timeout)| {
+                let deadline = Instant::now() + timeout;
+                let aliases = Arc::new(aliases);
+
+                future::loop_fn(since, move |since| {
+                    get_state(&cert_client, &aliases).and_then(move |state| {
+                        let events = state.events_since(since.as_ref());
+                        if since.is_none() || !events.is_empty() || Instant::now() >= deadline {
+                            return Either::A(future::ok(Loop::Break((state, events))));
+                        }
+
+                        let next_poll = cmp::min(Instant::now() + POLL_INTERVAL, deadline);
+                        Either::B(
+                            Delay::new(next_poll)
+                                .map_err(|err| {
+                                    Error::from(err.context(ErrorKind::CertOperation(
+                                        CertOperation::WatchCerts,
+                                    )))
+                                })
+                                .map(move |()| Loop::Continue(since)),
+                        )
+                    })
+                })
+            })
+            .and_then(|(state, events)| -> Result<_> {
+                let body = serde_json::to_string(&CertificateEventsResponse::new(
+                    state.to_token()?,
+                    events,
+                ))
+                .context(ErrorKind::CertOperation(CertOperation::WatchCerts))?;
+                let response = Response::builder()
+                    .status(StatusCode::OK)
+                    .header(CONTENT_TYPE, "application/json")
+                    .header(CONTENT_LENGTH, body.len().to_string().as_str())
+                    .body(body.into())
+                    .context(ErrorKind::CertOperation(CertOperation::WatchCerts))?;
+                Ok(response)
+            })
+            .or_else(|e| Ok(e.into_response()));
+
+        Box::new(response)
+    }
+}
+
+struct CertAliases {
+    trust_bundle: String,
+    manifest_trust_bundle: String,
+    identity: String,
+    server: String,
+}
+
+fn parse_options(query: &str) -> Result<(Option<WatchState>, Duration)> {
+    let mut since = None;
+    let mut timeout = Duration::from_secs(DEFAULT_TIMEOUT_SECS);
+
+    for (key, value) in form_urlencoded::parse(query.as_bytes()) {
+        match &*key {
+            "since" => since = Some(WatchState::from_token(&value)?),
+            "timeout" => {
+                let secs = value
+                    .parse::<u64>()
+                    .context(ErrorKind::MalformedRequestParameter("timeout"))?;
+                if secs == 0 || secs > MAX_TIMEOUT_SECS {
+                    return Err(ErrorKind::MalformedRequestParameter("timeout").into());
+                }
+                timeout = Duration::from_secs(secs);
+            }
+            _ => (),
+        }
+    }
+
+    Ok((since, timeout))
+}
+
+/// What a module has been told about its certificates. Trust bundles are compared by
+/// digest; module certs by their expiry, which changes whenever the module renews them.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+struct WatchState {
+    trust_bundle: String,
+    manifest_trust_bundle: String,
+    identity_cert: Option<CertState>,
+    server_cert: Option<CertState>,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+struct CertState {
+    not_after: DateTime<Utc>,
+    expiring: bool,
+}
+
+impl WatchState {
+    fn from_token(token: &str) -> Result<Self> {
+        let state = base64::decode_config(token, base64::URL_SAFE_NO_PAD)
+            .context(ErrorKind::MalformedRequestParameter("since"))?;
+        let state = serde_json::from_slice(&state)
+            .context(ErrorKind::MalformedRequestParameter("since"))?;
+        Ok(state)
+    }
+
+    fn to_token(&self) -> Result<String> {
+        let state = serde_json::to_vec(self)
+            .context(ErrorKind::CertOperation(CertOperation::WatchCerts))?;
+        Ok(base64::encode_config(&state, base64::URL_SAFE_NO_PAD))
+    }
+
+    fn events_since(&self, since: Option<&WatchState>) -> Vec<CertificateEvent> {
+        let mut events = vec![];
+
+        if let Some(since) = since {
+            if self.trust_bundle != since.trust_bundle {
+                events.push(CertificateEvent::new("trustBundleChanged".to_string()));
+            }
+            if self.manifest_trust_bundle != since.manifest_trust_bundle {
+                events.push(CertificateEvent::new(
+                    "manifestTrustBundleChanged".to_string(),
+                ));
+            }
+        }
+
+        let certs = [
+            (
+                "identityCertExpiring",
+                self.identity_cert.as_ref(),
+                since.and_then(|since| since.identity_cert.as_ref()),
+            ),
+            (
+                "serverCertExpiring",
+                self.server_cert.as_ref(),
+                since.and_then(|since| since.server_cert.as_ref()),
+            ),
+        ];
+        for (kind, current, previous) in &certs {
+            if let Some(current) = current {
+                // Report each cert once, unless the module was told about it before it
+                // started nearing expiry.
+                let reported = previous.map_or(false, |previous| {
+                    previous.not_after == current.not_after && previous.expiring
+                });
+                if current.expiring && !reported {
+                    events.push(
+                        CertificateEvent::new((*kind).to_string())
+                            .with_expiration(current.not_after.to_rfc3339()),
+                    );
+                }
+            }
+        }
+
+        events
+    }
+}
+
+fn get_state(
+    cert_client: &Arc<Mutex<CertificateClient>>,
+    aliases: &CertAliases,
+) -> impl Future<Item = WatchState, Error = Error> + Send {
+    let get_cert = |alias: &str| {
+        cert_client
+            .lock()
+            .expect("cert client lock failed")
+            .get_cert(alias)
+    };
+
+    let trust_bundle = get_cert(&aliases.trust_bundle).map_err(|err| {
+        Error::from(err.context(ErrorKind::CertOperation(CertOperation::WatchCerts)))
+    });
+    // Module certs and the manifest trust bundle don't exist until they're first created.
+    let manifest_trust_bundle = get_cert(&aliases.manifest_trust_bundle)
+        .then(|cert| Ok::<_, Error>(cert.unwrap_or_default()));
+    let identity_cert = get_cert(&aliases.identity).then(|cert| Ok(cert.ok()));
+    let server_cert = get_cert(&aliases.server).then(|cert| Ok(cert.ok()));
+
+    trust_bundle
+        .join4(manifest_trust_bundle, identity_cert, server_cert)
+        .and_then(
+            |(trust_bundle, manifest_trust_bundle, identity_cert, server_cert)| {
+                Ok(WatchState {
+                    trust_bundle: digest(&trust_bundle),
+                    manifest_trust_bundle: digest(&manifest_trust_bundle),
+                    identity_cert: identity_cert.map(|cert| cert_state(&cert)).transpose()?,
+                    server_cert: server_cert.map(|cert| cert_state(&cert)).transpose()?,
+                })
+            },
+        )
+}
+
+fn digest(pem: &[u8]) -> String {
+    base64::encode_config(&openssl::sha::sha256(pem), base64::URL_SAFE_NO_PAD)
+}
+
+fn cert_state(pem: &[u8]) -> Result<CertState> {
+    let cert = openssl::x509::X509::from_pem(pem)
+        .context(ErrorKind::CertOperation(CertOperation::WatchCerts))?;
+    let not_before = parse_openssl_time(cert.not_before())
+        .context(ErrorKind::CertOperation(CertOperation::WatchCerts))?;
+    let not_after = parse_openssl_time(cert.not_after())
+        .context(ErrorKind::CertOperation(CertOperation::WatchCerts))?;
+
+    Ok(CertState {
+        not_after,
+        expiring: is_expiring(not_before, not_after, Utc::now()),
+    })
+}
+
+fn is_expiring(not_before: DateTime<Utc>, not_after: DateTime<Utc>, now: DateTime<Utc>) -> bool {
+    let threshold = (not_after - not_before) / EXPIRY_THRESHOLD_DIVISOR;
+    now >= not_after - threshold
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use chrono::{DateTime, TimeZone, Utc};
+
+    use super::{cert_state, is_expiring, parse_options, CertState, WatchState};
+    use crate::error::ErrorKind;
+
+    fn time(day: u32) -> DateTime<Utc> {
+        Utc.ymd(2021, 1, day).and_hms(0, 0, 0)
+    }
+
+    fn state(trust_bundle: &str, server_cert: Option<CertState>) -> WatchState {
+        WatchState {
+            trust_bundle: trust_bundle.to_string(),
+            manifest_trust_bundle: "manifest".to_string(),
+            identity_cert: None,
+            server_cert,
+        }
+    }
+
+    fn cert(day: u32, expiring: bool) -> Option<CertState> {
+        Some(CertState {
+            not_after: time(day),
+            expiring,
+        })
+    }
+
+    fn kinds(events: &[workload::models::CertificateEvent]) -> Vec<&str> {
+        events.iter().map(|event| event.kind().as_str()).collect()
+    }
+
+    #[test]
+    fn events_since_reports_trust_bundle_changes() {
+        let since = state("a", None);
+        assert!(state("a", None).events_since(Some(&since)).is_empty());
+        assert_eq!(
+            vec!["trustBundleChanged"],
+            kinds(&state("b", None).events_since(Some(&since)))
+        );
+
+        let mut current = state("a", None);
+        current.manifest_trust_bundle = "changed".to_string();
+        assert_eq!(
+            vec!["manifestTrustBundleChanged"],
+            kinds(&current.events_since(Some(&since)))
+        );
+
+        // Without a previous state there is nothing for the trust bundles to change from.
+        assert!(state("b", None).events_since(None).is_empty());
+    }
+
+    #[test]
+    fn events_since_reports_expiring_certs_once() {
+        let current = state("a", cert(20, true));
+        let events = current.events_since(None);
+        assert_eq!(vec!["serverCertExpiring"], kinds(&events));
+        assert_eq!(Some(time(20).to_rfc3339().as_str()), events[0].expiration());
+
+        // Already reported.
+        assert!(current.events_since(Some(&current)).is_empty());
+
+        // The module was told about the cert before it started nearing expiry.
+        assert_eq!(
+            vec!["serverCertExpiring"],
+            kinds(&current.events_since(Some(&state("a", cert(20, false)))))
+        );
+
+        // A renewed cert that isn't nearing expiry yet.
+        assert!(state("a", cert(28, false))
+            .events_since(Some(&current))
+            .is_empty());
+    }
+
+    #[test]
+    fn token_round_trips() {
+        let state = state("a", cert(20, true));
+        let token = state.to_token().unwrap();
+        assert_eq!(state, WatchState::from_token(&token).unwrap());
+
+        let err = WatchState::from_token("not a token").unwrap_err();
+        assert!(matches!(
+            err.kind(),
+            ErrorKind::MalformedRequestParameter("since")
+        ));
+    }
+
+    #[test]
+    fn parse_options_reads_since_and_timeout() {
+        let (since, timeout) = parse_options("").unwrap();
+        assert_eq!(None, since);
+        assert_eq!(Duration::from_secs(300), timeout);
+
+        let state = state("a", None);
+        let query = format!("since={}&timeout=30", state.to_token().unwrap());
+        let (since, timeout) = parse_options(&query).unwrap();
+        assert_eq!(Some(state), since);
+        assert_eq!(Duration::from_secs(30), timeout);
+
+        for query in &["timeout=0", "timeout=3601", "timeout=soon"] {
+            let err = parse_options(query).unwrap_err();
+            assert!(matches!(
+                err.kind(),
+                ErrorKind::MalformedRequestParameter("timeout")
+            ));
+        }
+        assert!(parse_options("since=not-a-token").is_err());
+    }
+
+    #[test]
+    fn certs_are_expiring_in_last_fifth_of_lifetime() {
+        // Valid for 10 days, so expiring for the last 2.
+        assert!(!is_expiring(time(1), time(11), time(2)));
+        assert!(!is_expiring(
+            time(1),
+            time(11),
+            time(9) - chrono::Duration::seconds(1)
+        ));
+        assert!(is_expiring(time(1), time(11), time(9)));
+        assert!(is_expiring(time(1), time(11), time(12)));
+    }
+
+    #[test]
+    fn cert_state_reads_cert_validity() {
+        let key =
+            openssl::pkey::PKey::from_rsa(openssl::rsa::Rsa::generate(2048).unwrap()).unwrap();
+        let mut name = openssl::x509::X509Name::builder().unwrap();
+        name.append_entry_by_text("CN", "test").unwrap();
+        let name = name.build();
+
+        let mut cert = openssl::x509::X509::builder().unwrap();
+        cert.set_subject_name(&name).unwrap();
+        cert.set_issuer_name(&name).unwrap();
+        cert.set_pubkey(&key).unwrap();
+        cert.set_not_before(&openssl::asn1::Asn1Time::days_from_now(0).unwrap())
+            .unwrap();
+        cert.set_not_after(&openssl::asn1::Asn1Time::days_from_now(1).unwrap())
+            .unwrap();
+        cert.sign(&key, openssl::hash::MessageDigest::sha256())
+            .unwrap();
+        let pem = cert.build().to_pem().unwrap();
+
+        let state = cert_state(&pem).unwrap();
+        assert!(!state.expiring);
+        assert!(state.not_after > Utc::now());
+
+        assert!(cert_state(b"not a cert").is_err());
+    }
+}
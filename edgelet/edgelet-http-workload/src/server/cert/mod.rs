@@ -24,9 +24,11 @@ use crate::{
     IntoResponse,
 };
 
+mod events;
 mod identity;
 mod server;
 
+pub use self::events::CertificateEventsHandler;
 pub use self::identity::IdentityCertHandler;
 pub use self::server::ServerCertHandler;
 
@@ -83,12 +85,13 @@ fn refresh_cert(
     edge_ca: EdgeCaCertificate,
     context: ErrorKind,
 ) -> Box<dyn Future<Item = Response<Body>, Error = HttpError> + Send> {
+    let new_cert_props = new_cert_props.clone();
     let response = generate_local_keypair()
         .map({
             let context = context.clone();
             |(privkey, pubkey)| {
-                create_csr(new_cert_props, &privkey, &pubkey)
-                    .map(|csr| (privkey, csr))
+                create_csr(&new_cert_props, &privkey, &pubkey)
+                    .map(|csr| (privkey, pubkey, csr))
                     .map_err(|e| Error::from(e.context(context)))
             }
         })
@@ -98,7 +101,7 @@ fn refresh_cert(
         })
         .into_future()
         .flatten()
-        .and_then(move |(new_cert_privkey, new_cert_csr)| {
+        .and_then(move |(new_cert_privkey, new_cert_pubkey, new_cert_csr)| {
             prepare_edge_ca(
                 key_client.clone(),
                 cert_client.clone(),
@@ -114,38 +117,56 @@ fn refresh_cert(
                     .load_key_pair(edge_ca.key_id.as_str())
                     .map_err(|e| Error::from(e.context(context.clone())))
                     .and_then(move |aziot_edged_ca_key_pair_handle| -> Result<_> {
-                        let response = cert_client
-                            .lock()
-                            .expect("certificate client lock error")
-                            .create_cert(
-                                &alias,
-                                &new_cert_csr,
-                                Some((edge_ca.cert_id.as_str(), &aziot_edged_ca_key_pair_handle)),
-                            )
-                            .map_err({
-                                let context = context.clone();
-                                |e| Error::from(e.context(context))
-                            })
-                            .and_then(move |cert| {
-                                let pk = new_cert_privkey
-                                    .private_key_to_pem_pkcs8()
-                                    .context(context.clone())?;
-                                let cert = Certificate::new(cert, pk);
-                                let cert = cert_to_response(&cert, context.clone())?;
-                                let body = match serde_json::to_string(&cert) {
-                                    Ok(body) => body,
-                                    Err(err) => return Err(Error::from(err.context(context))),
-                                };
-
-                                let response = Response::builder()
-                                    .status(StatusCode::CREATED)
-                                    .header(CONTENT_TYPE, "application/json")
-                                    .header(CONTENT_LENGTH, body.len().to_string().as_str())
-                                    .body(body.into())
-                                    .context(context)?;
-
-                                Ok(response)
-                            });
+                        let cert = match new_cert_props.validity_in_secs() {
+                            None => future::Either::A(
+                                cert_client
+                                    .lock()
+                                    .expect("certificate client lock error")
+                                    .create_cert(
+                                        &alias,
+                                        &new_cert_csr,
+                                        Some((
+                                            edge_ca.cert_id.as_str(),
+                                            &aziot_edged_ca_key_pair_handle,
+                                        )),
+                                    )
+                                    .map_err({
+                                        let context = context.clone();
+                                        |e| Error::from(e.context(context))
+                                    }),
+                            ),
+                            Some(validity_in_secs) => future::Either::B(issue_cert(
+                                key_client,
+                                cert_client,
+                                alias,
+                                new_cert_props,
+                                new_cert_pubkey,
+                                validity_in_secs,
+                                edge_ca,
+                                aziot_edged_ca_key_pair_handle,
+                                context.clone(),
+                            )),
+                        };
+                        let response = cert.and_then(move |cert| {
+                            let pk = new_cert_privkey
+                                .private_key_to_pem_pkcs8()
+                                .context(context.clone())?;
+                            let cert = Certificate::new(cert, pk);
+                            let cert = cert_to_response(&cert, context.clone())?;
+                            let body = match serde_json::to_string(&cert) {
+                                Ok(body) => body,
+                                Err(err) => return Err(Error::from(err.context(context))),
+                            };
+
+                            let response = Response::builder()
+                                .status(StatusCode::CREATED)
+                                .header(CONTENT_TYPE, "application/json")
+                                .header(CONTENT_LENGTH, body.len().to_string().as_str())
+                                .body(body.into())
+                                .context(context)?;
+
+                            Ok(response)
+                        });
                         Ok(response)
                     })
             })
@@ -160,6 +181,143 @@ fn refresh_cert(
     Box::new(response)
 }
 
+/// Signs a certificate for `props` with the Edge CA key and imports it into certd as `alias`.
+///
+/// certd's create API has no way to request a validity, so this is only used for modules whose
+/// server certificate policy sets a maximum validity. The certificate never outlives the Edge CA
+/// certificate.
+#[allow(clippy::too_many_arguments)]
+fn issue_cert(
+    key_client: Arc<aziot_key_client::Client>,
+    cert_client: Arc<Mutex<CertificateClient>>,
+    alias: String,
+    props: CertificateProperties,
+    public_key: openssl::pkey::PKey<openssl::pkey::Public>,
+    validity_in_secs: i64,
+    edge_ca: EdgeCaCertificate,
+    edge_ca_key_pair_handle: aziot_key_common::KeyHandle,
+    context: ErrorKind,
+) -> impl Future<Item = Vec<u8>, Error = Error> + Send {
+    cert_client
+        .lock()
+        .expect("certificate client lock error")
+        .get_cert(&edge_ca.cert_id)
+        .map_err({
+            let context = context.clone();
+            |e| Error::from(e.context(context))
+        })
+        .and_then(move |edge_ca_cert_pem| -> Result<_> {
+            let edge_ca_cert = openssl::x509::X509::from_pem(&edge_ca_cert_pem)
+                .map_err(|e| Error::from(e.context(context.clone())))?;
+            let mut key_engine = create_key_engine(key_client)
+                .map_err(|e| Error::from(e.context(context.clone())))?;
+            let (edge_ca_private_key, _) =
+                load_keypair(&edge_ca_key_pair_handle, &mut key_engine, &context)?;
+
+            let mut cert = sign_cert(
+                &props,
+                &public_key,
+                validity_in_secs,
+                &edge_ca_cert,
+                &edge_ca_private_key,
+            )
+            .map_err(|e| Error::from(e.context(context.clone())))?;
+            cert.extend_from_slice(&edge_ca_cert_pem);
+
+            Ok(cert_client
+                .lock()
+                .expect("certificate client lock error")
+                .import_cert(&alias, &cert)
+                .map_err(|e| Error::from(e.context(context))))
+        })
+        .flatten()
+}
+
+fn sign_cert(
+    props: &CertificateProperties,
+    public_key: &openssl::pkey::PKeyRef<openssl::pkey::Public>,
+    validity_in_secs: i64,
+    issuer_cert: &openssl::x509::X509Ref,
+    issuer_private_key: &openssl::pkey::PKeyRef<openssl::pkey::Private>,
+) -> std::result::Result<Vec<u8>, ErrorStack> {
+    let mut cert = openssl::x509::X509::builder()?;
+
+    cert.set_version(2)?;
+
+    // Setting the top bit keeps the serial number positive and non-zero.
+    let mut serial_number = openssl::bn::BigNum::new()?;
+    serial_number.rand(128, openssl::bn::MsbOption::ONE, false)?;
+    cert.set_serial_number(&serial_number.to_asn1_integer()?)?;
+
+    let mut subject_name = openssl::x509::X509Name::builder()?;
+    subject_name.append_entry_by_text("CN", props.common_name())?;
+    let subject_name = subject_name.build();
+    cert.set_subject_name(&subject_name)?;
+    cert.set_issuer_name(issuer_cert.subject_name())?;
+
+    cert.set_pubkey(public_key)?;
+
+    let not_before = openssl::asn1::Asn1Time::days_from_now(0)?;
+    cert.set_not_before(&not_before)?;
+    let not_after = (Utc::now() + chrono::Duration::seconds(validity_in_secs))
+        .format("%Y%m%d%H%M%SZ")
+        .to_string();
+    let not_after = openssl::asn1::Asn1Time::from_str(&not_after)?;
+    if not_after < issuer_cert.not_after() {
+        cert.set_not_after(&not_after)?;
+    } else {
+        cert.set_not_after(issuer_cert.not_after())?;
+    }
+
+    cert.append_extension(
+        openssl::x509::extension::BasicConstraints::new()
+            .critical()
+            .build()?,
+    )?;
+    cert.append_extension(
+        openssl::x509::extension::KeyUsage::new()
+            .critical()
+            .digital_signature()
+            .key_encipherment()
+            .build()?,
+    )?;
+
+    let mut extended_key_usage = openssl::x509::extension::ExtendedKeyUsage::new();
+    match *props.certificate_type() {
+        edgelet_core::CertificateType::Client => {
+            cert.append_extension(extended_key_usage.client_auth().build()?)?;
+        }
+        edgelet_core::CertificateType::Server => {
+            cert.append_extension(extended_key_usage.server_auth().build()?)?;
+        }
+        edgelet_core::CertificateType::Ca | edgelet_core::CertificateType::Unknown => {}
+    }
+
+    let subject_key_identifier = openssl::x509::extension::SubjectKeyIdentifier::new()
+        .build(&cert.x509v3_context(Some(issuer_cert), None))?;
+    cert.append_extension(subject_key_identifier)?;
+    let authority_key_identifier = openssl::x509::extension::AuthorityKeyIdentifier::new()
+        .keyid(false)
+        .build(&cert.x509v3_context(Some(issuer_cert), None))?;
+    cert.append_extension(authority_key_identifier)?;
+
+    if props.dns_san_entries().is_some() || props.ip_entries().is_some() {
+        let mut subject_alt_name = openssl::x509::extension::SubjectAlternativeName::new();
+        props.dns_san_entries().into_iter().flatten().for_each(|s| {
+            subject_alt_name.dns(s);
+        });
+        props.ip_entries().into_iter().flatten().for_each(|s| {
+            subject_alt_name.ip(s);
+        });
+        let san = subject_alt_name.build(&cert.x509v3_context(Some(issuer_cert), None))?;
+        cert.append_extension(san)?;
+    }
+
+    cert.sign(issuer_private_key, openssl::hash::MessageDigest::sha256())?;
+
+    cert.build().to_pem()
+}
+
 fn generate_local_keypair() -> std::result::Result<
     (
         openssl::pkey::PKey<openssl::pkey::Private>,
@@ -383,6 +541,18 @@ fn create_edge_ca_certificate(
     Box::new(fut)
 }
 
+pub(crate) fn parse_openssl_time(
+    time: &openssl::asn1::Asn1TimeRef,
+) -> chrono::ParseResult<chrono::DateTime<chrono::Utc>> {
+    // openssl::asn1::Asn1TimeRef does not expose any way to convert the ASN1_TIME to a Rust-friendly type
+    //
+    // Its Display impl uses ASN1_TIME_print, so we convert it into a String and parse it back
+    // into a chrono::DateTime<chrono::Utc>
+    let time = time.to_string();
+    let time = chrono::NaiveDateTime::parse_from_str(&time, "%b %e %H:%M:%S %Y GMT")?;
+    Ok(chrono::DateTime::<chrono::Utc>::from_utc(time, chrono::Utc))
+}
+
 #[derive(Debug)]
 pub struct Certificate {
     pem: Vec<u8>,
@@ -412,18 +582,6 @@ impl CoreCertificate for Certificate {
     }
 
     fn get_valid_to(&self) -> std::result::Result<DateTime<Utc>, edgelet_core::Error> {
-        fn parse_openssl_time(
-            time: &openssl::asn1::Asn1TimeRef,
-        ) -> chrono::ParseResult<chrono::DateTime<chrono::Utc>> {
-            // openssl::asn1::Asn1TimeRef does not expose any way to convert the ASN1_TIME to a Rust-friendly type
-            //
-            // Its Display impl uses ASN1_TIME_print, so we convert it into a String and parse it back
-            // into a chrono::DateTime<chrono::Utc>
-            let time = time.to_string();
-            let time = chrono::NaiveDateTime::parse_from_str(&time, "%b %e %H:%M:%S %Y GMT")?;
-            Ok(chrono::DateTime::<chrono::Utc>::from_utc(time, chrono::Utc))
-        }
-
         let cert = openssl::x509::X509::from_pem(&self.pem)
             .map_err(|_| edgelet_core::Error::from(edgelet_core::ErrorKind::CertificateCreate))?;
         let not_after = parse_openssl_time(cert.not_after())
@@ -435,3 +593,73 @@ impl CoreCertificate for Certificate {
         unimplemented!()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use edgelet_core::{CertificateProperties, CertificateType};
+
+    use super::{generate_local_keypair, sign_cert};
+
+    fn issuer() -> (
+        openssl::x509::X509,
+        openssl::pkey::PKey<openssl::pkey::Private>,
+    ) {
+        let (private_key, public_key) = generate_local_keypair().unwrap();
+
+        let mut name = openssl::x509::X509Name::builder().unwrap();
+        name.append_entry_by_text("CN", "edge ca").unwrap();
+        let name = name.build();
+
+        let mut cert = openssl::x509::X509::builder().unwrap();
+        cert.set_version(2).unwrap();
+        cert.set_subject_name(&name).unwrap();
+        cert.set_issuer_name(&name).unwrap();
+        cert.set_pubkey(&public_key).unwrap();
+        cert.set_not_before(&openssl::asn1::Asn1Time::days_from_now(0).unwrap())
+            .unwrap();
+        cert.set_not_after(&openssl::asn1::Asn1Time::days_from_now(30).unwrap())
+            .unwrap();
+        cert.append_extension(
+            openssl::x509::extension::BasicConstraints::new()
+                .critical()
+                .ca()
+                .build()
+                .unwrap(),
+        )
+        .unwrap();
+        cert.sign(&private_key, openssl::hash::MessageDigest::sha256())
+            .unwrap();
+
+        (cert.build(), private_key)
+    }
+
+    #[test]
+    fn signed_server_cert_is_not_a_ca() {
+        let (issuer_cert, issuer_private_key) = issuer();
+        let (_, public_key) = generate_local_keypair().unwrap();
+        let props = CertificateProperties::new(
+            "api".to_string(),
+            CertificateType::Server,
+            "alias".to_string(),
+        )
+        .with_dns_san_entries(vec!["api".to_string()]);
+
+        let pem = sign_cert(&props, &public_key, 3600, &issuer_cert, &issuer_private_key).unwrap();
+        let cert = openssl::x509::X509::from_pem(&pem).unwrap();
+
+        let serial_number = cert.serial_number().to_bn().unwrap();
+        assert!(!serial_number.is_negative());
+        assert!(serial_number > openssl::bn::BigNum::from_u32(0).unwrap());
+
+        let text = String::from_utf8(cert.to_text().unwrap()).unwrap();
+        assert!(text.contains("CA:FALSE"), "{}", text);
+        assert!(
+            text.contains("Digital Signature, Key Encipherment"),
+            "{}",
+            text
+        );
+        assert!(text.contains("TLS Web Server Authentication"), "{}", text);
+
+        assert!(cert.verify(&issuer_cert.public_key().unwrap()).unwrap());
+    }
+}
@@ -1,21 +1,24 @@
 // Copyright (c) Microsoft. All rights reserved.
+use std::convert::TryFrom;
 use std::net::IpAddr;
 use std::str::FromStr;
 use std::sync::{Arc, Mutex};
 
 use super::refresh_cert;
+use chrono::{DateTime, Utc};
 use failure::ResultExt;
 use futures::{future, Future, IntoFuture, Stream};
 use hyper::{Body, Request, Response};
+use log::debug;
 
 use cert_client::client::CertificateClient;
-use edgelet_core::{CertificateProperties, CertificateType, WorkloadConfig};
+use edgelet_core::{CertificateProperties, CertificateType, ServerCertPolicy, WorkloadConfig};
 use edgelet_http::route::{Handler, Parameters};
 use edgelet_http::Error as HttpError;
 use edgelet_utils::{ensure_not_empty_with_context, prepare_dns_san_entries};
 use workload::models::ServerCertificateRequest;
 
-use crate::error::{CertOperation, Error, ErrorKind};
+use crate::error::{CertOperation, Error, ErrorKind, Result};
 use crate::IntoResponse;
 
 pub struct ServerCertHandler<W: WorkloadConfig> {
@@ -79,34 +82,7 @@ where
             .and_then(move |(alias, body, module_id)| {
                 let cert_req: ServerCertificateRequest =
                     serde_json::from_slice(&body).context(ErrorKind::MalformedRequestBody)?;
-
-                let common_name = cert_req.common_name();
-                ensure_not_empty_with_context(common_name, || ErrorKind::MalformedRequestBody)?;
-
-                // add a DNS SAN entry in the server cert that uses the module identifier as
-                // an alternative DNS name; we also need to add the common_name that we are using
-                // as a DNS name since the presence of a DNS name SAN will take precedence over
-                // the common name
-                let mut dns: Vec<String> =
-                    prepare_dns_san_entries([&*module_id].iter().copied()).collect();
-
-                let mut ip: Vec<String> = Vec::new();
-
-                if IpAddr::from_str(common_name).is_ok() {
-                    ip.push(common_name.clone());
-                } else {
-                    dns.push(common_name.clone());
-                };
-
-                #[allow(clippy::cast_sign_loss)]
-                let props = CertificateProperties::new(
-                    common_name.to_string(),
-                    CertificateType::Server,
-                    alias.clone(),
-                )
-                .with_dns_san_entries(dns)
-                .with_ip_entries(ip);
-
+                let props = server_cert_properties(&cfg, &module_id, alias.clone(), &cert_req)?;
                 Ok((alias, props, cfg))
             })
             .and_then(move |(alias, props, cfg)| {
@@ -130,3 +106,244 @@ where
         Box::new(response)
     }
 }
+
+/// Checks a module's server certificate request against its policy.
+fn server_cert_properties<W: WorkloadConfig>(
+    cfg: &W,
+    module_id: &str,
+    alias: String,
+    cert_req: &ServerCertificateRequest,
+) -> Result<CertificateProperties> {
+    let common_name = cert_req.common_name();
+    ensure_not_empty_with_context(common_name, || ErrorKind::MalformedRequestBody)?;
+
+    // A requested validity is only honoured for modules whose policy sets a maximum validity.
+    // Longer requests are shortened to the maximum rather than refused. Other modules get the
+    // validity that certd issues certificates with.
+    let policy = cfg.server_cert_policy(module_id);
+    let validity_in_secs = policy
+        .and_then(ServerCertPolicy::max_validity)
+        .map(|max_validity| {
+            let max_validity_in_secs = i64::try_from(max_validity.as_secs()).unwrap_or(i64::MAX);
+            requested_validity_in_secs(cert_req.expiration())
+                .map_or(max_validity_in_secs, |validity_in_secs| {
+                    validity_in_secs.min(max_validity_in_secs)
+                })
+        });
+
+    // add a DNS SAN entry in the server cert that uses the module identifier as
+    // an alternative DNS name; we also need to add the common_name that we are using
+    // as a DNS name since the presence of a DNS name SAN will take precedence over
+    // the common name
+    let mut dns: Vec<String> = prepare_dns_san_entries([module_id].iter().copied()).collect();
+
+    let mut ip: Vec<String> = Vec::new();
+
+    if IpAddr::from_str(common_name).is_ok() {
+        ip.push(common_name.clone());
+    } else {
+        dns.push(common_name.clone());
+    };
+
+    // Any further SANs have to be allowed by the module's policy.
+    for name in cert_req.dns_names().into_iter().flatten() {
+        if dns.contains(name) {
+            continue;
+        }
+        if !policy.map_or(false, |policy| policy.allows_dns_name(name)) {
+            return Err(Error::from(ErrorKind::ServerCertNotAllowed(format!(
+                "DNS name {} is not allowed for module {}",
+                name, module_id
+            ))));
+        }
+        dns.push(name.clone());
+    }
+
+    for address in cert_req.ip_addresses().into_iter().flatten() {
+        if ip.contains(address) {
+            continue;
+        }
+        let parsed = IpAddr::from_str(address).context(ErrorKind::MalformedRequestBody)?;
+        if !policy.map_or(false, |policy| policy.allows_ip_address(parsed)) {
+            return Err(Error::from(ErrorKind::ServerCertNotAllowed(format!(
+                "IP address {} is not allowed for module {}",
+                address, module_id
+            ))));
+        }
+        ip.push(address.clone());
+    }
+
+    let props = CertificateProperties::new(common_name.to_string(), CertificateType::Server, alias)
+        .with_dns_san_entries(dns)
+        .with_ip_entries(ip);
+    Ok(match validity_in_secs {
+        Some(validity_in_secs) => props.with_validity_in_secs(validity_in_secs),
+        None => props,
+    })
+}
+
+/// Clients have always sent an expiration, which used to be ignored, so one that can't be
+/// parsed or is in the past gets the maximum validity instead of failing the request.
+fn requested_validity_in_secs(expiration: &str) -> Option<i64> {
+    let expiration = match DateTime::parse_from_rfc3339(expiration) {
+        Ok(expiration) => expiration,
+        Err(err) => {
+            debug!(
+                "Ignoring server certificate expiration {:?}: {}",
+                expiration, err
+            );
+            return None;
+        }
+    };
+    let validity_in_secs = (expiration.with_timezone(&Utc) - Utc::now()).num_seconds();
+    if validity_in_secs <= 0 {
+        debug!(
+            "Ignoring server certificate expiration {} in the past",
+            expiration
+        );
+        return None;
+    }
+    Some(validity_in_secs)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::time::Duration;
+
+    use chrono::Utc;
+
+    use edgelet_core::{CertificateType, ServerCertPolicy, WorkloadConfig};
+    use workload::models::ServerCertificateRequest;
+
+    use super::{requested_validity_in_secs, server_cert_properties};
+    use crate::error::ErrorKind;
+
+    const MAX_DURATION_SECS: i64 = 90 * 24 * 3600;
+
+    struct TestConfig {
+        policies: BTreeMap<String, ServerCertPolicy>,
+    }
+
+    impl WorkloadConfig for TestConfig {
+        fn iot_hub_name(&self) -> &str {
+            "hub"
+        }
+
+        fn device_id(&self) -> &str {
+            "device"
+        }
+
+        fn edge_ca_cert(&self) -> &str {
+            "edge-ca"
+        }
+
+        fn edge_ca_key(&self) -> &str {
+            "edge-ca"
+        }
+
+        fn trust_bundle_cert(&self) -> &str {
+            "trust-bundle"
+        }
+
+        fn manifest_trust_bundle_cert(&self) -> &str {
+            "manifest-trust-bundle"
+        }
+
+        fn get_cert_max_duration(&self, _cert_type: CertificateType) -> i64 {
+            MAX_DURATION_SECS
+        }
+
+        fn server_cert_policy(&self, module_id: &str) -> Option<&ServerCertPolicy> {
+            self.policies.get(module_id)
+        }
+    }
+
+    fn config() -> TestConfig {
+        let mut policies = BTreeMap::new();
+        policies.insert(
+            "api".to_owned(),
+            ServerCertPolicy {
+                allowed_dns_names: vec!["*.contoso.local".to_owned()],
+                allowed_ip_addresses: vec!["10.0.0.0/24".to_owned()],
+                max_validity: Some(Duration::from_secs(3600)),
+            },
+        );
+        TestConfig { policies }
+    }
+
+    fn expiration_in(secs: i64) -> String {
+        (Utc::now() + chrono::Duration::seconds(secs)).to_rfc3339()
+    }
+
+    #[test]
+    fn allowed_sans_are_added() {
+        let cert_req = ServerCertificateRequest::new("api".to_owned(), expiration_in(60))
+            .with_dns_names(vec!["web.contoso.local".to_owned(), "api".to_owned()])
+            .with_ip_addresses(vec!["10.0.0.5".to_owned()]);
+
+        let props =
+            server_cert_properties(&config(), "api", "alias".to_owned(), &cert_req).unwrap();
+        assert_eq!(CertificateType::Server, *props.certificate_type());
+        assert_eq!(
+            Some(&["api".to_owned(), "web.contoso.local".to_owned()][..]),
+            props.dns_san_entries()
+        );
+        assert_eq!(Some(&["10.0.0.5".to_owned()][..]), props.ip_entries());
+    }
+
+    #[test]
+    fn denied_sans_are_refused() {
+        let cert_req = ServerCertificateRequest::new("api".to_owned(), expiration_in(60))
+            .with_dns_names(vec!["contoso.com".to_owned()]);
+        let err =
+            server_cert_properties(&config(), "api", "alias".to_owned(), &cert_req).unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::ServerCertNotAllowed(_)));
+
+        let cert_req = ServerCertificateRequest::new("api".to_owned(), expiration_in(60))
+            .with_ip_addresses(vec!["10.0.1.5".to_owned()]);
+        let err =
+            server_cert_properties(&config(), "api", "alias".to_owned(), &cert_req).unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::ServerCertNotAllowed(_)));
+
+        // Modules without a policy can't request any further SANs.
+        let cert_req = ServerCertificateRequest::new("other".to_owned(), expiration_in(60))
+            .with_dns_names(vec!["web.contoso.local".to_owned()]);
+        let err =
+            server_cert_properties(&config(), "other", "alias".to_owned(), &cert_req).unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::ServerCertNotAllowed(_)));
+    }
+
+    #[test]
+    fn validity_is_clamped_to_maximum() {
+        let cert_req = ServerCertificateRequest::new("api".to_owned(), expiration_in(600));
+        let props =
+            server_cert_properties(&config(), "api", "alias".to_owned(), &cert_req).unwrap();
+        let validity_in_secs = props.validity_in_secs().unwrap();
+        assert!(validity_in_secs > 590 && validity_in_secs <= 600);
+
+        let cert_req = ServerCertificateRequest::new("api".to_owned(), expiration_in(7200));
+        let props =
+            server_cert_properties(&config(), "api", "alias".to_owned(), &cert_req).unwrap();
+        assert_eq!(Some(3600), props.validity_in_secs());
+    }
+
+    #[test]
+    fn validity_is_left_to_certd_without_policy() {
+        let cert_req = ServerCertificateRequest::new("other".to_owned(), expiration_in(600));
+        let props =
+            server_cert_properties(&config(), "other", "alias".to_owned(), &cert_req).unwrap();
+        assert_eq!(None, props.validity_in_secs());
+    }
+
+    #[test]
+    fn invalid_expiration_gets_maximum_validity() {
+        assert_eq!(None, requested_validity_in_secs("not a date"));
+        assert_eq!(None, requested_validity_in_secs(&expiration_in(-60)));
+
+        let cert_req = ServerCertificateRequest::new("api".to_owned(), "not a date".to_owned());
+        let props =
+            server_cert_properties(&config(), "api", "alias".to_owned(), &cert_req).unwrap();
+        assert_eq!(Some(3600), props.validity_in_secs());
+    }
+}
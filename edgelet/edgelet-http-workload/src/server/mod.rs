@@ -4,6 +4,7 @@ mod cert;
 mod decrypt;
 mod encrypt;
 mod manifest_trust_bundle;
+mod secret;
 mod sign;
 mod trust_bundle;
 
@@ -27,13 +28,15 @@ use hyper::{Body, Request};
 use serde::Serialize;
 use std::sync::{Arc, Mutex};
 
-use self::cert::{IdentityCertHandler, ServerCertHandler};
+use self::cert::{CertificateEventsHandler, IdentityCertHandler, ServerCertHandler};
 use self::decrypt::DecryptHandler;
 use self::encrypt::EncryptHandler;
 use self::manifest_trust_bundle::ManifestTrustBundleHandler;
+use self::secret::{DeleteSecretHandler, GetSecretHandler, SetSecretHandler};
 use self::sign::SignHandler;
 use self::trust_bundle::TrustBundleHandler;
 use crate::error::{Error, ErrorKind};
+use crate::secret_store::SecretStore;
 
 #[derive(Clone)]
 pub struct WorkloadService {
@@ -46,6 +49,7 @@ impl WorkloadService {
         identity_client: Arc<Mutex<IdentityClient>>,
         cert_client: Arc<Mutex<CertificateClient>>,
         key_client: Arc<aziot_key_client::Client>,
+        secret_store: SecretStore,
         config: W,
     ) -> impl Future<Item = Self, Error = Error> + 'static
     where
@@ -73,6 +77,10 @@ impl WorkloadService {
             post  Version2018_06_28 runtime Policy::Caller =>    "/modules/(?P<name>[^/]+)/genid/(?P<genid>[^/]+)/encrypt"  => EncryptHandler::new(key_client.clone()),
             post  Version2018_06_28 runtime Policy::Caller =>    "/modules/(?P<name>[^/]+)/certificate/identity"            => IdentityCertHandler::new(key_client.clone(), cert_client.clone(), config.clone()),
             post  Version2018_06_28 runtime Policy::Caller =>    "/modules/(?P<name>[^/]+)/genid/(?P<genid>[^/]+)/certificate/server" => ServerCertHandler::new(key_client, cert_client.clone(), config.clone()),
+            get   Version2020_10_10 runtime Policy::Caller =>    "/modules/(?P<name>[^/]+)/genid/(?P<genid>[^/]+)/certificate/events" => CertificateEventsHandler::new(cert_client.clone(), config.clone()),
+            get   Version2020_10_10 runtime Policy::Caller =>    "/modules/(?P<name>[^/]+)/secrets/(?P<key>[^/]+)" => GetSecretHandler::new(secret_store.clone()),
+            put   Version2020_10_10 runtime Policy::Caller =>    "/modules/(?P<name>[^/]+)/secrets/(?P<key>[^/]+)" => SetSecretHandler::new(secret_store.clone()),
+            delete Version2020_10_10 runtime Policy::Caller =>    "/modules/(?P<name>[^/]+)/secrets/(?P<key>[^/]+)" => DeleteSecretHandler::new(secret_store),
             get   Version2018_06_28 runtime Policy::Anonymous => "/trust-bundle" => TrustBundleHandler::new(cert_client.clone(), config.clone()),
             get   Version2018_06_28 runtime Policy::Anonymous => "/manifest-trust-bundle" => ManifestTrustBundleHandler::new(cert_client, config),
         );
@@ -0,0 +1,315 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use failure::{Fail, ResultExt};
+use futures::{Future, IntoFuture, Stream};
+use hyper::header::{CONTENT_LENGTH, CONTENT_TYPE};
+use hyper::{Body, Request, Response, StatusCode};
+
+use edgelet_http::route::{Handler, Parameters};
+use edgelet_http::Error as HttpError;
+use workload::models::{SecretRequest, SecretResponse};
+
+use crate::error::{Error, ErrorKind};
+use crate::secret_store::{SecretStore, MAX_VALUE_SIZE};
+use crate::IntoResponse;
+
+/// The largest request body that can hold a secret value, which is sent in base 64.
+const MAX_BODY_SIZE: usize = MAX_VALUE_SIZE / 3 * 4 + 1024;
+
+pub struct GetSecretHandler {
+    secret_store: SecretStore,
+}
+
+impl GetSecretHandler {
+    pub fn new(secret_store: SecretStore) -> Self {
+        GetSecretHandler { secret_store }
+    }
+}
+
+impl Handler<Parameters> for GetSecretHandler {
+    fn handle(
+        &self,
+        _req: Request<Body>,
+        params: Parameters,
+    ) -> Box<dyn Future<Item = Response<Body>, Error = HttpError> + Send> {
+        let response = get_module_and_key(&params)
+            .and_then(|(module_id, key)| {
+                let value = self
+                    .secret_store
+                    .get(&module_id, &key)?
+                    .ok_or_else(|| ErrorKind::SecretNotFound(key))?;
+
+                let body = serde_json::to_string(&SecretResponse::new(base64::encode(&value)))
+                    .context(ErrorKind::SecretStore)?;
+                let response = Response::builder()
+                    .status(StatusCode::OK)
+                    .header(CONTENT_TYPE, "application/json")
+                    .header(CONTENT_LENGTH, body.len().to_string().as_str())
+                    .body(body.into())
+                    .context(ErrorKind::SecretStore)?;
+                Ok(response)
+            })
+            .or_else(|e| Ok(e.into_response()))
+            .into_future();
+
+        Box::new(response)
+    }
+}
+
+pub struct SetSecretHandler {
+    secret_store: SecretStore,
+}
+
+impl SetSecretHandler {
+    pub fn new(secret_store: SecretStore) -> Self {
+        SetSecretHandler { secret_store }
+    }
+}
+
+impl Handler<Parameters> for SetSecretHandler {
+    fn handle(
+        &self,
+        req: Request<Body>,
+        params: Parameters,
+    ) -> Box<dyn Future<Item = Response<Body>, Error = HttpError> + Send> {
+        let secret_store = self.secret_store.clone();
+
+        let response = get_module_and_key(&params)
+            .into_future()
+            .and_then(|(module_id, key)| {
+                // The body is read no further than the largest one a secret can have.
+                req.into_body()
+                    .map_err(|err| Error::from(err.context(ErrorKind::SecretStore)))
+                    .fold(Vec::new(), |mut body, chunk| {
+                        if body.len() + chunk.len() > MAX_BODY_SIZE {
+                            return Err(Error::from(ErrorKind::MalformedRequestBody));
+                        }
+                        body.extend_from_slice(&chunk);
+                        Ok(body)
+                    })
+                    .and_then(|body| {
+                        let request: SecretRequest = serde_json::from_slice(&body)
+                            .context(ErrorKind::MalformedRequestBody)?;
+                        Ok((module_id, key, request))
+                    })
+            })
+            .and_then(move |(module_id, key, request)| -> Result<_, Error> {
+                let value =
+                    base64::decode(request.value()).context(ErrorKind::MalformedRequestBody)?;
+                secret_store.set(&module_id, &key, &value)?;
+
+                let response = Response::builder()
+                    .status(StatusCode::NO_CONTENT)
+                    .body(Body::empty())
+                    .context(ErrorKind::SecretStore)?;
+                Ok(response)
+            })
+            .or_else(|e| Ok(e.into_response()));
+
+        Box::new(response)
+    }
+}
+
+pub struct DeleteSecretHandler {
+    secret_store: SecretStore,
+}
+
+impl DeleteSecretHandler {
+    pub fn new(secret_store: SecretStore) -> Self {
+        DeleteSecretHandler { secret_store }
+    }
+}
+
+impl Handler<Parameters> for DeleteSecretHandler {
+    fn handle(
+        &self,
+        _req: Request<Body>,
+        params: Parameters,
+    ) -> Box<dyn Future<Item = Response<Body>, Error = HttpError> + Send> {
+        let response = get_module_and_key(&params)
+            .and_then(|(module_id, key)| {
+                if !self.secret_store.delete(&module_id, &key)? {
+                    return Err(ErrorKind::SecretNotFound(key).into());
+                }
+
+                let response = Response::builder()
+                    .status(StatusCode::NO_CONTENT)
+                    .body(Body::empty())
+                    .context(ErrorKind::SecretStore)?;
+                Ok(response)
+            })
+            .or_else(|e| Ok(e.into_response()))
+            .into_future();
+
+        Box::new(response)
+    }
+}
+
+fn get_module_and_key(params: &Parameters) -> Result<(String, String), Error> {
+    let module_id = params
+        .name("name")
+        .ok_or_else(|| Error::from(ErrorKind::MissingRequiredParameter("name")))?;
+    let key = params
+        .name("key")
+        .ok_or_else(|| Error::from(ErrorKind::MissingRequiredParameter("key")))?;
+    Ok((module_id.to_string(), key.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::{stream, Future, Stream};
+    use hyper::{Body, Request, StatusCode};
+
+    use edgelet_core::{AuthId, Policy};
+    use edgelet_http::authorization::Authorization;
+    use edgelet_http::route::{Handler, Parameters};
+    use workload::models::{SecretRequest, SecretResponse};
+
+    use super::{DeleteSecretHandler, GetSecretHandler, SetSecretHandler};
+    use crate::secret_store::tests::test_secret_store;
+    use crate::secret_store::MAX_VALUE_SIZE;
+
+    fn params(module_id: &str, key: &str) -> Parameters {
+        Parameters::with_captures(vec![
+            (Some("name".to_string()), module_id.to_string()),
+            (Some("key".to_string()), key.to_string()),
+        ])
+    }
+
+    fn request(caller: &str, body: Body) -> Request<Body> {
+        let mut request = Request::new(body);
+        request
+            .extensions_mut()
+            .insert(AuthId::Value(caller.into()));
+        request
+    }
+
+    fn set_request(caller: &str, value: &[u8]) -> Request<Body> {
+        let body = serde_json::to_string(&SecretRequest::new(base64::encode(value))).unwrap();
+        request(caller, body.into())
+    }
+
+    #[test]
+    fn set_get_delete() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = test_secret_store(dir.path());
+
+        let response = SetSecretHandler::new(store.clone())
+            .handle(set_request("m1", b"v1"), params("m1", "k1"))
+            .wait()
+            .unwrap();
+        assert_eq!(StatusCode::NO_CONTENT, response.status());
+
+        let response = GetSecretHandler::new(store.clone())
+            .handle(request("m1", Body::empty()), params("m1", "k1"))
+            .wait()
+            .unwrap();
+        assert_eq!(StatusCode::OK, response.status());
+        let body = response.into_body().concat2().wait().unwrap();
+        let secret: SecretResponse = serde_json::from_slice(&body).unwrap();
+        assert_eq!(b"v1".to_vec(), base64::decode(secret.value()).unwrap());
+
+        let response = DeleteSecretHandler::new(store.clone())
+            .handle(request("m1", Body::empty()), params("m1", "k1"))
+            .wait()
+            .unwrap();
+        assert_eq!(StatusCode::NO_CONTENT, response.status());
+
+        let response = GetSecretHandler::new(store.clone())
+            .handle(request("m1", Body::empty()), params("m1", "k1"))
+            .wait()
+            .unwrap();
+        assert_eq!(StatusCode::NOT_FOUND, response.status());
+
+        let response = DeleteSecretHandler::new(store)
+            .handle(request("m1", Body::empty()), params("m1", "k1"))
+            .wait()
+            .unwrap();
+        assert_eq!(StatusCode::NOT_FOUND, response.status());
+    }
+
+    #[test]
+    fn set_rejects_malformed_values() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = test_secret_store(dir.path());
+
+        let response = SetSecretHandler::new(store.clone())
+            .handle(request("m1", "not json".into()), params("m1", "k1"))
+            .wait()
+            .unwrap();
+        assert_eq!(StatusCode::BAD_REQUEST, response.status());
+
+        let body = serde_json::to_string(&SecretRequest::new("!".to_string())).unwrap();
+        let response = SetSecretHandler::new(store.clone())
+            .handle(request("m1", body.into()), params("m1", "k1"))
+            .wait()
+            .unwrap();
+        assert_eq!(StatusCode::BAD_REQUEST, response.status());
+
+        let response = SetSecretHandler::new(store)
+            .handle(set_request("m1", b"v1"), params("m1", ".hidden"))
+            .wait()
+            .unwrap();
+        assert_eq!(StatusCode::BAD_REQUEST, response.status());
+    }
+
+    #[test]
+    fn set_stops_reading_oversized_bodies() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = test_secret_store(dir.path());
+
+        // The body never ends, so the handler only returns if it stops reading.
+        let body = Body::wrap_stream(stream::repeat::<_, std::io::Error>(vec![b'a'; 1024]));
+        let response = SetSecretHandler::new(store.clone())
+            .handle(request("m1", body), params("m1", "k1"))
+            .wait()
+            .unwrap();
+        assert_eq!(StatusCode::BAD_REQUEST, response.status());
+
+        let response = SetSecretHandler::new(store.clone())
+            .handle(
+                set_request("m1", &vec![b'a'; MAX_VALUE_SIZE]),
+                params("m1", "k1"),
+            )
+            .wait()
+            .unwrap();
+        assert_eq!(StatusCode::NO_CONTENT, response.status());
+        assert_eq!(
+            Some(vec![b'a'; MAX_VALUE_SIZE]),
+            store.get("m1", "k1").unwrap()
+        );
+    }
+
+    #[test]
+    fn modules_cannot_access_other_modules_secrets() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = test_secret_store(dir.path());
+        store.set("m1", "k1", b"v1").unwrap();
+
+        let response = Authorization::new(GetSecretHandler::new(store.clone()), Policy::Caller)
+            .handle(request("m2", Body::empty()), params("m1", "k1"))
+            .wait()
+            .unwrap();
+        assert_eq!(StatusCode::NOT_FOUND, response.status());
+
+        let response = Authorization::new(SetSecretHandler::new(store.clone()), Policy::Caller)
+            .handle(set_request("m2", b"v2"), params("m1", "k1"))
+            .wait()
+            .unwrap();
+        assert_eq!(StatusCode::NOT_FOUND, response.status());
+
+        let response = Authorization::new(DeleteSecretHandler::new(store.clone()), Policy::Caller)
+            .handle(request("m2", Body::empty()), params("m1", "k1"))
+            .wait()
+            .unwrap();
+        assert_eq!(StatusCode::NOT_FOUND, response.status());
+
+        assert_eq!(Some(b"v1".to_vec()), store.get("m1", "k1").unwrap());
+
+        let response = Authorization::new(GetSecretHandler::new(store), Policy::Caller)
+            .handle(request("m1", Body::empty()), params("m1", "k1"))
+            .wait()
+            .unwrap();
+        assert_eq!(StatusCode::OK, response.status());
+    }
+}
@@ -6,13 +6,14 @@ use failure::ResultExt;
 use futures::{future, Future};
 use hyper::{Body, Request, Response};
 
-use edgelet_core::{AuthId, Policy};
+use edgelet_core::{AuthId, Policy, Role};
 
 use crate::route::{Handler, Parameters};
 use crate::{Error, ErrorKind, IntoResponse};
 
 pub struct Authorization<H> {
     policy: Policy,
+    role: Option<Role>,
     inner: Arc<H>,
 }
 
@@ -20,9 +21,17 @@ impl<H> Authorization<H> {
     pub fn new(inner: H, policy: Policy) -> Self {
         Authorization {
             policy,
+            role: None,
             inner: Arc::new(inner),
         }
     }
+
+    /// Allows clients that authenticated with a certificate to make the request if they have at
+    /// least this role. Without one, only clients that are authorized by the policy can.
+    pub fn with_role(mut self, role: Role) -> Self {
+        self.role = Some(role);
+        self
+    }
 }
 
 impl<H> Handler<Parameters> for Authorization<H>
@@ -41,20 +50,24 @@ where
                 .cloned()
                 .unwrap_or_else(|| AuthId::None),
         );
+        let client_role = req.extensions().get::<Role>().copied();
         let inner = self.inner.clone();
 
-        let authorized = self.policy.authorize(name, auth_id);
+        let denied = match (client_role, self.role) {
+            (Some(client_role), Some(role)) if client_role >= role => None,
+            (Some(client_role), _) => Some(ErrorKind::InsufficientRole(client_role)),
+            (None, _) if self.policy.authorize(name, auth_id) => None,
+            (None, _) => Some(ErrorKind::ModuleNotFound(name.unwrap_or("").to_string())),
+        };
 
-        let response = if authorized {
+        let response = if let Some(denied) = denied {
+            future::Either::B(future::err(Error::from(denied)))
+        } else {
             future::Either::A(
                 inner
                     .handle(req, params)
                     .then(|resp| resp.context(ErrorKind::Authorization).map_err(Error::from)),
             )
-        } else {
-            future::Either::B(future::err(Error::from(ErrorKind::ModuleNotFound(
-                name.unwrap_or("").to_string(),
-            ))))
         };
 
         Box::new(response.or_else(|e| future::ok(e.into_response())))
@@ -66,7 +79,7 @@ mod tests {
     use futures::{Future, Stream};
     use hyper::{Body, Request, Response, StatusCode};
 
-    use super::{future, AuthId, Authorization, Handler, Parameters, Policy};
+    use super::{future, AuthId, Authorization, Handler, Parameters, Policy, Role};
     use crate::error::Error as HttpError;
 
     #[test]
@@ -120,6 +133,43 @@ mod tests {
         assert_eq!(404, response.status());
     }
 
+    #[test]
+    fn handler_calls_inner_handler_when_client_role_is_sufficient() {
+        let params = Parameters::with_captures(vec![(Some("name".to_string()), "abc".to_string())]);
+        let mut request = Request::default();
+        request.extensions_mut().insert(AuthId::None);
+        request.extensions_mut().insert(Role::Admin);
+
+        let auth = Authorization::new(TestHandler::new(), Policy::Caller).with_role(Role::Operator);
+        let response = auth.handle(request, params).wait().unwrap();
+        assert_eq!(200, response.status());
+    }
+
+    #[test]
+    fn handler_responds_with_forbidden_when_client_role_is_insufficient() {
+        let params = Parameters::with_captures(vec![(Some("name".to_string()), "abc".to_string())]);
+        let mut request = Request::default();
+        request.extensions_mut().insert(AuthId::None);
+        request.extensions_mut().insert(Role::ReadOnly);
+
+        let auth =
+            Authorization::new(TestHandler::new(), Policy::Anonymous).with_role(Role::Operator);
+        let response = auth.handle(request, params).wait().unwrap();
+        assert_eq!(403, response.status());
+    }
+
+    #[test]
+    fn handler_responds_with_forbidden_when_route_has_no_role() {
+        let params = Parameters::with_captures(vec![]);
+        let mut request = Request::default();
+        request.extensions_mut().insert(AuthId::None);
+        request.extensions_mut().insert(Role::Admin);
+
+        let auth = Authorization::new(TestHandler::new(), Policy::Anonymous);
+        let response = auth.handle(request, params).wait().unwrap();
+        assert_eq!(403, response.status());
+    }
+
     #[derive(Clone)]
     struct TestHandler {}
 
@@ -11,6 +11,8 @@ use serde_json::json;
 use systemd::Fd;
 use url::Url;
 
+use edgelet_core::Role;
+
 use crate::IntoResponse;
 
 #[derive(Debug)]
@@ -26,6 +28,9 @@ pub enum ErrorKind {
     #[fail(display = "An error occurred while binding a listener to {}", _0)]
     BindListener(BindListenerType),
 
+    #[fail(display = "Client certificate subject {:?} is not assigned a role", _0)]
+    ClientNotAuthorized(String),
+
     #[fail(display = "Unable to delete a TLS certificate")]
     CertificateDeletionError,
 
@@ -59,6 +64,9 @@ pub enum ErrorKind {
     #[fail(display = "Reading identity private key from PEM bytes failed {}", _0)]
     IdentityPrivateKeyRead(String),
 
+    #[fail(display = "The {} role is not allowed to make this request", _0)]
+    InsufficientRole(Role),
+
     #[fail(display = "Could not initialize")]
     Initialization,
 
@@ -102,6 +110,9 @@ pub enum ErrorKind {
     #[fail(display = "An error occurred configuring the TLS stack")]
     TlsBootstrapError,
 
+    #[fail(display = "An error occurred during the TLS handshake")]
+    TlsHandshake,
+
     #[fail(display = "An error occurred during creation of the TLS identity from cert")]
     TlsIdentityCreationError,
 
@@ -177,6 +188,7 @@ impl IntoResponse for Error {
 
         let status_code = match *self.kind() {
             ErrorKind::Authorization | ErrorKind::ModuleNotFound(_) => StatusCode::NOT_FOUND,
+            ErrorKind::InsufficientRole(_) => StatusCode::FORBIDDEN,
             ErrorKind::InvalidApiVersion(_) => StatusCode::BAD_REQUEST,
             _ => StatusCode::INTERNAL_SERVER_ERROR,
         };
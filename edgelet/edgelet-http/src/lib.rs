@@ -23,6 +23,7 @@ use std::os::unix::io::FromRawFd;
 use std::sync::Arc;
 
 use failure::{Fail, ResultExt};
+use futures::future::Either;
 use futures::{future, Future, Poll, Stream};
 use hyper::server::conn::Http;
 use hyper::service::{NewService, Service};
@@ -52,20 +53,25 @@ pub mod error;
 pub mod logging;
 mod pid;
 pub mod route;
+mod tls;
 mod unix;
 mod util;
 mod version;
 
 pub use error::{BindListenerType, Error, ErrorKind, InvalidUrlReason};
 pub use pid::Pid;
+pub use tls::MutualTls;
 pub use util::proxy::MaybeProxyClient;
 pub use util::UrlConnector;
 pub use version::{Version, API_VERSION};
 
 use crate::pid::PidService;
+use crate::tls::RoleService;
 use crate::util::incoming::Incoming;
+use crate::util::StreamSelector;
 
 const HTTP_SCHEME: &str = "http";
+const HTTPS_SCHEME: &str = "https";
 const TCP_SCHEME: &str = "tcp";
 #[cfg(target_os = "linux")]
 const FD_SCHEME: &str = "fd";
@@ -191,6 +197,7 @@ pub struct Server<S> {
     protocol: Http,
     new_service: S,
     incoming: Incoming,
+    tls: Option<MutualTls>,
 }
 
 impl<S> Server<S>
@@ -213,6 +220,7 @@ where
             protocol,
             new_service,
             incoming,
+            tls,
         } = self;
 
         let protocol = Arc::new(protocol);
@@ -221,21 +229,47 @@ where
             let protocol = protocol.clone();
 
             debug!("accepted new connection ({})", addr);
-            let pid = socket.pid()?;
+            let addr = addr.to_string();
+
+            // A TLS server only listens on TCP, so all of its connections are handshaken here.
+            let connection = match (&tls, socket) {
+                (Some(tls), StreamSelector::Tcp(stream)) => {
+                    let addr = addr.clone();
+                    Either::A(tls.accept(stream).then(move |result| match result {
+                        Ok((stream, role)) => {
+                            Ok((StreamSelector::MutualTls(stream), Pid::None, Some(role)))
+                        }
+                        Err(err) => {
+                            error!("TLS handshake error: ({})", addr);
+                            log_failure(Level::Error, &err);
+                            Err(())
+                        }
+                    }))
+                }
+                (_, socket) => {
+                    let pid = socket.pid()?;
+                    Either::B(future::ok((socket, pid, None)))
+                }
+            };
+
+            let new_service_addr = addr.clone();
             let fut = new_service
                 .new_service()
                 .then(move |srv| match srv {
-                    Ok(srv) => Ok((srv, addr)),
+                    Ok(srv) => Ok(srv),
                     Err(err) => {
-                        error!("server connection error: ({})", addr);
+                        error!("server connection error: ({})", new_service_addr);
                         log_failure(Level::Error, &err);
                         Err(())
                     }
                 })
-                .and_then(move |(srv, addr)| {
-                    let service = PidService::new(pid, srv);
+                .join(connection)
+                .and_then(move |(srv, (socket, pid, role))| {
+                    let service = PidService::new(pid, RoleService::new(role, srv));
                     protocol
                         .serve_connection(socket, service)
+                        // Lets handlers take over the connection, like exec does.
+                        .with_upgrades()
                         .then(move |result| match result {
                             Ok(_) => Ok(()),
                             Err(err) => {
@@ -277,6 +311,26 @@ pub trait HyperExt {
     fn bind_url<S>(&self, url: Url, new_service: S) -> Result<Server<S>, Error>
     where
         S: NewService<ReqBody = Body>;
+
+    /// Binds an `https` URL, whose clients must authenticate with a certificate that `tls`
+    /// assigns a role to.
+    fn bind_tls_url<S>(&self, url: Url, tls: MutualTls, new_service: S) -> Result<Server<S>, Error>
+    where
+        S: NewService<ReqBody = Body>;
+}
+
+fn bind_tcp(url: &Url) -> Result<Incoming, Error> {
+    let addr = url
+        .socket_addrs(|| None)
+        .context(ErrorKind::InvalidUrl(url.to_string()))?;
+    let addr = addr.get(0);
+    let addr = addr.ok_or_else(|| {
+        ErrorKind::InvalidUrlWithReason(url.to_string(), InvalidUrlReason::NoAddress)
+    })?;
+
+    let listener = TcpListener::bind(&addr)
+        .with_context(|_| ErrorKind::BindListener(BindListenerType::Address(*addr)))?;
+    Ok(Incoming::Tcp(listener))
 }
 
 // This variable is used on Unix but not Windows
@@ -287,19 +341,7 @@ impl HyperExt for Http {
         S: NewService<ReqBody = Body>,
     {
         let incoming = match url.scheme() {
-            HTTP_SCHEME | TCP_SCHEME => {
-                let addr = url
-                    .socket_addrs(|| None)
-                    .context(ErrorKind::InvalidUrl(url.to_string()))?;
-                let addr = addr.get(0);
-                let addr = addr.ok_or_else(|| {
-                    ErrorKind::InvalidUrlWithReason(url.to_string(), InvalidUrlReason::NoAddress)
-                })?;
-
-                let listener = TcpListener::bind(&addr)
-                    .with_context(|_| ErrorKind::BindListener(BindListenerType::Address(*addr)))?;
-                Incoming::Tcp(listener)
-            }
+            HTTP_SCHEME | TCP_SCHEME => bind_tcp(&url)?,
             UNIX_SCHEME => {
                 let path = url
                     .to_uds_file_path()
@@ -363,6 +405,26 @@ impl HyperExt for Http {
             protocol: self.clone(),
             new_service,
             incoming,
+            tls: None,
+        })
+    }
+
+    fn bind_tls_url<S>(&self, url: Url, tls: MutualTls, new_service: S) -> Result<Server<S>, Error>
+    where
+        S: NewService<ReqBody = Body>,
+    {
+        if url.scheme() != HTTPS_SCHEME {
+            return Err(Error::from(ErrorKind::InvalidUrlWithReason(
+                url.to_string(),
+                InvalidUrlReason::InvalidScheme,
+            )));
+        }
+
+        Ok(Server {
+            protocol: self.clone(),
+            new_service,
+            incoming: bind_tcp(&url)?,
+            tls: Some(tls),
         })
     }
 }
@@ -9,4 +9,11 @@ macro_rules! router {
             .finish()
         )
     });
+    ($($method:ident $ver:ident $runtime:ident $policy:expr, $role:expr => $path:expr => $handler:expr),+ $(,)*) => ({
+        Router::from(
+            $crate::route::RegexRoutesBuilder::default()
+            $(.$method(Version::$ver, $path, Authentication::new(Authorization::new($handler, $policy).with_role($role), $policy, $runtime.clone())))*
+            .finish()
+        )
+    });
 }
@@ -0,0 +1,251 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+//! TLS for listeners whose clients authenticate with a certificate, like the remote management
+//! API. `tokio-tls` can't ask clients for a certificate, so connections are handshaken with
+//! openssl directly and its `SslStream` is driven over the non-blocking tokio socket.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::sync::Arc;
+
+use failure::{Fail, ResultExt};
+use futures::{Async, Future, Poll};
+use hyper::service::Service;
+use hyper::{Body, Request};
+use openssl::nid::Nid;
+use openssl::pkey::PKey;
+use openssl::ssl::{
+    ErrorCode, HandshakeError, MidHandshakeSslStream, SslAcceptor, SslMethod, SslStream,
+    SslVerifyMode, SslVersion,
+};
+use openssl::x509::X509;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpStream;
+
+use edgelet_core::{ManagementTls, Protocol, Role};
+
+use crate::error::{Error, ErrorKind};
+
+#[derive(Clone)]
+pub struct MutualTls {
+    acceptor: SslAcceptor,
+    roles: Arc<BTreeMap<String, Role>>,
+}
+
+impl MutualTls {
+    /// `cert` is the PEM of the server certificate followed by the rest of its chain, and
+    /// `client_ca` is the PEM of the CA certificates that client certificates must chain to.
+    /// `roles` maps the common name of a client certificate's subject to the client's role.
+    pub fn new(
+        cert: &[u8],
+        key: &[u8],
+        client_ca: &[u8],
+        roles: BTreeMap<String, Role>,
+        min_tls_version: Protocol,
+    ) -> Result<Self, Error> {
+        let mut builder = SslAcceptor::mozilla_intermediate(SslMethod::tls())
+            .context(ErrorKind::TlsBootstrapError)?;
+        builder
+            .set_min_proto_version(Some(match min_tls_version {
+                Protocol::Tls10 => SslVersion::TLS1,
+                Protocol::Tls11 => SslVersion::TLS1_1,
+                Protocol::Tls12 => SslVersion::TLS1_2,
+            }))
+            .context(ErrorKind::TlsBootstrapError)?;
+
+        let mut certs = X509::stack_from_pem(cert)
+            .context(ErrorKind::TlsIdentityCreationError)?
+            .into_iter();
+        let server_cert = certs.next().ok_or(ErrorKind::CertificateNotFound)?;
+        builder
+            .set_certificate(&server_cert)
+            .context(ErrorKind::TlsIdentityCreationError)?;
+        for cert in certs {
+            builder
+                .add_extra_chain_cert(cert)
+                .context(ErrorKind::TlsIdentityCreationError)?;
+        }
+        let key = PKey::private_key_from_pem(key)
+            .with_context(|err| ErrorKind::IdentityPrivateKeyRead(err.to_string()))?;
+        builder
+            .set_private_key(&key)
+            .context(ErrorKind::TlsIdentityCreationError)?;
+        builder
+            .check_private_key()
+            .context(ErrorKind::TlsIdentityCreationError)?;
+
+        let client_ca = X509::stack_from_pem(client_ca).context(ErrorKind::TrustBundle)?;
+        if client_ca.is_empty() {
+            return Err(ErrorKind::TrustBundle.into());
+        }
+        for ca in client_ca {
+            builder.add_client_ca(&ca).context(ErrorKind::TrustBundle)?;
+            builder
+                .cert_store_mut()
+                .add_cert(ca)
+                .context(ErrorKind::TrustBundle)?;
+        }
+        builder.set_verify(SslVerifyMode::PEER | SslVerifyMode::FAIL_IF_NO_PEER_CERT);
+
+        Ok(MutualTls {
+            acceptor: builder.build(),
+            roles: Arc::new(roles),
+        })
+    }
+
+    pub fn from_settings(
+        settings: &ManagementTls,
+        min_tls_version: Protocol,
+    ) -> Result<Self, Error> {
+        MutualTls::new(
+            &read(settings.cert())?,
+            &read(settings.key())?,
+            &read(settings.client_ca())?,
+            settings.roles().clone(),
+            min_tls_version,
+        )
+    }
+
+    /// Handshakes with a client, which is then given the role that its certificate maps to.
+    pub fn accept(&self, stream: TcpStream) -> Accept {
+        Accept {
+            acceptor: self.acceptor.clone(),
+            roles: self.roles.clone(),
+            handshake: Some(Handshake::Start(stream)),
+        }
+    }
+}
+
+fn read(path: &Path) -> Result<Vec<u8>, Error> {
+    Ok(fs::read(path).with_context(|_| ErrorKind::Path(path.display().to_string()))?)
+}
+
+enum Handshake {
+    Start(TcpStream),
+    Mid(MidHandshakeSslStream<TcpStream>),
+}
+
+pub struct Accept {
+    acceptor: SslAcceptor,
+    roles: Arc<BTreeMap<String, Role>>,
+    handshake: Option<Handshake>,
+}
+
+impl Future for Accept {
+    type Item = (MutualTlsStream, Role);
+    type Error = Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        let result = match self
+            .handshake
+            .take()
+            .expect("Accept polled after completion")
+        {
+            Handshake::Start(stream) => self.acceptor.accept(stream),
+            Handshake::Mid(stream) => stream.handshake(),
+        };
+
+        match result {
+            Ok(stream) => {
+                let subject = stream
+                    .ssl()
+                    .peer_certificate()
+                    .and_then(|cert| {
+                        cert.subject_name()
+                            .entries_by_nid(Nid::COMMONNAME)
+                            .next()
+                            .and_then(|entry| entry.data().as_utf8().ok())
+                            .map(|common_name| common_name.to_string())
+                    })
+                    .unwrap_or_default();
+                let role = self
+                    .roles
+                    .get(&subject)
+                    .copied()
+                    .ok_or_else(|| ErrorKind::ClientNotAuthorized(subject))?;
+                Ok(Async::Ready((MutualTlsStream(stream), role)))
+            }
+            Err(HandshakeError::WouldBlock(stream)) => {
+                self.handshake = Some(Handshake::Mid(stream));
+                Ok(Async::NotReady)
+            }
+            Err(HandshakeError::SetupFailure(err)) => {
+                Err(Error::from(err.context(ErrorKind::TlsHandshake)))
+            }
+            Err(HandshakeError::Failure(stream)) => Err(Error::from(
+                stream.into_error().context(ErrorKind::TlsHandshake),
+            )),
+        }
+    }
+}
+
+pub struct MutualTlsStream(SslStream<TcpStream>);
+
+impl Read for MutualTlsStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0.read(buf)
+    }
+}
+
+impl Write for MutualTlsStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+impl AsyncRead for MutualTlsStream {}
+
+impl AsyncWrite for MutualTlsStream {
+    fn shutdown(&mut self) -> Poll<(), io::Error> {
+        match self.0.shutdown() {
+            Ok(_) => (),
+            Err(ref err) if err.code() == ErrorCode::ZERO_RETURN => (),
+            Err(err) => {
+                return match err.into_io_error() {
+                    Ok(ref err) if err.kind() == io::ErrorKind::WouldBlock => Ok(Async::NotReady),
+                    Ok(err) => Err(err),
+                    Err(err) => Err(io::Error::new(io::ErrorKind::Other, err)),
+                };
+            }
+        }
+
+        self.0.get_mut().shutdown()
+    }
+}
+
+/// Adds the role of the client of a connection to each of its requests, for `Authorization`.
+#[derive(Clone)]
+pub struct RoleService<T> {
+    role: Option<Role>,
+    inner: T,
+}
+
+impl<T> RoleService<T> {
+    pub fn new(role: Option<Role>, inner: T) -> Self {
+        RoleService { role, inner }
+    }
+}
+
+impl<T> Service for RoleService<T>
+where
+    T: Service<ReqBody = Body>,
+{
+    type ReqBody = T::ReqBody;
+    type ResBody = T::ResBody;
+    type Error = T::Error;
+    type Future = T::Future;
+
+    fn call(&mut self, req: Request<Self::ReqBody>) -> Self::Future {
+        let mut req = req;
+        if let Some(role) = self.role {
+            req.extensions_mut().insert(role);
+        }
+        self.inner.call(req)
+    }
+}
@@ -9,9 +9,9 @@
 //! hyper's `Service` trait so it can be used directly with its `Client` type.
 //! The `Service` trait's `Response` associated type is a struct named
 //! `StreamSelector` which is also defined in this module. `StreamSelector` is
-//! an enumeration that switches between a `TcpStream` or a `UnixStream` (or
-//! other kinds of streams in the future when we support more protocols) for
-//! HTTP and Unix sockets respectively.
+//! an enumeration that switches between a `TcpStream`, a `TlsStream` or a
+//! `UnixStream` (or other kinds of streams in the future when we support more
+//! protocols) for HTTP, HTTPS and Unix sockets respectively.
 
 use std::io;
 
@@ -22,20 +22,33 @@ use hyper::client::HttpConnector;
 use hyper::Uri;
 #[cfg(unix)]
 use hyperlocal::{UnixConnector, Uri as HyperlocalUri};
+use native_tls::{Certificate, TlsConnector as NativeTlsConnector};
+use tokio_tls::TlsConnector;
 use url::{ParseError, Url};
 
 use crate::error::{Error, ErrorKind, InvalidUrlReason};
 use crate::util::StreamSelector;
-use crate::{HTTP_SCHEME, UNIX_SCHEME};
+use crate::{PemCertificate, HTTPS_SCHEME, HTTP_SCHEME, UNIX_SCHEME};
 
 #[derive(Clone)]
 pub enum UrlConnector {
     Http(HttpConnector),
+    Https(HttpConnector, TlsConnector),
     Unix(UnixConnector),
 }
 
 impl UrlConnector {
     pub fn new(url: &Url) -> Result<Self, Error> {
+        UrlConnector::with_tls(url, None, None)
+    }
+
+    /// Like `new`, but an `https` URL is connected to with `client_cert` as the client
+    /// certificate, and the server certificate is also trusted if it is issued by `trusted_ca`.
+    pub fn with_tls(
+        url: &Url,
+        client_cert: Option<&PemCertificate>,
+        trusted_ca: Option<&[u8]>,
+    ) -> Result<Self, Error> {
         match url.scheme() {
             UNIX_SCHEME => Ok(UrlConnector::Unix(UnixConnector::new())),
 
@@ -45,6 +58,29 @@ impl UrlConnector {
                 //       this time.
                 Ok(UrlConnector::Http(HttpConnector::new(4)))
             }
+
+            HTTPS_SCHEME => {
+                let mut tls = NativeTlsConnector::builder();
+                if let Some(client_cert) = client_cert {
+                    tls.identity(client_cert.get_identity()?);
+                }
+                if let Some(trusted_ca) = trusted_ca {
+                    for cert in openssl::x509::X509::stack_from_pem(trusted_ca)
+                        .context(ErrorKind::TrustBundle)?
+                    {
+                        let cert = cert.to_der().context(ErrorKind::TrustBundle)?;
+                        tls.add_root_certificate(
+                            Certificate::from_der(&cert).context(ErrorKind::TrustBundle)?,
+                        );
+                    }
+                }
+                let tls = tls.build().context(ErrorKind::TlsBootstrapError)?;
+
+                let mut http = HttpConnector::new(4);
+                http.enforce_http(false);
+                Ok(UrlConnector::Https(http, tls.into()))
+            }
+
             _ => Err(ErrorKind::InvalidUrlWithReason(
                 url.to_string(),
                 InvalidUrlReason::InvalidScheme,
@@ -56,7 +92,7 @@ impl UrlConnector {
     pub fn build_hyper_uri(scheme: &str, base_path: &str, path: &str) -> Result<Uri, Error> {
         match &*scheme {
             UNIX_SCHEME => Ok(HyperlocalUri::new(base_path, &path).into()),
-            HTTP_SCHEME => Ok(Url::parse(base_path)
+            HTTP_SCHEME | HTTPS_SCHEME => Ok(Url::parse(base_path)
                 .and_then(|base| base.join(path))
                 .and_then(|url| url.as_str().parse().map_err(|_| ParseError::IdnaError))
                 .with_context(|_| ErrorKind::MalformedUrl {
@@ -78,6 +114,7 @@ impl std::fmt::Debug for UrlConnector {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             UrlConnector::Http(_) => f.debug_struct("Http").finish(),
+            UrlConnector::Https(..) => f.debug_struct("Https").finish(),
             UrlConnector::Unix(_) => f.debug_struct("UnixConnector").finish(),
         }
     }
@@ -93,6 +130,8 @@ impl Connect for UrlConnector {
         match (self, dst.scheme()) {
             (UrlConnector::Http(_), HTTP_SCHEME) => (),
 
+            (UrlConnector::Https(..), HTTPS_SCHEME) => (),
+
             (UrlConnector::Unix(_), UNIX_SCHEME) => (),
 
             (_, scheme) => {
@@ -110,6 +149,20 @@ impl Connect for UrlConnector {
                 })) as Self::Future
             }
 
+            UrlConnector::Https(connector, tls) => {
+                let tls = tls.clone();
+                let host = dst.host().to_owned();
+                Box::new(
+                    connector
+                        .connect(dst)
+                        .and_then(move |(tcp_stream, connected)| {
+                            tls.connect(&host, tcp_stream)
+                                .map(|tls_stream| (StreamSelector::Tls(tls_stream), connected))
+                                .map_err(|err| io::Error::new(io::ErrorKind::Other, err))
+                        }),
+                ) as Self::Future
+            }
+
             UrlConnector::Unix(connector) => {
                 Box::new(connector.connect(dst).and_then(|(unix_stream, connected)| {
                     Ok((StreamSelector::Unix(unix_stream), connected))
@@ -137,4 +190,17 @@ mod tests {
     fn create_http_succeeds() {
         let _connector = UrlConnector::new(&Url::parse("http://localhost:2375").unwrap()).unwrap();
     }
+
+    #[test]
+    fn create_https_succeeds() {
+        let _connector =
+            UrlConnector::new(&Url::parse("https://localhost:15580").unwrap()).unwrap();
+    }
+
+    #[test]
+    fn build_https_uri() {
+        let uri =
+            UrlConnector::build_hyper_uri("https", "https://localhost:15580", "/modules").unwrap();
+        assert_eq!("https://localhost:15580/modules", uri.to_string());
+    }
 }
@@ -16,6 +16,7 @@ use tokio_tls::TlsStream;
 use tokio_uds::UnixStream;
 
 use crate::pid::{Pid, UnixStreamExt};
+use crate::tls::MutualTlsStream;
 
 pub mod connector;
 mod hyperwrap;
@@ -28,6 +29,7 @@ pub use incoming::Incoming;
 pub enum StreamSelector {
     Tcp(TcpStream),
     Tls(TlsStream<TcpStream>),
+    MutualTls(MutualTlsStream),
     Unix(UnixStream),
 }
 
@@ -37,6 +39,8 @@ impl StreamSelector {
         match *self {
             StreamSelector::Tcp(_) => Ok(Pid::Any),
             StreamSelector::Tls(_) => Ok(Pid::Any),
+            // Clients of a mutual TLS listener are authorized by their role instead.
+            StreamSelector::MutualTls(_) => Ok(Pid::None),
             StreamSelector::Unix(ref stream) => stream.pid(),
         }
     }
@@ -47,6 +51,7 @@ impl Read for StreamSelector {
         match self {
             StreamSelector::Tcp(stream) => stream.read(buf),
             StreamSelector::Tls(stream) => stream.read(buf),
+            StreamSelector::MutualTls(stream) => stream.read(buf),
             StreamSelector::Unix(stream) => stream.read(buf),
         }
     }
@@ -57,6 +62,7 @@ impl Write for StreamSelector {
         match self {
             StreamSelector::Tcp(stream) => stream.write(buf),
             StreamSelector::Tls(stream) => stream.write(buf),
+            StreamSelector::MutualTls(stream) => stream.write(buf),
             StreamSelector::Unix(stream) => stream.write(buf),
         }
     }
@@ -65,6 +71,7 @@ impl Write for StreamSelector {
         match self {
             StreamSelector::Tcp(stream) => stream.flush(),
             StreamSelector::Tls(stream) => stream.flush(),
+            StreamSelector::MutualTls(stream) => stream.flush(),
             StreamSelector::Unix(stream) => stream.flush(),
         }
     }
@@ -76,6 +83,7 @@ impl AsyncRead for StreamSelector {
         match *self {
             StreamSelector::Tcp(ref stream) => stream.prepare_uninitialized_buffer(buf),
             StreamSelector::Tls(ref stream) => stream.prepare_uninitialized_buffer(buf),
+            StreamSelector::MutualTls(ref stream) => stream.prepare_uninitialized_buffer(buf),
             StreamSelector::Unix(ref stream) => stream.prepare_uninitialized_buffer(buf),
         }
     }
@@ -85,6 +93,7 @@ impl AsyncRead for StreamSelector {
         match self {
             StreamSelector::Tcp(stream) => stream.read_buf(buf),
             StreamSelector::Tls(stream) => stream.read_buf(buf),
+            StreamSelector::MutualTls(stream) => stream.read_buf(buf),
             StreamSelector::Unix(stream) => stream.read_buf(buf),
         }
     }
@@ -95,6 +104,7 @@ impl AsyncWrite for StreamSelector {
         match self {
             StreamSelector::Tcp(stream) => AsyncWrite::shutdown(stream),
             StreamSelector::Tls(stream) => TlsStream::shutdown(stream),
+            StreamSelector::MutualTls(stream) => AsyncWrite::shutdown(stream),
             StreamSelector::Unix(stream) => AsyncWrite::shutdown(stream),
         }
     }
@@ -104,6 +114,7 @@ impl AsyncWrite for StreamSelector {
         match self {
             StreamSelector::Tcp(stream) => stream.write_buf(buf),
             StreamSelector::Tls(stream) => stream.write_buf(buf),
+            StreamSelector::MutualTls(stream) => stream.write_buf(buf),
             StreamSelector::Unix(stream) => stream.write_buf(buf),
         }
     }
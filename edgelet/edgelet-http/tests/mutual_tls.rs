@@ -0,0 +1,169 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+#![deny(rust_2018_idioms, warnings)]
+#![deny(clippy::all, clippy::pedantic)]
+
+use std::collections::BTreeMap;
+use std::io;
+
+use futures::future;
+use futures::prelude::*;
+use hyper::server::conn::Http;
+use hyper::service::service_fn;
+use hyper::{Body, Client, Error as HyperError, Request, Response};
+use openssl::asn1::Asn1Time;
+use openssl::bn::{BigNum, MsbOption};
+use openssl::ec::{EcGroup, EcKey};
+use openssl::hash::MessageDigest;
+use openssl::nid::Nid;
+use openssl::pkey::{PKey, Private};
+use openssl::x509::extension::{BasicConstraints, SubjectAlternativeName};
+use openssl::x509::{X509Name, X509};
+use url::Url;
+
+use edgelet_core::{Protocol, Role};
+use edgelet_http::{HyperExt, MutualTls, PemCertificate, UrlConnector};
+
+struct Credentials {
+    cert: X509,
+    key: PKey<Private>,
+}
+
+impl Credentials {
+    fn new(common_name: &str, issuer: Option<&Credentials>) -> Self {
+        let key = PKey::from_ec_key(
+            EcKey::generate(&EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap()).unwrap(),
+        )
+        .unwrap();
+
+        let mut name = X509Name::builder().unwrap();
+        name.append_entry_by_nid(Nid::COMMONNAME, common_name)
+            .unwrap();
+        let name = name.build();
+
+        let mut serial = BigNum::new().unwrap();
+        serial.rand(64, MsbOption::MAYBE_ZERO, false).unwrap();
+
+        let mut cert = X509::builder().unwrap();
+        cert.set_version(2).unwrap();
+        cert.set_serial_number(&serial.to_asn1_integer().unwrap())
+            .unwrap();
+        cert.set_subject_name(&name).unwrap();
+        cert.set_pubkey(&key).unwrap();
+        cert.set_not_before(&Asn1Time::days_from_now(0).unwrap())
+            .unwrap();
+        cert.set_not_after(&Asn1Time::days_from_now(1).unwrap())
+            .unwrap();
+        let san = SubjectAlternativeName::new()
+            .dns("localhost")
+            .build(&cert.x509v3_context(issuer.map(|issuer| &*issuer.cert), None))
+            .unwrap();
+        cert.append_extension(san).unwrap();
+        if let Some(issuer) = issuer {
+            cert.set_issuer_name(issuer.cert.subject_name()).unwrap();
+            cert.sign(&issuer.key, MessageDigest::sha256()).unwrap();
+        } else {
+            cert.append_extension(BasicConstraints::new().critical().ca().build().unwrap())
+                .unwrap();
+            cert.set_issuer_name(&name).unwrap();
+            cert.sign(&key, MessageDigest::sha256()).unwrap();
+        }
+
+        Credentials {
+            cert: cert.build(),
+            key,
+        }
+    }
+
+    fn cert_pem(&self) -> Vec<u8> {
+        self.cert.to_pem().unwrap()
+    }
+
+    fn key_pem(&self) -> Vec<u8> {
+        self.key.private_key_to_pem_pkcs8().unwrap()
+    }
+
+    fn to_pem_certificate(&self) -> PemCertificate {
+        PemCertificate::new(self.cert_pem(), Some(self.key_pem()), None, None)
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn role_handler(req: Request<Body>) -> impl Future<Item = Response<Body>, Error = HyperError> {
+    let role = req
+        .extensions()
+        .get::<Role>()
+        .map_or_else(|| "none".to_string(), ToString::to_string);
+    future::ok(Response::new(role.into()))
+}
+
+fn get_role(ca: &Credentials, client: Option<&Credentials>) -> Result<String, HyperError> {
+    let server = Credentials::new("localhost", Some(ca));
+    let mut roles = BTreeMap::new();
+    roles.insert("fleet-operator".to_string(), Role::Operator);
+    let tls = MutualTls::new(
+        &server.cert_pem(),
+        &server.key_pem(),
+        &ca.cert_pem(),
+        roles,
+        Protocol::Tls12,
+    )
+    .unwrap();
+
+    let server = Http::new()
+        .bind_tls_url(Url::parse("https://127.0.0.1:0").unwrap(), tls, || {
+            Ok::<_, io::Error>(service_fn(role_handler))
+        })
+        .unwrap();
+    let url = format!("https://localhost:{}", server.port().unwrap());
+    let server = server.run().map_err(|err| panic!("{}", err));
+
+    let client_cert = client.map(Credentials::to_pem_certificate);
+    let connector = UrlConnector::with_tls(
+        &Url::parse(&url).unwrap(),
+        client_cert.as_ref(),
+        Some(&ca.cert_pem()),
+    )
+    .unwrap();
+    let client = Client::builder().build::<_, Body>(connector);
+    let task = client
+        .get(url.parse().unwrap())
+        .and_then(|res| res.into_body().concat2())
+        .map(|body| String::from_utf8_lossy(body.as_ref()).into_owned());
+
+    let mut runtime = tokio::runtime::Runtime::new().unwrap();
+    runtime.spawn(server);
+    runtime.block_on(task)
+}
+
+#[test]
+fn client_is_given_the_role_of_its_certificate() {
+    let ca = Credentials::new("test-ca", None);
+    let client = Credentials::new("fleet-operator", Some(&ca));
+
+    assert_eq!("operator", get_role(&ca, Some(&client)).unwrap());
+}
+
+#[test]
+fn client_without_a_role_is_rejected() {
+    let ca = Credentials::new("test-ca", None);
+    let client = Credentials::new("someone-else", Some(&ca));
+
+    assert!(get_role(&ca, Some(&client)).is_err());
+}
+
+#[test]
+fn client_with_a_certificate_from_another_ca_is_rejected() {
+    let ca = Credentials::new("test-ca", None);
+    let other_ca = Credentials::new("other-ca", None);
+    let client = Credentials::new("fleet-operator", Some(&other_ca));
+
+    assert!(get_role(&ca, Some(&client)).is_err());
+}
+
+#[test]
+fn client_without_a_certificate_is_rejected() {
+    let ca = Credentials::new("test-ca", None);
+
+    assert!(get_role(&ca, None).is_err());
+}
@@ -0,0 +1,287 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use std::io;
+use std::time::Duration;
+
+use bytes::Bytes;
+use failure::Fail;
+use futures::{Future, Stream};
+use hyper::{Body, Request};
+use log::{info, Level};
+
+use docker::models::InlineResponse20011;
+use edgelet_core::{
+    Authenticator, ExecOptions, LogOptions, MakeModuleRuntime, ModuleRegistry, ModuleRuntime,
+    ModuleSpec, RuntimeOperation,
+};
+use edgelet_docker::{DockerConfig, DockerModuleRuntime, Error, ErrorKind, Settings};
+use edgelet_utils::log_failure;
+
+/// The name Podman reports for itself among the components of its version.
+const PODMAN_COMPONENT: &str = "Podman Engine";
+
+/// The oldest Podman release whose Docker-compatible API has everything modules need.
+const MIN_PODMAN_VERSION: (u64, u64) = (3, 0);
+
+/// Runs modules with Podman.
+///
+/// Podman serves the Docker API, so modules are managed the same way as with Moby once the
+/// engine is known to be a supported release of Podman. The configuration is read from the
+/// `[moby_runtime]` section, with `engine = "podman"`.
+#[derive(Clone, Debug)]
+pub struct PodmanModuleRuntime {
+    inner: DockerModuleRuntime,
+}
+
+impl MakeModuleRuntime for PodmanModuleRuntime {
+    type Config = DockerConfig;
+    type Settings = Settings;
+    type ModuleRuntime = Self;
+    type Error = Error;
+    type Future = Box<dyn Future<Item = Self, Error = Self::Error> + Send>;
+    type ReloadFuture = <DockerModuleRuntime as MakeModuleRuntime>::ReloadFuture;
+
+    fn make_runtime(settings: Settings) -> Self::Future {
+        info!("Initializing Podman module runtime...");
+
+        let runtime = DockerModuleRuntime::make_runtime(settings).and_then(|inner| {
+            inner.version().and_then(move |version| {
+                check_engine(&version).map_err(|err| {
+                    log_failure(Level::Warn, &err);
+                    err
+                })?;
+                info!("Successfully initialized Podman module runtime");
+                Ok(PodmanModuleRuntime { inner })
+            })
+        });
+        Box::new(runtime)
+    }
+
+    fn reload(runtime: &Self, settings: &Settings) -> Self::ReloadFuture {
+        DockerModuleRuntime::reload(&runtime.inner, settings)
+    }
+}
+
+fn check_engine(version: &InlineResponse20011) -> Result<(), Error> {
+    let unsupported = |reason: String| {
+        Error::from(
+            ErrorKind::UnsupportedContainerEngine(reason)
+                .context(ErrorKind::RuntimeOperation(RuntimeOperation::Init)),
+        )
+    };
+
+    let podman = version
+        .components()
+        .unwrap_or_default()
+        .iter()
+        .find(|component| component.name() == PODMAN_COMPONENT)
+        .ok_or_else(|| unsupported("the engine is not Podman".to_string()))?;
+
+    match parse_version(podman.version()) {
+        Some(podman_version) if podman_version >= MIN_PODMAN_VERSION => Ok(()),
+        _ => Err(unsupported(format!(
+            "Podman {} is older than {}.{}",
+            podman.version(),
+            MIN_PODMAN_VERSION.0,
+            MIN_PODMAN_VERSION.1,
+        ))),
+    }
+}
+
+/// Parses the major and minor parts of a version like `4.3.1` or `3.0.0-rc1`.
+fn parse_version(version: &str) -> Option<(u64, u64)> {
+    let mut parts = version.split(|c| c == '.' || c == '-');
+    let major = parts.next()?.parse().ok()?;
+    let minor = parts.next()?.parse().ok()?;
+    Some((major, minor))
+}
+
+impl ModuleRegistry for PodmanModuleRuntime {
+    type Error = Error;
+    type PullFuture = <DockerModuleRuntime as ModuleRegistry>::PullFuture;
+    type RemoveFuture = <DockerModuleRuntime as ModuleRegistry>::RemoveFuture;
+    type ListImagesFuture = <DockerModuleRuntime as ModuleRegistry>::ListImagesFuture;
+    type Config = DockerConfig;
+
+    fn pull(&self, config: &Self::Config) -> Self::PullFuture {
+        ModuleRegistry::pull(&self.inner, config)
+    }
+
+    fn remove(&self, name: &str) -> Self::RemoveFuture {
+        ModuleRegistry::remove(&self.inner, name)
+    }
+
+    fn list_images(&self) -> Self::ListImagesFuture {
+        self.inner.list_images()
+    }
+}
+
+impl ModuleRuntime for PodmanModuleRuntime {
+    type Error = Error;
+    type Config = DockerConfig;
+    type Module = <DockerModuleRuntime as ModuleRuntime>::Module;
+    type ModuleRegistry = Self;
+    type Chunk = <DockerModuleRuntime as ModuleRuntime>::Chunk;
+    type Logs = <DockerModuleRuntime as ModuleRuntime>::Logs;
+    type Archive = <DockerModuleRuntime as ModuleRuntime>::Archive;
+
+    type CreateFuture = <DockerModuleRuntime as ModuleRuntime>::CreateFuture;
+    type GetFuture = <DockerModuleRuntime as ModuleRuntime>::GetFuture;
+    type ListFuture = <DockerModuleRuntime as ModuleRuntime>::ListFuture;
+    type ListWithDetailsStream = <DockerModuleRuntime as ModuleRuntime>::ListWithDetailsStream;
+    type LogsFuture = <DockerModuleRuntime as ModuleRuntime>::LogsFuture;
+    type RemoveFuture = <DockerModuleRuntime as ModuleRuntime>::RemoveFuture;
+    type RestartFuture = <DockerModuleRuntime as ModuleRuntime>::RestartFuture;
+    type StartFuture = <DockerModuleRuntime as ModuleRuntime>::StartFuture;
+    type StopFuture = <DockerModuleRuntime as ModuleRuntime>::StopFuture;
+    type SystemInfoFuture = <DockerModuleRuntime as ModuleRuntime>::SystemInfoFuture;
+    type SystemResourcesFuture = <DockerModuleRuntime as ModuleRuntime>::SystemResourcesFuture;
+    type RemoveAllFuture = <DockerModuleRuntime as ModuleRuntime>::RemoveAllFuture;
+    type StartAllFuture = <DockerModuleRuntime as ModuleRuntime>::StartAllFuture;
+    type StopAllFuture = <DockerModuleRuntime as ModuleRuntime>::StopAllFuture;
+    type ExecIo = <DockerModuleRuntime as ModuleRuntime>::ExecIo;
+    type ExecExitCodeFuture = <DockerModuleRuntime as ModuleRuntime>::ExecExitCodeFuture;
+    type ExecFuture = <DockerModuleRuntime as ModuleRuntime>::ExecFuture;
+    type GetArchiveFuture = <DockerModuleRuntime as ModuleRuntime>::GetArchiveFuture;
+    type PutArchiveFuture = <DockerModuleRuntime as ModuleRuntime>::PutArchiveFuture;
+
+    fn create(&self, module: ModuleSpec<Self::Config>) -> Self::CreateFuture {
+        self.inner.create(module)
+    }
+
+    fn get(&self, id: &str) -> Self::GetFuture {
+        self.inner.get(id)
+    }
+
+    fn start(&self, id: &str) -> Self::StartFuture {
+        self.inner.start(id)
+    }
+
+    fn stop(&self, id: &str, wait_before_kill: Option<Duration>) -> Self::StopFuture {
+        self.inner.stop(id, wait_before_kill)
+    }
+
+    fn restart(&self, id: &str) -> Self::RestartFuture {
+        self.inner.restart(id)
+    }
+
+    fn remove(&self, id: &str) -> Self::RemoveFuture {
+        ModuleRuntime::remove(&self.inner, id)
+    }
+
+    fn system_info(&self) -> Self::SystemInfoFuture {
+        self.inner.system_info()
+    }
+
+    fn system_resources(&self) -> Self::SystemResourcesFuture {
+        self.inner.system_resources()
+    }
+
+    fn list(&self) -> Self::ListFuture {
+        self.inner.list()
+    }
+
+    fn list_with_details(&self) -> Self::ListWithDetailsStream {
+        self.inner.list_with_details()
+    }
+
+    fn logs(&self, id: &str, options: &LogOptions) -> Self::LogsFuture {
+        self.inner.logs(id, options)
+    }
+
+    fn registry(&self) -> &Self::ModuleRegistry {
+        self
+    }
+
+    fn remove_all(&self) -> Self::RemoveAllFuture {
+        self.inner.remove_all()
+    }
+
+    fn start_all(&self) -> Self::StartAllFuture {
+        self.inner.start_all()
+    }
+
+    fn stop_all(&self, wait_before_kill: Option<Duration>) -> Self::StopAllFuture {
+        self.inner.stop_all(wait_before_kill)
+    }
+
+    fn exec(&self, id: &str, options: &ExecOptions) -> Self::ExecFuture {
+        self.inner.exec(id, options)
+    }
+
+    fn get_archive(&self, id: &str, path: &str) -> Self::GetArchiveFuture {
+        self.inner.get_archive(id, path)
+    }
+
+    fn put_archive<S>(&self, id: &str, path: &str, archive: S) -> Self::PutArchiveFuture
+    where
+        S: Stream<Item = Bytes, Error = io::Error> + Send + 'static,
+    {
+        self.inner.put_archive(id, path, archive)
+    }
+}
+
+impl Authenticator for PodmanModuleRuntime {
+    type Error = Error;
+    type Request = Request<Body>;
+    type AuthenticateFuture = <DockerModuleRuntime as Authenticator>::AuthenticateFuture;
+
+    fn authenticate(&self, req: &Self::Request) -> Self::AuthenticateFuture {
+        self.inner.authenticate(req)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use docker::models::{InlineResponse20011, InlineResponse20011Components};
+    use edgelet_docker::ErrorKind;
+    use failure::Fail;
+
+    use super::{check_engine, parse_version};
+
+    fn version(name: &str, version: &str) -> InlineResponse20011 {
+        InlineResponse20011::new().with_components(vec![InlineResponse20011Components::new(
+            name.to_string(),
+            version.to_string(),
+        )])
+    }
+
+    #[test]
+    fn parse_versions() {
+        assert_eq!(Some((4, 3)), parse_version("4.3.1"));
+        assert_eq!(Some((3, 0)), parse_version("3.0.0-rc1"));
+        assert_eq!(Some((3, 4)), parse_version("3.4-dev"));
+        assert_eq!(None, parse_version("4"));
+        assert_eq!(None, parse_version("dev"));
+    }
+
+    #[test]
+    fn supported_podman() {
+        check_engine(&version("Podman Engine", "3.0.1")).unwrap();
+        check_engine(&version("Podman Engine", "4.5.0")).unwrap();
+    }
+
+    #[test]
+    fn old_podman_is_unsupported() {
+        let err = check_engine(&version("Podman Engine", "2.2.1")).unwrap_err();
+        match err.kind() {
+            ErrorKind::RuntimeOperation(_) => (),
+            kind => panic!("unexpected error kind {:?}", kind),
+        }
+        assert_eq!(
+            "Unsupported container engine: Podman 2.2.1 is older than 3.0",
+            err.cause().unwrap().to_string()
+        );
+    }
+
+    #[test]
+    fn docker_is_unsupported() {
+        let err = check_engine(&version("Engine", "20.10.7")).unwrap_err();
+        assert_eq!(
+            "Unsupported container engine: the engine is not Podman",
+            err.cause().unwrap().to_string()
+        );
+
+        check_engine(&InlineResponse20011::new()).unwrap_err();
+    }
+}
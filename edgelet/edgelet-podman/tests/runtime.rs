@@ -0,0 +1,133 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+#![deny(rust_2018_idioms, warnings)]
+#![deny(clippy::all, clippy::pedantic)]
+#![allow(clippy::default_trait_access, clippy::too_many_lines)]
+
+use std::collections::BTreeMap;
+
+use failure::Fail;
+use futures::future;
+use futures::prelude::*;
+use hyper::{Body, Method, Request, Response};
+use maplit::btreemap;
+use serde_json::json;
+use typed_headers::{mime, ContentLength, ContentType, HeaderMapExt};
+
+use edgelet_core::{MakeModuleRuntime, RuntimeOperation};
+use edgelet_docker::{Error, ErrorKind};
+use edgelet_podman::PodmanModuleRuntime;
+use edgelet_test_utils::web::{
+    make_req_dispatcher, HttpMethod, RequestHandler, RequestPath, ResponseFuture,
+};
+use edgelet_test_utils::{routes, run_tcp_server};
+
+#[path = "../../edgelet-docker/tests/suite/mod.rs"]
+mod suite;
+
+use suite::{
+    default_create_network_handler, default_get_networks_handler, make_settings, not_found_handler,
+};
+
+type RuntimeUnderTest = PodmanModuleRuntime;
+
+fn engine_routes() -> BTreeMap<(HttpMethod, RequestPath), RequestHandler> {
+    routes!(
+        GET "/version" => podman_version_handler,
+    )
+}
+
+fn make_version_handler(
+    name: &'static str,
+    version: &'static str,
+) -> impl Fn(Request<Body>) -> ResponseFuture + Clone {
+    move |_| {
+        let response = json!({
+            "Version": version,
+            "ApiVersion": "1.40",
+            "Components": [
+                {
+                    "Name": name,
+                    "Version": version,
+                }
+            ],
+        })
+        .to_string();
+        let response_len = response.len();
+
+        let mut response = Response::new(response.into());
+        response
+            .headers_mut()
+            .typed_insert(&ContentLength(response_len as u64));
+        response
+            .headers_mut()
+            .typed_insert(&ContentType(mime::APPLICATION_JSON));
+        Box::new(future::ok(response)) as ResponseFuture
+    }
+}
+
+fn podman_version_handler(req: Request<Body>) -> ResponseFuture {
+    make_version_handler("Podman Engine", "4.3.1")(req)
+}
+
+fn runtime_init_fails_for_engine(name: &'static str, version: &'static str) -> Error {
+    let dispatch_table = routes!(
+        GET "/networks" => default_get_networks_handler(),
+        POST "/networks/create" => default_create_network_handler(),
+        GET "/version" => make_version_handler(name, version),
+    );
+
+    let (server, port) = run_tcp_server(
+        "127.0.0.1",
+        make_req_dispatcher(dispatch_table, Box::new(not_found_handler)),
+    );
+    let server = server.map_err(|err| panic!(err));
+
+    let settings = make_settings(&format!(
+        r#"
+[moby_runtime]
+uri = "http://localhost:{}"
+network = "azure-iot-edge"
+engine = "podman"
+"#,
+        port
+    ));
+
+    let task = PodmanModuleRuntime::make_runtime(settings);
+
+    let mut runtime = tokio::runtime::current_thread::Runtime::new().unwrap();
+    runtime.spawn(server);
+    runtime.block_on(task).unwrap_err()
+}
+
+#[test]
+fn runtime_init_fails_for_docker() {
+    let err = runtime_init_fails_for_engine("Engine", "20.10.7");
+
+    match err.kind() {
+        ErrorKind::RuntimeOperation(RuntimeOperation::Init) => (),
+        kind => panic!("expected RuntimeOperation(Init) but got {:?}", kind),
+    }
+    match err
+        .cause()
+        .and_then(|cause| cause.downcast_ref::<ErrorKind>())
+    {
+        Some(ErrorKind::UnsupportedContainerEngine(_)) => (),
+        cause => panic!("expected UnsupportedContainerEngine but got {:?}", cause),
+    }
+}
+
+#[test]
+fn runtime_init_fails_for_old_podman() {
+    let err = runtime_init_fails_for_engine("Podman Engine", "2.2.1");
+
+    match err
+        .cause()
+        .and_then(|cause| cause.downcast_ref::<ErrorKind>())
+    {
+        Some(ErrorKind::UnsupportedContainerEngine(reason)) => {
+            assert_eq!("Podman 2.2.1 is older than 3.0", reason);
+        }
+        cause => panic!("expected UnsupportedContainerEngine but got {:?}", cause),
+    }
+}
@@ -0,0 +1,122 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+//! Resource limits for modules, applied with a cgroup (v2) per module.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use crate::config::ResourceLimits;
+
+const CPU_PERIOD: u64 = 100_000;
+const CONTROLLERS: &str = "+cpu +memory +pids";
+
+#[derive(Clone, Debug)]
+pub(crate) struct Cgroup {
+    path: PathBuf,
+}
+
+impl Cgroup {
+    pub(crate) fn new(root: &Path, name: &str) -> Self {
+        Cgroup {
+            path: root.join(name),
+        }
+    }
+
+    /// Creates the module's cgroup, if it doesn't exist yet, and sets its limits. Limits that
+    /// aren't set are reset, so that they don't outlive a change to the module.
+    pub(crate) fn apply(&self, limits: &ResourceLimits) -> io::Result<()> {
+        fs::create_dir_all(&self.path)?;
+
+        let max = |limit: Option<u64>| limit.map_or_else(|| "max".to_string(), |l| l.to_string());
+        fs::write(self.path.join("memory.max"), max(limits.memory))?;
+        fs::write(self.path.join("pids.max"), max(limits.pids))?;
+        fs::write(self.path.join("cpu.max"), cpu_max(limits.cpus))?;
+
+        Ok(())
+    }
+
+    /// Opens the file that processes are moved into the cgroup with.
+    pub(crate) fn procs(&self) -> io::Result<File> {
+        OpenOptions::new()
+            .write(true)
+            .open(self.path.join("cgroup.procs"))
+    }
+
+    /// Whether the process is in the cgroup. Only aziot-edged can move processes between
+    /// cgroups, so modules can't join each other's.
+    pub(crate) fn contains(&self, pid: i32) -> io::Result<bool> {
+        let procs = match fs::read_to_string(self.path.join("cgroup.procs")) {
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(false),
+            procs => procs?,
+        };
+        let pid = pid.to_string();
+        Ok(procs.lines().any(|line| line == pid))
+    }
+
+    /// Removes the cgroup. It must not have any processes left.
+    pub(crate) fn remove(&self) -> io::Result<()> {
+        match fs::remove_dir(&self.path) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Enables the controllers that limits are set with for the cgroups under `root`.
+pub(crate) fn enable_controllers(root: &Path) -> io::Result<()> {
+    fs::create_dir_all(root)?;
+    OpenOptions::new()
+        .write(true)
+        .open(root.join("cgroup.subtree_control"))?
+        .write_all(CONTROLLERS.as_bytes())
+}
+
+fn cpu_max(cpus: Option<f64>) -> String {
+    match cpus {
+        #[allow(
+            clippy::cast_possible_truncation,
+            clippy::cast_precision_loss,
+            clippy::cast_sign_loss
+        )]
+        Some(cpus) if cpus > 0.0 => format!("{} {}", (cpus * CPU_PERIOD as f64) as u64, CPU_PERIOD),
+        _ => format!("max {}", CPU_PERIOD),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::{cpu_max, Cgroup, ResourceLimits};
+
+    #[test]
+    fn cpu_limits() {
+        assert_eq!("50000 100000", cpu_max(Some(0.5)));
+        assert_eq!("200000 100000", cpu_max(Some(2.0)));
+        assert_eq!("max 100000", cpu_max(None));
+        assert_eq!("max 100000", cpu_max(Some(0.0)));
+    }
+
+    #[test]
+    fn limits_are_written() {
+        let root = tempfile::tempdir().unwrap();
+        let cgroup = Cgroup::new(root.path(), "plc");
+
+        cgroup
+            .apply(&ResourceLimits {
+                memory: Some(1024),
+                cpus: Some(1.5),
+                pids: None,
+            })
+            .unwrap();
+
+        let read = |file| fs::read_to_string(root.path().join("plc").join(file)).unwrap();
+        assert_eq!("1024", read("memory.max"));
+        assert_eq!("150000 100000", read("cpu.max"));
+        assert_eq!("max", read("pids.max"));
+
+        cgroup.apply(&ResourceLimits::default()).unwrap();
+        assert_eq!("max", read("memory.max"));
+    }
+}
@@ -0,0 +1,172 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use std::path::{Path, PathBuf};
+
+use edgelet_core::module::{ModuleImage, NestedEdgeBodge};
+use edgelet_utils::ensure_not_empty_with_context;
+
+use crate::error::{ErrorKind, Result};
+
+/// How to run a module as a process on the host.
+#[derive(Debug, serde_derive::Serialize, serde_derive::Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ProcessConfig {
+    /// The executable to run. Commands without a `/` are looked up in the default `PATH`.
+    pub command: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub args: Vec<String>,
+    /// Defaults to the module's data directory, which only the module's user can access.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub working_dir: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resource_limits: Option<ResourceLimits>,
+}
+
+impl ProcessConfig {
+    pub fn new(command: String) -> Result<Self> {
+        ensure_not_empty_with_context(&command, || ErrorKind::InvalidCommand(command.clone()))?;
+
+        Ok(ProcessConfig {
+            command,
+            args: Vec::new(),
+            working_dir: None,
+            resource_limits: None,
+        })
+    }
+
+    pub fn command(&self) -> &str {
+        &self.command
+    }
+
+    pub fn args(&self) -> &[String] {
+        &self.args
+    }
+
+    pub fn with_args(mut self, args: Vec<String>) -> Self {
+        self.args = args;
+        self
+    }
+
+    pub fn working_dir(&self) -> Option<&Path> {
+        self.working_dir.as_deref()
+    }
+
+    pub fn with_working_dir(mut self, working_dir: PathBuf) -> Self {
+        self.working_dir = Some(working_dir);
+        self
+    }
+
+    pub fn resource_limits(&self) -> Option<&ResourceLimits> {
+        self.resource_limits.as_ref()
+    }
+
+    pub fn with_resource_limits(mut self, resource_limits: ResourceLimits) -> Self {
+        self.resource_limits = Some(resource_limits);
+        self
+    }
+}
+
+/// Limits on the resources a module's processes can use, applied with a cgroup (v2) per module.
+#[derive(Clone, Debug, Default, serde_derive::Deserialize, PartialEq, serde_derive::Serialize)]
+pub struct ResourceLimits {
+    /// Memory limit in bytes.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memory: Option<u64>,
+    /// Number of CPUs the module's processes can use at most, like 0.5 or 2.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cpus: Option<f64>,
+    /// Largest number of processes and threads the module can have at once.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pids: Option<u64>,
+}
+
+impl ResourceLimits {
+    pub fn is_empty(&self) -> bool {
+        self.memory.is_none() && self.cpus.is_none() && self.pids.is_none()
+    }
+}
+
+pub const UPSTREAM_PARENT_KEYWORD: &str = "$upstream";
+
+impl ModuleImage for ProcessConfig {
+    fn image(&self) -> &str {
+        &self.command
+    }
+
+    fn image_id(&self) -> Option<&str> {
+        None
+    }
+}
+
+impl NestedEdgeBodge for ProcessConfig {
+    fn parent_hostname_resolve(&mut self, parent_hostname: &str) {
+        for arg in &mut self.args {
+            if arg.contains(UPSTREAM_PARENT_KEYWORD) {
+                *arg = arg.replace(UPSTREAM_PARENT_KEYWORD, parent_hostname);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use edgelet_core::module::NestedEdgeBodge;
+
+    use super::{ProcessConfig, ResourceLimits};
+
+    #[test]
+    fn empty_command_fails() {
+        ProcessConfig::new(String::new()).unwrap_err();
+        ProcessConfig::new("   ".to_string()).unwrap_err();
+    }
+
+    #[test]
+    fn deserialize() {
+        let config: ProcessConfig = serde_json::from_str(
+            r#"{
+                "command": "/usr/bin/plc-adapter",
+                "args": ["--port", "502"],
+                "workingDir": "/var/lib/plc",
+                "resourceLimits": { "memory": 67108864, "cpus": 0.5 }
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!("/usr/bin/plc-adapter", config.command());
+        assert_eq!(&["--port", "502"], config.args());
+        assert_eq!(
+            Some(std::path::Path::new("/var/lib/plc")),
+            config.working_dir()
+        );
+        assert_eq!(
+            Some(&ResourceLimits {
+                memory: Some(64 * 1024 * 1024),
+                cpus: Some(0.5),
+                pids: None,
+            }),
+            config.resource_limits()
+        );
+
+        let config: ProcessConfig = serde_json::from_str(r#"{ "command": "plc" }"#).unwrap();
+        assert!(config.args().is_empty());
+        assert!(config.resource_limits().is_none());
+    }
+
+    #[test]
+    fn upstream_in_args_is_resolved() {
+        let mut config = ProcessConfig::new("/usr/bin/plc-adapter".to_string())
+            .unwrap()
+            .with_args(vec![
+                "--hub".to_string(),
+                "$upstream:8883".to_string(),
+                "--verbose".to_string(),
+            ]);
+
+        config.parent_hostname_resolve("parent.contoso.local");
+
+        assert_eq!(
+            &["--hub", "parent.contoso.local:8883", "--verbose"],
+            config.args()
+        );
+    }
+}
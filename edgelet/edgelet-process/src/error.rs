@@ -0,0 +1,131 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use std::fmt;
+use std::fmt::Display;
+
+use failure::{Backtrace, Context, Fail};
+
+use edgelet_core::{
+    ModuleOperation, ModuleRuntimeErrorReason, RegistryOperation, RuntimeOperation,
+};
+
+pub type Result<T> = ::std::result::Result<T, Error>;
+
+#[derive(Debug)]
+pub struct Error {
+    inner: Context<ErrorKind>,
+}
+
+#[derive(Debug, Fail)]
+pub enum ErrorKind {
+    #[fail(display = "Could not apply resource limits to module {}", _0)]
+    Cgroup(String),
+
+    #[fail(
+        display = "Module {} has resource limits, but process_runtime.cgroup_root is not set",
+        _0
+    )]
+    CgroupRootNotSet(String),
+
+    #[fail(display = "Command {:?} was not found or is not executable", _0)]
+    CommandNotFound(String),
+
+    #[fail(display = "Module {} already exists", _0)]
+    Conflict(String),
+
+    #[fail(display = "Could not initialize module runtime")]
+    Initialization,
+
+    #[fail(display = "Invalid command {:?}", _0)]
+    InvalidCommand(String),
+
+    #[fail(display = "Invalid module name {:?}", _0)]
+    InvalidModuleName(String),
+
+    #[fail(display = "Invalid module type {:?}", _0)]
+    InvalidModuleType(String),
+
+    #[fail(display = "{}", _0)]
+    ModuleOperation(ModuleOperation),
+
+    #[fail(display = "Could not run module {} as user {:?}", _0, _1)]
+    ModuleUser(String, String),
+
+    #[fail(
+        display = "Module {} has no user to run as, set process_runtime.default_user or process_runtime.module_users",
+        _0
+    )]
+    ModuleUserNotSet(String),
+
+    #[fail(display = "Module {} is not running", _0)]
+    NotRunning(String),
+
+    #[fail(display = "No such module: {}", _0)]
+    NotFound(String),
+
+    #[fail(display = "Target of operation already in this state")]
+    NotModified,
+
+    #[fail(
+        display = "Module {} can't run as user {:?}, which is root or the user aziot-edged runs as",
+        _0, _1
+    )]
+    PrivilegedModuleUser(String, String),
+
+    #[fail(display = "{}", _0)]
+    RegistryOperation(RegistryOperation),
+
+    #[fail(display = "{}", _0)]
+    RuntimeOperation(RuntimeOperation),
+
+    #[fail(display = "Could not save the state of module {}", _0)]
+    SaveState(String),
+
+    #[fail(display = "{} is not supported for process modules", _0)]
+    Unsupported(&'static str),
+}
+
+impl Fail for Error {
+    fn cause(&self) -> Option<&dyn Fail> {
+        self.inner.cause()
+    }
+
+    fn backtrace(&self) -> Option<&Backtrace> {
+        self.inner.backtrace()
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        Display::fmt(&self.inner, f)
+    }
+}
+
+impl Error {
+    pub fn kind(&self) -> &ErrorKind {
+        self.inner.get_context()
+    }
+}
+
+impl From<ErrorKind> for Error {
+    fn from(kind: ErrorKind) -> Self {
+        Error {
+            inner: Context::new(kind),
+        }
+    }
+}
+
+impl From<Context<ErrorKind>> for Error {
+    fn from(inner: Context<ErrorKind>) -> Self {
+        Error { inner }
+    }
+}
+
+impl<'a> From<&'a Error> for ModuleRuntimeErrorReason {
+    fn from(err: &'a Error) -> Self {
+        match Fail::find_root_cause(err).downcast_ref::<ErrorKind>() {
+            Some(ErrorKind::NotFound(_)) => ModuleRuntimeErrorReason::NotFound,
+            _ => ModuleRuntimeErrorReason::Other,
+        }
+    }
+}
@@ -0,0 +1,116 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use std::io::{self, Read, Write};
+use std::net::Shutdown;
+
+use futures::{Async, Poll};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio_uds::UnixStream;
+
+const FRAME_HEADER_LEN: usize = 8;
+
+/// The input and output of a command run in a module.
+///
+/// Without a terminal, stdout and stderr are read as frames with the same header as logs,
+/// like the output of commands run in containers. With one, they're read as a single stream.
+#[derive(Debug)]
+pub struct ExecIo {
+    /// The command's stdin and stdout, and stderr with a terminal.
+    stdio: UnixStream,
+    stderr: Option<UnixStream>,
+    stdout_done: bool,
+    stderr_done: bool,
+}
+
+impl ExecIo {
+    pub(crate) fn new(stdio: UnixStream, stderr: Option<UnixStream>) -> Self {
+        ExecIo {
+            stdio,
+            stderr,
+            stdout_done: false,
+            stderr_done: false,
+        }
+    }
+}
+
+impl Read for ExecIo {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let stderr = match &mut self.stderr {
+            Some(stderr) => stderr,
+            None => return self.stdio.read(buf),
+        };
+
+        let mut would_block = false;
+        for (stream_type, stream, done) in &mut [
+            (1, &mut self.stdio, &mut self.stdout_done),
+            (2, stderr, &mut self.stderr_done),
+        ] {
+            if **done {
+                continue;
+            }
+            match read_frame(*stream_type, stream, buf) {
+                Ok(0) => **done = true,
+                Ok(n) => return Ok(n),
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => would_block = true,
+                Err(err) => return Err(err),
+            }
+        }
+
+        if would_block {
+            Err(io::ErrorKind::WouldBlock.into())
+        } else {
+            Ok(0)
+        }
+    }
+}
+
+/// Reads from `stream` into a frame in `buf`. Returns 0 once the stream ends.
+fn read_frame(stream_type: u8, stream: &mut UnixStream, buf: &mut [u8]) -> io::Result<usize> {
+    if buf.len() <= FRAME_HEADER_LEN {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "buffer is too small for a frame",
+        ));
+    }
+
+    let n = stream.read(&mut buf[FRAME_HEADER_LEN..])?;
+    if n == 0 {
+        return Ok(0);
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    let len = (n as u32).to_be_bytes();
+    buf[..FRAME_HEADER_LEN].copy_from_slice(&[
+        stream_type,
+        0,
+        0,
+        0,
+        len[0],
+        len[1],
+        len[2],
+        len[3],
+    ]);
+    Ok(FRAME_HEADER_LEN + n)
+}
+
+impl AsyncRead for ExecIo {}
+
+impl Write for ExecIo {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.stdio.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stdio.flush()
+    }
+}
+
+impl AsyncWrite for ExecIo {
+    /// Closes the command's stdin, while its output can still be read.
+    fn shutdown(&mut self) -> Poll<(), io::Error> {
+        match self.stdio.shutdown(Shutdown::Write) {
+            Err(err) if err.kind() != io::ErrorKind::NotConnected => Err(err),
+            _ => Ok(Async::Ready(())),
+        }
+    }
+}
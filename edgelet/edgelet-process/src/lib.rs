@@ -0,0 +1,33 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+#![deny(rust_2018_idioms, warnings)]
+#![deny(clippy::all, clippy::pedantic)]
+#![allow(
+    clippy::default_trait_access,
+    clippy::missing_errors_doc,
+    clippy::module_name_repetitions,
+    clippy::must_use_candidate,
+    clippy::too_many_lines,
+    clippy::use_self
+)]
+
+mod cgroup;
+mod config;
+mod error;
+mod exec;
+mod logs;
+mod module;
+mod proc;
+mod runtime;
+mod settings;
+mod startup;
+mod supervisor;
+mod user;
+
+pub use config::{ProcessConfig, ResourceLimits, UPSTREAM_PARENT_KEYWORD};
+pub use error::{Error, ErrorKind};
+pub use exec::ExecIo;
+pub use logs::Logs;
+pub use module::{ProcessModule, MODULE_TYPE};
+pub use runtime::{Archive, ProcessModuleRuntime};
+pub use settings::{LoadSettingsError, ProcessRuntime, Settings, CONFIG_FILE_DEFAULT};
@@ -0,0 +1,463 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+//! Output of modules' processes.
+//!
+//! Lines that a module writes to stdout and stderr are stored in its log file in the same
+//! frames that Docker returns logs in, so that they can be read with `LogDecode` like the logs
+//! of containers. The payload of each frame starts with the time the line was written, in
+//! RFC 3339 format, and a space. Once the file is larger than the runtime's `max_log_size`,
+//! it's moved aside to `<file>.1`, replacing the older logs there.
+
+use std::collections::VecDeque;
+use std::convert::TryFrom;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::os::unix::fs::{MetadataExt, OpenOptionsExt};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use bytes::Bytes;
+use chrono::{DateTime, SecondsFormat, Utc};
+use failure::{Fail, ResultExt};
+use futures::{Async, Poll, Stream};
+use hyper::Body;
+use log::warn;
+use tokio::timer::Interval;
+
+use edgelet_core::{LogChunk, LogOptions, LogTail, RuntimeOperation};
+
+use crate::error::{Error, ErrorKind};
+
+const FRAME_HEADER_LEN: usize = 8;
+const MAX_LINE_LEN: u64 = 16 * 1024;
+const FOLLOW_POLL_INTERVAL: Duration = Duration::from_millis(250);
+/// Only aziot-edged can read modules' logs.
+const LOG_FILE_MODE: u32 = 0o600;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum OutputStream {
+    Stdout,
+    Stderr,
+}
+
+/// Appends the output of a module's processes to its log file.
+#[derive(Debug)]
+pub(crate) struct LogWriter {
+    path: PathBuf,
+    max_size: u64,
+    file: Mutex<(File, u64)>,
+}
+
+impl LogWriter {
+    pub(crate) fn open(path: &Path, max_size: u64) -> io::Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .mode(LOG_FILE_MODE)
+            .open(path)?;
+        let size = file.metadata()?.len();
+        Ok(LogWriter {
+            path: path.to_path_buf(),
+            max_size,
+            file: Mutex::new((file, size)),
+        })
+    }
+
+    fn write_line(&self, stream: OutputStream, line: &[u8]) -> io::Result<()> {
+        let mut payload = Utc::now()
+            .to_rfc3339_opts(SecondsFormat::Nanos, true)
+            .into_bytes();
+        payload.push(b' ');
+        payload.extend_from_slice(line);
+        let payload = Bytes::from(payload);
+        let frame = match stream {
+            OutputStream::Stdout => LogChunk::Stdout(payload),
+            OutputStream::Stderr => LogChunk::Stderr(payload),
+        }
+        .encode();
+
+        let mut file = self.file.lock().expect("log file lock poisoned");
+        if file.1 >= self.max_size {
+            fs::rename(&self.path, rotated(&self.path))?;
+            *file = (
+                OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .mode(LOG_FILE_MODE)
+                    .open(&self.path)?,
+                0,
+            );
+        }
+        file.0.write_all(&frame)?;
+        file.1 += frame.len() as u64;
+        Ok(())
+    }
+}
+
+/// Copies lines from one of a process's output streams to the module's log file, until the
+/// stream is closed.
+pub(crate) fn capture<R>(name: &str, output: R, stream: OutputStream, writer: Arc<LogWriter>)
+where
+    R: Read + Send + 'static,
+{
+    let name = name.to_string();
+    thread::spawn(move || {
+        let mut output = BufReader::new(output);
+        let mut line = vec![];
+        loop {
+            line.clear();
+            // Long lines are split, so that a process can't make the runtime buffer a line of
+            // any length.
+            match output
+                .by_ref()
+                .take(MAX_LINE_LEN)
+                .read_until(b'\n', &mut line)
+            {
+                Ok(0) => break,
+                Ok(_) => {
+                    if let Err(err) = writer.write_line(stream, &line) {
+                        warn!("Could not write the logs of module {}: {}", name, err);
+                    }
+                }
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => (),
+                Err(err) => {
+                    warn!("Could not read the output of module {}: {}", name, err);
+                    break;
+                }
+            }
+        }
+    });
+}
+
+/// The logs of a module, as a stream of frames.
+pub struct Logs {
+    name: String,
+    frames: VecDeque<Bytes>,
+    follow: Option<Follow>,
+}
+
+struct Follow {
+    path: PathBuf,
+    file: File,
+    pending: Vec<u8>,
+    filter: Filter,
+    interval: Interval,
+    is_running: Box<dyn Fn() -> bool + Send>,
+}
+
+#[derive(Clone, Copy)]
+struct Filter {
+    since: Option<i64>,
+    until: Option<i64>,
+    timestamps: bool,
+}
+
+impl Logs {
+    /// Reads the logs in `path`, and the logs that were moved aside before them. With
+    /// `options.follow()`, the stream continues with new logs until `is_running` is false.
+    pub(crate) fn read<F>(
+        name: &str,
+        path: &Path,
+        options: &LogOptions,
+        is_running: F,
+    ) -> Result<Self, Error>
+    where
+        F: Fn() -> bool + Send + 'static,
+    {
+        let context =
+            || ErrorKind::RuntimeOperation(RuntimeOperation::GetModuleLogs(name.to_string()));
+        let filter = Filter {
+            since: Some(i64::from(options.since())).filter(|since| *since > 0),
+            until: options.until().map(i64::from),
+            timestamps: options.timestamps(),
+        };
+
+        let mut data = match fs::read(rotated(path)) {
+            Ok(data) => data,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => vec![],
+            Err(err) => return Err(Error::from(err.context(context()))),
+        };
+        let mut file = match File::open(path) {
+            Ok(file) => Some(file),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => None,
+            Err(err) => return Err(Error::from(err.context(context()))),
+        };
+        let rotated_len = data.len();
+        if let Some(file) = &mut file {
+            file.read_to_end(&mut data).with_context(|_| context())?;
+        }
+
+        let mut frames = VecDeque::new();
+        let consumed = parse_frames(&data, filter, &mut frames);
+        if let LogTail::Num(tail) = options.tail() {
+            let tail = usize::try_from(*tail).unwrap_or(usize::MAX);
+            while frames.len() > tail {
+                frames.pop_front();
+            }
+        }
+
+        let follow = match file {
+            Some(mut file) if options.follow() => {
+                // A frame that was only partly written is read again once it's complete.
+                let offset = consumed.saturating_sub(rotated_len) as u64;
+                file.seek(SeekFrom::Start(offset))
+                    .with_context(|_| context())?;
+                Some(Follow {
+                    path: path.to_path_buf(),
+                    file,
+                    pending: vec![],
+                    filter,
+                    interval: Interval::new_interval(FOLLOW_POLL_INTERVAL),
+                    is_running: Box::new(is_running),
+                })
+            }
+            _ => None,
+        };
+
+        Ok(Logs {
+            name: name.to_string(),
+            frames,
+            follow,
+        })
+    }
+}
+
+impl Follow {
+    /// Reads the frames written since the last read. Once the log file was rotated, the rest
+    /// of the old file is read before switching to the new one.
+    fn read(&mut self, frames: &mut VecDeque<Bytes>) -> io::Result<()> {
+        self.file.read_to_end(&mut self.pending)?;
+
+        let rotated = match fs::metadata(&self.path) {
+            Ok(metadata) => metadata.ino() != self.file.metadata()?.ino(),
+            Err(_) => false,
+        };
+        if rotated {
+            self.file = File::open(&self.path)?;
+            self.file.read_to_end(&mut self.pending)?;
+        }
+
+        let consumed = parse_frames(&self.pending, self.filter, frames);
+        self.pending.drain(..consumed);
+        Ok(())
+    }
+}
+
+impl Stream for Logs {
+    type Item = Bytes;
+    type Error = Error;
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        loop {
+            if let Some(frame) = self.frames.pop_front() {
+                return Ok(Async::Ready(Some(frame)));
+            }
+
+            let follow = match &mut self.follow {
+                Some(follow) => follow,
+                None => return Ok(Async::Ready(None)),
+            };
+
+            // Checked before reading, so that everything the module wrote before it stopped
+            // is read.
+            let is_running = (follow.is_running)();
+            let name = &self.name;
+            follow.read(&mut self.frames).with_context(|_| {
+                ErrorKind::RuntimeOperation(RuntimeOperation::GetModuleLogs(name.clone()))
+            })?;
+
+            if !self.frames.is_empty() {
+                continue;
+            }
+            if !is_running {
+                self.follow = None;
+                continue;
+            }
+
+            match follow.interval.poll() {
+                Ok(Async::Ready(_)) => (),
+                Ok(Async::NotReady) => return Ok(Async::NotReady),
+                Err(err) => {
+                    return Err(Error::from(err.context(ErrorKind::RuntimeOperation(
+                        RuntimeOperation::GetModuleLogs(self.name.clone()),
+                    ))))
+                }
+            }
+        }
+    }
+}
+
+impl From<Logs> for Body {
+    fn from(logs: Logs) -> Self {
+        Body::wrap_stream(logs.map_err(|err| io::Error::new(io::ErrorKind::Other, err.to_string())))
+    }
+}
+
+fn rotated(path: &Path) -> PathBuf {
+    let mut rotated = path.as_os_str().to_owned();
+    rotated.push(".1");
+    PathBuf::from(rotated)
+}
+
+/// Parses the complete frames in `data` and adds those that pass `filter` to `frames`.
+/// Returns the number of bytes parsed.
+fn parse_frames(data: &[u8], filter: Filter, frames: &mut VecDeque<Bytes>) -> usize {
+    let mut offset = 0;
+    while data.len() - offset >= FRAME_HEADER_LEN {
+        let header = &data[offset..offset + FRAME_HEADER_LEN];
+        let len = u32::from_be_bytes([header[4], header[5], header[6], header[7]]) as usize;
+        let end = offset + FRAME_HEADER_LEN + len;
+        if data.len() < end {
+            break;
+        }
+
+        if let Some(frame) = filter.apply(header[0], &data[offset + FRAME_HEADER_LEN..end]) {
+            frames.push_back(frame);
+        }
+        offset = end;
+    }
+    offset
+}
+
+impl Filter {
+    fn apply(self, stream: u8, payload: &[u8]) -> Option<Bytes> {
+        let separator = payload.iter().position(|b| *b == b' ')?;
+        let timestamp = std::str::from_utf8(&payload[..separator])
+            .ok()
+            .and_then(|timestamp| DateTime::parse_from_rfc3339(timestamp).ok())?
+            .timestamp();
+
+        if self.since.map_or(false, |since| timestamp < since)
+            || self.until.map_or(false, |until| timestamp > until)
+        {
+            return None;
+        }
+
+        let payload = if self.timestamps {
+            Bytes::from(payload)
+        } else {
+            Bytes::from(&payload[separator + 1..])
+        };
+        let chunk = match stream {
+            1 => LogChunk::Stdout(payload),
+            2 => LogChunk::Stderr(payload),
+            _ => LogChunk::Unknown(payload),
+        };
+        Some(chunk.encode())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+    use std::fs;
+
+    use bytes::Bytes;
+    use futures::{Future, Stream};
+
+    use edgelet_core::{LogChunk, LogDecode, LogOptions, LogTail};
+
+    use super::{parse_frames, Filter, LogWriter, Logs, OutputStream};
+
+    fn frame(stream: u8, timestamp: &str, line: &str) -> Bytes {
+        let payload = Bytes::from(format!("{} {}", timestamp, line));
+        match stream {
+            1 => LogChunk::Stdout(payload),
+            _ => LogChunk::Stderr(payload),
+        }
+        .encode()
+    }
+
+    fn read_all(logs: Logs) -> Vec<LogChunk> {
+        let data: Vec<u8> = logs.concat2().wait().unwrap().to_vec();
+        LogDecode::new(std::io::Cursor::new(data))
+            .collect()
+            .wait()
+            .unwrap()
+    }
+
+    #[test]
+    fn frames_are_filtered() {
+        let mut data = vec![];
+        data.extend_from_slice(&frame(1, "2021-01-01T00:00:00.000000001Z", "one\n"));
+        data.extend_from_slice(&frame(2, "2021-01-01T00:01:00Z", "two\n"));
+        data.extend_from_slice(&frame(1, "2021-01-01T00:02:00Z", "three\n"));
+        // A frame that's only partly written isn't parsed.
+        let partial = frame(1, "2021-01-01T00:03:00Z", "four\n");
+        data.extend_from_slice(&partial[..10]);
+
+        let mut frames = VecDeque::new();
+        let filter = Filter {
+            since: Some(1_609_459_260),
+            until: Some(1_609_459_260),
+            timestamps: false,
+        };
+        let consumed = parse_frames(&data, filter, &mut frames);
+
+        assert_eq!(data.len() - 10, consumed);
+        assert_eq!(
+            vec![LogChunk::Stderr(Bytes::from("two\n")).encode()],
+            Vec::from(frames)
+        );
+
+        let mut frames = VecDeque::new();
+        let filter = Filter {
+            since: None,
+            until: None,
+            timestamps: true,
+        };
+        parse_frames(&data, filter, &mut frames);
+        assert_eq!(3, frames.len());
+        assert_eq!(frame(1, "2021-01-01T00:02:00Z", "three\n"), frames[2]);
+    }
+
+    #[test]
+    fn written_logs_are_read_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("output.log");
+
+        // Each line is larger than the limit, so that the file is rotated after every line.
+        let writer = LogWriter::open(&path, 1).unwrap();
+        writer.write_line(OutputStream::Stdout, b"one\n").unwrap();
+        writer.write_line(OutputStream::Stderr, b"two\n").unwrap();
+        writer.write_line(OutputStream::Stdout, b"three\n").unwrap();
+        assert!(fs::metadata(dir.path().join("output.log.1")).is_ok());
+
+        // The oldest line was dropped when the file was rotated the second time.
+        let logs = Logs::read("m", &path, &LogOptions::new(), || false).unwrap();
+        assert_eq!(
+            vec![
+                LogChunk::Stderr(Bytes::from("two\n")),
+                LogChunk::Stdout(Bytes::from("three\n")),
+            ],
+            read_all(logs)
+        );
+
+        let logs = Logs::read(
+            "m",
+            &path,
+            &LogOptions::new().with_tail(LogTail::Num(1)),
+            || false,
+        )
+        .unwrap();
+        assert_eq!(
+            vec![LogChunk::Stdout(Bytes::from("three\n"))],
+            read_all(logs)
+        );
+    }
+
+    #[test]
+    fn missing_logs_are_empty() {
+        let dir = tempfile::tempdir().unwrap();
+        let logs = Logs::read(
+            "m",
+            &dir.path().join("output.log"),
+            &LogOptions::new(),
+            || false,
+        )
+        .unwrap();
+        assert!(read_all(logs).is_empty());
+    }
+}
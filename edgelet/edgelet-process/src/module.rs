@@ -0,0 +1,66 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use std::sync::Arc;
+
+use failure::Fail;
+use futures::future::{self, FutureResult};
+
+use edgelet_core::{Module, ModuleOperation, ModuleRuntimeState};
+
+use crate::config::ProcessConfig;
+use crate::error::{Error, ErrorKind};
+use crate::supervisor::Supervisor;
+
+pub const MODULE_TYPE: &str = "process";
+
+pub struct ProcessModule {
+    supervisor: Arc<Supervisor>,
+    name: String,
+    config: ProcessConfig,
+}
+
+impl std::fmt::Debug for ProcessModule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ProcessModule")
+            .field("name", &self.name)
+            .field("config", &self.config)
+            .finish()
+    }
+}
+
+impl ProcessModule {
+    pub(crate) fn new(supervisor: Arc<Supervisor>, name: String, config: ProcessConfig) -> Self {
+        ProcessModule {
+            supervisor,
+            name,
+            config,
+        }
+    }
+}
+
+impl Module for ProcessModule {
+    type Config = ProcessConfig;
+    type Error = Error;
+    type RuntimeStateFuture = FutureResult<ModuleRuntimeState, Self::Error>;
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn type_(&self) -> &str {
+        MODULE_TYPE
+    }
+
+    fn config(&self) -> &Self::Config {
+        &self.config
+    }
+
+    fn runtime_state(&self) -> Self::RuntimeStateFuture {
+        future::result(self.supervisor.runtime_state(&self.name).ok_or_else(|| {
+            Error::from(
+                ErrorKind::NotFound(self.name.clone())
+                    .context(ErrorKind::ModuleOperation(ModuleOperation::RuntimeState)),
+            )
+        }))
+    }
+}
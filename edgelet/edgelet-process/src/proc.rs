@@ -0,0 +1,155 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+//! What the runtime needs to know about processes, read from `/proc`.
+
+use std::fs;
+
+/// Parents are followed this many levels up at most when looking for a module's process.
+const MAX_ANCESTORS: usize = 64;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct ProcStat {
+    pub(crate) ppid: i32,
+    /// Time the process started after boot, in clock ticks. Together with the pid, it
+    /// identifies a process even after its pid is reused.
+    pub(crate) start_time: u64,
+    pub(crate) zombie: bool,
+}
+
+pub(crate) fn stat(pid: i32) -> Option<ProcStat> {
+    let stat = fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+    parse_stat(&stat)
+}
+
+/// Parses `/proc/<pid>/stat`. The command name is in parentheses and can contain spaces and
+/// parentheses itself, so fields are counted from the last `)`.
+fn parse_stat(stat: &str) -> Option<ProcStat> {
+    let rest = &stat[stat.rfind(')')? + 1..];
+    let fields: Vec<&str> = rest.split_whitespace().collect();
+
+    // `fields[0]` is the third field of the file, the state.
+    Some(ProcStat {
+        zombie: *fields.get(0)? == "Z",
+        ppid: fields.get(1)?.parse().ok()?,
+        start_time: fields.get(19)?.parse().ok()?,
+    })
+}
+
+/// Whether the process that was started at `start_time` is still running.
+pub(crate) fn is_alive(pid: i32, start_time: u64) -> bool {
+    match stat(pid) {
+        Some(stat) => stat.start_time == start_time && !stat.zombie,
+        None => false,
+    }
+}
+
+/// Whether `pid` is one of `roots`, the processes that were started for a module by pid and
+/// start time, or one of their descendants.
+///
+/// A parent never starts after its child, so an ancestor that does is a reused pid. Processes
+/// that were reparented, like daemons, are no longer part of the module.
+pub(crate) fn belongs_to<F>(pid: i32, roots: &[(i32, u64)], stat: F) -> bool
+where
+    F: Fn(i32) -> Option<ProcStat>,
+{
+    let mut current = pid;
+    let mut child_start_time = u64::MAX;
+    for _ in 0..MAX_ANCESTORS {
+        let stat = match stat(current) {
+            Some(stat) if stat.start_time <= child_start_time => stat,
+            _ => return false,
+        };
+        if roots.contains(&(current, stat.start_time)) {
+            return true;
+        }
+        if stat.ppid <= 1 {
+            return false;
+        }
+
+        current = stat.ppid;
+        child_start_time = stat.start_time;
+    }
+
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::{belongs_to, parse_stat, ProcStat};
+
+    #[test]
+    fn parse_stat_with_odd_command() {
+        let stat = "1234 (my (odd) cmd) S 1000 1234 1000 0 -1 4194560 100 0 0 0 1 2 0 0 20 0 1 0 \
+                    98765 10000000 200 18446744073709551615 1 1 0 0 0 0 0 0 0 0 0 0 17 3 0 0 0 0 0";
+
+        assert_eq!(
+            Some(ProcStat {
+                ppid: 1000,
+                start_time: 98765,
+                zombie: false,
+            }),
+            parse_stat(stat)
+        );
+
+        assert!(
+            parse_stat("1234 (zombie) Z 1 1234 1 0 -1 0 0 0 0 0 0 0 0 0 20 0 1 0 5")
+                .unwrap()
+                .zombie
+        );
+        assert_eq!(None, parse_stat("1234 (truncated) S 1"));
+    }
+
+    #[test]
+    fn descendants_belong_to_module() {
+        // 1 -> 100 (module) -> 101 -> 102 -> 300, 1 -> 150 (command run in the module), and
+        // 200 under 1, which was started after the module's process
+        let processes: BTreeMap<i32, (i32, u64)> = vec![
+            (100, (1, 10)),
+            (101, (100, 11)),
+            (102, (101, 12)),
+            (150, (1, 15)),
+            (200, (1, 20)),
+            (300, (102, 30)),
+        ]
+        .into_iter()
+        .collect();
+        let stat = |pid| {
+            processes.get(&pid).map(|&(ppid, start_time)| ProcStat {
+                ppid,
+                start_time,
+                zombie: false,
+            })
+        };
+        let roots = [(100, 10), (150, 15)];
+
+        assert!(belongs_to(100, &roots, stat));
+        assert!(belongs_to(101, &roots, stat));
+        assert!(belongs_to(102, &roots, stat));
+        assert!(belongs_to(300, &roots, stat));
+        assert!(belongs_to(150, &roots, stat));
+        assert!(!belongs_to(200, &roots, stat));
+        assert!(!belongs_to(999, &roots, stat));
+
+        // The module's pid, reused by another process
+        assert!(!belongs_to(100, &[(100, 5)], stat));
+    }
+
+    #[test]
+    fn reused_parent_pids_dont_belong_to_module() {
+        // 201's parent exited while it was looked up, and the module's process reused the
+        // parent's pid.
+        let processes: BTreeMap<i32, (i32, u64)> =
+            vec![(100, (1, 30)), (201, (100, 20))].into_iter().collect();
+        let stat = |pid| {
+            processes.get(&pid).map(|&(ppid, start_time)| ProcStat {
+                ppid,
+                start_time,
+                zombie: false,
+            })
+        };
+
+        assert!(!belongs_to(201, &[(100, 30)], stat));
+    }
+}
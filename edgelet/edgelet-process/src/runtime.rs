@@ -0,0 +1,849 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use std::convert::TryFrom;
+use std::env;
+use std::fs;
+use std::io;
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::io::{FromRawFd, IntoRawFd};
+use std::os::unix::net::UnixStream as StdUnixStream;
+use std::path::{Path, PathBuf};
+use std::process::{self, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use bytes::Bytes;
+use failure::{Fail, ResultExt};
+use futures::future::{self, FutureResult};
+use futures::sync::oneshot;
+use futures::{stream, Future, IntoFuture, Poll, Stream};
+use hyper::{Body, Request};
+use log::{info, warn, Level};
+use sysinfo::{DiskExt, ProcessExt, ProcessorExt, System, SystemExt};
+use tokio::reactor::Handle;
+use tokio_uds::UnixStream;
+
+use edgelet_core::{
+    shutdown_stages, startup_sequence, stop_in_stages, AuthId, Authenticator, DiskInfo,
+    ExecOptions, ImageInfo, LogOptions, MakeModuleRuntime, ModuleId, ModuleRegistry, ModuleRuntime,
+    ModuleRuntimeState, ModuleSpec, ModuleStartup, ProvisioningInfo, RegistryOperation,
+    RuntimeOperation, RuntimeSettings, SystemInfo as CoreSystemInfo, SystemResources,
+};
+use edgelet_http::Pid;
+use edgelet_utils::log_failure;
+
+use crate::cgroup;
+use crate::config::ProcessConfig;
+use crate::error::{Error, ErrorKind, Result};
+use crate::exec::ExecIo;
+use crate::logs::Logs;
+use crate::module::{ProcessModule, MODULE_TYPE};
+use crate::settings::Settings;
+use crate::startup;
+use crate::supervisor::{self, Supervisor, DEFAULT_PATH};
+use crate::user::ModuleUsers;
+
+/// Directory under the home directory that modules' files are kept in.
+const MODULES_DIR: &str = "process-modules";
+
+type ExecExitCode = Box<dyn Future<Item = Option<i32>, Error = Error> + Send>;
+
+/// How long modules are given to stop before they're killed, when the caller doesn't say.
+const DEFAULT_STOP_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Clone)]
+pub struct ProcessModuleRuntime {
+    supervisor: Arc<Supervisor>,
+    system_resources: Arc<Mutex<System>>,
+}
+
+impl std::fmt::Debug for ProcessModuleRuntime {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ProcessModuleRuntime").finish()
+    }
+}
+
+impl ModuleRegistry for ProcessModuleRuntime {
+    type Error = Error;
+    type PullFuture = FutureResult<(), Self::Error>;
+    type RemoveFuture = FutureResult<(), Self::Error>;
+    type ListImagesFuture = FutureResult<Vec<ImageInfo>, Self::Error>;
+    type Config = ProcessConfig;
+
+    /// Processes run commands that are already installed on the device, so there's nothing
+    /// to pull. The command is only checked to exist, so that a module that can't be started
+    /// fails before it's created.
+    fn pull(&self, config: &Self::Config) -> Self::PullFuture {
+        let command = config.command();
+        if let Some(path) = find_command(command) {
+            info!("Found command {} at {}", command, path.display());
+            future::ok(())
+        } else {
+            let err = Error::from(ErrorKind::CommandNotFound(command.to_string()).context(
+                ErrorKind::RegistryOperation(RegistryOperation::PullImage(command.to_string())),
+            ));
+            log_failure(Level::Warn, &err);
+            future::err(err)
+        }
+    }
+
+    fn remove(&self, _name: &str) -> Self::RemoveFuture {
+        future::ok(())
+    }
+
+    fn list_images(&self) -> Self::ListImagesFuture {
+        future::ok(vec![])
+    }
+}
+
+impl MakeModuleRuntime for ProcessModuleRuntime {
+    type Config = ProcessConfig;
+    type Settings = Settings;
+    type ModuleRuntime = Self;
+    type Error = Error;
+    type Future = FutureResult<Self, Self::Error>;
+    type ReloadFuture = FutureResult<(), Self::Error>;
+
+    fn make_runtime(settings: Settings) -> Self::Future {
+        info!("Initializing module runtime...");
+
+        let process_runtime = settings.process_runtime();
+        if let Some(cgroup_root) = process_runtime.cgroup_root() {
+            if let Err(err) = cgroup::enable_controllers(cgroup_root) {
+                warn!(
+                    "Could not enable cgroup controllers in {}, resource limits of modules may not apply: {}",
+                    cgroup_root.display(),
+                    err
+                );
+            }
+        }
+
+        let users = ModuleUsers::new(
+            process_runtime.default_user().map(ToOwned::to_owned),
+            process_runtime.module_users().clone(),
+        );
+        let result = Supervisor::load(
+            &settings.homedir().join(MODULES_DIR),
+            process_runtime.cgroup_root(),
+            process_runtime.max_log_size(),
+            users,
+        )
+        .map(|supervisor| ProcessModuleRuntime {
+            supervisor: Arc::new(supervisor),
+            system_resources: Arc::new(Mutex::new(System::new_all())),
+        });
+
+        match &result {
+            Ok(_) => info!("Successfully initialized module runtime"),
+            Err(err) => log_failure(Level::Warn, err),
+        }
+        future::result(result)
+    }
+
+    fn reload(_runtime: &Self, _settings: &Settings) -> Self::ReloadFuture {
+        future::ok(())
+    }
+}
+
+impl ModuleRuntime for ProcessModuleRuntime {
+    type Error = Error;
+    type Config = ProcessConfig;
+    type Module = ProcessModule;
+    type ModuleRegistry = Self;
+    type Chunk = Bytes;
+    type Logs = Logs;
+    type Archive = Archive;
+
+    type CreateFuture = Box<dyn Future<Item = (), Error = Self::Error> + Send>;
+    type GetFuture =
+        Box<dyn Future<Item = (Self::Module, ModuleRuntimeState), Error = Self::Error> + Send>;
+    type ListFuture = Box<dyn Future<Item = Vec<Self::Module>, Error = Self::Error> + Send>;
+    type ListWithDetailsStream =
+        Box<dyn Stream<Item = (Self::Module, ModuleRuntimeState), Error = Self::Error> + Send>;
+    type LogsFuture = Box<dyn Future<Item = Self::Logs, Error = Self::Error> + Send>;
+    type RemoveFuture = Box<dyn Future<Item = (), Error = Self::Error> + Send>;
+    type RestartFuture = Box<dyn Future<Item = (), Error = Self::Error> + Send>;
+    type StartFuture = Box<dyn Future<Item = (), Error = Self::Error> + Send>;
+    type StopFuture = Box<dyn Future<Item = (), Error = Self::Error> + Send>;
+    type SystemInfoFuture = Box<dyn Future<Item = CoreSystemInfo, Error = Self::Error> + Send>;
+    type SystemResourcesFuture =
+        Box<dyn Future<Item = SystemResources, Error = Self::Error> + Send>;
+    type RemoveAllFuture = Box<dyn Future<Item = (), Error = Self::Error> + Send>;
+    type StartAllFuture = Box<dyn Future<Item = (), Error = Self::Error> + Send>;
+    type StopAllFuture = Box<dyn Future<Item = (), Error = Self::Error> + Send>;
+    type ExecIo = ExecIo;
+    type ExecExitCodeFuture = ExecExitCode;
+    type ExecFuture = Box<
+        dyn Future<Item = (Self::ExecIo, Self::ExecExitCodeFuture), Error = Self::Error> + Send,
+    >;
+    type GetArchiveFuture = Box<dyn Future<Item = Self::Archive, Error = Self::Error> + Send>;
+    type PutArchiveFuture = Box<dyn Future<Item = (), Error = Self::Error> + Send>;
+
+    fn create(&self, module: ModuleSpec<Self::Config>) -> Self::CreateFuture {
+        info!("Creating module {}...", module.name());
+
+        // we only want "process" modules
+        if module.type_() != MODULE_TYPE {
+            return Box::new(future::err(Error::from(ErrorKind::InvalidModuleType(
+                module.type_().to_string(),
+            ))));
+        }
+
+        let name = module.name().to_string();
+        let result = self.supervisor.create(module);
+        match &result {
+            Ok(()) => info!("Successfully created module {}", name),
+            Err(err) => log_failure(Level::Warn, err),
+        }
+        Box::new(future::result(result))
+    }
+
+    fn get(&self, id: &str) -> Self::GetFuture {
+        let result = self
+            .supervisor
+            .spec(id)
+            .and_then(|spec| {
+                let state = self.supervisor.runtime_state(id)?;
+                let module = ProcessModule::new(
+                    self.supervisor.clone(),
+                    id.to_string(),
+                    spec.config().clone(),
+                );
+                Some((module, state))
+            })
+            .ok_or_else(|| {
+                Error::from(ErrorKind::NotFound(id.to_string()).context(
+                    ErrorKind::RuntimeOperation(RuntimeOperation::GetModule(id.to_string())),
+                ))
+            });
+        Box::new(future::result(result))
+    }
+
+    fn start(&self, id: &str) -> Self::StartFuture {
+        info!("Starting module {}...", id);
+
+        let result = self.supervisor.start(id);
+        match &result {
+            Ok(()) => info!("Successfully started module {}", id),
+            Err(err) => log_failure(Level::Warn, err),
+        }
+        Box::new(future::result(result))
+    }
+
+    fn stop(&self, id: &str, wait_before_kill: Option<Duration>) -> Self::StopFuture {
+        info!("Stopping module {}...", id);
+
+        let supervisor = self.supervisor.clone();
+        let id = id.to_string();
+        Box::new(
+            blocking(RuntimeOperation::StopModule(id.clone()), move || {
+                supervisor.stop(&id, wait_before_kill.unwrap_or(DEFAULT_STOP_TIMEOUT))?;
+                Ok(id)
+            })
+            .then(|result| {
+                match &result {
+                    Ok(id) => info!("Successfully stopped module {}", id),
+                    Err(err) => log_failure(Level::Warn, err),
+                }
+                result.map(|_| ())
+            }),
+        )
+    }
+
+    fn restart(&self, id: &str) -> Self::RestartFuture {
+        info!("Restarting module {}...", id);
+
+        let supervisor = self.supervisor.clone();
+        let id = id.to_string();
+        Box::new(
+            blocking(RuntimeOperation::RestartModule(id.clone()), move || {
+                match supervisor.stop(&id, DEFAULT_STOP_TIMEOUT) {
+                    Err(err) if !is_not_modified(&err) => {
+                        return Err(Error::from(err.context(ErrorKind::RuntimeOperation(
+                            RuntimeOperation::RestartModule(id),
+                        ))));
+                    }
+                    _ => (),
+                }
+                supervisor.start(&id).map_err(|err| {
+                    Error::from(err.context(ErrorKind::RuntimeOperation(
+                        RuntimeOperation::RestartModule(id.clone()),
+                    )))
+                })?;
+                Ok(id)
+            })
+            .then(|result| {
+                match &result {
+                    Ok(id) => info!("Successfully restarted module {}", id),
+                    Err(err) => log_failure(Level::Warn, err),
+                }
+                result.map(|_| ())
+            }),
+        )
+    }
+
+    fn remove(&self, id: &str) -> Self::RemoveFuture {
+        info!("Removing module {}...", id);
+
+        let supervisor = self.supervisor.clone();
+        let id = id.to_string();
+        Box::new(
+            blocking(RuntimeOperation::RemoveModule(id.clone()), move || {
+                supervisor.remove(&id)?;
+                Ok(id)
+            })
+            .then(|result| {
+                match &result {
+                    Ok(id) => info!("Successfully removed module {}", id),
+                    Err(err) => log_failure(Level::Warn, err),
+                }
+                result.map(|_| ())
+            }),
+        )
+    }
+
+    fn system_info(&self) -> Self::SystemInfoFuture {
+        info!("Querying system info...");
+
+        // Provisioning information is no longer available in aziot-edged. This information should
+        // be emitted from Identity Service
+        let provisioning = ProvisioningInfo {
+            r#type: "ProvisioningType".into(),
+            dynamic_reprovisioning: false,
+            always_reprovision_on_startup: false,
+        };
+
+        let cpus = {
+            let mut system = self
+                .system_resources
+                .lock()
+                .expect("Could not acquire system resources lock");
+            system.refresh_cpu();
+            i32::try_from(system.get_processors().len()).unwrap_or_default()
+        };
+
+        let system_info = CoreSystemInfo {
+            os_type: env::consts::OS.to_string(),
+            architecture: env::consts::ARCH.to_string(),
+            version: edgelet_core::version_with_source_version(),
+            provisioning,
+            cpus,
+            virtualized: match edgelet_core::is_virtualized_env() {
+                Ok(Some(true)) => "yes",
+                Ok(Some(false)) => "no",
+                Ok(None) | Err(_) => "unknown",
+            },
+            kernel_version: fs::read_to_string("/proc/sys/kernel/osrelease")
+                .map(|release| release.trim().to_string())
+                .unwrap_or_default(),
+            operating_system: operating_system().unwrap_or_default(),
+            // There's no container engine, so no server.
+            server_version: String::new(),
+        };
+        info!("Successfully queried system info");
+        Box::new(future::ok(system_info))
+    }
+
+    fn system_resources(&self) -> Self::SystemResourcesFuture {
+        info!("Querying system resources...");
+
+        let mut system_resources = self
+            .system_resources
+            .lock()
+            .expect("Could not acquire system resources lock");
+
+        let uptime = {
+            let mut info: libc::sysinfo = unsafe { std::mem::zeroed() };
+            if unsafe { libc::sysinfo(&mut info) } == 0 {
+                u64::try_from(info.uptime).unwrap_or_default()
+            } else {
+                0
+            }
+        };
+
+        let current_time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let start_time = i32::try_from(process::id())
+            .map(|id| {
+                system_resources.refresh_process(id);
+                system_resources
+                    .get_process(id)
+                    .map(ProcessExt::start_time)
+                    .unwrap_or_default()
+            })
+            .unwrap_or_default();
+
+        system_resources.refresh_system();
+        let used_cpu = system_resources.get_global_processor_info().get_cpu_usage();
+        let total_memory = system_resources.get_total_memory() * 1000;
+        let used_memory = system_resources.get_used_memory() * 1000;
+
+        system_resources.refresh_disks();
+        let disks = system_resources
+            .get_disks()
+            .iter()
+            .map(|disk| {
+                DiskInfo::new(
+                    disk.get_name().to_string_lossy().into_owned(),
+                    disk.get_available_space(),
+                    disk.get_total_space(),
+                    String::from_utf8_lossy(disk.get_file_system()).into_owned(),
+                    format!("{:?}", disk.get_type()),
+                    disk.get_mount_point().to_string_lossy().into_owned(),
+                )
+            })
+            .collect();
+
+        // The stats of modules' main processes, in place of the stats of their containers.
+        let stats: Vec<_> = self
+            .supervisor
+            .list()
+            .iter()
+            .filter_map(|spec| {
+                let pid = self.supervisor.pid(spec.name())?;
+                system_resources.refresh_process(pid);
+                let process = system_resources.get_process(pid)?;
+                Some(serde_json::json!({
+                    "name": spec.name(),
+                    "pid": pid,
+                    "cpu_usage": process.cpu_usage(),
+                    "memory_usage": process.memory() * 1000,
+                    "start_time": process.start_time(),
+                }))
+            })
+            .collect();
+
+        let result = serde_json::to_string(&stats)
+            .context(ErrorKind::RuntimeOperation(
+                RuntimeOperation::SystemResources,
+            ))
+            .map(|stats| {
+                SystemResources::new(
+                    uptime,
+                    current_time - start_time,
+                    used_cpu.into(),
+                    used_memory,
+                    total_memory,
+                    disks,
+                    stats,
+                )
+            })
+            .map_err(Error::from);
+        Box::new(future::result(result))
+    }
+
+    fn list(&self) -> Self::ListFuture {
+        let modules = self
+            .supervisor
+            .list()
+            .into_iter()
+            .map(|spec| {
+                ProcessModule::new(
+                    self.supervisor.clone(),
+                    spec.name().to_string(),
+                    spec.config().clone(),
+                )
+            })
+            .collect();
+        Box::new(future::ok(modules))
+    }
+
+    fn list_with_details(&self) -> Self::ListWithDetailsStream {
+        // Modules that were removed since they were listed are left out.
+        let modules: Vec<_> = self
+            .supervisor
+            .list()
+            .into_iter()
+            .filter_map(|spec| {
+                let state = self.supervisor.runtime_state(spec.name())?;
+                let module = ProcessModule::new(
+                    self.supervisor.clone(),
+                    spec.name().to_string(),
+                    spec.config().clone(),
+                );
+                Some((module, state))
+            })
+            .collect();
+        Box::new(stream::iter_ok(modules))
+    }
+
+    fn logs(&self, id: &str, options: &LogOptions) -> Self::LogsFuture {
+        info!("Getting logs for module {}...", id);
+
+        let result = if self.supervisor.spec(id).is_some() {
+            let supervisor = self.supervisor.clone();
+            let name = id.to_string();
+            Logs::read(id, &self.supervisor.log_path(id), options, move || {
+                supervisor.is_running(&name)
+            })
+        } else {
+            Err(Error::from(ErrorKind::NotFound(id.to_string()).context(
+                ErrorKind::RuntimeOperation(RuntimeOperation::GetModuleLogs(id.to_string())),
+            )))
+        };
+
+        match &result {
+            Ok(_) => info!("Successfully got logs for module {}", id),
+            Err(err) => log_failure(Level::Warn, err),
+        }
+        Box::new(future::result(result))
+    }
+
+    fn registry(&self) -> &Self::ModuleRegistry {
+        self
+    }
+
+    fn remove_all(&self) -> Self::RemoveAllFuture {
+        let removes = self
+            .supervisor
+            .list()
+            .into_iter()
+            .map(|spec| <ProcessModuleRuntime as ModuleRuntime>::remove(self, spec.name()))
+            .collect::<Vec<_>>();
+        Box::new(future::join_all(removes).map(|_| ()))
+    }
+
+    fn start_all(&self) -> Self::StartAllFuture {
+        let self_for_start = self.clone();
+        let sequence = startup_sequence(self.module_startups())
+            .context(ErrorKind::RuntimeOperation(RuntimeOperation::StartModules))
+            .map_err(Error::from);
+
+        // Modules without a startup order or dependencies are left for edgeAgent to start.
+        Box::new(sequence.into_future().and_then(move |sequence| {
+            stream::iter_ok(sequence.into_iter().filter(startup::is_ordered)).for_each(
+                move |module| {
+                    let self_for_start = self_for_start.clone();
+                    let supervisor = self_for_start.supervisor.clone();
+                    let name = module.name().to_string();
+                    let dependencies = module.depends_on().to_vec();
+
+                    stream::iter_ok(dependencies)
+                        .for_each(move |dependency| {
+                            startup::wait_until_ready(supervisor.clone(), dependency)
+                        })
+                        .and_then(move |()| {
+                            <ProcessModuleRuntime as ModuleRuntime>::start(&self_for_start, &name)
+                                .or_else(|err| {
+                                    if is_not_modified(&err) {
+                                        Ok(())
+                                    } else {
+                                        Err(err)
+                                    }
+                                })
+                        })
+                },
+            )
+        }))
+    }
+
+    /// The startup order and dependencies of modules come from their supervisor specs. If they
+    /// can't be put in order, all supervised modules are stopped at the same time.
+    fn stop_all(&self, wait_before_kill: Option<Duration>) -> Self::StopAllFuture {
+        let self_for_stop = self.clone();
+        let stop = move |name: String, wait_before_kill: Option<Duration>| {
+            <ProcessModuleRuntime as ModuleRuntime>::stop(&self_for_stop, &name, wait_before_kill)
+                .or_else(
+                    |err| match Fail::find_root_cause(&err).downcast_ref::<ErrorKind>() {
+                        Some(ErrorKind::NotFound(_)) | Some(ErrorKind::NotModified) => Ok(()),
+                        _ => Err(err),
+                    },
+                )
+        };
+
+        let stages: Vec<Vec<String>> = match startup_sequence(self.module_startups()) {
+            Ok(sequence) => shutdown_stages(sequence)
+                .into_iter()
+                .map(|stage| stage.iter().map(|m| m.name().to_string()).collect())
+                .collect(),
+            Err(err) => {
+                warn!("Stopping modules in any order. {}", err);
+                vec![self
+                    .supervisor
+                    .list()
+                    .iter()
+                    .map(|spec| spec.name().to_string())
+                    .collect()]
+            }
+        };
+
+        Box::new(stop_in_stages(stages, wait_before_kill, stop))
+    }
+
+    fn exec(&self, id: &str, options: &ExecOptions) -> Self::ExecFuture {
+        info!("Running {:?} in module {}...", options.cmd(), id);
+
+        let result = exec(&self.supervisor, id, options);
+        match &result {
+            Ok(_) => info!("Successfully started command in module {}", id),
+            Err(err) => log_failure(Level::Warn, err),
+        }
+        Box::new(future::result(result))
+    }
+
+    fn get_archive(&self, id: &str, _path: &str) -> Self::GetArchiveFuture {
+        Box::new(future::err(Error::from(
+            ErrorKind::Unsupported("Copying files").context(ErrorKind::RuntimeOperation(
+                RuntimeOperation::GetModuleArchive(id.to_string()),
+            )),
+        )))
+    }
+
+    fn put_archive<S>(&self, id: &str, _path: &str, _archive: S) -> Self::PutArchiveFuture
+    where
+        S: Stream<Item = Bytes, Error = io::Error> + Send + 'static,
+    {
+        Box::new(future::err(Error::from(
+            ErrorKind::Unsupported("Copying files").context(ErrorKind::RuntimeOperation(
+                RuntimeOperation::PutModuleArchive(id.to_string()),
+            )),
+        )))
+    }
+}
+
+impl ProcessModuleRuntime {
+    fn module_startups(&self) -> Vec<ModuleStartup> {
+        self.supervisor
+            .list()
+            .iter()
+            .map(|spec| {
+                ModuleStartup::new(
+                    spec.name().to_string(),
+                    spec.startup_order(),
+                    spec.depends_on().to_vec(),
+                )
+            })
+            .collect()
+    }
+}
+
+impl Authenticator for ProcessModuleRuntime {
+    type Error = Error;
+    type Request = Request<Body>;
+    type AuthenticateFuture = FutureResult<AuthId, Self::Error>;
+
+    /// A caller is a module when its process is one of the module's processes.
+    fn authenticate(&self, req: &Self::Request) -> Self::AuthenticateFuture {
+        let pid = req
+            .extensions()
+            .get::<Pid>()
+            .cloned()
+            .unwrap_or_else(|| Pid::None);
+        let expected_module_id = req.extensions().get::<ModuleId>().cloned();
+
+        let auth_id = match (pid, expected_module_id) {
+            (Pid::None, _) | (Pid::Value(_), None) => AuthId::None,
+            (Pid::Any, _) => AuthId::Any,
+            (Pid::Value(pid), Some(expected_module_id)) => {
+                let module = self
+                    .supervisor
+                    .list()
+                    .into_iter()
+                    .map(|spec| spec.name().to_string())
+                    .find(|name| expected_module_id == name.as_str());
+                match module {
+                    Some(name) if self.supervisor.owns(&name, pid) => AuthId::Value(name.into()),
+                    _ => {
+                        info!("Unable to find a module for caller pid: {}", pid);
+                        AuthId::None
+                    }
+                }
+            }
+        };
+        future::ok(auth_id)
+    }
+}
+
+/// Copying files isn't supported for process modules, so there are never any archives.
+#[derive(Debug)]
+pub enum Archive {}
+
+impl Stream for Archive {
+    type Item = Bytes;
+    type Error = Error;
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        match *self {}
+    }
+}
+
+impl From<Archive> for Body {
+    fn from(archive: Archive) -> Self {
+        match archive {}
+    }
+}
+
+fn is_not_modified(err: &Error) -> bool {
+    matches!(
+        Fail::find_root_cause(err).downcast_ref::<ErrorKind>(),
+        Some(ErrorKind::NotModified)
+    )
+}
+
+/// Runs `f` on its own thread, for operations that wait for processes.
+fn blocking<F, T>(operation: RuntimeOperation, f: F) -> impl Future<Item = T, Error = Error> + Send
+where
+    F: FnOnce() -> Result<T> + Send + 'static,
+    T: Send + 'static,
+{
+    let (sender, receiver) = oneshot::channel();
+    thread::spawn(move || {
+        let _ = sender.send(f());
+    });
+    receiver.then(move |result| match result {
+        Ok(result) => result,
+        Err(oneshot::Canceled) => Err(Error::from(ErrorKind::RuntimeOperation(operation))),
+    })
+}
+
+/// Finds a command the way it's found when a module is started without its own `PATH`.
+fn find_command(command: &str) -> Option<PathBuf> {
+    let is_executable = |path: &Path| {
+        fs::metadata(path).map_or(false, |metadata| {
+            metadata.is_file() && metadata.permissions().mode() & 0o111 != 0
+        })
+    };
+
+    if command.contains('/') {
+        let path = PathBuf::from(command);
+        return if is_executable(&path) {
+            Some(path)
+        } else {
+            None
+        };
+    }
+    env::split_paths(DEFAULT_PATH)
+        .map(|dir| dir.join(command))
+        .find(|path| is_executable(path))
+}
+
+/// The name of the host's distribution, from `/etc/os-release`.
+fn operating_system() -> Option<String> {
+    let os_release = fs::read_to_string("/etc/os-release").ok()?;
+    os_release.lines().find_map(|line| {
+        let value = line.trim().strip_prefix("PRETTY_NAME=")?;
+        Some(value.trim_matches('"').to_string())
+    })
+}
+
+/// Runs a command like the module's process. It's stopped with the module. Processes don't have terminals, so with `tty` the command's output is only
+/// returned as a single stream.
+fn exec(
+    supervisor: &Supervisor,
+    name: &str,
+    options: &ExecOptions,
+) -> Result<(ExecIo, ExecExitCode)> {
+    let context = || ErrorKind::RuntimeOperation(RuntimeOperation::ExecModule(name.to_string()));
+
+    let spec = supervisor
+        .spec(name)
+        .ok_or_else(|| ErrorKind::NotFound(name.to_string()).context(context()))?;
+    if !supervisor.is_running(name) {
+        return Err(Error::from(
+            ErrorKind::NotRunning(name.to_string()).context(context()),
+        ));
+    }
+    let (program, args) = options
+        .cmd()
+        .split_first()
+        .ok_or_else(|| ErrorKind::InvalidCommand(String::new()).context(context()))?;
+
+    let (stdio, child_stdio) = StdUnixStream::pair().with_context(|_| context())?;
+    let (stderr, child_stderr) = if options.tty() {
+        (None, None)
+    } else {
+        let (stderr, child_stderr) = StdUnixStream::pair().with_context(|_| context())?;
+        (Some(stderr), Some(child_stderr))
+    };
+
+    let child = {
+        let (mut command, procs) = supervisor
+            .command(&spec, program, args)
+            .with_context(|_| context())?;
+        command
+            .stdin(if options.stdin() {
+                to_stdio(&child_stdio).with_context(|_| context())?
+            } else {
+                Stdio::null()
+            })
+            .stdout(to_stdio(&child_stdio).with_context(|_| context())?)
+            .stderr(
+                to_stdio(child_stderr.as_ref().unwrap_or(&child_stdio))
+                    .with_context(|_| context())?,
+            );
+        let child = command.spawn().with_context(|_| context())?;
+        drop(procs);
+        #[allow(clippy::cast_possible_wrap)]
+        let pid = child.id() as i32;
+        supervisor.add_exec(name, pid);
+        child
+    };
+    // Only the command may have the other ends open, so that its output ends when it exits.
+    drop(child_stdio);
+    drop(child_stderr);
+
+    let handle = Handle::default();
+    let stdio = UnixStream::from_std(stdio, &handle).with_context(|_| context())?;
+    let stderr = stderr
+        .map(|stderr| UnixStream::from_std(stderr, &handle))
+        .transpose()
+        .with_context(|_| context())?;
+
+    let (sender, receiver) = oneshot::channel();
+    let name = name.to_string();
+    thread::spawn(move || {
+        let mut child = child;
+        let exit_code = match child.wait() {
+            Ok(status) => i32::try_from(supervisor::exit_code(status)).ok(),
+            Err(err) => {
+                warn!(
+                    "Could not wait for command in module {} to exit: {}",
+                    name, err
+                );
+                None
+            }
+        };
+        let _ = sender.send(exit_code);
+    });
+    let exit_code = receiver.then(|result| Ok(result.unwrap_or(None)));
+
+    Ok((ExecIo::new(stdio, stderr), Box::new(exit_code)))
+}
+
+fn to_stdio(stream: &StdUnixStream) -> io::Result<Stdio> {
+    let stream = stream.try_clone()?;
+    Ok(unsafe { Stdio::from_raw_fd(stream.into_raw_fd()) })
+}
+
+#[cfg(test)]
+mod tests {
+    use failure::Fail;
+
+    use edgelet_core::RuntimeOperation;
+
+    use super::{find_command, is_not_modified};
+    use crate::error::{Error, ErrorKind};
+
+    #[test]
+    fn commands_are_found_on_path() {
+        assert!(find_command("sh").is_some());
+        assert!(find_command("/bin/sh").is_some());
+        assert!(find_command("surely-not-a-command").is_none());
+        // Not executable
+        assert!(find_command("/etc/passwd").is_none());
+    }
+
+    #[test]
+    fn not_modified() {
+        let context = || ErrorKind::RuntimeOperation(RuntimeOperation::StartModule("m".into()));
+        assert!(is_not_modified(&Error::from(
+            ErrorKind::NotModified.context(context())
+        )));
+        assert!(!is_not_modified(&Error::from(
+            ErrorKind::NotFound("m".into()).context(context())
+        )));
+    }
+}
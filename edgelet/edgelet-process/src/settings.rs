@@ -0,0 +1,209 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use edgelet_core::{
+    settings::AutoReprovisioningMode, Connect, Endpoints, ImageGarbageCollection, Listen,
+    ModuleSpec, RuntimeSettings, ServerCertPolicy, Settings as BaseSettings, WatchdogSettings,
+};
+use failure::{Context, Fail};
+
+use crate::config::ProcessConfig;
+
+const DEFAULT_MAX_LOG_SIZE: u64 = 10 * 1024 * 1024;
+
+#[derive(Clone, Debug, Default, serde_derive::Deserialize, serde_derive::Serialize)]
+pub struct ProcessRuntime {
+    /// The cgroup (v2) directory that modules' cgroups are created in. It must be delegated to
+    /// the user aziot-edged runs as. Modules can only have resource limits when it's set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cgroup_root: Option<PathBuf>,
+    /// Size in bytes that a module's log file grows to before it's rotated.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_log_size: Option<u64>,
+    /// The user, by name or uid, that modules run as unless `module_users` has one for them.
+    /// Modules without a user can't be started, and neither can modules whose user is root
+    /// or the user aziot-edged runs as. Switching users needs aziot-edged to have the
+    /// `CAP_SETUID`, `CAP_SETGID`, `CAP_CHOWN` and `CAP_KILL` capabilities.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_user: Option<String>,
+    /// Map of module names to the users that they run as. Modules that run as the same user
+    /// can read and write each other's files, so modules that must be kept apart need users
+    /// of their own.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub module_users: BTreeMap<String, String>,
+}
+
+impl ProcessRuntime {
+    pub fn cgroup_root(&self) -> Option<&Path> {
+        self.cgroup_root.as_deref()
+    }
+
+    pub fn max_log_size(&self) -> u64 {
+        self.max_log_size.unwrap_or(DEFAULT_MAX_LOG_SIZE)
+    }
+
+    pub fn default_user(&self) -> Option<&str> {
+        self.default_user.as_deref()
+    }
+
+    pub fn module_users(&self) -> &BTreeMap<String, String> {
+        &self.module_users
+    }
+}
+
+/// This struct is the same as the Settings type from the `edgelet_core` crate
+/// except that modules, including edge agent, are run as processes on the host.
+#[derive(Clone, Debug, serde_derive::Deserialize, serde_derive::Serialize)]
+pub struct Settings {
+    #[serde(flatten)]
+    pub base: BaseSettings<ProcessConfig>,
+    #[serde(default)]
+    pub process_runtime: ProcessRuntime,
+}
+
+pub const CONFIG_FILE_DEFAULT: &str = "/etc/aziot/edged/config.toml";
+
+impl Settings {
+    /// Load the aziot-edged configuration.
+    ///
+    /// Configuration is made up of /etc/aziot/edged/config.toml (overridden by the `AZIOT_EDGED_CONFIG` env var)
+    /// and any files in the /etc/aziot/edged/config.d directory (overridden by the `AZIOT_EDGED_CONFIG_DIR` env var).
+    pub fn new() -> Result<Self, LoadSettingsError> {
+        const CONFIG_ENV_VAR: &str = "AZIOT_EDGED_CONFIG";
+        const CONFIG_DIRECTORY_ENV_VAR: &str = "AZIOT_EDGED_CONFIG_DIR";
+        const CONFIG_DIRECTORY_DEFAULT: &str = "/etc/aziot/edged/config.d";
+
+        let config_path: std::path::PathBuf =
+            std::env::var_os(CONFIG_ENV_VAR).map_or_else(|| CONFIG_FILE_DEFAULT.into(), Into::into);
+
+        let config_directory_path: std::path::PathBuf = std::env::var_os(CONFIG_DIRECTORY_ENV_VAR)
+            .map_or_else(|| CONFIG_DIRECTORY_DEFAULT.into(), Into::into);
+
+        let settings: Settings =
+            config_common::read_config(&config_path, Some(&config_directory_path))
+                .map_err(|err| LoadSettingsError(Context::new(Box::new(err))))?;
+
+        Ok(settings)
+    }
+
+    pub fn process_runtime(&self) -> &ProcessRuntime {
+        &self.process_runtime
+    }
+}
+
+impl RuntimeSettings for Settings {
+    type Config = ProcessConfig;
+
+    fn agent(&self) -> &ModuleSpec<ProcessConfig> {
+        self.base.agent()
+    }
+
+    fn agent_mut(&mut self) -> &mut ModuleSpec<ProcessConfig> {
+        self.base.agent_mut()
+    }
+
+    fn hostname(&self) -> &str {
+        self.base.hostname()
+    }
+
+    fn connect(&self) -> &Connect {
+        self.base.connect()
+    }
+
+    fn listen(&self) -> &Listen {
+        self.base.listen()
+    }
+
+    fn homedir(&self) -> &Path {
+        self.base.homedir()
+    }
+
+    fn watchdog(&self) -> &WatchdogSettings {
+        self.base.watchdog()
+    }
+
+    fn image_garbage_collection(&self) -> &ImageGarbageCollection {
+        self.base.image_garbage_collection()
+    }
+
+    fn server_cert_policies(&self) -> &BTreeMap<String, ServerCertPolicy> {
+        self.base.server_cert_policies()
+    }
+
+    fn module_secrets(&self) -> &BTreeMap<String, BTreeMap<String, String>> {
+        self.base.module_secrets()
+    }
+
+    fn endpoints(&self) -> &Endpoints {
+        self.base.endpoints()
+    }
+
+    fn edge_ca_cert(&self) -> Option<&str> {
+        self.base.edge_ca_cert()
+    }
+
+    fn edge_ca_key(&self) -> Option<&str> {
+        self.base.edge_ca_key()
+    }
+
+    fn trust_bundle_cert(&self) -> Option<&str> {
+        self.base.trust_bundle_cert()
+    }
+
+    fn manifest_trust_bundle_cert(&self) -> Option<&str> {
+        self.base.manifest_trust_bundle_cert()
+    }
+
+    fn auto_reprovisioning_mode(&self) -> &AutoReprovisioningMode {
+        self.base.auto_reprovisioning_mode()
+    }
+
+    fn log_level(&self) -> Option<&str> {
+        self.base.log_level()
+    }
+}
+
+#[derive(Debug, Fail)]
+#[fail(display = "Could not load settings")]
+pub struct LoadSettingsError(#[cause] Context<Box<dyn std::fmt::Display + Send + Sync>>);
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use edgelet_core::RuntimeSettings;
+
+    use super::Settings;
+
+    static GOOD_SETTINGS: &str = "test/linux/sample_settings.toml";
+
+    #[test]
+    fn settings_are_read() {
+        std::env::set_var("AZIOT_EDGED_CONFIG", GOOD_SETTINGS);
+        let settings = Settings::new().unwrap();
+
+        assert_eq!(
+            "/usr/lib/aziot-edge/edgeAgent",
+            settings.agent().config().command()
+        );
+        assert_eq!(
+            Some(Path::new("/sys/fs/cgroup/aziot-edge.slice")),
+            settings.process_runtime().cgroup_root()
+        );
+        assert_eq!(1024 * 1024, settings.process_runtime().max_log_size());
+        assert_eq!(
+            Some("aziot-module"),
+            settings.process_runtime().default_user()
+        );
+        assert_eq!(
+            Some("plc-adapter"),
+            settings
+                .process_runtime()
+                .module_users()
+                .get("plc")
+                .map(String::as_str)
+        );
+    }
+}
@@ -0,0 +1,73 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use std::net::{Ipv4Addr, SocketAddr};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use futures::future::{self, Either, Loop};
+use futures::Future;
+use log::{info, warn};
+use tokio::net::TcpStream;
+use tokio::timer::Delay;
+use tokio::util::FutureExt;
+
+use edgelet_core::{ModuleDependency, ModuleStartup, ReadinessCondition};
+
+use crate::error::Error;
+use crate::supervisor::Supervisor;
+
+const READINESS_POLL_INTERVAL: Duration = Duration::from_secs(1);
+const READINESS_TIMEOUT: Duration = Duration::from_secs(120);
+const TCP_CONNECT_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Whether the runtime orders the start of this module, rather than leaving it to edgeAgent.
+pub(crate) fn is_ordered(module: &ModuleStartup) -> bool {
+    module.order().is_some() || !module.depends_on().is_empty()
+}
+
+/// Waits until the module a dependency refers to meets its readiness condition.
+///
+/// Processes don't have healthchecks, so they're healthy once they're running. Ports are
+/// connected to on the host, which is where processes listen.
+pub(crate) fn wait_until_ready(
+    supervisor: Arc<Supervisor>,
+    dependency: ModuleDependency,
+) -> impl Future<Item = (), Error = Error> + Send {
+    let deadline = Instant::now() + READINESS_TIMEOUT;
+
+    future::loop_fn(dependency, move |dependency| {
+        let ready = if supervisor.is_running(dependency.name()) {
+            match dependency.condition() {
+                ReadinessCondition::Started | ReadinessCondition::Healthy => {
+                    Either::A(future::ok(true))
+                }
+                ReadinessCondition::TcpPort { port } => Either::B(
+                    TcpStream::connect(&SocketAddr::new(Ipv4Addr::LOCALHOST.into(), port))
+                        .timeout(TCP_CONNECT_TIMEOUT)
+                        .then(|result| Ok(result.is_ok())),
+                ),
+            }
+        } else {
+            Either::A(future::ok(false))
+        };
+
+        ready.and_then(move |ready| {
+            if ready {
+                info!("Module {} is {}", dependency.name(), dependency.condition());
+                Either::A(future::ok(Loop::Break(())))
+            } else if Instant::now() >= deadline {
+                warn!(
+                    "Timed out waiting for module {} to be {}",
+                    dependency.name(),
+                    dependency.condition()
+                );
+                Either::A(future::ok(Loop::Break(())))
+            } else {
+                Either::B(
+                    Delay::new(Instant::now() + READINESS_POLL_INTERVAL)
+                        .then(move |_| Ok(Loop::Continue(dependency))),
+                )
+            }
+        })
+    })
+}
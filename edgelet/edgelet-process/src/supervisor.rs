@@ -0,0 +1,786 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+//! Runs modules' processes and keeps track of them.
+//!
+//! Each module has a directory under the runtime's home directory with its spec, the state
+//! of its process and its logs, so that modules are known again after aziot-edged restarts.
+//! Processes are started in a session and process group of their own, which is what they're
+//! stopped with, and in the module's cgroup when the runtime has a cgroup root. Other
+//! processes can't join a group in another session, so a group only ever has the processes of
+//! one module. Commands that are run in a module get a session of their own too, and are
+//! stopped with the module.
+//!
+//! Processes run as the module's user. Only aziot-edged can read the files in a module's
+//! directory, except for its `data` directory, which belongs to the module's user and is
+//! the working directory of processes that don't have one of their own.
+
+use std::collections::BTreeMap;
+use std::ffi::CString;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::{MetadataExt, OpenOptionsExt, PermissionsExt};
+use std::os::unix::io::AsRawFd;
+use std::os::unix::process::{CommandExt, ExitStatusExt};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use failure::{Fail, ResultExt};
+use log::{info, warn};
+
+use edgelet_core::{ModuleRuntimeState, ModuleSpec, ModuleStatus, RuntimeOperation};
+
+use crate::cgroup::Cgroup;
+use crate::config::ProcessConfig;
+use crate::error::{Error, ErrorKind, Result};
+use crate::logs::{self, LogWriter, OutputStream};
+use crate::proc;
+use crate::user::{ModuleUsers, User};
+
+const SPEC_FILE: &str = "spec.json";
+const STATE_FILE: &str = "state.json";
+const LOG_FILE: &str = "output.log";
+const DATA_DIR: &str = "data";
+
+/// Modules can reach their `data` directory, but can't list the modules or read their files.
+const DIR_MODE: u32 = 0o711;
+const DATA_DIR_MODE: u32 = 0o700;
+const FILE_MODE: u32 = 0o600;
+
+/// The `PATH` that modules' processes start with, unless their spec sets it.
+pub(crate) const DEFAULT_PATH: &str =
+    "/usr/local/sbin:/usr/local/bin:/usr/sbin:/usr/bin:/sbin:/bin";
+
+const STOP_POLL_INTERVAL: Duration = Duration::from_millis(100);
+const KILL_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Clone, Debug, Default, serde_derive::Deserialize, serde_derive::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ProcessState {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pid: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    start_time: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    started_at: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    finished_at: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    exit_code: Option<i64>,
+}
+
+impl ProcessState {
+    fn running_pid(&self) -> Option<i32> {
+        match (self.pid, self.start_time) {
+            (Some(pid), Some(start_time)) if proc::is_alive(pid, start_time) => Some(pid),
+            _ => None,
+        }
+    }
+}
+
+struct Entry {
+    spec: ModuleSpec<ProcessConfig>,
+    state: ProcessState,
+    /// Whether the process was started by this instance of the runtime, which then records
+    /// how it exits. Processes that were started before aziot-edged restarted are only
+    /// watched.
+    supervised: bool,
+    /// Commands that were run in the module, by pid and start time.
+    execs: Vec<(i32, u64)>,
+}
+
+impl Entry {
+    /// The pid of the module's process, if it's running. Processes that this runtime started
+    /// count as running until their exit is recorded, so that they're never seen to have
+    /// exited without an exit code.
+    fn running_pid(&self) -> Option<i32> {
+        if self.supervised {
+            self.state.pid
+        } else {
+            self.state.running_pid()
+        }
+    }
+}
+
+pub(crate) struct Supervisor {
+    dir: PathBuf,
+    cgroup_root: Option<PathBuf>,
+    max_log_size: u64,
+    users: ModuleUsers,
+    modules: Mutex<BTreeMap<String, Entry>>,
+}
+
+impl Supervisor {
+    /// Loads the modules in `dir`. Modules whose files can't be read are skipped.
+    pub(crate) fn load(
+        dir: &Path,
+        cgroup_root: Option<&Path>,
+        max_log_size: u64,
+        users: ModuleUsers,
+    ) -> Result<Self> {
+        fs::create_dir_all(dir).context(ErrorKind::Initialization)?;
+        fs::set_permissions(dir, fs::Permissions::from_mode(DIR_MODE))
+            .context(ErrorKind::Initialization)?;
+
+        let mut modules = BTreeMap::new();
+        for module_dir in fs::read_dir(dir).context(ErrorKind::Initialization)? {
+            let module_dir = module_dir.context(ErrorKind::Initialization)?.path();
+            let spec: ModuleSpec<ProcessConfig> = match read_json(&module_dir.join(SPEC_FILE)) {
+                Ok(spec) => spec,
+                Err(err) => {
+                    warn!("Skipping module in {}: {}", module_dir.display(), err);
+                    continue;
+                }
+            };
+            let state = read_json(&module_dir.join(STATE_FILE)).unwrap_or_default();
+            // The files of modules that were created by older versions may be readable by others.
+            if let Err(err) = restrict_access(&module_dir) {
+                warn!(
+                    "Could not restrict access to {}: {}",
+                    module_dir.display(),
+                    err
+                );
+            }
+            modules.insert(
+                spec.name().to_string(),
+                Entry {
+                    spec,
+                    state,
+                    supervised: false,
+                    execs: vec![],
+                },
+            );
+        }
+
+        Ok(Supervisor {
+            dir: dir.to_path_buf(),
+            cgroup_root: cgroup_root.map(Path::to_path_buf),
+            max_log_size,
+            users,
+            modules: Mutex::new(modules),
+        })
+    }
+
+    fn modules(&self) -> MutexGuard<'_, BTreeMap<String, Entry>> {
+        self.modules.lock().expect("module lock poisoned")
+    }
+
+    fn module_dir(&self, name: &str) -> PathBuf {
+        self.dir.join(name)
+    }
+
+    pub(crate) fn log_path(&self, name: &str) -> PathBuf {
+        self.module_dir(name).join(LOG_FILE)
+    }
+
+    fn cgroup(&self, name: &str) -> Option<Cgroup> {
+        self.cgroup_root
+            .as_ref()
+            .map(|root| Cgroup::new(root, name))
+    }
+
+    pub(crate) fn create(&self, spec: ModuleSpec<ProcessConfig>) -> Result<()> {
+        let name = spec.name().to_string();
+        let context = || ErrorKind::RuntimeOperation(RuntimeOperation::CreateModule(name.clone()));
+
+        if name.contains('/') || name == "." || name == ".." {
+            return Err(Error::from(
+                ErrorKind::InvalidModuleName(name.clone()).context(context()),
+            ));
+        }
+        let has_limits = spec
+            .config()
+            .resource_limits()
+            .map_or(false, |limits| !limits.is_empty());
+        if has_limits && self.cgroup_root.is_none() {
+            return Err(Error::from(
+                ErrorKind::CgroupRootNotSet(name.clone()).context(context()),
+            ));
+        }
+
+        let mut modules = self.modules();
+        if modules.contains_key(&name) {
+            return Err(Error::from(
+                ErrorKind::Conflict(name.clone()).context(context()),
+            ));
+        }
+
+        let dir = self.module_dir(&name);
+        fs::create_dir_all(&dir).with_context(|_| context())?;
+        fs::set_permissions(&dir, fs::Permissions::from_mode(DIR_MODE))
+            .with_context(|_| context())?;
+        write_json(&dir.join(SPEC_FILE), &spec).with_context(|_| context())?;
+        write_json(&dir.join(STATE_FILE), &ProcessState::default()).with_context(|_| context())?;
+
+        modules.insert(
+            name,
+            Entry {
+                spec,
+                state: ProcessState::default(),
+                supervised: false,
+                execs: vec![],
+            },
+        );
+        Ok(())
+    }
+
+    pub(crate) fn list(&self) -> Vec<ModuleSpec<ProcessConfig>> {
+        self.modules()
+            .values()
+            .map(|entry| entry.spec.clone())
+            .collect()
+    }
+
+    pub(crate) fn spec(&self, name: &str) -> Option<ModuleSpec<ProcessConfig>> {
+        self.modules().get(name).map(|entry| entry.spec.clone())
+    }
+
+    pub(crate) fn runtime_state(&self, name: &str) -> Option<ModuleRuntimeState> {
+        self.modules()
+            .get(name)
+            .map(|entry| runtime_state(&entry.state, entry.running_pid()))
+    }
+
+    /// The pid of the module's process, if it's running.
+    pub(crate) fn pid(&self, name: &str) -> Option<i32> {
+        self.modules().get(name).and_then(Entry::running_pid)
+    }
+
+    pub(crate) fn is_running(&self, name: &str) -> bool {
+        self.pid(name).is_some()
+    }
+
+    /// Whether `pid` is one of the processes of the running module: in its cgroup when the
+    /// runtime has a cgroup root, or else its process, a command that was run in it, or one
+    /// of their descendants.
+    pub(crate) fn owns(&self, name: &str, pid: i32) -> bool {
+        let roots = {
+            let modules = self.modules();
+            let entry = match modules.get(name) {
+                Some(entry) if entry.running_pid().is_some() => entry,
+                _ => return false,
+            };
+            let mut roots = entry.execs.clone();
+            if let (Some(pid), Some(start_time)) = (entry.state.pid, entry.state.start_time) {
+                roots.push((pid, start_time));
+            }
+            roots
+        };
+
+        match self.cgroup(name) {
+            Some(cgroup) => cgroup.contains(pid).unwrap_or_else(|err| {
+                warn!("Could not read the processes of module {}: {}", name, err);
+                false
+            }),
+            None => proc::belongs_to(pid, &roots, proc::stat),
+        }
+    }
+
+    /// Records a command that was run in the module, so that it's stopped with the module.
+    pub(crate) fn add_exec(&self, name: &str, pid: i32) {
+        if let Some(entry) = self.modules().get_mut(name) {
+            entry
+                .execs
+                .retain(|&(pid, start_time)| proc::is_alive(pid, start_time));
+            if let Some(stat) = proc::stat(pid) {
+                entry.execs.push((pid, stat.start_time));
+            }
+        }
+    }
+
+    /// A command that runs `program` like the module's process: as its user, with its
+    /// environment, in its working directory and cgroup, in a session of its own. The returned
+    /// file must be kept open until the command is spawned.
+    pub(crate) fn command(
+        &self,
+        spec: &ModuleSpec<ProcessConfig>,
+        program: &str,
+        args: &[String],
+    ) -> Result<(Command, Option<File>)> {
+        let name = spec.name();
+        let user = self.users.lookup(name)?;
+
+        let working_dir = match spec.config().working_dir() {
+            Some(working_dir) => working_dir.to_path_buf(),
+            None => self.data_dir(name, &user).context(ErrorKind::ModuleUser(
+                name.to_string(),
+                user.uid.to_string(),
+            ))?,
+        };
+
+        let mut command = Command::new(program);
+        command
+            .args(args)
+            .env_clear()
+            .env("PATH", DEFAULT_PATH)
+            .envs(spec.env())
+            .current_dir(working_dir);
+
+        let procs = match self.cgroup(name) {
+            Some(cgroup) => {
+                let procs = cgroup
+                    .apply(&spec.config().resource_limits().cloned().unwrap_or_default())
+                    .and_then(|_| cgroup.procs())
+                    .with_context(|_| ErrorKind::Cgroup(name.to_string()))?;
+                Some(procs)
+            }
+            None => None,
+        };
+
+        let procs_fd = procs.as_ref().map(AsRawFd::as_raw_fd);
+        // Only async-signal-safe functions can be called between fork and exec. The process
+        // joins its cgroup before it gives up the privileges that that takes.
+        unsafe {
+            command.pre_exec(move || {
+                new_session()?;
+                if let Some(fd) = procs_fd {
+                    if libc::write(fd, b"0".as_ptr().cast(), 1) < 0 {
+                        return Err(io::Error::last_os_error());
+                    }
+                }
+                user.switch()
+            });
+        }
+
+        Ok((command, procs))
+    }
+
+    /// Creates the module's data directory, if it doesn't exist yet, and gives it to the
+    /// module's user.
+    fn data_dir(&self, name: &str, user: &User) -> io::Result<PathBuf> {
+        let data_dir = self.module_dir(name).join(DATA_DIR);
+        fs::create_dir_all(&data_dir)?;
+        if fs::metadata(&data_dir)?.uid() != user.uid {
+            fs::set_permissions(&data_dir, fs::Permissions::from_mode(DATA_DIR_MODE))?;
+            let c_path = CString::new(data_dir.as_os_str().as_bytes())
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+            if unsafe { libc::chown(c_path.as_ptr(), user.uid, user.gid) } != 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+
+        Ok(data_dir)
+    }
+
+    /// Empties the module's data directory as the user that owns it, since aziot-edged may
+    /// not have access to the files that the module created in it.
+    fn clear_data_dir(&self, name: &str) -> io::Result<()> {
+        let data_dir = self.module_dir(name).join(DATA_DIR);
+        let metadata = match fs::symlink_metadata(&data_dir) {
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+            metadata => metadata?,
+        };
+        if !metadata.is_dir() || metadata.uid() == unsafe { libc::geteuid() } {
+            return Ok(());
+        }
+
+        let owner = User::owner(&metadata);
+        let mut command = Command::new("find");
+        command
+            .arg(&data_dir)
+            .args(&["-mindepth", "1", "-delete"])
+            .env_clear()
+            .env("PATH", DEFAULT_PATH)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null());
+        unsafe {
+            command.pre_exec(move || owner.switch());
+        }
+        let status = command.status()?;
+        if !status.success() {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                format!("could not delete the files in {}", data_dir.display()),
+            ));
+        }
+        Ok(())
+    }
+
+    pub(crate) fn start(self: &Arc<Self>, name: &str) -> Result<()> {
+        let context =
+            || ErrorKind::RuntimeOperation(RuntimeOperation::StartModule(name.to_string()));
+
+        let mut modules = self.modules();
+        let entry = modules
+            .get_mut(name)
+            .ok_or_else(|| ErrorKind::NotFound(name.to_string()).context(context()))?;
+        if entry.running_pid().is_some() {
+            return Err(Error::from(ErrorKind::NotModified.context(context())));
+        }
+
+        let spec = &entry.spec;
+        let writer =
+            LogWriter::open(&self.log_path(name), self.max_log_size).with_context(|_| context())?;
+        let (mut command, procs) = self
+            .command(spec, spec.config().command(), spec.config().args())
+            .with_context(|_| context())?;
+        let mut child = command
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .with_context(|_| context())?;
+        drop(procs);
+
+        #[allow(clippy::cast_possible_wrap)]
+        let pid = child.id() as i32;
+        entry.state = ProcessState {
+            pid: Some(pid),
+            start_time: proc::stat(pid).map(|stat| stat.start_time),
+            started_at: Some(Utc::now()),
+            finished_at: None,
+            exit_code: None,
+        };
+        entry.supervised = true;
+        self.save_state(name, &entry.state);
+
+        let writer = Arc::new(writer);
+        if let Some(stdout) = child.stdout.take() {
+            logs::capture(name, stdout, OutputStream::Stdout, writer.clone());
+        }
+        if let Some(stderr) = child.stderr.take() {
+            logs::capture(name, stderr, OutputStream::Stderr, writer);
+        }
+        self.wait(name, pid, child);
+
+        info!("Started module {} with pid {}", name, pid);
+        Ok(())
+    }
+
+    /// Waits for the module's process to exit, and records how it exited.
+    fn wait(self: &Arc<Self>, name: &str, pid: i32, mut child: Child) {
+        let supervisor = self.clone();
+        let name = name.to_string();
+        thread::spawn(move || {
+            let exit_code = match child.wait() {
+                Ok(status) => Some(exit_code(status)),
+                Err(err) => {
+                    warn!("Could not wait for module {} to exit: {}", name, err);
+                    None
+                }
+            };
+            info!("Module {} exited with code {:?}", name, exit_code);
+
+            let mut modules = supervisor.modules();
+            if let Some(entry) = modules.get_mut(&name) {
+                if entry.state.pid == Some(pid) {
+                    entry.state.pid = None;
+                    entry.state.start_time = None;
+                    entry.state.finished_at = Some(Utc::now());
+                    entry.state.exit_code = exit_code;
+                    supervisor.save_state(&name, &entry.state);
+                }
+            }
+        });
+    }
+
+    /// Stops the module's processes, first with SIGTERM and then, after `timeout`, with
+    /// SIGKILL. Without a timeout, they're killed right away.
+    pub(crate) fn stop(&self, name: &str, timeout: Duration) -> Result<()> {
+        let context =
+            || ErrorKind::RuntimeOperation(RuntimeOperation::StopModule(name.to_string()));
+
+        let (pid, execs) = {
+            let modules = self.modules();
+            let entry = modules
+                .get(name)
+                .ok_or_else(|| ErrorKind::NotFound(name.to_string()).context(context()))?;
+            let pid = entry
+                .running_pid()
+                .ok_or_else(|| ErrorKind::NotModified.context(context()))?;
+            (pid, entry.execs.clone())
+        };
+        let signal = |signal| -> io::Result<()> {
+            signal_group(pid, signal)?;
+            for &(pid, start_time) in &execs {
+                if proc::is_alive(pid, start_time) {
+                    signal_group(pid, signal)?;
+                }
+            }
+            Ok(())
+        };
+
+        let mut stopped = false;
+        if timeout > Duration::from_secs(0) {
+            signal(libc::SIGTERM).with_context(|_| context())?;
+            stopped = self.wait_for_exit(name, pid, timeout);
+            if !stopped {
+                warn!(
+                    "Module {} did not stop within {:?}, killing it",
+                    name, timeout
+                );
+            }
+        }
+        if !stopped {
+            signal(libc::SIGKILL).with_context(|_| context())?;
+            self.wait_for_exit(name, pid, KILL_TIMEOUT);
+        }
+
+        // Nothing waits for processes that were started before aziot-edged restarted, so
+        // their exit is recorded here, without an exit code.
+        let mut modules = self.modules();
+        if let Some(entry) = modules.get_mut(name) {
+            if !entry.supervised && entry.state.pid == Some(pid) {
+                entry.state.pid = None;
+                entry.state.start_time = None;
+                entry.state.finished_at = Some(Utc::now());
+                self.save_state(name, &entry.state);
+            }
+        }
+
+        info!("Stopped module {}", name);
+        Ok(())
+    }
+
+    /// Waits until the process exited and, if this runtime started it, its exit was recorded.
+    fn wait_for_exit(&self, name: &str, pid: i32, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let exited = match self.modules().get(name) {
+                Some(entry) if entry.state.pid == Some(pid) => {
+                    !entry.supervised && entry.state.running_pid().is_none()
+                }
+                _ => true,
+            };
+            if exited {
+                return true;
+            }
+            if Instant::now() >= deadline {
+                return false;
+            }
+            thread::sleep(STOP_POLL_INTERVAL);
+        }
+    }
+
+    /// Removes the module, killing its processes if it's running.
+    pub(crate) fn remove(&self, name: &str) -> Result<()> {
+        let context =
+            || ErrorKind::RuntimeOperation(RuntimeOperation::RemoveModule(name.to_string()));
+
+        if !self.modules().contains_key(name) {
+            return Err(Error::from(
+                ErrorKind::NotFound(name.to_string()).context(context()),
+            ));
+        }
+
+        match self.stop(name, Duration::from_secs(0)) {
+            Ok(()) => (),
+            Err(err) => match Fail::find_root_cause(&err).downcast_ref::<ErrorKind>() {
+                Some(ErrorKind::NotModified) | Some(ErrorKind::NotFound(_)) => (),
+                _ => return Err(err),
+            },
+        }
+
+        if let Some(cgroup) = self.cgroup(name) {
+            if let Err(err) = cgroup.remove() {
+                warn!("Could not remove the cgroup of module {}: {}", name, err);
+            }
+        }
+        self.clear_data_dir(name).with_context(|_| context())?;
+        match fs::remove_dir_all(self.module_dir(name)) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => {
+                return Err(Error::from(err.context(context())));
+            }
+            _ => (),
+        }
+
+        self.modules().remove(name);
+        info!("Removed module {}", name);
+        Ok(())
+    }
+
+    fn save_state(&self, name: &str, state: &ProcessState) {
+        if let Err(err) = write_json(&self.module_dir(name).join(STATE_FILE), state) {
+            let err = err.context(ErrorKind::SaveState(name.to_string()));
+            warn!("{}", err);
+        }
+    }
+}
+
+fn runtime_state(state: &ProcessState, pid: Option<i32>) -> ModuleRuntimeState {
+    let (status, description) = match (pid, state.exit_code, state.started_at) {
+        (Some(_), _, _) => (ModuleStatus::Running, "running"),
+        (None, Some(0), _) => (ModuleStatus::Stopped, "exited"),
+        // Processes that exit while the runtime isn't watching them exit without a known code.
+        (None, _, Some(_)) => (ModuleStatus::Failed, "exited"),
+        (None, _, None) => (ModuleStatus::Stopped, "created"),
+    };
+
+    ModuleRuntimeState::default()
+        .with_status(status)
+        .with_exit_code(state.exit_code)
+        .with_status_description(Some(description.to_string()))
+        .with_started_at(state.started_at)
+        .with_finished_at(state.finished_at)
+        .with_pid(pid)
+}
+
+/// Exit codes follow the shell's convention, so processes killed by a signal exit with
+/// 128 plus the signal's number.
+pub(crate) fn exit_code(status: ExitStatus) -> i64 {
+    match (status.code(), status.signal()) {
+        (Some(code), _) => i64::from(code),
+        (None, Some(signal)) => 128 + i64::from(signal),
+        (None, None) => -1,
+    }
+}
+
+/// Makes the process the leader of a new session and process group. It's called between fork
+/// and exec, so it must be async-signal-safe.
+fn new_session() -> io::Result<()> {
+    if unsafe { libc::setsid() } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+fn signal_group(pid: i32, signal: libc::c_int) -> io::Result<()> {
+    if unsafe { libc::kill(-pid, signal) } == 0 {
+        return Ok(());
+    }
+    match io::Error::last_os_error() {
+        // The process group is already gone.
+        err if err.raw_os_error() == Some(libc::ESRCH) => Ok(()),
+        err => Err(err),
+    }
+}
+
+/// Makes the module's directory and the files in it private to aziot-edged, apart from the
+/// module's data directory.
+fn restrict_access(module_dir: &Path) -> io::Result<()> {
+    fs::set_permissions(module_dir, fs::Permissions::from_mode(DIR_MODE))?;
+    for entry in fs::read_dir(module_dir)? {
+        let entry = entry?;
+        if entry.file_type()?.is_file() {
+            fs::set_permissions(entry.path(), fs::Permissions::from_mode(FILE_MODE))?;
+        }
+    }
+    Ok(())
+}
+
+fn read_json<T>(path: &Path) -> io::Result<T>
+where
+    T: serde::de::DeserializeOwned,
+{
+    let file = File::open(path)?;
+    serde_json::from_reader(file).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+/// Writes to a temporary file first, so that the file is never left partly written.
+fn write_json<T>(path: &Path, value: &T) -> io::Result<()>
+where
+    T: serde::Serialize,
+{
+    let temp = path.with_extension("tmp");
+    let data =
+        serde_json::to_vec(value).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+    OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(FILE_MODE)
+        .open(&temp)?
+        .write_all(&data)?;
+    fs::rename(temp, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+    use std::os::unix::process::{CommandExt, ExitStatusExt};
+    use std::process::{Command, ExitStatus};
+
+    use chrono::Utc;
+
+    use edgelet_core::ModuleStatus;
+
+    use super::{exit_code, new_session, runtime_state, signal_group, ProcessState};
+
+    #[test]
+    fn exit_codes() {
+        assert_eq!(0, exit_code(ExitStatus::from_raw(0)));
+        assert_eq!(3, exit_code(ExitStatus::from_raw(3 << 8)));
+        assert_eq!(128 + 15, exit_code(ExitStatus::from_raw(15)));
+    }
+
+    #[test]
+    #[allow(clippy::cast_possible_wrap)]
+    fn processes_cant_join_a_module_group() {
+        let mut module = Command::new("sleep");
+        module.arg("10");
+        unsafe {
+            module.pre_exec(new_session);
+        }
+        let mut module = module.spawn().unwrap();
+        let group = module.id() as i32;
+
+        let mut other = Command::new("true");
+        unsafe {
+            other.pre_exec(move || {
+                if libc::setpgid(0, group) != 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                Ok(())
+            });
+        }
+        let err = other.spawn().unwrap_err();
+        assert_eq!(Some(libc::EPERM), err.raw_os_error());
+
+        signal_group(group, libc::SIGKILL).unwrap();
+        module.wait().unwrap();
+    }
+
+    #[test]
+    #[allow(clippy::cast_possible_wrap)]
+    fn states() {
+        let created = runtime_state(&ProcessState::default(), None);
+        assert_eq!(&ModuleStatus::Stopped, created.status());
+        assert_eq!(Some("created"), created.status_description());
+
+        let exited = ProcessState {
+            started_at: Some(Utc::now()),
+            finished_at: Some(Utc::now()),
+            exit_code: Some(0),
+            ..ProcessState::default()
+        };
+        assert_eq!(
+            &ModuleStatus::Stopped,
+            runtime_state(&exited, exited.running_pid()).status()
+        );
+
+        let failed = ProcessState {
+            exit_code: Some(1),
+            ..exited.clone()
+        };
+        assert_eq!(
+            &ModuleStatus::Failed,
+            runtime_state(&failed, failed.running_pid()).status()
+        );
+
+        // A process that's gone, without a recorded exit
+        let gone = ProcessState {
+            pid: Some(i32::MAX),
+            start_time: Some(1),
+            exit_code: None,
+            ..exited
+        };
+        let gone = runtime_state(&gone, gone.running_pid());
+        assert_eq!(&ModuleStatus::Failed, gone.status());
+        assert_eq!(None, gone.pid());
+
+        let running = ProcessState {
+            pid: Some(std::process::id() as i32),
+            start_time: super::proc::stat(std::process::id() as i32).map(|stat| stat.start_time),
+            started_at: Some(Utc::now()),
+            ..ProcessState::default()
+        };
+        let running = runtime_state(&running, running.running_pid());
+        assert_eq!(&ModuleStatus::Running, running.status());
+        assert_eq!(Some(std::process::id() as i32), running.pid());
+    }
+}
@@ -0,0 +1,225 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+//! The users that modules' processes run as.
+//!
+//! Modules never run as root or as the user aziot-edged runs as, so that they can't use its
+//! access to the other services or read the files of aziot-edged and of other modules.
+
+use std::collections::BTreeMap;
+use std::ffi::{CStr, CString};
+use std::fs;
+use std::io;
+use std::mem;
+use std::os::unix::fs::MetadataExt;
+use std::ptr;
+
+use failure::ResultExt;
+
+use crate::error::{Error, ErrorKind, Result};
+
+const INITIAL_BUFFER_SIZE: usize = 1024;
+const MAX_BUFFER_SIZE: usize = 1024 * 1024;
+
+/// Which user each module runs as.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct ModuleUsers {
+    default: Option<String>,
+    modules: BTreeMap<String, String>,
+}
+
+impl ModuleUsers {
+    pub(crate) fn new(default: Option<String>, modules: BTreeMap<String, String>) -> Self {
+        ModuleUsers { default, modules }
+    }
+
+    /// Looks up the user that the module runs as. Fails if the module has no user, or if
+    /// it's root or the user of this process.
+    pub(crate) fn lookup(&self, module: &str) -> Result<User> {
+        let name = self
+            .modules
+            .get(module)
+            .or_else(|| self.default.as_ref())
+            .ok_or_else(|| ErrorKind::ModuleUserNotSet(module.to_string()))?;
+
+        let user = User::lookup(name)
+            .with_context(|_| ErrorKind::ModuleUser(module.to_string(), name.clone()))?;
+        if user.uid == 0 || user.uid == unsafe { libc::geteuid() } {
+            return Err(Error::from(ErrorKind::PrivilegedModuleUser(
+                module.to_string(),
+                name.clone(),
+            )));
+        }
+        Ok(user)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct User {
+    pub(crate) uid: libc::uid_t,
+    pub(crate) gid: libc::gid_t,
+    groups: Vec<libc::gid_t>,
+}
+
+impl User {
+    /// Looks up a user by name, or by uid if `name` is a number.
+    fn lookup(name: &str) -> io::Result<Self> {
+        let mut buffer: Vec<libc::c_char> = vec![0; INITIAL_BUFFER_SIZE];
+        loop {
+            let mut passwd: libc::passwd = unsafe { mem::zeroed() };
+            let mut result = ptr::null_mut();
+            let err = match name.parse::<libc::uid_t>() {
+                Ok(uid) => unsafe {
+                    libc::getpwuid_r(
+                        uid,
+                        &mut passwd,
+                        buffer.as_mut_ptr(),
+                        buffer.len(),
+                        &mut result,
+                    )
+                },
+                Err(_) => {
+                    let c_name = CString::new(name)
+                        .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+                    unsafe {
+                        libc::getpwnam_r(
+                            c_name.as_ptr(),
+                            &mut passwd,
+                            buffer.as_mut_ptr(),
+                            buffer.len(),
+                            &mut result,
+                        )
+                    }
+                }
+            };
+
+            match err {
+                0 if result.is_null() => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::NotFound,
+                        format!("user {:?} does not exist", name),
+                    ));
+                }
+                0 => {
+                    let user_name = unsafe { CStr::from_ptr(passwd.pw_name) }.to_owned();
+                    let groups = groups(&user_name, passwd.pw_gid)?;
+                    return Ok(User {
+                        uid: passwd.pw_uid,
+                        gid: passwd.pw_gid,
+                        groups,
+                    });
+                }
+                libc::ERANGE if buffer.len() < MAX_BUFFER_SIZE => {
+                    buffer.resize(buffer.len() * 2, 0);
+                }
+                err => return Err(io::Error::from_raw_os_error(err)),
+            }
+        }
+    }
+
+    /// The user that owns a file, with only its group.
+    pub(crate) fn owner(metadata: &fs::Metadata) -> Self {
+        User {
+            uid: metadata.uid(),
+            gid: metadata.gid(),
+            groups: vec![metadata.gid()],
+        }
+    }
+
+    /// Changes the credentials of this process to the user's.
+    ///
+    /// Only async-signal-safe functions are called, so that this can run between fork and exec.
+    pub(crate) fn switch(&self) -> io::Result<()> {
+        unsafe {
+            if libc::setgroups(self.groups.len(), self.groups.as_ptr()) != 0
+                || libc::setgid(self.gid) != 0
+                || libc::setuid(self.uid) != 0
+            {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The groups that a user is a member of, including its primary group.
+fn groups(name: &CStr, gid: libc::gid_t) -> io::Result<Vec<libc::gid_t>> {
+    let mut groups: Vec<libc::gid_t> = vec![0; 16];
+    loop {
+        #[allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap)]
+        let mut count = groups.len() as libc::c_int;
+        let result =
+            unsafe { libc::getgrouplist(name.as_ptr(), gid, groups.as_mut_ptr(), &mut count) };
+        #[allow(clippy::cast_sign_loss)]
+        let count = count as usize;
+        if result >= 0 {
+            groups.truncate(count);
+            return Ok(groups);
+        }
+        if count <= groups.len() {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                "could not look up the groups of the user",
+            ));
+        }
+        groups.resize(count, 0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::{ModuleUsers, User};
+    use crate::error::ErrorKind;
+
+    #[test]
+    fn lookup_by_name_and_uid() {
+        let root = User::lookup("root").unwrap();
+        assert_eq!(0, root.uid);
+        assert_eq!(0, root.gid);
+        assert!(root.groups.contains(&0));
+
+        let root = User::lookup("0").unwrap();
+        assert_eq!(0, root.uid);
+
+        User::lookup("surely-not-a-user").unwrap_err();
+    }
+
+    #[test]
+    fn module_users() {
+        let mut modules = BTreeMap::new();
+        modules.insert("m1".to_string(), "root".to_string());
+        modules.insert("m2".to_string(), "surely-not-a-user".to_string());
+        modules.insert("m3".to_string(), unsafe { libc::geteuid() }.to_string());
+
+        let users = ModuleUsers::new(None, modules.clone());
+        let mut expected = vec![
+            ("m1", "PrivilegedModuleUser"),
+            ("m2", "ModuleUser"),
+            ("m4", "ModuleUserNotSet"),
+        ];
+        // The user of this process may not have an entry in the passwd database.
+        if User::lookup(&modules["m3"]).is_ok() {
+            expected.push(("m3", "PrivilegedModuleUser"));
+        }
+        for (module, kind) in &expected {
+            let err = users.lookup(module).unwrap_err();
+            let actual = match err.kind() {
+                ErrorKind::PrivilegedModuleUser(..) => "PrivilegedModuleUser",
+                ErrorKind::ModuleUser(..) => "ModuleUser",
+                ErrorKind::ModuleUserNotSet(_) => "ModuleUserNotSet",
+                kind => panic!("unexpected error kind {:?}", kind),
+            };
+            assert_eq!(kind, &actual, "module {}", module);
+        }
+
+        let users = ModuleUsers::new(Some("root".to_string()), modules);
+        match users.lookup("m4").unwrap_err().kind() {
+            ErrorKind::PrivilegedModuleUser(module, user) => {
+                assert_eq!("m4", module);
+                assert_eq!("root", user);
+            }
+            kind => panic!("unexpected error kind {:?}", kind),
+        }
+    }
+}
@@ -0,0 +1,371 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+#![deny(rust_2018_idioms, warnings)]
+#![deny(clippy::all, clippy::pedantic)]
+#![allow(clippy::default_trait_access)]
+
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use failure::Fail;
+use futures::{Future, Stream};
+use tempfile::{NamedTempFile, TempDir};
+
+use edgelet_core::{
+    ExecOptions, ImagePullPolicy, LogChunk, LogDecode, LogOptions, MakeModuleRuntime, Module,
+    ModuleRegistry, ModuleRuntime, ModuleSpec, ModuleStatus,
+};
+use edgelet_process::{ErrorKind, ProcessConfig, ProcessModuleRuntime, Settings, MODULE_TYPE};
+
+fn make_runtime(homedir: &Path) -> ProcessModuleRuntime {
+    make_runtime_with_users(homedir, r#"default_user = "nobody""#)
+}
+
+fn make_runtime_with_users(homedir: &Path, users: &str) -> ProcessModuleRuntime {
+    lazy_static::lazy_static! {
+        static ref ENV_LOCK: std::sync::Mutex<()> = Default::default();
+    }
+
+    let _env_lock = ENV_LOCK.lock().expect("env lock poisoned");
+
+    let mut config_file = NamedTempFile::new().expect("could not create tempfile for config");
+    write!(
+        config_file,
+        r#"
+hostname = "zoo"
+homedir = "{}"
+
+[agent]
+name = "edgeAgent"
+type = "process"
+
+[agent.config]
+command = "/usr/lib/aziot-edge/edgeAgent"
+
+[connect]
+workload_uri = "unix:///var/lib/iotedge/workload.sock"
+management_uri = "unix:///var/lib/iotedge/mgmt.sock"
+
+[listen]
+workload_uri = "unix:///var/lib/iotedge/workload.sock"
+management_uri = "unix:///var/lib/iotedge/mgmt.sock"
+
+[process_runtime]
+{}
+"#,
+        homedir.display(),
+        users
+    )
+    .expect("could not write to config file");
+
+    std::env::set_var("AZIOT_EDGED_CONFIG", config_file.path());
+    std::env::set_var("AZIOT_EDGED_CONFIG_DIR", homedir.join("config.d"));
+
+    let settings = Settings::new().unwrap();
+    ProcessModuleRuntime::make_runtime(settings).wait().unwrap()
+}
+
+/// Modules run as their own users, which only root can switch to.
+fn can_run_modules() -> bool {
+    unsafe { libc::geteuid() == 0 }
+}
+
+fn shell_module(name: &str, script: &str) -> ModuleSpec<ProcessConfig> {
+    let config = ProcessConfig::new("sh".to_string())
+        .unwrap()
+        .with_args(vec!["-c".to_string(), script.to_string()]);
+    let mut env = BTreeMap::new();
+    env.insert("GREETING".to_string(), "hello".to_string());
+    ModuleSpec::new(
+        name.to_string(),
+        MODULE_TYPE.to_string(),
+        config,
+        env,
+        ImagePullPolicy::default(),
+    )
+    .unwrap()
+}
+
+fn wait_for_status(runtime: &ProcessModuleRuntime, name: &str, status: ModuleStatus) {
+    let deadline = Instant::now() + Duration::from_secs(10);
+    loop {
+        let (_, state) = runtime.get(name).wait().unwrap();
+        if *state.status() == status {
+            return;
+        }
+        assert!(
+            Instant::now() < deadline,
+            "module {} is {:?}, not {:?}",
+            name,
+            state.status(),
+            status
+        );
+        thread::sleep(Duration::from_millis(50));
+    }
+}
+
+fn read_logs(runtime: &ProcessModuleRuntime, name: &str) -> Vec<LogChunk> {
+    let logs = runtime.logs(name, &LogOptions::new()).wait().unwrap();
+    let data = logs.concat2().wait().unwrap().to_vec();
+    LogDecode::new(std::io::Cursor::new(data))
+        .collect()
+        .wait()
+        .unwrap()
+}
+
+#[test]
+fn module_runs_to_completion() {
+    if !can_run_modules() {
+        return;
+    }
+
+    let homedir = TempDir::new().unwrap();
+    let runtime = make_runtime(homedir.path());
+
+    runtime
+        .create(shell_module("m1", "echo $GREETING; echo oops >&2"))
+        .wait()
+        .unwrap();
+    let (module, state) = runtime.get("m1").wait().unwrap();
+    assert_eq!(MODULE_TYPE, module.type_());
+    assert_eq!(&ModuleStatus::Stopped, state.status());
+    assert_eq!(Some("created"), state.status_description());
+
+    ModuleRuntime::start(&runtime, "m1").wait().unwrap();
+    wait_for_status(&runtime, "m1", ModuleStatus::Stopped);
+    let (_, state) = runtime.get("m1").wait().unwrap();
+    assert_eq!(Some(0), state.exit_code());
+
+    // Output is captured by threads of its own, which may still be writing it.
+    let deadline = Instant::now() + Duration::from_secs(10);
+    let mut logs = read_logs(&runtime, "m1");
+    while logs.len() < 2 && Instant::now() < deadline {
+        thread::sleep(Duration::from_millis(50));
+        logs = read_logs(&runtime, "m1");
+    }
+    assert!(logs.contains(&LogChunk::Stdout("hello\n".into())));
+    assert!(logs.contains(&LogChunk::Stderr("oops\n".into())));
+}
+
+#[test]
+fn module_that_fails_is_failed() {
+    if !can_run_modules() {
+        return;
+    }
+
+    let homedir = TempDir::new().unwrap();
+    let runtime = make_runtime(homedir.path());
+
+    runtime.create(shell_module("m1", "exit 3")).wait().unwrap();
+    ModuleRuntime::start(&runtime, "m1").wait().unwrap();
+    wait_for_status(&runtime, "m1", ModuleStatus::Failed);
+
+    let (_, state) = runtime.get("m1").wait().unwrap();
+    assert_eq!(Some(3), state.exit_code());
+}
+
+#[test]
+fn module_is_stopped_and_removed() {
+    if !can_run_modules() {
+        return;
+    }
+
+    let homedir = TempDir::new().unwrap();
+    let runtime = make_runtime(homedir.path());
+
+    runtime
+        .create(shell_module("m1", "exec sleep 30"))
+        .wait()
+        .unwrap();
+    ModuleRuntime::start(&runtime, "m1").wait().unwrap();
+    let (_, state) = runtime.get("m1").wait().unwrap();
+    assert_eq!(&ModuleStatus::Running, state.status());
+    assert!(state.pid().is_some());
+
+    // Starting a running module doesn't start it again.
+    let err = ModuleRuntime::start(&runtime, "m1").wait().unwrap_err();
+    match Fail::find_root_cause(&err).downcast_ref::<ErrorKind>() {
+        Some(ErrorKind::NotModified) => (),
+        kind => panic!("expected NotModified but got {:?}", kind),
+    }
+
+    ModuleRuntime::stop(&runtime, "m1", Some(Duration::from_secs(5)))
+        .wait()
+        .unwrap();
+    let (_, state) = runtime.get("m1").wait().unwrap();
+    assert_eq!(None, state.pid());
+    // sleep is stopped by SIGTERM.
+    assert_eq!(Some(128 + 15), state.exit_code());
+
+    ModuleRuntime::remove(&runtime, "m1").wait().unwrap();
+    assert!(runtime.list().wait().unwrap().is_empty());
+    assert!(!homedir.path().join("process-modules").join("m1").exists());
+
+    let err = runtime.get("m1").wait().unwrap_err();
+    match err.kind() {
+        ErrorKind::RuntimeOperation(_) => (),
+        kind => panic!("expected RuntimeOperation but got {:?}", kind),
+    }
+    match Fail::find_root_cause(&err).downcast_ref::<ErrorKind>() {
+        Some(ErrorKind::NotFound(_)) => (),
+        kind => panic!("expected NotFound but got {:?}", kind),
+    }
+}
+
+#[test]
+fn modules_are_loaded_again() {
+    if !can_run_modules() {
+        return;
+    }
+
+    let homedir = TempDir::new().unwrap();
+    {
+        let runtime = make_runtime(homedir.path());
+        runtime
+            .create(shell_module("m1", "exec sleep 30"))
+            .wait()
+            .unwrap();
+        ModuleRuntime::start(&runtime, "m1").wait().unwrap();
+    }
+
+    // The process keeps running without the runtime that started it.
+    let runtime = make_runtime(homedir.path());
+    let modules = runtime.list().wait().unwrap();
+    assert_eq!(1, modules.len());
+    assert_eq!("m1", modules[0].name());
+    let (_, state) = runtime.get("m1").wait().unwrap();
+    assert_eq!(&ModuleStatus::Running, state.status());
+
+    ModuleRuntime::remove(&runtime, "m1").wait().unwrap();
+}
+
+#[test]
+fn start_fails_without_unprivileged_user() {
+    let homedir = TempDir::new().unwrap();
+    let runtime = make_runtime_with_users(homedir.path(), "");
+    runtime.create(shell_module("m1", "true")).wait().unwrap();
+    let err = ModuleRuntime::start(&runtime, "m1").wait().unwrap_err();
+    match Fail::find_root_cause(&err).downcast_ref::<ErrorKind>() {
+        Some(ErrorKind::ModuleUserNotSet(module)) => assert_eq!("m1", module),
+        kind => panic!("expected ModuleUserNotSet but got {:?}", kind),
+    }
+
+    let homedir = TempDir::new().unwrap();
+    let runtime = make_runtime_with_users(homedir.path(), r#"default_user = "root""#);
+    runtime.create(shell_module("m1", "true")).wait().unwrap();
+    let err = ModuleRuntime::start(&runtime, "m1").wait().unwrap_err();
+    match Fail::find_root_cause(&err).downcast_ref::<ErrorKind>() {
+        Some(ErrorKind::PrivilegedModuleUser(module, user)) => {
+            assert_eq!("m1", module);
+            assert_eq!("root", user);
+        }
+        kind => panic!("expected PrivilegedModuleUser but got {:?}", kind),
+    }
+    let (_, state) = runtime.get("m1").wait().unwrap();
+    assert_eq!(None, state.pid());
+}
+
+#[test]
+fn create_fails_for_existing_module() {
+    let homedir = TempDir::new().unwrap();
+    let runtime = make_runtime(homedir.path());
+
+    runtime.create(shell_module("m1", "true")).wait().unwrap();
+    let err = runtime
+        .create(shell_module("m1", "true"))
+        .wait()
+        .unwrap_err();
+    match Fail::find_root_cause(&err).downcast_ref::<ErrorKind>() {
+        Some(ErrorKind::Conflict(_)) => (),
+        kind => panic!("expected Conflict but got {:?}", kind),
+    }
+}
+
+#[test]
+fn create_fails_for_other_module_types() {
+    let homedir = TempDir::new().unwrap();
+    let runtime = make_runtime(homedir.path());
+
+    let spec = shell_module("m1", "true").with_type_("docker".to_string());
+    let err = runtime.create(spec).wait().unwrap_err();
+    match err.kind() {
+        ErrorKind::InvalidModuleType(type_) => assert_eq!("docker", type_),
+        kind => panic!("expected InvalidModuleType but got {:?}", kind),
+    }
+}
+
+#[test]
+fn pull_checks_command_exists() {
+    let homedir = TempDir::new().unwrap();
+    let runtime = make_runtime(homedir.path());
+
+    runtime
+        .registry()
+        .pull(&ProcessConfig::new("sh".to_string()).unwrap())
+        .wait()
+        .unwrap();
+
+    let err = runtime
+        .registry()
+        .pull(&ProcessConfig::new("/opt/not-installed".to_string()).unwrap())
+        .wait()
+        .unwrap_err();
+    match Fail::find_root_cause(&err).downcast_ref::<ErrorKind>() {
+        Some(ErrorKind::CommandNotFound(command)) => assert_eq!("/opt/not-installed", command),
+        kind => panic!("expected CommandNotFound but got {:?}", kind),
+    }
+}
+
+#[test]
+fn exec_runs_command_in_module() {
+    if !can_run_modules() {
+        return;
+    }
+
+    let homedir = TempDir::new().unwrap();
+    let runtime = make_runtime(homedir.path());
+
+    runtime
+        .create(shell_module("m1", "exec sleep 30"))
+        .wait()
+        .unwrap();
+
+    let err = match runtime
+        .exec("m1", &ExecOptions::new(vec!["true".to_string()]))
+        .wait()
+    {
+        Ok(_) => panic!("expected exec to fail"),
+        Err(err) => err,
+    };
+    match Fail::find_root_cause(&err).downcast_ref::<ErrorKind>() {
+        Some(ErrorKind::NotRunning(_)) => (),
+        kind => panic!("expected NotRunning but got {:?}", kind),
+    }
+
+    ModuleRuntime::start(&runtime, "m1").wait().unwrap();
+
+    let mut tokio_runtime = tokio::runtime::Runtime::new().unwrap();
+    let cmd = vec![
+        "sh".to_string(),
+        "-c".to_string(),
+        "echo $GREETING; echo oops >&2; exit 2".to_string(),
+    ];
+    let (io, exit_code) = tokio_runtime
+        .block_on(runtime.exec("m1", &ExecOptions::new(cmd)))
+        .unwrap();
+    let (_, output) = tokio_runtime
+        .block_on(tokio::io::read_to_end(io, vec![]))
+        .unwrap();
+    let chunks: Vec<_> = LogDecode::new(std::io::Cursor::new(output))
+        .collect()
+        .wait()
+        .unwrap();
+    assert!(chunks.contains(&LogChunk::Stdout("hello\n".into())));
+    assert!(chunks.contains(&LogChunk::Stderr("oops\n".into())));
+    assert_eq!(Some(2), tokio_runtime.block_on(exit_code).unwrap());
+
+    ModuleRuntime::remove(&runtime, "m1").wait().unwrap();
+}
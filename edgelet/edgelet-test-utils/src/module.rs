@@ -1,11 +1,16 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::{self, Cursor, Read, Write};
 use std::marker::PhantomData;
 use std::path::Path;
 use std::time::Duration;
 
+use bytes::Bytes;
 use edgelet_core::{
-    settings::AutoReprovisioningMode, AuthId, Authenticator, Connect, DiskInfo, Endpoints, Listen,
-    LogOptions, MakeModuleRuntime, Module, ModuleRegistry, ModuleRuntime, ModuleRuntimeState,
-    ModuleSpec, ProvisioningInfo, RuntimeSettings, SystemInfo, SystemResources, WatchdogSettings,
+    settings::AutoReprovisioningMode, AuthId, Authenticator, Connect, DiskInfo, Endpoints,
+    ExecOptions, ImageGarbageCollection, ImageInfo, Listen, LogOptions, MakeModuleRuntime, Module,
+    ModuleRegistry, ModuleRuntime, ModuleRuntimeState, ModuleSpec, ProvisioningInfo,
+    RuntimeSettings, ServerCertPolicy, SystemInfo, SystemResources, WatchdogSettings,
 };
 use failure::Fail;
 use futures::future::{self, FutureResult};
@@ -13,6 +18,7 @@ use futures::prelude::*;
 use futures::stream;
 use futures::IntoFuture;
 use hyper::{Body, Request};
+use tokio::io::{AsyncRead, AsyncWrite};
 
 #[derive(Clone, Debug)]
 pub struct TestRegistry<E, C> {
@@ -36,6 +42,7 @@ where
     type Error = E;
     type PullFuture = FutureResult<(), Self::Error>;
     type RemoveFuture = FutureResult<(), Self::Error>;
+    type ListImagesFuture = FutureResult<Vec<ImageInfo>, Self::Error>;
     type Config = C;
 
     fn pull(&self, _config: &Self::Config) -> Self::PullFuture {
@@ -51,6 +58,13 @@ where
             None => future::ok(()),
         }
     }
+
+    fn list_images(&self) -> Self::ListImagesFuture {
+        match self.err {
+            Some(ref e) => future::err(e.clone()),
+            None => future::ok(Vec::new()),
+        }
+    }
 }
 
 #[derive(Clone, Debug, serde_derive::Serialize, serde_derive::Deserialize)]
@@ -68,6 +82,12 @@ impl TestConfig {
     }
 }
 
+impl fmt::Display for TestConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.image)
+    }
+}
+
 #[derive(Clone, Default, serde_derive::Serialize)]
 pub struct TestSettings;
 
@@ -108,6 +128,18 @@ impl RuntimeSettings for TestSettings {
         unimplemented!()
     }
 
+    fn image_garbage_collection(&self) -> &ImageGarbageCollection {
+        unimplemented!()
+    }
+
+    fn server_cert_policies(&self) -> &BTreeMap<String, ServerCertPolicy> {
+        unimplemented!()
+    }
+
+    fn module_secrets(&self) -> &BTreeMap<String, BTreeMap<String, String>> {
+        unimplemented!()
+    }
+
     fn endpoints(&self) -> &Endpoints {
         unimplemented!()
     }
@@ -131,6 +163,10 @@ impl RuntimeSettings for TestSettings {
     fn auto_reprovisioning_mode(&self) -> &AutoReprovisioningMode {
         unimplemented!()
     }
+
+    fn log_level(&self) -> Option<&str> {
+        unimplemented!()
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -209,6 +245,7 @@ where
     module: Option<Result<TestModule<E, S::Config>, E>>,
     registry: TestRegistry<E, S::Config>,
     settings: S,
+    auth_id: AuthId,
 }
 
 impl<E, S> TestRuntime<E, S>
@@ -225,6 +262,12 @@ where
         self.registry = registry;
         self
     }
+
+    /// Callers are authenticated as `auth_id` instead of as any module.
+    pub fn with_auth_id(mut self, auth_id: AuthId) -> Self {
+        self.auth_id = auth_id;
+        self
+    }
 }
 
 impl<E, S> Authenticator for TestRuntime<E, S>
@@ -237,7 +280,7 @@ where
     type AuthenticateFuture = Box<dyn Future<Item = AuthId, Error = Self::Error> + Send>;
 
     fn authenticate(&self, _req: &Self::Request) -> Self::AuthenticateFuture {
-        Box::new(future::ok(AuthId::Any))
+        Box::new(future::ok(self.auth_id.clone()))
     }
 }
 
@@ -286,6 +329,51 @@ impl<E> From<TestBody<E>> for Body {
     }
 }
 
+/// The connection to a command run with `TestRuntime::exec`. Reads return `output`,
+/// and writes are collected in `input`.
+#[derive(Clone, Debug, Default)]
+pub struct TestExecIo {
+    output: Cursor<Vec<u8>>,
+    input: Vec<u8>,
+}
+
+impl TestExecIo {
+    pub fn new(output: Vec<u8>) -> Self {
+        TestExecIo {
+            output: Cursor::new(output),
+            input: vec![],
+        }
+    }
+
+    pub fn input(&self) -> &[u8] {
+        &self.input
+    }
+}
+
+impl Read for TestExecIo {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.output.read(buf)
+    }
+}
+
+impl AsyncRead for TestExecIo {}
+
+impl Write for TestExecIo {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.input.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl AsyncWrite for TestExecIo {
+    fn shutdown(&mut self) -> Poll<(), io::Error> {
+        Ok(Async::Ready(()))
+    }
+}
+
 impl<E, S> MakeModuleRuntime for TestRuntime<E, S>
 where
     E: Clone + Fail,
@@ -297,14 +385,20 @@ where
     type ModuleRuntime = Self;
     type Error = E;
     type Future = FutureResult<Self, Self::Error>;
+    type ReloadFuture = FutureResult<(), Self::Error>;
 
     fn make_runtime(settings: Self::Settings) -> Self::Future {
         future::ok(TestRuntime {
             module: None,
             registry: TestRegistry::new(None),
             settings,
+            auth_id: AuthId::Any,
         })
     }
+
+    fn reload(_runtime: &Self, _settings: &Self::Settings) -> Self::ReloadFuture {
+        future::ok(())
+    }
 }
 
 impl<E, S> ModuleRuntime for TestRuntime<E, S>
@@ -319,6 +413,7 @@ where
     type ModuleRegistry = TestRegistry<E, S::Config>;
     type Chunk = &'static [u8];
     type Logs = TestBody<E>;
+    type Archive = TestBody<E>;
 
     type CreateFuture = FutureResult<(), Self::Error>;
     type GetFuture = FutureResult<(Self::Module, ModuleRuntimeState), Self::Error>;
@@ -333,7 +428,13 @@ where
     type SystemInfoFuture = FutureResult<SystemInfo, Self::Error>;
     type SystemResourcesFuture = FutureResult<SystemResources, Self::Error>;
     type RemoveAllFuture = FutureResult<(), Self::Error>;
+    type StartAllFuture = FutureResult<(), Self::Error>;
     type StopAllFuture = FutureResult<(), Self::Error>;
+    type ExecIo = TestExecIo;
+    type ExecExitCodeFuture = FutureResult<Option<i32>, Self::Error>;
+    type ExecFuture = FutureResult<(Self::ExecIo, Self::ExecExitCodeFuture), Self::Error>;
+    type GetArchiveFuture = FutureResult<Self::Archive, Self::Error>;
+    type PutArchiveFuture = Box<dyn Future<Item = (), Error = Self::Error> + Send>;
 
     fn create(&self, _module: ModuleSpec<Self::Config>) -> Self::CreateFuture {
         match self.module.as_ref().unwrap() {
@@ -412,6 +513,7 @@ where
                     20000,
                     "test system".to_owned(),
                     "test type".to_owned(),
+                    "/".to_owned(),
                 )],
                 "fake docker stats".to_owned(),
             )),
@@ -451,7 +553,35 @@ where
         future::ok(())
     }
 
+    fn start_all(&self) -> Self::StartAllFuture {
+        future::ok(())
+    }
+
     fn stop_all(&self, _wait_before_kill: Option<Duration>) -> Self::StopAllFuture {
         future::ok(())
     }
+
+    fn exec(&self, _id: &str, _options: &ExecOptions) -> Self::ExecFuture {
+        match self.module.as_ref().unwrap() {
+            Ok(_) => future::ok((TestExecIo::default(), future::ok(Some(0)))),
+            Err(ref e) => future::err(e.clone()),
+        }
+    }
+
+    fn get_archive(&self, _id: &str, _path: &str) -> Self::GetArchiveFuture {
+        match self.module.as_ref().unwrap() {
+            Ok(_) => future::ok(TestBody::new(vec![&b"archive"[..]])),
+            Err(ref e) => future::err(e.clone()),
+        }
+    }
+
+    fn put_archive<A>(&self, _id: &str, _path: &str, archive: A) -> Self::PutArchiveFuture
+    where
+        A: Stream<Item = Bytes, Error = io::Error> + Send + 'static,
+    {
+        match self.module.as_ref().unwrap() {
+            Ok(_) => Box::new(archive.for_each(|_| Ok(())).then(|_| Ok(()))),
+            Err(ref e) => Box::new(future::err(e.clone())),
+        }
+    }
 }
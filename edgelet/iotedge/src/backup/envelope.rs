@@ -0,0 +1,268 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+//! The encrypted file that backups are written to.
+//!
+//! The file starts with a header of the magic bytes, the number of PBKDF2 iterations, a salt
+//! and a nonce. The rest of the file is the archive encrypted with AES-256-GCM, with a key
+//! derived from the passphrase and the salt, followed by the 16 byte authentication tag. The
+//! header is authenticated along with the archive.
+
+use std::convert::TryInto;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+
+use openssl::hash::MessageDigest;
+use openssl::symm::{Cipher, Crypter, Mode};
+
+const MAGIC: &[u8; 8] = b"IOTEDGE\x01";
+const ITERATIONS: u32 = 600_000;
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+const TAG_LEN: usize = 16;
+const HEADER_LEN: usize = MAGIC.len() + 4 + SALT_LEN + NONCE_LEN;
+
+const BUFFER_LEN: usize = 64 * 1024;
+
+pub struct Encryptor<W> {
+    inner: W,
+    crypter: Crypter,
+    buf: Vec<u8>,
+}
+
+impl<W> Encryptor<W>
+where
+    W: Write,
+{
+    pub fn new(mut inner: W, passphrase: &[u8]) -> io::Result<Self> {
+        let mut salt = [0; SALT_LEN];
+        let mut nonce = [0; NONCE_LEN];
+        openssl::rand::rand_bytes(&mut salt).map_err(to_io_error)?;
+        openssl::rand::rand_bytes(&mut nonce).map_err(to_io_error)?;
+
+        let mut header = Vec::with_capacity(HEADER_LEN);
+        header.extend_from_slice(MAGIC);
+        header.extend_from_slice(&ITERATIONS.to_be_bytes());
+        header.extend_from_slice(&salt);
+        header.extend_from_slice(&nonce);
+
+        let crypter = crypter(Mode::Encrypt, passphrase, &header)?;
+        inner.write_all(&header)?;
+
+        Ok(Encryptor {
+            inner,
+            crypter,
+            buf: vec![],
+        })
+    }
+
+    /// Writes the authentication tag. A backup that isn't finished can't be restored.
+    pub fn finish(mut self) -> io::Result<W> {
+        let mut rest = [0; TAG_LEN];
+        let len = self.crypter.finalize(&mut rest).map_err(to_io_error)?;
+        self.inner.write_all(&rest[..len])?;
+
+        let mut tag = [0; TAG_LEN];
+        self.crypter.get_tag(&mut tag).map_err(to_io_error)?;
+        self.inner.write_all(&tag)?;
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+}
+
+impl<W> Write for Encryptor<W>
+where
+    W: Write,
+{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let buf = &buf[..buf.len().min(BUFFER_LEN)];
+        self.buf.resize(buf.len() + TAG_LEN, 0);
+        let len = self
+            .crypter
+            .update(buf, &mut self.buf)
+            .map_err(to_io_error)?;
+        self.inner.write_all(&self.buf[..len])?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Decrypts a backup. Reading fails with `io::ErrorKind::InvalidData` at the end of the
+/// backup if the passphrase is wrong or the backup was changed, so nothing that was read
+/// can be trusted until the reader returned EOF.
+pub struct Decryptor<R> {
+    inner: io::Take<R>,
+    crypter: Crypter,
+    input: Vec<u8>,
+    output: Vec<u8>,
+    pos: usize,
+    done: bool,
+}
+
+impl<R> Decryptor<R>
+where
+    R: Read + Seek,
+{
+    pub fn new(mut inner: R, passphrase: &[u8]) -> io::Result<Self> {
+        let len = inner.seek(SeekFrom::End(0))?;
+        if len < (HEADER_LEN + TAG_LEN) as u64 {
+            return Err(invalid_data("the file is too short to be a backup"));
+        }
+
+        let mut tag = [0; TAG_LEN];
+        inner.seek(SeekFrom::Start(len - TAG_LEN as u64))?;
+        inner.read_exact(&mut tag)?;
+
+        let mut header = [0; HEADER_LEN];
+        inner.seek(SeekFrom::Start(0))?;
+        inner.read_exact(&mut header)?;
+        if &header[..MAGIC.len()] != MAGIC {
+            return Err(invalid_data("the file is not a backup"));
+        }
+
+        let mut crypter = crypter(Mode::Decrypt, passphrase, &header)?;
+        crypter.set_tag(&tag).map_err(to_io_error)?;
+
+        Ok(Decryptor {
+            inner: inner.take(len - (HEADER_LEN + TAG_LEN) as u64),
+            crypter,
+            input: vec![0; BUFFER_LEN],
+            output: vec![],
+            pos: 0,
+            done: false,
+        })
+    }
+}
+
+impl<R> Read for Decryptor<R>
+where
+    R: Read,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.pos == self.output.len() {
+            if self.done {
+                return Ok(0);
+            }
+
+            let len = self.inner.read(&mut self.input)?;
+            self.output.resize(len + TAG_LEN, 0);
+            self.pos = 0;
+            let len = if len == 0 {
+                self.done = true;
+                self.crypter
+                    .finalize(&mut self.output)
+                    .map_err(|_| invalid_data("the passphrase is wrong or the backup is corrupt"))?
+            } else {
+                self.crypter
+                    .update(&self.input[..len], &mut self.output)
+                    .map_err(to_io_error)?
+            };
+            self.output.truncate(len);
+        }
+
+        let len = buf.len().min(self.output.len() - self.pos);
+        buf[..len].copy_from_slice(&self.output[self.pos..self.pos + len]);
+        self.pos += len;
+        Ok(len)
+    }
+}
+
+fn crypter(mode: Mode, passphrase: &[u8], header: &[u8]) -> io::Result<Crypter> {
+    let iterations = u32::from_be_bytes(
+        header[MAGIC.len()..MAGIC.len() + 4]
+            .try_into()
+            .expect("slice has the length of a u32"),
+    );
+    // The header isn't authenticated until the whole backup was read, so the work of deriving
+    // the key is bounded.
+    if iterations == 0 || iterations > 10 * ITERATIONS {
+        return Err(invalid_data("the file is not a backup"));
+    }
+    let salt = &header[MAGIC.len() + 4..MAGIC.len() + 4 + SALT_LEN];
+    let nonce = &header[MAGIC.len() + 4 + SALT_LEN..];
+
+    let cipher = Cipher::aes_256_gcm();
+    let mut key = vec![0; cipher.key_len()];
+    openssl::pkcs5::pbkdf2_hmac(
+        passphrase,
+        salt,
+        iterations as usize,
+        MessageDigest::sha256(),
+        &mut key,
+    )
+    .map_err(to_io_error)?;
+
+    let mut crypter = Crypter::new(cipher, mode, &key, Some(nonce)).map_err(to_io_error)?;
+    crypter.aad_update(header).map_err(to_io_error)?;
+    Ok(crypter)
+}
+
+fn invalid_data(message: &'static str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn to_io_error(err: openssl::error::ErrorStack) -> io::Error {
+    io::Error::new(io::ErrorKind::Other, err)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{self, Cursor, Read, Write};
+
+    use super::{Decryptor, Encryptor, HEADER_LEN};
+
+    fn encrypt(plaintext: &[u8], passphrase: &[u8]) -> Vec<u8> {
+        let mut encryptor = Encryptor::new(vec![], passphrase).unwrap();
+        encryptor.write_all(plaintext).unwrap();
+        encryptor.finish().unwrap()
+    }
+
+    fn decrypt(ciphertext: Vec<u8>, passphrase: &[u8]) -> io::Result<Vec<u8>> {
+        let mut plaintext = vec![];
+        Decryptor::new(Cursor::new(ciphertext), passphrase)?.read_to_end(&mut plaintext)?;
+        Ok(plaintext)
+    }
+
+    #[test]
+    fn round_trip() {
+        let plaintext: Vec<u8> = (0..=255).cycle().take(200_000).collect();
+        let ciphertext = encrypt(&plaintext, b"passphrase");
+
+        assert_ne!(&plaintext[..100], &ciphertext[HEADER_LEN..HEADER_LEN + 100]);
+        assert_eq!(plaintext, decrypt(ciphertext, b"passphrase").unwrap());
+    }
+
+    #[test]
+    fn wrong_passphrase_is_rejected() {
+        let ciphertext = encrypt(b"hello", b"passphrase");
+
+        let err = decrypt(ciphertext, b"other passphrase").unwrap_err();
+        assert_eq!(io::ErrorKind::InvalidData, err.kind());
+    }
+
+    #[test]
+    fn changes_are_detected() {
+        let mut ciphertext = encrypt(b"hello", b"passphrase");
+
+        // in the archive
+        ciphertext[HEADER_LEN] ^= 1;
+        assert!(decrypt(ciphertext.clone(), b"passphrase").is_err());
+        ciphertext[HEADER_LEN] ^= 1;
+
+        // in the header
+        ciphertext[HEADER_LEN - 1] ^= 1;
+        assert!(decrypt(ciphertext.clone(), b"passphrase").is_err());
+        ciphertext[HEADER_LEN - 1] ^= 1;
+
+        // truncated
+        ciphertext.truncate(ciphertext.len() - 1);
+        assert!(decrypt(ciphertext, b"passphrase").is_err());
+    }
+
+    #[test]
+    fn other_files_are_rejected() {
+        let err = decrypt(vec![0; 100], b"passphrase").unwrap_err();
+        assert_eq!(io::ErrorKind::InvalidData, err.kind());
+    }
+}
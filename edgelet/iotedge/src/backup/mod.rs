@@ -0,0 +1,636 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+//! `iotedge system backup` and `iotedge system restore`, which move the state of a device onto
+//! new hardware.
+//!
+//! A backup is a tar archive, encrypted as described in `envelope`, of:
+//!
+//! - `manifest.json`, which a restore checks the compatibility of the device with,
+//! - `config.toml`, the super-config,
+//! - `modules.json`, the specs of the modules that were deployed,
+//! - `cache/`, the cache directory of aziot-edged, with its provisioning state,
+//! - `volumes/<name>/`, the contents of each named volume that the modules mount.
+//!
+//! The keys and certificates of the identity service aren't backed up. A restored device
+//! provisions again with the credentials in its super-config, and edgeAgent then redeploys the
+//! modules.
+
+mod envelope;
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Component, Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use chrono::{DateTime, Utc};
+use failure::{Fail, ResultExt};
+use futures::future::{self, FutureResult};
+use futures::{Future, Stream};
+use serde_derive::{Deserialize, Serialize};
+
+use edgelet_core::{Module, ModuleRuntime};
+use edgelet_docker::DockerConfig;
+use edgelet_http_mgmt::ModuleClient;
+use management::models::Config;
+
+use crate::check::docker;
+use crate::config::apply::AZIOT_EDGED_HOMEDIR_PATH;
+use crate::config::super_config;
+use crate::error::{Error, ErrorKind};
+use crate::{Command, System};
+
+use self::envelope::{Decryptor, Encryptor};
+
+/// The version of the layout of backups. Backups can only be restored by a version of
+/// `iotedge` that writes the same layout.
+const FORMAT_VERSION: u32 = 1;
+
+const MANIFEST_PATH: &str = "manifest.json";
+const CONFIG_PATH: &str = "config.toml";
+const MODULES_PATH: &str = "modules.json";
+const CACHE_DIR: &str = "cache";
+const VOLUMES_DIR: &str = "volumes";
+
+/// The file that aziot-edged keeps its provisioning state in, in its cache directory.
+const PROVISIONING_STATE_FILENAME: &str = "provisioning_state";
+
+#[derive(Debug, Deserialize, PartialEq, Serialize)]
+struct Manifest {
+    format_version: u32,
+    version: String,
+    arch: String,
+    created: DateTime<Utc>,
+    volumes: Vec<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct ModuleSpec {
+    name: String,
+    #[serde(rename = "type")]
+    type_: String,
+    config: Config,
+}
+
+pub struct Backup {
+    client: ModuleClient,
+    config: PathBuf,
+    output: PathBuf,
+    passphrase: Vec<u8>,
+}
+
+impl Backup {
+    /// Backs up the device with the super-config at `config` into a new file at `output`,
+    /// encrypted with `passphrase`.
+    pub fn new(
+        client: ModuleClient,
+        config: PathBuf,
+        output: PathBuf,
+        passphrase: Vec<u8>,
+    ) -> Self {
+        Backup {
+            client,
+            config,
+            output,
+            passphrase,
+        }
+    }
+}
+
+impl Command for Backup {
+    type Future = Box<dyn Future<Item = (), Error = Error> + Send>;
+
+    fn execute(self) -> Self::Future {
+        let Backup {
+            client,
+            config,
+            output,
+            passphrase,
+        } = self;
+
+        let result = client
+            .list_with_details()
+            .map(|(module, _)| ModuleSpec {
+                name: module.name().to_string(),
+                type_: module.config().type_().to_string(),
+                config: module.config().spec().clone(),
+            })
+            .collect()
+            .map_err(|err| Error::from(err.context(ErrorKind::ModuleRuntime)))
+            .and_then(move |modules| backup(&config, &output, &passphrase, &modules));
+        Box::new(result)
+    }
+}
+
+fn backup(
+    config_path: &Path,
+    output: &Path,
+    passphrase: &[u8],
+    modules: &[ModuleSpec],
+) -> Result<(), Error> {
+    let context = || ErrorKind::Backup(output.display().to_string());
+
+    let config = fs::read(config_path).with_context(|_| context())?;
+    let docker_host_arg = docker_host_arg(&config)?;
+
+    let volumes = named_volumes(modules);
+    let mut mountpoints = vec![];
+    for volume in &volumes {
+        mountpoints.push(mountpoint(&docker_host_arg, volume)?);
+    }
+
+    let manifest = Manifest {
+        format_version: FORMAT_VERSION,
+        version: edgelet_core::version().to_string(),
+        arch: std::env::consts::ARCH.to_string(),
+        created: Utc::now(),
+        volumes: volumes.iter().cloned().collect(),
+    };
+
+    // The backup has the device's secrets, so only root may read it.
+    let file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(output)
+        .with_context(|_| context())?;
+    let mut archive =
+        tar::Builder::new(Encryptor::new(file, passphrase).with_context(|_| context())?);
+    archive.follow_symlinks(false);
+
+    append_file(
+        &mut archive,
+        MANIFEST_PATH,
+        &serde_json::to_vec_pretty(&manifest).with_context(|_| context())?,
+    )
+    .with_context(|_| context())?;
+    append_file(&mut archive, CONFIG_PATH, &config).with_context(|_| context())?;
+    append_file(
+        &mut archive,
+        MODULES_PATH,
+        &serde_json::to_vec_pretty(modules).with_context(|_| context())?,
+    )
+    .with_context(|_| context())?;
+
+    let cache = Path::new(AZIOT_EDGED_HOMEDIR_PATH).join(CACHE_DIR);
+    if cache.exists() {
+        archive
+            .append_dir_all(CACHE_DIR, &cache)
+            .with_context(|_| context())?;
+    }
+
+    for (volume, mountpoint) in volumes.iter().zip(mountpoints) {
+        archive
+            .append_dir_all(Path::new(VOLUMES_DIR).join(volume), mountpoint)
+            .with_context(|_| context())?;
+    }
+
+    archive
+        .into_inner()
+        .and_then(Encryptor::finish)
+        .with_context(|_| context())?;
+
+    println!(
+        "Backed up the configuration, {} module(s) and {} volume(s) to {}.",
+        modules.len(),
+        volumes.len(),
+        output.display(),
+    );
+    Ok(())
+}
+
+fn append_file<W>(archive: &mut tar::Builder<W>, path: &str, contents: &[u8]) -> io::Result<()>
+where
+    W: Write,
+{
+    let mut header = tar::Header::new_gnu();
+    header.set_size(contents.len() as u64);
+    header.set_mode(0o600);
+    header.set_mtime(
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.as_secs()),
+    );
+    header.set_cksum();
+    archive.append_data(&mut header, path, contents)
+}
+
+pub struct Restore {
+    input: PathBuf,
+    config: PathBuf,
+    passphrase: Vec<u8>,
+    force: bool,
+}
+
+impl Restore {
+    /// Restores the backup at `input`, writing its super-config to `config`.
+    pub fn new(input: PathBuf, config: PathBuf, passphrase: Vec<u8>) -> Self {
+        Restore {
+            input,
+            config,
+            passphrase,
+            force: false,
+        }
+    }
+
+    /// Restores the backup even if the device already has a super-config, provisioning
+    /// state or volumes that the backup would overwrite.
+    pub fn with_force(mut self, force: bool) -> Self {
+        self.force = force;
+        self
+    }
+}
+
+impl Command for Restore {
+    type Future = FutureResult<(), Error>;
+
+    fn execute(self) -> Self::Future {
+        future::result(restore(
+            &self.input,
+            &self.config,
+            &self.passphrase,
+            self.force,
+        ))
+    }
+}
+
+fn restore(input: &Path, config_path: &Path, passphrase: &[u8], force: bool) -> Result<(), Error> {
+    let context = || ErrorKind::Restore(input.display().to_string());
+
+    // The backup is only authenticated once it's been read to the end, so the whole backup is
+    // read once before anything is restored from it.
+    let open = || -> io::Result<Decryptor<File>> { Decryptor::new(File::open(input)?, passphrase) };
+    io::copy(&mut open().with_context(|_| context())?, &mut io::sink())
+        .with_context(|_| context())?;
+
+    let mut archive = tar::Archive::new(open().with_context(|_| context())?);
+    archive.set_preserve_permissions(true);
+    // Files in volumes are owned by the users of the modules' containers, which have the
+    // same IDs on every device.
+    let is_root = nix::unistd::Uid::current().is_root();
+    archive.set_preserve_ownerships(is_root);
+    let mut entries = archive.entries().with_context(|_| context())?;
+    let mut next_file = |path: &str| -> Result<Vec<u8>, Error> {
+        let mut entry = entries
+            .next()
+            .ok_or_else(|| ErrorKind::BadBackupEntry(path.to_string()))?
+            .with_context(|_| context())?;
+        if entry.path().with_context(|_| context())? != Path::new(path) {
+            return Err(ErrorKind::BadBackupEntry(path.to_string()).into());
+        }
+        let mut contents = vec![];
+        entry
+            .read_to_end(&mut contents)
+            .with_context(|_| context())?;
+        Ok(contents)
+    };
+
+    let manifest: Manifest =
+        serde_json::from_slice(&next_file(MANIFEST_PATH)?).with_context(|_| context())?;
+    let config = next_file(CONFIG_PATH)?;
+    let modules: Vec<ModuleSpec> =
+        serde_json::from_slice(&next_file(MODULES_PATH)?).with_context(|_| context())?;
+
+    check_compatibility(&manifest, edgelet_core::version(), std::env::consts::ARCH)
+        .map_err(ErrorKind::IncompatibleBackup)?;
+
+    println!(
+        "Restoring the backup made with IoT Edge {} on {}.",
+        manifest.version,
+        manifest.created.to_rfc3339(),
+    );
+
+    let docker_host_arg = docker_host_arg(&config)?;
+    let cache = Path::new(AZIOT_EDGED_HOMEDIR_PATH).join(CACHE_DIR);
+    if !force {
+        if config_path.exists() {
+            return Err(ErrorKind::DeviceNotFresh(config_path.display().to_string()).into());
+        }
+        if cache.join(PROVISIONING_STATE_FILENAME).exists() {
+            return Err(ErrorKind::DeviceNotFresh(cache.display().to_string()).into());
+        }
+        for volume in &manifest.volumes {
+            if mountpoint(&docker_host_arg, volume).is_ok() {
+                return Err(ErrorKind::DeviceNotFresh(format!("volume {}", volume)).into());
+            }
+        }
+    }
+
+    System::system_stop()?;
+
+    OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(config_path)
+        .and_then(|mut file| file.write_all(&config))
+        .with_context(|_| context())?;
+
+    let mut mountpoints = BTreeMap::new();
+    for volume in &manifest.volumes {
+        docker(&docker_host_arg, &["volume", "create", volume])
+            .map_err(|(_, err)| Error::from(err.context(ErrorKind::Docker)))?;
+        mountpoints.insert(volume.as_str(), mountpoint(&docker_host_arg, volume)?);
+    }
+
+    for entry in entries {
+        let mut entry = entry.with_context(|_| context())?;
+        let path = entry.path().with_context(|_| context())?.into_owned();
+        let target = restore_target(&path, &cache, &mountpoints)
+            .ok_or_else(|| ErrorKind::BadBackupEntry(path.display().to_string()))?;
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent).with_context(|_| context())?;
+        }
+        entry.unpack(&target).with_context(|_| context())?;
+    }
+
+    // The files of aziot-edged are owned by its user, whose ID can differ between devices.
+    if is_root && cache.exists() {
+        let user = nix::unistd::User::from_name("iotedge")
+            .with_context(|_| context())?
+            .ok_or_else(|| ErrorKind::Config("could not query iotedge user information".into()))?;
+        chown_all(&cache, user.uid, user.gid).with_context(|_| context())?;
+    }
+
+    println!(
+        "Restored the configuration and {} volume(s) from {}.",
+        manifest.volumes.len(),
+        input.display(),
+    );
+    println!("edgeAgent will redeploy these modules once the device has provisioned:");
+    for module in &modules {
+        println!("    {}", module.name);
+    }
+    println!();
+
+    crate::config::apply::execute(config_path, false).map_err(ErrorKind::Config)?;
+    Ok(())
+}
+
+/// Checks that a backup can be restored by this version of `iotedge`, on this architecture.
+/// Backups of older versions can be restored by newer ones with the same major version, but
+/// not the other way around.
+fn check_compatibility(manifest: &Manifest, version: &str, arch: &str) -> Result<(), String> {
+    if manifest.format_version != FORMAT_VERSION {
+        return Err(format!(
+            "the backup has format version {}, but this version of iotedge only restores format version {}",
+            manifest.format_version, FORMAT_VERSION,
+        ));
+    }
+
+    if manifest.arch != arch {
+        return Err(format!(
+            "the backup was made on a {} device, but this device is {}",
+            manifest.arch, arch,
+        ));
+    }
+
+    let backup_version = major_minor(&manifest.version)
+        .ok_or_else(|| format!("the backup has an invalid version {}", manifest.version))?;
+    let installed_version =
+        major_minor(version).ok_or_else(|| format!("invalid version {}", version))?;
+    if backup_version.0 != installed_version.0 || backup_version.1 > installed_version.1 {
+        return Err(format!(
+            "the backup was made with IoT Edge {}, which can't be restored by IoT Edge {}",
+            manifest.version, version,
+        ));
+    }
+
+    Ok(())
+}
+
+fn major_minor(version: &str) -> Option<(u32, u32)> {
+    let mut parts = version.split(|c: char| !c.is_ascii_digit());
+    let major = parts.next()?.parse().ok()?;
+    let minor = parts.next()?.parse().ok()?;
+    Some((major, minor))
+}
+
+/// Where an entry of a backup is restored to, or `None` if the entry doesn't belong in a
+/// backup. Entries are only ever restored below the cache directory or a volume.
+fn restore_target(
+    path: &Path,
+    cache: &Path,
+    mountpoints: &BTreeMap<&str, PathBuf>,
+) -> Option<PathBuf> {
+    if !path
+        .components()
+        .all(|component| matches!(component, Component::Normal(_)))
+    {
+        return None;
+    }
+
+    let mut components = path.components();
+    match components.next()?.as_os_str().to_str()? {
+        CACHE_DIR => Some(cache.join(components.as_path())),
+        VOLUMES_DIR => {
+            let volume = components.next()?.as_os_str().to_str()?;
+            let mountpoint = mountpoints.get(volume)?;
+            Some(mountpoint.join(components.as_path()))
+        }
+        _ => None,
+    }
+}
+
+fn chown_all(path: &Path, uid: nix::unistd::Uid, gid: nix::unistd::Gid) -> io::Result<()> {
+    nix::unistd::fchownat(
+        None,
+        path,
+        Some(uid),
+        Some(gid),
+        nix::unistd::FchownatFlags::NoFollowSymlink,
+    )
+    .map_err(|err| io::Error::new(io::ErrorKind::Other, err))?;
+
+    if fs::symlink_metadata(path)?.is_dir() {
+        for entry in fs::read_dir(path)? {
+            chown_all(&entry?.path(), uid, gid)?;
+        }
+    }
+    Ok(())
+}
+
+fn docker_host_arg(config: &[u8]) -> Result<String, Error> {
+    let config: super_config::Config = toml::from_slice(config)
+        .map_err(|err| ErrorKind::Config(format!("could not parse config file: {}", err).into()))?;
+    let uri = config.moby_runtime.uri;
+    if uri.scheme() == "unix" {
+        Ok(uri.to_string())
+    } else {
+        Err(
+            ErrorKind::Config(format!("the container engine at {} is not supported", uri).into())
+                .into(),
+        )
+    }
+}
+
+fn mountpoint(docker_host_arg: &str, volume: &str) -> Result<PathBuf, Error> {
+    let output = docker(
+        docker_host_arg,
+        &["volume", "inspect", "--format", "{{.Mountpoint}}", volume],
+    )
+    .map_err(|(_, err)| Error::from(err.context(ErrorKind::Docker)))?;
+    Ok(PathBuf::from(String::from_utf8_lossy(&output).trim()))
+}
+
+/// The named volumes that the modules mount, with `HostConfig.Binds` or `HostConfig.Mounts`
+/// in their create options. Host directories that are bound into modules aren't backed up.
+fn named_volumes(modules: &[ModuleSpec]) -> BTreeSet<String> {
+    let mut volumes = BTreeSet::new();
+
+    for module in modules {
+        if module.type_ != edgelet_docker::MODULE_TYPE {
+            continue;
+        }
+        let config: DockerConfig = match serde_json::from_value(module.config.settings().clone()) {
+            Ok(config) => config,
+            Err(_) => continue,
+        };
+        let host_config = match config.create_options().host_config() {
+            Some(host_config) => host_config,
+            None => continue,
+        };
+
+        let binds = host_config
+            .binds()
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|bind| bind.split(':').next());
+        let mounts = host_config
+            .mounts()
+            .unwrap_or_default()
+            .iter()
+            .filter(|mount| mount._type() == Some("volume"))
+            .filter_map(|mount| mount.source());
+        volumes.extend(
+            binds
+                .chain(mounts)
+                .filter(|source| is_volume_name(source))
+                .map(ToString::to_string),
+        );
+    }
+
+    volumes
+}
+
+/// Whether `source` is the name of a volume rather than a path, like docker's
+/// `[a-zA-Z0-9][a-zA-Z0-9_.-]+`.
+fn is_volume_name(source: &str) -> bool {
+    let mut chars = source.chars();
+    chars.next().map_or(false, |c| c.is_ascii_alphanumeric())
+        && source.len() > 1
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.' || c == '-')
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::path::{Path, PathBuf};
+
+    use chrono::Utc;
+    use management::models::Config;
+    use serde_json::json;
+
+    use super::{
+        check_compatibility, named_volumes, restore_target, Manifest, ModuleSpec, FORMAT_VERSION,
+    };
+
+    fn manifest(version: &str, arch: &str) -> Manifest {
+        Manifest {
+            format_version: FORMAT_VERSION,
+            version: version.to_string(),
+            arch: arch.to_string(),
+            created: Utc::now(),
+            volumes: vec![],
+        }
+    }
+
+    #[test]
+    fn compatibility() {
+        assert!(check_compatibility(&manifest("1.2.0", "x86_64"), "1.2.3", "x86_64").is_ok());
+        assert!(check_compatibility(&manifest("1.1.0", "x86_64"), "1.2.0~dev", "x86_64").is_ok());
+
+        // newer backups
+        assert!(check_compatibility(&manifest("1.3.0", "x86_64"), "1.2.0", "x86_64").is_err());
+        assert!(check_compatibility(&manifest("1.2.0", "x86_64"), "2.0.0", "x86_64").is_err());
+
+        // other architectures
+        assert!(check_compatibility(&manifest("1.2.0", "aarch64"), "1.2.0", "x86_64").is_err());
+
+        // other formats
+        let mut other_format = manifest("1.2.0", "x86_64");
+        other_format.format_version += 1;
+        assert!(check_compatibility(&other_format, "1.2.0", "x86_64").is_err());
+    }
+
+    #[test]
+    fn volumes_of_modules() {
+        let module = |type_: &str, create_options| ModuleSpec {
+            name: "module".to_string(),
+            type_: type_.to_string(),
+            config: Config::new(json!({
+                "image": "image",
+                "createOptions": create_options,
+            })),
+        };
+        let modules = vec![
+            module(
+                "docker",
+                json!({
+                    "HostConfig": {
+                        "Binds": ["edgehub-data:/data", "/etc/host:/etc/host:ro"],
+                        "Mounts": [
+                            { "Type": "volume", "Source": "sensor-data", "Target": "/sensor" },
+                            { "Type": "bind", "Source": "/var/log", "Target": "/log" },
+                        ],
+                    },
+                }),
+            ),
+            module("docker", json!({})),
+            module(
+                "other",
+                json!({ "HostConfig": { "Binds": ["other-data:/data"] } }),
+            ),
+        ];
+
+        assert_eq!(
+            vec!["edgehub-data".to_string(), "sensor-data".to_string()],
+            named_volumes(&modules).into_iter().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn entries_are_restored_below_the_cache_or_a_volume() {
+        let cache = Path::new("/var/lib/aziot/edged/cache");
+        let mut mountpoints = BTreeMap::new();
+        mountpoints.insert("data", PathBuf::from("/var/lib/docker/volumes/data/_data"));
+
+        assert_eq!(
+            Some(cache.join("provisioning_state")),
+            restore_target(Path::new("cache/provisioning_state"), cache, &mountpoints)
+        );
+        assert_eq!(
+            Some(PathBuf::from("/var/lib/docker/volumes/data/_data/db/file")),
+            restore_target(Path::new("volumes/data/db/file"), cache, &mountpoints)
+        );
+
+        assert_eq!(
+            None,
+            restore_target(Path::new("volumes/other/file"), cache, &mountpoints)
+        );
+        assert_eq!(
+            None,
+            restore_target(Path::new("cache/../../etc/passwd"), cache, &mountpoints)
+        );
+        assert_eq!(
+            None,
+            restore_target(Path::new("/etc/passwd"), cache, &mountpoints)
+        );
+        assert_eq!(
+            None,
+            restore_target(Path::new("config.toml"), cache, &mountpoints)
+        );
+    }
+}
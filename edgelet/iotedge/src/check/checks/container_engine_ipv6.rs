@@ -36,11 +36,17 @@ impl ContainerEngineIPv6 {
 
         let is_edge_ipv6_configured = check.settings.as_ref().map_or(false, |settings| {
             let moby_network = settings.moby_runtime().network();
-            if let MobyNetwork::Network(network) = moby_network {
+            let is_network_ipv6 = if let MobyNetwork::Network(network) = moby_network {
                 network.ipv6().unwrap_or_default()
             } else {
                 false
-            }
+            };
+            is_network_ipv6
+                || settings
+                    .moby_runtime()
+                    .additional_networks()
+                    .iter()
+                    .any(|network| network.ipv6().unwrap_or_default())
         });
         self.expected_use_ipv6 = Some(is_edge_ipv6_configured);
 
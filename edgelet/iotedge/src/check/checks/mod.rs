@@ -9,6 +9,8 @@ mod container_engine_is_moby;
 mod container_engine_logrotate;
 mod container_local_time;
 mod container_resolve_parent_hostname;
+mod module_networks;
+mod module_resource_limits;
 mod parent_hostname;
 mod storage_mounted_from_host;
 mod up_to_date_config;
@@ -25,6 +27,8 @@ pub(crate) use self::container_engine_is_moby::ContainerEngineIsMoby;
 pub(crate) use self::container_engine_logrotate::ContainerEngineLogrotate;
 pub(crate) use self::container_local_time::ContainerLocalTime;
 pub(crate) use self::container_resolve_parent_hostname::ContainerResolveParentHostname;
+pub(crate) use self::module_networks::ModuleNetworks;
+pub(crate) use self::module_resource_limits::ModuleResourceLimits;
 pub(crate) use self::parent_hostname::ParentHostname;
 pub(crate) use self::storage_mounted_from_host::{EdgeAgentStorageMounted, EdgeHubStorageMounted};
 pub(crate) use self::up_to_date_config::UpToDateConfig;
@@ -72,9 +76,9 @@ where
 }
 
 // built-in checks, as opposed to those that are deferred to `aziot check`
-pub(crate) fn built_in_checks() -> [(&'static str, Vec<Box<dyn Checker>>); 2] {
+pub(crate) fn built_in_checks() -> Vec<(&'static str, Vec<Box<dyn Checker>>)> {
     /* Note: keep ordering consistent. Later tests may depend on earlier tests. */
-    [
+    vec![
         (
             "Configuration checks",
             vec![
@@ -90,6 +94,8 @@ pub(crate) fn built_in_checks() -> [(&'static str, Vec<Box<dyn Checker>>); 2] {
                 Box::new(ContainerEngineIPv6::default()),
                 Box::new(ContainerEngineIsMoby::default()),
                 Box::new(ContainerEngineLogrotate::default()),
+                Box::new(ModuleResourceLimits::default()),
+                Box::new(ModuleNetworks::default()),
                 Box::new(EdgeAgentStorageMounted::default()),
                 Box::new(EdgeHubStorageMounted::default()),
                 Box::new(CheckAgentImage::default()),
@@ -0,0 +1,315 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use failure::{self, Context, ResultExt};
+
+use crate::check::{checker::Checker, Check, CheckResult};
+
+const EDGE_HUB: &str = "edgeHub";
+
+const MODULE_OWNER_FILTER: &str =
+    "label=net.azure-devices.edge.owner=Microsoft.Azure.Devices.Edge.Agent";
+
+#[derive(Default, serde_derive::Serialize)]
+pub(crate) struct ModuleNetworks {
+    undeclared_networks: Option<BTreeMap<String, Vec<String>>>,
+    modules_isolated_from_edge_hub: Option<Vec<String>>,
+    modules_on_other_networks: Option<BTreeMap<String, Vec<String>>>,
+}
+
+impl Checker for ModuleNetworks {
+    fn id(&self) -> &'static str {
+        "module-networks"
+    }
+    fn description(&self) -> &'static str {
+        "module network isolation policies"
+    }
+    fn execute(&mut self, check: &mut Check, _: &mut tokio::runtime::Runtime) -> CheckResult {
+        self.inner_execute(check)
+            .unwrap_or_else(CheckResult::Failed)
+    }
+    fn get_json(&self) -> serde_json::Value {
+        serde_json::to_value(self).unwrap()
+    }
+}
+
+impl ModuleNetworks {
+    fn inner_execute(&mut self, check: &mut Check) -> Result<CheckResult, failure::Error> {
+        let settings = if let Some(settings) = &check.settings {
+            settings
+        } else {
+            return Ok(CheckResult::Skipped);
+        };
+
+        let moby_runtime = settings.moby_runtime();
+        let module_networks = moby_runtime.module_networks();
+        if module_networks.is_empty() {
+            return Ok(CheckResult::Ignored);
+        }
+
+        let default_network = moby_runtime.network().name();
+        let declared_networks: BTreeSet<&str> = std::iter::once(default_network)
+            .chain(
+                moby_runtime
+                    .additional_networks()
+                    .iter()
+                    .map(edgelet_core::Network::name),
+            )
+            .collect();
+
+        let undeclared = undeclared_networks(module_networks, &declared_networks);
+        self.undeclared_networks = Some(undeclared.clone());
+        if !undeclared.is_empty() {
+            let modules: Vec<String> = undeclared
+                .iter()
+                .map(|(name, networks)| format!("{} ({})", name, networks.join(", ")))
+                .collect();
+            return Ok(CheckResult::Failed(
+                Context::new(format!(
+                    "The following modules are configured to join networks that aren't declared: {}.\n\
+                     These modules can't be created. Declare the networks in \
+                     [[moby_runtime.additional_networks]] in /etc/aziot/config.toml.",
+                    modules.join(", "),
+                ))
+                .into(),
+            ));
+        }
+
+        let isolated = modules_isolated_from_edge_hub(module_networks, default_network);
+        self.modules_isolated_from_edge_hub = Some(isolated.clone());
+        if !isolated.is_empty() {
+            return Ok(CheckResult::Warning(
+                Context::new(format!(
+                    "The following modules don't share a network with edgeHub: {}.\n\
+                     They can't send or receive messages through edgeHub. Add one of their \
+                     networks to edgeHub's networks in [moby_runtime.module_networks] \
+                     in /etc/aziot/config.toml.",
+                    isolated.join(", "),
+                ))
+                .into(),
+            ));
+        }
+
+        let docker_host_arg = if let Some(docker_host_arg) = &check.docker_host_arg {
+            docker_host_arg
+        } else {
+            return Ok(CheckResult::Ok);
+        };
+
+        self.check_running_modules(docker_host_arg, module_networks)
+    }
+
+    fn check_running_modules(
+        &mut self,
+        docker_host_arg: &str,
+        module_networks: &BTreeMap<String, Vec<String>>,
+    ) -> Result<CheckResult, failure::Error> {
+        let output = super::docker(
+            docker_host_arg,
+            &["ps", "--quiet", "--filter", MODULE_OWNER_FILTER],
+        )
+        .map_err(|(_, err)| err)
+        .context("Could not list module containers")?;
+        let container_ids: Vec<&str> = std::str::from_utf8(&output)
+            .context("Could not parse result of docker ps")?
+            .split_whitespace()
+            .collect();
+        if container_ids.is_empty() {
+            return Ok(CheckResult::Ok);
+        }
+
+        let output = super::docker(
+            docker_host_arg,
+            std::iter::once("inspect").chain(container_ids),
+        )
+        .map_err(|(_, err)| err)
+        .context("Could not inspect module containers")?;
+        let inspect_results: Vec<docker::models::InlineResponse200> =
+            serde_json::from_slice(&output).context("Could not parse result of docker inspect")?;
+
+        let mut modules_on_other_networks = BTreeMap::new();
+        for inspect_result in inspect_results {
+            let name = inspect_result
+                .name()
+                .map_or("<unknown>", |name| name.trim_start_matches('/'));
+            let expected = if let Some(expected) = module_networks.get(name) {
+                expected
+            } else {
+                continue;
+            };
+            let actual: BTreeSet<&str> = inspect_result
+                .network_settings()
+                .and_then(docker::models::NetworkSettings::networks)
+                .map(|networks| networks.keys().map(String::as_str).collect())
+                .unwrap_or_default();
+            let other = other_networks(expected, &actual);
+            if !other.is_empty() {
+                modules_on_other_networks.insert(name.to_owned(), other);
+            }
+        }
+        self.modules_on_other_networks = Some(modules_on_other_networks.clone());
+
+        if modules_on_other_networks.is_empty() {
+            return Ok(CheckResult::Ok);
+        }
+
+        let modules: Vec<String> = modules_on_other_networks
+            .iter()
+            .map(|(name, networks)| format!("{} ({})", name, networks.join(", ")))
+            .collect();
+        Ok(CheckResult::Warning(
+            Context::new(format!(
+                "The following modules are running on networks that they aren't configured to join: {}.\n\
+                 The modules were probably created before their networks were configured, \
+                 or were connected to other networks afterwards. \
+                 Remove the modules' containers so that edgeAgent creates them again.",
+                modules.join(", "),
+            ))
+            .into(),
+        ))
+    }
+}
+
+fn undeclared_networks(
+    module_networks: &BTreeMap<String, Vec<String>>,
+    declared_networks: &BTreeSet<&str>,
+) -> BTreeMap<String, Vec<String>> {
+    module_networks
+        .iter()
+        .filter_map(|(name, networks)| {
+            let undeclared: Vec<String> = networks
+                .iter()
+                .filter(|network| !declared_networks.contains(network.as_str()))
+                .cloned()
+                .collect();
+            if undeclared.is_empty() {
+                None
+            } else {
+                Some((name.clone(), undeclared))
+            }
+        })
+        .collect()
+}
+
+/// Modules without any networks are offline on purpose, so they aren't reported.
+fn modules_isolated_from_edge_hub(
+    module_networks: &BTreeMap<String, Vec<String>>,
+    default_network: &str,
+) -> Vec<String> {
+    let edge_hub_networks: BTreeSet<&str> = module_networks.get(EDGE_HUB).map_or_else(
+        || std::iter::once(default_network).collect(),
+        |networks| networks.iter().map(String::as_str).collect(),
+    );
+
+    module_networks
+        .iter()
+        .filter(|(name, networks)| {
+            name.as_str() != EDGE_HUB
+                && !networks.is_empty()
+                && !networks
+                    .iter()
+                    .any(|network| edge_hub_networks.contains(network.as_str()))
+        })
+        .map(|(name, _)| name.clone())
+        .collect()
+}
+
+/// A module without networks runs with the network mode "none", which docker reports as a
+/// network of that name.
+fn other_networks(expected: &[String], actual: &BTreeSet<&str>) -> Vec<String> {
+    actual
+        .iter()
+        .filter(|&&network| {
+            if expected.is_empty() {
+                network != "none"
+            } else {
+                !expected.iter().any(|expected| expected == network)
+            }
+        })
+        .map(|&network| network.to_owned())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{BTreeMap, BTreeSet};
+
+    use super::{modules_isolated_from_edge_hub, other_networks, undeclared_networks};
+
+    fn module_networks(modules: &[(&str, &[&str])]) -> BTreeMap<String, Vec<String>> {
+        modules
+            .iter()
+            .map(|(name, networks)| {
+                (
+                    (*name).to_owned(),
+                    networks.iter().map(|&network| network.to_owned()).collect(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_undeclared_networks() {
+        let declared: BTreeSet<&str> = vec!["azure-iot-edge", "third-party"].into_iter().collect();
+
+        assert!(undeclared_networks(
+            &module_networks(&[
+                ("edgeHub", &["azure-iot-edge", "third-party"]),
+                ("sensor", &["third-party"]),
+                ("offline", &[]),
+            ]),
+            &declared,
+        )
+        .is_empty());
+
+        assert_eq!(
+            module_networks(&[("sensor", &["thrid-party"])]),
+            undeclared_networks(
+                &module_networks(&[
+                    ("edgeHub", &["azure-iot-edge", "third-party"]),
+                    ("sensor", &["thrid-party", "azure-iot-edge"]),
+                ]),
+                &declared,
+            )
+        );
+    }
+
+    #[test]
+    fn test_modules_isolated_from_edge_hub() {
+        assert!(modules_isolated_from_edge_hub(
+            &module_networks(&[
+                ("edgeHub", &["azure-iot-edge", "third-party"]),
+                ("sensor", &["third-party"]),
+                ("offline", &[]),
+            ]),
+            "azure-iot-edge",
+        )
+        .is_empty());
+
+        // edgeHub is on the default network when it isn't listed
+        assert_eq!(
+            vec!["sensor".to_owned()],
+            modules_isolated_from_edge_hub(
+                &module_networks(&[("sensor", &["third-party"]), ("local", &["azure-iot-edge"])]),
+                "azure-iot-edge",
+            )
+        );
+    }
+
+    #[test]
+    fn test_other_networks() {
+        let expected = vec!["third-party".to_owned()];
+
+        let actual: BTreeSet<&str> = vec!["third-party"].into_iter().collect();
+        assert!(other_networks(&expected, &actual).is_empty());
+
+        let actual: BTreeSet<&str> = vec!["azure-iot-edge", "third-party"].into_iter().collect();
+        assert_eq!(
+            vec!["azure-iot-edge".to_owned()],
+            other_networks(&expected, &actual)
+        );
+
+        let actual: BTreeSet<&str> = vec!["none"].into_iter().collect();
+        assert!(other_networks(&[], &actual).is_empty());
+        assert_eq!(vec!["none".to_owned()], other_networks(&expected, &actual));
+    }
+}
@@ -0,0 +1,154 @@
+use std::collections::BTreeMap;
+
+use failure::{self, Context, ResultExt};
+
+use docker::models::HostConfig;
+
+use crate::check::{checker::Checker, Check, CheckResult};
+
+const MODULE_OWNER_FILTER: &str =
+    "label=net.azure-devices.edge.owner=Microsoft.Azure.Devices.Edge.Agent";
+
+#[derive(Default, serde_derive::Serialize)]
+pub(crate) struct ModuleResourceLimits {
+    modules_without_limits: Option<BTreeMap<String, Vec<&'static str>>>,
+}
+
+impl Checker for ModuleResourceLimits {
+    fn id(&self) -> &'static str {
+        "module-resource-limits"
+    }
+    fn description(&self) -> &'static str {
+        "production readiness: modules have resource limits"
+    }
+    fn execute(&mut self, check: &mut Check, _: &mut tokio::runtime::Runtime) -> CheckResult {
+        self.inner_execute(check)
+            .unwrap_or_else(CheckResult::Failed)
+    }
+    fn get_json(&self) -> serde_json::Value {
+        serde_json::to_value(self).unwrap()
+    }
+}
+
+impl ModuleResourceLimits {
+    fn inner_execute(&mut self, check: &mut Check) -> Result<CheckResult, failure::Error> {
+        let docker_host_arg = if let Some(docker_host_arg) = &check.docker_host_arg {
+            docker_host_arg
+        } else {
+            return Ok(CheckResult::Skipped);
+        };
+
+        let output = super::docker(
+            docker_host_arg,
+            &["ps", "--quiet", "--filter", MODULE_OWNER_FILTER],
+        )
+        .map_err(|(_, err)| err)
+        .context("Could not list module containers")?;
+        let container_ids: Vec<&str> = std::str::from_utf8(&output)
+            .context("Could not parse result of docker ps")?
+            .split_whitespace()
+            .collect();
+        if container_ids.is_empty() {
+            return Ok(CheckResult::Ignored);
+        }
+
+        let output = super::docker(
+            docker_host_arg,
+            std::iter::once("inspect").chain(container_ids),
+        )
+        .map_err(|(_, err)| err)
+        .context("Could not inspect module containers")?;
+        let inspect_results: Vec<docker::models::InlineResponse200> =
+            serde_json::from_slice(&output).context("Could not parse result of docker inspect")?;
+
+        let mut modules_without_limits = BTreeMap::new();
+        for inspect_result in inspect_results {
+            let name = inspect_result
+                .name()
+                .map_or("<unknown>", |name| name.trim_start_matches('/'));
+            let missing = inspect_result
+                .host_config()
+                .map_or_else(|| missing_limits(&HostConfig::new()), missing_limits);
+            if !missing.is_empty() {
+                modules_without_limits.insert(name.to_owned(), missing);
+            }
+        }
+        self.modules_without_limits = Some(modules_without_limits.clone());
+
+        if modules_without_limits.is_empty() {
+            return Ok(CheckResult::Ok);
+        }
+
+        let modules: Vec<String> = modules_without_limits
+            .iter()
+            .map(|(name, missing)| format!("{} (no {} limit)", name, missing.join(", no ")))
+            .collect();
+        Ok(CheckResult::Warning(
+            Context::new(format!(
+                "The following modules are running without resource limits: {}.\n\
+                 A single module can exhaust the device's memory, CPU or process table.\n\
+                 Set limits in the module's createOptions, or set device-wide defaults \
+                 in the [moby_runtime.resource_limits] section of /etc/aziot/config.toml.",
+                modules.join(", "),
+            ))
+            .into(),
+        ))
+    }
+}
+
+/// Docker reports an unset limit as either absent or zero.
+fn missing_limits(host_config: &HostConfig) -> Vec<&'static str> {
+    fn is_set(value: Option<i64>) -> bool {
+        value.map_or(false, |value| value != 0)
+    }
+
+    let mut missing = vec![];
+    if !is_set(host_config.memory()) {
+        missing.push("memory");
+    }
+    if !is_set(host_config.nano_cp_us()) && !is_set(host_config.cpu_quota()) {
+        missing.push("CPU");
+    }
+    if !host_config
+        .pids_limit()
+        .map_or(false, |pids_limit| pids_limit > 0)
+    {
+        missing.push("pids");
+    }
+    missing
+}
+
+#[cfg(test)]
+mod tests {
+    use docker::models::HostConfig;
+
+    use super::missing_limits;
+
+    #[test]
+    fn test_missing_limits() {
+        assert_eq!(
+            vec!["memory", "CPU", "pids"],
+            missing_limits(&HostConfig::new())
+        );
+        assert_eq!(
+            vec!["memory", "CPU", "pids"],
+            missing_limits(
+                &HostConfig::new()
+                    .with_memory(0)
+                    .with_nano_cp_us(0)
+                    .with_pids_limit(-1)
+            )
+        );
+        assert_eq!(
+            vec!["pids"],
+            missing_limits(&HostConfig::new().with_memory(1024).with_cpu_quota(50_000))
+        );
+        assert!(missing_limits(
+            &HostConfig::new()
+                .with_memory(1024)
+                .with_nano_cp_us(500_000_000)
+                .with_pids_limit(256)
+        )
+        .is_empty());
+    }
+}
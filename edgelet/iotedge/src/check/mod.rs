@@ -2,7 +2,7 @@
 
 use std::collections::{BTreeMap, BTreeSet};
 use std::io::Write;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use failure::Fail;
 use failure::{self, ResultExt};
@@ -19,6 +19,9 @@ use crate::error::{Error, ErrorKind};
 mod additional_info;
 use self::additional_info::AdditionalInfo;
 
+mod report;
+use self::report::CheckReport;
+
 mod stdout;
 use self::stdout::Stdout;
 
@@ -28,11 +31,15 @@ mod checker;
 use checker::Checker;
 
 mod checks;
+pub(crate) use self::checks::docker;
+
+mod user_checks;
 
 pub struct Check {
     container_engine_config_path: PathBuf,
     diagnostics_image_name: String,
     dont_run: BTreeSet<String>,
+    only_run: BTreeSet<String>,
     aziot_edged: PathBuf,
     expected_aziot_edged_version: Option<String>,
     expected_aziot_version: Option<String>,
@@ -55,6 +62,8 @@ pub struct Check {
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub enum OutputFormat {
     Json,
+    Junit,
+    Sarif,
     Text,
 }
 
@@ -87,6 +96,7 @@ impl Check {
         container_engine_config_path: PathBuf,
         diagnostics_image_name: String,
         dont_run: BTreeSet<String>,
+        only_run: BTreeSet<String>,
         expected_aziot_edged_version: Option<String>,
         expected_aziot_version: Option<String>,
         aziot_edged: PathBuf,
@@ -101,6 +111,7 @@ impl Check {
             container_engine_config_path,
             diagnostics_image_name,
             dont_run,
+            only_run,
             aziot_edged,
             expected_aziot_edged_version,
             expected_aziot_version,
@@ -178,6 +189,23 @@ impl Check {
             all_checks.extend(checks);
         }
 
+        // get the user-defined checks
+        {
+            let user_checks = user_checks::load(Path::new(user_checks::USER_CHECKS_DIR));
+            if !user_checks.is_empty() {
+                all_checks.push((
+                    user_checks::USER_CHECKS_SECTION.to_owned(),
+                    user_checks
+                        .iter()
+                        .map(|c| CheckerMetaSerializable {
+                            id: c.id().into(),
+                            description: c.description().into(),
+                        })
+                        .collect(),
+                ));
+            }
+        }
+
         // All our text is ASCII, so we can measure text width in bytes rather than using unicode-segmentation to count graphemes.
         let widest_section_name_len = all_checks
             .iter()
@@ -220,6 +248,41 @@ impl Check {
         Ok(())
     }
 
+    /// Whether the check was selected with `--checks` and not excluded with `--dont-run`.
+    fn should_run(&self, check_id: &str) -> bool {
+        !self.dont_run.contains(check_id)
+            && (self.only_run.is_empty() || self.only_run.contains(check_id))
+    }
+
+    /// The checks that aziot should not run. When only some checks are selected, these include
+    /// all of aziot's checks that weren't.
+    fn aziot_dont_run(&self) -> BTreeSet<String> {
+        let mut dont_run = self.dont_run.clone();
+
+        if !self.only_run.is_empty() {
+            // If aziot's checks can't be listed, the ones that weren't selected still run,
+            // but their results are ignored.
+            let aziot_checks: Option<BTreeMap<String, Vec<CheckerMetaSerializable>>> =
+                std::process::Command::new(&self.aziot_bin)
+                    .arg("check-list")
+                    .arg("--output=json")
+                    .output()
+                    .ok()
+                    .and_then(|out| serde_json::from_slice(&out.stdout).ok());
+
+            dont_run.extend(
+                aziot_checks
+                    .into_iter()
+                    .flat_map(BTreeMap::into_iter)
+                    .flat_map(|(_, checks)| checks)
+                    .map(|check| check.id)
+                    .filter(|id| !self.only_run.contains(id)),
+            );
+        }
+
+        dont_run
+    }
+
     fn output_section(&self, section_name: &str) {
         if self.output_format == OutputFormat::Text {
             println!();
@@ -233,13 +296,14 @@ impl Check {
         // whether or not it is built-in, or parsed from `aziot check`
         #[derive(Debug)]
         struct CheckOutput {
+            section: String,
             id: String,
             description: String,
             result: CheckResult,
             additional_info: serde_json::Value,
         };
 
-        let mut checks: BTreeMap<String, CheckOutputSerializable> = Default::default();
+        let mut checks: Vec<CheckReport> = vec![];
 
         let mut stdout = Stdout::new(self.output_format);
 
@@ -258,6 +322,7 @@ impl Check {
             }
 
             let CheckOutput {
+                section,
                 id: check_id,
                 description: check_name,
                 result: check_result,
@@ -269,13 +334,15 @@ impl Check {
                 CheckResult::Ok => {
                     num_successful += 1;
 
-                    checks.insert(
-                        check_id,
-                        CheckOutputSerializable {
+                    checks.push(CheckReport {
+                        section,
+                        id: check_id,
+                        description: check_name.clone(),
+                        output: CheckOutputSerializable {
                             result: CheckResultSerializable::Ok,
                             additional_info,
                         },
-                    );
+                    });
 
                     stdout.write_success(|stdout| {
                         writeln!(stdout, "\u{221a} {} - OK", check_name)?;
@@ -286,15 +353,17 @@ impl Check {
                 CheckResult::Warning(ref warning) if !warnings_as_errors => {
                     num_warnings += 1;
 
-                    checks.insert(
-                        check_id,
-                        CheckOutputSerializable {
+                    checks.push(CheckReport {
+                        section,
+                        id: check_id,
+                        description: check_name.clone(),
+                        output: CheckOutputSerializable {
                             result: CheckResultSerializable::Warning {
                                 details: warning.iter_chain().map(ToString::to_string).collect(),
                             },
                             additional_info,
                         },
-                    );
+                    });
 
                     stdout.write_warning(|stdout| {
                         writeln!(stdout, "\u{203c} {} - Warning", check_name)?;
@@ -319,25 +388,29 @@ impl Check {
                 }
 
                 CheckResult::Ignored => {
-                    checks.insert(
-                        check_id,
-                        CheckOutputSerializable {
+                    checks.push(CheckReport {
+                        section,
+                        id: check_id,
+                        description: check_name,
+                        output: CheckOutputSerializable {
                             result: CheckResultSerializable::Ignored,
                             additional_info,
                         },
-                    );
+                    });
                 }
 
                 CheckResult::Skipped => {
                     num_skipped += 1;
 
-                    checks.insert(
-                        check_id,
-                        CheckOutputSerializable {
+                    checks.push(CheckReport {
+                        section,
+                        id: check_id,
+                        description: check_name.clone(),
+                        output: CheckOutputSerializable {
                             result: CheckResultSerializable::Skipped,
                             additional_info,
                         },
-                    );
+                    });
 
                     if verbose {
                         stdout.write_warning(|stdout| {
@@ -351,15 +424,17 @@ impl Check {
                 CheckResult::Fatal(err) => {
                     num_fatal += 1;
 
-                    checks.insert(
-                        check_id,
-                        CheckOutputSerializable {
+                    checks.push(CheckReport {
+                        section,
+                        id: check_id,
+                        description: check_name.clone(),
+                        output: CheckOutputSerializable {
                             result: CheckResultSerializable::Fatal {
                                 details: err.iter_chain().map(ToString::to_string).collect(),
                             },
                             additional_info,
                         },
-                    );
+                    });
 
                     stdout.write_error(|stdout| {
                         writeln!(stdout, "\u{00d7} {} - Error", check_name)?;
@@ -386,15 +461,17 @@ impl Check {
                 CheckResult::Warning(err) | CheckResult::Failed(err) => {
                     num_errors += 1;
 
-                    checks.insert(
-                        check_id,
-                        CheckOutputSerializable {
+                    checks.push(CheckReport {
+                        section,
+                        id: check_id,
+                        description: check_name.clone(),
+                        output: CheckOutputSerializable {
                             result: CheckResultSerializable::Error {
                                 details: err.iter_chain().map(ToString::to_string).collect(),
                             },
                             additional_info,
                         },
-                    );
+                    });
 
                     stdout.write_error(|stdout| {
                         writeln!(stdout, "\u{00d7} {} - Error", check_name)?;
@@ -472,10 +549,11 @@ impl Check {
                 }
             }
 
-            if !self.dont_run.is_empty() {
+            let aziot_dont_run = self.aziot_dont_run();
+            if !aziot_dont_run.is_empty() {
                 aziot_check
                     .arg("--dont-run")
-                    .arg(self.dont_run.iter().cloned().collect::<Vec<_>>().join(" "));
+                    .arg(aziot_dont_run.into_iter().collect::<Vec<_>>().join(" "));
             }
 
             if let Some(version) = &self.expected_aziot_version {
@@ -484,20 +562,30 @@ impl Check {
 
             match aziot_check.spawn() {
                 Ok(child) => {
+                    let mut aziot_section = "(aziot-identity-service)".to_owned();
+
                     for val in
                         serde_json::Deserializer::from_reader(child.stdout.unwrap()).into_iter()
                     {
                         let val = val.context(ErrorKind::Aziot)?;
                         match val {
                             CheckOutputSerializableStreaming::Section { name } => {
-                                self.output_section(&format!("{} (aziot-identity-service)", name))
+                                aziot_section = format!("{} (aziot-identity-service)", name);
+                                self.output_section(&aziot_section)
                             }
                             CheckOutputSerializableStreaming::Check { meta, output } => {
+                                let result = if self.should_run(&meta.id) {
+                                    to_check_result(output.result)
+                                } else {
+                                    CheckResult::Ignored
+                                };
+
                                 if output_check(
                                     CheckOutput {
+                                        section: aziot_section.clone(),
                                         id: meta.id,
                                         description: meta.description,
-                                        result: to_check_result(output.result),
+                                        result,
                                         additional_info: output.additional_info,
                                     },
                                     self.verbose,
@@ -535,6 +623,7 @@ impl Check {
                     self.output_section("(aziot-identity-service)");
                     output_check(
                         CheckOutput {
+                            section: "(aziot-identity-service)".into(),
                             id: "(aziot-identity-service-error)".into(),
                             description: format!(
                                 "aziot-identity-service checks unavailable - could not communicate with '{}' binary.",
@@ -550,19 +639,26 @@ impl Check {
             };
         }
 
-        // run the built-in checks
-        'outer: for (section_name, section_checks) in &mut checks::built_in_checks() {
+        // run the built-in checks, followed by the user-defined checks
+        let mut all_checks = checks::built_in_checks();
+        let user_checks = user_checks::load(Path::new(user_checks::USER_CHECKS_DIR));
+        if !user_checks.is_empty() {
+            all_checks.push((user_checks::USER_CHECKS_SECTION, user_checks));
+        }
+
+        'outer: for (section_name, section_checks) in &mut all_checks {
             self.output_section(&section_name);
 
             for check in section_checks {
-                let check_result = if self.dont_run.contains(check.id()) {
-                    CheckResult::Ignored
-                } else {
+                let check_result = if self.should_run(check.id()) {
                     check.execute(self, runtime)
+                } else {
+                    CheckResult::Ignored
                 };
 
                 if output_check(
                     CheckOutput {
+                        section: (*section_name).to_owned(),
                         id: check.id().into(),
                         description: check.description().into(),
                         result: check_result,
@@ -627,18 +723,37 @@ impl Check {
             Ok(())
         };
 
-        if self.output_format == OutputFormat::Json {
-            let check_results = CheckResultsSerializable {
-                additional_info: serde_json::to_value(&self.additional_info).unwrap(),
-                checks,
-            };
+        match self.output_format {
+            OutputFormat::Json => {
+                let check_results = CheckResultsSerializable {
+                    additional_info: serde_json::to_value(&self.additional_info).unwrap(),
+                    checks: checks
+                        .into_iter()
+                        .map(|check| (check.id, check.output))
+                        .collect(),
+                };
 
-            if let Err(err) = serde_json::to_writer(std::io::stdout(), &check_results) {
-                eprintln!("Could not write JSON output: {}", err,);
-                return Err(ErrorKind::Diagnostics.into());
+                if let Err(err) = serde_json::to_writer(std::io::stdout(), &check_results) {
+                    eprintln!("Could not write JSON output: {}", err,);
+                    return Err(ErrorKind::Diagnostics.into());
+                }
+
+                println!();
             }
 
-            println!();
+            OutputFormat::Junit => print!("{}", report::junit(&checks)),
+
+            OutputFormat::Sarif => {
+                if let Err(err) = serde_json::to_writer(std::io::stdout(), &report::sarif(&checks))
+                {
+                    eprintln!("Could not write SARIF output: {}", err,);
+                    return Err(ErrorKind::Diagnostics.into());
+                }
+
+                println!();
+            }
+
+            OutputFormat::Text => (),
         }
 
         result
@@ -694,6 +809,7 @@ mod tests {
                 "daemon.json".into(), // unused for this test
                 "mcr.microsoft.com/azureiotedge-diagnostics:1.0.0".to_owned(), // unused for this test
                 Default::default(),
+                Default::default(),
                 Some("1.0.0".to_owned()),  // unused for this test
                 Some("1.0.0".to_owned()),  // unused for this test
                 "aziot-edged".into(),      // unused for this test
@@ -744,6 +860,7 @@ mod tests {
                 "daemon.json".into(), // unused for this test
                 "mcr.microsoft.com/azureiotedge-diagnostics:1.0.0".to_owned(), // unused for this test
                 Default::default(),
+                Default::default(),
                 Some("1.0.0".to_owned()),  // unused for this test
                 Some("1.0.0".to_owned()),  // unused for this test
                 "aziot-edged".into(),      // unused for this test
@@ -795,6 +912,7 @@ mod tests {
             "daemon.json".into(), // unused for this test
             "mcr.microsoft.com/azureiotedge-diagnostics:1.0.0".to_owned(), // unused for this test
             Default::default(),
+            Default::default(),
             Some("1.0.0".to_owned()),  // unused for this test
             Some("1.0.0".to_owned()),  // unused for this test
             "aziot-edged".into(),      // unused for this test
@@ -834,6 +952,7 @@ mod tests {
             "daemon.json".into(), // unused for this test
             "mcr.microsoft.com/azureiotedge-diagnostics:1.0.0".to_owned(), // unused for this test
             Default::default(),
+            Default::default(),
             Some("1.0.0".to_owned()),  // unused for this test
             Some("1.0.0".to_owned()),  // unused for this test
             "aziot-edged".into(),      // unused for this test
@@ -0,0 +1,281 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+//! `JUnit` XML and SARIF reports of the results of `iotedge check`.
+
+use aziotctl_common::{CheckOutputSerializable, CheckResultSerializable};
+
+/// The outcome of a single check, with what's needed to report it.
+#[derive(Debug)]
+pub(super) struct CheckReport {
+    pub(super) section: String,
+    pub(super) id: String,
+    pub(super) description: String,
+    pub(super) output: CheckOutputSerializable,
+}
+
+/// Renders the checks as a `JUnit` XML document, with a test suite for each section.
+pub(super) fn junit(checks: &[CheckReport]) -> String {
+    let mut sections: Vec<(&str, Vec<&CheckReport>)> = vec![];
+    for check in checks {
+        match sections.last_mut() {
+            Some((section, section_checks)) if *section == check.section => {
+                section_checks.push(check)
+            }
+            _ => sections.push((&check.section, vec![check])),
+        }
+    }
+
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str(&format!(
+        "<testsuites name=\"iotedge check\"{}>\n",
+        junit_counts(checks.iter())
+    ));
+
+    for (section, section_checks) in sections {
+        xml.push_str(&format!(
+            "  <testsuite name=\"{}\"{}>\n",
+            xml_escape(section),
+            junit_counts(section_checks.iter().copied())
+        ));
+
+        for check in section_checks {
+            xml.push_str(&format!(
+                "    <testcase classname=\"{}\" name=\"{}\">\n",
+                xml_escape(section),
+                xml_escape(&check.id)
+            ));
+            xml.push_str(&format!(
+                "      <properties>\n        <property name=\"description\" value=\"{}\"/>\n      </properties>\n",
+                xml_escape(&check.description)
+            ));
+
+            match &check.output.result {
+                CheckResultSerializable::Ok => (),
+                CheckResultSerializable::Warning { details } => xml.push_str(&format!(
+                    "      <system-out>{}</system-out>\n",
+                    xml_escape(&details.join("\ncaused by: "))
+                )),
+                CheckResultSerializable::Ignored => xml.push_str(
+                    "      <skipped message=\"check is not applicable or was not selected\"/>\n",
+                ),
+                CheckResultSerializable::Skipped => xml.push_str(
+                    "      <skipped message=\"skipped because of errors from other checks\"/>\n",
+                ),
+                CheckResultSerializable::Error { details } => {
+                    xml.push_str(&junit_problem("failure", details))
+                }
+                CheckResultSerializable::Fatal { details } => {
+                    xml.push_str(&junit_problem("error", details))
+                }
+            }
+
+            xml.push_str("    </testcase>\n");
+        }
+
+        xml.push_str("  </testsuite>\n");
+    }
+
+    xml.push_str("</testsuites>\n");
+    xml
+}
+
+fn junit_counts<'a>(checks: impl Iterator<Item = &'a CheckReport>) -> String {
+    let (mut tests, mut failures, mut errors, mut skipped) = (0, 0, 0, 0);
+    for check in checks {
+        tests += 1;
+        match check.output.result {
+            CheckResultSerializable::Ok | CheckResultSerializable::Warning { .. } => (),
+            CheckResultSerializable::Ignored | CheckResultSerializable::Skipped => skipped += 1,
+            CheckResultSerializable::Error { .. } => failures += 1,
+            CheckResultSerializable::Fatal { .. } => errors += 1,
+        }
+    }
+
+    format!(
+        " tests=\"{}\" failures=\"{}\" errors=\"{}\" skipped=\"{}\"",
+        tests, failures, errors, skipped
+    )
+}
+
+fn junit_problem(element: &str, details: &[String]) -> String {
+    format!(
+        "      <{0} message=\"{1}\">{2}</{0}>\n",
+        element,
+        xml_escape(details.first().map_or("", String::as_str)),
+        xml_escape(&details.join("\ncaused by: "))
+    )
+}
+
+fn xml_escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Renders the checks as a SARIF 2.1.0 log, with a rule for each check.
+pub(super) fn sarif(checks: &[CheckReport]) -> serde_json::Value {
+    let rules: Vec<_> = checks
+        .iter()
+        .map(|check| {
+            serde_json::json!({
+                "id": check.id,
+                "shortDescription": { "text": check.description },
+                "properties": { "category": check.section },
+            })
+        })
+        .collect();
+
+    let results: Vec<_> = checks
+        .iter()
+        .enumerate()
+        .map(|(rule_index, check)| {
+            let (kind, level, message) = match &check.output.result {
+                CheckResultSerializable::Ok => ("pass", "none", check.description.clone()),
+                CheckResultSerializable::Warning { details } => {
+                    ("fail", "warning", details.join("\ncaused by: "))
+                }
+                CheckResultSerializable::Ignored => (
+                    "notApplicable",
+                    "none",
+                    "check is not applicable or was not selected".to_owned(),
+                ),
+                CheckResultSerializable::Skipped => (
+                    "open",
+                    "none",
+                    "skipped because of errors from other checks".to_owned(),
+                ),
+                CheckResultSerializable::Error { details }
+                | CheckResultSerializable::Fatal { details } => {
+                    ("fail", "error", details.join("\ncaused by: "))
+                }
+            };
+
+            serde_json::json!({
+                "ruleId": check.id,
+                "ruleIndex": rule_index,
+                "kind": kind,
+                "level": level,
+                "message": { "text": message },
+            })
+        })
+        .collect();
+
+    serde_json::json!({
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": "iotedge check",
+                    "version": edgelet_core::version_with_source_version(),
+                    "rules": rules,
+                },
+            },
+            "results": results,
+        }],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use aziotctl_common::{CheckOutputSerializable, CheckResultSerializable};
+
+    use super::CheckReport;
+
+    fn checks() -> Vec<CheckReport> {
+        vec![
+            CheckReport {
+                section: "Configuration checks".to_owned(),
+                id: "aziot-edged-config-well-formed".to_owned(),
+                description: "config.toml is well-formed".to_owned(),
+                output: CheckOutputSerializable {
+                    result: CheckResultSerializable::Ok,
+                    additional_info: serde_json::Value::Null,
+                },
+            },
+            CheckReport {
+                section: "Configuration checks".to_owned(),
+                id: "container-engine-logrotate".to_owned(),
+                description: "production readiness: logs policy".to_owned(),
+                output: CheckOutputSerializable {
+                    result: CheckResultSerializable::Warning {
+                        details: vec!["<max-size> is not set".to_owned()],
+                    },
+                    additional_info: serde_json::Value::Null,
+                },
+            },
+            CheckReport {
+                section: "Connectivity checks".to_owned(),
+                id: "host-connect-iothub-amqp".to_owned(),
+                description: "host can connect to and perform TLS handshake with iothub AMQP port"
+                    .to_owned(),
+                output: CheckOutputSerializable {
+                    result: CheckResultSerializable::Error {
+                        details: vec![
+                            "Could not connect".to_owned(),
+                            "Connection refused".to_owned(),
+                        ],
+                    },
+                    additional_info: serde_json::Value::Null,
+                },
+            },
+        ]
+    }
+
+    #[test]
+    fn junit_has_a_test_suite_for_each_section() {
+        let junit = super::junit(&checks());
+
+        assert!(junit.contains(
+            "<testsuites name=\"iotedge check\" tests=\"3\" failures=\"1\" errors=\"0\" skipped=\"0\">"
+        ));
+        assert!(junit.contains(
+            "<testsuite name=\"Configuration checks\" tests=\"2\" failures=\"0\" errors=\"0\" skipped=\"0\">"
+        ));
+        assert!(junit.contains(
+            "<testsuite name=\"Connectivity checks\" tests=\"1\" failures=\"1\" errors=\"0\" skipped=\"0\">"
+        ));
+        assert!(junit.contains(
+            "<failure message=\"Could not connect\">Could not connect\ncaused by: Connection refused</failure>"
+        ));
+        assert!(junit.contains("<system-out>&lt;max-size&gt; is not set</system-out>"));
+    }
+
+    #[test]
+    fn sarif_has_a_result_for_each_check() {
+        let sarif = super::sarif(&checks());
+
+        let run = &sarif["runs"][0];
+        assert_eq!(
+            "aziot-edged-config-well-formed",
+            run["tool"]["driver"]["rules"][0]["id"]
+        );
+
+        let results = run["results"].as_array().unwrap();
+        let kinds: Vec<_> = results
+            .iter()
+            .map(|result| (result["kind"].as_str(), result["level"].as_str()))
+            .collect();
+        assert_eq!(
+            vec![
+                (Some("pass"), Some("none")),
+                (Some("fail"), Some("warning")),
+                (Some("fail"), Some("error")),
+            ],
+            kinds
+        );
+        assert_eq!(
+            "Could not connect\ncaused by: Connection refused",
+            results[2]["message"]["text"]
+        );
+    }
+}
@@ -10,7 +10,8 @@ pub(super) enum Stdout {
         error_color_spec: termcolor::ColorSpec,
     },
 
-    DefaultJson,
+    /// Machine-readable output formats are written all at once after the checks have run.
+    Silent,
 
     DefaultText,
 }
@@ -37,7 +38,9 @@ impl Stdout {
             }
         } else {
             match output_format {
-                super::OutputFormat::Json => Stdout::DefaultJson,
+                super::OutputFormat::Json
+                | super::OutputFormat::Junit
+                | super::OutputFormat::Sarif => Stdout::Silent,
                 super::OutputFormat::Text => Stdout::DefaultText,
             }
         }
@@ -53,7 +56,7 @@ impl Stdout {
                 success_color_spec,
                 ..
             } => write_colored(stdout, success_color_spec, f),
-            Stdout::DefaultJson => Ok(()),
+            Stdout::Silent => Ok(()),
             Stdout::DefaultText => f(&mut std::io::stdout()),
         };
         result.expect("could not write to stdout");
@@ -69,7 +72,7 @@ impl Stdout {
                 warning_color_spec,
                 ..
             } => write_colored(stdout, warning_color_spec, f),
-            Stdout::DefaultJson => Ok(()),
+            Stdout::Silent => Ok(()),
             Stdout::DefaultText => f(&mut std::io::stdout()),
         };
         result.expect("could not write to stdout");
@@ -85,7 +88,7 @@ impl Stdout {
                 error_color_spec,
                 ..
             } => write_colored(stdout, error_color_spec, f),
-            Stdout::DefaultJson => Ok(()),
+            Stdout::Silent => Ok(()),
             Stdout::DefaultText => f(&mut std::io::stdout()),
         };
         result.expect("could not write to stdout");
@@ -0,0 +1,834 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+//! Checks that are declared in TOML files in the checks.d directory, for requirements that are
+//! specific to a device or site. They run after the built-in checks.
+//!
+//! Each file contains any number of checks:
+//!
+//! ```toml
+//! [[check]]
+//! id = "data-mounted"
+//! description = "data volume is mounted"
+//! severity = "warning"
+//! kind = "file"
+//! path = "/mnt/data"
+//! mode = 0o750
+//! ```
+
+use std::collections::BTreeSet;
+use std::ffi::OsStr;
+use std::fs::File;
+use std::io::Read;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use failure::{self, Context, Fail, ResultExt};
+use futures::Stream;
+
+use edgelet_core::{Module, ModuleRuntime, RuntimeSettings};
+use edgelet_http_mgmt::ModuleClient;
+
+use crate::check::{checker::Checker, checks::built_in_checks, Check, CheckResult};
+
+pub(crate) const USER_CHECKS_DIR: &str = "/etc/aziot/edged/checks.d";
+
+pub(crate) const USER_CHECKS_SECTION: &str = "User-defined checks";
+
+const COMMAND_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+#[derive(Debug, serde_derive::Deserialize)]
+struct UserChecksFile {
+    #[serde(default, rename = "check")]
+    checks: Vec<UserCheck>,
+}
+
+#[derive(Debug, serde_derive::Deserialize, serde_derive::Serialize)]
+pub(crate) struct UserCheck {
+    id: String,
+    description: String,
+    #[serde(default)]
+    severity: Severity,
+    #[serde(flatten)]
+    kind: Kind,
+}
+
+/// How a failed check is reported.
+#[derive(Clone, Copy, Debug, PartialEq, serde_derive::Deserialize, serde_derive::Serialize)]
+#[serde(rename_all = "lowercase")]
+enum Severity {
+    Error,
+    Warning,
+}
+
+impl Default for Severity {
+    fn default() -> Self {
+        Severity::Error
+    }
+}
+
+#[derive(Debug, serde_derive::Deserialize, serde_derive::Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum Kind {
+    /// The command exits with the expected exit code. It's killed if it runs for longer than
+    /// the timeout.
+    Command {
+        command: Vec<String>,
+        #[serde(default)]
+        exit_code: i32,
+        #[serde(default = "default_timeout_secs")]
+        timeout_secs: u64,
+    },
+
+    /// A TCP connection can be opened to the address.
+    TcpConnect {
+        address: String,
+        #[serde(default = "default_timeout_secs")]
+        timeout_secs: u64,
+    },
+
+    /// The file exists, or doesn't, and has the expected permissions and owner.
+    File {
+        path: PathBuf,
+        #[serde(default = "default_exists")]
+        exists: bool,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        mode: Option<u32>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        owner: Option<String>,
+    },
+
+    /// The module is deployed, has the expected status, and runs the expected image. An image
+    /// that starts with "sha256:" is matched against the ID and the repository digests of the
+    /// image that the module's container runs.
+    Module {
+        name: String,
+        #[serde(default = "default_status")]
+        status: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        image: Option<String>,
+    },
+}
+
+fn default_timeout_secs() -> u64 {
+    10
+}
+
+fn default_exists() -> bool {
+    true
+}
+
+fn default_status() -> String {
+    "running".to_owned()
+}
+
+/// Loads the checks from all the .toml files in `dir`, in the order of their file names.
+///
+/// A file that can't be read or parsed is reported as a failed check of its own, so that the
+/// other checks still run. So is a file that others than root and the user that runs the
+/// checks could have changed, since its commands run as that user. Checks whose ID is already
+/// used by a built-in check or an earlier user-defined check are skipped with a warning, since
+/// results are reported by ID.
+pub(crate) fn load(dir: &Path) -> Vec<Box<dyn Checker>> {
+    let mut paths: Vec<PathBuf> = match std::fs::read_dir(dir) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension() == Some(OsStr::new("toml")))
+            .collect(),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return vec![],
+        Err(err) => {
+            return vec![Box::new(InvalidUserChecks::new(
+                dir.to_owned(),
+                err.context(format!("could not read {}", dir.display()))
+                    .into(),
+            ))]
+        }
+    };
+    paths.sort();
+
+    let mut ids: BTreeSet<String> = built_in_checks()
+        .iter()
+        .flat_map(|(_, checks)| checks.iter().map(|check| check.id().to_owned()))
+        .collect();
+    let mut checks: Vec<Box<dyn Checker>> = vec![];
+    for path in paths {
+        let file = match read(&path) {
+            Ok(file) => file,
+            Err(err) => {
+                checks.push(Box::new(InvalidUserChecks::new(path, err)));
+                continue;
+            }
+        };
+
+        let mut skipped = vec![];
+        for check in file.checks {
+            if ids.insert(check.id.clone()) {
+                checks.push(Box::new(check));
+            } else {
+                skipped.push(check.id);
+            }
+        }
+        if !skipped.is_empty() {
+            let err = Context::new(format!(
+                "checks {} in {} were skipped, since their IDs are already used",
+                skipped.join(", "),
+                path.display()
+            ));
+            checks.push(Box::new(InvalidUserChecks::skipped(path, err.into())));
+        }
+    }
+
+    checks
+}
+
+fn read(path: &Path) -> Result<UserChecksFile, failure::Error> {
+    let mut file =
+        File::open(path).with_context(|_| format!("could not read {}", path.display()))?;
+    let metadata = file
+        .metadata()
+        .with_context(|_| format!("could not read metadata of {}", path.display()))?;
+    if metadata.uid() != 0 && metadata.uid() != nix::unistd::Uid::effective().as_raw() {
+        return Err(Context::new(format!(
+            "{} is owned by uid {}, so its checks are not run",
+            path.display(),
+            metadata.uid()
+        ))
+        .into());
+    }
+    if metadata.mode() & 0o022 != 0 {
+        return Err(Context::new(format!(
+            "{} can be written by its group or others, so its checks are not run",
+            path.display()
+        ))
+        .into());
+    }
+
+    let mut contents = vec![];
+    file.read_to_end(&mut contents)
+        .with_context(|_| format!("could not read {}", path.display()))?;
+    let file = toml::from_slice(&contents)
+        .with_context(|_| format!("could not parse {}", path.display()))?;
+    Ok(file)
+}
+
+impl Checker for UserCheck {
+    fn id(&self) -> &str {
+        &self.id
+    }
+    fn description(&self) -> &str {
+        &self.description
+    }
+    fn execute(&mut self, check: &mut Check, runtime: &mut tokio::runtime::Runtime) -> CheckResult {
+        match self.inner_execute(check, runtime) {
+            Ok(result) => result,
+            Err(err) if self.severity == Severity::Warning => CheckResult::Warning(err),
+            Err(err) => CheckResult::Failed(err),
+        }
+    }
+    fn get_json(&self) -> serde_json::Value {
+        serde_json::to_value(self).unwrap()
+    }
+}
+
+impl UserCheck {
+    fn inner_execute(
+        &self,
+        check: &mut Check,
+        runtime: &mut tokio::runtime::Runtime,
+    ) -> Result<CheckResult, failure::Error> {
+        match &self.kind {
+            Kind::Command {
+                command,
+                exit_code,
+                timeout_secs,
+            } => run_command(command, *exit_code, *timeout_secs),
+            Kind::TcpConnect {
+                address,
+                timeout_secs,
+            } => tcp_connect(address, *timeout_secs),
+            Kind::File {
+                path,
+                exists,
+                mode,
+                owner,
+            } => file(path, *exists, *mode, owner.as_deref()),
+            Kind::Module {
+                name,
+                status,
+                image,
+            } => module(check, runtime, name, status, image.as_deref()),
+        }
+    }
+}
+
+fn run_command(
+    command: &[String],
+    exit_code: i32,
+    timeout_secs: u64,
+) -> Result<CheckResult, failure::Error> {
+    let (program, args) = command
+        .split_first()
+        .ok_or_else(|| Context::new("command is empty"))?;
+
+    let mut child = std::process::Command::new(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .with_context(|_| format!("could not run {}", program))?;
+    // The output is read while the command runs, so that it doesn't block on a full pipe.
+    let stdout = read_to_end(child.stdout.take());
+    let stderr = read_to_end(child.stderr.take());
+
+    let timeout = Duration::from_secs(timeout_secs);
+    let deadline = Instant::now() + timeout;
+    let status = loop {
+        if let Some(status) = child
+            .try_wait()
+            .with_context(|_| format!("could not wait for {}", program))?
+        {
+            break status;
+        }
+        if Instant::now() >= deadline {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(Context::new(format!(
+                "{} did not exit within {} seconds",
+                program, timeout_secs
+            ))
+            .into());
+        }
+        thread::sleep(COMMAND_POLL_INTERVAL);
+    };
+
+    if status.code() == Some(exit_code) {
+        return Ok(CheckResult::Ok);
+    }
+
+    // Processes that the command started can keep the pipes open after it exited, so its
+    // output is waited for no longer than the command itself.
+    let deadline = Instant::now() + timeout;
+    let output = [stdout, stderr].iter().map(|output| {
+        output
+            .recv_timeout(deadline.saturating_duration_since(Instant::now()))
+            .unwrap_or_default()
+    });
+    let mut message = match status.code() {
+        Some(code) => format!(
+            "{} exited with code {} instead of {}",
+            program, code, exit_code
+        ),
+        None => format!("{} was terminated by a signal", program),
+    };
+    for stream in output {
+        let stream = String::from_utf8_lossy(&stream);
+        let stream = stream.trim();
+        if !stream.is_empty() {
+            message.push('\n');
+            message.push_str(stream);
+        }
+    }
+    Err(Context::new(message).into())
+}
+
+fn read_to_end<R>(pipe: Option<R>) -> mpsc::Receiver<Vec<u8>>
+where
+    R: Read + Send + 'static,
+{
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let mut output = vec![];
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_end(&mut output);
+        }
+        let _ = sender.send(output);
+    });
+    receiver
+}
+
+fn tcp_connect(address: &str, timeout_secs: u64) -> Result<CheckResult, failure::Error> {
+    let addrs = address
+        .to_socket_addrs()
+        .with_context(|_| format!("could not resolve {}", address))?;
+
+    let mut last_err = None;
+    for addr in addrs {
+        match TcpStream::connect_timeout(&addr, Duration::from_secs(timeout_secs)) {
+            Ok(_) => return Ok(CheckResult::Ok),
+            Err(err) => last_err = Some(err),
+        }
+    }
+
+    Err(match last_err {
+        Some(err) => err
+            .context(format!("could not connect to {}", address))
+            .into(),
+        None => Context::new(format!("{} did not resolve to any address", address)).into(),
+    })
+}
+
+fn file(
+    path: &Path,
+    exists: bool,
+    mode: Option<u32>,
+    owner: Option<&str>,
+) -> Result<CheckResult, failure::Error> {
+    let metadata = match std::fs::metadata(path) {
+        Ok(_) if !exists => return Err(Context::new(format!("{} exists", path.display())).into()),
+        Ok(metadata) => metadata,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+            return if exists {
+                Err(Context::new(format!("{} does not exist", path.display())).into())
+            } else {
+                Ok(CheckResult::Ok)
+            };
+        }
+        Err(err) => {
+            return Err(err
+                .context(format!("could not read metadata of {}", path.display()))
+                .into())
+        }
+    };
+
+    if let Some(mode) = mode {
+        let actual_mode = metadata.permissions().mode() & 0o7777;
+        if actual_mode != mode {
+            return Err(Context::new(format!(
+                "{} has mode {:o} instead of {:o}",
+                path.display(),
+                actual_mode,
+                mode
+            ))
+            .into());
+        }
+    }
+
+    if let Some(owner) = owner {
+        let uid = metadata.uid();
+        let actual_owner = nix::unistd::User::from_uid(nix::unistd::Uid::from_raw(uid))
+            .ok()
+            .flatten()
+            .map_or_else(|| uid.to_string(), |user| user.name);
+        if actual_owner != owner {
+            return Err(Context::new(format!(
+                "{} is owned by {} instead of {}",
+                path.display(),
+                actual_owner,
+                owner
+            ))
+            .into());
+        }
+    }
+
+    Ok(CheckResult::Ok)
+}
+
+fn module(
+    check: &Check,
+    runtime: &mut tokio::runtime::Runtime,
+    name: &str,
+    status: &str,
+    image: Option<&str>,
+) -> Result<CheckResult, failure::Error> {
+    let settings = if let Some(settings) = &check.settings {
+        settings
+    } else {
+        return Ok(CheckResult::Skipped);
+    };
+
+    let client = ModuleClient::new(settings.connect().management_uri())
+        .context("could not create management API client")?;
+    let modules = runtime
+        .block_on(client.list_with_details().collect())
+        .context("could not list modules")?;
+
+    let (module, state) = modules
+        .iter()
+        .find(|(module, _)| module.name() == name)
+        .ok_or_else(|| Context::new(format!("module {} is not deployed", name)))?;
+
+    let actual_status = state.status().to_string();
+    if actual_status != status {
+        return Err(Context::new(format!(
+            "module {} is {} instead of {}",
+            name, actual_status, status
+        ))
+        .into());
+    }
+
+    if let Some(image) = image {
+        if image.starts_with("sha256:") {
+            let image_id = state
+                .image_id()
+                .ok_or_else(|| Context::new(format!("module {} has no image ID", name)))?;
+            let digests = image_digests(check, image_id)?;
+            if !digests.iter().any(|digest| digest == image) {
+                return Err(Context::new(format!(
+                    "module {} runs image {} instead of {}",
+                    name,
+                    digests.join(", "),
+                    image
+                ))
+                .into());
+            }
+        } else {
+            let actual_image = module.config().to_string();
+            if actual_image != image {
+                return Err(Context::new(format!(
+                    "module {} runs image {} instead of {}",
+                    name, actual_image, image
+                ))
+                .into());
+            }
+        }
+    }
+
+    Ok(CheckResult::Ok)
+}
+
+/// The ID of an image, and the digests it was pulled by when the container engine can be
+/// asked for them.
+fn image_digests(check: &Check, image_id: &str) -> Result<Vec<String>, failure::Error> {
+    let mut digests = vec![image_id.to_owned()];
+
+    if let Some(docker_host_arg) = &check.docker_host_arg {
+        let output = super::checks::docker(
+            docker_host_arg,
+            &[
+                "image",
+                "inspect",
+                "--format",
+                "{{json .RepoDigests}}",
+                image_id,
+            ],
+        )
+        .map_err(|(_, err)| err)
+        .with_context(|_| format!("could not inspect image {}", image_id))?;
+        let repo_digests: Option<Vec<String>> = serde_json::from_slice(&output)
+            .with_context(|_| format!("could not parse the digests of image {}", image_id))?;
+        // Repository digests look like "mcr.microsoft.com/azureiotedge-agent@sha256:...".
+        digests.extend(
+            repo_digests
+                .into_iter()
+                .flatten()
+                .filter_map(|repo_digest| repo_digest.rsplit('@').next().map(ToOwned::to_owned)),
+        );
+    }
+
+    Ok(digests)
+}
+
+/// A file of user-defined checks that couldn't be loaded, or some of whose checks were skipped.
+pub(crate) struct InvalidUserChecks {
+    id: String,
+    description: String,
+    path: PathBuf,
+    err: Option<failure::Error>,
+    severity: Severity,
+}
+
+impl InvalidUserChecks {
+    fn new(path: PathBuf, err: failure::Error) -> Self {
+        InvalidUserChecks::with_severity(path, err, Severity::Error)
+    }
+
+    fn skipped(path: PathBuf, err: failure::Error) -> Self {
+        InvalidUserChecks::with_severity(path, err, Severity::Warning)
+    }
+
+    fn with_severity(path: PathBuf, err: failure::Error, severity: Severity) -> Self {
+        let file_name = path
+            .file_name()
+            .map_or_else(
+                || path.to_string_lossy(),
+                |file_name| file_name.to_string_lossy(),
+            )
+            .into_owned();
+        InvalidUserChecks {
+            id: format!("checks.d/{}", file_name),
+            description: format!("user-defined checks in {} are well-formed", file_name),
+            path,
+            err: Some(err),
+            severity,
+        }
+    }
+}
+
+impl Checker for InvalidUserChecks {
+    fn id(&self) -> &str {
+        &self.id
+    }
+    fn description(&self) -> &str {
+        &self.description
+    }
+    fn execute(&mut self, _: &mut Check, _: &mut tokio::runtime::Runtime) -> CheckResult {
+        match (self.err.take(), self.severity) {
+            (Some(err), Severity::Warning) => CheckResult::Warning(err),
+            (Some(err), Severity::Error) => CheckResult::Failed(err),
+            (None, _) => CheckResult::Skipped,
+        }
+    }
+    fn get_json(&self) -> serde_json::Value {
+        serde_json::json!({ "path": self.path })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::TcpListener;
+    use std::os::unix::fs::PermissionsExt;
+    use std::time::{Duration, Instant};
+
+    use super::{load, Check, CheckResult};
+
+    /// Writes a file of checks that can be run, whatever the umask.
+    fn write(path: impl AsRef<std::path::Path>, contents: impl AsRef<[u8]>) {
+        std::fs::write(&path, contents).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644)).unwrap();
+    }
+
+    fn check() -> Check {
+        Check::new(
+            "daemon.json".into(), // unused for this test
+            "mcr.microsoft.com/azureiotedge-diagnostics:1.0.0".to_owned(), // unused for this test
+            Default::default(),
+            Default::default(),
+            Some("1.0.0".to_owned()),         // unused for this test
+            Some("1.0.0".to_owned()),         // unused for this test
+            "aziot-edged".into(),             // unused for this test
+            super::super::OutputFormat::Text, // unused for this test
+            false,
+            false,
+            "".into(), // unused for this test
+            None,
+            None,
+        )
+    }
+
+    #[test]
+    fn user_checks_are_run() {
+        let mut runtime = tokio::runtime::Runtime::new().unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        write(
+            dir.path().join("10-site.toml"),
+            format!(
+                r#"
+[[check]]
+id = "command-ok"
+description = "command exits with 3"
+kind = "command"
+command = ["sh", "-c", "exit 3"]
+exit_code = 3
+
+[[check]]
+id = "command-fails"
+description = "command exits with 0"
+severity = "warning"
+kind = "command"
+command = ["sh", "-c", "echo oops; exit 1"]
+
+[[check]]
+id = "command-times-out"
+description = "command exits within a second"
+severity = "warning"
+kind = "command"
+command = ["sleep", "30"]
+timeout_secs = 1
+
+[[check]]
+id = "port-open"
+description = "port is open"
+kind = "tcp_connect"
+address = "{}"
+
+[[check]]
+id = "file-mode"
+description = "checks directory has the right mode"
+kind = "file"
+path = "{}"
+mode = 0o700
+
+[[check]]
+id = "file-absent"
+description = "file does not exist"
+kind = "file"
+path = "/nonexistent"
+exists = false
+"#,
+                listener.local_addr().unwrap(),
+                dir.path().display(),
+            ),
+        );
+        write(dir.path().join("20-ignored.txt"), "not a check");
+
+        let mut check = check();
+        let results: Vec<_> = load(dir.path())
+            .iter_mut()
+            .map(|checker| {
+                (
+                    checker.id().to_owned(),
+                    checker.execute(&mut check, &mut runtime),
+                )
+            })
+            .collect();
+
+        let ids: Vec<_> = results.iter().map(|(id, _)| id.as_str()).collect();
+        assert_eq!(
+            vec![
+                "command-ok",
+                "command-fails",
+                "command-times-out",
+                "port-open",
+                "file-mode",
+                "file-absent"
+            ],
+            ids
+        );
+
+        match &results[1].1 {
+            CheckResult::Warning(err) => {
+                assert_eq!("sh exited with code 1 instead of 0\noops", err.to_string())
+            }
+            result => panic!("command-fails returned {:?}", result),
+        }
+        match &results[2].1 {
+            CheckResult::Warning(err) => {
+                assert_eq!("sleep did not exit within 1 seconds", err.to_string())
+            }
+            result => panic!("command-times-out returned {:?}", result),
+        }
+        for (id, result) in results
+            .iter()
+            .filter(|(id, _)| !id.starts_with("command-") || id == "command-ok")
+        {
+            match result {
+                CheckResult::Ok => (),
+                result => panic!("{} returned {:?}", id, result),
+            }
+        }
+    }
+
+    #[test]
+    fn invalid_user_checks_file_is_a_failed_check() {
+        let mut runtime = tokio::runtime::Runtime::new().unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        write(
+            dir.path().join("site.toml"),
+            "[[check]]\nid = \"unknown-kind\"\ndescription = \"\"\nkind = \"ping\"\n",
+        );
+
+        let mut checks = load(dir.path());
+        assert_eq!(1, checks.len());
+        assert_eq!("checks.d/site.toml", checks[0].id());
+        match checks[0].execute(&mut check(), &mut runtime) {
+            CheckResult::Failed(_) => (),
+            result => panic!("invalid file returned {:?}", result),
+        }
+    }
+
+    #[test]
+    fn checks_with_used_ids_are_skipped() {
+        let mut runtime = tokio::runtime::Runtime::new().unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        write(
+            dir.path().join("site.toml"),
+            r#"
+[[check]]
+id = "absent"
+description = "file does not exist"
+kind = "file"
+path = "/nonexistent"
+exists = false
+
+[[check]]
+id = "absent"
+description = "another file does not exist"
+kind = "file"
+path = "/nonexistent2"
+exists = false
+
+[[check]]
+id = "aziot-edged-config-well-formed"
+description = "config is well-formed"
+kind = "file"
+path = "/etc/aziot/config.toml"
+"#,
+        );
+
+        let mut checks = load(dir.path());
+        let ids: Vec<_> = checks.iter().map(|check| check.id().to_owned()).collect();
+        assert_eq!(vec!["absent", "checks.d/site.toml"], ids);
+        assert_eq!("file does not exist", checks[0].description());
+        match checks[1].execute(&mut check(), &mut runtime) {
+            CheckResult::Warning(err) => assert!(err
+                .to_string()
+                .starts_with("checks absent, aziot-edged-config-well-formed in ")),
+            result => panic!("skipped checks returned {:?}", result),
+        }
+    }
+
+    #[test]
+    fn writable_user_checks_file_is_not_run() {
+        let mut runtime = tokio::runtime::Runtime::new().unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("site.toml");
+        write(
+            &path,
+            "[[check]]\nid = \"c\"\ndescription = \"\"\nkind = \"command\"\ncommand = [\"true\"]\n",
+        );
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o666)).unwrap();
+
+        let mut checks = load(dir.path());
+        assert_eq!(1, checks.len());
+        assert_eq!("checks.d/site.toml", checks[0].id());
+        match checks[0].execute(&mut check(), &mut runtime) {
+            CheckResult::Failed(err) => assert!(err
+                .to_string()
+                .ends_with("can be written by its group or others, so its checks are not run")),
+            result => panic!("writable file returned {:?}", result),
+        }
+    }
+
+    #[test]
+    fn command_output_is_not_waited_for_past_timeout() {
+        let mut runtime = tokio::runtime::Runtime::new().unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        write(
+            dir.path().join("site.toml"),
+            r#"
+[[check]]
+id = "command-leaves-child"
+description = "command exits with 0"
+kind = "command"
+command = ["sh", "-c", "sleep 30 & exit 1"]
+timeout_secs = 1
+"#,
+        );
+
+        let mut checks = load(dir.path());
+        let start = Instant::now();
+        match checks[0].execute(&mut check(), &mut runtime) {
+            CheckResult::Failed(err) => {
+                assert_eq!("sh exited with code 1 instead of 0", err.to_string())
+            }
+            result => panic!("command-leaves-child returned {:?}", result),
+        }
+        assert!(start.elapsed() < Duration::from_secs(10));
+    }
+
+    #[test]
+    fn missing_user_checks_dir_has_no_checks() {
+        assert!(load(std::path::Path::new("/nonexistent/checks.d")).is_empty());
+    }
+}
@@ -2,30 +2,42 @@
 
 //! This subcommand takes the super-config file, converts it into the individual services' config files,
 //! writes those files, and restarts the services.
+//!
+//! With `--dry-run`, it only shows how the config files would change and which services would be restarted.
 
 use std::path::Path;
 
 use aziotctl_common::config as common_config;
 
-use super::super_config;
+use super::{diff, super_config};
 
-const AZIOT_EDGED_HOMEDIR_PATH: &str = "/var/lib/aziot/edged";
+pub(crate) const AZIOT_EDGED_HOMEDIR_PATH: &str = "/var/lib/aziot/edged";
+
+const KEYD_CONFIG_PATH: &str = "/etc/aziot/keyd/config.d/00-super.toml";
+const CERTD_CONFIG_PATH: &str = "/etc/aziot/certd/config.d/00-super.toml";
+const IDENTITYD_CONFIG_PATH: &str = "/etc/aziot/identityd/config.d/00-super.toml";
+const TPMD_CONFIG_PATH: &str = "/etc/aziot/tpmd/config.d/00-super.toml";
+const EDGED_CONFIG_PATH: &str = "/etc/aziot/edged/config.d/00-super.toml";
+
+const DEVICE_ID_PK_PATH: &str = "/var/secrets/aziot/keyd/device-id";
+const MASTER_ENCRYPTION_KEY_PATH: &str = "/var/secrets/aziot/keyd/imported-master-encryption-key";
 
 const TRUST_BUNDLE_USER_ALIAS: &str = "trust-bundle-user";
 
 // TODO: Dedupe this with edgelet-http-workload
 const IOTEDGED_COMMONNAME_PREFIX: &str = "iotedged workload ca";
 
-pub fn execute(config: &Path) -> Result<(), std::borrow::Cow<'static, str>> {
+pub fn execute(config: &Path, dry_run: bool) -> Result<(), std::borrow::Cow<'static, str>> {
     // In production, running as root is the easiest way to guarantee the tool has write access to every service's config file.
     // But it's convenient to not do this for the sake of development because the the development machine doesn't necessarily
     // have the package installed and the users created, and it's easier to have the config files owned by the current user anyway.
     //
     // So when running as root, get the four users appropriately.
     // Otherwise, if this is a debug build, fall back to using the current user.
+    // Otherwise, if this is a dry run, get the four users without needing root, since nothing will be written.
     // Otherwise, tell the user to re-run as root.
     let (aziotks_user, aziotcs_user, aziotid_user, aziottpm_user, iotedge_user) =
-        if nix::unistd::Uid::current().is_root() {
+        if nix::unistd::Uid::current().is_root() || (dry_run && !cfg!(debug_assertions)) {
             let aziotks_user = nix::unistd::User::from_name("aziotks")
                 .map_err(|err| format!("could not query aziotks user information: {}", err))?
                 .ok_or_else(|| "could not query aziotks user information")?;
@@ -68,6 +80,12 @@ pub fn execute(config: &Path) -> Result<(), std::borrow::Cow<'static, str>> {
             return Err("this command must be run as root".into());
         };
 
+    let run_output = execute_inner(config, aziotcs_user.uid, aziotid_user.uid, iotedge_user.uid)?;
+
+    if dry_run {
+        return print_dry_run(&run_output);
+    }
+
     let RunOutput {
         keyd_config,
         certd_config,
@@ -76,15 +94,18 @@ pub fn execute(config: &Path) -> Result<(), std::borrow::Cow<'static, str>> {
         edged_config,
         preloaded_device_id_pk_bytes,
         preloaded_master_encryption_key_bytes,
-    } = execute_inner(config, aziotcs_user.uid, aziotid_user.uid, iotedge_user.uid)?;
+    } = run_output;
 
     if let Some(preloaded_device_id_pk_bytes) = preloaded_device_id_pk_bytes {
-        println!("Note: Symmetric key will be written to /var/secrets/aziot/keyd/device-id");
+        println!(
+            "Note: Symmetric key will be written to {}",
+            DEVICE_ID_PK_PATH
+        );
 
         common_config::create_dir_all("/var/secrets/aziot/keyd", &aziotks_user, 0o0700)
             .map_err(|err| format!("{:?}", err))?;
         common_config::write_file(
-            "/var/secrets/aziot/keyd/device-id",
+            DEVICE_ID_PK_PATH,
             &preloaded_device_id_pk_bytes,
             &aziotks_user,
             0o0600,
@@ -93,12 +114,15 @@ pub fn execute(config: &Path) -> Result<(), std::borrow::Cow<'static, str>> {
     }
 
     if let Some(preloaded_master_encryption_key_bytes) = preloaded_master_encryption_key_bytes {
-        println!("Note: Imported master encryption key will be written to /var/secrets/aziot/keyd/imported-master-encryption-key");
+        println!(
+            "Note: Imported master encryption key will be written to {}",
+            MASTER_ENCRYPTION_KEY_PATH
+        );
 
         common_config::create_dir_all("/var/secrets/aziot/keyd", &aziotks_user, 0o0700)
             .map_err(|err| format!("{:?}", err))?;
         common_config::write_file(
-            "/var/secrets/aziot/keyd/imported-master-encryption-key",
+            MASTER_ENCRYPTION_KEY_PATH,
             &preloaded_master_encryption_key_bytes,
             &aziotks_user,
             0o0600,
@@ -106,45 +130,25 @@ pub fn execute(config: &Path) -> Result<(), std::borrow::Cow<'static, str>> {
         .map_err(|err| format!("{:?}", err))?;
     }
 
-    common_config::write_file(
-        "/etc/aziot/keyd/config.d/00-super.toml",
-        &keyd_config,
-        &aziotks_user,
-        0o0600,
-    )
-    .map_err(|err| format!("{:?}", err))?;
+    common_config::write_file(KEYD_CONFIG_PATH, &keyd_config, &aziotks_user, 0o0600)
+        .map_err(|err| format!("{:?}", err))?;
 
-    common_config::write_file(
-        "/etc/aziot/certd/config.d/00-super.toml",
-        &certd_config,
-        &aziotcs_user,
-        0o0600,
-    )
-    .map_err(|err| format!("{:?}", err))?;
+    common_config::write_file(CERTD_CONFIG_PATH, &certd_config, &aziotcs_user, 0o0600)
+        .map_err(|err| format!("{:?}", err))?;
 
     common_config::write_file(
-        "/etc/aziot/identityd/config.d/00-super.toml",
+        IDENTITYD_CONFIG_PATH,
         &identityd_config,
         &aziotid_user,
         0o0600,
     )
     .map_err(|err| format!("{:?}", err))?;
 
-    common_config::write_file(
-        "/etc/aziot/tpmd/config.d/00-super.toml",
-        &tpmd_config,
-        &aziottpm_user,
-        0o0600,
-    )
-    .map_err(|err| format!("{:?}", err))?;
+    common_config::write_file(TPMD_CONFIG_PATH, &tpmd_config, &aziottpm_user, 0o0600)
+        .map_err(|err| format!("{:?}", err))?;
 
-    common_config::write_file(
-        "/etc/aziot/edged/config.d/00-super.toml",
-        &edged_config,
-        &iotedge_user,
-        0o0600,
-    )
-    .map_err(|err| format!("{:?}", err))?;
+    common_config::write_file(EDGED_CONFIG_PATH, &edged_config, &iotedge_user, 0o0600)
+        .map_err(|err| format!("{:?}", err))?;
 
     println!("Azure IoT Edge has been configured successfully!");
     println!();
@@ -155,6 +159,127 @@ pub fn execute(config: &Path) -> Result<(), std::borrow::Cow<'static, str>> {
     Ok(())
 }
 
+fn print_dry_run(run_output: &RunOutput) -> Result<(), std::borrow::Cow<'static, str>> {
+    let RunOutput {
+        keyd_config,
+        certd_config,
+        identityd_config,
+        tpmd_config,
+        edged_config,
+        preloaded_device_id_pk_bytes,
+        preloaded_master_encryption_key_bytes,
+    } = run_output;
+
+    let mut changed_services = vec![];
+    let mut unknown_services = vec![];
+
+    for (path, config, service) in &[
+        (KEYD_CONFIG_PATH, keyd_config, "aziot-keyd.service"),
+        (CERTD_CONFIG_PATH, certd_config, "aziot-certd.service"),
+        (
+            IDENTITYD_CONFIG_PATH,
+            identityd_config,
+            "aziot-identityd.service",
+        ),
+        (TPMD_CONFIG_PATH, tpmd_config, "aziot-tpmd.service"),
+        (EDGED_CONFIG_PATH, edged_config, "aziot-edged.service"),
+    ] {
+        // The config files are only readable by their services' users, so without root, how
+        // they would change is unknown.
+        let (current_name, current_config) = match std::fs::read(path) {
+            Ok(current_config) => (*path, current_config),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => ("/dev/null", vec![]),
+            Err(err) if err.kind() == std::io::ErrorKind::PermissionDenied => {
+                println!(
+                    "{} can't be read without root, so it may or may not change.",
+                    path
+                );
+                unknown_services.push(*service);
+                continue;
+            }
+            Err(err) => return Err(format!("could not read {}: {}", path, err).into()),
+        };
+
+        let current_config = String::from_utf8_lossy(&current_config);
+        let config = String::from_utf8_lossy(config);
+        match diff::unified(
+            &mask_secrets(&current_config),
+            &mask_secrets(&config),
+            current_name,
+            path,
+        ) {
+            Some(diff) => {
+                print!("{}", diff);
+                changed_services.push(*service);
+            }
+            None if current_config != config => {
+                println!(
+                    "{} would only change in secret values, which aren't shown.",
+                    path
+                );
+                changed_services.push(*service);
+            }
+            None => println!("{} is unchanged.", path),
+        }
+    }
+
+    // The contents of the key files are secret, so they're never shown.
+    if preloaded_device_id_pk_bytes.is_some() {
+        println!(
+            "Note: Symmetric key would be written to {}",
+            DEVICE_ID_PK_PATH
+        );
+    }
+    if preloaded_master_encryption_key_bytes.is_some() {
+        println!(
+            "Note: Imported master encryption key would be written to {}",
+            MASTER_ENCRYPTION_KEY_PATH
+        );
+    }
+
+    println!();
+    println!("These services would be restarted:");
+    for service in crate::System::services() {
+        if changed_services.contains(&service) {
+            println!("    {} (configuration changed)", service);
+        } else if unknown_services.contains(&service) {
+            println!("    {} (configuration may have changed)", service);
+        } else {
+            println!("    {}", service);
+        }
+    }
+    println!();
+    println!("This was a dry run. No files were written and no services were restarted.");
+
+    Ok(())
+}
+
+/// Replaces the values of the keys that hold secrets, the registry passwords of the agent and
+/// of EST and the module secrets, so that they aren't shown in the diffs of a dry run.
+fn mask_secrets(config: &str) -> String {
+    let mut in_module_secrets = false;
+    let mut masked = String::with_capacity(config.len());
+    for line in config.lines() {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with('[') {
+            let table = trimmed.trim_start_matches('[');
+            in_module_secrets =
+                table.starts_with("module_secrets]") || table.starts_with("module_secrets.");
+        } else if let Some(equals) = trimmed.find('=') {
+            let key = trimmed[..equals].trim();
+            if in_module_secrets || key.trim_matches('"') == "password" {
+                masked.push_str(&line[..line.len() - trimmed.len()]);
+                masked.push_str(key);
+                masked.push_str(" = \"<secret>\"\n");
+                continue;
+            }
+        }
+        masked.push_str(line);
+        masked.push('\n');
+    }
+    masked
+}
+
 #[derive(Debug)]
 struct RunOutput {
     certd_config: Vec<u8>,
@@ -185,6 +310,10 @@ fn execute_inner(
         connect,
         listen,
         watchdog,
+        image_garbage_collection,
+        log_level,
+        server_cert_policies,
+        module_secrets,
         edge_ca,
         moby_runtime,
     } = toml::from_slice(&config).map_err(|err| format!("could not parse config file: {}", err))?;
@@ -413,6 +542,14 @@ fn execute_inner(
 
             watchdog,
 
+            image_garbage_collection,
+
+            log_level,
+
+            server_cert_policies,
+
+            module_secrets,
+
             endpoints: Default::default(),
         },
 
@@ -420,12 +557,26 @@ fn execute_inner(
             let super_config::MobyRuntime {
                 uri,
                 network,
+                additional_networks,
+                module_networks,
                 content_trust,
+                resource_limits,
+                registry_mirrors,
+                image_import_dir,
+                max_copy_size,
+                engine,
             } = moby_runtime;
 
             edgelet_docker::MobyRuntime {
                 uri,
                 network,
+                additional_networks,
+                module_networks,
+                resource_limits,
+                registry_mirrors,
+                image_import_dir,
+                max_copy_size,
+                engine,
                 content_trust: content_trust
                     .map(
                         |content_trust| -> Result<_, std::borrow::Cow<'static, str>> {
@@ -539,6 +690,40 @@ fn set_quickstart_ca(
 
 #[cfg(test)]
 mod tests {
+    #[test]
+    fn mask_secrets() {
+        let config = "\
+            hostname = \"my-device\"\n\
+            \n\
+            [agent.config.auth]\n\
+            username = \"AcrUsername\"\n\
+            password = \"QWNyUGFzc3dvcmQ=\"\n\
+            \n\
+            [module_secrets.sensor]\n\
+            api_key = \"0123456789abcdef\"\n\
+            \n\
+            [moby_runtime]\n\
+            uri = \"unix:///var/run/docker.sock\"\n\
+        ";
+
+        assert_eq!(
+            "\
+            hostname = \"my-device\"\n\
+            \n\
+            [agent.config.auth]\n\
+            username = \"AcrUsername\"\n\
+            password = \"<secret>\"\n\
+            \n\
+            [module_secrets.sensor]\n\
+            api_key = \"<secret>\"\n\
+            \n\
+            [moby_runtime]\n\
+            uri = \"unix:///var/run/docker.sock\"\n\
+            ",
+            super::mask_secrets(config)
+        );
+    }
+
     #[test]
     fn test() {
         let files_directory =
@@ -0,0 +1,153 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+//! Line-based unified diffs, used to preview the config files that `iotedge config apply` would write.
+
+/// Number of unchanged lines shown around each change.
+const CONTEXT: usize = 3;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Line<'a> {
+    Unchanged(&'a str),
+    Removed(&'a str),
+    Added(&'a str),
+}
+
+/// Returns the unified diff from `old` to `new`, or `None` if they have the same lines.
+pub fn unified(old: &str, new: &str, old_name: &str, new_name: &str) -> Option<String> {
+    let old: Vec<_> = old.lines().collect();
+    let new: Vec<_> = new.lines().collect();
+    let lines = diff_lines(&old, &new);
+
+    let changed: Vec<usize> = lines
+        .iter()
+        .enumerate()
+        .filter(|(_, line)| !matches!(line, Line::Unchanged(_)))
+        .map(|(i, _)| i)
+        .collect();
+    if changed.is_empty() {
+        return None;
+    }
+
+    let mut diff = format!("--- {}\n+++ {}\n", old_name, new_name);
+
+    let mut next = 0;
+    while next < changed.len() {
+        // A hunk continues for as long as the next change is close enough for their contexts to overlap.
+        let start = changed[next].saturating_sub(CONTEXT);
+        let mut last = changed[next];
+        while next < changed.len() && changed[next] <= last + 2 * CONTEXT {
+            last = changed[next];
+            next += 1;
+        }
+        let end = std::cmp::min(last + CONTEXT + 1, lines.len());
+
+        let old_lines = |lines: &[Line<'_>]| {
+            lines
+                .iter()
+                .filter(|line| !matches!(line, Line::Added(_)))
+                .count()
+        };
+        let new_lines = |lines: &[Line<'_>]| {
+            lines
+                .iter()
+                .filter(|line| !matches!(line, Line::Removed(_)))
+                .count()
+        };
+        diff.push_str(&format!(
+            "@@ -{} +{} @@\n",
+            hunk_range(old_lines(&lines[..start]), old_lines(&lines[start..end])),
+            hunk_range(new_lines(&lines[..start]), new_lines(&lines[start..end])),
+        ));
+
+        for line in &lines[start..end] {
+            let (prefix, line) = match line {
+                Line::Unchanged(line) => (' ', line),
+                Line::Removed(line) => ('-', line),
+                Line::Added(line) => ('+', line),
+            };
+            diff.push(prefix);
+            diff.push_str(line);
+            diff.push('\n');
+        }
+    }
+
+    Some(diff)
+}
+
+/// The range of a hunk, given the number of lines before it and in it.
+fn hunk_range(skipped: usize, len: usize) -> String {
+    match len {
+        // An empty range is identified by the line before it.
+        0 => format!("{},0", skipped),
+        1 => format!("{}", skipped + 1),
+        len => format!("{},{}", skipped + 1, len),
+    }
+}
+
+/// Diffs two sequences of lines using their longest common subsequence.
+fn diff_lines<'a>(old: &[&'a str], new: &[&'a str]) -> Vec<Line<'a>> {
+    // common[i][j] is the length of the longest common subsequence of old[i..] and new[j..]
+    let mut common = vec![vec![0_usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            common[i][j] = if old[i] == new[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                std::cmp::max(common[i + 1][j], common[i][j + 1])
+            };
+        }
+    }
+
+    let mut lines = Vec::with_capacity(old.len() + new.len());
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() && j < new.len() {
+        if old[i] == new[j] {
+            lines.push(Line::Unchanged(old[i]));
+            i += 1;
+            j += 1;
+        } else if common[i + 1][j] >= common[i][j + 1] {
+            lines.push(Line::Removed(old[i]));
+            i += 1;
+        } else {
+            lines.push(Line::Added(new[j]));
+            j += 1;
+        }
+    }
+    lines.extend(old[i..].iter().map(|line| Line::Removed(line)));
+    lines.extend(new[j..].iter().map(|line| Line::Added(line)));
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::unified;
+
+    #[test]
+    fn same_lines_have_no_diff() {
+        assert_eq!(None, unified("a\nb\n", "a\nb\n", "old", "new"));
+    }
+
+    #[test]
+    fn new_file_is_all_additions() {
+        assert_eq!(
+            Some("--- /dev/null\n+++ new\n@@ -0,0 +1,2 @@\n+a\n+b\n".to_owned()),
+            unified("", "a\nb\n", "/dev/null", "new")
+        );
+    }
+
+    #[test]
+    fn changes_are_grouped_into_hunks_with_context() {
+        let old = "1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n11\n12\n13\n14\n15\n16\n";
+        let new = "1\ntwo\n3\n4\n5\n6\n7\n8\n9\n10\n11\n12\n13\n14\n15\nsixteen\n17\n";
+
+        assert_eq!(
+            Some(
+                "--- old\n+++ new\n\
+                 @@ -1,5 +1,5 @@\n 1\n-2\n+two\n 3\n 4\n 5\n\
+                 @@ -13,4 +13,5 @@\n 13\n 14\n 15\n-16\n+sixteen\n+17\n"
+                    .to_owned()
+            ),
+            unified(old, new, "old", "new")
+        );
+    }
+}
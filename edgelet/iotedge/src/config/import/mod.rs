@@ -425,6 +425,8 @@ fn execute_inner(
                     }
                     old_config::ImagePullPolicy::Never => edgelet_core::ImagePullPolicy::Never,
                 },
+                startup_order: None,
+                depends_on: Vec::new(),
             }
         },
 
@@ -483,6 +485,7 @@ fn execute_inner(
                     old_config::Protocol::Tls11 => edgelet_core::Protocol::Tls11,
                     old_config::Protocol::Tls12 => edgelet_core::Protocol::Tls12,
                 },
+                management_tls: None,
             }
         },
 
@@ -496,6 +499,14 @@ fn execute_inner(
             }
         },
 
+        image_garbage_collection: Default::default(),
+
+        log_level: None,
+
+        server_cert_policies: Default::default(),
+
+        module_secrets: Default::default(),
+
         edge_ca,
 
         moby_runtime: {
@@ -572,6 +583,14 @@ fn execute_inner(
                         },
                     )
                     .transpose()?,
+
+                additional_networks: Vec::new(),
+                module_networks: Default::default(),
+                resource_limits: None,
+                registry_mirrors: Vec::new(),
+                image_import_dir: None,
+                max_copy_size: None,
+                engine: None,
             }
         },
     };
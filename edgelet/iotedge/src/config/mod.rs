@@ -1,6 +1,7 @@
 // Copyright (c) Microsoft. All rights reserved.
 
 pub mod apply;
+mod diff;
 pub mod import;
 pub mod mp;
 pub mod super_config;
@@ -80,6 +80,14 @@ To reconfigure IoT Edge, run:
 
         watchdog: Default::default(),
 
+        image_garbage_collection: Default::default(),
+
+        log_level: None,
+
+        server_cert_policies: Default::default(),
+
+        module_secrets: Default::default(),
+
         edge_ca: None,
 
         moby_runtime: Default::default(),
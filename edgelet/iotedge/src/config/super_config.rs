@@ -43,6 +43,18 @@ pub struct Config {
     #[serde(default)]
     pub watchdog: edgelet_core::WatchdogSettings,
 
+    #[serde(default)]
+    pub image_garbage_collection: edgelet_core::ImageGarbageCollection,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub log_level: Option<String>,
+
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub server_cert_policies: BTreeMap<String, edgelet_core::ServerCertPolicy>,
+
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub module_secrets: BTreeMap<String, BTreeMap<String, String>>,
+
     #[serde(skip_serializing_if = "Option::is_none")]
     pub edge_ca: Option<EdgeCa>,
 
@@ -63,6 +75,8 @@ pub fn default_agent() -> edgelet_core::ModuleSpec<edgelet_docker::DockerConfig>
             auth: None,
         },
         env: Default::default(),
+        startup_order: None,
+        depends_on: Vec::new(),
     }
 }
 
@@ -85,9 +99,23 @@ pub enum EdgeCa {
 #[derive(Debug, serde_derive::Deserialize, serde_derive::Serialize)]
 pub struct MobyRuntime {
     pub uri: Url,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub registry_mirrors: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub image_import_dir: Option<std::path::PathBuf>,
     pub network: edgelet_core::MobyNetwork,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub additional_networks: Vec<edgelet_core::Network>,
+    #[serde(default, skip_serializing_if = "std::collections::BTreeMap::is_empty")]
+    pub module_networks: std::collections::BTreeMap<String, Vec<String>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub content_trust: Option<ContentTrust>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resource_limits: Option<edgelet_docker::ResourceLimits>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_copy_size: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub engine: Option<edgelet_docker::ContainerEngine>,
 }
 
 impl Default for MobyRuntime {
@@ -99,7 +127,14 @@ impl Default for MobyRuntime {
                 .parse()
                 .expect("hard-coded url::Url must parse successfully"),
             network: edgelet_core::MobyNetwork::Name(edgelet_core::DEFAULT_NETWORKID.to_owned()),
+            additional_networks: Vec::new(),
+            module_networks: Default::default(),
             content_trust: None,
+            resource_limits: None,
+            registry_mirrors: Vec::new(),
+            image_import_dir: None,
+            max_copy_size: None,
+            engine: None,
         }
     }
 }
@@ -0,0 +1,319 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use failure::{Fail, ResultExt};
+use futures::future::{self, Either};
+use futures::{Future, Stream};
+use hyper::Body;
+
+use edgelet_http_mgmt::ModuleClient;
+
+use crate::error::{Error, ErrorKind};
+use crate::Command;
+
+/// One side of a copy: a path in a module, a local path, or stdin/stdout as a tar archive.
+#[derive(Clone, Debug, PartialEq)]
+pub enum CopyLocation {
+    Module { id: String, path: String },
+    Local(PathBuf),
+    Stdio,
+}
+
+impl CopyLocation {
+    /// Parses `MODULE:PATH`, `-` or a local path. Like `docker cp`, paths that start with
+    /// `/` or `.` are always local, so that local paths with colons can be given.
+    pub fn parse(location: &str) -> Self {
+        if location == "-" {
+            return CopyLocation::Stdio;
+        }
+        if !location.starts_with('/') && !location.starts_with('.') {
+            if let Some(index) = location.find(':') {
+                let (id, path) = (&location[..index], &location[index + 1..]);
+                if !id.is_empty() {
+                    return CopyLocation::Module {
+                        id: id.to_string(),
+                        path: path.to_string(),
+                    };
+                }
+            }
+        }
+        CopyLocation::Local(PathBuf::from(location))
+    }
+}
+
+pub struct CopyFiles<W> {
+    src: CopyLocation,
+    dest: CopyLocation,
+    client: ModuleClient,
+    output: Arc<Mutex<W>>,
+}
+
+impl<W> CopyFiles<W> {
+    pub fn new(src: CopyLocation, dest: CopyLocation, client: ModuleClient, output: W) -> Self {
+        CopyFiles {
+            src,
+            dest,
+            client,
+            output: Arc::new(Mutex::new(output)),
+        }
+    }
+}
+
+impl<W> Command for CopyFiles<W>
+where
+    W: 'static + Write + Send,
+{
+    type Future = Box<dyn Future<Item = (), Error = Error> + Send>;
+
+    fn execute(self) -> Self::Future {
+        match (self.src, self.dest) {
+            (CopyLocation::Module { .. }, CopyLocation::Module { .. }) => {
+                Box::new(future::err(Error::from(ErrorKind::BadCopyLocations)))
+            }
+            (CopyLocation::Module { id, path }, dest) => Box::new(copy_from_module(
+                &self.client,
+                &id,
+                &path,
+                dest,
+                self.output,
+            )),
+            (src, CopyLocation::Module { id, path }) => {
+                Box::new(copy_to_module(&self.client, &id, &path, &src))
+            }
+            _ => Box::new(future::err(Error::from(ErrorKind::BadCopyLocations))),
+        }
+    }
+}
+
+fn copy_from_module<W>(
+    client: &ModuleClient,
+    id: &str,
+    path: &str,
+    dest: CopyLocation,
+    output: Arc<Mutex<W>>,
+) -> impl Future<Item = (), Error = Error> + Send
+where
+    W: 'static + Write + Send,
+{
+    client
+        .get_module_archive(id, path)
+        .map_err(|err| Error::from(err.context(ErrorKind::ModuleRuntime)))
+        .and_then(|body| {
+            // The archive is limited in size by the runtime, so it's buffered to extract it
+            // without blocking the stream.
+            body.concat2()
+                .map_err(|err| Error::from(err.context(ErrorKind::ModuleRuntime)))
+        })
+        .and_then(move |archive| {
+            if let CopyLocation::Local(dest) = dest {
+                extract(&archive[..], &dest)
+            } else {
+                let mut w = output.lock().unwrap();
+                w.write_all(&archive).context(ErrorKind::WriteToStdout)?;
+                w.flush().context(ErrorKind::WriteToStdout)?;
+                Ok(())
+            }
+        })
+}
+
+fn copy_to_module(
+    client: &ModuleClient,
+    id: &str,
+    path: &str,
+    src: &CopyLocation,
+) -> impl Future<Item = (), Error = Error> + Send {
+    let archive = if let CopyLocation::Local(src) = src {
+        archive_destination(path).and_then(|(dir, name)| Ok((dir, create(src, name.as_deref())?)))
+    } else {
+        // A tar archive from stdin is extracted into the destination directory as is.
+        let mut archive = vec![];
+        io::stdin()
+            .read_to_end(&mut archive)
+            .context(ErrorKind::CreateArchive("stdin".to_string()))
+            .map_err(Error::from)
+            .map(|_| (path.to_string(), archive))
+    };
+
+    match archive {
+        Ok((dir, archive)) => Either::A(
+            client
+                .put_module_archive(id, &dir, Body::from(archive))
+                .map_err(|err| Error::from(err.context(ErrorKind::ModuleRuntime))),
+        ),
+        Err(err) => Either::B(future::err(err)),
+    }
+}
+
+/// Splits a destination in a module into the directory to extract the archive in and the
+/// name of its top-level entry. A destination that ends with `/` is a directory to copy
+/// into, so the entry keeps the name of the source.
+fn archive_destination(path: &str) -> Result<(String, Option<String>), Error> {
+    if path.ends_with('/') {
+        return Ok((path.to_string(), None));
+    }
+
+    let path = Path::new(path);
+    let name = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .ok_or_else(|| ErrorKind::BadCopyPath(path.display().to_string()))?;
+    let dir = match path.parent() {
+        Some(dir) if dir != Path::new("") => dir.to_string_lossy().into_owned(),
+        _ => "/".to_string(),
+    };
+    Ok((dir, Some(name.to_string())))
+}
+
+fn create(src: &Path, name: Option<&str>) -> Result<Vec<u8>, Error> {
+    let name = match name {
+        Some(name) => name.to_string(),
+        None => src
+            .file_name()
+            .and_then(|name| name.to_str())
+            .ok_or_else(|| ErrorKind::BadCopyPath(src.display().to_string()))?
+            .to_string(),
+    };
+
+    let mut builder = tar::Builder::new(vec![]);
+    builder.follow_symlinks(false);
+    let result = if src.is_dir() {
+        builder.append_dir_all(&name, src)
+    } else {
+        builder.append_path_with_name(src, &name)
+    };
+    result
+        .and_then(|()| builder.into_inner())
+        .with_context(|_| ErrorKind::CreateArchive(src.display().to_string()))
+        .map_err(Error::from)
+}
+
+/// Extracts an archive from a module. If `dest` is a directory, the archive's top-level
+/// entry is extracted into it, otherwise the entry is extracted as `dest`.
+fn extract<R>(archive: R, dest: &Path) -> Result<(), Error>
+where
+    R: Read,
+{
+    let context = || ErrorKind::ExtractArchive(dest.display().to_string());
+    let into_dir = dest.is_dir() || dest.to_string_lossy().ends_with('/');
+
+    let mut archive = tar::Archive::new(archive);
+    for entry in archive.entries().with_context(|_| context())? {
+        let mut entry = entry.with_context(|_| context())?;
+        let path = entry.path().with_context(|_| context())?.into_owned();
+
+        // Entries are only ever extracted below the destination.
+        if !path
+            .components()
+            .all(|component| matches!(component, Component::Normal(_) | Component::CurDir))
+        {
+            return Err(Error::from(
+                ErrorKind::BadCopyPath(path.display().to_string()).context(context()),
+            ));
+        }
+
+        let target = if into_dir {
+            dest.join(&path)
+        } else {
+            let mut components = path.components();
+            components.next();
+            match components.as_path() {
+                rest if rest == Path::new("") => dest.to_path_buf(),
+                rest => dest.join(rest),
+            }
+        };
+
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent).with_context(|_| context())?;
+        }
+        entry.unpack(&target).with_context(|_| context())?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::{archive_destination, create, extract, CopyLocation};
+
+    #[test]
+    fn parse_locations() {
+        assert_eq!(
+            CopyLocation::Module {
+                id: "SimulatedSensor".to_string(),
+                path: "/app/config.json".to_string(),
+            },
+            CopyLocation::parse("SimulatedSensor:/app/config.json")
+        );
+        assert_eq!(CopyLocation::Stdio, CopyLocation::parse("-"));
+        assert_eq!(
+            CopyLocation::Local("./a:b".into()),
+            CopyLocation::parse("./a:b")
+        );
+        assert_eq!(
+            CopyLocation::Local("config.json".into()),
+            CopyLocation::parse("config.json")
+        );
+    }
+
+    #[test]
+    fn destinations_in_modules() {
+        assert_eq!(
+            ("/app".to_string(), Some("config.json".to_string())),
+            archive_destination("/app/config.json").unwrap()
+        );
+        assert_eq!(
+            ("/app/".to_string(), None),
+            archive_destination("/app/").unwrap()
+        );
+        assert_eq!(
+            ("/".to_string(), Some("app".to_string())),
+            archive_destination("app").unwrap()
+        );
+    }
+
+    #[test]
+    fn round_trip_directory() {
+        let tmp = tempfile::tempdir().unwrap();
+        let src = tmp.path().join("src");
+        fs::create_dir_all(src.join("sub")).unwrap();
+        fs::write(src.join("sub").join("file.txt"), "hello").unwrap();
+
+        let archive = create(&src, None).unwrap();
+
+        // Into an existing directory, the entry keeps its name.
+        let into = tmp.path().join("into");
+        fs::create_dir(&into).unwrap();
+        extract(&archive[..], &into).unwrap();
+        assert_eq!(
+            "hello",
+            fs::read_to_string(into.join("src").join("sub").join("file.txt")).unwrap()
+        );
+
+        // Otherwise, the entry is renamed to the destination.
+        let renamed = tmp.path().join("renamed");
+        extract(&archive[..], &renamed).unwrap();
+        assert_eq!(
+            "hello",
+            fs::read_to_string(renamed.join("sub").join("file.txt")).unwrap()
+        );
+    }
+
+    #[test]
+    fn round_trip_file() {
+        let tmp = tempfile::tempdir().unwrap();
+        let src = tmp.path().join("file.txt");
+        fs::write(&src, "hello").unwrap();
+
+        let archive = create(&src, Some("other.txt")).unwrap();
+        let dest = tmp.path().join("dest.txt");
+        extract(&archive[..], &dest).unwrap();
+
+        assert_eq!("hello", fs::read_to_string(dest).unwrap());
+    }
+}
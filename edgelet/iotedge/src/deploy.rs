@@ -0,0 +1,95 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use failure::{Fail, ResultExt};
+use futures::future;
+use futures::Future;
+use tabwriter::TabWriter;
+
+use edgelet_http_mgmt::{ErrorKind as MgmtErrorKind, ModuleClient};
+use management::apis::Error as ApiError;
+
+use crate::error::{Error, ErrorKind};
+use crate::Command;
+
+pub struct Deploy<W> {
+    manifest: PathBuf,
+    client: ModuleClient,
+    output: Arc<Mutex<TabWriter<W>>>,
+}
+
+impl<W> Deploy<W>
+where
+    W: Write,
+{
+    pub fn new(manifest: PathBuf, client: ModuleClient, output: W) -> Self {
+        let tab = TabWriter::new(output).minwidth(15);
+        Deploy {
+            manifest,
+            client,
+            output: Arc::new(Mutex::new(tab)),
+        }
+    }
+}
+
+impl<W> Command for Deploy<W>
+where
+    W: 'static + Write + Send,
+{
+    type Future = Box<dyn Future<Item = (), Error = Error> + Send>;
+
+    fn execute(self) -> Self::Future {
+        let manifest = match read_manifest(&self.manifest) {
+            Ok(manifest) => manifest,
+            Err(err) => return Box::new(future::err(err)),
+        };
+
+        let write = self.output.clone();
+        let result = self
+            .client
+            .deploy(manifest)
+            .map_err(deployment_error)
+            .and_then(move |modules| {
+                let mut w = write.lock().unwrap();
+                writeln!(w, "NAME\tACTION").context(ErrorKind::WriteToStdout)?;
+                for module in modules {
+                    writeln!(w, "{}\t{}", module.name(), module.action())
+                        .context(ErrorKind::WriteToStdout)?;
+                }
+                w.flush().context(ErrorKind::WriteToStdout)?;
+                Ok(())
+            });
+        Box::new(result)
+    }
+}
+
+fn read_manifest(path: &Path) -> Result<serde_json::Value, Error> {
+    let manifest = fs::read(path).context(ErrorKind::ReadDeployment)?;
+    let manifest = serde_json::from_slice(&manifest).context(ErrorKind::ReadDeployment)?;
+    Ok(manifest)
+}
+
+/// Surfaces the reason the daemon gave for rejecting the deployment, which is otherwise
+/// hidden behind a generic client error.
+fn deployment_error(err: edgelet_http_mgmt::Error) -> Error {
+    let fail: &dyn Fail = &err;
+    let message = fail
+        .iter_chain()
+        .find_map(|cause| match cause.downcast_ref::<MgmtErrorKind>() {
+            Some(MgmtErrorKind::Client(ApiError::Api(api))) => api
+                .content
+                .as_ref()
+                .and_then(|content| content.get("message"))
+                .and_then(serde_json::Value::as_str),
+            _ => None,
+        });
+
+    match message {
+        Some(message) => Error::from(ErrorKind::DeploymentRejected(message.to_string())),
+        None => Error::from(err.context(ErrorKind::Deployment)),
+    }
+}
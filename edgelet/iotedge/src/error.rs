@@ -12,18 +12,76 @@ pub struct Error {
 
 #[derive(Clone, Debug, Fail)]
 pub enum ErrorKind {
+    #[fail(
+        display = "Exactly one of the source and destination must be in a module, as MODULE:PATH"
+    )]
+    BadCopyLocations,
+
+    #[fail(display = "Invalid path {}", _0)]
+    BadCopyPath(String),
+
+    #[fail(display = "Could not read the file given as the --ca-cert parameter")]
+    BadCaCertParameter,
+
+    #[fail(
+        display = "Could not read the files given as the --client-cert and --client-key parameters"
+    )]
+    BadClientCertParameter,
+
+    #[fail(display = "Backup has an unexpected entry {}", _0)]
+    BadBackupEntry(String),
+
     #[fail(display = "Invalid value for --host parameter")]
     BadHostParameter,
 
+    #[fail(display = "Could not read the file given as the --passphrase-file parameter")]
+    BadPassphraseFileParameter,
+
+    #[fail(display = "Invalid value for --redact-env-key parameter")]
+    BadRedactEnvKeyParameter,
+
+    #[fail(display = "Invalid value for --regex parameter")]
+    BadRegexParameter,
+
+    #[fail(display = "Invalid value for --severity parameter")]
+    BadSeverityParameter,
+
     #[fail(display = "Invalid value for --since parameter")]
     BadSinceParameter,
 
     #[fail(display = "Invalid value for --tail parameter")]
     BadTailParameter,
 
+    #[fail(display = "Could not back up the device to {}", _0)]
+    Backup(String),
+
+    #[fail(display = "Could not archive {}", _0)]
+    CreateArchive(String),
+
+    #[fail(display = "Could not apply deployment")]
+    Deployment,
+
+    #[fail(display = "Deployment was rejected: {}", _0)]
+    DeploymentRejected(String),
+
+    #[fail(
+        display = "{} already exists on this device. Use --force to restore the backup anyway",
+        _0
+    )]
+    DeviceNotFresh(String),
+
     #[fail(display = "")]
     Diagnostics,
 
+    #[fail(display = "Command exited with code {}", _0)]
+    ExecExitCode(i32),
+
+    #[fail(display = "Could not get the exit code of the command")]
+    ExecExitCodeUnknown,
+
+    #[fail(display = "Could not extract files to {}", _0)]
+    ExtractArchive(String),
+
     #[fail(
         display = "Error while fetching latest versions of edge components: {}",
         _0
@@ -33,6 +91,9 @@ pub enum ErrorKind {
     #[fail(display = "Command failed: {}", _0)]
     Config(std::borrow::Cow<'static, str>),
 
+    #[fail(display = "The backup can't be restored on this device: {}", _0)]
+    IncompatibleBackup(String),
+
     #[fail(display = "Could not initialize tokio runtime")]
     InitializeTokio,
 
@@ -42,9 +103,24 @@ pub enum ErrorKind {
     #[fail(display = "A module runtime error occurred")]
     ModuleRuntime,
 
+    #[fail(display = "No modules match {:?}", _0)]
+    NoMatchingModules(Vec<String>),
+
+    #[fail(display = "Could not read deployment manifest")]
+    ReadDeployment,
+
+    #[fail(display = "Could not restore the backup {}", _0)]
+    Restore(String),
+
     #[fail(display = "Could not generate support bundle")]
     SupportBundle,
 
+    #[fail(display = "Could not watch the modules")]
+    WatchModules,
+
+    #[fail(display = "Could not write to stderr")]
+    WriteToStderr,
+
     #[fail(display = "Could not write to stdout")]
     WriteToStdout,
 
@@ -80,6 +156,12 @@ impl Display for Error {
     }
 }
 
+impl Error {
+    pub fn kind(&self) -> &ErrorKind {
+        self.inner.get_context()
+    }
+}
+
 impl From<ErrorKind> for Error {
     fn from(kind: ErrorKind) -> Self {
         Error {
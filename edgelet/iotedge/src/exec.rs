@@ -0,0 +1,101 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+
+use failure::{Fail, ResultExt};
+use futures::future::Either;
+use futures::{Future, Stream};
+use tokio::codec::FramedRead;
+use tokio::io::AsyncRead;
+
+use edgelet_core::ExecOptions;
+use edgelet_http_mgmt::{ExecCodec, ExecFrame, ModuleClient};
+
+use crate::error::{Error, ErrorKind};
+use crate::Command;
+
+pub struct Exec<W, E> {
+    id: String,
+    options: ExecOptions,
+    client: ModuleClient,
+    stdout: Arc<Mutex<W>>,
+    stderr: Arc<Mutex<E>>,
+}
+
+impl<W, E> Exec<W, E> {
+    pub fn new(
+        id: String,
+        options: ExecOptions,
+        client: ModuleClient,
+        stdout: W,
+        stderr: E,
+    ) -> Self {
+        Exec {
+            id,
+            options,
+            client,
+            stdout: Arc::new(Mutex::new(stdout)),
+            stderr: Arc::new(Mutex::new(stderr)),
+        }
+    }
+}
+
+impl<W, E> Command for Exec<W, E>
+where
+    W: 'static + Write + Send,
+    E: 'static + Write + Send,
+{
+    type Future = Box<dyn Future<Item = (), Error = Error> + Send>;
+
+    fn execute(self) -> Self::Future {
+        let stdin = self.options.stdin();
+        let stdout = self.stdout;
+        let stderr = self.stderr;
+
+        let result = self
+            .client
+            .exec_module(&self.id, &self.options)
+            .map_err(|err| Error::from(err.context(ErrorKind::ModuleRuntime)))
+            .and_then(move |connection| {
+                let (read, write) = connection.split();
+
+                // Closing the write half tells the command that its input has ended.
+                let input = if stdin {
+                    Either::A(
+                        tokio::io::copy(tokio::io::stdin(), write)
+                            .and_then(|(_, _, write)| tokio::io::shutdown(write)),
+                    )
+                } else {
+                    Either::B(tokio::io::shutdown(write))
+                };
+                tokio::spawn(input.map(|_| ()).map_err(|_| ()));
+
+                FramedRead::new(read, ExecCodec)
+                    .map_err(|err| Error::from(err.context(ErrorKind::ModuleRuntime)))
+                    .fold(None, move |exit_code, frame| -> Result<_, Error> {
+                        match frame {
+                            ExecFrame::Stdout(bytes) => {
+                                let mut w = stdout.lock().unwrap();
+                                w.write_all(&bytes).context(ErrorKind::WriteToStdout)?;
+                                w.flush().context(ErrorKind::WriteToStdout)?;
+                                Ok(exit_code)
+                            }
+                            ExecFrame::Stderr(bytes) => {
+                                let mut w = stderr.lock().unwrap();
+                                w.write_all(&bytes).context(ErrorKind::WriteToStderr)?;
+                                w.flush().context(ErrorKind::WriteToStderr)?;
+                                Ok(exit_code)
+                            }
+                            ExecFrame::Exit(code) => Ok(Some(code)),
+                        }
+                    })
+            })
+            .and_then(|exit_code| match exit_code {
+                Some(Some(0)) => Ok(()),
+                Some(Some(code)) => Err(Error::from(ErrorKind::ExecExitCode(code))),
+                Some(None) | None => Err(Error::from(ErrorKind::ExecExitCodeUnknown)),
+            });
+        Box::new(result)
+    }
+}
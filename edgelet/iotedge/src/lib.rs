@@ -19,9 +19,13 @@
 use futures::Future;
 use serde_derive::Deserialize;
 
+mod backup;
 mod check;
 pub mod config;
+mod cp;
+mod deploy;
 mod error;
+mod exec;
 mod list;
 mod logs;
 mod restart;
@@ -30,10 +34,14 @@ mod system;
 mod unknown;
 mod version;
 
+pub use crate::backup::{Backup, Restore};
 pub use crate::check::{Check, OutputFormat};
+pub use crate::cp::{CopyFiles, CopyLocation};
+pub use crate::deploy::Deploy;
 pub use crate::error::{Error, ErrorKind, FetchLatestVersionsReason};
-pub use crate::list::List;
-pub use crate::logs::Logs;
+pub use crate::exec::Exec;
+pub use crate::list::{List, ListFormat};
+pub use crate::logs::{AggregatedLogs, Logs, ModuleSelector};
 pub use crate::restart::Restart;
 pub use crate::support_bundle::SupportBundleCommand;
 pub use crate::system::System;
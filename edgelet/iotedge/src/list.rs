@@ -3,20 +3,33 @@
 use std::fmt::Display;
 use std::io::Write;
 use std::sync::{Arc, Mutex};
+use std::time::Instant;
 
 use chrono::{Duration, Utc};
 use chrono_humanize::{Accuracy, HumanTime, Tense};
 use failure::{Fail, ResultExt};
 use futures::{Future, Stream};
 use tabwriter::TabWriter;
+use tokio::timer::Interval;
 
 use edgelet_core::{Module, ModuleRuntime, ModuleRuntimeState, ModuleStatus};
 
 use crate::error::{Error, ErrorKind};
 use crate::Command;
 
+/// How often the modules are listed in watch mode.
+const WATCH_INTERVAL: std::time::Duration = std::time::Duration::from_secs(2);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ListFormat {
+    Text,
+    Json,
+}
+
 pub struct List<M, W> {
     runtime: M,
+    format: ListFormat,
+    watch: bool,
     output: Arc<Mutex<TabWriter<W>>>,
 }
 
@@ -24,10 +37,14 @@ impl<M, W> List<M, W>
 where
     W: Write,
 {
-    pub fn new(runtime: M, output: W) -> Self {
+    /// With `watch`, the modules are listed again every time their state changes, until the
+    /// command is interrupted. Each JSON list is then written on a line of its own.
+    pub fn new(runtime: M, format: ListFormat, watch: bool, output: W) -> Self {
         let tab = TabWriter::new(output).minwidth(15);
         List {
             runtime,
+            format,
+            watch,
             output: Arc::new(Mutex::new(tab)),
         }
     }
@@ -35,7 +52,7 @@ where
 
 impl<M, W> Command for List<M, W>
 where
-    M: 'static + ModuleRuntime + Clone,
+    M: 'static + ModuleRuntime + Clone + Send,
     M::Module: Clone,
     M::Config: Display,
     W: 'static + Write + Send,
@@ -43,36 +60,156 @@ where
     type Future = Box<dyn Future<Item = (), Error = Error> + Send>;
 
     fn execute(self) -> Self::Future {
-        let write = self.output.clone();
-        let result = self
-            .runtime
-            .list_with_details()
-            .map_err(|err| Error::from(err.context(ErrorKind::ModuleRuntime)))
-            .collect()
-            .and_then(move |mut result| {
-                result.sort_by(|(mod1, _), (mod2, _)| mod1.name().cmp(mod2.name()));
-
-                let mut w = write.lock().unwrap();
-                writeln!(w, "NAME\tSTATUS\tDESCRIPTION\tCONFIG")
-                    .context(ErrorKind::WriteToStdout)?;
-                for (module, state) in result {
-                    writeln!(
-                        w,
-                        "{}\t{}\t{}\t{}",
-                        module.name(),
-                        state.status(),
-                        humanize_state(&state),
-                        module.config(),
-                    )
-                    .context(ErrorKind::WriteToStdout)?;
+        let List {
+            runtime,
+            format,
+            watch,
+            output,
+        } = self;
+
+        if !watch {
+            let result = list(&runtime).and_then(move |modules| {
+                let mut w = output.lock().unwrap();
+                match format {
+                    ListFormat::Text => write_text(&mut *w, &modules)?,
+                    ListFormat::Json => {
+                        serde_json::to_writer_pretty(&mut *w, &to_json(&modules))
+                            .context(ErrorKind::WriteToStdout)?;
+                        writeln!(w).context(ErrorKind::WriteToStdout)?;
+                    }
                 }
                 w.flush().context(ErrorKind::WriteToStdout)?;
                 Ok(())
             });
+            return Box::new(result);
+        }
+
+        let clear_screen = format == ListFormat::Text && atty::is(atty::Stream::Stdout);
+        let result = Interval::new(Instant::now(), WATCH_INTERVAL)
+            .map_err(|err| Error::from(err.context(ErrorKind::WatchModules)))
+            .fold(None, move |last, _| {
+                watch_once(&runtime, format, clear_screen, output.clone(), last)
+            })
+            .map(|_| ());
         Box::new(result)
     }
 }
 
+/// Lists the modules again in watch mode, and writes them if they changed since they were last
+/// written as `last`. Returns the JSON of the modules that were written last.
+///
+/// Errors from the runtime don't end watch mode. They're reported, and the modules are listed
+/// again at the next interval.
+fn watch_once<M, W>(
+    runtime: &M,
+    format: ListFormat,
+    clear_screen: bool,
+    output: Arc<Mutex<TabWriter<W>>>,
+    last: Option<serde_json::Value>,
+) -> impl Future<Item = Option<serde_json::Value>, Error = Error> + Send
+where
+    M: 'static + ModuleRuntime,
+    M::Config: Display,
+    W: 'static + Write + Send,
+{
+    list(runtime).then(move |modules| -> Result<_, Error> {
+        let modules = match modules {
+            Ok(modules) => modules,
+            Err(err) => {
+                eprintln!("Could not list modules: {}", err);
+                for cause in Fail::iter_causes(&err) {
+                    eprintln!("\tcaused by: {}", cause);
+                }
+                return Ok(last);
+            }
+        };
+
+        // The humanized times of the text format change constantly, so changes are detected
+        // in the JSON format regardless of the output format.
+        let json = to_json(&modules);
+        if last.as_ref() == Some(&json) {
+            return Ok(last);
+        }
+
+        let mut w = output.lock().unwrap();
+        match format {
+            ListFormat::Text => {
+                if clear_screen {
+                    write!(w, "\x1B[2J\x1B[H").context(ErrorKind::WriteToStdout)?;
+                }
+                write_text(&mut *w, &modules)?;
+            }
+            ListFormat::Json => {
+                serde_json::to_writer(&mut *w, &json).context(ErrorKind::WriteToStdout)?;
+                writeln!(w).context(ErrorKind::WriteToStdout)?;
+            }
+        }
+        w.flush().context(ErrorKind::WriteToStdout)?;
+        Ok(Some(json))
+    })
+}
+
+fn list<M>(
+    runtime: &M,
+) -> impl Future<Item = Vec<(M::Module, ModuleRuntimeState)>, Error = Error> + Send
+where
+    M: 'static + ModuleRuntime,
+{
+    runtime
+        .list_with_details()
+        .map_err(|err| Error::from(err.context(ErrorKind::ModuleRuntime)))
+        .collect()
+        .map(|mut modules| {
+            modules.sort_by(|(mod1, _), (mod2, _)| mod1.name().cmp(mod2.name()));
+            modules
+        })
+}
+
+fn write_text<W, T>(w: &mut W, modules: &[(T, ModuleRuntimeState)]) -> Result<(), Error>
+where
+    W: Write,
+    T: Module,
+    T::Config: Display,
+{
+    writeln!(w, "NAME\tSTATUS\tDESCRIPTION\tCONFIG").context(ErrorKind::WriteToStdout)?;
+    for (module, state) in modules {
+        writeln!(
+            w,
+            "{}\t{}\t{}\t{}",
+            module.name(),
+            state.status(),
+            humanize_state(state),
+            module.config(),
+        )
+        .context(ErrorKind::WriteToStdout)?;
+    }
+    Ok(())
+}
+
+#[derive(serde_derive::Serialize)]
+struct ModuleJson<'a> {
+    name: &'a str,
+    config: String,
+    #[serde(flatten)]
+    state: &'a ModuleRuntimeState,
+}
+
+fn to_json<T>(modules: &[(T, ModuleRuntimeState)]) -> serde_json::Value
+where
+    T: Module,
+    T::Config: Display,
+{
+    let modules: Vec<_> = modules
+        .iter()
+        .map(|(module, state)| ModuleJson {
+            name: module.name(),
+            config: module.config().to_string(),
+            state,
+        })
+        .collect();
+    serde_json::to_value(modules).expect("serializing modules cannot fail")
+}
+
 fn humanize_state(state: &ModuleRuntimeState) -> String {
     match *state.status() {
         ModuleStatus::Unknown => "Unknown".to_string(),
@@ -116,3 +253,132 @@ fn time_string(ht: &HumanTime, tense: Tense) -> String {
         ht.to_text_en(Accuracy::Rough, tense)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use chrono::{TimeZone, Utc};
+    use failure::Fail;
+    use futures::Future;
+    use tabwriter::TabWriter;
+
+    use edgelet_core::{MakeModuleRuntime, ModuleRuntimeState, ModuleStatus};
+    use edgelet_test_utils::module::{TestConfig, TestModule, TestRuntime, TestSettings};
+
+    use super::{to_json, watch_once, ListFormat};
+
+    #[derive(Clone, Copy, Debug, Fail)]
+    #[fail(display = "the runtime is not available")]
+    struct Unavailable;
+
+    type Runtime = TestRuntime<Unavailable, TestSettings>;
+
+    fn running_since(hour: u32) -> ModuleRuntimeState {
+        ModuleRuntimeState::default()
+            .with_status(ModuleStatus::Running)
+            .with_started_at(Some(Utc.ymd(2021, 4, 13).and_hms(hour, 0, 0)))
+            .with_pid(Some(1234))
+    }
+
+    fn runtime(module: Result<ModuleRuntimeState, Unavailable>) -> Runtime {
+        let module = module.map(|state| {
+            TestModule::new(
+                "sensor".to_string(),
+                TestConfig::new("contoso/sensor:1.0".to_string()),
+                Ok(state),
+            )
+        });
+        Runtime::make_runtime(TestSettings::new())
+            .wait()
+            .unwrap()
+            .with_module(module)
+    }
+
+    fn written(output: &Arc<Mutex<TabWriter<Vec<u8>>>>) -> String {
+        let output = output.lock().unwrap();
+        String::from_utf8(output.get_ref().clone()).unwrap()
+    }
+
+    #[test]
+    fn json_has_module_name_config_and_state() {
+        let module: TestModule<Unavailable, _> = TestModule::new_with_config(
+            "sensor".to_string(),
+            "contoso/sensor:1.0".to_string(),
+            Ok(running_since(14)),
+        );
+
+        let json = to_json(&[(module, running_since(14))]);
+        assert_eq!(
+            serde_json::json!([{
+                "name": "sensor",
+                "config": "contoso/sensor:1.0",
+                "status": "running",
+                "exit_code": null,
+                "status_description": null,
+                "started_at": "2021-04-13T14:00:00Z",
+                "finished_at": null,
+                "image_id": null,
+                "pid": 1234,
+            }]),
+            json
+        );
+    }
+
+    #[test]
+    fn watch_writes_modules_only_when_they_change() {
+        let output = Arc::new(Mutex::new(TabWriter::new(vec![])));
+
+        let last = watch_once(
+            &runtime(Ok(running_since(14))),
+            ListFormat::Json,
+            false,
+            output.clone(),
+            None,
+        )
+        .wait()
+        .unwrap();
+        assert!(last.is_some());
+        assert_eq!(1, written(&output).lines().count());
+
+        let last = watch_once(
+            &runtime(Ok(running_since(14))),
+            ListFormat::Json,
+            false,
+            output.clone(),
+            last,
+        )
+        .wait()
+        .unwrap();
+        assert_eq!(1, written(&output).lines().count());
+
+        watch_once(
+            &runtime(Ok(running_since(15))),
+            ListFormat::Json,
+            false,
+            output.clone(),
+            last,
+        )
+        .wait()
+        .unwrap();
+        assert_eq!(2, written(&output).lines().count());
+    }
+
+    #[test]
+    fn watch_keeps_going_after_runtime_errors() {
+        let output = Arc::new(Mutex::new(TabWriter::new(vec![])));
+        let last = Some(serde_json::json!([]));
+
+        let result = watch_once(
+            &runtime(Err(Unavailable)),
+            ListFormat::Json,
+            false,
+            output.clone(),
+            last.clone(),
+        )
+        .wait()
+        .unwrap();
+        assert_eq!(last, result);
+        assert!(written(&output).is_empty());
+    }
+}
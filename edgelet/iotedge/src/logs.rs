@@ -1,10 +1,15 @@
 // Copyright (c) Microsoft. All rights reserved.
 
-use std::io::stdout;
+use std::io::{self, stdout, Write};
 
+use failure::{Fail, ResultExt};
+use futures::future;
 use futures::prelude::*;
+use regex::Regex;
 
-use edgelet_core::{LogOptions, ModuleRuntime};
+use edgelet_core::{
+    decode_log_lines, LogFormat, LogLine, LogOptions, MergedLogs, Module, ModuleRuntime,
+};
 use support_bundle::pull_logs;
 
 use crate::error::{Error, ErrorKind};
@@ -34,9 +39,166 @@ where
 
     fn execute(self) -> Self::Future {
         let id = self.id.clone();
-        let result = pull_logs(&self.runtime, &id, &self.options, stdout())
-            .map_err(|_| Error::from(ErrorKind::ModuleRuntime))
+        match self.options.format() {
+            LogFormat::Raw => {
+                let result = pull_logs(&self.runtime, &id, &self.options, stdout())
+                    .map_err(|_| Error::from(ErrorKind::ModuleRuntime))
+                    .map(drop);
+                Box::new(result)
+            }
+            LogFormat::Ndjson => {
+                // NDJSON lines are produced by the management API and are copied verbatim
+                let result = self
+                    .runtime
+                    .logs(&id, &self.options)
+                    .map_err(|err| Error::from(err.context(ErrorKind::ModuleRuntime)))
+                    .and_then(|logs| {
+                        logs.map_err(|err| Error::from(err.context(ErrorKind::ModuleRuntime)))
+                            .for_each(|chunk| {
+                                let mut stdout = stdout();
+                                stdout
+                                    .write_all(chunk.as_ref())
+                                    .and_then(|()| stdout.flush())
+                                    .context(ErrorKind::WriteToStdout)?;
+                                Ok(())
+                            })
+                    });
+                Box::new(result)
+            }
+        }
+    }
+}
+
+/// Selects the modules whose logs are merged by [`AggregatedLogs`]
+pub enum ModuleSelector {
+    All,
+    /// Module names, which may contain the glob wildcards `*` and `?`
+    Patterns(Vec<String>),
+}
+
+impl ModuleSelector {
+    pub fn is_pattern(name: &str) -> bool {
+        name.contains(|c| c == '*' || c == '?')
+    }
+
+    fn matches(&self, name: &str) -> bool {
+        match self {
+            ModuleSelector::All => true,
+            ModuleSelector::Patterns(patterns) => patterns.iter().any(|pattern| {
+                let pattern = format!(
+                    "^{}$",
+                    regex::escape(pattern)
+                        .replace(r"\*", ".*")
+                        .replace(r"\?", ".")
+                );
+                Regex::new(&pattern).map_or(false, |pattern| pattern.is_match(name))
+            }),
+        }
+    }
+}
+
+/// Merges the logs of several modules into a single stream ordered by the timestamps
+/// reported by the container runtime, prefixing each line with the module name.
+pub struct AggregatedLogs<M, W> {
+    selector: ModuleSelector,
+    options: LogOptions,
+    runtime: M,
+    output: W,
+}
+
+impl<M, W> AggregatedLogs<M, W> {
+    pub fn new(selector: ModuleSelector, options: LogOptions, runtime: M, output: W) -> Self {
+        AggregatedLogs {
+            selector,
+            options,
+            runtime,
+            output,
+        }
+    }
+}
+
+impl<M, W> Command for AggregatedLogs<M, W>
+where
+    M: 'static + ModuleRuntime + Clone + Send,
+    W: 'static + Write + Send,
+{
+    type Future = Box<dyn Future<Item = (), Error = Error> + Send>;
+
+    fn execute(self) -> Self::Future {
+        let AggregatedLogs {
+            selector,
+            options,
+            runtime,
+            output,
+        } = self;
+
+        let result = runtime
+            .list()
+            .map_err(|err| Error::from(err.context(ErrorKind::ModuleRuntime)))
+            .and_then(move |modules| {
+                let mut names: Vec<String> = modules
+                    .iter()
+                    .map(|module| module.name().to_string())
+                    .filter(|name| selector.matches(name))
+                    .collect();
+                names.sort();
+                let width = names.iter().map(String::len).max().unwrap_or_default();
+
+                if let (true, ModuleSelector::Patterns(patterns)) = (names.is_empty(), selector) {
+                    return future::Either::A(future::err(Error::from(
+                        ErrorKind::NoMatchingModules(patterns),
+                    )));
+                }
+
+                // Lines are always requested with timestamps so that they can be ordered.
+                let module_options = options
+                    .clone()
+                    .with_timestamps(true)
+                    .with_format(LogFormat::Raw);
+                let logs = names.into_iter().map(|name| {
+                    runtime
+                        .logs(&name, &module_options)
+                        .map_err(|err| Error::from(err.context(ErrorKind::ModuleRuntime)))
+                        .map(move |logs| {
+                            let logs = logs
+                                .map_err(|err| io::Error::new(io::ErrorKind::Other, err.compat()));
+                            decode_log_lines(name, logs, true)
+                        })
+                });
+                future::Either::B(
+                    future::join_all(logs).map(move |streams| (streams, options, width)),
+                )
+            })
+            .and_then(move |(streams, options, width)| {
+                MergedLogs::new(streams, options.follow())
+                    .map_err(|err| Error::from(err.context(ErrorKind::ModuleRuntime)))
+                    .fold(output, move |mut w, line| -> Result<W, Error> {
+                        write_line(&mut w, &line, &options, width)?;
+                        Ok(w)
+                    })
+            })
             .map(drop);
         Box::new(result)
     }
 }
+
+fn write_line<W>(w: &mut W, line: &LogLine, options: &LogOptions, width: usize) -> Result<(), Error>
+where
+    W: Write,
+{
+    match options.format() {
+        LogFormat::Raw => {
+            write!(w, "{:width$} | ", line.module(), width = width)
+                .context(ErrorKind::WriteToStdout)?;
+            if let (true, Some(timestamp)) = (options.timestamps(), line.timestamp()) {
+                write!(w, "{} ", timestamp.to_rfc3339()).context(ErrorKind::WriteToStdout)?;
+            }
+            writeln!(w, "{}", line.message()).context(ErrorKind::WriteToStdout)?;
+        }
+        LogFormat::Ndjson => w
+            .write_all(&line.to_ndjson())
+            .context(ErrorKind::WriteToStdout)?,
+    }
+    w.flush().context(ErrorKind::WriteToStdout)?;
+    Ok(())
+}
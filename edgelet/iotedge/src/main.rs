@@ -4,7 +4,9 @@
 #![deny(clippy::all, clippy::pedantic)]
 #![allow(clippy::let_unit_value, clippy::similar_names)]
 
+use std::collections::BTreeSet;
 use std::ffi::OsStr;
+use std::fs;
 use std::io;
 use std::process;
 use std::str::FromStr;
@@ -13,17 +15,24 @@ use clap::{crate_description, crate_name, App, AppSettings, Arg, SubCommand};
 use failure::{Fail, ResultExt};
 use url::Url;
 
-use edgelet_core::{parse_since, LogOptions, LogTail};
+use edgelet_core::{parse_since, ExecOptions, LogFilter, LogFormat, LogOptions, LogTail};
+use edgelet_http::PemCertificate;
 use edgelet_http_mgmt::ModuleClient;
-use support_bundle::OutputLocation;
+use support_bundle::{Component, OutputLocation, Redactor};
 
 use iotedge::{
-    Check, Command, Error, ErrorKind, List, Logs, OutputFormat, Restart, SupportBundleCommand,
-    System, Unknown, Version,
+    AggregatedLogs, Backup, Check, Command, CopyFiles, CopyLocation, Deploy, Error, ErrorKind,
+    Exec, List, ListFormat, Logs, ModuleSelector, OutputFormat, Restart, Restore,
+    SupportBundleCommand, System, Unknown, Version,
 };
 
 fn main() {
     if let Err(ref error) = run() {
+        // The exit code of a command run with `iotedge exec` is passed through as is.
+        if let ErrorKind::ExecExitCode(code) = error.kind() {
+            process::exit(*code);
+        }
+
         let fail: &dyn Fail = error;
 
         eprintln!("{}", error.to_string());
@@ -56,7 +65,7 @@ fn run() -> Result<(), Error> {
         .setting(AppSettings::SubcommandRequiredElseHelp)
         .arg(
             Arg::with_name("host")
-                .help("Daemon socket to connect to")
+                .help("Daemon socket, or https URL of a remote management API, to connect to")
                 .short("H")
                 .long("host")
                 .takes_value(true)
@@ -65,6 +74,35 @@ fn run() -> Result<(), Error> {
                 .env("IOTEDGE_HOST")
                 .default_value(default_mgmt_uri),
         )
+        .arg(
+            Arg::with_name("client-cert")
+                .help("Client certificate to authenticate with when the host is an https URL")
+                .long("client-cert")
+                .takes_value(true)
+                .value_name("FILE")
+                .global(true)
+                .env("IOTEDGE_CLIENT_CERT")
+                .requires("client-key"),
+        )
+        .arg(
+            Arg::with_name("client-key")
+                .help("Private key of the client certificate")
+                .long("client-key")
+                .takes_value(true)
+                .value_name("FILE")
+                .global(true)
+                .env("IOTEDGE_CLIENT_KEY")
+                .requires("client-cert"),
+        )
+        .arg(
+            Arg::with_name("ca-cert")
+                .help("CA certificate to trust the host's certificate by, in addition to the system's CA certificates")
+                .long("ca-cert")
+                .takes_value(true)
+                .value_name("FILE")
+                .global(true)
+                .env("IOTEDGE_CA_CERT"),
+        )
         .subcommand(
             SubCommand::with_name("check")
                 .about("Check for common config and deployment issues")
@@ -92,6 +130,24 @@ fn run() -> Result<(), Error> {
                         .multiple(true)
                         .takes_value(true)
                 )
+                .arg(
+                    Arg::with_name("skip")
+                        .long("skip")
+                        .value_name("CHECK_ID")
+                        .help("Comma-separated list of check IDs to skip. Same as --dont-run.")
+                        .multiple(true)
+                        .use_delimiter(true)
+                        .takes_value(true)
+                )
+                .arg(
+                    Arg::with_name("checks")
+                        .long("checks")
+                        .value_name("CHECK_ID")
+                        .help("Comma-separated list of check IDs. Only the checks listed here will be run. See 'iotedge check-list' for details of all checks.")
+                        .multiple(true)
+                        .use_delimiter(true)
+                        .takes_value(true)
+                )
                 .arg(
                     Arg::with_name("expected-aziot-edged-version")
                         .long("expected-aziot-edged-version")
@@ -141,9 +197,9 @@ fn run() -> Result<(), Error> {
                         .long("output")
                         .short("o")
                         .value_name("FORMAT")
-                        .help("Output format. Note that JSON output contains some additional information like OS name, OS version, disk space, etc.")
+                        .help("Output format. Note that JSON output contains some additional information like OS name, OS version, disk space, etc. JUnit XML and SARIF output contain the result of each check.")
                         .takes_value(true)
-                        .possible_values(&["json", "text"])
+                        .possible_values(&["json", "junit", "sarif", "text"])
                         .default_value("text"),
                 )
                 .arg(
@@ -178,6 +234,12 @@ fn run() -> Result<(), Error> {
                             .takes_value(true)
                             .default_value("/etc/aziot/config.toml"),
                     )
+                    .arg(
+                        Arg::with_name("dry-run")
+                            .long("dry-run")
+                            .help("Show how the services' configuration files would change and which services would be restarted, without changing anything. Secrets aren't shown.")
+                            .takes_value(false),
+                    )
                 )
                 .subcommand(
                     SubCommand::with_name("import")
@@ -238,7 +300,83 @@ fn run() -> Result<(), Error> {
                     )
                 )
         )
-        .subcommand(SubCommand::with_name("list").about("List modules"))
+        .subcommand(
+            SubCommand::with_name("list")
+                .about("List modules")
+                .arg(
+                    Arg::with_name("output")
+                        .help("Output format. The JSON format includes the full runtime state of each module.")
+                        .long("output")
+                        .short("o")
+                        .takes_value(true)
+                        .value_name("FORMAT")
+                        .possible_values(&["text", "json"])
+                        .default_value("text"),
+                )
+                .arg(
+                    Arg::with_name("watch")
+                        .help("List the modules again whenever their state changes. With the JSON format, each list is written on a line of its own.")
+                        .long("watch")
+                        .short("w")
+                        .takes_value(false),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("deploy")
+                .about("Converge the modules on the device to a local deployment manifest. Requires a remote management API host and an admin client certificate")
+                .arg(
+                    Arg::with_name("FILE")
+                        .help("Path of the deployment manifest, either a full deployment or the desired properties of edgeAgent")
+                        .required(true)
+                        .index(1),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("exec")
+                .about("Run a command in a module")
+                .setting(AppSettings::TrailingVarArg)
+                .arg(
+                    Arg::with_name("MODULE")
+                        .help("Sets the module to run the command in")
+                        .required(true)
+                        .index(1),
+                )
+                .arg(
+                    Arg::with_name("COMMAND")
+                        .help("The command to run and its arguments")
+                        .required(true)
+                        .multiple(true)
+                        .index(2),
+                )
+                .arg(
+                    Arg::with_name("interactive")
+                        .help("Send stdin to the command")
+                        .short("i")
+                        .long("interactive"),
+                )
+                .arg(
+                    Arg::with_name("tty")
+                        .help("Allocate a pseudo-TTY for the command")
+                        .short("t")
+                        .long("tty"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("cp")
+                .about("Copy files between a module and the local filesystem")
+                .arg(
+                    Arg::with_name("SRC")
+                        .help("The file or directory to copy, as MODULE:PATH or a local path, or - to read a tar archive from stdin")
+                        .required(true)
+                        .index(1),
+                )
+                .arg(
+                    Arg::with_name("DEST")
+                        .help("Where to copy to, as MODULE:PATH or a local path, or - to write a tar archive to stdout")
+                        .required(true)
+                        .index(2),
+                ),
+        )
         .subcommand(
             SubCommand::with_name("restart")
                 .about("Restart a module")
@@ -251,13 +389,20 @@ fn run() -> Result<(), Error> {
         )
         .subcommand(
             SubCommand::with_name("logs")
-                .about("Fetch the logs of a module")
+                .about("Fetch the logs of one or more modules")
                 .arg(
                     Arg::with_name("MODULE")
-                        .help("Sets the module identity to get logs")
-                        .required(true)
+                        .help("Sets the module identity to get logs. Several modules or glob patterns (such as 'edge*') merge the logs of all matching modules, ordered by time and prefixed with the module name")
+                        .required_unless("all")
+                        .multiple(true)
                         .index(1),
                 )
+                .arg(
+                    Arg::with_name("all")
+                        .help("Merge the logs of all modules, ordered by time and prefixed with the module name")
+                        .long("all")
+                        .conflicts_with("MODULE"),
+                )
                 .arg(
                     Arg::with_name("tail")
                         .help("Number of lines to show from the end of the log")
@@ -286,6 +431,51 @@ fn run() -> Result<(), Error> {
                         .help("Follow output log")
                         .short("f")
                         .long("follow"),
+                )
+                .arg(
+                    Arg::with_name("timestamps")
+                        .help("Show the timestamp of each log line")
+                        .short("t")
+                        .long("timestamps"),
+                )
+                .arg(
+                    Arg::with_name("stream")
+                        .help("Only return lines written to this stream")
+                        .long("stream")
+                        .takes_value(true)
+                        .value_name("STREAM")
+                        .possible_values(&["stdout", "stderr"]),
+                )
+                .arg(
+                    Arg::with_name("contains")
+                        .help("Only return lines containing this text")
+                        .long("contains")
+                        .takes_value(true)
+                        .value_name("TEXT"),
+                )
+                .arg(
+                    Arg::with_name("regex")
+                        .help("Only return lines matching this regular expression")
+                        .long("regex")
+                        .takes_value(true)
+                        .value_name("REGEX"),
+                )
+                .arg(
+                    Arg::with_name("severity")
+                        .help("Only return lines at least as severe as this syslog severity, as a name (emergency, alert, critical, error, warning, notice, info, debug) or number (0-7). The severity is read from syslog <n> prefixes and JSON log lines")
+                        .long("severity")
+                        .takes_value(true)
+                        .value_name("SEVERITY"),
+                )
+                .arg(
+                    Arg::with_name("output")
+                        .help("Output format. ndjson prints one JSON object per line with the timestamp, module, stream, severity and message of each log line")
+                        .long("output")
+                        .short("o")
+                        .takes_value(true)
+                        .value_name("FORMAT")
+                        .possible_values(&["text", "ndjson"])
+                        .default_value("text"),
                 ),
         )
         .subcommand(
@@ -328,6 +518,71 @@ fn run() -> Result<(), Error> {
                     SubCommand::with_name("reprovision")
                     .about("Reprovision device with IoT Hub.")
                 )
+                .subcommand(
+                    SubCommand::with_name("backup")
+                    .about("Back up the configuration, provisioning state, module specs and module volumes of the device into an encrypted file.")
+                    .arg(
+                        Arg::with_name("output")
+                            .help("The file to write the backup to")
+                            .long("output")
+                            .short("o")
+                            .takes_value(true)
+                            .value_name("FILE")
+                            .required(true),
+                    )
+                    .arg(
+                        Arg::with_name("passphrase-file")
+                            .help("A file with the passphrase to encrypt the backup with")
+                            .long("passphrase-file")
+                            .takes_value(true)
+                            .value_name("FILE")
+                            .env("IOTEDGE_BACKUP_PASSPHRASE_FILE")
+                            .required(true),
+                    )
+                    .arg(
+                        Arg::with_name("config-file")
+                            .short("c")
+                            .long("config-file")
+                            .value_name("FILE")
+                            .help("The path of the IoT Edge system configuration file")
+                            .takes_value(true)
+                            .default_value("/etc/aziot/config.toml"),
+                    )
+                )
+                .subcommand(
+                    SubCommand::with_name("restore")
+                    .about("Restore a backup made with iotedge system backup onto this device, and apply its configuration.")
+                    .arg(
+                        Arg::with_name("BACKUP")
+                            .help("The backup to restore")
+                            .required(true)
+                            .index(1),
+                    )
+                    .arg(
+                        Arg::with_name("passphrase-file")
+                            .help("A file with the passphrase that the backup was encrypted with")
+                            .long("passphrase-file")
+                            .takes_value(true)
+                            .value_name("FILE")
+                            .env("IOTEDGE_BACKUP_PASSPHRASE_FILE")
+                            .required(true),
+                    )
+                    .arg(
+                        Arg::with_name("config-file")
+                            .short("c")
+                            .long("config-file")
+                            .value_name("FILE")
+                            .help("The path to restore the IoT Edge system configuration file to")
+                            .takes_value(true)
+                            .default_value("/etc/aziot/config.toml"),
+                    )
+                    .arg(
+                        Arg::with_name("force")
+                            .long("force")
+                            .help("Restore the backup even if the device already has a configuration, provisioning state or volumes that it would overwrite")
+                            .takes_value(false),
+                    )
+                )
         )
         .subcommand(
             SubCommand::with_name("support-bundle")
@@ -368,6 +623,32 @@ fn run() -> Result<(), Error> {
                         .value_name("IOTHUB_HOSTNAME")
                         .help("Sets the hostname of the Azure IoT Hub that this device would connect to. If using manual provisioning, this does not need to be specified.")
                         .takes_value(true),
+                ).arg(
+                    Arg::with_name("include")
+                        .help("Comma-separated list of the components to bundle. Defaults to all of them.")
+                        .long("include")
+                        .value_name("COMPONENT")
+                        .possible_values(&["check", "inspect", "logs", "network", "system-logs"])
+                        .multiple(true)
+                        .use_delimiter(true)
+                        .takes_value(true),
+                ).arg(
+                    Arg::with_name("exclude")
+                        .help("Comma-separated list of the components not to bundle.")
+                        .long("exclude")
+                        .value_name("COMPONENT")
+                        .possible_values(&["check", "inspect", "logs", "network", "system-logs"])
+                        .multiple(true)
+                        .use_delimiter(true)
+                        .takes_value(true),
+                ).arg(
+                    Arg::with_name("redact-env-key")
+                        .help("Regular expression for the names of environment variables whose values are redacted from the bundle, in addition to names that contain password, secret, token, key, credential, connection string or SAS. Can be specified multiple times.")
+                        .long("redact-env-key")
+                        .value_name("REGEX")
+                        .multiple(true)
+                        .number_of_values(1)
+                        .takes_value(true),
                 ).arg(
                     Arg::with_name("quiet")
                         .help("Suppress status output")
@@ -388,7 +669,25 @@ fn run() -> Result<(), Error> {
                     .map_err(Error::from)
             },
         )?;
-        let runtime = ModuleClient::new(&url).context(ErrorKind::ModuleRuntime)?;
+        let client_cert = match (
+            matches.value_of_os("client-cert"),
+            matches.value_of_os("client-key"),
+        ) {
+            (Some(cert), Some(key)) => Some(PemCertificate::new(
+                fs::read(cert).context(ErrorKind::BadClientCertParameter)?,
+                Some(fs::read(key).context(ErrorKind::BadClientCertParameter)?),
+                None,
+                None,
+            )),
+            _ => None,
+        };
+        let ca_cert = matches
+            .value_of_os("ca-cert")
+            .map(fs::read)
+            .transpose()
+            .context(ErrorKind::BadCaCertParameter)?;
+        let runtime = ModuleClient::with_tls(&url, client_cert.as_ref(), ca_cert.as_deref())
+            .context(ErrorKind::ModuleRuntime)?;
         Ok(runtime)
     };
 
@@ -405,6 +704,12 @@ fn run() -> Result<(), Error> {
                     .expect("arg has a default value")
                     .to_string(),
                 args.values_of("dont-run")
+                    .into_iter()
+                    .flatten()
+                    .chain(args.values_of("skip").into_iter().flatten())
+                    .map(ToOwned::to_owned)
+                    .collect(),
+                args.values_of("checks")
                     .into_iter()
                     .flatten()
                     .map(ToOwned::to_owned)
@@ -420,6 +725,8 @@ fn run() -> Result<(), Error> {
                 args.value_of("output")
                     .map(|arg| match arg {
                         "json" => OutputFormat::Json,
+                        "junit" => OutputFormat::Junit,
+                        "sarif" => OutputFormat::Sarif,
                         "text" => OutputFormat::Text,
                         _ => unreachable!(),
                     })
@@ -440,7 +747,10 @@ fn run() -> Result<(), Error> {
                     .expect("arg has a default value");
                 let config_file = std::path::Path::new(config_file);
 
-                let () = iotedge::config::apply::execute(config_file).map_err(ErrorKind::Config)?;
+                let dry_run = args.is_present("dry-run");
+
+                let () = iotedge::config::apply::execute(config_file, dry_run)
+                    .map_err(ErrorKind::Config)?;
                 Ok(())
             }
             ("import", Some(args)) => {
@@ -482,7 +792,54 @@ fn run() -> Result<(), Error> {
                 std::process::exit(1);
             }
         },
-        ("list", _) => tokio_runtime.block_on(List::new(runtime()?, io::stdout()).execute()),
+        ("list", Some(args)) => tokio_runtime.block_on(
+            List::new(
+                runtime()?,
+                match args.value_of("output").expect("arg has a default value") {
+                    "json" => ListFormat::Json,
+                    _ => ListFormat::Text,
+                },
+                args.is_present("watch"),
+                io::stdout(),
+            )
+            .execute(),
+        ),
+        ("deploy", Some(args)) => tokio_runtime.block_on(
+            Deploy::new(
+                args.value_of_os("FILE").expect("arg is required").into(),
+                runtime()?,
+                io::stdout(),
+            )
+            .execute(),
+        ),
+        ("exec", Some(args)) => tokio_runtime.block_on(
+            Exec::new(
+                args.value_of("MODULE")
+                    .expect("arg is required")
+                    .to_string(),
+                ExecOptions::new(
+                    args.values_of("COMMAND")
+                        .expect("arg is required")
+                        .map(ToOwned::to_owned)
+                        .collect(),
+                )
+                .with_stdin(args.is_present("interactive"))
+                .with_tty(args.is_present("tty")),
+                runtime()?,
+                io::stdout(),
+                io::stderr(),
+            )
+            .execute(),
+        ),
+        ("cp", Some(args)) => tokio_runtime.block_on(
+            CopyFiles::new(
+                CopyLocation::parse(args.value_of("SRC").unwrap()),
+                CopyLocation::parse(args.value_of("DEST").unwrap()),
+                runtime()?,
+                io::stdout(),
+            )
+            .execute(),
+        ),
         ("restart", Some(args)) => tokio_runtime.block_on(
             Restart::new(
                 args.value_of("MODULE").unwrap().to_string(),
@@ -492,7 +849,12 @@ fn run() -> Result<(), Error> {
             .execute(),
         ),
         ("logs", Some(args)) => {
-            let id = args.value_of("MODULE").unwrap().to_string();
+            let modules: Vec<String> = args
+                .values_of("MODULE")
+                .into_iter()
+                .flatten()
+                .map(ToOwned::to_owned)
+                .collect();
             let follow = args.is_present("follow");
             let tail = args
                 .value_of("tail")
@@ -508,10 +870,37 @@ fn run() -> Result<(), Error> {
                 .transpose()
                 .context(ErrorKind::BadSinceParameter)?
                 .expect("arg has a default value");
+            let format = args
+                .value_of("output")
+                .map(|arg| match arg {
+                    "ndjson" => LogFormat::Ndjson,
+                    "text" => LogFormat::Raw,
+                    _ => unreachable!(),
+                })
+                .expect("arg has a default value");
+            let mut filter = LogFilter::new();
+            if let Some(stream) = args.value_of("stream") {
+                filter = filter.with_stream(stream.parse().expect("arg has possible values"));
+            }
+            if let Some(contains) = args.value_of("contains") {
+                filter = filter.with_contains(contains.to_string());
+            }
+            if let Some(regex) = args.value_of("regex") {
+                filter = filter
+                    .with_regex(regex)
+                    .context(ErrorKind::BadRegexParameter)?;
+            }
+            if let Some(severity) = args.value_of("severity") {
+                filter = filter
+                    .with_severity(severity.parse().context(ErrorKind::BadSeverityParameter)?);
+            }
             let mut options = LogOptions::new()
                 .with_follow(follow)
                 .with_tail(tail)
-                .with_since(since);
+                .with_since(since)
+                .with_timestamps(args.is_present("timestamps"))
+                .with_filter(filter)
+                .with_format(format);
             if let Some(until) = args
                 .value_of("until")
                 .map(|s| parse_since(s))
@@ -520,7 +909,21 @@ fn run() -> Result<(), Error> {
             {
                 options = options.with_until(until);
             }
-            tokio_runtime.block_on(Logs::new(id, options, runtime()?).execute())
+            match modules.as_slice() {
+                [id] if !args.is_present("all") && !ModuleSelector::is_pattern(id) => {
+                    tokio_runtime.block_on(Logs::new(id.clone(), options, runtime()?).execute())
+                }
+                _ => {
+                    let selector = if args.is_present("all") {
+                        ModuleSelector::All
+                    } else {
+                        ModuleSelector::Patterns(modules)
+                    };
+                    tokio_runtime.block_on(
+                        AggregatedLogs::new(selector, options, runtime()?, io::stdout()).execute(),
+                    )
+                }
+            }
         }
         ("system", Some(args)) => match args.subcommand() {
             ("logs", Some(args)) => {
@@ -538,6 +941,28 @@ fn run() -> Result<(), Error> {
                     .expect("Value is restricted to parsable fields"),
             ),
             ("reprovision", Some(_args)) => System::reprovision(&mut tokio_runtime),
+            ("backup", Some(args)) => tokio_runtime.block_on(
+                Backup::new(
+                    runtime()?,
+                    args.value_of_os("config-file")
+                        .expect("arg has a default value")
+                        .into(),
+                    args.value_of_os("output").expect("arg is required").into(),
+                    read_passphrase(args.value_of_os("passphrase-file"))?,
+                )
+                .execute(),
+            ),
+            ("restore", Some(args)) => tokio_runtime.block_on(
+                Restore::new(
+                    args.value_of_os("BACKUP").expect("arg is required").into(),
+                    args.value_of_os("config-file")
+                        .expect("arg has a default value")
+                        .into(),
+                    read_passphrase(args.value_of_os("passphrase-file"))?,
+                )
+                .with_force(args.is_present("force"))
+                .execute(),
+            ),
 
             (command, _) => {
                 eprintln!("Unknown system subcommand {:?}", command);
@@ -567,6 +992,21 @@ fn run() -> Result<(), Error> {
             let include_ms_only = args.is_present("include-edge-runtime-only");
             let verbose = !args.is_present("quiet");
             let iothub_hostname = args.value_of("iothub-hostname").map(ToOwned::to_owned);
+            let mut components: BTreeSet<Component> = match args.values_of("include") {
+                Some(include) => include
+                    .map(|component| component.parse().expect("arg has possible values"))
+                    .collect(),
+                None => Component::ALL.iter().copied().collect(),
+            };
+            for component in args.values_of("exclude").into_iter().flatten() {
+                components.remove(
+                    &component
+                        .parse::<Component>()
+                        .expect("arg has possible values"),
+                );
+            }
+            let redactor = Redactor::new(args.values_of("redact-env-key").into_iter().flatten())
+                .context(ErrorKind::BadRedactEnvKeyParameter)?;
             let output_location = if location == "-" {
                 OutputLocation::Memory
             } else {
@@ -579,6 +1019,8 @@ fn run() -> Result<(), Error> {
                     include_ms_only,
                     verbose,
                     iothub_hostname,
+                    components,
+                    redactor,
                     output_location,
                     runtime()?,
                 )
@@ -589,3 +1031,19 @@ fn run() -> Result<(), Error> {
         (command, _) => tokio_runtime.block_on(Unknown::new(command.to_string()).execute()),
     }
 }
+
+/// Reads the passphrase of a backup from a file, without its trailing newline.
+fn read_passphrase(path: Option<&OsStr>) -> Result<Vec<u8>, Error> {
+    let mut passphrase =
+        fs::read(path.expect("arg is required")).context(ErrorKind::BadPassphraseFileParameter)?;
+    while passphrase
+        .last()
+        .map_or(false, |&c| c == b'\n' || c == b'\r')
+    {
+        passphrase.pop();
+    }
+    if passphrase.is_empty() {
+        return Err(ErrorKind::BadPassphraseFileParameter.into());
+    }
+    Ok(passphrase)
+}
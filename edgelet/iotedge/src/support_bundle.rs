@@ -1,5 +1,6 @@
 // Copyright (c) Microsoft. All rights reserved.
 
+use std::collections::BTreeSet;
 use std::io::{copy, stdout};
 use std::path::PathBuf;
 
@@ -7,7 +8,7 @@ use failure::Fail;
 use futures::Future;
 
 use edgelet_core::{LogOptions, ModuleRuntime};
-use support_bundle::{make_bundle, OutputLocation};
+use support_bundle::{make_bundle, Component, OutputLocation, Redactor};
 
 use crate::error::{Error, ErrorKind};
 use crate::Command;
@@ -18,6 +19,8 @@ pub struct SupportBundleCommand<M> {
     include_ms_only: bool,
     verbose: bool,
     iothub_hostname: Option<String>,
+    components: BTreeSet<Component>,
+    redactor: Redactor,
     output_location: OutputLocation,
 }
 
@@ -30,6 +33,8 @@ where
         include_ms_only: bool,
         verbose: bool,
         iothub_hostname: Option<String>,
+        components: BTreeSet<Component>,
+        redactor: Redactor,
         output_location: OutputLocation,
         runtime: M,
     ) -> Self {
@@ -39,6 +44,8 @@ where
             include_ms_only,
             verbose,
             iothub_hostname,
+            components,
+            redactor,
             output_location,
         }
     }
@@ -60,6 +67,8 @@ where
             self.include_ms_only,
             self.verbose,
             self.iothub_hostname,
+            self.components,
+            self.redactor,
             self.runtime,
         );
 
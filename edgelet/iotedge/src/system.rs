@@ -54,6 +54,11 @@ impl System {
         })
     }
 
+    /// The services that `system_restart` restarts.
+    pub fn services() -> Vec<&'static str> {
+        SERVICE_DEFINITIONS.iter().map(|s| s.service).collect()
+    }
+
     pub fn system_restart() -> Result<(), Error> {
         restart(&SERVICE_DEFINITIONS).map_err(|err| {
             eprintln!("{:#?}", err);
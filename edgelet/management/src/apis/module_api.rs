@@ -38,15 +38,37 @@ pub trait ModuleApi: Send + Sync {
         api_version: &str,
         name: &str,
     ) -> Box<dyn Future<Item = (), Error = Error<serde_json::Value>>>;
+    fn deploy_modules(
+        &self,
+        api_version: &str,
+        deployment: serde_json::Value,
+    ) -> Box<
+        dyn Future<Item = crate::models::DeploymentResult, Error = Error<serde_json::Value>> + Send,
+    >;
+    fn exec_module(
+        &self,
+        api_version: &str,
+        name: &str,
+        cmd: &[String],
+        tty: bool,
+        stdin: bool,
+    ) -> Box<dyn Future<Item = hyper::upgrade::Upgraded, Error = Error<serde_json::Value>> + Send>;
     fn get_module(
         &self,
         api_version: &str,
         name: &str,
     ) -> Box<dyn Future<Item = crate::models::ModuleDetails, Error = Error<serde_json::Value>>>;
+    fn get_module_archive(
+        &self,
+        api_version: &str,
+        name: &str,
+        path: &str,
+    ) -> Box<dyn Future<Item = hyper::Body, Error = Error<serde_json::Value>> + Send>;
     fn list_modules(
         &self,
         api_version: &str,
     ) -> Box<dyn Future<Item = crate::models::ModuleList, Error = Error<serde_json::Value>> + Send>;
+    #[allow(clippy::too_many_arguments)]
     fn module_logs(
         &self,
         api_version: &str,
@@ -54,7 +76,20 @@ pub trait ModuleApi: Send + Sync {
         follow: bool,
         tail: &str,
         since: i32,
+        timestamps: bool,
+        format: &str,
+        stream: Option<&str>,
+        contains: Option<&str>,
+        regex: Option<&str>,
+        severity: Option<&str>,
     ) -> Box<dyn Future<Item = hyper::Body, Error = Error<serde_json::Value>> + Send>;
+    fn put_module_archive(
+        &self,
+        api_version: &str,
+        name: &str,
+        path: &str,
+        archive: hyper::Body,
+    ) -> Box<dyn Future<Item = (), Error = Error<serde_json::Value>> + Send>;
     fn restart_module(
         &self,
         api_version: &str,
@@ -202,6 +237,135 @@ where
         )
     }
 
+    fn deploy_modules(
+        &self,
+        api_version: &str,
+        deployment: serde_json::Value,
+    ) -> Box<
+        dyn Future<Item = crate::models::DeploymentResult, Error = Error<serde_json::Value>> + Send,
+    > {
+        let configuration: &configuration::Configuration<C> = self.configuration.borrow();
+
+        let method = hyper::Method::POST;
+
+        let query = ::url::form_urlencoded::Serializer::new(String::new())
+            .append_pair("api-version", &api_version.to_string())
+            .finish();
+        let uri_str = format!("/deployment?{}", query);
+
+        let uri = (configuration.uri_composer)(&configuration.base_path, &uri_str);
+        // TODO(farcaller): handle error
+        // if let Err(e) = uri {
+        //     return Box::new(futures::future::err(e));
+        // }
+        let serialized = serde_json::to_string(&deployment).unwrap();
+        let serialized_len = serialized.len();
+
+        let mut req = hyper::Request::builder();
+        req.method(method).uri(uri.unwrap());
+        if let Some(ref user_agent) = configuration.user_agent {
+            req.header(http::header::USER_AGENT, &**user_agent);
+        }
+        let mut req = req
+            .body(hyper::Body::from(serialized))
+            .expect("could not build hyper::Request");
+        req.headers_mut()
+            .typed_insert(&typed_headers::ContentType(mime::APPLICATION_JSON));
+        req.headers_mut()
+            .typed_insert(&typed_headers::ContentLength(serialized_len as u64));
+
+        // send request
+        Box::new(
+            configuration
+                .client
+                .request(req)
+                .map_err(Error::from)
+                .and_then(|resp| {
+                    let (http::response::Parts { status, .. }, body) = resp.into_parts();
+                    body.concat2()
+                        .and_then(move |body| Ok((status, body)))
+                        .map_err(Error::from)
+                })
+                .and_then(|(status, body)| {
+                    if status.is_success() {
+                        Ok(body)
+                    } else {
+                        Err(Error::from((status, &*body)))
+                    }
+                })
+                .and_then(|body| {
+                    let parsed: Result<crate::models::DeploymentResult, _> =
+                        serde_json::from_slice(&body);
+                    parsed.map_err(Error::from)
+                }),
+        )
+    }
+
+    fn exec_module(
+        &self,
+        api_version: &str,
+        name: &str,
+        cmd: &[String],
+        tty: bool,
+        stdin: bool,
+    ) -> Box<dyn Future<Item = hyper::upgrade::Upgraded, Error = Error<serde_json::Value>> + Send>
+    {
+        let configuration: &configuration::Configuration<C> = self.configuration.borrow();
+
+        let method = hyper::Method::POST;
+
+        let mut query = ::url::form_urlencoded::Serializer::new(String::new());
+        query
+            .append_pair("api-version", &api_version.to_string())
+            .append_pair("tty", &tty.to_string())
+            .append_pair("stdin", &stdin.to_string());
+        for arg in cmd {
+            query.append_pair("cmd", arg);
+        }
+        let query = query.finish();
+        let uri_str = format!(
+            "/modules/{name}/exec?{}",
+            query,
+            name = percent_encode(name.as_bytes(), PATH_SEGMENT_ENCODE_SET)
+        );
+
+        let uri = (configuration.uri_composer)(&configuration.base_path, &uri_str);
+        // TODO(farcaller): handle error
+        // if let Err(e) = uri {
+        //     return Box::new(futures::future::err(e));
+        // }
+        let mut req = hyper::Request::builder();
+        req.method(method).uri(uri.unwrap());
+        if let Some(ref user_agent) = configuration.user_agent {
+            req.header(http::header::USER_AGENT, &**user_agent);
+        }
+        req.header(http::header::CONNECTION, "Upgrade");
+        req.header(http::header::UPGRADE, "iotedge-exec");
+        let req = req
+            .body(hyper::Body::empty())
+            .expect("could not build hyper::Request");
+
+        // send request
+        Box::new(
+            configuration
+                .client
+                .request(req)
+                .map_err(Error::from)
+                .and_then(|resp| {
+                    let (http::response::Parts { status, .. }, body) = resp.into_parts();
+                    if status == hyper::StatusCode::SWITCHING_PROTOCOLS {
+                        futures::future::Either::A(body.on_upgrade().map_err(Error::from))
+                    } else {
+                        futures::future::Either::B(
+                            body.concat2()
+                                .map_err(Error::from)
+                                .and_then(move |body| Err(Error::from((status, &*body)))),
+                        )
+                    }
+                }),
+        )
+    }
+
     fn get_module(
         &self,
         api_version: &str,
@@ -262,6 +426,61 @@ where
         )
     }
 
+    fn get_module_archive(
+        &self,
+        api_version: &str,
+        name: &str,
+        path: &str,
+    ) -> Box<dyn Future<Item = hyper::Body, Error = Error<serde_json::Value>> + Send> {
+        let configuration: &configuration::Configuration<C> = self.configuration.borrow();
+
+        let method = hyper::Method::GET;
+
+        let query = ::url::form_urlencoded::Serializer::new(String::new())
+            .append_pair("api-version", &api_version.to_string())
+            .append_pair("path", path)
+            .finish();
+        let uri_str = format!(
+            "/modules/{name}/archive?{}",
+            query,
+            name = percent_encode(name.as_bytes(), PATH_SEGMENT_ENCODE_SET)
+        );
+
+        let uri = (configuration.uri_composer)(&configuration.base_path, &uri_str);
+        // TODO(farcaller): handle error
+        // if let Err(e) = uri {
+        //     return Box::new(futures::future::err(e));
+        // }
+        let mut req = hyper::Request::builder();
+        req.method(method).uri(uri.unwrap());
+        if let Some(ref user_agent) = configuration.user_agent {
+            req.header(http::header::USER_AGENT, &**user_agent);
+        }
+        let req = req
+            .body(hyper::Body::empty())
+            .expect("could not build hyper::Request");
+
+        // send request
+        Box::new(
+            configuration
+                .client
+                .request(req)
+                .map_err(Error::from)
+                .and_then(|resp| {
+                    let (http::response::Parts { status, .. }, body) = resp.into_parts();
+                    if status.is_success() {
+                        futures::future::Either::A(futures::future::ok(body))
+                    } else {
+                        futures::future::Either::B(
+                            body.concat2()
+                                .map_err(Error::from)
+                                .and_then(move |body| Err(Error::from((status, &*body)))),
+                        )
+                    }
+                }),
+        )
+    }
+
     fn list_modules(
         &self,
         api_version: &str,
@@ -324,17 +543,36 @@ where
         follow: bool,
         tail: &str,
         since: i32,
+        timestamps: bool,
+        format: &str,
+        stream: Option<&str>,
+        contains: Option<&str>,
+        regex: Option<&str>,
+        severity: Option<&str>,
     ) -> Box<dyn Future<Item = hyper::Body, Error = Error<serde_json::Value>> + Send> {
         let configuration: &configuration::Configuration<C> = self.configuration.borrow();
 
         let method = hyper::Method::GET;
 
-        let query = ::url::form_urlencoded::Serializer::new(String::new())
+        let mut query = ::url::form_urlencoded::Serializer::new(String::new());
+        query
             .append_pair("api-version", &api_version.to_string())
             .append_pair("follow", &follow.to_string())
             .append_pair("tail", &tail.to_string())
             .append_pair("since", &since.to_string())
-            .finish();
+            .append_pair("timestamps", &timestamps.to_string())
+            .append_pair("format", format);
+        for (key, value) in &[
+            ("stream", stream),
+            ("contains", contains),
+            ("regex", regex),
+            ("severity", severity),
+        ] {
+            if let Some(value) = value {
+                query.append_pair(key, value);
+            }
+        }
+        let query = query.finish();
         let uri_str = format!(
             "/modules/{name}/logs?{}",
             query,
@@ -373,6 +611,64 @@ where
         )
     }
 
+    fn put_module_archive(
+        &self,
+        api_version: &str,
+        name: &str,
+        path: &str,
+        archive: hyper::Body,
+    ) -> Box<dyn Future<Item = (), Error = Error<serde_json::Value>> + Send> {
+        let configuration: &configuration::Configuration<C> = self.configuration.borrow();
+
+        let method = hyper::Method::PUT;
+
+        let query = ::url::form_urlencoded::Serializer::new(String::new())
+            .append_pair("api-version", &api_version.to_string())
+            .append_pair("path", path)
+            .finish();
+        let uri_str = format!(
+            "/modules/{name}/archive?{}",
+            query,
+            name = percent_encode(name.as_bytes(), PATH_SEGMENT_ENCODE_SET)
+        );
+
+        let uri = (configuration.uri_composer)(&configuration.base_path, &uri_str);
+        // TODO(farcaller): handle error
+        // if let Err(e) = uri {
+        //     return Box::new(futures::future::err(e));
+        // }
+        let mut req = hyper::Request::builder();
+        req.method(method).uri(uri.unwrap());
+        if let Some(ref user_agent) = configuration.user_agent {
+            req.header(http::header::USER_AGENT, &**user_agent);
+        }
+        let mut req = req.body(archive).expect("could not build hyper::Request");
+        req.headers_mut().typed_insert(&typed_headers::ContentType(
+            "application/x-tar".parse().unwrap(),
+        ));
+
+        // send request
+        Box::new(
+            configuration
+                .client
+                .request(req)
+                .map_err(Error::from)
+                .and_then(|resp| {
+                    let (http::response::Parts { status, .. }, body) = resp.into_parts();
+                    body.concat2()
+                        .and_then(move |body| Ok((status, body)))
+                        .map_err(Error::from)
+                })
+                .and_then(|(status, body)| {
+                    if status.is_success() {
+                        Ok(())
+                    } else {
+                        Err(Error::from((status, &*body)))
+                    }
+                }),
+        )
+    }
+
     fn restart_module(
         &self,
         api_version: &str,
@@ -0,0 +1,53 @@
+/*
+ * IoT Edge Management API
+ *
+ * No description provided (generated by Swagger Codegen https://github.com/swagger-api/swagger-codegen)
+ *
+ * OpenAPI spec version: 2020-07-07
+ *
+ * Generated by: https://github.com/swagger-api/swagger-codegen.git
+ */
+
+use serde_derive::{Deserialize, Serialize};
+#[allow(unused_imports)]
+use serde_json::Value;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DeployedModule {
+    #[serde(rename = "name")]
+    name: String,
+    #[serde(rename = "action")]
+    action: String,
+}
+
+impl DeployedModule {
+    pub fn new(name: String, action: String) -> Self {
+        DeployedModule { name, action }
+    }
+
+    pub fn set_name(&mut self, name: String) {
+        self.name = name;
+    }
+
+    pub fn with_name(mut self, name: String) -> Self {
+        self.name = name;
+        self
+    }
+
+    pub fn name(&self) -> &String {
+        &self.name
+    }
+
+    pub fn set_action(&mut self, action: String) {
+        self.action = action;
+    }
+
+    pub fn with_action(mut self, action: String) -> Self {
+        self.action = action;
+        self
+    }
+
+    pub fn action(&self) -> &String {
+        &self.action
+    }
+}
@@ -0,0 +1,38 @@
+/*
+ * IoT Edge Management API
+ *
+ * No description provided (generated by Swagger Codegen https://github.com/swagger-api/swagger-codegen)
+ *
+ * OpenAPI spec version: 2020-07-07
+ *
+ * Generated by: https://github.com/swagger-api/swagger-codegen.git
+ */
+
+use serde_derive::{Deserialize, Serialize};
+#[allow(unused_imports)]
+use serde_json::Value;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DeploymentResult {
+    #[serde(rename = "modules")]
+    modules: Vec<crate::models::DeployedModule>,
+}
+
+impl DeploymentResult {
+    pub fn new(modules: Vec<crate::models::DeployedModule>) -> Self {
+        DeploymentResult { modules }
+    }
+
+    pub fn set_modules(&mut self, modules: Vec<crate::models::DeployedModule>) {
+        self.modules = modules;
+    }
+
+    pub fn with_modules(mut self, modules: Vec<crate::models::DeployedModule>) -> Self {
+        self.modules = modules;
+        self
+    }
+
+    pub fn modules(&self) -> &[crate::models::DeployedModule] {
+        &self.modules
+    }
+}
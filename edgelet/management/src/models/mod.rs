@@ -1,5 +1,9 @@
 mod config;
 pub use self::config::Config;
+mod deployed_module;
+pub use self::deployed_module::DeployedModule;
+mod deployment_result;
+pub use self::deployment_result::DeploymentResult;
 mod env_var;
 pub use self::env_var::EnvVar;
 mod error_response;
@@ -14,6 +18,8 @@ mod identity_spec;
 pub use self::identity_spec::IdentitySpec;
 mod update_identity;
 pub use self::update_identity::UpdateIdentity;
+mod module_dependency;
+pub use self::module_dependency::ModuleDependency;
 mod module_details;
 pub use self::module_details::ModuleDetails;
 mod module_list;
@@ -0,0 +1,79 @@
+/*
+ * IoT Edge Management API
+ *
+ * No description provided (generated by Swagger Codegen https://github.com/swagger-api/swagger-codegen)
+ *
+ * OpenAPI spec version: 2020-07-07
+ *
+ * Generated by: https://github.com/swagger-api/swagger-codegen.git
+ */
+
+use serde_derive::{Deserialize, Serialize};
+#[allow(unused_imports)]
+use serde_json::Value;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ModuleDependency {
+    /// The name of the module that has to be ready first.
+    #[serde(rename = "name")]
+    name: String,
+    /// When the module counts as ready: `started`, `healthy` or `tcpPort`.
+    #[serde(rename = "condition")]
+    condition: String,
+    /// The port that has to accept connections, for the `tcpPort` condition.
+    #[serde(rename = "port", skip_serializing_if = "Option::is_none")]
+    port: Option<i32>,
+}
+
+impl ModuleDependency {
+    pub fn new(name: String, condition: String) -> Self {
+        ModuleDependency {
+            name,
+            condition,
+            port: None,
+        }
+    }
+
+    pub fn set_name(&mut self, name: String) {
+        self.name = name;
+    }
+
+    pub fn with_name(mut self, name: String) -> Self {
+        self.name = name;
+        self
+    }
+
+    pub fn name(&self) -> &String {
+        &self.name
+    }
+
+    pub fn set_condition(&mut self, condition: String) {
+        self.condition = condition;
+    }
+
+    pub fn with_condition(mut self, condition: String) -> Self {
+        self.condition = condition;
+        self
+    }
+
+    pub fn condition(&self) -> &String {
+        &self.condition
+    }
+
+    pub fn set_port(&mut self, port: i32) {
+        self.port = Some(port);
+    }
+
+    pub fn with_port(mut self, port: i32) -> Self {
+        self.port = Some(port);
+        self
+    }
+
+    pub fn port(&self) -> Option<i32> {
+        self.port
+    }
+
+    pub fn reset_port(&mut self) {
+        self.port = None;
+    }
+}
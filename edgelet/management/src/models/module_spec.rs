@@ -23,6 +23,10 @@ pub struct ModuleSpec {
     config: crate::models::Config,
     #[serde(rename = "imagePullPolicy", skip_serializing_if = "Option::is_none")]
     image_pull_policy: Option<String>,
+    #[serde(rename = "startupOrder", skip_serializing_if = "Option::is_none")]
+    startup_order: Option<i64>,
+    #[serde(rename = "dependsOn", skip_serializing_if = "Option::is_none")]
+    depends_on: Option<Vec<crate::models::ModuleDependency>>,
 }
 
 impl ModuleSpec {
@@ -32,6 +36,8 @@ impl ModuleSpec {
             type_,
             config,
             image_pull_policy: None,
+            startup_order: None,
+            depends_on: None,
         }
     }
 
@@ -90,4 +96,38 @@ impl ModuleSpec {
     pub fn reset_image_pull_policy(&mut self) {
         self.image_pull_policy = None;
     }
+
+    pub fn set_startup_order(&mut self, startup_order: i64) {
+        self.startup_order = Some(startup_order);
+    }
+
+    pub fn with_startup_order(mut self, startup_order: i64) -> Self {
+        self.startup_order = Some(startup_order);
+        self
+    }
+
+    pub fn startup_order(&self) -> Option<i64> {
+        self.startup_order
+    }
+
+    pub fn reset_startup_order(&mut self) {
+        self.startup_order = None;
+    }
+
+    pub fn set_depends_on(&mut self, depends_on: Vec<crate::models::ModuleDependency>) {
+        self.depends_on = Some(depends_on);
+    }
+
+    pub fn with_depends_on(mut self, depends_on: Vec<crate::models::ModuleDependency>) -> Self {
+        self.depends_on = Some(depends_on);
+        self
+    }
+
+    pub fn depends_on(&self) -> Option<&[crate::models::ModuleDependency]> {
+        self.depends_on.as_ref().map(AsRef::as_ref)
+    }
+
+    pub fn reset_depends_on(&mut self) {
+        self.depends_on = None;
+    }
 }
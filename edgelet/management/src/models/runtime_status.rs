@@ -18,6 +18,10 @@ pub struct RuntimeStatus {
     status: String,
     #[serde(rename = "description", skip_serializing_if = "Option::is_none")]
     description: Option<String>,
+    #[serde(rename = "imageId", skip_serializing_if = "Option::is_none")]
+    image_id: Option<String>,
+    #[serde(rename = "pid", skip_serializing_if = "Option::is_none")]
+    pid: Option<i32>,
 }
 
 impl RuntimeStatus {
@@ -25,6 +29,8 @@ impl RuntimeStatus {
         RuntimeStatus {
             status,
             description: None,
+            image_id: None,
+            pid: None,
         }
     }
 
@@ -57,4 +63,38 @@ impl RuntimeStatus {
     pub fn reset_description(&mut self) {
         self.description = None;
     }
+
+    pub fn set_image_id(&mut self, image_id: String) {
+        self.image_id = Some(image_id);
+    }
+
+    pub fn with_image_id(mut self, image_id: String) -> Self {
+        self.image_id = Some(image_id);
+        self
+    }
+
+    pub fn image_id(&self) -> Option<&str> {
+        self.image_id.as_ref().map(AsRef::as_ref)
+    }
+
+    pub fn reset_image_id(&mut self) {
+        self.image_id = None;
+    }
+
+    pub fn set_pid(&mut self, pid: i32) {
+        self.pid = Some(pid);
+    }
+
+    pub fn with_pid(mut self, pid: i32) -> Self {
+        self.pid = Some(pid);
+        self
+    }
+
+    pub fn pid(&self) -> Option<i32> {
+        self.pid
+    }
+
+    pub fn reset_pid(&mut self) {
+        self.pid = None;
+    }
 }
@@ -12,12 +12,18 @@ pub struct Error {
 
 #[derive(Clone, Debug, Fail)]
 pub enum ErrorKind {
+    #[fail(display = "Invalid redaction pattern {:?}", _0)]
+    InvalidRedactPattern(String),
+
     #[fail(display = "A module runtime error occurred")]
     ModuleRuntime,
 
     #[fail(display = "Could not generate support bundle")]
     SupportBundle,
 
+    #[fail(display = "Unknown support bundle component {:?}", _0)]
+    UnknownComponent(String),
+
     #[fail(display = "Could not write")]
     Write,
 }
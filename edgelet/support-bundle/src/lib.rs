@@ -2,12 +2,18 @@
 
 #![deny(rust_2018_idioms, warnings)]
 #![deny(clippy::all, clippy::pedantic)]
-#![allow(clippy::module_name_repetitions)]
+#![allow(
+    clippy::missing_errors_doc,
+    clippy::module_name_repetitions,
+    clippy::must_use_candidate
+)]
 
 mod error;
 mod logs;
+mod redact;
 mod support_bundle;
 
 pub use crate::error::{Error, ErrorKind};
 pub use crate::logs::pull_logs;
-pub use crate::support_bundle::{make_bundle, OutputLocation};
+pub use crate::redact::Redactor;
+pub use crate::support_bundle::{make_bundle, Component, OutputLocation};
@@ -1,14 +1,18 @@
 // Copyright (c) Microsoft. All rights reserved.
 
+use std::collections::BTreeSet;
 use std::env;
 use std::ffi::OsString;
+use std::fmt;
 use std::fs::File;
-use std::io::{Cursor, Seek};
+use std::io::{self, Cursor, Seek};
 use std::path::Path;
 use std::process::Command as ShellCommand;
+use std::str::FromStr;
 
 use chrono::{DateTime, Local, NaiveDateTime, Utc};
 use failure::Fail;
+use futures::future::Either;
 use futures::{Future, Stream};
 use tokio::prelude::*;
 use zip::{write::FileOptions, CompressionMethod, ZipWriter};
@@ -17,13 +21,18 @@ use edgelet_core::{LogOptions, LogTail, Module, ModuleRuntime};
 
 use crate::error::{Error, ErrorKind};
 use crate::logs::pull_logs;
+use crate::redact::{RedactStream, Redactor};
 
+/// Bundles the `components` into a zip file, after removing secrets from them with `redactor`.
+#[allow(clippy::too_many_arguments)]
 pub fn make_bundle<M>(
     output_location: OutputLocation,
     log_options: LogOptions,
     include_ms_only: bool,
     verbose: bool,
     iothub_hostname: Option<String>,
+    components: BTreeSet<Component>,
+    redactor: Redactor,
     runtime: M,
 ) -> Box<dyn Future<Item = (Box<dyn Read + Send>, u64), Error = Error> + Send>
 where
@@ -42,6 +51,8 @@ where
                     include_ms_only,
                     verbose,
                     iothub_hostname,
+                    components,
+                    redactor,
                     runtime,
                     writer,
                 )
@@ -71,6 +82,8 @@ where
                     include_ms_only,
                     verbose,
                     iothub_hostname,
+                    components,
+                    redactor,
                     runtime,
                     writer,
                 )
@@ -95,11 +108,14 @@ where
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn make_state<M, W>(
     log_options: LogOptions,
     include_ms_only: bool,
     verbose: bool,
     iothub_hostname: Option<String>,
+    components: BTreeSet<Component>,
+    redactor: Redactor,
     runtime: M,
     writer: W,
 ) -> Result<BundleState<M, W>, Error>
@@ -116,6 +132,8 @@ where
         include_ms_only,
         verbose,
         iothub_hostname,
+        components,
+        redactor,
         file_options,
         zip_writer,
     })
@@ -130,6 +148,8 @@ where
     include_ms_only: bool,
     verbose: bool,
     iothub_hostname: Option<String>,
+    components: BTreeSet<Component>,
+    redactor: Redactor,
     file_options: FileOptions,
     zip_writer: ZipWriter<W>,
 }
@@ -153,6 +173,10 @@ where
     }
 
     fn write_module_logs(self) -> impl Future<Item = Self, Error = Error> {
+        if !self.includes(Component::Logs) {
+            return Either::A(future::ok(self));
+        }
+
         /* Print status */
         if self.verbose {
             let since_time: DateTime<Utc> = DateTime::from_utc(
@@ -182,17 +206,28 @@ where
             );
         }
 
-        self.get_modules()
-            .and_then(|(names, state)| stream::iter_ok(names).fold(state, Self::write_log_to_file))
+        Either::B(
+            self.get_modules().and_then(|(names, state)| {
+                stream::iter_ok(names).fold(state, Self::write_log_to_file)
+            }),
+        )
     }
 
     fn write_all_inspects(self) -> impl Future<Item = Self, Error = Error> {
-        self.get_modules().and_then(|(names, s2)| {
+        if !self.includes(Component::Inspect) {
+            return Either::A(future::ok(self));
+        }
+
+        Either::B(self.get_modules().and_then(|(names, s2)| {
             stream::iter_ok(names).fold(s2, |s3, name| s3.write_inspect_to_file(&name))
-        })
+        }))
     }
 
     fn write_all_network_inspects(self) -> Result<Self, Error> {
+        if !self.includes(Component::Network) {
+            return Ok(self);
+        }
+
         self.get_docker_networks().and_then(|(names, s2)| {
             names.into_iter().fold(Ok(s2), |s3, name| {
                 if let Ok(s3) = s3 {
@@ -230,42 +265,44 @@ where
     }
 
     fn write_log_to_file(
-        state: Self,
+        mut state: Self,
         module_name: String,
     ) -> impl Future<Item = Self, Error = Error> {
         state.print_verbose(&format!("Writing {} logs to file", module_name));
-        let BundleState {
-            runtime,
-            log_options,
-            include_ms_only,
-            verbose,
-            iothub_hostname,
-            file_options,
-            mut zip_writer,
-        } = state;
-
-        zip_writer
-            .start_file(format!("logs/{}_log.txt", module_name), file_options)
-            .into_future()
-            .map_err(|err| Error::from(err.context(ErrorKind::SupportBundle)))
-            .and_then(move |_| {
-                pull_logs(&runtime, &module_name, &log_options, zip_writer).map(move |zw| {
-                    let state = BundleState {
-                        runtime,
-                        log_options,
-                        include_ms_only,
-                        verbose,
-                        iothub_hostname,
-                        file_options,
-                        zip_writer: zw,
-                    };
-                    state.print_verbose(&format!("Wrote {} logs to file", module_name));
-                    state
-                })
-            })
+
+        if let Err(err) = state
+            .zip_writer
+            .start_file(format!("logs/{}_log.txt", module_name), state.file_options)
+        {
+            return Either::A(future::err(Error::from(
+                err.context(ErrorKind::SupportBundle),
+            )));
+        }
+
+        // The logs are redacted while they're pulled, since they can be large.
+        let runtime = state.runtime.clone();
+        let log_options = state.log_options.clone();
+        let log_file = LogFile {
+            state,
+            redact: RedactStream::default(),
+        };
+        Either::B(
+            pull_logs(&runtime, &module_name, &log_options, log_file).and_then(move |log_file| {
+                let LogFile { mut state, redact } = log_file;
+                redact
+                    .finish(&state.redactor, &mut state.zip_writer)
+                    .map_err(|err| Error::from(err.context(ErrorKind::SupportBundle)))?;
+                state.print_verbose(&format!("Wrote {} logs to file", module_name));
+                Ok(state)
+            }),
+        )
     }
 
     fn write_system_log(mut self, name: &str, unit: &str) -> Result<Self, Error> {
+        if !self.includes(Component::SystemLogs) {
+            return Ok(self);
+        }
+
         self.print_verbose(format!("Getting system logs for {}", name).as_str());
         let since_time: DateTime<Utc> = DateTime::from_utc(
             NaiveDateTime::from_timestamp(self.log_options.since().into(), 0),
@@ -309,19 +346,17 @@ where
             )
         };
 
-        self.zip_writer
-            .start_file(file_name, self.file_options)
-            .map_err(|err| Error::from(err.context(ErrorKind::SupportBundle)))?;
-
-        self.zip_writer
-            .write_all(&output)
-            .map_err(|err| Error::from(err.context(ErrorKind::SupportBundle)))?;
+        self.write_file(&file_name, &output)?;
 
         self.print_verbose(format!("Got logs for {}", name).as_str());
         Ok(self)
     }
 
     fn write_check(mut self) -> Result<Self, Error> {
+        if !self.includes(Component::Check) {
+            return Ok(self);
+        }
+
         self.print_verbose("Calling iotedge check");
 
         let mut iotedge = env::args().next().unwrap();
@@ -340,17 +375,7 @@ where
             .output()
             .map_err(|err| Error::from(err.context(ErrorKind::SupportBundle)))?;
 
-        self.zip_writer
-            .start_file("check.json", self.file_options)
-            .map_err(|err| Error::from(err.context(ErrorKind::SupportBundle)))?;
-
-        self.zip_writer
-            .write_all(&check.stdout)
-            .map_err(|err| Error::from(err.context(ErrorKind::SupportBundle)))?;
-
-        self.zip_writer
-            .write_all(&check.stderr)
-            .map_err(|err| Error::from(err.context(ErrorKind::SupportBundle)))?;
+        self.write_file("check.json", &[check.stdout, check.stderr].concat())?;
 
         self.print_verbose("Wrote check output to file");
         Ok(self)
@@ -381,13 +406,7 @@ where
             )
         };
 
-        self.zip_writer
-            .start_file(file_name, self.file_options)
-            .map_err(|err| Error::from(err.context(ErrorKind::SupportBundle)))?;
-
-        self.zip_writer
-            .write_all(&output)
-            .map_err(|err| Error::from(err.context(ErrorKind::SupportBundle)))?;
+        self.write_file(&file_name, &output)?;
 
         self.print_verbose(&format!("Got docker inspect for {}", module_name));
         Ok(self)
@@ -458,16 +477,27 @@ where
             )
         };
 
+        self.write_file(&file_name, &output)?;
+
+        self.print_verbose(&format!("Got docker network inspect for {}", network_name));
+        Ok(self)
+    }
+
+    fn includes(&self, component: Component) -> bool {
+        self.components.contains(&component)
+    }
+
+    /// Adds a file to the bundle, without any of the secrets in `contents`.
+    fn write_file(&mut self, file_name: &str, contents: &[u8]) -> Result<(), Error> {
         self.zip_writer
             .start_file(file_name, self.file_options)
             .map_err(|err| Error::from(err.context(ErrorKind::SupportBundle)))?;
 
         self.zip_writer
-            .write_all(&output)
+            .write_all(&self.redactor.redact(contents))
             .map_err(|err| Error::from(err.context(ErrorKind::SupportBundle)))?;
 
-        self.print_verbose(&format!("Got docker network inspect for {}", network_name));
-        Ok(self)
+        Ok(())
     }
 
     fn print_verbose(&self, message: &str) {
@@ -477,12 +507,93 @@ where
     }
 }
 
+/// The file in the bundle that a module's logs are written to as they're pulled.
+struct LogFile<M, W>
+where
+    W: Write + Seek + Send,
+{
+    state: BundleState<M, W>,
+    redact: RedactStream,
+}
+
+impl<M, W> Write for LogFile<M, W>
+where
+    W: Write + Seek + Send,
+{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let BundleState {
+            redactor,
+            zip_writer,
+            ..
+        } = &mut self.state;
+        self.redact.write(redactor, buf, zip_writer)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.state.zip_writer.flush()
+    }
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub enum OutputLocation {
     File(OsString),
     Memory,
 }
 
+/// The kinds of troubleshooting information that a support bundle can contain.
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub enum Component {
+    /// The output of `iotedge check`.
+    Check,
+    /// The logs of the modules.
+    Logs,
+    /// The logs of the system services, such as aziot-edged and docker.
+    SystemLogs,
+    /// `docker inspect` of the modules.
+    Inspect,
+    /// `docker network inspect` of the container networks.
+    Network,
+}
+
+impl Component {
+    pub const ALL: &'static [Component] = &[
+        Component::Check,
+        Component::Logs,
+        Component::SystemLogs,
+        Component::Inspect,
+        Component::Network,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Component::Check => "check",
+            Component::Logs => "logs",
+            Component::SystemLogs => "system-logs",
+            Component::Inspect => "inspect",
+            Component::Network => "network",
+        }
+    }
+}
+
+impl fmt::Display for Component {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Component {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Component::ALL
+            .iter()
+            .copied()
+            .find(|component| component.name() == s)
+            .ok_or_else(|| Error::from(ErrorKind::UnknownComponent(s.to_owned())))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::fs;
@@ -497,7 +608,8 @@ mod tests {
     use edgelet_test_utils::module::{TestConfig, TestModule, TestRuntime, TestSettings};
 
     use super::{
-        make_bundle, pull_logs, Fail, File, Future, LogOptions, LogTail, OsString, OutputLocation,
+        make_bundle, pull_logs, Component, Fail, File, Future, LogOptions, LogTail, OsString,
+        OutputLocation, Redactor,
     };
 
     #[allow(dead_code)]
@@ -525,6 +637,8 @@ mod tests {
             false,
             false,
             None,
+            Component::ALL.iter().copied().collect(),
+            Redactor::default(),
             runtime,
         );
 
@@ -602,6 +716,41 @@ mod tests {
             .any(|f| network_in_inspect.is_match(&f)));
     }
 
+    #[test]
+    fn excluded_components_are_not_bundled() {
+        let module_name = "test-module";
+        let runtime = make_runtime(module_name);
+        let tmp_dir = tempdir().unwrap();
+        let file_path = tmp_dir
+            .path()
+            .join("iotedge_bundle.zip")
+            .to_str()
+            .unwrap()
+            .to_owned();
+
+        let bundle = make_bundle(
+            OutputLocation::File(OsString::from(file_path.to_owned())),
+            LogOptions::default(),
+            false,
+            false,
+            None,
+            vec![Component::Logs].into_iter().collect(),
+            Redactor::default(),
+            runtime,
+        );
+
+        tokio::runtime::current_thread::Runtime::new()
+            .unwrap()
+            .block_on(bundle)
+            .unwrap();
+
+        let mut archive = zip::ZipArchive::new(File::open(&file_path).unwrap()).unwrap();
+        let names: Vec<_> = (0..archive.len())
+            .map(|i| archive.by_index(i).unwrap().name().to_owned())
+            .collect();
+        assert_eq!(vec![format!("logs/{}_log.txt", module_name)], names);
+    }
+
     #[test]
     fn get_logs() {
         let module_name = "test-module";
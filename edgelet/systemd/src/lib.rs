@@ -15,6 +15,8 @@ use std::net::SocketAddr;
 mod error;
 #[cfg(target_os = "linux")]
 mod linux;
+#[cfg(target_os = "linux")]
+mod notify;
 
 pub use self::error::{Error, ErrorKind, SocketLookupType};
 
@@ -29,3 +31,7 @@ pub enum Socket {
 
 #[cfg(target_os = "linux")]
 pub use self::linux::{listener, listener_name, listeners_name, LISTEN_FDS_START};
+#[cfg(target_os = "linux")]
+pub use self::notify::{
+    notify, notify_extend_timeout, notify_ready, notify_stopping, notify_watchdog, watchdog_enabled,
+};
@@ -0,0 +1,217 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+//! Implements the daemon interface for notifying the service manager of the daemon's state.
+//! Based off of [`sd_notify`](https://www.freedesktop.org/software/systemd/man/sd_notify.html)
+//! and [`sd_watchdog_enabled`](https://www.freedesktop.org/software/systemd/man/sd_watchdog_enabled.html)
+
+use std::env;
+use std::ffi::OsStr;
+use std::os::unix::ffi::OsStrExt;
+use std::time::Duration;
+
+use failure::ResultExt;
+use log::debug;
+use nix::sys::socket::{self, AddressFamily, MsgFlags, SockAddr, SockFlag, SockType, UnixAddr};
+use nix::unistd::{self, Pid};
+
+use crate::error::{Error, ErrorKind};
+
+const ENV_NOTIFY_SOCKET: &str = "NOTIFY_SOCKET";
+const ENV_WATCHDOG_PID: &str = "WATCHDOG_PID";
+const ENV_WATCHDOG_USEC: &str = "WATCHDOG_USEC";
+
+/// Tells the service manager that the daemon finished starting up.
+pub fn notify_ready() -> Result<bool, Error> {
+    notify("READY=1")
+}
+
+/// Tells the service manager that the daemon is shutting down.
+pub fn notify_stopping() -> Result<bool, Error> {
+    notify("STOPPING=1")
+}
+
+/// Sends a keep-alive to the service manager's watchdog.
+pub fn notify_watchdog() -> Result<bool, Error> {
+    notify("WATCHDOG=1")
+}
+
+/// Asks the service manager to wait `timeout` longer for the daemon to finish starting up or
+/// shutting down, counting from now.
+pub fn notify_extend_timeout(timeout: Duration) -> Result<bool, Error> {
+    notify(&format!("EXTEND_TIMEOUT_USEC={}", timeout.as_micros()))
+}
+
+/// Sends newline-separated `state` assignments to the service manager.
+///
+/// Returns false when the daemon wasn't started by a service manager that listens for
+/// notifications, in which case nothing is sent.
+pub fn notify(state: &str) -> Result<bool, Error> {
+    let path = match env::var_os(ENV_NOTIFY_SOCKET) {
+        Some(path) => path,
+        None => return Ok(false),
+    };
+    debug!("{} {:?}", ENV_NOTIFY_SOCKET, path);
+
+    let addr = notify_addr(&path)?;
+    let fd = socket::socket(
+        AddressFamily::Unix,
+        SockType::Datagram,
+        SockFlag::SOCK_CLOEXEC,
+        None,
+    )
+    .context(ErrorKind::Syscall("socket"))?;
+    let sent = socket::sendto(fd, state.as_bytes(), &addr, MsgFlags::empty());
+    let _ = unistd::close(fd);
+    sent.context(ErrorKind::Syscall("sendto"))?;
+
+    Ok(true)
+}
+
+/// Returns how often the service manager expects keep-alives, if its watchdog is enabled for
+/// this process. Keep-alives should be sent at about half this interval.
+pub fn watchdog_enabled() -> Result<Option<Duration>, Error> {
+    let usec = match env::var(ENV_WATCHDOG_USEC) {
+        Err(env::VarError::NotPresent) => return Ok(None),
+        usec => usec.with_context(|_| ErrorKind::InvalidVar(ENV_WATCHDOG_USEC.to_string()))?,
+    };
+    debug!("{} {}", ENV_WATCHDOG_USEC, usec);
+    let usec = usec
+        .parse::<u64>()
+        .with_context(|_| ErrorKind::InvalidVar(ENV_WATCHDOG_USEC.to_string()))?;
+    if usec == 0 {
+        return Err(ErrorKind::InvalidVar(ENV_WATCHDOG_USEC.to_string()).into());
+    }
+
+    // The watchdog is meant for a different process, like the parent of a process that
+    // inherited the environment.
+    if let Ok(pid) = env::var(ENV_WATCHDOG_PID) {
+        debug!("{} {}", ENV_WATCHDOG_PID, pid);
+        let pid = Pid::from_raw(
+            pid.parse::<i32>()
+                .context(ErrorKind::ParsePid(ENV_WATCHDOG_PID.to_string()))?,
+        );
+        if pid != Pid::this() {
+            return Ok(None);
+        }
+    }
+
+    Ok(Some(Duration::from_micros(usec)))
+}
+
+/// Socket paths that start with `@` are in the abstract namespace.
+fn notify_addr(path: &OsStr) -> Result<SockAddr, Error> {
+    let addr = match path.as_bytes() {
+        [b'@', name @ ..] => UnixAddr::new_abstract(name),
+        [b'/', ..] => UnixAddr::new(path),
+        _ => return Err(ErrorKind::InvalidVar(ENV_NOTIFY_SOCKET.to_string()).into()),
+    }
+    .with_context(|_| ErrorKind::InvalidVar(ENV_NOTIFY_SOCKET.to_string()))?;
+
+    Ok(SockAddr::Unix(addr))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        env, notify, notify_extend_timeout, notify_ready, watchdog_enabled, ErrorKind, Pid,
+        ENV_NOTIFY_SOCKET, ENV_WATCHDOG_PID, ENV_WATCHDOG_USEC,
+    };
+
+    use std::os::unix::net::UnixDatagram;
+    use std::sync::{Mutex, MutexGuard};
+    use std::time::Duration;
+
+    use lazy_static::lazy_static;
+
+    lazy_static! {
+        static ref LOCK: Mutex<()> = Mutex::new(());
+    }
+
+    fn lock_env<'a>() -> MutexGuard<'a, ()> {
+        LOCK.lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+
+    #[test]
+    fn test_notify() {
+        let _l = lock_env();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notify.sock");
+        let listener = UnixDatagram::bind(&path).unwrap();
+        env::set_var(ENV_NOTIFY_SOCKET, &path);
+
+        assert!(notify_ready().unwrap());
+        assert!(notify("STATUS=Running\nWATCHDOG=1").unwrap());
+        assert!(notify_extend_timeout(Duration::from_secs(90)).unwrap());
+
+        let mut buf = [0; 64];
+        let n = listener.recv(&mut buf).unwrap();
+        assert_eq!(b"READY=1", &buf[..n]);
+        let n = listener.recv(&mut buf).unwrap();
+        assert_eq!(b"STATUS=Running\nWATCHDOG=1", &buf[..n]);
+        let n = listener.recv(&mut buf).unwrap();
+        assert_eq!(b"EXTEND_TIMEOUT_USEC=90000000", &buf[..n]);
+
+        env::remove_var(ENV_NOTIFY_SOCKET);
+    }
+
+    #[test]
+    fn test_notify_without_socket() {
+        let _l = lock_env();
+        env::remove_var(ENV_NOTIFY_SOCKET);
+
+        assert!(!notify_ready().unwrap());
+    }
+
+    #[test]
+    fn test_notify_with_invalid_socket() {
+        let _l = lock_env();
+        env::set_var(ENV_NOTIFY_SOCKET, "relative/notify.sock");
+
+        match notify_ready() {
+            Ok(_) => panic!("expected notify to fail"),
+            Err(err) => match err.kind() {
+                ErrorKind::InvalidVar(s) if s == ENV_NOTIFY_SOCKET => (),
+                _ => panic!(
+                    "expected notify to raise ErrorKind::InvalidVar({}) but it raised {:?}",
+                    ENV_NOTIFY_SOCKET, err
+                ),
+            },
+        }
+
+        env::remove_var(ENV_NOTIFY_SOCKET);
+    }
+
+    #[test]
+    fn test_watchdog_enabled() {
+        let _l = lock_env();
+        env::remove_var(ENV_WATCHDOG_PID);
+        env::remove_var(ENV_WATCHDOG_USEC);
+        assert_eq!(None, watchdog_enabled().unwrap());
+
+        env::set_var(ENV_WATCHDOG_USEC, "30000000");
+        assert_eq!(Some(Duration::from_secs(30)), watchdog_enabled().unwrap());
+
+        env::set_var(ENV_WATCHDOG_PID, format!("{}", Pid::this()));
+        assert_eq!(Some(Duration::from_secs(30)), watchdog_enabled().unwrap());
+
+        // The watchdog is for another process.
+        env::set_var(ENV_WATCHDOG_PID, "1");
+        assert_eq!(None, watchdog_enabled().unwrap());
+
+        env::remove_var(ENV_WATCHDOG_PID);
+        env::set_var(ENV_WATCHDOG_USEC, "soon");
+        match watchdog_enabled() {
+            Ok(_) => panic!("expected watchdog_enabled to fail"),
+            Err(err) => match err.kind() {
+                ErrorKind::InvalidVar(s) if s == ENV_WATCHDOG_USEC => (),
+                _ => panic!(
+                    "expected watchdog_enabled to raise ErrorKind::InvalidVar({}) but it raised {:?}",
+                    ENV_WATCHDOG_USEC, err
+                ),
+            },
+        }
+
+        env::remove_var(ENV_WATCHDOG_USEC);
+    }
+}
@@ -0,0 +1,62 @@
+/*
+ * IoT Edge Module Workload API
+ *
+ * No description provided (generated by Swagger Codegen https://github.com/swagger-api/swagger-codegen)
+ *
+ * OpenAPI spec version: 2018-06-28
+ *
+ * Generated by: https://github.com/swagger-api/swagger-codegen.git
+ */
+
+use serde_derive::{Deserialize, Serialize};
+#[allow(unused_imports)]
+use serde_json::Value;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CertificateEvent {
+    /// What changed: identityCertExpiring, serverCertExpiring, trustBundleChanged or manifestTrustBundleChanged
+    #[serde(rename = "kind")]
+    kind: String,
+    /// Expiration date-time (ISO 8601) of the certificate that is nearing expiry
+    #[serde(rename = "expiration", skip_serializing_if = "Option::is_none")]
+    expiration: Option<String>,
+}
+
+impl CertificateEvent {
+    pub fn new(kind: String) -> Self {
+        CertificateEvent {
+            kind,
+            expiration: None,
+        }
+    }
+
+    pub fn set_kind(&mut self, kind: String) {
+        self.kind = kind;
+    }
+
+    pub fn with_kind(mut self, kind: String) -> Self {
+        self.kind = kind;
+        self
+    }
+
+    pub fn kind(&self) -> &String {
+        &self.kind
+    }
+
+    pub fn set_expiration(&mut self, expiration: String) {
+        self.expiration = Some(expiration);
+    }
+
+    pub fn with_expiration(mut self, expiration: String) -> Self {
+        self.expiration = Some(expiration);
+        self
+    }
+
+    pub fn expiration(&self) -> Option<&str> {
+        self.expiration.as_ref().map(AsRef::as_ref)
+    }
+
+    pub fn reset_expiration(&mut self) {
+        self.expiration = None;
+    }
+}
@@ -0,0 +1,55 @@
+/*
+ * IoT Edge Module Workload API
+ *
+ * No description provided (generated by Swagger Codegen https://github.com/swagger-api/swagger-codegen)
+ *
+ * OpenAPI spec version: 2018-06-28
+ *
+ * Generated by: https://github.com/swagger-api/swagger-codegen.git
+ */
+
+use serde_derive::{Deserialize, Serialize};
+#[allow(unused_imports)]
+use serde_json::Value;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CertificateEventsResponse {
+    /// Opaque token to pass as `since` in the next request
+    #[serde(rename = "token")]
+    token: String,
+    /// Changes since the state described by the `since` token
+    #[serde(rename = "events")]
+    events: Vec<crate::models::CertificateEvent>,
+}
+
+impl CertificateEventsResponse {
+    pub fn new(token: String, events: Vec<crate::models::CertificateEvent>) -> Self {
+        CertificateEventsResponse { token, events }
+    }
+
+    pub fn set_token(&mut self, token: String) {
+        self.token = token;
+    }
+
+    pub fn with_token(mut self, token: String) -> Self {
+        self.token = token;
+        self
+    }
+
+    pub fn token(&self) -> &String {
+        &self.token
+    }
+
+    pub fn set_events(&mut self, events: Vec<crate::models::CertificateEvent>) {
+        self.events = events;
+    }
+
+    pub fn with_events(mut self, events: Vec<crate::models::CertificateEvent>) -> Self {
+        self.events = events;
+        self
+    }
+
+    pub fn events(&self) -> &[crate::models::CertificateEvent] {
+        &self.events
+    }
+}
@@ -1,3 +1,7 @@
+mod certificate_event;
+pub use self::certificate_event::CertificateEvent;
+mod certificate_events_response;
+pub use self::certificate_events_response::CertificateEventsResponse;
 mod certificate_response;
 pub use self::certificate_response::CertificateResponse;
 mod decrypt_request;
@@ -16,6 +20,10 @@ mod manifest_trust_bundle_response;
 pub use self::manifest_trust_bundle_response::ManifestTrustBundleResponse;
 mod private_key;
 pub use self::private_key::PrivateKey;
+mod secret_request;
+pub use self::secret_request::SecretRequest;
+mod secret_response;
+pub use self::secret_response::SecretResponse;
 mod server_certificate_request;
 pub use self::server_certificate_request::ServerCertificateRequest;
 mod sign_request;
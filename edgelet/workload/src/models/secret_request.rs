@@ -0,0 +1,39 @@
+/*
+ * IoT Edge Module Workload API
+ *
+ * No description provided (generated by Swagger Codegen https://github.com/swagger-api/swagger-codegen)
+ *
+ * OpenAPI spec version: 2018-06-28
+ *
+ * Generated by: https://github.com/swagger-api/swagger-codegen.git
+ */
+
+use serde_derive::{Deserialize, Serialize};
+#[allow(unused_imports)]
+use serde_json::Value;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SecretRequest {
+    /// The value of the secret encoded in base 64.
+    #[serde(rename = "value")]
+    value: String,
+}
+
+impl SecretRequest {
+    pub fn new(value: String) -> Self {
+        SecretRequest { value }
+    }
+
+    pub fn set_value(&mut self, value: String) {
+        self.value = value;
+    }
+
+    pub fn with_value(mut self, value: String) -> Self {
+        self.value = value;
+        self
+    }
+
+    pub fn value(&self) -> &String {
+        &self.value
+    }
+}
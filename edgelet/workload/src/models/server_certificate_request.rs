@@ -20,6 +20,12 @@ pub struct ServerCertificateRequest {
     /// Certificate expiration date-time (ISO 8601)
     #[serde(rename = "expiration")]
     expiration: String,
+    /// Additional DNS names for the certificate's subject alternative names
+    #[serde(rename = "dnsNames", skip_serializing_if = "Option::is_none")]
+    dns_names: Option<Vec<String>>,
+    /// Additional IP addresses for the certificate's subject alternative names
+    #[serde(rename = "ipAddresses", skip_serializing_if = "Option::is_none")]
+    ip_addresses: Option<Vec<String>>,
 }
 
 impl ServerCertificateRequest {
@@ -27,6 +33,8 @@ impl ServerCertificateRequest {
         ServerCertificateRequest {
             common_name,
             expiration,
+            dns_names: None,
+            ip_addresses: None,
         }
     }
 
@@ -55,4 +63,38 @@ impl ServerCertificateRequest {
     pub fn expiration(&self) -> &String {
         &self.expiration
     }
+
+    pub fn set_dns_names(&mut self, dns_names: Vec<String>) {
+        self.dns_names = Some(dns_names);
+    }
+
+    pub fn with_dns_names(mut self, dns_names: Vec<String>) -> Self {
+        self.dns_names = Some(dns_names);
+        self
+    }
+
+    pub fn dns_names(&self) -> Option<&[String]> {
+        self.dns_names.as_ref().map(AsRef::as_ref)
+    }
+
+    pub fn reset_dns_names(&mut self) {
+        self.dns_names = None;
+    }
+
+    pub fn set_ip_addresses(&mut self, ip_addresses: Vec<String>) {
+        self.ip_addresses = Some(ip_addresses);
+    }
+
+    pub fn with_ip_addresses(mut self, ip_addresses: Vec<String>) -> Self {
+        self.ip_addresses = Some(ip_addresses);
+        self
+    }
+
+    pub fn ip_addresses(&self) -> Option<&[String]> {
+        self.ip_addresses.as_ref().map(AsRef::as_ref)
+    }
+
+    pub fn reset_ip_addresses(&mut self) {
+        self.ip_addresses = None;
+    }
 }